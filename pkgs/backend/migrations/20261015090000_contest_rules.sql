@@ -0,0 +1,4 @@
+-- Optional rules document participants must accept before viewing problems
+ALTER TABLE contest ADD COLUMN rules TEXT;
+
+ALTER TABLE participant ADD COLUMN rules_accepted_at TIMESTAMP;
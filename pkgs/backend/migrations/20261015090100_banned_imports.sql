@@ -0,0 +1,4 @@
+-- Per-problem banned import patterns, see problems/imports.rs for the format
+ALTER TABLE problem ADD COLUMN banned_imports TEXT NOT NULL DEFAULT '';
+ALTER TABLE problem ADD COLUMN flag_banned_imports BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE judge_run ADD COLUMN flag TEXT;
@@ -0,0 +1,16 @@
+-- Batch rejudges of a contest's runs, progress is updated as each run is reprocessed
+CREATE TABLE IF NOT EXISTS rejudge (
+    id INTEGER PRIMARY KEY NOT NULL,
+    contest_id INTEGER NOT NULL,
+    problem_id INTEGER,
+    status TEXT NOT NULL,
+    total INTEGER NOT NULL,
+    processed INTEGER NOT NULL DEFAULT 0,
+    now_passing INTEGER NOT NULL DEFAULT 0,
+    now_failing INTEGER NOT NULL DEFAULT 0,
+    errors INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP,
+    FOREIGN KEY (contest_id) REFERENCES contest(id) ON DELETE CASCADE,
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE
+);
@@ -0,0 +1,25 @@
+-- Local username/password accounts for deployments without SSO
+CREATE TABLE IF NOT EXISTS user_password (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    hash TEXT NOT NULL,
+    must_change BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS password_reset (
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL,
+    token TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+-- Self-registered accounts nobody has shown they own the email of yet, their email never
+-- counts as an admin's and signing in with SSO as that email takes it back
+CREATE TABLE IF NOT EXISTS email_verification (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+-- Verification links emailed to self-registered accounts, see auth/password.rs.
+-- Accounts from before emails could be sent have no link yet, logging in sends them one
+ALTER TABLE email_verification ADD COLUMN token TEXT;
+ALTER TABLE email_verification ADD COLUMN expires_at TIMESTAMP;
+CREATE UNIQUE INDEX IF NOT EXISTS email_verification_token ON email_verification(token);
@@ -0,0 +1,6 @@
+-- Opt-in reuse of accepted verdicts for identical submissions, see run/manager.rs
+ALTER TABLE contest ADD COLUMN verdict_cache BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE judge_run ADD COLUMN cache_key TEXT;
+ALTER TABLE judge_run ADD COLUMN cached_from INTEGER REFERENCES judge_run(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS judge_run_cache_key ON judge_run (problem_id, cache_key);
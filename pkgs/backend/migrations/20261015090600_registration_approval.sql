@@ -0,0 +1,7 @@
+-- Judges approve registrations before participants can compete
+ALTER TABLE contest ADD COLUMN require_approval BOOLEAN NOT NULL DEFAULT FALSE;
+-- Extra questions asked on sign up, one per line, `Label: option, option` for a fixed set of answers
+ALTER TABLE contest ADD COLUMN registration_fields TEXT;
+ALTER TABLE participant ADD COLUMN pending BOOLEAN NOT NULL DEFAULT FALSE;
+-- JSON object of registration field label to the participant's answer
+ALTER TABLE participant ADD COLUMN registration_data TEXT;
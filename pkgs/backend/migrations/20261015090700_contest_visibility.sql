@@ -0,0 +1,4 @@
+-- One of Public, Unlisted or Private, see contests/visibility.rs
+ALTER TABLE contest ADD COLUMN visibility TEXT NOT NULL DEFAULT 'Public';
+-- Code participants must enter to join, always required for private contests
+ALTER TABLE contest ADD COLUMN join_code TEXT;
@@ -0,0 +1,3 @@
+-- Last time a judge used a judge-only page, for the "judges online" indicator
+ALTER TABLE participant ADD COLUMN last_active_at TIMESTAMP;
+ALTER TABLE participant ADD COLUMN presence_hidden BOOLEAN NOT NULL DEFAULT FALSE;
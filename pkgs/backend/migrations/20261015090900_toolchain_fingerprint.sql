@@ -0,0 +1,16 @@
+-- Last known state of each language's toolchain, see run/fingerprint.rs
+CREATE TABLE IF NOT EXISTS toolchain_fingerprint (
+    language TEXT PRIMARY KEY NOT NULL,
+    -- JSON object of component (version output, binary path) to its value or hash
+    fingerprint TEXT NOT NULL,
+    recorded_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Changes found when comparing against the last fingerprint on startup
+CREATE TABLE IF NOT EXISTS toolchain_drift (
+    id INTEGER PRIMARY KEY NOT NULL,
+    language TEXT NOT NULL,
+    diff TEXT NOT NULL,
+    detected_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    acknowledged BOOLEAN NOT NULL DEFAULT FALSE
+);
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS series (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    -- Only each participant's best this many contests count, NULL to add up every contest
+    best_n INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS series_contest (
+    series_id INTEGER NOT NULL,
+    contest_id INTEGER NOT NULL,
+    FOREIGN KEY (series_id) REFERENCES series(id) ON DELETE CASCADE,
+    FOREIGN KEY (contest_id) REFERENCES contest(id) ON DELETE CASCADE,
+    UNIQUE (series_id, contest_id)
+);
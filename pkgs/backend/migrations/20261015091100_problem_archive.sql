@@ -0,0 +1,6 @@
+-- Problems from ended contests published for practice, see archive/mod.rs
+CREATE TABLE IF NOT EXISTS archived_problem (
+    problem_id INTEGER PRIMARY KEY NOT NULL,
+    published_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE
+);
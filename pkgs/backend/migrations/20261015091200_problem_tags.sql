@@ -0,0 +1,4 @@
+-- Comma separated, lowercase, see problems/tags.rs
+ALTER TABLE problem ADD COLUMN tags TEXT NOT NULL DEFAULT '';
+-- One of Unrated, Easy, Medium or Hard, see problems/tags.rs
+ALTER TABLE problem ADD COLUMN difficulty TEXT NOT NULL DEFAULT 'Unrated';
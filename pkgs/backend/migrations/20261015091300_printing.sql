@@ -0,0 +1,17 @@
+-- Participants can send code to be printed, for onsite contests
+ALTER TABLE contest ADD COLUMN printing_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS print_job (
+    id INTEGER PRIMARY KEY NOT NULL,
+    contest_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    title VARCHAR(100) NOT NULL,
+    content TEXT NOT NULL,
+    -- One of Queued, Sent, Failed or Delivered, see printing/mod.rs
+    status TEXT NOT NULL DEFAULT 'Queued',
+    -- Why the printer didn't take the job, only set when Failed
+    error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (contest_id) REFERENCES contest(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
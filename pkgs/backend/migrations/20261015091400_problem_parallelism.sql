@@ -0,0 +1,2 @@
+-- How many workers to split test cases across when judging, see run/job.rs
+ALTER TABLE problem ADD COLUMN parallelism INTEGER NOT NULL DEFAULT 1 CHECK (parallelism >= 1);
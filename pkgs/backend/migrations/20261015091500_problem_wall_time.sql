@@ -0,0 +1,2 @@
+-- Real time limit per test case in seconds, 0 falls back to the server's hard timeout
+ALTER TABLE problem ADD COLUMN wall_time INTEGER NOT NULL DEFAULT 0 CHECK (wall_time >= 0);
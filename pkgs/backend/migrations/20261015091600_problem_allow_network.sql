@@ -0,0 +1,2 @@
+-- Run submissions with loopback networking, only honored when run.isolation.allow_network is on
+ALTER TABLE problem ADD COLUMN allow_network BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Syscall seccomp stopped the run for, when run.isolation.seccomp.diagnostics is on
+ALTER TABLE judge_run ADD COLUMN blocked_syscall TEXT;
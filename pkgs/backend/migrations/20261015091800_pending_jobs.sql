@@ -0,0 +1,16 @@
+-- Judging jobs that haven't finished, started again on startup if a restart cut them off, see run/pending.rs
+CREATE TABLE IF NOT EXISTS pending_jobs (
+    -- Users only have one job at a time
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    problem_id INTEGER NOT NULL,
+    contest_id INTEGER NOT NULL,
+    program TEXT NOT NULL,
+    language TEXT NOT NULL,
+    flag TEXT,
+    practice BOOLEAN NOT NULL DEFAULT FALSE,
+    -- How many times a restart cut the job off, each restart supersedes the attempt before it
+    attempts INTEGER NOT NULL DEFAULT 0,
+    queued_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE
+);
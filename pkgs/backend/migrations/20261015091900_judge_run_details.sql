@@ -0,0 +1,4 @@
+-- JSON array of each case's outcome and time, see `CaseVerdict` in run/job.rs
+ALTER TABLE judge_run ADD COLUMN case_verdicts TEXT;
+-- What the compiler printed if the program didn't compile
+ALTER TABLE judge_run ADD COLUMN compile_output TEXT;
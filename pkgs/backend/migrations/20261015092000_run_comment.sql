@@ -0,0 +1,14 @@
+-- Notes judges leave on a run for the participant who submitted it
+CREATE TABLE IF NOT EXISTS run_comment (
+    id INTEGER PRIMARY KEY NOT NULL,
+    run_id INTEGER NOT NULL,
+    -- NULL once the judge's account is deleted
+    author_id INTEGER,
+    body TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (run_id) REFERENCES judge_run(id) ON DELETE CASCADE,
+    FOREIGN KEY (author_id) REFERENCES user(id) ON DELETE SET NULL
+);
+
+-- Participants see comments as soon as they're written instead of once the contest ends
+ALTER TABLE contest ADD COLUMN comments_during_contest BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- A verdict set by a judge that replaces the automated one
+ALTER TABLE judge_run ADD COLUMN manual_verdict BOOLEAN;
+ALTER TABLE judge_run ADD COLUMN override_reason TEXT;
+ALTER TABLE judge_run ADD COLUMN overridden_by INTEGER REFERENCES user(id) ON DELETE SET NULL;
+ALTER TABLE judge_run ADD COLUMN overridden_at DATETIME;
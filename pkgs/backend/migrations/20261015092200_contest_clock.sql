@@ -0,0 +1,4 @@
+-- When the contest clock was paused, NULL while it's running
+ALTER TABLE contest ADD COLUMN paused_at DATETIME;
+-- JSON array of earlier pauses as [start, end] pairs, time inside them doesn't count towards the contest
+ALTER TABLE contest ADD COLUMN pauses TEXT;
@@ -0,0 +1,2 @@
+-- Extra minutes a participant gets on top of the contest's end time, as an accommodation
+ALTER TABLE participant ADD COLUMN extra_minutes INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+-- Draft contests are only visible to judges and admins until they're published,
+-- publish_at schedules that to happen automatically
+ALTER TABLE contest ADD COLUMN published BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE contest ADD COLUMN publish_at DATETIME;
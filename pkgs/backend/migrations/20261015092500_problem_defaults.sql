@@ -0,0 +1,7 @@
+-- Limits and languages new problems in a contest start with
+ALTER TABLE contest ADD COLUMN default_cpu_time INTEGER NOT NULL DEFAULT 1 CHECK (default_cpu_time >= 1);
+ALTER TABLE contest ADD COLUMN default_memory_limit INTEGER NOT NULL DEFAULT 125 CHECK (default_memory_limit >= 1);
+ALTER TABLE contest ADD COLUMN default_languages TEXT NOT NULL DEFAULT '';
+
+-- Comma separated language keys participants can submit in, empty allows every language
+ALTER TABLE problem ADD COLUMN allowed_languages TEXT NOT NULL DEFAULT '';
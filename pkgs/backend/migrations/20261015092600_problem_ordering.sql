@@ -0,0 +1,10 @@
+-- Problems are shown in this order and labelled A, B, C... within their contest
+ALTER TABLE problem ADD COLUMN ordering INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE problem ADD COLUMN label TEXT NOT NULL DEFAULT '';
+
+-- Keep the order existing problems were created in
+UPDATE problem SET ordering = (SELECT COUNT(*) FROM problem AS p WHERE p.contest_id = problem.contest_id AND p.id < problem.id);
+UPDATE problem SET label = CASE
+    WHEN ordering < 26 THEN char(65 + ordering)
+    ELSE char(64 + ordering / 26) || char(65 + ordering % 26)
+END;
@@ -0,0 +1,3 @@
+-- Program run against every test case's input when the problem is saved, empty for no validator
+ALTER TABLE problem ADD COLUMN validator TEXT NOT NULL DEFAULT '';
+ALTER TABLE problem ADD COLUMN validator_language TEXT NOT NULL DEFAULT '';
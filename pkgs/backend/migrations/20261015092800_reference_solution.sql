@@ -0,0 +1,15 @@
+-- Known solutions to a problem and the verdict each should get, checked by verifying the problem
+CREATE TABLE IF NOT EXISTS reference_solution (
+    id INTEGER PRIMARY KEY NOT NULL,
+    problem_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    language TEXT NOT NULL,
+    program TEXT NOT NULL,
+    expected TEXT NOT NULL,
+    -- Verdict from the last verification, NULL until the problem has been verified
+    last_verdict TEXT,
+    last_detail TEXT,
+    verified_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE
+);
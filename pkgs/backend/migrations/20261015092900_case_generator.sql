@@ -0,0 +1,7 @@
+-- Program that prints a test case's input for a seed, and the seeds to run it with, one per line
+ALTER TABLE problem ADD COLUMN generator TEXT NOT NULL DEFAULT '';
+ALTER TABLE problem ADD COLUMN generator_language TEXT NOT NULL DEFAULT '';
+ALTER TABLE problem ADD COLUMN generator_seeds TEXT NOT NULL DEFAULT '';
+
+-- Generated cases are replaced each time cases are generated and aren't edited by hand
+ALTER TABLE test_case ADD COLUMN generated BOOLEAN NOT NULL DEFAULT FALSE;
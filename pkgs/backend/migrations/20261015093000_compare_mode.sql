@@ -0,0 +1,7 @@
+-- How output is checked against the expected output, cases inherit the problem's mode unless they pick one
+ALTER TABLE problem ADD COLUMN compare_mode TEXT NOT NULL DEFAULT 'Exact';
+-- Tolerances for the Float mode, a number passes if it's within either of them
+ALTER TABLE problem ADD COLUMN abs_epsilon REAL NOT NULL DEFAULT 0.000001 CHECK (abs_epsilon >= 0);
+ALTER TABLE problem ADD COLUMN rel_epsilon REAL NOT NULL DEFAULT 0.000001 CHECK (rel_epsilon >= 0);
+
+ALTER TABLE test_case ADD COLUMN compare_mode TEXT NOT NULL DEFAULT 'Inherit';
@@ -0,0 +1,11 @@
+-- Code a user is still working on for a problem, autosaved from the editor
+CREATE TABLE IF NOT EXISTS code_draft (
+    user_id INTEGER NOT NULL,
+    problem_id INTEGER NOT NULL,
+    language TEXT NOT NULL,
+    program TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, problem_id),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE
+);
@@ -0,0 +1,25 @@
+-- Largest team participants can form, teams are turned off when unset
+ALTER TABLE contest ADD COLUMN max_team_size INTEGER;
+
+CREATE TABLE IF NOT EXISTS team (
+    id INTEGER PRIMARY KEY NOT NULL,
+    contest_id INTEGER NOT NULL,
+    name VARCHAR(50) NOT NULL,
+    -- Secret part of the team's join link
+    invite_code TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (contest_id, name),
+    FOREIGN KEY (contest_id) REFERENCES contest(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS team_member (
+    team_id INTEGER NOT NULL,
+    p_id INTEGER NOT NULL,
+    is_leader BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Invited by a leader but hasn't accepted yet
+    pending BOOLEAN NOT NULL DEFAULT FALSE,
+    joined_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (team_id, p_id),
+    FOREIGN KEY (team_id) REFERENCES team(id) ON DELETE CASCADE,
+    FOREIGN KEY (p_id) REFERENCES participant(p_id) ON DELETE CASCADE
+);
@@ -0,0 +1,14 @@
+-- Admin viewing the site as this user, see auth/impersonation.rs
+ALTER TABLE session ADD COLUMN impersonating_id INTEGER REFERENCES user(id) ON DELETE SET NULL;
+
+-- Everything an admin did while viewing as someone else
+CREATE TABLE IF NOT EXISTS impersonation_log (
+    id INTEGER PRIMARY KEY NOT NULL,
+    admin_id INTEGER NOT NULL,
+    target_id INTEGER NOT NULL,
+    -- Started, Stopped, or the method and path of a request made meanwhile
+    action TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (admin_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (target_id) REFERENCES user(id) ON DELETE CASCADE
+);
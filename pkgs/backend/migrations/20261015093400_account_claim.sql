@@ -0,0 +1,8 @@
+-- One-time links for accounts an admin created ahead of time, see admin/import.rs
+CREATE TABLE IF NOT EXISTS account_claim (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    token TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- Certificates participants can download once a contest is over, see certificates/mod.rs
+CREATE TABLE IF NOT EXISTS certificate_template (
+    contest_id INTEGER PRIMARY KEY NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- SVG with {{placeholders}}
+    template TEXT NOT NULL,
+    FOREIGN KEY (contest_id) REFERENCES contest(id) ON DELETE CASCADE
+);
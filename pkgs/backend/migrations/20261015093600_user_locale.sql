@@ -0,0 +1,2 @@
+-- The locale a user picked to see the site in, NULL follows their browser, see i18n/mod.rs
+ALTER TABLE user ADD COLUMN locale TEXT;
@@ -0,0 +1,10 @@
+-- Markdown pages admins add to the site, see pages/mod.rs
+CREATE TABLE IF NOT EXISTS page (
+    id INTEGER PRIMARY KEY NOT NULL,
+    -- Shown at /pages/<slug>
+    slug VARCHAR(50) NOT NULL UNIQUE,
+    title VARCHAR(100) NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
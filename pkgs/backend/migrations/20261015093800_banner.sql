@@ -0,0 +1,10 @@
+-- The site-wide announcement banner, there's only ever one so it's always row 1, see banner.rs
+CREATE TABLE IF NOT EXISTS banner (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    -- info, warning or maintenance
+    kind TEXT NOT NULL,
+    message TEXT NOT NULL,
+    -- Only shown between these, NULL for no limit
+    starts_at TIMESTAMP,
+    ends_at TIMESTAMP
+);
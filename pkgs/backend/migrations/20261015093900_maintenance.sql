@@ -0,0 +1,8 @@
+-- Maintenance mode turned on by an admin, the site is in maintenance mode while this has a row,
+-- see maintenance.rs
+CREATE TABLE IF NOT EXISTS maintenance (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    -- Shown on the maintenance page
+    message TEXT NOT NULL DEFAULT '',
+    started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,5 @@
+-- Participants who registered after a contest filled up, they're also pending so they're kept
+-- out of everything until they get a spot
+ALTER TABLE participant ADD COLUMN waitlisted BOOLEAN NOT NULL DEFAULT FALSE;
+-- When they got a spot off the waitlist
+ALTER TABLE participant ADD COLUMN promoted_at TIMESTAMP;
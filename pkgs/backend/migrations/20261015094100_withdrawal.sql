@@ -0,0 +1,2 @@
+-- Participants can leave a contest themselves until it starts
+ALTER TABLE contest ADD COLUMN allow_withdrawal BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,4 @@
+-- Spectators (coaches, media) follow a contest without competing, they see verdicts but not code
+ALTER TABLE participant ADD COLUMN is_spectator BOOLEAN NOT NULL DEFAULT FALSE;
+-- Spectators see the real standings while the leaderboard is frozen
+ALTER TABLE contest ADD COLUMN spectators_see_unfrozen BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,18 @@
+-- Standings from an earlier contest replayed on a contest's leaderboard for training, see
+-- leaderboard/ghosts.rs
+CREATE TABLE IF NOT EXISTS ghost (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    contest_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    FOREIGN KEY (contest_id) REFERENCES contest(id) ON DELETE CASCADE
+);
+-- A problem a ghost solved, it shows once the contest clock gets to solve_seconds
+CREATE TABLE IF NOT EXISTS ghost_solve (
+    ghost_id INTEGER NOT NULL,
+    problem_id INTEGER NOT NULL,
+    solve_seconds INTEGER NOT NULL,
+    num_wrong INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (ghost_id, problem_id),
+    FOREIGN KEY (ghost_id) REFERENCES ghost(id) ON DELETE CASCADE,
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- Seconds a participant has to wait between submissions to a problem, NULL for no wait
+ALTER TABLE problem ADD COLUMN submission_cooldown INTEGER;
+-- Submissions a participant gets for a problem, NULL for no limit
+ALTER TABLE problem ADD COLUMN max_attempts INTEGER;
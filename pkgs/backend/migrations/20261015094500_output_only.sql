@@ -0,0 +1,2 @@
+-- Problems where participants hand in an answer file for each case instead of a program
+ALTER TABLE problem ADD COLUMN output_only BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Problems answered with a single short answer instead of a program, each case is an accepted answer
+ALTER TABLE problem ADD COLUMN quiz BOOLEAN NOT NULL DEFAULT FALSE;
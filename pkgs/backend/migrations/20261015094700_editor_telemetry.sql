@@ -0,0 +1,15 @@
+-- Record pastes and focus loss from the problem editor for judges, participants are told on the
+-- rules page, see problems/telemetry.rs
+ALTER TABLE contest ADD COLUMN editor_telemetry BOOLEAN NOT NULL DEFAULT FALSE;
+-- Running totals for a participant on a problem
+CREATE TABLE IF NOT EXISTS editor_telemetry (
+    participant_id INTEGER NOT NULL,
+    problem_id INTEGER NOT NULL,
+    large_pastes INTEGER NOT NULL DEFAULT 0,
+    pasted_chars INTEGER NOT NULL DEFAULT 0,
+    focus_losses INTEGER NOT NULL DEFAULT 0,
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (participant_id, problem_id),
+    FOREIGN KEY (participant_id) REFERENCES participant(p_id) ON DELETE CASCADE,
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE
+);
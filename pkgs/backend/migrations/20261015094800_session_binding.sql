@@ -0,0 +1,26 @@
+-- Tie each competitor's submissions to the address and device of their first one, see
+-- contests/binding.rs. Off, Flag or Block
+ALTER TABLE contest ADD COLUMN session_binding TEXT NOT NULL DEFAULT 'Off';
+CREATE TABLE IF NOT EXISTS session_binding (
+    participant_id INTEGER PRIMARY KEY NOT NULL,
+    ip TEXT NOT NULL,
+    device TEXT NOT NULL,
+    bound_at DATETIME NOT NULL,
+    -- The last submission that didn't match, so judges can allow it
+    mismatch_ip TEXT,
+    mismatch_device TEXT,
+    mismatch_at DATETIME,
+    mismatches INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (participant_id) REFERENCES participant(p_id) ON DELETE CASCADE
+);
+-- Other addresses or devices judges let a participant submit from, e.g. after a network change
+CREATE TABLE IF NOT EXISTS session_binding_allowance (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    participant_id INTEGER NOT NULL,
+    ip TEXT,
+    device TEXT,
+    added_by INTEGER,
+    added_at DATETIME NOT NULL,
+    FOREIGN KEY (participant_id) REFERENCES participant(p_id) ON DELETE CASCADE,
+    FOREIGN KEY (added_by) REFERENCES user(id) ON DELETE SET NULL
+);
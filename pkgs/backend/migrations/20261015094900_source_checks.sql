@@ -0,0 +1,5 @@
+-- Checks on a program's source before it's judged, see contests/source_checks.rs.
+-- banned_patterns is one regex per line, required_header one line of text per line
+ALTER TABLE contest ADD COLUMN max_line_length INTEGER;
+ALTER TABLE contest ADD COLUMN banned_patterns TEXT;
+ALTER TABLE contest ADD COLUMN required_header TEXT;
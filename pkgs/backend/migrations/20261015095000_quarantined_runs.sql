@@ -0,0 +1,19 @@
+-- Runs that kept hitting judge errors after being retried, held for an admin instead of being
+-- saved as a judge error, see run/quarantine.rs
+CREATE TABLE IF NOT EXISTS quarantined_run (
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id INTEGER NOT NULL,
+    problem_id INTEGER NOT NULL,
+    contest_id INTEGER NOT NULL,
+    program TEXT NOT NULL,
+    language TEXT NOT NULL,
+    flag TEXT,
+    practice BOOLEAN NOT NULL DEFAULT FALSE,
+    -- The judge error from the last attempt
+    error TEXT NOT NULL,
+    -- How many times it was judged, including retries
+    attempts INTEGER NOT NULL,
+    quarantined_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE
+);
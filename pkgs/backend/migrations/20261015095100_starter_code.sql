@@ -0,0 +1,12 @@
+-- Code the editor starts with for a problem in one language, see problems/starter.rs.
+-- A non-empty locked prefix or suffix has to be kept as is by every submission in that language
+CREATE TABLE IF NOT EXISTS starter_code (
+    problem_id INTEGER NOT NULL,
+    language TEXT NOT NULL,
+    locked_prefix TEXT NOT NULL DEFAULT '',
+    code TEXT NOT NULL DEFAULT '',
+    locked_suffix TEXT NOT NULL DEFAULT '',
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (problem_id, language),
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+-- Cases participants can see and run their program against before submitting
+ALTER TABLE test_case ADD COLUMN sample BOOLEAN NOT NULL DEFAULT 0;
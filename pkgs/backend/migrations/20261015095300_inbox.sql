@@ -0,0 +1,30 @@
+-- Messages judges send to a participant or a whole team, see inbox/mod.rs
+CREATE TABLE IF NOT EXISTS inbox_message (
+    id INTEGER PRIMARY KEY NOT NULL,
+    contest_id INTEGER NOT NULL,
+    -- The judge who sent it, kept even if they're no longer a judge
+    sender_id INTEGER,
+    -- Exactly one of these is set
+    recipient_id INTEGER,
+    team_id INTEGER,
+    subject VARCHAR(100) NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (contest_id) REFERENCES contest(id) ON DELETE CASCADE,
+    FOREIGN KEY (sender_id) REFERENCES user(id) ON DELETE SET NULL,
+    FOREIGN KEY (recipient_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (team_id) REFERENCES team(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS inbox_message_recipient ON inbox_message (recipient_id);
+CREATE INDEX IF NOT EXISTS inbox_message_team ON inbox_message (team_id);
+
+-- Each member of a team reads a team message on their own
+CREATE TABLE IF NOT EXISTS inbox_read (
+    message_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    read_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (message_id, user_id),
+    FOREIGN KEY (message_id) REFERENCES inbox_message(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
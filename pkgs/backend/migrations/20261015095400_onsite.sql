@@ -0,0 +1,10 @@
+-- Accounts an admin generated for an on-site contest, see auth/onsite.rs. They can only log in
+-- with their password from the configured addresses, never through SSO.
+CREATE TABLE IF NOT EXISTS onsite_account (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    -- The contest they were registered for when generated, if any
+    contest_id INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (contest_id) REFERENCES contest(id) ON DELETE SET NULL
+);
@@ -0,0 +1,34 @@
+-- Problems kept outside any contest so they can be reused, see bank/mod.rs
+CREATE TABLE IF NOT EXISTS bank_problem (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    -- Normalized like a problem's tags, comma separated
+    tags TEXT NOT NULL DEFAULT '',
+    difficulty TEXT NOT NULL DEFAULT 'Unrated',
+    created_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES user(id) ON DELETE SET NULL
+);
+
+-- Every saved version of a bank problem, revisions are never changed once made
+CREATE TABLE IF NOT EXISTS bank_revision (
+    id INTEGER PRIMARY KEY NOT NULL,
+    bank_problem_id INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    -- The problem in the same JSON format as a problem export
+    data TEXT NOT NULL,
+    note TEXT NOT NULL DEFAULT '',
+    created_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (bank_problem_id) REFERENCES bank_problem(id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES user(id) ON DELETE SET NULL,
+    UNIQUE (bank_problem_id, version)
+);
+
+-- Which revision a contest's problem was made from
+CREATE TABLE IF NOT EXISTS bank_usage (
+    problem_id INTEGER PRIMARY KEY NOT NULL,
+    revision_id INTEGER NOT NULL,
+    FOREIGN KEY (problem_id) REFERENCES problem(id) ON DELETE CASCADE,
+    FOREIGN KEY (revision_id) REFERENCES bank_revision(id) ON DELETE CASCADE
+);
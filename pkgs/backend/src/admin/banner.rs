@@ -0,0 +1,151 @@
+#![allow(clippy::blocks_in_conditions)] // Needed for the derive of FromForm, rocket is weird
+
+use std::collections::HashMap;
+
+use chrono::TimeZone;
+use rocket::{
+    form::{self, Contextual, Form},
+    get, post, FromForm,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    banner::{Banner, BANNER_KINDS},
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    template::{FormTemplateObject, TemplatedForm},
+    times::{datetime_to_html_time, ClientTimeZone, FormDateTime},
+};
+
+struct BannerFormTemplate<'r> {
+    banner: Option<&'r Banner>,
+    timezone: &'r ClientTimeZone,
+}
+
+impl TemplatedForm for BannerFormTemplate<'_> {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        let tz = self.timezone.timezone();
+        let html_time = |t: Option<chrono::NaiveDateTime>| {
+            t.map(|t| datetime_to_html_time(&tz.from_utc_datetime(&t)))
+                .unwrap_or_default()
+        };
+        if let Some(banner) = self.banner {
+            HashMap::from_iter([
+                ("kind".to_string(), banner.kind.clone()),
+                ("message".to_string(), banner.message.clone()),
+                ("starts_at".to_string(), html_time(banner.starts_at)),
+                ("ends_at".to_string(), html_time(banner.ends_at)),
+            ])
+        } else {
+            HashMap::from_iter([
+                ("kind".to_string(), BANNER_KINDS[0].to_string()),
+                ("message".to_string(), "".to_string()),
+                ("starts_at".to_string(), "".to_string()),
+                ("ends_at".to_string(), "".to_string()),
+            ])
+        }
+    }
+}
+
+#[inline]
+fn valid_kind<'e>(kind: &str) -> Result<(), form::Errors<'e>> {
+    if BANNER_KINDS.contains(&kind) {
+        Ok(())
+    } else {
+        Err(form::Error::validation("Invalid banner kind").into())
+    }
+}
+
+#[inline]
+fn after_start<'e>(
+    ends_at: &Option<FormDateTime>,
+    starts_at: &Option<FormDateTime>,
+) -> Result<(), form::Errors<'e>> {
+    match (starts_at, ends_at) {
+        (Some(s), Some(e)) if e.0 <= s.0 => {
+            Err(form::Error::validation("Must be after the start time").into())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[derive(FromForm)]
+pub struct BannerForm<'r> {
+    #[field(validate = valid_kind())]
+    kind: &'r str,
+    #[field(validate = len(1..=500))]
+    message: &'r str,
+    starts_at: Option<FormDateTime>,
+    #[field(validate = after_start(&self.starts_at))]
+    ends_at: Option<FormDateTime>,
+}
+
+#[get("/banner")]
+pub async fn banner_get(
+    user: &User,
+    _admin: &Admin,
+    _token: &CsrfToken,
+    timezone: ClientTimeZone,
+) -> Template {
+    let banner = Banner::current();
+    let form_template = BannerFormTemplate {
+        banner: banner.as_ref(),
+        timezone: &timezone,
+    };
+    let form = FormTemplateObject::get(form_template);
+    let is_active = banner.as_ref().is_some_and(Banner::is_active);
+    let ctx = context_with_base_authed!(user, form, current_banner: banner, is_active, kinds: BANNER_KINDS);
+    Template::render("admin/banner", ctx)
+}
+
+#[post("/banner", data = "<form>")]
+pub async fn banner_post(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    timezone: ClientTimeZone,
+    form: Form<Contextual<'_, BannerForm<'_>>>,
+) -> FormResponse {
+    if let Some(ref value) = form.value {
+        let tz = timezone.timezone();
+        let to_utc = |t: &Option<FormDateTime>| {
+            t.as_ref()
+                .map(|t| tz.from_local_datetime(&t.0).unwrap().naive_utc())
+        };
+        let banner = Banner {
+            kind: value.kind.to_string(),
+            message: value.message.trim().to_string(),
+            starts_at: to_utc(&value.starts_at),
+            ends_at: to_utc(&value.ends_at),
+        };
+        banner.save(&mut db).await?;
+        Ok(Message::success("Banner Saved").to("/admin/banner"))
+    } else {
+        let banner = Banner::current();
+        let form_template = BannerFormTemplate {
+            banner: None,
+            timezone: &timezone,
+        };
+        let form = FormTemplateObject::from_rocket_context(form_template, &form.context);
+        let is_active = banner.as_ref().is_some_and(Banner::is_active);
+        let ctx = context_with_base_authed!(user, form, current_banner: banner, is_active, kinds: BANNER_KINDS);
+        Err(Template::render("admin/banner", ctx).into())
+    }
+}
+
+#[post("/banner/clear")]
+pub async fn banner_clear_post(
+    mut db: DbConnection,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    Banner::clear(&mut db).await?;
+    Ok(Message::success("Banner Removed").to("/admin/banner"))
+}
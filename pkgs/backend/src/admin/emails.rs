@@ -0,0 +1,60 @@
+use rocket::{get, http::Status, response::content::RawHtml, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base_authed,
+    email::{EmailConfig, EmailKind, EmailTemplates},
+    error::prelude::*,
+};
+
+#[derive(Serialize)]
+struct EmailKindInfo {
+    key: &'static str,
+    description: &'static str,
+    enabled: bool,
+}
+
+#[get("/emails")]
+pub async fn emails(user: &User, _admin: &Admin, config: &State<EmailConfig>) -> Template {
+    let kinds = EmailKind::ALL
+        .iter()
+        .map(|k| EmailKindInfo {
+            key: k.key(),
+            description: k.description(),
+            enabled: config.is_enabled(*k),
+        })
+        .collect::<Vec<_>>();
+    let ctx = context_with_base_authed!(user, kinds);
+    Template::render("admin/emails", ctx)
+}
+
+#[get("/emails/<key>")]
+pub async fn email_preview(
+    key: &str,
+    user: &User,
+    _admin: &Admin,
+    config: &State<EmailConfig>,
+    templates: &State<EmailTemplates>,
+) -> ResultResponse<Template> {
+    let kind = EmailKind::from_key(key).ok_or(Status::NotFound)?;
+    let email = templates.render_preview(kind)?;
+    let info = EmailKindInfo {
+        key: kind.key(),
+        description: kind.description(),
+        enabled: config.is_enabled(kind),
+    };
+    let ctx = context_with_base_authed!(user, info, email);
+    Ok(Template::render("admin/email_preview", ctx))
+}
+
+#[get("/emails/<key>/preview.html")]
+pub async fn email_preview_html(
+    key: &str,
+    _admin: &Admin,
+    templates: &State<EmailTemplates>,
+) -> ResultResponse<RawHtml<String>> {
+    let kind = EmailKind::from_key(key).ok_or(Status::NotFound)?;
+    let email = templates.render_preview(kind)?;
+    Ok(RawHtml(email.html))
+}
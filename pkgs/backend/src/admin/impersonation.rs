@@ -0,0 +1,150 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use chrono::TimeZone;
+use log::warn;
+use rocket::{
+    get,
+    http::{Cookie, CookieJar, Status},
+    post,
+    response::Redirect,
+    State,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        impersonation::{Impersonation, ImpersonationLog, BANNER_COOKIE_NAME},
+        sessions::Session,
+        users::{Admin, AdminUsers, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    times::{format_datetime_human_readable, ClientTimeZone},
+};
+
+const LOG_LIMIT: i64 = 500;
+
+#[get("/users/<id>/impersonate")]
+pub async fn impersonate_get(
+    id: i64,
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let target_user = User::get_or_404(&mut db, id).await?;
+    let ctx = context_with_base_authed!(user, target_user);
+    Ok(Template::render("admin/impersonate", ctx))
+}
+
+#[post("/users/<id>/impersonate")]
+pub async fn impersonate_post(
+    id: i64,
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    admins: &State<AdminUsers>,
+    cookies: &CookieJar<'_>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let target = User::get_or_404(&mut db, id).await?;
+    if target.id == user.id {
+        return Ok(Message::error("You can't view as yourself").to("/admin/users"));
+    }
+    // Another admin's view could be used to act with their privileges
+    if admins.contains(&target.email) {
+        return Ok(Message::error("You can't view as another admin").to("/admin/users"));
+    }
+    let token = cookies
+        .get_private(Session::TOKEN_COOKIE_NAME)
+        .map(|c| c.value().to_string())
+        .ok_or(Status::Unauthorized)?;
+    let mut session = Session::from_token(&mut db, &token)
+        .await?
+        .ok_or(Status::Unauthorized)?;
+    session.set_impersonating(&mut db, Some(target.id)).await?;
+    ImpersonationLog::record(&mut db, user.id, target.id, "Started").await?;
+    warn!("Admin {} started viewing as user {}", user.id, target.id);
+    cookies.add(Impersonation::banner_cookie(&target));
+    Ok(
+        Message::info("You're now viewing as {name}, everything is read-only")
+            .arg("name", target.display_name())
+            .to("/"),
+    )
+}
+
+#[post("/impersonate/stop")]
+pub async fn stop_impersonating(
+    mut db: DbConnection,
+    impersonation: &Impersonation,
+    cookies: &CookieJar<'_>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let mut session = Session::from_token(
+        &mut db,
+        &cookies
+            .get_private(Session::TOKEN_COOKIE_NAME)
+            .map(|c| c.value().to_string())
+            .ok_or(Status::Unauthorized)?,
+    )
+    .await?
+    .filter(|s| s.id == impersonation.session.id)
+    .ok_or(Status::Unauthorized)?;
+    session.set_impersonating(&mut db, None).await?;
+    ImpersonationLog::record(
+        &mut db,
+        impersonation.admin.id,
+        impersonation.target.id,
+        "Stopped",
+    )
+    .await?;
+    warn!(
+        "Admin {} stopped viewing as user {}",
+        impersonation.admin.id, impersonation.target.id
+    );
+    cookies.remove(Cookie::build(BANNER_COOKIE_NAME).path("/"));
+    Ok(Message::success("You're back to your own account").to("/admin/users"))
+}
+
+#[derive(Serialize)]
+struct LogRow {
+    entry: ImpersonationLog,
+    admin_name: String,
+    target_name: String,
+    time: String,
+}
+
+#[get("/impersonations")]
+pub async fn impersonation_log(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    tz: ClientTimeZone,
+) -> ResultResponse<Template> {
+    let entries = ImpersonationLog::list(&mut db, LOG_LIMIT).await?;
+    let tz = tz.timezone();
+    let mut names: HashMap<i64, String> = HashMap::new();
+    let mut rows = Vec::with_capacity(entries.len());
+    for entry in entries {
+        for id in [entry.admin_id, entry.target_id] {
+            if let Entry::Vacant(slot) = names.entry(id) {
+                let name = User::get(&mut db, id)
+                    .await?
+                    .map(|u| u.display_name().to_string())
+                    .unwrap_or_else(|| format!("Deleted user {id}"));
+                slot.insert(name);
+            }
+        }
+        rows.push(LogRow {
+            admin_name: names[&entry.admin_id].clone(),
+            target_name: names[&entry.target_id].clone(),
+            time: format_datetime_human_readable(tz.from_utc_datetime(&entry.created_at)),
+            entry,
+        });
+    }
+    let ctx = context_with_base_authed!(user, rows);
+    Ok(Template::render("admin/impersonations", ctx))
+}
@@ -0,0 +1,259 @@
+use log::error;
+use rocket::{form::Form, get, http::Status, post, response::Redirect, FromForm, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        password::{self, AccountClaim, PasswordAuthConfig},
+        users::{Admin, User},
+    },
+    contests::{Contest, Participant, Team, TeamMember},
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    email::{EmailConfig, EmailTemplates},
+    error::prelude::*,
+    leaderboard::{split_csv_line, LeaderboardManagerHandle},
+    messages::Message,
+    run::CodeInfo,
+};
+
+const IMPORT_URI: &str = "/admin/users/import";
+const MAX_ROWS: usize = 1000;
+
+#[get("/users/import")]
+pub async fn import_get(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    if !password_auth.enabled {
+        return Err(Status::NotFound.into());
+    }
+    let contests = Contest::list(&mut db).await?;
+    let results: Option<Vec<ImportResult>> = None;
+    let ctx = context_with_base_authed!(user, contests, results);
+    Ok(Template::render("admin/import_users", ctx))
+}
+
+#[derive(FromForm)]
+pub struct ImportForm<'r> {
+    csv: &'r str,
+    contest_id: Option<i64>,
+}
+
+/// One line of the CSV, `name,email[,team]`
+struct ImportRow {
+    line: usize,
+    name: String,
+    email: String,
+    team: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ImportResult {
+    line: usize,
+    name: String,
+    email: String,
+    team: Option<String>,
+    /// What happened, shown as-is
+    status: String,
+    /// Path of the claim link for accounts that were just created
+    claim_path: Option<String>,
+}
+
+fn parse_csv(csv: &str) -> Result<Vec<ImportRow>, String> {
+    let mut rows = vec![];
+    for (i, line) in csv.lines().enumerate() {
+        let line_no = i + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(line);
+        // Header rows exported from spreadsheets are skipped
+        if rows.is_empty()
+            && fields
+                .first()
+                .is_some_and(|f| f.eq_ignore_ascii_case("name"))
+        {
+            continue;
+        }
+        let (name, email, team) = match fields.as_slice() {
+            [name, email] => (name, email, None),
+            [name, email, team] => (name, email, Some(team)),
+            _ => {
+                return Err(format!(
+                    "Line {line_no} should have a name, an email, and optionally a team"
+                ))
+            }
+        };
+        if name.is_empty() || name.chars().count() > 32 {
+            return Err(format!(
+                "Line {line_no} needs a name of at most 32 characters"
+            ));
+        }
+        if !email.contains('@') || email.len() > 100 {
+            return Err(format!("Line {line_no} doesn't have a valid email"));
+        }
+        rows.push(ImportRow {
+            line: line_no,
+            name: name.clone(),
+            email: email.clone(),
+            team: team.filter(|t| !t.is_empty()).cloned(),
+        });
+        if rows.len() > MAX_ROWS {
+            return Err(format!("Only {MAX_ROWS} users can be imported at once"));
+        }
+    }
+    if rows.is_empty() {
+        Err("There's no one to import".to_string())
+    } else {
+        Ok(rows)
+    }
+}
+
+async fn find_by_email(db: &mut DbPoolConnection, email: &str) -> Result<Option<User>> {
+    sqlx::query_as!(User, "SELECT * FROM user WHERE email = ?", email)
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't fetch user with email {email}"))
+}
+
+/// Add the user to the contest and team, leaving them where they are if they're
+/// already in it. Returns a note about anything that couldn't be done.
+async fn assign(
+    db: &mut DbPoolConnection,
+    contest: &Contest,
+    user: &User,
+    team: Option<&str>,
+) -> Result<Option<String>> {
+    let participant = match Participant::get(db, contest.id, user.id).await? {
+        Some(p) if p.is_judge => return Ok(Some("they're a judge of this contest".to_string())),
+        Some(p) if p.is_spectator => {
+            return Ok(Some("they're a spectator of this contest".to_string()))
+        }
+        Some(p) => p,
+        None => {
+            Participant::temp(user.id, contest.id, false)
+                .insert(db)
+                .await?
+        }
+    };
+    let Some(team_name) = team else {
+        return Ok(None);
+    };
+    if contest.max_team_size.is_none() {
+        return Ok(Some("this contest doesn't have teams".to_string()));
+    }
+    if team_name.chars().count() > 50 {
+        return Ok(Some("team names can be at most 50 characters".to_string()));
+    }
+    if let Some(current) = Team::for_participant(db, participant.p_id).await? {
+        let note = (current.name != team_name).then(|| format!("already on team {}", current.name));
+        return Ok(note);
+    }
+    let team = match Team::by_name(db, contest.id, team_name).await? {
+        Some(team) => team,
+        None => Team::insert(db, contest.id, team_name).await?,
+    };
+    let members = team.members(db).await?;
+    if !team.has_room(contest, &members) {
+        return Ok(Some(format!("team {} is full", team.name)));
+    }
+    // The first person listed for a team leads it
+    let is_leader = !members.iter().any(|(m, _)| m.is_leader);
+    TeamMember::clear_invites(db, participant.p_id).await?;
+    TeamMember::insert(db, team.id, participant.p_id, is_leader, false).await?;
+    Ok(None)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/users/import", data = "<form>")]
+pub async fn import_post(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+    code_info: &State<CodeInfo>,
+    leaderboards: &State<LeaderboardManagerHandle>,
+    email_config: &State<EmailConfig>,
+    templates: &State<EmailTemplates>,
+    form: Form<ImportForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    if !password_auth.enabled {
+        return Err(Status::NotFound.into());
+    }
+    let rows = match parse_csv(form.csv) {
+        Ok(rows) => rows,
+        Err(why) => return Ok(Err(Message::error(&why).to(IMPORT_URI))),
+    };
+    let contest = match form.contest_id {
+        Some(id) => Some(Contest::get_or_404(&mut db, id).await?),
+        None => None,
+    };
+
+    let mut results = Vec::with_capacity(rows.len());
+    for row in rows {
+        let (target, claim_path, mut status) = match find_by_email(&mut db, &row.email).await? {
+            Some(existing) => (existing, None, "Already had an account".to_string()),
+            None => {
+                let created = password::provision_local_user(
+                    &mut db,
+                    &row.email,
+                    &row.name,
+                    &code_info.run_config.default_language,
+                )
+                .await?
+                .ok_or_else(|| anyhow!("User {} appeared mid-import", row.email))?;
+                let token = AccountClaim::create(&mut db, created.id).await?;
+                let emailed = AccountClaim::email(&created, &token, email_config, templates)
+                    .await
+                    .unwrap_or_else(|why| {
+                        error!(
+                            "Couldn't email claim link to user {}: {:?}",
+                            created.id, why
+                        );
+                        false
+                    });
+                let status = if emailed {
+                    "Created, claim link emailed"
+                } else {
+                    "Created"
+                };
+                (
+                    created,
+                    Some(format!("/auth/password/claim/{token}")),
+                    status.to_string(),
+                )
+            }
+        };
+        if let Some(contest) = contest.as_ref() {
+            if let Some(note) = assign(&mut db, contest, &target, row.team.as_deref()).await? {
+                status = format!("{status}, {note}");
+            }
+        }
+        results.push(ImportResult {
+            line: row.line,
+            name: row.name,
+            email: row.email,
+            team: row.team,
+            status,
+            claim_path,
+        });
+    }
+
+    if let Some(contest) = contest.as_ref() {
+        let mut leaderboard_manager = leaderboards.lock().await;
+        leaderboard_manager
+            .refresh_leaderboard(&mut db, contest)
+            .await?;
+    }
+
+    let contests = Contest::list(&mut db).await?;
+    let results = Some(results);
+    let ctx = context_with_base_authed!(user, contests, results);
+    Ok(Ok(Template::render("admin/import_users", ctx)))
+}
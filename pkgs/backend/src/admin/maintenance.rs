@@ -0,0 +1,57 @@
+use rocket::{form::Form, get, post, FromForm, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    maintenance::Maintenance,
+    messages::Message,
+};
+
+#[get("/maintenance")]
+pub async fn maintenance_get(
+    user: &User,
+    _admin: &Admin,
+    _token: &CsrfToken,
+    maintenance: &State<Maintenance>,
+) -> Template {
+    let manual = maintenance.manual();
+    let ctx = context_with_base_authed!(
+        user,
+        enabled: manual.is_some(),
+        message: manual.unwrap_or_default(),
+        locked: maintenance.locked()
+    );
+    Template::render("admin/maintenance", ctx)
+}
+
+#[derive(FromForm)]
+pub struct MaintenanceForm<'r> {
+    enabled: bool,
+    #[field(validate = len(..=1000))]
+    message: &'r str,
+}
+
+#[post("/maintenance", data = "<form>")]
+pub async fn maintenance_post(
+    mut db: DbConnection,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    maintenance: &State<Maintenance>,
+    form: Form<MaintenanceForm<'_>>,
+) -> FormResponse {
+    let message = form.enabled.then(|| form.message.trim().to_string());
+    let enabled = message.is_some();
+    maintenance.set(&mut db, message).await?;
+    let msg = if enabled {
+        "Maintenance mode is on, only admins can use the site"
+    } else {
+        "Maintenance mode is off"
+    };
+    Ok(Message::success(msg).to("/admin/maintenance"))
+}
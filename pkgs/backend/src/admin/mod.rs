@@ -5,19 +5,37 @@ use samael::service_provider::ServiceProvider;
 
 use crate::{
     auth::{
+        csrf::CsrfToken,
+        sessions::CleanupStatsHandle,
         users::{Admin, User},
         SamlOptions, PREFERRED_SSO_BINDING,
     },
+    branding::ParsedColorConfig,
     context_with_base_authed,
-    run::CodeInfo,
+    db::DbConnection,
+    error::prelude::*,
+    run::{
+        fingerprint::ToolchainDrift, nodes::SchedulerHandle, versions::ToolchainVersionsHandle,
+        CodeInfo, ManagerHandle,
+    },
     times::{format_datetime_human_readable, ClientTimeZone},
 };
 
+mod banner;
+mod emails;
+mod impersonation;
+mod import;
+mod maintenance;
+mod onsite;
 mod runs;
+mod sessions;
+mod toolchain;
 mod users;
 
 #[get("/")]
+#[allow(clippy::too_many_arguments)]
 async fn index(
+    mut db: DbConnection,
     user: &User,
     _admin: &Admin,
     so: &State<SamlOptions>,
@@ -25,7 +43,12 @@ async fn index(
     dt: &State<StartTime>,
     tz: ClientTimeZone,
     lang_config: &State<CodeInfo>,
-) -> Template {
+    versions: &State<ToolchainVersionsHandle>,
+    scheduler: &State<SchedulerHandle>,
+    cleanup_stats: &State<CleanupStatsHandle>,
+    manager_handle: &State<ManagerHandle>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
     let saml_options = so.inner();
     let idp_id = sp
         .inner()
@@ -50,6 +73,14 @@ async fn index(
     let tz = tz.timezone();
     let start_time_local = tz.from_utc_datetime(&dt.get());
     let start_time_formatted = format_datetime_human_readable(start_time_local);
+    let cleanup_stats = cleanup_stats.lock().await.clone();
+    let toolchain_changes = ToolchainDrift::count_unacknowledged(&mut db).await?;
+    let toolchain_versions = versions.read().await.clone();
+    let judge_nodes = scheduler.nodes();
+    let reduced_enforcement = manager_handle.lock().await.reduced_enforcement().cloned();
+    let cleanup_last_run = cleanup_stats
+        .last_run
+        .map(|t| format_datetime_human_readable(tz.from_utc_datetime(&t)));
 
     let ctx = context_with_base_authed!(
         user,
@@ -59,14 +90,22 @@ async fn index(
         sp_id,
         idp_sso_binding,
         rustc_version,
-        run_config
+        run_config,
+        cleanup_stats,
+        cleanup_last_run,
+        toolchain_changes,
+        toolchain_versions,
+        judge_nodes,
+        reduced_enforcement,
+        local_jobs: scheduler.local_running(),
+        local_slots: scheduler.local_slots()
     );
-    Template::render("admin", ctx)
+    Ok(Template::render("admin", ctx))
 }
 
 #[get("/styles")]
-async fn styles(user: &User, _admin: &Admin) -> Template {
-    let ctx = context_with_base_authed!(user,);
+async fn styles(user: &User, _admin: &Admin, colors: &State<ParsedColorConfig>) -> Template {
+    let ctx = context_with_base_authed!(user, palettes: colors.palettes());
     Template::render("admin/styles", ctx)
 }
 
@@ -87,14 +126,48 @@ pub fn stage() -> AdHoc {
                 routes![
                     index,
                     styles,
+                    banner::banner_get,
+                    banner::banner_post,
+                    banner::banner_clear_post,
+                    maintenance::maintenance_get,
+                    maintenance::maintenance_post,
                     users::users,
                     users::delete_user_get,
                     users::delete_user_post,
+                    users::new_user_get,
+                    users::new_user_post,
+                    users::password_reset_get,
+                    users::password_reset_post,
+                    import::import_get,
+                    import::import_post,
+                    onsite::onsite_get,
+                    onsite::onsite_post,
+                    onsite::reprint_post,
+                    impersonation::impersonate_get,
+                    impersonation::impersonate_post,
+                    impersonation::stop_impersonating,
+                    impersonation::impersonation_log,
                     runs::runs,
                     runs::cancel_run,
                     runs::cancel_run_post,
                     runs::cancel_all_runs,
                     runs::cancel_all_runs_post,
+                    runs::drain_runs_post,
+                    runs::resume_runs_post,
+                    runs::retry_quarantined_post,
+                    runs::dismiss_quarantined_post,
+                    runs::crash_report,
+                    runs::clear_crash_reports_post,
+                    runs::request_snapshot_post,
+                    runs::cancel_snapshot_post,
+                    runs::snapshot,
+                    runs::clear_snapshots_post,
+                    sessions::purge_sessions_post,
+                    emails::emails,
+                    emails::email_preview,
+                    emails::email_preview_html,
+                    toolchain::toolchain,
+                    toolchain::acknowledge_drift,
                 ],
             )
             .manage(StartTime(now))
@@ -0,0 +1,170 @@
+use rocket::{form::Form, get, http::Status, post, response::Redirect, FromForm, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        onsite::{OnsiteAccount, OnsiteConfig},
+        password::PasswordAuthConfig,
+        users::{Admin, User},
+    },
+    contests::{Contest, Participant},
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    leaderboard::LeaderboardManagerHandle,
+    messages::Message,
+    run::CodeInfo,
+};
+
+const ONSITE_URI: &str = "/admin/users/onsite";
+const MAX_TEAMS: usize = 1000;
+
+#[get("/users/onsite")]
+pub async fn onsite_get(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+    config: &State<OnsiteConfig>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    if !password_auth.enabled {
+        return Err(Status::NotFound.into());
+    }
+    let contests = Contest::list(&mut db).await?;
+    let accounts = OnsiteAccount::list_users(&mut db, None).await?;
+    let restricted = !config.allowed_ips.is_empty();
+    let ctx = context_with_base_authed!(user, contests, accounts, restricted);
+    Ok(Template::render("admin/onsite", ctx))
+}
+
+#[derive(FromForm)]
+pub struct GenerateForm<'r> {
+    /// Team names, one per line
+    names: &'r str,
+    #[field(validate = len(1..=20))]
+    prefix: &'r str,
+    contest_id: Option<i64>,
+}
+
+fn parse_names(names: &str) -> Result<Vec<&str>, String> {
+    let names = names
+        .lines()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        return Err("There are no teams to make accounts for".to_string());
+    }
+    if names.len() > MAX_TEAMS {
+        return Err(format!("Only {MAX_TEAMS} accounts can be made at once"));
+    }
+    if let Some(long) = names.iter().find(|n| n.chars().count() > 32) {
+        return Err(format!(
+            "{long} is too long, names can be at most 32 characters"
+        ));
+    }
+    Ok(names)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/users/onsite", data = "<form>")]
+pub async fn onsite_post(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+    code_info: &State<CodeInfo>,
+    leaderboards: &State<LeaderboardManagerHandle>,
+    form: Form<GenerateForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    if !password_auth.enabled {
+        return Err(Status::NotFound.into());
+    }
+    let names = match parse_names(form.names) {
+        Ok(names) => names,
+        Err(why) => return Ok(Err(Message::error(&why).to(ONSITE_URI))),
+    };
+    let prefix = form.prefix.trim();
+    if prefix.is_empty()
+        || !prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Ok(Err(Message::error(
+            "Usernames can only have letters, numbers, dashes and underscores",
+        )
+        .to(ONSITE_URI)));
+    }
+    let contest = match form.contest_id {
+        Some(id) => Some(Contest::get_or_404(&mut db, id).await?),
+        None => None,
+    };
+
+    let mut credentials = Vec::with_capacity(names.len());
+    for name in names {
+        let (account, creds) = OnsiteAccount::generate(
+            &mut db,
+            prefix,
+            name,
+            contest.as_ref().map(|c| c.id),
+            &code_info.run_config.default_language,
+        )
+        .await?;
+        if let Some(contest) = contest.as_ref() {
+            Participant::temp(account.id, contest.id, false)
+                .insert(&mut db)
+                .await?;
+        }
+        credentials.push(creds);
+    }
+
+    if let Some(contest) = contest.as_ref() {
+        let mut leaderboard_manager = leaderboards.lock().await;
+        leaderboard_manager
+            .refresh_leaderboard(&mut db, contest)
+            .await?;
+    }
+
+    let ctx = context_with_base_authed!(user, credentials, contest);
+    Ok(Ok(Template::render("admin/onsite_credentials", ctx)))
+}
+
+#[derive(FromForm)]
+pub struct ReprintForm {
+    contest_id: Option<i64>,
+}
+
+/// Passwords aren't kept in a form we can print again, so reprinting gives every account a new one
+#[post("/users/onsite/reprint", data = "<form>")]
+pub async fn reprint_post(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+    form: Form<ReprintForm>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    if !password_auth.enabled {
+        return Err(Status::NotFound.into());
+    }
+    let contest = match form.contest_id {
+        Some(id) => Some(Contest::get_or_404(&mut db, id).await?),
+        None => None,
+    };
+    let accounts = OnsiteAccount::list_users(&mut db, form.contest_id).await?;
+    if accounts.is_empty() {
+        return Ok(Err(Message::error(
+            "There are no on-site accounts to reprint",
+        )
+        .to(ONSITE_URI)));
+    }
+    let mut credentials = Vec::with_capacity(accounts.len());
+    for account in accounts.iter() {
+        credentials.push(OnsiteAccount::reset_password(&mut db, account).await?);
+    }
+    let ctx = context_with_base_authed!(user, credentials, contest);
+    Ok(Ok(Template::render("admin/onsite_credentials", ctx)))
+}
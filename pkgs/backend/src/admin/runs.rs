@@ -1,4 +1,11 @@
-use rocket::{get, http::Status, post, response::Redirect, State};
+use rocket::{
+    form::Form,
+    get,
+    http::{ContentType, Header, Status},
+    post,
+    response::Redirect,
+    FromForm, Responder, State,
+};
 use rocket_dyn_templates::Template;
 
 use crate::{
@@ -11,7 +18,10 @@ use crate::{
     db::DbConnection,
     error::prelude::*,
     messages::Message,
-    run::ManagerHandle,
+    run::{
+        worker::forensics::{self, ReportKind},
+        CodeInfo, ManagerHandle, QuarantinedRun,
+    },
 };
 
 #[derive(Serialize)]
@@ -27,15 +37,49 @@ pub struct RunsAdminRow {
     problem: TempProblem,
 }
 
+#[derive(Responder)]
+pub struct ReportDownload {
+    inner: (ContentType, Vec<u8>),
+    disposition: Header<'static>,
+}
+
+#[derive(FromForm)]
+pub struct SnapshotForm {
+    user_id: i64,
+}
+
+async fn download(
+    info: &CodeInfo,
+    kind: ReportKind,
+    content_type: ContentType,
+    name: &str,
+) -> ResultResponse<ReportDownload> {
+    let dir = forensics::crash_reports_dir(&info.run_config.isolation);
+    let bytes = forensics::read(&dir, kind, name)
+        .await?
+        .ok_or(Status::NotFound)?;
+    Ok(ReportDownload {
+        inner: (content_type, bytes),
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("attachment; filename=\"{name}\""),
+        ),
+    })
+}
+
 #[get("/runs")]
 pub async fn runs(
     mut db: DbConnection,
     user: &User,
     _admin: &Admin,
+    _token: &CsrfToken,
     manager_handle: &State<ManagerHandle>,
+    info: &State<CodeInfo>,
 ) -> ResultResponse<Template> {
     let manager = manager_handle.lock().await;
     let jobs = manager.all_active_jobs().await;
+    let draining = manager.is_draining();
+    let snapshot_user_ids = manager.snapshot_users();
     drop(manager);
     let mut rows = Vec::with_capacity(jobs.len());
     for (job_user_id, problem_id) in jobs {
@@ -59,8 +103,27 @@ pub async fn runs(
     }
 
     let contests = Contest::list(&mut db).await?;
+    let quarantined = QuarantinedRun::list(&mut db).await?;
+    let reports_dir = forensics::crash_reports_dir(&info.run_config.isolation);
+    let crash_reports = forensics::list(&reports_dir, ReportKind::Crash).await?;
+    let snapshots = forensics::list(&reports_dir, ReportKind::Snapshot).await?;
+    let mut snapshot_users = Vec::with_capacity(snapshot_user_ids.len());
+    for id in snapshot_user_ids {
+        if let Some(u) = User::get(&mut db, id).await? {
+            snapshot_users.push(u);
+        }
+    }
 
-    let ctx = context_with_base_authed!(user, rows, contests);
+    let ctx = context_with_base_authed!(
+        user,
+        rows,
+        contests,
+        draining,
+        quarantined,
+        crash_reports,
+        snapshots,
+        snapshot_users
+    );
     Ok(Template::render("admin/runs", ctx))
 }
 
@@ -120,3 +183,150 @@ pub async fn cancel_all_runs_post(
     manager.shutdown().await;
     Message::success("All Runs Cancelled").to("/admin/runs")
 }
+
+#[post("/runs/drain")]
+pub async fn drain_runs_post(
+    _user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    manager_handle: &State<ManagerHandle>,
+) -> Redirect {
+    manager_handle.lock().await.set_draining(true);
+    Message::success("Judging Paused").to("/admin/runs")
+}
+
+#[post("/runs/resume")]
+pub async fn resume_runs_post(
+    _user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    manager_handle: &State<ManagerHandle>,
+) -> Redirect {
+    manager_handle.lock().await.set_draining(false);
+    Message::success("Judging Resumed").to("/admin/runs")
+}
+
+#[post("/runs/quarantine/<id>/retry")]
+pub async fn retry_quarantined_post(
+    mut db: DbConnection,
+    id: i64,
+    _user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    manager_handle: &State<ManagerHandle>,
+) -> ResultResponse<Redirect> {
+    let run = QuarantinedRun::get(&mut db, id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    let res = manager_handle.lock().await.retry_quarantined(&run).await;
+    if let Err(why) = res {
+        return Ok(Message::error(&why).to("/admin/runs"));
+    }
+    QuarantinedRun::remove(&mut db, id).await?;
+    Ok(Message::success("Run Queued Again").to("/admin/runs"))
+}
+
+/// Drop a quarantined run without judging it, the participant can always submit it again
+#[post("/runs/quarantine/<id>/dismiss")]
+pub async fn dismiss_quarantined_post(
+    mut db: DbConnection,
+    id: i64,
+    _user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    QuarantinedRun::get(&mut db, id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    QuarantinedRun::remove(&mut db, id).await?;
+    Ok(Message::success("Run Dismissed").to("/admin/runs"))
+}
+
+/// Download a worker's crash report, only ones saved on this server, judge nodes keep their own
+#[get("/runs/crashes/<name>")]
+pub async fn crash_report(
+    name: &str,
+    _user: &User,
+    _admin: &Admin,
+    info: &State<CodeInfo>,
+) -> ResultResponse<ReportDownload> {
+    download(info, ReportKind::Crash, ContentType::ZIP, name).await
+}
+
+#[post("/runs/crashes/clear")]
+pub async fn clear_crash_reports_post(
+    _user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    info: &State<CodeInfo>,
+) -> ResultResponse<Redirect> {
+    forensics::clear(
+        &forensics::crash_reports_dir(&info.run_config.isolation),
+        ReportKind::Crash,
+    )
+    .await?;
+    Ok(Message::success("Crash Reports Cleared").to("/admin/runs"))
+}
+
+/// Keep the sandbox's files from a user's next run, for debugging compile problems that only
+/// happen on the judge
+#[post("/runs/snapshots/request", data = "<form>")]
+pub async fn request_snapshot_post(
+    mut db: DbConnection,
+    form: Form<SnapshotForm>,
+    _user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    manager_handle: &State<ManagerHandle>,
+) -> ResultResponse<Redirect> {
+    let Some(target) = User::get(&mut db, form.user_id).await? else {
+        return Ok(Message::error("No user with that ID").to("/admin/runs"));
+    };
+    manager_handle
+        .lock()
+        .await
+        .set_snapshot_next_run(target.id, true);
+    Ok(Message::success("Snapshot Requested").to("/admin/runs"))
+}
+
+// Not `/cancel`, that would collide with cancelling a run
+#[post("/runs/snapshots/<user_id>/withdraw")]
+pub async fn cancel_snapshot_post(
+    user_id: i64,
+    _user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    manager_handle: &State<ManagerHandle>,
+) -> Redirect {
+    manager_handle
+        .lock()
+        .await
+        .set_snapshot_next_run(user_id, false);
+    Message::success("Snapshot Cancelled").to("/admin/runs")
+}
+
+/// Download a snapshot, only ones taken on this server, judge nodes keep their own
+#[get("/runs/snapshots/<name>")]
+pub async fn snapshot(
+    name: &str,
+    _user: &User,
+    _admin: &Admin,
+    info: &State<CodeInfo>,
+) -> ResultResponse<ReportDownload> {
+    download(info, ReportKind::Snapshot, ContentType::GZIP, name).await
+}
+
+#[post("/runs/snapshots/clear")]
+pub async fn clear_snapshots_post(
+    _user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    info: &State<CodeInfo>,
+) -> ResultResponse<Redirect> {
+    forensics::clear(
+        &forensics::crash_reports_dir(&info.run_config.isolation),
+        ReportKind::Snapshot,
+    )
+    .await?;
+    Ok(Message::success("Snapshots Cleared").to("/admin/runs"))
+}
@@ -0,0 +1,29 @@
+use rocket::{post, response::Redirect, State};
+
+use crate::{
+    auth::{
+        csrf::{ArCsrfTokens, VerifyCsrfToken},
+        sessions::{purge_expired, CleanupStatsHandle},
+        users::Admin,
+    },
+    db::Database,
+    error::prelude::*,
+    messages::Message,
+};
+
+#[post("/sessions/purge")]
+pub async fn purge_sessions_post(
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    db: &Database,
+    csrf_tokens: &State<ArCsrfTokens>,
+    stats: &State<CleanupStatsHandle>,
+) -> ResultResponse<Redirect> {
+    let (sessions, csrf) = purge_expired(&db.0, csrf_tokens, stats).await?;
+    Ok(
+        Message::success("Purged {sessions} expired sessions and {csrf} expired CSRF tokens")
+            .arg("sessions", sessions)
+            .arg("csrf", csrf)
+            .to("/admin"),
+    )
+}
@@ -0,0 +1,45 @@
+use chrono::TimeZone;
+use rocket::{get, post, response::Redirect};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    run::fingerprint::ToolchainDrift,
+    times::{format_datetime_human_readable, ClientTimeZone},
+};
+
+#[get("/toolchain")]
+pub async fn toolchain(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    tz: ClientTimeZone,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let drifts = ToolchainDrift::list(&mut db).await?;
+    let tz = tz.timezone();
+    let detected_times = drifts
+        .iter()
+        .map(|d| format_datetime_human_readable(tz.from_utc_datetime(&d.detected_at)))
+        .collect::<Vec<_>>();
+    let ctx = context_with_base_authed!(user, drifts, detected_times);
+    Ok(Template::render("admin/toolchain", ctx))
+}
+
+#[post("/toolchain/<id>/acknowledge")]
+pub async fn acknowledge_drift(
+    mut db: DbConnection,
+    id: i64,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    ToolchainDrift::acknowledge(&mut db, id).await?;
+    Ok(Message::success("Change acknowledged").to("/admin/toolchain"))
+}
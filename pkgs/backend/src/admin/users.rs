@@ -1,26 +1,171 @@
 use log::error;
-use rocket::{get, http::Status, post, response::Redirect, State};
+use rocket::{form::Form, get, http::Status, post, response::Redirect, FromForm, State};
 use rocket_dyn_templates::Template;
 
 use crate::{
     auth::{
         csrf::{CsrfToken, VerifyCsrfToken},
+        password::{self, PasswordAuthConfig, PasswordCredential, PasswordReset},
         users::{Admin, User},
     },
     context_with_base_authed,
     db::DbConnection,
+    email::{EmailConfig, EmailTemplates},
     error::prelude::*,
     leaderboard::LeaderboardManagerHandle,
     messages::Message,
+    run::CodeInfo,
 };
 
 #[get("/users")]
-pub async fn users(mut db: DbConnection, user: &User, _admin: &Admin) -> ResultResponse<Template> {
+pub async fn users(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+) -> ResultResponse<Template> {
     let users = User::list(&mut db).await?;
-    let ctx = context_with_base_authed!(user, users);
+    let password_auth = password_auth.inner();
+    let ctx = context_with_base_authed!(user, users, password_auth);
     Ok(Template::render("admin/users", ctx))
 }
 
+#[get("/users/new")]
+pub async fn new_user_get(
+    user: &User,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    if !password_auth.enabled {
+        return Err(Status::NotFound.into());
+    }
+    let min_length = password_auth.min_length;
+    let ctx = context_with_base_authed!(user, min_length);
+    Ok(Template::render("admin/new_user", ctx))
+}
+
+#[derive(FromForm)]
+pub struct NewUserForm<'r> {
+    #[field(validate = len(1..=100))]
+    email: &'r str,
+    #[field(validate = len(1..=32))]
+    display_name: &'r str,
+    temporary_password: &'r str,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/users/new", data = "<form>")]
+pub async fn new_user_post(
+    mut db: DbConnection,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+    code_info: &State<CodeInfo>,
+    email_config: &State<EmailConfig>,
+    templates: &State<EmailTemplates>,
+    form: Form<NewUserForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    if !password_auth.enabled {
+        return Err(Status::NotFound.into());
+    }
+    if let Err(why) =
+        password_auth.check_new_password(form.temporary_password, form.temporary_password)
+    {
+        return Ok(Message::error(&why).to("/admin/users/new"));
+    }
+
+    // They'll have to pick their own password the first time they log in
+    let created = password::create_local_user(
+        &mut db,
+        form.email.trim(),
+        form.display_name.trim(),
+        form.temporary_password,
+        true,
+        &code_info.run_config.default_language,
+    )
+    .await?;
+
+    let Some(created) = created else {
+        return Ok(Message::error("A user with this email already exists").to("/admin/users/new"));
+    };
+    let emailed = password::email_temporary_password(
+        &created,
+        form.temporary_password,
+        email_config,
+        templates,
+    )
+    .await
+    .unwrap_or_else(|why| {
+        error!(
+            "Couldn't email temporary password to user {}: {:?}",
+            created.id, why
+        );
+        false
+    });
+
+    if emailed {
+        Ok(
+            Message::success("User created, their temporary password has been emailed to them")
+                .to("/admin/users"),
+        )
+    } else {
+        Ok(Message::success("User created").to("/admin/users"))
+    }
+}
+
+#[get("/users/<id>/password_reset")]
+pub async fn password_reset_get(
+    id: i64,
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    if !password_auth.enabled {
+        return Err(Status::NotFound.into());
+    }
+    let target_user = User::get_or_404(&mut db, id).await?;
+    let has_password = PasswordCredential::get(&mut db, id).await?.is_some();
+    let reset_token: Option<String> = None;
+    let emailed = false;
+    let ctx = context_with_base_authed!(user, target_user, has_password, reset_token, emailed);
+    Ok(Template::render("admin/password_reset", ctx))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/users/<id>/password_reset")]
+pub async fn password_reset_post(
+    id: i64,
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    password_auth: &State<PasswordAuthConfig>,
+    email_config: &State<EmailConfig>,
+    templates: &State<EmailTemplates>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Template> {
+    if !password_auth.enabled {
+        return Err(Status::NotFound.into());
+    }
+    let target_user = User::get_or_404(&mut db, id).await?;
+    let has_password = PasswordCredential::get(&mut db, id).await?.is_some();
+    if !has_password {
+        return Err(Status::BadRequest.into());
+    }
+    let token = PasswordReset::create(&mut db, id).await?;
+    let emailed = PasswordReset::email(&target_user, &token, email_config, templates)
+        .await
+        .unwrap_or_else(|why| {
+            error!("Couldn't email password reset to user {id}: {:?}", why);
+            false
+        });
+    let reset_token = Some(token);
+    let ctx = context_with_base_authed!(user, target_user, has_password, reset_token, emailed);
+    Ok(Template::render("admin/password_reset", ctx))
+}
+
 #[get("/users/<id>/delete")]
 pub async fn delete_user_get(
     id: i64,
@@ -0,0 +1,24 @@
+use rocket::get;
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::User, context_with_base, db::DbConnection, error::prelude::*,
+    problems::ProblemFilter,
+};
+
+use super::ArchivedProblem;
+
+#[get("/?<filter..>")]
+pub async fn archive_list(
+    user: Option<&User>,
+    filter: ProblemFilter,
+    mut db: DbConnection,
+) -> ResultResponse<Template> {
+    let mut problems = ArchivedProblem::list(&mut db).await?;
+    let tags = ProblemFilter::all_tags(&problems);
+    filter.apply(&mut problems);
+    Ok(Template::render(
+        "archive",
+        context_with_base!(user, problems, tags, filter),
+    ))
+}
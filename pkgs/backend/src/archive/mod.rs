@@ -0,0 +1,139 @@
+use chrono::NaiveDateTime;
+use rocket::{fairing::AdHoc, http::Status, routes};
+
+use crate::{
+    auth::users::{Admin, User},
+    contests::{Contest, Participant},
+    db::DbPoolConnection,
+    error::prelude::*,
+    problems::{Problem, ProblemDifficulty, Tagged},
+};
+
+mod list;
+mod publish;
+mod view;
+
+/// A problem from an ended contest that anyone can keep solving for practice.
+/// Runs on archived problems are judged and saved like any other but never touch completions or leaderboards.
+#[derive(Serialize)]
+pub struct ArchivedProblem {
+    pub problem_id: i64,
+    pub problem_name: String,
+    pub contest_id: i64,
+    pub contest_name: String,
+    pub tags: String,
+    pub difficulty: ProblemDifficulty,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub published_at: NaiveDateTime,
+}
+
+impl ArchivedProblem {
+    /// Every archived problem whose contest has ended and isn't private
+    pub async fn list(db: &mut DbPoolConnection) -> Result<Vec<Self>> {
+        let now = chrono::offset::Utc::now().naive_utc();
+        sqlx::query_as!(
+            ArchivedProblem,
+            "SELECT archived_problem.problem_id, problem.name AS problem_name, contest.id AS contest_id, contest.name AS contest_name, problem.tags, problem.difficulty, archived_problem.published_at
+            FROM archived_problem JOIN problem ON archived_problem.problem_id = problem.id JOIN contest ON problem.contest_id = contest.id
+            WHERE contest.end_time < ? AND contest.visibility != 'Private'
+            ORDER BY archived_problem.published_at DESC",
+            now
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to list archived problems")
+    }
+
+    pub async fn is_published(db: &mut DbPoolConnection, problem_id: i64) -> Result<bool> {
+        sqlx::query!(
+            "SELECT problem_id FROM archived_problem WHERE problem_id = ?",
+            problem_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .map(|r| r.is_some())
+        .with_context(|| format!("Failed to check if problem {problem_id} is archived"))
+    }
+
+    pub async fn publish(db: &mut DbPoolConnection, problem_id: i64) -> Result {
+        sqlx::query!(
+            "INSERT INTO archived_problem (problem_id) VALUES (?) ON CONFLICT (problem_id) DO NOTHING",
+            problem_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to archive problem {problem_id}"))
+    }
+
+    pub async fn unpublish(db: &mut DbPoolConnection, problem_id: i64) -> Result {
+        sqlx::query!(
+            "DELETE FROM archived_problem WHERE problem_id = ?",
+            problem_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to remove problem {problem_id} from the archive"))
+    }
+
+    /// Get an archived problem and its contest, 404ing if it isn't published,
+    /// its contest hasn't ended yet, or the contest is private and the user isn't in it
+    pub async fn get_or_404(
+        db: &mut DbPoolConnection,
+        problem_id: i64,
+        user: Option<&User>,
+        admin: Option<&Admin>,
+    ) -> ResultResponse<(Problem, Contest)> {
+        let contest_id = sqlx::query!(
+            "SELECT problem.contest_id FROM archived_problem JOIN problem ON archived_problem.problem_id = problem.id WHERE archived_problem.problem_id = ?",
+            problem_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get archived problem {problem_id}"))?
+        .ok_or(Status::NotFound)?
+        .contest_id;
+        let contest = Contest::get_or_404(db, contest_id).await?;
+        let participant = if let Some(user) = user {
+            Participant::get(db, contest_id, user.id).await?
+        } else {
+            None
+        };
+        if !contest.has_ended() || !contest.is_visible_to(participant.as_ref(), admin) {
+            return Err(Status::NotFound.into());
+        }
+        let problem = Problem::by_id(db, contest_id, problem_id)
+            .await?
+            .ok_or(Status::NotFound)?;
+        Ok((problem, contest))
+    }
+}
+
+impl Tagged for ArchivedProblem {
+    fn name(&self) -> &str {
+        &self.problem_name
+    }
+
+    fn tags(&self) -> &str {
+        &self.tags
+    }
+
+    fn difficulty(&self) -> ProblemDifficulty {
+        self.difficulty
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Problem Archive", |rocket| async {
+        rocket
+            .mount(
+                "/archive",
+                routes![list::archive_list, view::view_archived_problem],
+            )
+            .mount(
+                "/contests",
+                routes![publish::publish_problem, publish::unpublish_problem],
+            )
+    })
+}
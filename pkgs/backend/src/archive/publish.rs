@@ -0,0 +1,56 @@
+use rocket::post;
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        users::{Admin, User},
+    },
+    contests::Contest,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    problems::Problem,
+};
+
+use super::ArchivedProblem;
+
+#[post("/<contest_id>/problems/<slug>/archive")]
+pub async fn publish_problem(
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    slug: &str,
+    _token: &VerifyCsrfToken,
+    mut db: DbConnection,
+) -> FormResponse {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let problem_url = format!("/contests/{contest_id}/problems/{slug}");
+    if !contest.has_ended() {
+        return Ok(
+            Message::error("Problems can only be archived once the contest has ended")
+                .to(&problem_url),
+        );
+    }
+    ArchivedProblem::publish(&mut db, problem.id).await?;
+    Ok(Message::success("Problem published to the practice archive").to(&problem_url))
+}
+
+#[post("/<contest_id>/problems/<slug>/unarchive")]
+pub async fn unpublish_problem(
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    slug: &str,
+    _token: &VerifyCsrfToken,
+    mut db: DbConnection,
+) -> FormResponse {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    ArchivedProblem::unpublish(&mut db, problem.id).await?;
+    Ok(
+        Message::success("Problem removed from the practice archive")
+            .to(&format!("/contests/{contest_id}/problems/{slug}")),
+    )
+}
@@ -0,0 +1,85 @@
+use rocket::{get, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base,
+    db::DbConnection,
+    error::prelude::*,
+    problems::{JudgeRun, StarterCode, TestCase},
+    run::CodeInfo,
+};
+
+use super::ArchivedProblem;
+
+#[get("/<problem_id>")]
+pub async fn view_archived_problem(
+    user: Option<&User>,
+    admin: Option<&Admin>,
+    info: &State<CodeInfo>,
+    mut db: DbConnection,
+    problem_id: i64,
+) -> ResultResponse<Template> {
+    let (problem, contest) = ArchivedProblem::get_or_404(&mut db, problem_id, user, admin).await?;
+
+    let case_count = TestCase::count_for_problem(&mut db, problem.id)
+        .await
+        .unwrap_or(0);
+
+    let samples = TestCase::get_samples_for_problem(&mut db, problem.id).await?;
+
+    let last_run = if let Some(user) = user {
+        JudgeRun::get_latest(&mut db, user.id, problem.id).await?
+    } else {
+        None
+    };
+
+    let most_recent_code = serde_json::to_string(
+        &last_run
+            .as_ref()
+            .map(|lr| (lr.program.as_str(), lr.language.as_str())),
+    )
+    .context("Failed to serialize most recent code")?;
+
+    // Don't show runs when test cases have changed
+    let last_run = last_run.filter(|r| r.total_cases == case_count);
+
+    let mut languages = info.run_config.get_languages_for_dropdown();
+    languages.retain(|(key, _)| problem.allows_language(key));
+    let starters = StarterCode::list_for_problem(&mut db, problem.id).await?;
+    let code_info = StarterCode::code_info(&info.languages_json, &starters)?;
+    let default_language = user
+        .map(|u| &u.default_language)
+        .filter(|l| info.run_config.languages.contains_key(*l))
+        .into_iter()
+        .chain([&info.run_config.default_language])
+        .find(|l| problem.allows_language(l))
+        .or_else(|| languages.first().map(|(key, _)| *key))
+        .unwrap_or(&info.run_config.default_language);
+    let language_limits = info
+        .run_config
+        .limits_per_language((problem.cpu_time as u64, problem.memory_limit as u64)); // `as` is safe due to DB constraint
+
+    Ok(Template::render(
+        "problems/view",
+        context_with_base!(
+            user,
+            problem,
+            last_run,
+            case_count,
+            samples,
+            most_recent_code,
+            ended: true,
+            contest,
+            code_info,
+            languages,
+            language_limits,
+            default_language,
+            can_edit: false,
+            is_admin: false,
+            participating: false,
+            practice: true,
+            archived: true,
+        ),
+    ))
+}
@@ -0,0 +1,217 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::NaiveDateTime;
+use log::warn;
+use rand::{distr::Alphanumeric, Rng};
+use rocket::{
+    fairing::AdHoc,
+    http::{Cookie, CookieJar, SameSite, Status},
+    request::{self, FromRequest},
+    time::Duration,
+    FromForm, Request, State,
+};
+use serde::Serialize;
+
+fn default_difficulty() -> u32 {
+    16
+}
+
+/// Lightweight proof-of-work challenge used to slow down scripted joins / registrations.
+/// The client has to find a `solution` such that `sha256("{challenge}:{solution}")`
+/// starts with at least `difficulty` zero bits.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProofOfWorkConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_difficulty")]
+    pub difficulty: u32,
+}
+
+impl Default for ProofOfWorkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            difficulty: default_difficulty(),
+        }
+    }
+}
+
+/// The challenge in the user's cookie, pass this to the template as `pow`.
+/// `challenge` is `None` when proof-of-work is disabled.
+#[derive(Debug, Clone, Serialize)]
+pub struct Challenge {
+    challenge: Option<String>,
+    difficulty: u32,
+}
+
+impl Challenge {
+    const COOKIE_NAME: &'static str = "pow_challenge";
+    const LENGTH: usize = 32;
+    const LIFETIME_MINUTES: i64 = 15;
+    /// A challenge the browser already has is only handed out again if it has at least this
+    /// long left, so there's still time to solve it and submit the form
+    const REUSE_MINUTES: i64 = 5;
+
+    fn generate() -> String {
+        rand::rng()
+            .sample_iter(&Alphanumeric)
+            .take(Self::LENGTH)
+            .map(char::from)
+            .collect()
+    }
+
+    /// The cookie holds when the challenge was issued alongside it, so it can't be kept
+    /// around past its lifetime by ignoring the cookie's expiry
+    fn cookie_value(challenge: &str, issued_at: NaiveDateTime) -> String {
+        format!("{}:{challenge}", issued_at.and_utc().timestamp())
+    }
+
+    fn parse_cookie(value: &str) -> Option<(&str, NaiveDateTime)> {
+        let (issued_at, challenge) = value.split_once(':')?;
+        let issued_at = chrono::DateTime::from_timestamp(issued_at.parse().ok()?, 0)?;
+        Some((challenge, issued_at.naive_utc()))
+    }
+}
+
+/// Challenges that have already been answered, kept until they'd have expired anyway so a
+/// solved challenge can't be replayed while its cookie is still around
+#[derive(Default)]
+pub struct SpentChallenges(Mutex<HashMap<String, NaiveDateTime>>);
+
+impl SpentChallenges {
+    /// Mark a challenge as used, returns false if it already was
+    fn spend(&self, challenge: &str, expires_at: NaiveDateTime) -> bool {
+        let now = chrono::offset::Utc::now().naive_utc();
+        let mut spent = self.0.lock().unwrap();
+        spent.retain(|_, expires_at| *expires_at > now);
+        spent.insert(challenge.to_string(), expires_at).is_none()
+    }
+
+    fn is_spent(&self, challenge: &str) -> bool {
+        self.0.lock().unwrap().contains_key(challenge)
+    }
+}
+
+fn leading_zero_bits(hex_digest: &str) -> u32 {
+    let mut bits = 0;
+    for c in hex_digest.chars() {
+        match c.to_digit(16) {
+            Some(0) => bits += 4,
+            Some(v) => return bits + (v.leading_zeros() - 28),
+            None => return bits,
+        }
+    }
+    bits
+}
+
+/// Check a submitted solution against the challenge in the user's cookies.
+/// The challenge is single-use, so it's removed whether or not the solution is valid, and
+/// remembered until it expires so the same cookie can't be sent again.
+pub fn verify(
+    config: &ProofOfWorkConfig,
+    spent: &SpentChallenges,
+    cookies: &CookieJar<'_>,
+    solution: Option<&str>,
+) -> bool {
+    if !config.enabled {
+        return true;
+    }
+
+    let cookie = cookies
+        .get_private(Challenge::COOKIE_NAME)
+        .map(|c| c.value().to_string());
+    cookies.remove_private(Challenge::COOKIE_NAME);
+
+    let Some((challenge, issued_at)) = cookie.as_deref().and_then(Challenge::parse_cookie) else {
+        return false;
+    };
+    let expires_at = issued_at + chrono::Duration::minutes(Challenge::LIFETIME_MINUTES);
+    if expires_at <= chrono::offset::Utc::now().naive_utc() || !spent.spend(challenge, expires_at) {
+        return false;
+    }
+
+    match solution {
+        Some(solution) if !solution.is_empty() && solution.len() <= 32 => {
+            let digest = sha256::digest(format!("{challenge}:{solution}"));
+            leading_zero_bits(&digest) >= config.difficulty
+        }
+        _ => false,
+    }
+}
+
+#[derive(FromForm)]
+pub struct ChallengeForm<'r> {
+    pub pow_solution: Option<&'r str>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Challenge {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let config = match req.guard::<&State<ProofOfWorkConfig>>().await.succeeded() {
+            Some(config) => config,
+            None => return request::Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        if !config.enabled {
+            return request::Outcome::Success(Challenge {
+                challenge: None,
+                difficulty: config.difficulty,
+            });
+        }
+
+        // Hand out the challenge the browser already has while it's still good, otherwise opening
+        // the form in a second tab would replace the cookie the first tab's form was solved for
+        let now = chrono::offset::Utc::now().naive_utc();
+        let spent = req.guard::<&State<SpentChallenges>>().await.succeeded();
+        let current = req
+            .cookies()
+            .get_private(Challenge::COOKIE_NAME)
+            .and_then(|cookie| {
+                let (challenge, issued_at) = Challenge::parse_cookie(cookie.value())?;
+                let remaining =
+                    issued_at + chrono::Duration::minutes(Challenge::LIFETIME_MINUTES) - now;
+                let usable = remaining >= chrono::Duration::minutes(Challenge::REUSE_MINUTES)
+                    && spent.is_some_and(|spent| !spent.is_spent(challenge));
+                usable.then(|| challenge.to_string())
+            });
+        if let Some(challenge) = current {
+            return request::Outcome::Success(Challenge {
+                challenge: Some(challenge),
+                difficulty: config.difficulty,
+            });
+        }
+
+        let challenge = Challenge::generate();
+        req.cookies().add_private(
+            Cookie::build((
+                Challenge::COOKIE_NAME,
+                Challenge::cookie_value(&challenge, now),
+            ))
+            .same_site(SameSite::Strict)
+            .max_age(Duration::minutes(Challenge::LIFETIME_MINUTES)),
+        );
+
+        request::Outcome::Success(Challenge {
+            challenge: Some(challenge),
+            difficulty: config.difficulty,
+        })
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Proof of Work", |rocket| async {
+        let config = rocket
+            .figment()
+            .extract_inner::<ProofOfWorkConfig>("proof_of_work")
+            .unwrap_or_default();
+        if config.enabled && config.difficulty > 24 {
+            warn!(
+                "Proof of work difficulty is set to {}, this may take users a very long time to solve",
+                config.difficulty
+            );
+        }
+        rocket.manage(config).manage(SpentChallenges::default())
+    })
+}
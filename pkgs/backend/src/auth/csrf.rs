@@ -13,14 +13,20 @@ use rocket::{
 };
 use serde::Serialize;
 
-use crate::{auth::sessions::Session, error::prelude::*};
+use crate::{
+    auth::{
+        impersonation::{self, Impersonation},
+        sessions::Session,
+    },
+    error::prelude::*,
+};
 
 #[derive(Debug)]
 pub struct CsrfTokens {
     tokens: HashMap<CsrfToken, (Option<String>, OffsetDateTime)>,
 }
 
-type ArCsrfTokens = Arc<Mutex<CsrfTokens>>;
+pub type ArCsrfTokens = Arc<Mutex<CsrfTokens>>;
 
 impl CsrfTokens {
     const LENGTH_FOR_PRUNE: usize = 100;
@@ -51,11 +57,14 @@ impl CsrfTokens {
             .retain(|_, (token_session, _)| token_session.as_deref() != Some(session_token));
     }
 
-    fn prune(&mut self) {
+    /// Remove all expired tokens, returning how many were removed
+    pub fn prune(&mut self) -> usize {
+        let before = self.tokens.len();
         let now = OffsetDateTime::now_utc();
         self.tokens.retain(|_, (_, time_set)| {
             now < *time_set + Duration::minutes(CsrfToken::TOKEN_COOKIE_LIFETIME_MINUTES)
         });
+        before - self.tokens.len()
     }
 
     pub fn validate(
@@ -165,6 +174,16 @@ impl<'r> FromRequest<'r> for &'r VerifyCsrfToken {
     async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
         let token_result = req
             .local_cache_async(async {
+                // Viewing as someone else is read-only, the only thing that can be done is stopping
+                let impersonating = req
+                    .guard::<Option<&Impersonation>>()
+                    .await
+                    .succeeded()
+                    .flatten()
+                    .is_some();
+                if impersonating && req.uri().path() != impersonation::STOP_URI {
+                    return None;
+                }
                 let cookies = req.guard::<&CookieJar<'_>>().await.succeeded()?;
                 let tokens = req.guard::<&State<ArCsrfTokens>>().await.succeeded()?;
                 let session_token = cookies
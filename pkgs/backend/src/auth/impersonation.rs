@@ -0,0 +1,185 @@
+use chrono::NaiveDateTime;
+use log::{error, info};
+use rocket::{
+    fairing::AdHoc,
+    http::{Cookie, CookieJar, SameSite, Status},
+    outcome::IntoOutcome,
+    request::{self, FromRequest},
+    Request,
+};
+
+use crate::{
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+};
+
+use super::{sessions::Session, users::User};
+
+/// Plain cookie with the name of whoever is being viewed as, read by the page to show the banner
+pub const BANNER_COOKIE_NAME: &str = "impersonating";
+/// The only change an admin can make while viewing as someone else is going back to themselves
+pub const STOP_URI: &str = "/admin/impersonate/stop";
+
+/// An admin viewing the site as another user. The [`User`] guard gives the target user while
+/// this is active, so pages look exactly like they do for them, but everything is read-only
+/// and every request is recorded in the [`ImpersonationLog`].
+pub struct Impersonation {
+    pub session: Session,
+    pub admin: User,
+    pub target: User,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImpersonationLog {
+    pub id: i64,
+    pub admin_id: i64,
+    pub target_id: i64,
+    pub action: String,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+}
+
+impl ImpersonationLog {
+    pub async fn record(
+        db: &mut DbPoolConnection,
+        admin_id: i64,
+        target_id: i64,
+        action: &str,
+    ) -> Result {
+        sqlx::query!(
+            "INSERT INTO impersonation_log (admin_id, target_id, action) VALUES (?, ?, ?)",
+            admin_id,
+            target_id,
+            action
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .context("Couldn't record impersonation")
+    }
+
+    /// Most recent entries first
+    pub async fn list(db: &mut DbPoolConnection, limit: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            ImpersonationLog,
+            "SELECT * FROM impersonation_log ORDER BY created_at DESC, id DESC LIMIT ?",
+            limit
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Couldn't list impersonation log")
+    }
+}
+
+impl Impersonation {
+    pub fn banner_cookie(target: &User) -> Cookie<'static> {
+        Cookie::build((
+            BANNER_COOKIE_NAME,
+            urlencoding::encode(target.display_name()).to_string(),
+        ))
+        .path("/")
+        .same_site(SameSite::Strict)
+        .build()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r Impersonation {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let result = req
+            .local_cache_async(async {
+                let token = req
+                    .cookies()
+                    .get_private(Session::TOKEN_COOKIE_NAME)
+                    .map(|c| c.value().to_string())?;
+                let mut db = req.guard::<DbConnection>().await.succeeded()?;
+                let found = async {
+                    let Some(session) = Session::from_token(&mut db, &token).await? else {
+                        return Ok(None);
+                    };
+                    let Some(target_id) = session.impersonating_id else {
+                        return Ok(None);
+                    };
+                    let admin = User::get(&mut db, session.user_id).await?;
+                    let target = User::get(&mut db, target_id).await?;
+                    Ok::<_, anyhow::Error>(admin.zip(target).map(|(admin, target)| Impersonation {
+                        session,
+                        admin,
+                        target,
+                    }))
+                };
+                found.await.unwrap_or_else(|why| {
+                    error!("Couldn't check for impersonation: {:?}", why);
+                    None
+                })
+            })
+            .await;
+        result
+            .as_ref()
+            .or_forward(rocket::http::Status::Unauthorized)
+    }
+}
+
+/// Guard for changes that don't go through [`super::csrf::VerifyCsrfToken`], fails with a 403
+/// while viewing as someone else
+pub struct NotImpersonating;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for NotImpersonating {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match req
+            .guard::<Option<&Impersonation>>()
+            .await
+            .succeeded()
+            .flatten()
+        {
+            Some(_) => request::Outcome::Error((Status::Forbidden, ())),
+            None => request::Outcome::Success(NotImpersonating),
+        }
+    }
+}
+
+/// Audit every request made while viewing as someone else and keep the banner cookie in sync
+/// with the session, so it goes away if the session expires mid-impersonation
+pub fn stage() -> AdHoc {
+    AdHoc::on_request("Impersonation Audit", |req, _| {
+        Box::pin(async move {
+            let impersonation = req
+                .guard::<Option<&Impersonation>>()
+                .await
+                .succeeded()
+                .flatten();
+            let cookies = req.guard::<&CookieJar<'_>>().await.succeeded();
+            match (impersonation, cookies) {
+                (Some(impersonation), Some(cookies)) => {
+                    cookies.add(Impersonation::banner_cookie(&impersonation.target));
+                    let action = format!("{} {}", req.method(), req.uri());
+                    info!(
+                        "Admin {} as user {}: {}",
+                        impersonation.admin.id, impersonation.target.id, action
+                    );
+                    if let Some(mut db) = req.guard::<DbConnection>().await.succeeded() {
+                        if let Err(why) = ImpersonationLog::record(
+                            &mut db,
+                            impersonation.admin.id,
+                            impersonation.target.id,
+                            &action,
+                        )
+                        .await
+                        {
+                            error!("{:?}", why);
+                        }
+                    }
+                }
+                (None, Some(cookies)) if cookies.get(BANNER_COOKIE_NAME).is_some() => {
+                    cookies.remove(Cookie::build(BANNER_COOKIE_NAME).path("/"));
+                }
+                _ => {}
+            }
+        })
+    })
+}
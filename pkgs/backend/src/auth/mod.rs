@@ -9,7 +9,7 @@ use rocket::{
     response::Redirect,
     routes,
     time::Duration,
-    Request,
+    Request, State,
 };
 use rocket_dyn_templates::Template;
 use sqlx::sqlite::SqliteQueryResult;
@@ -17,24 +17,36 @@ use sqlx::sqlite::SqliteQueryResult;
 use crate::{
     context_with_base,
     db::{DbConnection, DbPoolConnection},
+    email::{EmailConfig, EmailKind},
     error::prelude::*,
     messages::Message,
+    rate_limit::{self, RateLimited},
     ResultResponse,
 };
 
 use self::{
+    csrf::CsrfToken,
+    onsite::OnsiteAccount,
+    password::PasswordAuthConfig,
     sessions::Session,
+    two_factor::LoginFlow,
     users::{AdminUsers, User},
 };
 
 mod github;
 mod google;
+mod register;
 mod saml;
 
 pub use saml::{SamlOptions, PREFERRED_SSO_BINDING};
 
+pub mod challenge;
 pub mod csrf;
+pub mod impersonation;
+pub mod onsite;
+pub mod password;
 pub mod sessions;
+pub mod two_factor;
 pub mod users;
 
 const LOGIN_URI: &str = "/auth/login";
@@ -53,7 +65,15 @@ async fn unauthorized(req: &Request<'_>) -> Redirect {
 const REDIRECT_COOKIE_NAME: &str = "redirect_after_auth";
 
 #[get("/login?<redirect>")]
-async fn login(user: Option<&User>, redirect: Option<&str>, cookies: &CookieJar<'_>) -> Template {
+async fn login(
+    user: Option<&User>,
+    redirect: Option<&str>,
+    cookies: &CookieJar<'_>,
+    password_auth: &State<PasswordAuthConfig>,
+    email_config: &State<EmailConfig>,
+    _limit: RateLimited<rate_limit::Login>,
+    _token: &CsrfToken,
+) -> Template {
     if let Some(redirect) = redirect {
         let mut cookie = Cookie::new(REDIRECT_COOKIE_NAME, redirect.to_string());
         cookie.set_same_site(SameSite::Lax);
@@ -61,7 +81,9 @@ async fn login(user: Option<&User>, redirect: Option<&str>, cookies: &CookieJar<
         cookie.set_max_age(Duration::minutes(5));
         cookies.add(cookie);
     }
-    let ctx = context_with_base!(user,);
+    let password_auth = password_auth.inner();
+    let forgot_password = email_config.can_send(EmailKind::PasswordReset);
+    let ctx = context_with_base!(user, password_auth, forgot_password);
     Template::render("auth/login", ctx)
 }
 
@@ -101,8 +123,22 @@ pub fn stage() -> AdHoc {
             .attach(github::stage())
             .attach(google::stage())
             .attach(csrf::stage())
+            .attach(challenge::stage())
+            .attach(password::stage())
+            .attach(onsite::stage())
+            .attach(two_factor::stage())
+            .attach(sessions::stage())
+            .attach(impersonation::stage())
             .register("/", catchers![unauthorized])
-            .mount("/auth", routes![login, logout,])
+            .mount(
+                "/auth",
+                routes![
+                    login,
+                    logout,
+                    register::register_get,
+                    register::register_post
+                ],
+            )
     })
 }
 
@@ -150,6 +186,7 @@ pub trait CallbackHandler {
         &self,
         user: Option<&User>,
         cookies: &CookieJar<'_>,
+        login: &LoginFlow<'_>,
         db: &mut DbPoolConnection,
     ) -> ResultResponse<Redirect> {
         let state = cookies
@@ -166,7 +203,7 @@ pub trait CallbackHandler {
         cookies.remove(Cookie::from(STATE_COOKIE_NAME));
 
         let redirect = if state == LOGIN_STATE {
-            self.handle_login_callback(db, cookies).await
+            self.handle_login_callback(db, cookies, login).await
         } else if state == LINK_STATE && user.is_some() {
             self.handle_link_callback(db, user.unwrap()).await
         } else {
@@ -181,19 +218,25 @@ pub trait CallbackHandler {
         db: &mut DbPoolConnection,
         user: &User,
     ) -> Result<Result<Redirect, Status>> {
+        if OnsiteAccount::is_onsite(db, user.id).await? {
+            return Ok(Ok(Message::error(
+                "On-site accounts can't be linked to {service}",
+            )
+            .arg("service", Self::SERVICE_NAME)
+            .to("/settings/account")));
+        }
         let user_info = self.fetch_user_info().await?;
         self.link_to(db, user, user_info).await.map(|linked| {
             if linked {
+                Ok(Message::success("Linked your account to {service}")
+                    .arg("service", Self::SERVICE_NAME)
+                    .to("/settings/account"))
+            } else {
                 Ok(
-                    Message::success(&format!("Linked your account to {}", Self::SERVICE_NAME))
+                    Message::error("This {service} account is already linked to another account")
+                        .arg("service", Self::SERVICE_NAME)
                         .to("/settings/account"),
                 )
-            } else {
-                Ok(Message::error(&format!(
-                    "This {} account is already linked to another account",
-                    Self::SERVICE_NAME
-                ))
-                .to("/settings/account"))
             }
         })
     }
@@ -202,6 +245,7 @@ pub trait CallbackHandler {
         &self,
         db: &mut DbPoolConnection,
         cookies: &CookieJar<'_>,
+        login: &LoginFlow<'_>,
     ) -> Result<Result<Redirect, Status>> {
         let user_info = self.fetch_user_info().await?;
 
@@ -212,6 +256,16 @@ pub trait CallbackHandler {
             .await
             .with_context(|| format!("Failed to get user info from {}", Self::SERVICE_NAME))?;
 
+        if let Some(user) = user.as_ref() {
+            let next_step = login
+                .login_sso(db_conn, user)
+                .await
+                .with_context(|| format!("Failed to login user from {}", Self::SERVICE_NAME))?;
+            if let Some(next_step) = next_step {
+                return Ok(Ok(next_step));
+            }
+        }
+
         let redirect = cookies
             .get(REDIRECT_COOKIE_NAME)
             .map(|c| c.value().to_string())
@@ -219,16 +273,13 @@ pub trait CallbackHandler {
 
         cookies.remove(Cookie::from(REDIRECT_COOKIE_NAME));
 
-        if let Some(user) = user {
-            user.login(db_conn, cookies)
-                .await
-                .with_context(|| format!("Failed to login user from {}", Self::SERVICE_NAME))?;
+        if user.is_some() {
             Ok(Ok(Redirect::to(redirect)))
         } else {
-            Ok(Ok(Message::error(&format!(
-                "No account found for this {} account",
-                Self::SERVICE_NAME
-            ))
+            Ok(Ok(Message::error(
+                "No account found for this {service} account",
+            )
+            .arg("service", Self::SERVICE_NAME)
             .to_with_params(
                 LOGIN_URI,
                 vec![(REDIRECT_COOKIE_NAME, &redirect)],
@@ -238,11 +289,9 @@ pub trait CallbackHandler {
 
     async fn handle_unlink(db: &mut DbPoolConnection, user: &User) -> ResultResponse<Redirect> {
         Self::unlink(db, user).await?;
-        Ok(Message::success(&format!(
-            "Unlinked your account from {}",
-            Self::SERVICE_NAME
-        ))
-        .to("/settings/account"))
+        Ok(Message::success("Unlinked your account from {service}")
+            .arg("service", Self::SERVICE_NAME)
+            .to("/settings/account"))
     }
 
     async fn fetch_user_info(&self) -> Result<Self::IntermediateUserInfo> {
@@ -277,10 +326,11 @@ mod prelude {
     pub use sqlx::sqlite::SqliteQueryResult;
 
     pub use crate::{
-        auth::users::User,
+        auth::{two_factor::LoginFlow, users::User},
         db::{DbConnection, DbPoolConnection},
         error::prelude::*,
         oauth_fairing,
+        rate_limit::RateLimited,
     };
 }
 
@@ -288,7 +338,11 @@ mod prelude {
 macro_rules! oauth_fairing {
     ($name: literal, $route: ident, $handler: ident, $scopes: expr) => {
         #[get("/login")]
-        fn login(oauth2: OAuth2<$handler>, cookies: &CookieJar<'_>) -> ResultResponse<Redirect> {
+        fn login(
+            oauth2: OAuth2<$handler>,
+            cookies: &CookieJar<'_>,
+            _limit: RateLimited<$crate::rate_limit::Login>,
+        ) -> ResultResponse<Redirect> {
             $handler::put_login_cookie(cookies);
             let redirect = oauth2.get_redirect(cookies, &$scopes).context(concat!(
                 "Error getting ",
@@ -319,9 +373,12 @@ macro_rules! oauth_fairing {
             token: TokenResponse<$handler>,
             user: Option<&User>,
             cookies: &CookieJar<'_>,
+            login: LoginFlow<'_>,
         ) -> ResultResponse<Redirect> {
             let handler = $handler(token.access_token().to_string());
-            handler.handle_callback(user, cookies, &mut db).await
+            handler
+                .handle_callback(user, cookies, &login, &mut db)
+                .await
         }
 
         #[get("/unlink")]
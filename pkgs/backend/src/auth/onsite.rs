@@ -0,0 +1,200 @@
+//! On-site mode for contests held in a lab, like ICPC regionals. Admins generate an account for
+//! each team and hand out printed credentials, those accounts can only log in with their
+//! password from the venue's network and can't be linked to SSO.
+
+use std::net::IpAddr;
+
+use chrono::NaiveDateTime;
+use rand::Rng;
+use rocket::fairing::AdHoc;
+
+use crate::{db::DbPoolConnection, error::prelude::*};
+
+use super::{password, users::User};
+
+/// A CIDR range like `10.20.0.0/16`, or a single address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct IpRange {
+    network: IpAddr,
+    prefix: u8,
+}
+
+/// An address as bits, with how many of them there are
+fn ip_bits(ip: IpAddr) -> (u128, u8) {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => (u32::from(ip) as u128, 32),
+        IpAddr::V6(ip) => (u128::from(ip), 128),
+    }
+}
+
+impl IpRange {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (network, prefix) = match s.trim().split_once('/') {
+            Some((network, prefix)) => {
+                (network.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?))
+            }
+            None => (s.trim().parse::<IpAddr>().ok()?, None),
+        };
+        let (_, width) = ip_bits(network);
+        let prefix = prefix.unwrap_or(width);
+        (prefix <= width).then_some(Self { network, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let (network, width) = ip_bits(self.network);
+        let (ip, ip_width) = ip_bits(ip);
+        if width != ip_width {
+            return false;
+        }
+        let shift = (width - self.prefix) as u32;
+        network.checked_shr(shift).unwrap_or(0) == ip.checked_shr(shift).unwrap_or(0)
+    }
+}
+
+impl TryFrom<String> for IpRange {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::parse(&s).ok_or_else(|| format!("Invalid IP range {s}"))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OnsiteConfig {
+    /// Where on-site accounts can log in from, when empty they can log in from anywhere
+    #[serde(default)]
+    pub allowed_ips: Vec<IpRange>,
+}
+
+impl OnsiteConfig {
+    pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+        self.allowed_ips.is_empty()
+            || ip.is_some_and(|ip| self.allowed_ips.iter().any(|r| r.contains(ip)))
+    }
+}
+
+/// Characters in generated passwords, leaving out ones that are easy to mix up on paper
+const PASSWORD_CHARS: &[u8] = b"abcdefghjkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const PASSWORD_LENGTH: usize = 10;
+
+pub fn gen_password() -> String {
+    let mut rng = rand::rng();
+    (0..PASSWORD_LENGTH)
+        .map(|_| PASSWORD_CHARS[rng.random_range(0..PASSWORD_CHARS.len())] as char)
+        .collect()
+}
+
+/// What goes on a team's credential sheet, the password is only known until the sheet is shown
+#[derive(Serialize, Debug, Clone)]
+pub struct Credentials {
+    pub name: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct OnsiteAccount {
+    pub user_id: i64,
+    pub contest_id: Option<i64>,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+}
+
+impl OnsiteAccount {
+    pub async fn get(db: &mut DbPoolConnection, user_id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            OnsiteAccount,
+            "SELECT * FROM onsite_account WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't fetch on-site account of user {user_id}"))
+    }
+
+    pub async fn is_onsite(db: &mut DbPoolConnection, user_id: i64) -> Result<bool> {
+        Ok(Self::get(db, user_id).await?.is_some())
+    }
+
+    /// Every on-site account, or only the ones generated for a contest
+    pub async fn list_users(
+        db: &mut DbPoolConnection,
+        contest_id: Option<i64>,
+    ) -> Result<Vec<User>> {
+        sqlx::query_as!(
+            User,
+            "SELECT user.* FROM user JOIN onsite_account ON onsite_account.user_id = user.id WHERE ? IS NULL OR onsite_account.contest_id = ? ORDER BY user.email",
+            contest_id,
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Couldn't list on-site accounts")
+    }
+
+    /// Make a local account with a generated username and password, the number in the
+    /// username is the first one after `prefix` that isn't taken
+    pub async fn generate(
+        db: &mut DbPoolConnection,
+        prefix: &str,
+        name: &str,
+        contest_id: Option<i64>,
+        default_language: &str,
+    ) -> Result<(User, Credentials)> {
+        let password = gen_password();
+        let mut n = 0;
+        loop {
+            n += 1;
+            let username = format!("{prefix}{n:03}");
+            // They keep the password on their sheet, so they aren't asked to change it
+            let Some(user) = password::create_local_user(
+                db,
+                &username,
+                name,
+                &password,
+                false,
+                default_language,
+            )
+            .await?
+            else {
+                continue;
+            };
+            sqlx::query!(
+                "INSERT INTO onsite_account (user_id, contest_id) VALUES (?, ?)",
+                user.id,
+                contest_id
+            )
+            .execute(&mut **db)
+            .await
+            .with_context(|| format!("Couldn't mark user {} as on-site", user.id))?;
+            let credentials = Credentials {
+                name: name.to_string(),
+                username,
+                password,
+            };
+            return Ok((user, credentials));
+        }
+    }
+
+    /// Give an on-site account a new password, for when the sheet gets lost
+    pub async fn reset_password(db: &mut DbPoolConnection, user: &User) -> Result<Credentials> {
+        let password = gen_password();
+        password::PasswordCredential::set(db, user.id, &password, false).await?;
+        Ok(Credentials {
+            name: user.display_name().to_string(),
+            username: user.email.clone(),
+            password,
+        })
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("On-site Accounts", |rocket| async {
+        let config = rocket
+            .figment()
+            .extract_inner::<OnsiteConfig>("onsite")
+            .unwrap_or_default();
+        rocket.manage(config)
+    })
+}
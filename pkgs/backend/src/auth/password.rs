@@ -0,0 +1,1033 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::NaiveDateTime;
+use log::{error, warn};
+use rand::{distr::Alphanumeric, Rng};
+use rocket::{
+    fairing::AdHoc,
+    form::Form,
+    get,
+    http::{Cookie, CookieJar, SameSite, Status},
+    post,
+    response::Redirect,
+    routes,
+    time::Duration,
+    FromForm, State,
+};
+use rocket_dyn_templates::Template;
+use tera::Context as TeraContext;
+
+use crate::{
+    context_with_base,
+    db::{DbConnection, DbPoolConnection},
+    email::{EmailConfig, EmailKind, EmailTemplates},
+    error::prelude::*,
+    messages::Message,
+    rate_limit::{self, RateLimited},
+    run::CodeInfo,
+};
+
+use super::{
+    challenge::{self, Challenge, ProofOfWorkConfig, SpentChallenges},
+    csrf::{CsrfToken, VerifyCsrfToken},
+    register::welcome_redirect,
+    sessions::Session,
+    two_factor::LoginFlow,
+    users::{AdminUsers, User},
+    LOGIN_URI, REDIRECT_COOKIE_NAME,
+};
+
+fn default_min_length() -> usize {
+    8
+}
+
+/// Local accounts for deployments that don't have SSO available.
+/// Disabled by default, leave it off where SAML / OAuth is mandated.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PasswordAuthConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Let anyone create a local account, otherwise only admins can create them.
+    /// New accounts have to verify their email first, so this needs `verify_email` emails.
+    #[serde(default)]
+    pub allow_registration: bool,
+    #[serde(default = "default_min_length")]
+    pub min_length: usize,
+}
+
+impl Default for PasswordAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allow_registration: false,
+            min_length: default_min_length(),
+        }
+    }
+}
+
+impl PasswordAuthConfig {
+    pub fn check_new_password(&self, password: &str, confirm: &str) -> Result<(), String> {
+        if password != confirm {
+            Err("Passwords don't match".to_string())
+        } else if password.chars().count() < self.min_length {
+            Err(format!(
+                "Passwords must be at least {} characters long",
+                self.min_length
+            ))
+        } else if password.len() > 1024 {
+            Err("Passwords can't be longer than 1024 characters".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// SSO IDs of local accounts, so they can never collide with a real SSO identity
+pub fn local_sso_id(email: &str) -> String {
+    format!("local:{email}")
+}
+
+const LINK_TOKEN_LENGTH: usize = 48;
+
+/// Token for a single-use link, returns the token to put in the link along with
+/// the hash to store and when the link stops working
+fn new_link_token(expiry_hours: i64) -> Result<(String, String, NaiveDateTime)> {
+    let token: String = rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(LINK_TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+    let hash = Session::hash_token(&token);
+    let expires_at = chrono::offset::Utc::now().naive_utc()
+        + chrono::TimeDelta::try_hours(expiry_hours).context("Invalid link expiry")?;
+    Ok((token, hash, expires_at))
+}
+
+/// Render and send one of the local account emails to the user,
+/// returns whether it was sent or that kind of email can't be sent
+async fn email_user(
+    user: &User,
+    kind: EmailKind,
+    mut ctx: TeraContext,
+    email_config: &EmailConfig,
+    templates: &EmailTemplates,
+) -> Result<bool> {
+    if !email_config.can_send(kind) {
+        return Ok(false);
+    }
+    ctx.insert("name", user.display_name());
+    let email = templates.render(kind, ctx)?;
+    email_config.send(kind, &user.email, &email).await?;
+    Ok(true)
+}
+
+/// Email an admin created user their temporary password, returns whether it was sent
+pub async fn email_temporary_password(
+    user: &User,
+    temporary_password: &str,
+    email_config: &EmailConfig,
+    templates: &EmailTemplates,
+) -> Result<bool> {
+    let mut ctx = TeraContext::new();
+    ctx.insert("email", &user.email);
+    ctx.insert("temporary_password", temporary_password);
+    ctx.insert("login_url", &format!("{}{LOGIN_URI}", templates.url()));
+    email_user(
+        user,
+        EmailKind::AccountCreated,
+        ctx,
+        email_config,
+        templates,
+    )
+    .await
+}
+
+async fn hash_password(password: String) -> Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>())
+            .map_err(|e| anyhow!("Couldn't encode salt: {e}"))?;
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|h| h.to_string())
+            .map_err(|e| anyhow!("Couldn't hash password: {e}"))
+    })
+    .await
+    .context("Password hashing task panicked")?
+}
+
+async fn verify_password(password: String, hash: String) -> Result<bool> {
+    tokio::task::spawn_blocking(move || {
+        let parsed = PasswordHash::new(&hash).map_err(|e| anyhow!("Invalid password hash: {e}"))?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok())
+    })
+    .await
+    .context("Password verification task panicked")?
+}
+
+pub struct PasswordCredential {
+    #[allow(dead_code)]
+    pub user_id: i64,
+    pub hash: String,
+    /// Set when an admin gives the user a temporary password
+    pub must_change: bool,
+    #[allow(dead_code)]
+    pub updated_at: NaiveDateTime,
+}
+
+impl PasswordCredential {
+    pub async fn get(db: &mut DbPoolConnection, user_id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            PasswordCredential,
+            "SELECT * FROM user_password WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't fetch password for user {user_id}"))
+    }
+
+    pub async fn set(
+        db: &mut DbPoolConnection,
+        user_id: i64,
+        password: &str,
+        must_change: bool,
+    ) -> Result {
+        let hash = hash_password(password.to_string()).await?;
+        let now = chrono::offset::Utc::now().naive_utc();
+        sqlx::query!(
+            "INSERT INTO user_password (user_id, hash, must_change, updated_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT(user_id) DO UPDATE SET hash = excluded.hash, must_change = excluded.must_change, updated_at = excluded.updated_at",
+            user_id,
+            hash,
+            must_change,
+            now
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't set password for user {user_id}"))?;
+        Ok(())
+    }
+
+    pub async fn verify(&self, password: &str) -> Result<bool> {
+        verify_password(password.to_string(), self.hash.clone()).await
+    }
+
+    async fn find_user(
+        db: &mut DbPoolConnection,
+        email: &str,
+    ) -> Result<Option<(User, PasswordCredential)>> {
+        let user = sqlx::query_as!(
+            User,
+            "SELECT user.* FROM user JOIN user_password ON user.id = user_password.user_id WHERE user.email = ?",
+            email
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't fetch local user with email {email}"))?;
+
+        if let Some(user) = user {
+            let credential = Self::get(db, user.id).await?;
+            Ok(credential.map(|c| (user, c)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+pub struct PasswordReset {
+    #[allow(dead_code)]
+    pub id: i64,
+    pub user_id: i64,
+    #[allow(dead_code)]
+    pub token: String,
+    #[allow(dead_code)]
+    pub created_at: NaiveDateTime,
+    #[allow(dead_code)]
+    pub expires_at: NaiveDateTime,
+}
+
+impl PasswordReset {
+    const EXPIRY_HOURS: i64 = 24;
+
+    /// Create a single-use reset token for the user, replacing any previous ones
+    pub async fn create(db: &mut DbPoolConnection, user_id: i64) -> Result<String> {
+        let (token, hash, expires) = new_link_token(Self::EXPIRY_HOURS)?;
+
+        Self::delete_for_user(db, user_id).await?;
+        sqlx::query!(
+            "INSERT INTO password_reset (user_id, token, expires_at) VALUES (?, ?, ?)",
+            user_id,
+            hash,
+            expires
+        )
+        .execute(&mut **db)
+        .await
+        .context("Couldn't insert password reset")?;
+
+        Ok(token)
+    }
+
+    async fn get(db: &mut DbPoolConnection, token: &str) -> Result<Option<Self>> {
+        let hash = Session::hash_token(token);
+        sqlx::query_as!(
+            PasswordReset,
+            "SELECT * FROM password_reset WHERE token = ? AND expires_at > CURRENT_TIMESTAMP",
+            hash
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .context("Couldn't fetch password reset")
+    }
+
+    /// Email the user a link made with [`PasswordReset::create`], returns whether it was sent
+    pub async fn email(
+        user: &User,
+        token: &str,
+        email_config: &EmailConfig,
+        templates: &EmailTemplates,
+    ) -> Result<bool> {
+        let mut ctx = TeraContext::new();
+        ctx.insert(
+            "reset_url",
+            &format!("{}/auth/password/reset/{token}", templates.url()),
+        );
+        ctx.insert("expires_hours", &Self::EXPIRY_HOURS);
+        email_user(user, EmailKind::PasswordReset, ctx, email_config, templates).await
+    }
+
+    async fn delete_for_user(db: &mut DbPoolConnection, user_id: i64) -> Result {
+        sqlx::query!("DELETE FROM password_reset WHERE user_id = ?", user_id)
+            .execute(&mut **db)
+            .await
+            .context("Couldn't delete password resets")?;
+        Ok(())
+    }
+}
+
+/// Link given to someone whose account was created for them, used once to pick a password.
+/// Lasts longer than a [`PasswordReset`] since it's usually handed out well before a contest.
+pub struct AccountClaim {
+    pub user_id: i64,
+    #[allow(dead_code)]
+    pub token: String,
+    #[allow(dead_code)]
+    pub created_at: NaiveDateTime,
+    #[allow(dead_code)]
+    pub expires_at: NaiveDateTime,
+}
+
+impl AccountClaim {
+    const EXPIRY_DAYS: i64 = 30;
+
+    /// Create the claim token for the user, replacing any previous one
+    pub async fn create(db: &mut DbPoolConnection, user_id: i64) -> Result<String> {
+        let (token, hash, expires) = new_link_token(Self::EXPIRY_DAYS * 24)?;
+
+        sqlx::query!(
+            "INSERT INTO account_claim (user_id, token, expires_at) VALUES (?, ?, ?)
+            ON CONFLICT(user_id) DO UPDATE SET token = excluded.token, created_at = CURRENT_TIMESTAMP, expires_at = excluded.expires_at",
+            user_id,
+            hash,
+            expires
+        )
+        .execute(&mut **db)
+        .await
+        .context("Couldn't insert account claim")?;
+
+        Ok(token)
+    }
+
+    async fn get(db: &mut DbPoolConnection, token: &str) -> Result<Option<Self>> {
+        let hash = Session::hash_token(token);
+        sqlx::query_as!(
+            AccountClaim,
+            "SELECT * FROM account_claim WHERE token = ? AND expires_at > CURRENT_TIMESTAMP",
+            hash
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .context("Couldn't fetch account claim")
+    }
+
+    async fn delete_for_user(db: &mut DbPoolConnection, user_id: i64) -> Result {
+        sqlx::query!("DELETE FROM account_claim WHERE user_id = ?", user_id)
+            .execute(&mut **db)
+            .await
+            .context("Couldn't delete account claim")?;
+        Ok(())
+    }
+
+    /// Email the user a link made with [`AccountClaim::create`], returns whether it was sent
+    pub async fn email(
+        user: &User,
+        token: &str,
+        email_config: &EmailConfig,
+        templates: &EmailTemplates,
+    ) -> Result<bool> {
+        let mut ctx = TeraContext::new();
+        ctx.insert("email", &user.email);
+        ctx.insert(
+            "claim_url",
+            &format!("{}/auth/password/claim/{token}", templates.url()),
+        );
+        ctx.insert("expires_days", &Self::EXPIRY_DAYS);
+        email_user(
+            user,
+            EmailKind::AccountCreated,
+            ctx,
+            email_config,
+            templates,
+        )
+        .await
+    }
+}
+
+/// Self-registered accounts nobody has shown they own the email of yet. They can't log in
+/// until they use the link we email them, their email doesn't count towards [`AdminUsers`],
+/// and signing in with SSO as that email takes it back.
+pub struct EmailVerification;
+
+impl EmailVerification {
+    const EXPIRY_HOURS: i64 = 24;
+
+    /// Create the verification link for the user, replacing any previous one
+    async fn create(db: &mut DbPoolConnection, user_id: i64) -> Result<String> {
+        let (token, hash, expires) = new_link_token(Self::EXPIRY_HOURS)?;
+
+        sqlx::query!(
+            "INSERT INTO email_verification (user_id, token, expires_at) VALUES (?, ?, ?)
+            ON CONFLICT(user_id) DO UPDATE SET token = excluded.token, created_at = CURRENT_TIMESTAMP, expires_at = excluded.expires_at",
+            user_id,
+            hash,
+            expires
+        )
+        .execute(&mut **db)
+        .await
+        .context("Couldn't insert email verification")?;
+
+        Ok(token)
+    }
+
+    /// The user a verification link is for, if it hasn't expired
+    async fn user_for(db: &mut DbPoolConnection, token: &str) -> Result<Option<i64>> {
+        let hash = Session::hash_token(token);
+        sqlx::query!(
+            "SELECT user_id FROM email_verification WHERE token = ? AND expires_at > CURRENT_TIMESTAMP",
+            hash
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .map(|r| r.map(|r| r.user_id))
+        .context("Couldn't fetch email verification")
+    }
+
+    /// Whether the user registered themselves and hasn't verified their email yet,
+    /// expired links still count, the user has to get a new one
+    pub async fn is_pending(db: &mut DbPoolConnection, user_id: i64) -> Result<bool> {
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM email_verification WHERE user_id = ?",
+            user_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count > 0)
+        .with_context(|| format!("Couldn't check email verification for user {user_id}"))
+    }
+
+    /// Remove unverified accounts using an email, so registering someone else's email first
+    /// can't stop them from signing in with SSO
+    pub async fn release_email(db: &mut DbPoolConnection, email: &str) -> Result {
+        sqlx::query!(
+            "DELETE FROM user WHERE email = ? AND id IN (SELECT user_id FROM email_verification)",
+            email
+        )
+        .execute(&mut **db)
+        .await
+        .context("Couldn't release email from unverified accounts")?;
+        Ok(())
+    }
+
+    async fn delete_for_user(db: &mut DbPoolConnection, user_id: i64) -> Result {
+        sqlx::query!("DELETE FROM email_verification WHERE user_id = ?", user_id)
+            .execute(&mut **db)
+            .await
+            .context("Couldn't delete email verification")?;
+        Ok(())
+    }
+
+    /// Email the user a new verification link, failures are only logged since the user
+    /// can get another link by logging in
+    async fn send(
+        db: &mut DbPoolConnection,
+        user: &User,
+        email_config: &EmailConfig,
+        templates: &EmailTemplates,
+    ) -> Result {
+        let token = Self::create(db, user.id).await?;
+        let mut ctx = TeraContext::new();
+        ctx.insert(
+            "verify_url",
+            &format!("{}/auth/password/verify/{token}", templates.url()),
+        );
+        ctx.insert("expires_hours", &Self::EXPIRY_HOURS);
+        if let Err(why) =
+            email_user(user, EmailKind::VerifyEmail, ctx, email_config, templates).await
+        {
+            error!(
+                "Couldn't send verification email to user {}: {:?}",
+                user.id, why
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Holds the user who logged in with a temporary password until they pick a new one,
+/// they don't get a session before then
+struct PendingPasswordChange;
+
+impl PendingPasswordChange {
+    const COOKIE_NAME: &'static str = "pending_password_change";
+    const LIFETIME_MINUTES: i64 = 15;
+
+    fn store(cookies: &CookieJar<'_>, user_id: i64) {
+        cookies.add_private(
+            Cookie::build((Self::COOKIE_NAME, user_id.to_string()))
+                .same_site(SameSite::Lax)
+                .max_age(Duration::minutes(Self::LIFETIME_MINUTES)),
+        );
+    }
+
+    fn get(cookies: &CookieJar<'_>) -> Option<i64> {
+        cookies
+            .get_private(Self::COOKIE_NAME)
+            .and_then(|c| c.value().parse().ok())
+    }
+
+    fn clear(cookies: &CookieJar<'_>) {
+        cookies.remove_private(Self::COOKIE_NAME);
+    }
+}
+
+#[derive(FromForm)]
+pub struct LoginForm<'r> {
+    email: &'r str,
+    password: &'r str,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/login", data = "<form>")]
+async fn login_post(
+    mut db: DbConnection,
+    cookies: &CookieJar<'_>,
+    login: LoginFlow<'_>,
+    form: Form<LoginForm<'_>>,
+    email_config: &State<EmailConfig>,
+    templates: &State<EmailTemplates>,
+    _limit: RateLimited<rate_limit::Login>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let found = PasswordCredential::find_user(&mut db, form.email.trim()).await?;
+    let (user, credential) = match found {
+        Some((user, credential)) if credential.verify(form.password).await? => (user, credential),
+        _ => return Ok(Message::error("Incorrect email or password").to(LOGIN_URI)),
+    };
+
+    if EmailVerification::is_pending(&mut db, user.id).await? {
+        EmailVerification::send(&mut db, &user, email_config, templates).await?;
+        return Ok(
+            Message::error("Please verify your email first, we've sent you a new link")
+                .to(LOGIN_URI),
+        );
+    }
+
+    if credential.must_change {
+        PendingPasswordChange::store(cookies, user.id);
+        return Ok(
+            Message::info("Please choose a new password to continue").to("/auth/password/change")
+        );
+    }
+
+    if let Some(next_step) = login.login(&mut db, &user).await? {
+        return Ok(next_step);
+    }
+
+    let redirect = cookies
+        .get(REDIRECT_COOKIE_NAME)
+        .map(|c| c.value().to_string())
+        .unwrap_or_else(|| "/".to_string());
+    cookies.remove(Cookie::from(REDIRECT_COOKIE_NAME));
+    Ok(Redirect::to(redirect))
+}
+
+#[get("/register")]
+async fn register_get(
+    user: Option<&User>,
+    config: &State<PasswordAuthConfig>,
+    pow: Challenge,
+    _token: &CsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    if user.is_some() {
+        return Ok(Err(Redirect::to("/")));
+    }
+    if !config.allow_registration {
+        return Err(Status::NotFound.into());
+    }
+    let min_length = config.min_length;
+    let ctx = context_with_base!(user, pow, min_length);
+    Ok(Ok(Template::render("auth/password_register", ctx)))
+}
+
+#[derive(FromForm)]
+pub struct RegisterForm<'r> {
+    #[field(validate = len(1..=100))]
+    email: &'r str,
+    #[field(validate = len(1..=32))]
+    display_name: &'r str,
+    password: &'r str,
+    password_confirm: &'r str,
+    pow_solution: Option<&'r str>,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/register", data = "<form>")]
+async fn register_post(
+    mut db: DbConnection,
+    cookies: &CookieJar<'_>,
+    form: Form<RegisterForm<'_>>,
+    config: &State<PasswordAuthConfig>,
+    pow_config: &State<ProofOfWorkConfig>,
+    spent_challenges: &State<SpentChallenges>,
+    code_info: &State<CodeInfo>,
+    admins: &State<AdminUsers>,
+    email_config: &State<EmailConfig>,
+    templates: &State<EmailTemplates>,
+    _limit: RateLimited<rate_limit::Login>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    const REGISTER_URI: &str = "/auth/password/register";
+
+    if !config.allow_registration {
+        return Err(Status::NotFound.into());
+    }
+    if !challenge::verify(pow_config, spent_challenges, cookies, form.pow_solution) {
+        return Ok(
+            Message::error("Couldn't verify your browser, please try again").to(REGISTER_URI),
+        );
+    }
+    if let Err(why) = config.check_new_password(form.password, form.password_confirm) {
+        return Ok(Message::error(&why).to(REGISTER_URI));
+    }
+
+    let email = form.email.trim();
+    if !email.contains('@') {
+        return Ok(Message::error("Please enter a valid email").to(REGISTER_URI));
+    }
+    if admins.contains(email) {
+        return Ok(Message::error("This email can't be used to register").to(REGISTER_URI));
+    }
+    let user = create_local_user(
+        &mut db,
+        email,
+        form.display_name.trim(),
+        form.password,
+        false,
+        &code_info.run_config.default_language,
+    )
+    .await?;
+    let user = match user {
+        Some(user) => user,
+        None => {
+            return Ok(Message::error("An account with this email already exists").to(LOGIN_URI))
+        }
+    };
+    EmailVerification::send(&mut db, &user, email_config, templates).await?;
+
+    Ok(Message::success("Account created, check your email for a link to verify it").to(LOGIN_URI))
+}
+
+#[get("/verify/<token>")]
+async fn verify_get(
+    mut db: DbConnection,
+    token: &str,
+    user: Option<&User>,
+    _csrf: &CsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    if EmailVerification::user_for(&mut db, token).await?.is_none() {
+        return Ok(Err(Message::error(
+            "This link is invalid or has expired, log in to get a new one",
+        )
+        .to(LOGIN_URI)));
+    }
+    let ctx = context_with_base!(user, token);
+    Ok(Ok(Template::render("auth/password_verify", ctx)))
+}
+
+#[post("/verify/<token>")]
+async fn verify_post(
+    mut db: DbConnection,
+    token: &str,
+    login: LoginFlow<'_>,
+    _csrf: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let Some(user_id) = EmailVerification::user_for(&mut db, token).await? else {
+        return Ok(
+            Message::error("This link is invalid or has expired, log in to get a new one")
+                .to(LOGIN_URI),
+        );
+    };
+    EmailVerification::delete_for_user(&mut db, user_id).await?;
+
+    let user = User::get_or_404(&mut db, user_id).await?;
+    if let Some(next_step) = login.login(&mut db, &user).await? {
+        return Ok(next_step);
+    }
+    Ok(welcome_redirect(&user))
+}
+
+#[get("/forgot")]
+async fn forgot_get(
+    user: Option<&User>,
+    email_config: &State<EmailConfig>,
+    _csrf: &CsrfToken,
+) -> ResultResponse<Template> {
+    if !email_config.can_send(EmailKind::PasswordReset) {
+        return Err(Status::NotFound.into());
+    }
+    let ctx = context_with_base!(user,);
+    Ok(Template::render("auth/password_forgot", ctx))
+}
+
+#[derive(FromForm)]
+pub struct ForgotForm<'r> {
+    email: &'r str,
+}
+
+#[post("/forgot", data = "<form>")]
+async fn forgot_post(
+    mut db: DbConnection,
+    form: Form<ForgotForm<'_>>,
+    email_config: &State<EmailConfig>,
+    templates: &State<EmailTemplates>,
+    _limit: RateLimited<rate_limit::Login>,
+    _csrf: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    if !email_config.can_send(EmailKind::PasswordReset) {
+        return Err(Status::NotFound.into());
+    }
+
+    // Same answer whether or not there's an account, so this can't be used to look up emails
+    if let Some((user, _)) = PasswordCredential::find_user(&mut db, form.email.trim()).await? {
+        if EmailVerification::is_pending(&mut db, user.id).await? {
+            EmailVerification::send(&mut db, &user, email_config, templates).await?;
+        } else {
+            let token = PasswordReset::create(&mut db, user.id).await?;
+            if let Err(why) = PasswordReset::email(&user, &token, email_config, templates).await {
+                error!(
+                    "Couldn't send password reset email to user {}: {:?}",
+                    user.id, why
+                );
+            }
+        }
+    }
+
+    Ok(Message::info(
+        "If there's an account with that email, we've sent it a link to reset its password",
+    )
+    .to(LOGIN_URI))
+}
+
+/// Create a new local account, returns `None` if the email is already taken
+pub async fn create_local_user(
+    db: &mut DbPoolConnection,
+    email: &str,
+    display_name: &str,
+    password: &str,
+    must_change: bool,
+    default_language: &str,
+) -> Result<Option<User>> {
+    let user = provision_local_user(db, email, display_name, default_language).await?;
+    if let Some(user) = user.as_ref() {
+        PasswordCredential::set(db, user.id, password, must_change).await?;
+    }
+    Ok(user)
+}
+
+/// Create a local account without a password, it can't be logged into until it's
+/// claimed with an [`AccountClaim`] link. Returns `None` if the email is already taken.
+pub async fn provision_local_user(
+    db: &mut DbPoolConnection,
+    email: &str,
+    display_name: &str,
+    default_language: &str,
+) -> Result<Option<User>> {
+    let existing = sqlx::query!("SELECT id FROM user WHERE email = ?", email)
+        .fetch_optional(&mut **db)
+        .await
+        .context("Couldn't check for existing user")?;
+    if existing.is_some() {
+        return Ok(None);
+    }
+
+    let user = User::temporary(
+        local_sso_id(email),
+        email.to_string(),
+        display_name.to_string(),
+        default_language,
+    )
+    .insert(db)
+    .await?;
+    Ok(Some(user))
+}
+
+/// The user changing their password, either logged in normally or
+/// forced here after logging in with a temporary password
+async fn change_target(
+    db: &mut DbPoolConnection,
+    user: Option<&User>,
+    cookies: &CookieJar<'_>,
+) -> Result<Option<(i64, bool)>> {
+    if let Some(user) = user {
+        return Ok(Some((user.id, false)));
+    }
+    match PendingPasswordChange::get(cookies) {
+        Some(id) => Ok(User::get(db, id).await?.map(|u| (u.id, true))),
+        None => Ok(None),
+    }
+}
+
+#[get("/change")]
+async fn change_get(
+    mut db: DbConnection,
+    user: Option<&User>,
+    cookies: &CookieJar<'_>,
+    config: &State<PasswordAuthConfig>,
+    _token: &CsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    let (user_id, forced) = match change_target(&mut db, user, cookies).await? {
+        Some(target) => target,
+        None => {
+            return Ok(Err(
+                Message::error("Please log in to change your password").to(LOGIN_URI)
+            ))
+        }
+    };
+    if PasswordCredential::get(&mut db, user_id).await?.is_none() {
+        return Err(Status::NotFound.into());
+    }
+    let min_length = config.min_length;
+    let ctx = context_with_base!(user, forced, min_length);
+    Ok(Ok(Template::render("auth/password_change", ctx)))
+}
+
+#[derive(FromForm)]
+pub struct ChangeForm<'r> {
+    current_password: &'r str,
+    new_password: &'r str,
+    new_password_confirm: &'r str,
+}
+
+#[post("/change", data = "<form>")]
+async fn change_post(
+    mut db: DbConnection,
+    user: Option<&User>,
+    cookies: &CookieJar<'_>,
+    login: LoginFlow<'_>,
+    config: &State<PasswordAuthConfig>,
+    form: Form<ChangeForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    const CHANGE_URI: &str = "/auth/password/change";
+
+    let (user_id, forced) = match change_target(&mut db, user, cookies).await? {
+        Some(target) => target,
+        None => return Ok(Message::error("Please log in to change your password").to(LOGIN_URI)),
+    };
+    let credential = PasswordCredential::get(&mut db, user_id)
+        .await?
+        .ok_or(Status::NotFound)?;
+
+    if !credential.verify(form.current_password).await? {
+        return Ok(Message::error("Your current password is incorrect").to(CHANGE_URI));
+    }
+    if let Err(why) = config.check_new_password(form.new_password, form.new_password_confirm) {
+        return Ok(Message::error(&why).to(CHANGE_URI));
+    }
+    if form.new_password == form.current_password {
+        return Ok(
+            Message::error("Your new password must be different from your current one")
+                .to(CHANGE_URI),
+        );
+    }
+
+    PasswordCredential::set(&mut db, user_id, form.new_password, false).await?;
+
+    if forced {
+        PendingPasswordChange::clear(cookies);
+        let user = User::get_or_404(&mut db, user_id).await?;
+        if let Some(next_step) = login.login(&mut db, &user).await? {
+            return Ok(next_step);
+        }
+        Ok(Message::success("Password changed").to("/"))
+    } else {
+        Ok(Message::success("Password changed").to("/settings/account"))
+    }
+}
+
+#[get("/reset/<token>")]
+async fn reset_get(
+    mut db: DbConnection,
+    token: &str,
+    user: Option<&User>,
+    config: &State<PasswordAuthConfig>,
+    _csrf: &CsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    if PasswordReset::get(&mut db, token).await?.is_none() {
+        return Ok(Err(Message::error(
+            "This reset link is invalid or has expired",
+        )
+        .to(LOGIN_URI)));
+    }
+    let min_length = config.min_length;
+    let ctx = context_with_base!(user, token, min_length);
+    Ok(Ok(Template::render("auth/password_reset", ctx)))
+}
+
+#[derive(FromForm)]
+pub struct ResetForm<'r> {
+    new_password: &'r str,
+    new_password_confirm: &'r str,
+}
+
+#[post("/reset/<token>", data = "<form>")]
+async fn reset_post(
+    mut db: DbConnection,
+    token: &str,
+    cookies: &CookieJar<'_>,
+    login: LoginFlow<'_>,
+    config: &State<PasswordAuthConfig>,
+    form: Form<ResetForm<'_>>,
+    _csrf: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let reset = match PasswordReset::get(&mut db, token).await? {
+        Some(reset) => reset,
+        None => {
+            return Ok(Message::error("This reset link is invalid or has expired").to(LOGIN_URI))
+        }
+    };
+    if let Err(why) = config.check_new_password(form.new_password, form.new_password_confirm) {
+        return Ok(Message::error(&why).to(&format!("/auth/password/reset/{token}")));
+    }
+
+    PasswordCredential::set(&mut db, reset.user_id, form.new_password, false).await?;
+    PasswordReset::delete_for_user(&mut db, reset.user_id).await?;
+    PendingPasswordChange::clear(cookies);
+
+    if EmailVerification::is_pending(&mut db, reset.user_id).await? {
+        return Ok(
+            Message::success("Your password has been reset, verify your email to log in")
+                .to(LOGIN_URI),
+        );
+    }
+
+    // A reset link only proves access to the password, not the second factor
+    let user = User::get_or_404(&mut db, reset.user_id).await?;
+    if let Some(next_step) = login.login(&mut db, &user).await? {
+        return Ok(next_step);
+    }
+    Ok(Message::success("Your password has been reset").to("/"))
+}
+
+#[get("/claim/<token>")]
+async fn claim_get(
+    mut db: DbConnection,
+    token: &str,
+    user: Option<&User>,
+    config: &State<PasswordAuthConfig>,
+    _csrf: &CsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    let Some(claim) = AccountClaim::get(&mut db, token).await? else {
+        return Ok(Err(Message::error(
+            "This link has already been used or has expired",
+        )
+        .to(LOGIN_URI)));
+    };
+    let claim_user = User::get_or_404(&mut db, claim.user_id).await?;
+    let min_length = config.min_length;
+    let ctx = context_with_base!(user, token, claim_user, min_length);
+    Ok(Ok(Template::render("auth/password_claim", ctx)))
+}
+
+#[post("/claim/<token>", data = "<form>")]
+async fn claim_post(
+    mut db: DbConnection,
+    token: &str,
+    login: LoginFlow<'_>,
+    config: &State<PasswordAuthConfig>,
+    form: Form<ResetForm<'_>>,
+    _csrf: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let Some(claim) = AccountClaim::get(&mut db, token).await? else {
+        return Ok(Message::error("This link has already been used or has expired").to(LOGIN_URI));
+    };
+    if let Err(why) = config.check_new_password(form.new_password, form.new_password_confirm) {
+        return Ok(Message::error(&why).to(&format!("/auth/password/claim/{token}")));
+    }
+
+    PasswordCredential::set(&mut db, claim.user_id, form.new_password, false).await?;
+    AccountClaim::delete_for_user(&mut db, claim.user_id).await?;
+
+    let user = User::get_or_404(&mut db, claim.user_id).await?;
+    if let Some(next_step) = login.login(&mut db, &user).await? {
+        return Ok(next_step);
+    }
+    Ok(welcome_redirect(&user))
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Password Auth", |rocket| async {
+        let mut config = rocket
+            .figment()
+            .extract_inner::<PasswordAuthConfig>("password_auth")
+            .unwrap_or_default();
+        let email_config = rocket
+            .figment()
+            .extract_inner::<EmailConfig>("email")
+            .unwrap_or_default();
+        if config.enabled
+            && config.allow_registration
+            && !email_config.can_send(EmailKind::VerifyEmail)
+        {
+            warn!("Password registration needs verify_email emails to be sent, turning it off");
+            config.allow_registration = false;
+        }
+        let enabled = config.enabled;
+        let rocket = rocket.manage(config);
+        if enabled {
+            rocket.mount(
+                "/auth/password",
+                routes![
+                    login_post,
+                    register_get,
+                    register_post,
+                    change_get,
+                    change_post,
+                    reset_get,
+                    reset_post,
+                    verify_get,
+                    verify_post,
+                    forgot_get,
+                    forgot_post,
+                    claim_get,
+                    claim_post
+                ],
+            )
+        } else {
+            rocket
+        }
+    })
+}
@@ -0,0 +1,135 @@
+use rocket::{
+    form::Form,
+    get,
+    http::{Cookie, CookieJar, SameSite},
+    post,
+    response::Redirect,
+    time::Duration,
+    State,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    context_with_base, db::DbConnection, error::prelude::*, messages::Message, run::CodeInfo,
+};
+
+use super::{
+    challenge::{self, Challenge, ChallengeForm, ProofOfWorkConfig, SpentChallenges},
+    csrf::{CsrfToken, VerifyCsrfToken},
+    two_factor::LoginFlow,
+    users::User,
+    LOGIN_URI,
+};
+
+/// An SSO identity that doesn't have an account yet, held in a private cookie
+/// until the user completes the registration challenge
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingRegistration {
+    pub sso_id: String,
+    pub email: String,
+    pub display_name: String,
+}
+
+impl PendingRegistration {
+    const COOKIE_NAME: &'static str = "pending_registration";
+    const LIFETIME_MINUTES: i64 = 15;
+
+    pub fn store(&self, cookies: &CookieJar<'_>) -> Result {
+        let raw = serde_json::to_string(self).context("Couldn't serialize pending registration")?;
+        cookies.add_private(
+            Cookie::build((Self::COOKIE_NAME, raw))
+                .same_site(SameSite::Lax)
+                .max_age(Duration::minutes(Self::LIFETIME_MINUTES)),
+        );
+        Ok(())
+    }
+
+    fn get(cookies: &CookieJar<'_>) -> Option<Self> {
+        cookies
+            .get_private(Self::COOKIE_NAME)
+            .and_then(|c| serde_json::from_str(c.value()).ok())
+    }
+
+    fn clear(cookies: &CookieJar<'_>) {
+        cookies.remove_private(Self::COOKIE_NAME);
+    }
+}
+
+pub fn welcome_redirect(user: &User) -> Redirect {
+    Message::info("Welcome {name}! Please look through your settings before joining a competition")
+        .arg("name", &user.default_display_name)
+        .to("/settings/profile")
+}
+
+#[get("/register")]
+pub async fn register_get(
+    user: Option<&User>,
+    cookies: &CookieJar<'_>,
+    pow: Challenge,
+    _token: &CsrfToken,
+) -> Result<Template, Redirect> {
+    if user.is_some() {
+        return Err(Redirect::to("/"));
+    }
+    let pending = PendingRegistration::get(cookies)
+        .ok_or_else(|| Message::error("Please sign in with SSO to register").to(LOGIN_URI))?;
+    let ctx = context_with_base!(user, pending, pow);
+    Ok(Template::render("auth/register", ctx))
+}
+
+#[post("/register", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn register_post(
+    mut db: DbConnection,
+    cookies: &CookieJar<'_>,
+    login: LoginFlow<'_>,
+    form: Form<ChallengeForm<'_>>,
+    pow_config: &State<ProofOfWorkConfig>,
+    spent_challenges: &State<SpentChallenges>,
+    code_info: &State<CodeInfo>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let pending = match PendingRegistration::get(cookies) {
+        Some(pending) => pending,
+        None => {
+            return Ok(Message::error("Your registration expired, please try again").to(LOGIN_URI));
+        }
+    };
+
+    if !challenge::verify(pow_config, spent_challenges, cookies, form.pow_solution) {
+        return Ok(
+            Message::error("Couldn't verify your browser, please try again").to("/auth/register"),
+        );
+    }
+
+    PendingRegistration::clear(cookies);
+
+    let user = User::temporary(
+        pending.sso_id,
+        pending.email,
+        pending.display_name,
+        &code_info.run_config.default_language,
+    );
+
+    // Someone may have registered this identity in another tab
+    if let Some(existing) = user.find_existing(&mut db).await? {
+        return Ok(login
+            .login_sso(&mut db, &existing)
+            .await?
+            .unwrap_or_else(|| Redirect::to("/")));
+    }
+
+    if user.email_taken(&mut db).await? {
+        return Ok(Message::error(
+            "An account with this email already exists, log in with your password instead",
+        )
+        .to(LOGIN_URI));
+    }
+
+    let user = user
+        .register(&mut db, cookies)
+        .await
+        .context("Couldn't register user")?;
+
+    Ok(welcome_redirect(&user))
+}
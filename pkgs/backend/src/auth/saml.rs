@@ -18,9 +18,21 @@ use samael::{
 };
 use serde::Deserialize;
 
-use crate::{db::DbConnection, error::prelude::*, messages::Message, run::CodeInfo};
+use crate::{
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    rate_limit::{self, RateLimited},
+    run::CodeInfo,
+};
 
-use super::{users::User, REDIRECT_COOKIE_NAME};
+use super::{
+    challenge::ProofOfWorkConfig,
+    register::{welcome_redirect, PendingRegistration},
+    two_factor::LoginFlow,
+    users::User,
+    LOGIN_URI, REDIRECT_COOKIE_NAME,
+};
 
 fn cn_oid() -> String {
     "urn:oid:2.5.4.3".to_string()
@@ -123,7 +135,11 @@ impl SamlOptions {
 }
 
 #[get("/login")]
-async fn login(sp: &State<ServiceProvider>, cookies: &CookieJar<'_>) -> ResultResponse<Redirect> {
+async fn login(
+    sp: &State<ServiceProvider>,
+    cookies: &CookieJar<'_>,
+    _limit: RateLimited<rate_limit::Login>,
+) -> ResultResponse<Redirect> {
     let base = sp
         .sso_binding_location(PREFERRED_SSO_BINDING)
         .ok_or_else(|| {
@@ -178,6 +194,7 @@ struct SamlAcsForm {
     relay_state: Option<String>,
 }
 
+#[allow(clippy::too_many_arguments)]
 #[post("/acs", data = "<form>")]
 async fn acs(
     mut db: DbConnection,
@@ -185,7 +202,10 @@ async fn acs(
     so: &State<SamlOptions>,
     form: Form<SamlAcsForm>,
     code_info: &State<CodeInfo>,
+    pow_config: &State<ProofOfWorkConfig>,
     cookies: &CookieJar<'_>,
+    login: LoginFlow<'_>,
+    _limit: RateLimited<rate_limit::Login>,
 ) -> ResultResponse<Redirect> {
     let form = form.into_inner();
 
@@ -229,19 +249,30 @@ async fn acs(
                 display_name.clone(),
                 &code_info.run_config.default_language,
             );
-            let (user, is_new) = user
-                .login_or_register(&mut db, cookies)
-                .await
-                .context("Couldn't log-in / register user")?;
-
-            if is_new {
-                Ok(Message::info(&format!(
-                    "Welcome {}! Please look through your settings before joining a competition",
-                    user.default_display_name
-                ))
-                .to("/settings/profile"))
+            if let Some(existing) = user.find_existing(&mut db).await? {
+                Ok(login
+                    .login_sso(&mut db, &existing)
+                    .await?
+                    .unwrap_or_else(|| Redirect::to(relay_state)))
+            } else if user.email_taken(&mut db).await? {
+                Ok(Message::error(
+                    "An account with this email already exists, log in with your password instead",
+                )
+                .to(LOGIN_URI))
+            } else if pow_config.enabled {
+                PendingRegistration {
+                    sso_id: user.sso_id,
+                    email: user.email,
+                    display_name: user.default_display_name,
+                }
+                .store(cookies)?;
+                Ok(Redirect::to("/auth/register"))
             } else {
-                Ok(Redirect::to(relay_state))
+                let user = user
+                    .register(&mut db, cookies)
+                    .await
+                    .context("Couldn't register user")?;
+                Ok(welcome_redirect(&user))
             }
         } else {
             warn!(
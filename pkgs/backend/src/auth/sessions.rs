@@ -1,7 +1,17 @@
+use std::{sync::Arc, time::Duration};
+
 use chrono::NaiveDateTime;
 use rand::{distr::Alphanumeric, Rng};
+use rocket::fairing::AdHoc;
+use rocket_db_pools::Database as R_Database;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::{Database, DbPool, DbPoolConnection},
+    error::prelude::*,
+};
 
-use crate::{db::DbPoolConnection, error::prelude::*};
+use super::csrf::ArCsrfTokens;
 
 pub struct Session {
     pub id: i64,
@@ -13,12 +23,15 @@ pub struct Session {
     #[allow(dead_code)]
     pub created_at: NaiveDateTime,
     pub expires_at: NaiveDateTime,
+    /// Set while an admin is viewing the site as this user, see [`super::impersonation`]
+    pub impersonating_id: Option<i64>,
 }
 
 impl Session {
     pub const TOKEN_COOKIE_NAME: &'static str = "token";
     const TOKEN_LENGTH: usize = 64;
     const EXPIRY_DAYS: i64 = 14;
+    const CLEANUP_BATCH_SIZE: i64 = 500;
 
     fn gen_token() -> String {
         rand::rng()
@@ -45,6 +58,23 @@ impl Session {
         Ok((session, token))
     }
 
+    pub async fn set_impersonating(
+        &mut self,
+        db: &mut DbPoolConnection,
+        user_id: Option<i64>,
+    ) -> Result {
+        sqlx::query!(
+            "UPDATE session SET impersonating_id = ? WHERE id = ?",
+            user_id,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't update impersonation for session {}", self.id))?;
+        self.impersonating_id = user_id;
+        Ok(())
+    }
+
     pub async fn from_token(db: &mut DbPoolConnection, token: &str) -> Result<Option<Session>> {
         let hash = Self::hash_token(token);
         sqlx::query_as!(
@@ -56,4 +86,111 @@ impl Session {
         .await
         .context("Couldn't fetch session by token")
     }
+
+    /// Delete expired sessions in batches, returning the total amount deleted
+    pub async fn delete_expired(db: &mut DbPoolConnection) -> Result<u64> {
+        let mut total = 0;
+        loop {
+            let deleted = sqlx::query!(
+                "DELETE FROM session WHERE id IN (SELECT id FROM session WHERE expires_at <= CURRENT_TIMESTAMP LIMIT ?)",
+                Self::CLEANUP_BATCH_SIZE
+            )
+            .execute(&mut **db)
+            .await
+            .context("Couldn't delete expired sessions")?
+            .rows_affected();
+            total += deleted;
+            if deleted < Self::CLEANUP_BATCH_SIZE as u64 {
+                break;
+            }
+        }
+        Ok(total)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CleanupStats {
+    /// How many times cleanup has run since startup
+    pub runs: u64,
+    pub sessions_deleted: u64,
+    pub csrf_tokens_deleted: u64,
+    /// Amounts deleted by the most recent run, (sessions, csrf tokens)
+    pub last_deleted: (u64, u64),
+    #[serde(serialize_with = "crate::times::serialize_option_to_js")]
+    pub last_run: Option<NaiveDateTime>,
+}
+
+pub type CleanupStatsHandle = Arc<Mutex<CleanupStats>>;
+
+/// Purge expired sessions and CSRF tokens now, recording the results in `stats`
+pub async fn purge_expired(
+    pool: &DbPool,
+    csrf_tokens: &ArCsrfTokens,
+    stats: &CleanupStatsHandle,
+) -> Result<(u64, u64)> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .context("Couldn't get db connection for session cleanup")?;
+    let sessions = Session::delete_expired(&mut conn).await?;
+    let csrf = csrf_tokens.lock().await.prune() as u64;
+
+    let mut stats = stats.lock().await;
+    stats.runs += 1;
+    stats.sessions_deleted += sessions;
+    stats.csrf_tokens_deleted += csrf;
+    stats.last_deleted = (sessions, csrf);
+    stats.last_run = Some(chrono::offset::Utc::now().naive_utc());
+
+    Ok((sessions, csrf))
+}
+
+const fn default_cleanup_interval_minutes() -> u64 {
+    60
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Session Cleanup", |rocket| async {
+        let interval = rocket
+            .figment()
+            .extract_inner::<u64>("session_cleanup_interval_minutes")
+            .unwrap_or(default_cleanup_interval_minutes())
+            .max(1);
+        rocket
+            .manage::<CleanupStatsHandle>(Arc::new(Mutex::new(CleanupStats::default())))
+            .attach(AdHoc::on_liftoff("Session Cleanup Task", move |rocket| {
+                Box::pin(async move {
+                    let (Some(db), Some(csrf), Some(stats)) = (
+                        Database::fetch(rocket),
+                        rocket.state::<ArCsrfTokens>(),
+                        rocket.state::<CleanupStatsHandle>(),
+                    ) else {
+                        error!("Couldn't start session cleanup task");
+                        return;
+                    };
+                    let pool = db.0.clone();
+                    let csrf = csrf.clone();
+                    let stats = stats.clone();
+                    let shutdown = rocket.shutdown();
+                    tokio::spawn(async move {
+                        let mut ticker =
+                            tokio::time::interval(Duration::from_secs(interval * 60));
+                        tokio::pin!(shutdown);
+                        loop {
+                            tokio::select! {
+                                _ = ticker.tick() => {
+                                    match purge_expired(&pool, &csrf, &stats).await {
+                                        Ok((sessions, csrf)) => info!(
+                                            "Cleaned up {sessions} expired sessions and {csrf} CSRF tokens"
+                                        ),
+                                        Err(why) => error!("Session cleanup failed: {why:?}"),
+                                    }
+                                }
+                                _ = &mut shutdown => break,
+                            }
+                        }
+                    });
+                })
+            }))
+    })
 }
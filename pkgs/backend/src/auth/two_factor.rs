@@ -0,0 +1,496 @@
+use std::{
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::NaiveDateTime;
+use log::error;
+use rand::{distr::Alphanumeric, Rng};
+use rocket::{
+    fairing::AdHoc,
+    form::Form,
+    get,
+    http::{Cookie, CookieJar, SameSite, Status},
+    post,
+    request::{self, FromRequest, Outcome},
+    response::Redirect,
+    routes,
+    time::Duration,
+    FromForm, Request,
+};
+use rocket_dyn_templates::Template;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::{
+    context_with_base,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+    rate_limit::{self, RateLimited},
+};
+
+use super::{
+    csrf::{CsrfToken, VerifyCsrfToken},
+    onsite::{OnsiteAccount, OnsiteConfig},
+    password::EmailVerification,
+    sessions::Session,
+    users::{AdminUsers, User},
+    LOGIN_URI, REDIRECT_COOKIE_NAME,
+};
+
+pub const VERIFY_URI: &str = "/auth/2fa";
+pub const SETTINGS_URI: &str = "/settings/account/2fa";
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct TwoFactorConfig {
+    /// Admins can't use admin pages until they've set up 2FA
+    #[serde(default)]
+    pub require_for_admins: bool,
+}
+
+impl TwoFactorConfig {
+    pub fn is_required_for(&self, admins: &AdminUsers, user: &User) -> bool {
+        self.require_for_admins && admins.contains(&user.email)
+    }
+}
+
+pub struct TwoFactor {
+    pub user_id: i64,
+    /// Base32 encoded, the same form authenticator apps take it in
+    pub secret: String,
+    /// `false` while enrolling, until the user confirms a code from their app
+    pub enabled: bool,
+    last_used_step: i64,
+    #[allow(dead_code)]
+    pub created_at: NaiveDateTime,
+    /// Wrong codes in a row since the last right one or lockout
+    failed_attempts: i64,
+    locked_until: Option<NaiveDateTime>,
+}
+
+impl TwoFactor {
+    const STEP_SECS: u64 = 30;
+    const SECRET_BYTES: usize = 20;
+    const RECOVERY_CODE_COUNT: usize = 10;
+    const RECOVERY_CODE_LENGTH: usize = 10;
+    const MAX_FAILED_ATTEMPTS: i64 = 5;
+    const LOCKOUT_MINUTES: i64 = 15;
+
+    pub async fn get(db: &mut DbPoolConnection, user_id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            TwoFactor,
+            "SELECT * FROM user_totp WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't fetch 2FA for user {user_id}"))
+    }
+
+    pub async fn is_enabled(db: &mut DbPoolConnection, user_id: i64) -> Result<bool> {
+        Ok(Self::get(db, user_id).await?.is_some_and(|t| t.enabled))
+    }
+
+    /// Start enrolling with a fresh secret, replacing any unfinished enrollment
+    pub async fn begin_enrollment(db: &mut DbPoolConnection, user_id: i64) -> Result<Self> {
+        let secret = Secret::Raw(rand::random::<[u8; Self::SECRET_BYTES]>().to_vec())
+            .to_encoded()
+            .to_string();
+        sqlx::query_as!(
+            TwoFactor,
+            "INSERT INTO user_totp (user_id, secret) VALUES (?, ?)
+            ON CONFLICT(user_id) DO UPDATE SET secret = excluded.secret, enabled = FALSE, last_used_step = 0
+            RETURNING *",
+            user_id,
+            secret
+        )
+        .fetch_one(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't start 2FA enrollment for user {user_id}"))
+    }
+
+    pub async fn enable(&mut self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!(
+            "UPDATE user_totp SET enabled = TRUE WHERE user_id = ?",
+            self.user_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't enable 2FA for user {}", self.user_id))?;
+        self.enabled = true;
+        Ok(())
+    }
+
+    /// Turn off 2FA, removing the secret and any recovery codes
+    pub async fn remove(db: &mut DbPoolConnection, user_id: i64) -> Result {
+        sqlx::query!("DELETE FROM totp_recovery_code WHERE user_id = ?", user_id)
+            .execute(&mut **db)
+            .await
+            .context("Couldn't delete recovery codes")?;
+        sqlx::query!("DELETE FROM user_totp WHERE user_id = ?", user_id)
+            .execute(&mut **db)
+            .await
+            .with_context(|| format!("Couldn't remove 2FA for user {user_id}"))?;
+        Ok(())
+    }
+
+    pub fn totp(&self, issuer: Option<String>, account_name: String) -> Result<TOTP> {
+        let secret = Secret::Encoded(self.secret.clone())
+            .to_bytes()
+            .map_err(|e| anyhow!("Invalid 2FA secret: {e:?}"))?;
+        TOTP::new(
+            Algorithm::SHA1,
+            6,
+            1,
+            Self::STEP_SECS,
+            secret,
+            issuer,
+            account_name,
+        )
+        .map_err(|e| anyhow!("Couldn't set up TOTP: {e:?}"))
+    }
+
+    /// QR code for authenticator apps to scan, as a PNG
+    pub fn qr_png(&self, issuer: &str, user: &User) -> Result<Vec<u8>> {
+        // Colons separate the issuer and account in the otpauth URL
+        self.totp(Some(issuer.replace(':', "")), user.email.replace(':', ""))?
+            .get_qr_png()
+            .map_err(|e| anyhow!("Couldn't generate 2FA QR code: {e}"))
+    }
+
+    /// Accepts a code for the current or an adjacent time step, each step can only be used once
+    async fn check_totp(&mut self, db: &mut DbPoolConnection, code: &str) -> Result<bool> {
+        let totp = self.totp(None, String::new())?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("System clock is before the epoch")?
+            .as_secs();
+        let current = now / Self::STEP_SECS;
+        let step = [current.saturating_sub(1), current, current + 1]
+            .into_iter()
+            .find(|s| {
+                *s as i64 > self.last_used_step && totp.generate(s * Self::STEP_SECS) == code
+            });
+
+        if let Some(step) = step {
+            let step = step as i64;
+            sqlx::query!(
+                "UPDATE user_totp SET last_used_step = ? WHERE user_id = ?",
+                step,
+                self.user_id
+            )
+            .execute(&mut **db)
+            .await
+            .context("Couldn't record used 2FA code")?;
+            self.last_used_step = step;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn normalize_recovery_code(code: &str) -> String {
+        code.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+
+    /// Recovery codes can only be used once, a used one is deleted
+    async fn use_recovery_code(&self, db: &mut DbPoolConnection, code: &str) -> Result<bool> {
+        let hash = Session::hash_token(&Self::normalize_recovery_code(code));
+        let res = sqlx::query!(
+            "DELETE FROM totp_recovery_code WHERE user_id = ? AND code = ?",
+            self.user_id,
+            hash
+        )
+        .execute(&mut **db)
+        .await
+        .context("Couldn't check recovery code")?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// When the user can try codes again, if they've been locked out for getting too many wrong
+    pub fn locked_until(&self) -> Option<NaiveDateTime> {
+        let now = chrono::offset::Utc::now().naive_utc();
+        self.locked_until.filter(|l| *l > now)
+    }
+
+    /// Count a wrong code, locking the user out once there are too many in a row,
+    /// or clear the count after a right one
+    async fn record_attempt(&mut self, db: &mut DbPoolConnection, valid: bool) -> Result {
+        if valid && self.failed_attempts == 0 {
+            return Ok(());
+        }
+        let (failed_attempts, locked_until) = if valid {
+            (0, None)
+        } else if self.failed_attempts + 1 >= Self::MAX_FAILED_ATTEMPTS {
+            let until = chrono::offset::Utc::now().naive_utc()
+                + chrono::Duration::minutes(Self::LOCKOUT_MINUTES);
+            (0, Some(until))
+        } else {
+            (self.failed_attempts + 1, self.locked_until)
+        };
+        sqlx::query!(
+            "UPDATE user_totp SET failed_attempts = ?, locked_until = ? WHERE user_id = ?",
+            failed_attempts,
+            locked_until,
+            self.user_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't record 2FA attempt for user {}", self.user_id))?;
+        self.failed_attempts = failed_attempts;
+        self.locked_until = locked_until;
+        Ok(())
+    }
+
+    /// Check a code from the user's authenticator app, or one of their recovery codes.
+    /// Every code is rejected while the user is locked out
+    pub async fn verify(&mut self, db: &mut DbPoolConnection, code: &str) -> Result<bool> {
+        if self.locked_until().is_some() {
+            return Ok(false);
+        }
+        let code = code.trim().replace(' ', "");
+        let valid = if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+            self.check_totp(db, &code).await?
+        } else {
+            self.use_recovery_code(db, &code).await?
+        };
+        self.record_attempt(db, valid).await?;
+        Ok(valid)
+    }
+
+    /// Replace the user's recovery codes, the returned codes are only ever shown once
+    pub async fn generate_recovery_codes(&self, db: &mut DbPoolConnection) -> Result<Vec<String>> {
+        sqlx::query!(
+            "DELETE FROM totp_recovery_code WHERE user_id = ?",
+            self.user_id
+        )
+        .execute(&mut **db)
+        .await
+        .context("Couldn't delete old recovery codes")?;
+
+        let mut codes = Vec::with_capacity(Self::RECOVERY_CODE_COUNT);
+        for _ in 0..Self::RECOVERY_CODE_COUNT {
+            let code: String = rand::rng()
+                .sample_iter(&Alphanumeric)
+                .take(Self::RECOVERY_CODE_LENGTH)
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .collect();
+            let hash = Session::hash_token(&code);
+            sqlx::query!(
+                "INSERT INTO totp_recovery_code (user_id, code) VALUES (?, ?)",
+                self.user_id,
+                hash
+            )
+            .execute(&mut **db)
+            .await
+            .context("Couldn't insert recovery code")?;
+            let (first, second) = code.split_at(Self::RECOVERY_CODE_LENGTH / 2);
+            codes.push(format!("{first}-{second}"));
+        }
+        Ok(codes)
+    }
+
+    pub async fn recovery_codes_left(&self, db: &mut DbPoolConnection) -> Result<i64> {
+        let row = sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM totp_recovery_code WHERE user_id = ?",
+            self.user_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Couldn't count recovery codes")?;
+        Ok(row.count)
+    }
+}
+
+/// Holds the user who passed the first login step until they enter a 2FA code,
+/// they don't get a session before then. The cookie carries when it was issued so it
+/// stops working after its lifetime even if the browser keeps it
+struct PendingTwoFactor;
+
+impl PendingTwoFactor {
+    const COOKIE_NAME: &'static str = "pending_two_factor";
+    const LIFETIME_MINUTES: i64 = 5;
+
+    fn store(cookies: &CookieJar<'_>, user_id: i64) {
+        let issued_at = chrono::offset::Utc::now().timestamp();
+        cookies.add_private(
+            Cookie::build((Self::COOKIE_NAME, format!("{user_id}:{issued_at}")))
+                .same_site(SameSite::Lax)
+                .max_age(Duration::minutes(Self::LIFETIME_MINUTES)),
+        );
+    }
+
+    fn get(cookies: &CookieJar<'_>) -> Option<i64> {
+        let cookie = cookies.get_private(Self::COOKIE_NAME)?;
+        let (user_id, issued_at) = cookie.value().split_once(':')?;
+        let issued_at = chrono::DateTime::from_timestamp(issued_at.parse().ok()?, 0)?;
+        let expires_at = issued_at + chrono::Duration::minutes(Self::LIFETIME_MINUTES);
+        (expires_at > chrono::offset::Utc::now())
+            .then(|| user_id.parse().ok())
+            .flatten()
+    }
+
+    fn clear(cookies: &CookieJar<'_>) {
+        cookies.remove_private(Self::COOKIE_NAME);
+    }
+}
+
+/// Every way of logging in goes through this so 2FA applies no matter the provider
+pub struct LoginFlow<'r> {
+    cookies: &'r CookieJar<'r>,
+    config: &'r TwoFactorConfig,
+    admins: &'r AdminUsers,
+    onsite: &'r OnsiteConfig,
+    client_ip: Option<IpAddr>,
+}
+
+impl<'r> LoginFlow<'r> {
+    /// Log the user in, unless they still need to pass 2FA.
+    /// Returns where to send the user instead of wherever the caller would have,
+    /// the caller should leave the redirect cookie alone in that case.
+    pub async fn login(&self, db: &mut DbPoolConnection, user: &User) -> Result<Option<Redirect>> {
+        if EmailVerification::is_pending(db, user.id).await? {
+            return Ok(Some(
+                Message::error("Please verify your email before logging in").to(LOGIN_URI),
+            ));
+        }
+        if OnsiteAccount::is_onsite(db, user.id).await? && !self.onsite.allows(self.client_ip) {
+            return Ok(Some(
+                Message::error("This account can only be used on site").to(LOGIN_URI),
+            ));
+        }
+
+        if TwoFactor::is_enabled(db, user.id).await? {
+            PendingTwoFactor::store(self.cookies, user.id);
+            return Ok(Some(
+                Message::info("Enter the code from your authenticator app to continue")
+                    .to(VERIFY_URI),
+            ));
+        }
+
+        user.login(db, self.cookies).await?;
+
+        if self.config.is_required_for(self.admins, user) {
+            Ok(Some(
+                Message::info("Admins need to set up two-factor authentication").to(SETTINGS_URI),
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Same as [`Self::login`] for users coming back from SSO or OAuth,
+    /// on-site accounts can only log in with their password
+    pub async fn login_sso(
+        &self,
+        db: &mut DbPoolConnection,
+        user: &User,
+    ) -> Result<Option<Redirect>> {
+        if OnsiteAccount::is_onsite(db, user.id).await? {
+            return Ok(Some(
+                Message::error("This account can only log in with its password").to(LOGIN_URI),
+            ));
+        }
+        self.login(db, user).await
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LoginFlow<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let rocket = req.rocket();
+        match (
+            rocket.state::<TwoFactorConfig>(),
+            rocket.state::<AdminUsers>(),
+            rocket.state::<OnsiteConfig>(),
+        ) {
+            (Some(config), Some(admins), Some(onsite)) => Outcome::Success(Self {
+                cookies: req.cookies(),
+                config,
+                admins,
+                onsite,
+                client_ip: req.client_ip(),
+            }),
+            _ => {
+                error!("Login config wasn't loaded");
+                Outcome::Error((Status::InternalServerError, ()))
+            }
+        }
+    }
+}
+
+#[get("/2fa")]
+async fn verify_get(
+    user: Option<&User>,
+    cookies: &CookieJar<'_>,
+    _token: &CsrfToken,
+) -> Result<Template, Redirect> {
+    if PendingTwoFactor::get(cookies).is_none() {
+        return Err(Message::info("Please log in again").to(LOGIN_URI));
+    }
+    let ctx = context_with_base!(user,);
+    Ok(Template::render("auth/two_factor", ctx))
+}
+
+#[derive(FromForm)]
+pub struct CodeForm<'r> {
+    pub code: &'r str,
+}
+
+#[post("/2fa", data = "<form>")]
+async fn verify_post(
+    mut db: DbConnection,
+    cookies: &CookieJar<'_>,
+    form: Form<CodeForm<'_>>,
+    _limit: RateLimited<rate_limit::Login>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let user_id = match PendingTwoFactor::get(cookies) {
+        Some(user_id) => user_id,
+        None => return Ok(Message::info("Please log in again").to(LOGIN_URI)),
+    };
+    let mut two_factor = TwoFactor::get(&mut db, user_id)
+        .await?
+        .filter(|t| t.enabled)
+        .ok_or(Status::NotFound)?;
+
+    if let Some(locked_until) = two_factor.locked_until() {
+        let minutes = (locked_until - chrono::offset::Utc::now().naive_utc()).num_minutes() + 1;
+        return Ok(
+            Message::error("Too many wrong codes, try again in {minutes} minutes")
+                .arg("minutes", minutes)
+                .to(VERIFY_URI),
+        );
+    }
+    if !two_factor.verify(&mut db, form.code).await? {
+        return Ok(Message::error("That code isn't valid, please try again").to(VERIFY_URI));
+    }
+
+    PendingTwoFactor::clear(cookies);
+    let user = User::get_or_404(&mut db, user_id).await?;
+    user.login(&mut db, cookies).await?;
+
+    let redirect = cookies
+        .get(REDIRECT_COOKIE_NAME)
+        .map(|c| c.value().to_string())
+        .unwrap_or_else(|| "/".to_string());
+    cookies.remove(Cookie::from(REDIRECT_COOKIE_NAME));
+    Ok(Redirect::to(redirect))
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Two Factor Auth", |rocket| async {
+        let config = rocket
+            .figment()
+            .extract_inner::<TwoFactorConfig>("two_factor")
+            .unwrap_or_default();
+        rocket
+            .manage(config)
+            .mount("/auth", routes![verify_get, verify_post])
+    })
+}
@@ -14,10 +14,13 @@ use crate::{
     error::prelude::*,
 };
 
-use super::sessions::Session;
+use super::{
+    password::EmailVerification,
+    sessions::Session,
+    two_factor::{TwoFactor, TwoFactorConfig},
+};
 
-#[derive(Debug, Clone, Serialize, FromFormField)]
-#[derive(Default)]
+#[derive(Debug, Clone, Serialize, FromFormField, Default)]
 pub enum ColorScheme {
     Light,
     Dark,
@@ -25,7 +28,6 @@ pub enum ColorScheme {
     UseSystem,
 }
 
-
 impl From<String> for ColorScheme {
     fn from(s: String) -> Self {
         match s.as_str() {
@@ -82,6 +84,8 @@ pub struct User {
     pub profile_picture_source: String,
     pub github_id: Option<i64>,
     pub google_id: Option<String>,
+    /// See [`crate::i18n::ClientLocale`], `None` to go by the browser
+    pub locale: Option<String>,
 }
 
 impl User {
@@ -110,6 +114,7 @@ impl User {
             created_at: chrono::offset::Utc::now().naive_utc(),
             github_id: None,
             google_id: None,
+            locale: None,
         }
     }
 
@@ -129,21 +134,20 @@ impl User {
         Ok(())
     }
 
-    async fn register<'a>(
+    pub async fn register<'a>(
         self,
         db: &mut DbPoolConnection,
         cookies: &'a CookieJar<'a>,
     ) -> Result<User> {
+        EmailVerification::release_email(db, &self.email).await?;
         let user = self.insert(db).await?;
         user.login(db, cookies).await?;
         Ok(user)
     }
 
-    pub async fn login_or_register<'a>(
-        self,
-        db: &mut DbPoolConnection,
-        cookies: &'a CookieJar<'a>,
-    ) -> Result<(User, bool)> {
+    /// Find the existing user with this SSO ID, updating their info from SSO if needed.
+    /// Returns `None` if no user with this SSO ID exists yet.
+    pub async fn find_existing(&self, db: &mut DbPoolConnection) -> Result<Option<User>> {
         let existing = sqlx::query_as!(User, "SELECT * FROM user WHERE sso_id = ?", self.sso_id)
             .fetch_optional(&mut **db)
             .await
@@ -154,6 +158,7 @@ impl User {
         if let Some(user) = existing {
             // Update the user's display name and email if they have changed
             if user.email != self.email || user.default_display_name != self.default_display_name {
+                EmailVerification::release_email(db, &self.email).await?;
                 let res = sqlx::query!(
                     "UPDATE user SET email = ?, default_display_name = ? WHERE id = ?",
                     self.email,
@@ -165,14 +170,25 @@ impl User {
 
                 res.context("Failed to update user info from SSO")?;
             }
-            user.login(db, cookies).await?;
-            Ok((user, false))
+            Ok(Some(user))
         } else {
-            let user = self.register(db, cookies).await;
-            user.map(|u| (u, true))
+            Ok(None)
         }
     }
 
+    /// Whether a verified account already uses this user's email,
+    /// unverified ones are removed when registering
+    pub async fn email_taken(&self, db: &mut DbPoolConnection) -> Result<bool> {
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM user WHERE email = ? AND id NOT IN (SELECT user_id FROM email_verification)",
+            self.email
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count > 0)
+        .with_context(|| format!("Failed to check if email {} is taken", self.email))
+    }
+
     pub async fn insert(self, db: &mut DbPoolConnection) -> Result<Self> {
         let new = sqlx::query_as!(
             User,
@@ -223,6 +239,14 @@ impl User {
 
 pub struct AdminUsers(pub Vec<String>);
 
+impl AdminUsers {
+    /// Emails are compared ignoring case, so a differently cased copy of an admin's
+    /// email can't be used to get around the list
+    pub fn contains(&self, email: &str) -> bool {
+        self.0.iter().any(|a| a.eq_ignore_ascii_case(email))
+    }
+}
+
 pub struct Admin();
 
 #[rocket::async_trait]
@@ -237,9 +261,10 @@ impl<'r> FromRequest<'r> for &'r User {
             })?;
             if let Some(token) = req.cookies().get_private(Session::TOKEN_COOKIE_NAME).map(|c| c.value().to_string()) {
                 let hash = Session::hash_token(&token);
+                // Admins viewing the site as someone else get that user, see impersonation.rs
                 let res = sqlx::query_as!(
                     User,
-                    "SELECT user.* FROM user JOIN session ON user.id = session.user_id WHERE session.token = ? AND expires_at > CURRENT_TIMESTAMP",
+                    "SELECT user.* FROM user JOIN session ON user.id = COALESCE(session.impersonating_id, session.user_id) WHERE session.token = ? AND expires_at > CURRENT_TIMESTAMP",
                     hash
                 )
                 .fetch_optional(&mut **db)
@@ -273,11 +298,31 @@ impl<'r> FromRequest<'r> for &'r Admin {
             .local_cache_async(async {
                 let user = req.guard::<&User>().await.succeeded()?;
                 let admin_users = req.guard::<&State<AdminUsers>>().await.succeeded()?;
-                if admin_users.0.contains(&user.email) {
-                    Some(Admin())
-                } else {
-                    None
+                if !admin_users.contains(&user.email) {
+                    return None;
+                }
+                let mut db = req.guard::<DbConnection>().await.succeeded()?;
+                // Nobody has shown they own the email of a self-registered account yet
+                match EmailVerification::is_pending(&mut db, user.id).await {
+                    Ok(false) => {}
+                    Ok(true) => return None,
+                    Err(why) => {
+                        error!("Couldn't check email verification for admin: {:?}", why);
+                        return None;
+                    }
+                }
+                let two_factor_config = req.guard::<&State<TwoFactorConfig>>().await.succeeded()?;
+                if two_factor_config.require_for_admins {
+                    match TwoFactor::is_enabled(&mut db, user.id).await {
+                        Ok(true) => {}
+                        Ok(false) => return None,
+                        Err(why) => {
+                            error!("Couldn't check 2FA for admin: {:?}", why);
+                            return None;
+                        }
+                    }
                 }
+                Some(Admin())
             })
             .await;
         admin_result.as_ref().or_error((Status::Forbidden, ()))
@@ -0,0 +1,223 @@
+use rocket::{form::Form, get, post, FromForm, State};
+use rocket_dyn_templates::Template;
+use serde::Serialize;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    leaderboard::LeaderboardManagerHandle,
+    messages::Message,
+    problems::{Problem, ProblemData, ProblemFilter, TestCase},
+    run::ManagerHandle,
+};
+
+use super::{BankProblem, BankRevision};
+
+#[derive(Serialize)]
+struct BankListing {
+    problem: BankProblem,
+    latest_version: i64,
+}
+
+#[get("/<contest_id>/problems/bank?<filter..>", rank = 1)]
+pub async fn add_from_bank_get(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    _admin: &Admin,
+    filter: ProblemFilter,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let mut problems = BankProblem::list(&mut db).await?;
+    let tags = ProblemFilter::all_tags(&problems);
+    filter.apply(&mut problems);
+    let mut listings = Vec::with_capacity(problems.len());
+    for problem in problems {
+        let latest_version = problem.latest_version(&mut db).await?;
+        listings.push(BankListing {
+            problem,
+            latest_version,
+        });
+    }
+    let ctx = context_with_base_authed!(user, contest, problems: listings, tags, filter);
+    Ok(Template::render("problems/bank", ctx))
+}
+
+#[derive(FromForm)]
+pub struct AddForm {
+    bank_problem_id: i64,
+    version: i64,
+}
+
+/// Make a contest problem from a bank revision, generated cases aren't kept in the bank so
+/// they have to be made again
+#[post("/<contest_id>/problems/bank", data = "<form>")]
+pub async fn add_from_bank_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    _admin: &Admin,
+    leaderboard_handle: &State<LeaderboardManagerHandle>,
+    form: Form<AddForm>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let revision = BankRevision::get_or_404(&mut db, form.bank_problem_id, form.version).await?;
+    let data = revision.data()?;
+    let problem = data.to_problem(contest_id);
+    if Problem::slug_exists(&mut db, &problem.slug, contest_id, None).await? {
+        return Ok(
+            Message::error("A problem with the name {name} is already in this contest")
+                .arg("name", &problem.name)
+                .to(&format!("/contests/{contest_id}/problems/bank")),
+        );
+    }
+    let problem = problem.insert(&mut db).await?;
+    TestCase::save_for_problem(&mut db, problem.id, data.to_cases(problem.id)).await?;
+    revision.link(&mut db, problem.id).await?;
+    let mut leaderboard_handle = leaderboard_handle.lock().await;
+    leaderboard_handle
+        .refresh_leaderboard(&mut db, &contest)
+        .await?;
+    let message = if problem.generator.is_empty() {
+        Message::success("Problem Added From The Bank")
+    } else {
+        Message::info(
+            "Problem Added From The Bank, add a reference solution and generate its cases again",
+        )
+    };
+    Ok(message.to(&format!("/contests/{contest_id}/problems/{}", problem.slug)))
+}
+
+#[get("/<contest_id>/problems/<slug>/bank")]
+pub async fn problem_bank_get(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    _admin: &Admin,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let revision = BankRevision::for_problem(&mut db, problem.id).await?;
+    let (bank_problem, latest_version) = match revision.as_ref() {
+        Some(revision) => {
+            let bank_problem = BankProblem::get(&mut db, revision.bank_problem_id)
+                .await?
+                .context("Bank revision has no problem")?;
+            let latest_version = bank_problem.latest_version(&mut db).await?;
+            (Some(bank_problem), latest_version)
+        }
+        None => (None, 0),
+    };
+    let ctx = context_with_base_authed!(
+        user,
+        contest,
+        problem,
+        revision,
+        bank_problem,
+        latest_version
+    );
+    Ok(Template::render("problems/bank-link", ctx))
+}
+
+#[derive(FromForm)]
+pub struct UpdateForm {
+    version: i64,
+}
+
+/// Replace the problem with another revision of the bank problem it came from, keeping its
+/// generated cases and its place in the contest
+#[post("/<contest_id>/problems/<slug>/bank/update", data = "<form>")]
+pub async fn problem_bank_update_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    _admin: &Admin,
+    manager: &State<ManagerHandle>,
+    form: Form<UpdateForm>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    Contest::get_or_404(&mut db, contest_id).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let url = format!("/contests/{contest_id}/problems/{slug}/bank");
+    let Some(current) = BankRevision::for_problem(&mut db, problem.id).await? else {
+        return Ok(Message::error("This problem didn't come from the bank").to(&url));
+    };
+    let revision = BankRevision::get_or_404(&mut db, current.bank_problem_id, form.version).await?;
+    let data = revision.data()?;
+    let mut updated = data.to_problem(contest_id);
+    if Problem::slug_exists(&mut db, &updated.slug, contest_id, Some(problem.id)).await? {
+        return Ok(
+            Message::error("A problem with the name {name} is already in this contest")
+                .arg("name", &updated.name)
+                .to(&url),
+        );
+    }
+    updated.id = problem.id;
+    updated.ordering = problem.ordering;
+    updated.label = problem.label.clone();
+    updated.update(&mut db).await?;
+    let generated = TestCase::get_for_problem(&mut db, problem.id)
+        .await?
+        .into_iter()
+        .filter(|c| c.generated)
+        .collect();
+    let test_cases = TestCase::combine(data.to_cases(problem.id), generated);
+    TestCase::save_for_problem(&mut db, problem.id, test_cases).await?;
+    revision.link(&mut db, problem.id).await?;
+    let mut manager = manager.lock().await;
+    manager.update_problem(problem.id).await;
+    Ok(Message::success("Problem Updated To Version {version}")
+        .arg("version", revision.version)
+        .to(&format!(
+            "/contests/{contest_id}/problems/{}/bank",
+            updated.slug
+        )))
+}
+
+#[derive(FromForm)]
+pub struct SaveForm<'r> {
+    #[field(validate = len(..=200))]
+    note: &'r str,
+}
+
+/// Save the problem as it is now to the bank, as a new version of the bank problem it came from
+/// or as a new bank problem
+#[post("/<contest_id>/problems/<slug>/bank/save", data = "<form>")]
+pub async fn problem_bank_save_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    _admin: &Admin,
+    form: Form<SaveForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    Contest::get_or_404(&mut db, contest_id).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let data = ProblemData::get_for_problem(&mut db, &problem).await?;
+    let note = form.note.trim();
+    let revision = match BankRevision::for_problem(&mut db, problem.id).await? {
+        Some(current) => {
+            let mut bank_problem = BankProblem::get(&mut db, current.bank_problem_id)
+                .await?
+                .context("Bank revision has no problem")?;
+            bank_problem
+                .add_revision(&mut db, &data, note, user.id)
+                .await?
+        }
+        None => BankProblem::create(&mut db, &data, note, user.id).await?.1,
+    };
+    revision.link(&mut db, problem.id).await?;
+    Ok(Message::success("Saved To The Bank As Version {version}")
+        .arg("version", revision.version)
+        .to(&format!("/contests/{contest_id}/problems/{slug}/bank")))
+}
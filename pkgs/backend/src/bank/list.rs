@@ -0,0 +1,26 @@
+use rocket::get;
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    problems::ProblemFilter,
+};
+
+use super::BankProblem;
+
+#[get("/?<filter..>")]
+pub async fn bank_list(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    filter: ProblemFilter,
+) -> ResultResponse<Template> {
+    let mut problems = BankProblem::list(&mut db).await?;
+    let tags = ProblemFilter::all_tags(&problems);
+    filter.apply(&mut problems);
+    let ctx = context_with_base_authed!(user, problems, tags, filter);
+    Ok(Template::render("bank/list", ctx))
+}
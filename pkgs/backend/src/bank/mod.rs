@@ -0,0 +1,338 @@
+//! A problem bank shared by every contest. Bank problems keep each saved version as a revision,
+//! contests add a problem from a revision and remember which one so it can be updated later.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use rocket::{fairing::AdHoc, http::Status, routes, FromForm};
+use serde::Serialize;
+
+use crate::{
+    db::DbPoolConnection,
+    error::prelude::*,
+    problems::{ProblemData, ProblemDifficulty, Tagged},
+    run::CodeInfo,
+    template::TemplatedForm,
+};
+
+mod contest;
+mod list;
+mod new;
+mod view;
+
+#[derive(Serialize, Clone)]
+pub struct BankProblem {
+    pub id: i64,
+    /// The name of the latest revision
+    pub name: String,
+    /// Normalized, see [`crate::problems::ProblemFilter`]
+    pub tags: String,
+    pub difficulty: ProblemDifficulty,
+    pub created_by: Option<i64>,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+}
+
+impl Tagged for BankProblem {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn tags(&self) -> &str {
+        &self.tags
+    }
+
+    fn difficulty(&self) -> ProblemDifficulty {
+        self.difficulty
+    }
+}
+
+impl BankProblem {
+    pub async fn list(db: &mut DbPoolConnection) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            BankProblem,
+            "SELECT * FROM bank_problem ORDER BY name COLLATE NOCASE"
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Error fetching bank problems")
+    }
+
+    pub async fn get(db: &mut DbPoolConnection, id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(BankProblem, "SELECT * FROM bank_problem WHERE id = ?", id)
+            .fetch_optional(&mut **db)
+            .await
+            .with_context(|| format!("Error fetching bank problem {id}"))
+    }
+
+    pub async fn get_or_404(db: &mut DbPoolConnection, id: i64) -> ResultResponse<Self> {
+        Self::get(db, id).await?.ok_or(Status::NotFound.into())
+    }
+
+    /// Put a problem in the bank with `data` as its first revision, it starts with the data's tags
+    pub async fn create(
+        db: &mut DbPoolConnection,
+        data: &ProblemData,
+        note: &str,
+        created_by: i64,
+    ) -> Result<(Self, BankRevision)> {
+        let name = data.name();
+        let tags = data.tags();
+        let difficulty = data.difficulty();
+        let mut problem = sqlx::query_as!(
+            BankProblem,
+            "INSERT INTO bank_problem (name, tags, difficulty, created_by) VALUES (?, ?, ?, ?) RETURNING *",
+            name,
+            tags,
+            difficulty,
+            created_by
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Error inserting bank problem")?;
+        let revision = problem.add_revision(db, data, note, created_by).await?;
+        Ok((problem, revision))
+    }
+
+    pub async fn set_tags(
+        &mut self,
+        db: &mut DbPoolConnection,
+        tags: String,
+        difficulty: ProblemDifficulty,
+    ) -> Result {
+        sqlx::query!(
+            "UPDATE bank_problem SET tags = ?, difficulty = ? WHERE id = ?",
+            tags,
+            difficulty,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Error updating tags of bank problem {}", self.id))?;
+        self.tags = tags;
+        self.difficulty = difficulty;
+        Ok(())
+    }
+
+    pub async fn delete(self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!("DELETE FROM bank_problem WHERE id = ?", self.id)
+            .execute(&mut **db)
+            .await
+            .map(|_| ())
+            .with_context(|| format!("Error deleting bank problem {}", self.id))
+    }
+
+    /// Save `data` as the next version, the bank problem takes its name
+    pub async fn add_revision(
+        &mut self,
+        db: &mut DbPoolConnection,
+        data: &ProblemData,
+        note: &str,
+        created_by: i64,
+    ) -> Result<BankRevision> {
+        let json = serde_json::to_string(data).context("Couldn't serialize problem data")?;
+        let revision = sqlx::query_as!(
+            BankRevision,
+            "INSERT INTO bank_revision (bank_problem_id, version, data, note, created_by) VALUES (?, (SELECT COALESCE(MAX(version), 0) + 1 FROM bank_revision WHERE bank_problem_id = ?), ?, ?, ?) RETURNING *",
+            self.id,
+            self.id,
+            json,
+            note,
+            created_by
+        )
+        .fetch_one(&mut **db)
+        .await
+        .with_context(|| format!("Error adding a revision to bank problem {}", self.id))?;
+        let name = data.name();
+        sqlx::query!(
+            "UPDATE bank_problem SET name = ? WHERE id = ?",
+            name,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Error renaming bank problem {}", self.id))?;
+        self.name = name.to_string();
+        Ok(revision)
+    }
+
+    /// Newest first
+    pub async fn revisions(&self, db: &mut DbPoolConnection) -> Result<Vec<BankRevision>> {
+        sqlx::query_as!(
+            BankRevision,
+            "SELECT * FROM bank_revision WHERE bank_problem_id = ? ORDER BY version DESC",
+            self.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Error fetching revisions of bank problem {}", self.id))
+    }
+
+    pub async fn latest_version(&self, db: &mut DbPoolConnection) -> Result<i64> {
+        sqlx::query!(
+            "SELECT COALESCE(MAX(version), 0) AS \"version!: i64\" FROM bank_revision WHERE bank_problem_id = ?",
+            self.id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.version)
+        .with_context(|| format!("Error fetching latest version of bank problem {}", self.id))
+    }
+
+    /// Contest problems made from any revision of this one
+    pub async fn usages(&self, db: &mut DbPoolConnection) -> Result<Vec<BankUsage>> {
+        sqlx::query_as!(
+            BankUsage,
+            "SELECT problem.contest_id, contest.name AS contest_name, problem.name AS problem_name, problem.slug, bank_revision.version FROM bank_usage JOIN bank_revision ON bank_revision.id = bank_usage.revision_id JOIN problem ON problem.id = bank_usage.problem_id JOIN contest ON contest.id = problem.contest_id WHERE bank_revision.bank_problem_id = ? ORDER BY contest.start_time DESC",
+            self.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Error fetching usages of bank problem {}", self.id))
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct BankRevision {
+    pub id: i64,
+    pub bank_problem_id: i64,
+    pub version: i64,
+    /// JSON in the problem export format, see [`BankRevision::data`]
+    #[serde(skip_serializing)]
+    pub data: String,
+    pub note: String,
+    pub created_by: Option<i64>,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+}
+
+impl BankRevision {
+    pub async fn get(
+        db: &mut DbPoolConnection,
+        bank_problem_id: i64,
+        version: i64,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            BankRevision,
+            "SELECT * FROM bank_revision WHERE bank_problem_id = ? AND version = ?",
+            bank_problem_id,
+            version
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| {
+            format!("Error fetching version {version} of bank problem {bank_problem_id}")
+        })
+    }
+
+    pub async fn get_or_404(
+        db: &mut DbPoolConnection,
+        bank_problem_id: i64,
+        version: i64,
+    ) -> ResultResponse<Self> {
+        Self::get(db, bank_problem_id, version)
+            .await?
+            .ok_or(Status::NotFound.into())
+    }
+
+    /// The revision a contest's problem was made from, if it came from the bank
+    pub async fn for_problem(db: &mut DbPoolConnection, problem_id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            BankRevision,
+            "SELECT bank_revision.* FROM bank_revision JOIN bank_usage ON bank_usage.revision_id = bank_revision.id WHERE bank_usage.problem_id = ?",
+            problem_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Error fetching bank revision of problem {problem_id}"))
+    }
+
+    pub fn data(&self) -> Result<ProblemData> {
+        serde_json::from_str(&self.data)
+            .with_context(|| format!("Bank revision {} has invalid data", self.id))
+    }
+
+    /// Remember a contest's problem now matches this revision
+    pub async fn link(&self, db: &mut DbPoolConnection, problem_id: i64) -> Result {
+        sqlx::query!(
+            "INSERT OR REPLACE INTO bank_usage (problem_id, revision_id) VALUES (?, ?)",
+            problem_id,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| {
+            format!(
+                "Error linking problem {problem_id} to bank revision {}",
+                self.id
+            )
+        })
+    }
+}
+
+/// A contest problem made from a bank problem
+#[derive(Serialize)]
+pub struct BankUsage {
+    pub contest_id: i64,
+    pub contest_name: String,
+    pub problem_name: String,
+    pub slug: String,
+    pub version: i64,
+}
+
+/// A revision's data as JSON pasted into a form, like a problem import
+#[derive(FromForm)]
+struct RevisionForm<'r> {
+    data: &'r str,
+    #[field(validate = len(..=200))]
+    note: &'r str,
+}
+
+struct RevisionFormTemplate;
+
+impl TemplatedForm for RevisionFormTemplate {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        HashMap::from_iter([
+            ("data".to_string(), "".to_string()),
+            ("note".to_string(), "".to_string()),
+        ])
+    }
+}
+
+/// Problem JSON from a form, checked to work on this server
+fn parse_data(data: &str, info: &CodeInfo) -> Result<ProblemData, String> {
+    let mut data = serde_json::from_str::<ProblemData>(data)
+        .map_err(|e| format!("Invalid JSON passed: {e}"))?;
+    data.check(info)?;
+    Ok(data)
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Problem Bank", |rocket| async {
+        rocket
+            .mount(
+                "/bank",
+                routes![
+                    list::bank_list,
+                    new::new_bank_problem_get,
+                    new::new_bank_problem_post,
+                    view::view_bank_problem,
+                    view::bank_tags_post,
+                    view::bank_revision_post,
+                    view::bank_revision_export,
+                    view::delete_bank_problem_post,
+                ],
+            )
+            .mount(
+                "/contests",
+                routes![
+                    contest::add_from_bank_get,
+                    contest::add_from_bank_post,
+                    contest::problem_bank_get,
+                    contest::problem_bank_update_post,
+                    contest::problem_bank_save_post,
+                ],
+            )
+    })
+}
@@ -0,0 +1,59 @@
+use rocket::{
+    form::{Contextual, Error, Form},
+    get, post, State,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    run::CodeInfo,
+    template::FormTemplateObject,
+};
+
+use super::{parse_data, BankProblem, RevisionForm, RevisionFormTemplate};
+
+#[get("/new")]
+pub async fn new_bank_problem_get(
+    user: &User,
+    _admin: &Admin,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let form = FormTemplateObject::get(RevisionFormTemplate);
+    let ctx = context_with_base_authed!(user, form);
+    Ok(Template::render("bank/new", ctx))
+}
+
+#[post("/new", data = "<form>")]
+pub async fn new_bank_problem_post(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    info: &State<CodeInfo>,
+    _token: &VerifyCsrfToken,
+    mut form: Form<Contextual<'_, RevisionForm<'_>>>,
+) -> FormResponse {
+    if let Some(value) = form.value.as_ref() {
+        match parse_data(value.data, info) {
+            Ok(data) => {
+                let (problem, _) =
+                    BankProblem::create(&mut db, &data, value.note.trim(), user.id).await?;
+                return Ok(Message::success("Problem Added To The Bank")
+                    .to(&format!("/bank/{}", problem.id)));
+            }
+            Err(why) => {
+                form.context
+                    .push_error(Error::validation(why).with_name("data"));
+            }
+        }
+    }
+    let form = FormTemplateObject::from_rocket_context(RevisionFormTemplate, &form.context);
+    let ctx = context_with_base_authed!(user, form);
+    Err(Template::render("bank/new", ctx).into())
+}
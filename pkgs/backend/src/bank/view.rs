@@ -0,0 +1,106 @@
+use rocket::{form::Form, get, post, serde::json::Json, FromForm, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    problems::{check_tags, normalize_tags, ProblemData, ProblemDifficulty},
+    rate_limit::{self, RateLimited},
+    run::CodeInfo,
+};
+
+use super::{parse_data, BankProblem, BankRevision, RevisionForm};
+
+#[get("/<id>")]
+pub async fn view_bank_problem(
+    mut db: DbConnection,
+    id: i64,
+    user: &User,
+    _admin: &Admin,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let problem = BankProblem::get_or_404(&mut db, id).await?;
+    let revisions = problem.revisions(&mut db).await?;
+    let usages = problem.usages(&mut db).await?;
+    let ctx = context_with_base_authed!(user, problem, revisions, usages);
+    Ok(Template::render("bank/view", ctx))
+}
+
+#[derive(FromForm)]
+pub struct TagsForm<'r> {
+    #[field(validate = check_tags())]
+    tags: &'r str,
+    difficulty: ProblemDifficulty,
+}
+
+#[post("/<id>/tags", data = "<form>")]
+pub async fn bank_tags_post(
+    mut db: DbConnection,
+    id: i64,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    form: Form<TagsForm<'_>>,
+) -> FormResponse {
+    let mut problem = BankProblem::get_or_404(&mut db, id).await?;
+    problem
+        .set_tags(&mut db, normalize_tags(form.tags), form.difficulty)
+        .await?;
+    Ok(Message::success("Tags Updated").to(&format!("/bank/{id}")))
+}
+
+/// Contests keep the revision they added until they're updated, so this doesn't change them
+#[post("/<id>/revisions", data = "<form>")]
+pub async fn bank_revision_post(
+    mut db: DbConnection,
+    id: i64,
+    user: &User,
+    _admin: &Admin,
+    info: &State<CodeInfo>,
+    _token: &VerifyCsrfToken,
+    form: Form<RevisionForm<'_>>,
+) -> FormResponse {
+    let url = format!("/bank/{id}");
+    let mut problem = BankProblem::get_or_404(&mut db, id).await?;
+    let data = match parse_data(form.data, info) {
+        Ok(data) => data,
+        Err(why) => return Ok(Message::error(&why).to(&url)),
+    };
+    let revision = problem
+        .add_revision(&mut db, &data, form.note.trim(), user.id)
+        .await?;
+    Ok(Message::success("Version {version} Saved")
+        .arg("version", revision.version)
+        .to(&url))
+}
+
+/// In the same format as a problem export, so it can be imported into a contest by hand
+#[get("/<id>/revisions/<version>/export")]
+pub async fn bank_revision_export(
+    mut db: DbConnection,
+    id: i64,
+    version: i64,
+    _admin: &Admin,
+    _limit: RateLimited<rate_limit::Api>,
+) -> ResultResponse<Json<ProblemData>> {
+    let revision = BankRevision::get_or_404(&mut db, id, version).await?;
+    Ok(Json(revision.data()?))
+}
+
+/// Problems already added to contests stay in them, they just stop being linked to the bank
+#[post("/<id>/delete")]
+pub async fn delete_bank_problem_post(
+    mut db: DbConnection,
+    id: i64,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let problem = BankProblem::get_or_404(&mut db, id).await?;
+    problem.delete(&mut db).await?;
+    Ok(Message::success("Problem Removed From The Bank").to("/bank"))
+}
@@ -0,0 +1,108 @@
+//! The announcement banner shown across the top of every page, kept in memory so
+//! [`crate::context_with_base`] can add it without a database query per request
+
+use std::sync::RwLock;
+
+use chrono::NaiveDateTime;
+use log::{error, warn};
+use rocket::fairing::AdHoc;
+use rocket_db_pools::Database as R_Database;
+
+use crate::{
+    db::{Database, DbPool, DbPoolConnection},
+    error::prelude::*,
+};
+
+/// The kinds of banner, they're shown in different colors
+pub const BANNER_KINDS: [&str; 3] = ["info", "warning", "maintenance"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Banner {
+    pub kind: String,
+    pub message: String,
+    pub starts_at: Option<NaiveDateTime>,
+    pub ends_at: Option<NaiveDateTime>,
+}
+
+static CURRENT: RwLock<Option<Banner>> = RwLock::new(None);
+
+impl Banner {
+    pub fn is_active(&self) -> bool {
+        let now = chrono::offset::Utc::now().naive_utc();
+        self.starts_at.is_none_or(|s| s <= now) && self.ends_at.is_none_or(|e| now < e)
+    }
+
+    /// The banner as it's saved, shown or not
+    pub fn current() -> Option<Self> {
+        CURRENT.read().ok().and_then(|b| b.clone())
+    }
+
+    async fn get(db: &mut DbPoolConnection) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Banner,
+            "SELECT kind, message, starts_at, ends_at FROM banner WHERE id = 1"
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .context("Error fetching banner")
+    }
+
+    pub async fn save(self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!(
+            "INSERT INTO banner (id, kind, message, starts_at, ends_at) VALUES (1, ?, ?, ?, ?) ON CONFLICT(id) DO UPDATE SET kind = excluded.kind, message = excluded.message, starts_at = excluded.starts_at, ends_at = excluded.ends_at",
+            self.kind,
+            self.message,
+            self.starts_at,
+            self.ends_at
+        )
+        .execute(&mut **db)
+        .await
+        .context("Error saving banner")?;
+        set_current(Some(self));
+        Ok(())
+    }
+
+    pub async fn clear(db: &mut DbPoolConnection) -> Result {
+        sqlx::query!("DELETE FROM banner")
+            .execute(&mut **db)
+            .await
+            .context("Error clearing banner")?;
+        set_current(None);
+        Ok(())
+    }
+}
+
+fn set_current(banner: Option<Banner>) {
+    match CURRENT.write() {
+        Ok(mut current) => *current = banner,
+        Err(e) => error!("Couldn't update the banner: {e}"),
+    }
+}
+
+async fn load(pool: &DbPool) -> Result {
+    let mut conn = pool
+        .acquire()
+        .await
+        .context("Couldn't get db connection for loading the banner")?;
+    set_current(Banner::get(&mut conn).await?);
+    Ok(())
+}
+
+/// The banner to show right now, if there is one
+pub fn active() -> Option<Banner> {
+    Banner::current().filter(Banner::is_active)
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_liftoff("Announcement Banner", |rocket| {
+        Box::pin(async move {
+            let Some(db) = Database::fetch(rocket) else {
+                error!("Couldn't load the banner, no database");
+                return;
+            };
+            if let Err(why) = load(&db.0).await {
+                warn!("Couldn't load the banner: {why:?}");
+            }
+        })
+    })
+}
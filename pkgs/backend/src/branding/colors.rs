@@ -38,6 +38,9 @@ pub struct ColorConfig {
     #[serde(default = "text")]
     /// Text color of the website
     pub text: String,
+    #[serde(default)]
+    /// Colors to use instead in the dark theme, any left out are the same as the light theme
+    pub dark: DarkColorConfig,
 }
 
 impl Default for ColorConfig {
@@ -48,10 +51,22 @@ impl Default for ColorConfig {
             accent: accent(),
             background: background(),
             text: text(),
+            dark: DarkColorConfig::default(),
         }
     }
 }
 
+/// Both themes are generated from the same colors by default, lightened for the light theme and
+/// darkened for the dark one, these replace them in the dark theme when that doesn't look right
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DarkColorConfig {
+    pub primary: Option<String>,
+    pub secondary: Option<String>,
+    pub accent: Option<String>,
+    pub background: Option<String>,
+    pub text: Option<String>,
+}
+
 const COLOR_SCALE: [(u16, i8); 11] = [
     (50, 90),
     (100, 70),
@@ -83,7 +98,7 @@ fn lighten_or_darken(color: &Color, amount: f64) -> Color {
     }
 }
 
-fn make_props(name: &str, color: &Color, mul: f64) -> Vec<String> {
+fn make_shades(name: &str, color: &Color, mul: f64) -> Vec<(u16, String)> {
     COLOR_SCALE
         .into_iter()
         .map(|(shade, light)| {
@@ -96,11 +111,18 @@ fn make_props(name: &str, color: &Color, mul: f64) -> Vec<String> {
                 *color
             };
             let color = lighten_or_darken(&color, (light as f64) * mul);
-            format!("--{}-{}:{};", name, shade, color.hex())
+            (shade, color.hex())
         })
         .collect()
 }
 
+fn make_props(name: &str, color: &Color, mul: f64) -> Vec<String> {
+    make_shades(name, color, mul)
+        .into_iter()
+        .map(|(shade, hex)| format!("--{}-{}:{};", name, shade, hex))
+        .collect()
+}
+
 fn make_theme(colors: &[(&str, Color)], mul: f64) -> String {
     colors
         .iter()
@@ -108,6 +130,15 @@ fn make_theme(colors: &[(&str, Color)], mul: f64) -> String {
         .collect()
 }
 
+/// One color in every shade, for previewing the themes
+#[derive(Debug, Serialize)]
+pub struct PaletteRow {
+    name: &'static str,
+    /// `(shade, hex)`, e.g. `(50, "#f5eef6")`
+    light: Vec<(u16, String)>,
+    dark: Vec<(u16, String)>,
+}
+
 const CSS_TEMPLATE: &str =
     ":root{@light}:root.dark{@dark}@media(prefers-color-scheme:dark){:root.system{@dark}}";
 
@@ -123,9 +154,32 @@ impl ColorConfig {
         let background =
             Color::from_str(&self.background).context("Failed to parse background color")?;
         let text = Color::from_str(&self.text).context("Failed to parse text color")?;
+
+        let dark_or = |name: &str, color: &Option<String>, light: Color| -> Result<Color> {
+            match color {
+                Some(c) => {
+                    Color::from_str(c).with_context(|| format!("Failed to parse dark {name} color"))
+                }
+                None => Ok(light),
+            }
+        };
+        let dark = [
+            ("primary", dark_or("primary", &self.dark.primary, primary)?),
+            (
+                "secondary",
+                dark_or("secondary", &self.dark.secondary, secondary)?,
+            ),
+            ("accent", dark_or("accent", &self.dark.accent, accent)?),
+            (
+                "background",
+                dark_or("background", &self.dark.background, background)?,
+            ),
+            ("text", dark_or("text", &self.dark.text, text)?),
+        ];
+
         let theme_color = (
             lighten_or_darken(&background, THEME_COLOR_AMOUNT).hex(),
-            lighten_or_darken(&background, -THEME_COLOR_AMOUNT).hex(),
+            lighten_or_darken(&dark[3].1, -THEME_COLOR_AMOUNT).hex(),
         );
         Ok(ParsedColorConfig {
             primary,
@@ -133,6 +187,7 @@ impl ColorConfig {
             accent,
             background,
             text,
+            dark,
             theme_color,
         })
     }
@@ -145,23 +200,40 @@ pub struct ParsedColorConfig {
     pub accent: Color,
     pub background: Color,
     pub text: Color,
+    /// The colors the dark theme is made from, see [`DarkColorConfig`]
+    dark: [(&'static str, Color); 5],
     // Light, Dark
     pub theme_color: (String, String),
 }
 
 impl ParsedColorConfig {
-    pub fn generate_theme_css(&self) -> String {
-        let colors = [
+    fn light_colors(&self) -> [(&'static str, Color); 5] {
+        [
             ("primary", self.primary),
             ("secondary", self.secondary),
             ("accent", self.accent),
             ("background", self.background),
             ("text", self.text),
-        ];
-        let light = make_theme(&colors, 1.0);
-        let dark = make_theme(&colors, -1.0);
+        ]
+    }
+
+    pub fn generate_theme_css(&self) -> String {
+        let light = make_theme(&self.light_colors(), 1.0);
+        let dark = make_theme(&self.dark, -1.0);
         CSS_TEMPLATE
             .replace("@light", &light)
             .replace("@dark", &dark)
     }
+
+    pub fn palettes(&self) -> Vec<PaletteRow> {
+        self.light_colors()
+            .iter()
+            .zip(self.dark.iter())
+            .map(|((name, light), (_, dark))| PaletteRow {
+                name,
+                light: make_shades(name, light, 1.0),
+                dark: make_shades(name, dark, -1.0),
+            })
+            .collect()
+    }
 }
@@ -7,6 +7,7 @@ mod icon;
 pub mod image;
 mod meta;
 
+pub use colors::ParsedColorConfig;
 pub use meta::SiteMetaInfo;
 use rocket::fairing::AdHoc;
 
@@ -32,6 +33,18 @@ enum FooterItem {
         #[serde(default)]
         new_tab: bool,
     },
+    /// One of the pages admins make at `/pages`
+    Page {
+        text: String,
+        slug: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NavbarPage {
+    text: String,
+    /// Slug of one of the pages admins make at `/pages`
+    slug: String,
 }
 
 fn default_name() -> String {
@@ -70,6 +83,9 @@ pub struct BrandingConfig {
     /// Text to show next to logo in the navbar, defaults to `name`
     navbar_brand_text: Option<String>,
     #[serde(default)]
+    /// Pages to link in the navbar after the built-in links, in order
+    navbar_pages: Vec<NavbarPage>,
+    #[serde(default)]
     /// Configuration for the homepage
     homepage: HomepageConfig,
     #[serde(default = "default_sso_name")]
@@ -79,6 +95,10 @@ pub struct BrandingConfig {
     #[serde(default)]
     /// Items to show in the footer, ordered from left to right
     footer_items: Vec<FooterItem>,
+    #[serde(default)]
+    /// Render math in markdown to MathML on the server instead of with KaTeX in the browser,
+    /// so it shows without JS and prints properly
+    pub server_side_math: bool,
 }
 
 impl Default for BrandingConfig {
@@ -91,8 +111,10 @@ impl Default for BrandingConfig {
             images: ImageConfig::default(),
             homepage: HomepageConfig::default(),
             navbar_brand_text: None,
+            navbar_pages: Vec::new(),
             sso_name: default_sso_name(),
             footer_items: Vec::new(),
+            server_side_math: false,
         }
     }
 }
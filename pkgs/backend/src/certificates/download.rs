@@ -0,0 +1,95 @@
+use rocket::{
+    get,
+    http::{ContentType, Header, Status},
+    Responder, State,
+};
+
+use crate::{
+    auth::users::{Admin, User},
+    contests::{Contest, Participant},
+    db::DbConnection,
+    error::prelude::*,
+    leaderboard::LeaderboardManagerHandle,
+    zip::ZipWriter,
+};
+
+use super::{document, results_final, CertificateTemplate, CertificatesConfig, Standings};
+
+#[derive(Responder)]
+pub struct Download {
+    inner: (ContentType, Vec<u8>),
+    disposition: Header<'static>,
+}
+
+impl Download {
+    fn new(content_type: ContentType, file_name: &str, body: Vec<u8>) -> Self {
+        Self {
+            inner: (content_type, body),
+            disposition: Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        }
+    }
+}
+
+/// The user's own certificate, once the contest is over for everyone
+#[get("/<contest_id>/certificate")]
+pub async fn certificate(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    config: &State<CertificatesConfig>,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+) -> ResultResponse<Download> {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let template = CertificateTemplate::get(&mut db, contest_id).await?;
+    if !template.enabled || !results_final(&mut db, &contest).await? {
+        return Err(Status::NotFound.into());
+    }
+    let participant = Participant::get(&mut db, contest_id, user.id)
+        .await?
+        .filter(Participant::competes)
+        .ok_or(Status::NotFound)?;
+    let standings = Standings::get(&mut db, leaderboard_manager, &contest).await?;
+    let values = standings
+        .values_for(&contest, participant.p_id)
+        .ok_or(Status::NotFound)?;
+    let (content_type, ext, body) = document(config, template.render(&values)).await?;
+    let file_name = format!("{}-certificate.{ext}", slug::slugify(&contest.name));
+    Ok(Download::new(content_type, &file_name, body))
+}
+
+/// Every participant's certificate in one zip, named by rank so they print in order
+#[get("/<contest_id>/admin/certificates/download")]
+pub async fn certificates_zip(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    config: &State<CertificatesConfig>,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+) -> ResultResponse<Download> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    if !results_final(&mut db, &contest).await? {
+        return Err(Status::Conflict.into());
+    }
+    let template = CertificateTemplate::get(&mut db, contest_id).await?;
+    let standings = Standings::get(&mut db, leaderboard_manager, &contest).await?;
+    let mut zip = ZipWriter::new(chrono::offset::Utc::now().naive_utc());
+    for (entry, rank) in standings.ranked() {
+        let Some(values) = standings.values_for(&contest, entry.p_id) else {
+            continue;
+        };
+        let (_, ext, body) = document(config, template.render(&values)).await?;
+        let name = format!(
+            "{rank:03}-{}-{}.{ext}",
+            slug::slugify(entry.user.display_name()),
+            entry.p_id
+        );
+        zip.add(&name, &body)?;
+    }
+    let file_name = format!("{}-certificates.zip", slug::slugify(&contest.name));
+    Ok(Download::new(ContentType::ZIP, &file_name, zip.finish()?))
+}
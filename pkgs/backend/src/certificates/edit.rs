@@ -0,0 +1,83 @@
+use rocket::{form::Form, get, http::ContentType, post, response::Redirect, FromForm};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+};
+
+use super::{results_final, CertificateTemplate, CertificateValues};
+
+const MAX_TEMPLATE_LENGTH: usize = 500_000;
+
+#[get("/<contest_id>/admin/certificates")]
+pub async fn certificates_get(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let certificate = CertificateTemplate::get(&mut db, contest_id).await?;
+    let results_final = results_final(&mut db, &contest).await?;
+    let ctx = context_with_base_authed!(user, contest, certificate, results_final);
+    Ok(Template::render("contests/admin/certificates", ctx))
+}
+
+#[derive(FromForm)]
+pub struct CertificateForm<'r> {
+    enabled: bool,
+    template: &'r str,
+}
+
+#[post("/<contest_id>/admin/certificates", data = "<form>")]
+pub async fn certificates_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    form: Form<CertificateForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let url = format!("/contests/{contest_id}/admin/certificates");
+    let template = form.template.trim();
+    if !template.starts_with("<svg") && !template.starts_with("<?xml") {
+        return Ok(Message::error("The template has to be an SVG").to(&url));
+    }
+    if template.len() > MAX_TEMPLATE_LENGTH {
+        return Ok(Message::error("The template is too large").to(&url));
+    }
+    CertificateTemplate {
+        contest_id,
+        enabled: form.enabled,
+        template: template.to_string(),
+    }
+    .save(&mut db)
+    .await?;
+    Ok(Message::success("Certificate saved").to(&url))
+}
+
+/// The saved template filled in with made up values
+#[get("/<contest_id>/admin/certificates/preview")]
+pub async fn certificate_preview(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+) -> ResultResponse<(ContentType, String)> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let certificate = CertificateTemplate::get(&mut db, contest_id).await?;
+    let svg = certificate.render(&CertificateValues::sample(&contest));
+    Ok((ContentType::SVG, svg))
+}
@@ -0,0 +1,225 @@
+use rocket::{fairing::AdHoc, http::ContentType, routes};
+
+use crate::{
+    contests::{Contest, Participant},
+    db::DbPoolConnection,
+    error::prelude::*,
+    leaderboard::{ranks, LeaderboardEntry, LeaderboardManagerHandle},
+    pdf,
+};
+
+mod download;
+mod edit;
+
+const DEFAULT_TEMPLATE: &str = include_str!("default.svg");
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CertificatesConfig {
+    /// Command that reads an SVG on stdin and writes a PDF to stdout, e.g.
+    /// `["rsvg-convert", "--format", "pdf"]`. When unset certificates are handed out as SVGs.
+    pub pdf_command: Option<Vec<String>>,
+}
+
+/// A contest's certificate, an SVG with `{{placeholders}}` filled in per participant
+#[derive(Serialize, Debug, Clone)]
+pub struct CertificateTemplate {
+    pub contest_id: i64,
+    pub enabled: bool,
+    pub template: String,
+}
+
+impl CertificateTemplate {
+    /// The contest's template, or the built in one if it was never customized
+    pub async fn get(db: &mut DbPoolConnection, contest_id: i64) -> Result<Self> {
+        let template = sqlx::query_as!(
+            CertificateTemplate,
+            "SELECT * FROM certificate_template WHERE contest_id = ?",
+            contest_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get certificate template for contest {contest_id}"))?;
+        Ok(template.unwrap_or_else(|| Self {
+            contest_id,
+            enabled: false,
+            template: DEFAULT_TEMPLATE.to_string(),
+        }))
+    }
+
+    pub async fn save(&self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!(
+            "INSERT INTO certificate_template (contest_id, enabled, template) VALUES (?, ?, ?)
+            ON CONFLICT(contest_id) DO UPDATE SET enabled = excluded.enabled, template = excluded.template",
+            self.contest_id,
+            self.enabled,
+            self.template
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| {
+            format!(
+                "Failed to save certificate template for contest {}",
+                self.contest_id
+            )
+        })
+    }
+
+    pub fn render(&self, values: &CertificateValues) -> String {
+        [
+            ("name", values.name.as_str()),
+            ("contest", values.contest.as_str()),
+            ("rank", values.rank.as_str()),
+            ("participants", values.participants.as_str()),
+            ("solved", values.solved.as_str()),
+            ("problems", values.problems.as_str()),
+            ("date", values.date.as_str()),
+        ]
+        .into_iter()
+        .fold(self.template.clone(), |svg, (key, value)| {
+            svg.replace(&format!("{{{{{key}}}}}"), &escape_xml(value))
+        })
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+/// What goes in each placeholder
+pub struct CertificateValues {
+    pub name: String,
+    pub contest: String,
+    pub rank: String,
+    pub participants: String,
+    pub solved: String,
+    pub problems: String,
+    pub date: String,
+}
+
+impl CertificateValues {
+    /// Filler for previewing a template
+    pub fn sample(contest: &Contest) -> Self {
+        Self {
+            name: "Ada Lovelace".to_string(),
+            contest: contest.name.clone(),
+            rank: ordinal(1),
+            participants: "42".to_string(),
+            solved: "5".to_string(),
+            problems: "6".to_string(),
+            date: contest.end_time.format("%B %-d, %Y").to_string(),
+        }
+    }
+}
+
+/// Final standings along with everything needed to fill in each participant's certificate
+pub struct Standings {
+    entries: Vec<LeaderboardEntry>,
+    ranks: Vec<usize>,
+    problems: usize,
+}
+
+impl Standings {
+    pub async fn get(
+        db: &mut DbPoolConnection,
+        leaderboard_manager: &LeaderboardManagerHandle,
+        contest: &Contest,
+    ) -> Result<Self> {
+        let problems = sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM problem WHERE contest_id = ?",
+            contest.id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Failed to count problems")?
+        .count as usize;
+        let mut manager = leaderboard_manager.lock().await;
+        let leaderboard = manager.get_leaderboard(db, contest).await?.clone();
+        drop(manager);
+        let entries = leaderboard.lock().await.full(db).await?;
+        let ranks = ranks(&entries);
+        Ok(Self {
+            entries,
+            ranks,
+            problems,
+        })
+    }
+
+    pub fn values_for(&self, contest: &Contest, p_id: i64) -> Option<CertificateValues> {
+        let (i, entry) = self
+            .entries
+            .iter()
+            .enumerate()
+            .find(|(_, e)| e.p_id == p_id)?;
+        Some(CertificateValues {
+            name: entry.user.display_name().to_string(),
+            contest: contest.name.clone(),
+            rank: ordinal(self.ranks[i]),
+            participants: self.entries.len().to_string(),
+            solved: entry.scores.len().to_string(),
+            problems: self.problems.to_string(),
+            date: contest.end_time.format("%B %-d, %Y").to_string(),
+        })
+    }
+
+    /// Everyone on the leaderboard in order, along with their rank
+    pub fn ranked(&self) -> impl Iterator<Item = (&LeaderboardEntry, usize)> {
+        self.entries.iter().zip(self.ranks.iter().copied())
+    }
+}
+
+/// Certificates wait until the contest is over for everyone, since someone with extra
+/// time could still change the standings
+pub async fn results_final(db: &mut DbPoolConnection, contest: &Contest) -> Result<bool> {
+    let participants = Participant::list_not_judge(db, contest.id).await?;
+    let last = participants.iter().max_by_key(|p| p.extra_minutes);
+    Ok(contest.has_ended_for(last))
+}
+
+/// Turn a rendered certificate into the file handed out, a PDF if there's a converter
+/// configured and the SVG as-is otherwise
+pub async fn document(
+    config: &CertificatesConfig,
+    svg: String,
+) -> Result<(ContentType, &'static str, Vec<u8>)> {
+    match config.pdf_command.as_deref() {
+        Some(command) if !command.is_empty() => {
+            Ok((ContentType::PDF, "pdf", pdf::convert(command, svg).await?))
+        }
+        _ => Ok((ContentType::SVG, "svg", svg.into_bytes())),
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Certificates", |rocket| async {
+        let config = rocket
+            .figment()
+            .extract_inner::<CertificatesConfig>("certificates")
+            .unwrap_or_default();
+        rocket.manage(config).mount(
+            "/contests",
+            routes![
+                download::certificate,
+                download::certificates_zip,
+                edit::certificates_get,
+                edit::certificates_post,
+                edit::certificate_preview,
+            ],
+        )
+    })
+}
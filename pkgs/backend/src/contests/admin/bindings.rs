@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use rocket::{form::Form, get, http::Status, post, response::Redirect, FromForm};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::{BindingAllowance, Contest, Participant, SessionBinding},
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+};
+
+#[derive(Serialize)]
+struct BindingRow {
+    binding: SessionBinding,
+    user_id: i64,
+    name: String,
+    allowances: Vec<BindingAllowance>,
+}
+
+#[derive(FromForm)]
+pub struct AllowForm<'r> {
+    #[field(validate = len(..=64))]
+    ip: &'r str,
+    #[field(validate = len(..=64))]
+    device: &'r str,
+}
+
+async fn assert_participant(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    p_id: i64,
+) -> ResultResponse<()> {
+    Participant::by_id(db, p_id)
+        .await?
+        .filter(|p| p.contest_id == contest_id)
+        .ok_or(Status::NotFound)?;
+    Ok(())
+}
+
+/// Where each competitor is bound to, and who's submitted from somewhere else
+#[get("/contests/<contest_id>/admin/bindings")]
+pub async fn bindings(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let mut users = Participant::list(&mut db, contest_id)
+        .await?
+        .into_iter()
+        .map(|(p, u)| (p.p_id, u))
+        .collect::<HashMap<_, _>>();
+    let mut allowances = HashMap::<i64, Vec<_>>::new();
+    for allowance in SessionBinding::allowances(&mut db, contest_id).await? {
+        allowances
+            .entry(allowance.participant_id)
+            .or_default()
+            .push(allowance);
+    }
+    let mut rows = SessionBinding::list(&mut db, contest_id)
+        .await?
+        .into_iter()
+        .filter_map(|binding| {
+            let participant_user = users.remove(&binding.participant_id)?;
+            Some(BindingRow {
+                user_id: participant_user.id,
+                name: participant_user.display_name().to_string(),
+                allowances: allowances
+                    .remove(&binding.participant_id)
+                    .unwrap_or_default(),
+                binding,
+            })
+        })
+        .collect::<Vec<_>>();
+    // Anyone with a mismatch to look at first
+    rows.sort_by_key(|r| r.binding.mismatch_at.is_none());
+    let ctx = context_with_base_authed!(user, contest, rows);
+    Ok(Template::render("contests/admin/bindings", ctx))
+}
+
+/// Let a participant submit from another address and/or device, usually the one they were just
+/// flagged or blocked from
+#[post("/contests/<contest_id>/admin/bindings/<p_id>/allow", data = "<form>")]
+pub async fn allow_binding(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    p_id: i64,
+    form: Form<AllowForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    assert_participant(&mut db, contest_id, p_id).await?;
+    let url = format!("/contests/{contest_id}/admin/bindings");
+    let ip = Some(form.ip.trim()).filter(|ip| !ip.is_empty());
+    let device = Some(form.device.trim()).filter(|d| !d.is_empty());
+    if ip.is_none() && device.is_none() {
+        return Ok(Message::error("Allow an address, a device, or both").to(&url));
+    }
+    SessionBinding::allow(&mut db, p_id, ip, device, user.id).await?;
+    Ok(Message::success("Allowed").to(&url))
+}
+
+#[post("/contests/<contest_id>/admin/bindings/<p_id>/allowances/<id>/remove")]
+pub async fn remove_allowance(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    p_id: i64,
+    id: i64,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    assert_participant(&mut db, contest_id, p_id).await?;
+    SessionBinding::remove_allowance(&mut db, p_id, id).await?;
+    Ok(Message::success("Allowance Removed").to(&format!("/contests/{contest_id}/admin/bindings")))
+}
+
+/// Unbind a participant, e.g. when they move to another computer for good
+#[post("/contests/<contest_id>/admin/bindings/<p_id>/reset")]
+pub async fn reset_binding(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    p_id: i64,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    assert_participant(&mut db, contest_id, p_id).await?;
+    SessionBinding::reset(&mut db, p_id).await?;
+    Ok(Message::success("Binding Reset").to(&format!("/contests/{contest_id}/admin/bindings")))
+}
@@ -0,0 +1,93 @@
+use log::info;
+use rocket::{form::Form, http::Status, post, response::Redirect, FromForm, State};
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        users::{Admin, User},
+    },
+    contests::Contest,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    leaderboard::LeaderboardManagerHandle,
+    messages::Message,
+};
+
+/// Save the clock and push the new times to everyone watching the leaderboard
+async fn save_clock(
+    db: &mut DbPoolConnection,
+    contest: &Contest,
+    leaderboard_handle: &LeaderboardManagerHandle,
+) -> Result {
+    contest.save_clock(db).await?;
+    let mut leaderboard_manager = leaderboard_handle.lock().await;
+    leaderboard_manager.refresh_leaderboard(db, contest).await
+}
+
+#[derive(FromForm)]
+pub struct ExtendForm {
+    #[field(validate = range(1..=1440))]
+    minutes: i64,
+}
+
+#[post("/contests/<contest_id>/admin/clock/extend", data = "<form>")]
+pub async fn extend_contest(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    leaderboard_handle: &State<LeaderboardManagerHandle>,
+    form: Form<ExtendForm>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let (mut contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let url = format!("/contests/{}/admin", contest_id);
+    if contest.has_ended() {
+        return Ok(Message::error("This contest has already ended").to(&url));
+    }
+    contest.end_time += chrono::Duration::minutes(form.minutes);
+    save_clock(&mut db, &contest, leaderboard_handle).await?;
+    info!(
+        "{} extended contest {} by {} minutes",
+        user.id, contest_id, form.minutes
+    );
+    Ok(Message::success("Contest extended by {minutes} minutes")
+        .arg("minutes", form.minutes)
+        .to(&url))
+}
+
+#[post("/contests/<contest_id>/admin/clock/<action>")]
+pub async fn control_clock(
+    mut db: DbConnection,
+    contest_id: i64,
+    action: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    leaderboard_handle: &State<LeaderboardManagerHandle>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let (mut contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let url = format!("/contests/{}/admin", contest_id);
+    let msg = match action {
+        "pause" => {
+            if !contest.is_running() {
+                return Ok(Message::error("Only a running contest can be paused").to(&url));
+            }
+            contest.pause_clock();
+            "Contest clock paused"
+        }
+        "resume" => {
+            if !contest.is_paused() {
+                return Ok(Message::error("This contest isn't paused").to(&url));
+            }
+            contest.resume_clock()?;
+            "Contest clock resumed"
+        }
+        _ => return Err(Status::NotFound.into()),
+    };
+    save_clock(&mut db, &contest, leaderboard_handle).await?;
+    info!("{} in contest {} by {}", msg, contest_id, user.id);
+    Ok(Message::success(msg).to(&url))
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use rocket::{form::Form, get, post, response::Redirect, FromForm, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    leaderboard::{Ghost, LeaderboardManagerHandle},
+    messages::Message,
+    problems::Problem,
+};
+
+#[derive(FromForm)]
+pub struct GhostImportForm<'r> {
+    csv: &'r str,
+}
+
+#[get("/contests/<contest_id>/admin/ghosts")]
+pub async fn ghosts(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let ghosts = Ghost::list(&mut db, contest_id).await?;
+    let problems = Problem::list(&mut db, contest_id).await?;
+    let ctx = context_with_base_authed!(user, contest, ghosts, problems);
+    Ok(Template::render("contests/admin/ghosts", ctx))
+}
+
+#[post("/contests/<contest_id>/admin/ghosts", data = "<form>")]
+pub async fn import_ghosts(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+    form: Form<GhostImportForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let url = format!("/contests/{contest_id}/admin/ghosts");
+    let labels = Problem::list(&mut db, contest_id)
+        .await?
+        .into_iter()
+        .map(|p| (p.label, p.id))
+        .collect::<HashMap<_, _>>();
+    let imported = match Ghost::parse_standings(form.csv, &labels) {
+        Ok(imported) => imported,
+        Err(why) => return Ok(Message::error(&why).to(&url)),
+    };
+    let count = imported.len();
+    Ghost::import(&mut db, contest_id, imported).await?;
+    let mut leaderboard_manager = leaderboard_manager.lock().await;
+    leaderboard_manager
+        .refresh_leaderboard(&mut db, &contest)
+        .await?;
+    Ok(Message::success("Imported {count} ghosts")
+        .arg("count", count)
+        .to(&url))
+}
+
+#[post("/contests/<contest_id>/admin/ghosts/clear")]
+pub async fn clear_ghosts(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    Ghost::clear(&mut db, contest_id).await?;
+    let mut leaderboard_manager = leaderboard_manager.lock().await;
+    leaderboard_manager
+        .refresh_leaderboard(&mut db, &contest)
+        .await?;
+    Ok(Message::success("Ghosts Cleared").to(&format!("/contests/{contest_id}/admin/ghosts")))
+}
@@ -1,18 +1,30 @@
-use rocket::{fairing::AdHoc, get, routes};
+use rocket::{fairing::AdHoc, get, http::Status, post, response::Redirect, routes};
 use rocket_dyn_templates::Template;
 
 use crate::{
-    auth::users::{Admin, User},
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
     context_with_base_authed,
     db::DbConnection,
     error::prelude::*,
+    messages::Message,
 };
 
 use super::Contest;
 
+mod bindings;
+mod clock;
 mod completions;
+mod ghosts;
 mod participants;
+mod problems;
+mod rejudge;
 mod runs;
+mod spectators;
+mod stats;
+mod telemetry;
 
 #[get("/contests/<contest_id>/admin")]
 async fn contest_admin(
@@ -20,22 +32,73 @@ async fn contest_admin(
     contest_id: i64,
     user: &User,
     admin: Option<&Admin>,
+    _token: &CsrfToken,
 ) -> ResultResponse<Template> {
-    let (contest, _) =
+    let (contest, participant) =
         Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
-    let ctx = context_with_base_authed!(user, contest);
+    let ctx = context_with_base_authed!(
+        user,
+        is_running: contest.is_running(),
+        ended: contest.has_ended(),
+        contest,
+        participant
+    );
     Ok(Template::render("contests/admin", ctx))
 }
 
+#[post("/contests/<contest_id>/admin/presence")]
+async fn toggle_presence(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let (_, participant) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    // Admins who aren't judges of this contest never show as online
+    let mut participant = participant.ok_or(Status::BadRequest)?;
+    let hidden = !participant.presence_hidden;
+    participant.set_presence_hidden(&mut db, hidden).await?;
+    let msg = if hidden {
+        "You're now hidden from the online judges count"
+    } else {
+        "You're now shown in the online judges count"
+    };
+    Ok(Message::success(msg).to(&format!("/contests/{}/admin", contest_id)))
+}
+
 pub fn stage() -> AdHoc {
     AdHoc::on_ignite("Contest Admin", |rocket| async {
         rocket.mount(
             "/",
             routes![
                 contest_admin,
+                toggle_presence,
+                clock::extend_contest,
+                clock::control_clock,
                 participants::participants,
+                participants::export_participants,
                 participants::kick_participant_get,
                 participants::kick_participant_post,
+                participants::approve_participant,
+                participants::reject_participant,
+                participants::extra_time_get,
+                participants::extra_time_post,
+                spectators::spectators,
+                spectators::add_spectator,
+                spectators::remove_spectator,
+                ghosts::ghosts,
+                ghosts::import_ghosts,
+                ghosts::clear_ghosts,
+                telemetry::telemetry,
+                telemetry::clear_telemetry,
+                bindings::bindings,
+                bindings::allow_binding,
+                bindings::remove_allowance,
+                bindings::reset_binding,
+                problems::problem_order_get,
+                problems::problem_order_post,
                 runs::runs,
                 runs::cancel,
                 runs::cancel_post,
@@ -43,6 +106,12 @@ pub fn stage() -> AdHoc {
                 runs::view_user_run,
                 completions::edit_completion,
                 completions::edit_completion_post,
+                rejudge::rejudges,
+                rejudge::start_rejudge,
+                rejudge::view_rejudge,
+                rejudge::control_rejudge,
+                rejudge::rejudge_ws,
+                stats::contest_stats,
             ],
         )
     })
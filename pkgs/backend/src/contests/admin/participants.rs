@@ -1,4 +1,13 @@
-use rocket::{get, http::Status, post, response::Redirect, State};
+use std::collections::HashMap;
+
+use rocket::{
+    form::{Contextual, Form},
+    get,
+    http::Status,
+    post,
+    response::Redirect,
+    FromForm, State,
+};
 use rocket_dyn_templates::Template;
 
 use crate::{
@@ -6,18 +15,46 @@ use crate::{
         csrf::{CsrfToken, VerifyCsrfToken},
         users::{Admin, User},
     },
-    contests::{Contest, Participant},
+    contests::{Contest, Participant, ParticipantRole},
     context_with_base_authed,
-    db::DbConnection,
+    db::{DbConnection, DbPoolConnection},
     error::prelude::*,
-    leaderboard::LeaderboardManagerHandle,
+    leaderboard::{Export, ExportFormat, LeaderboardManagerHandle, Table},
     messages::Message,
+    template::{FormTemplateObject, TemplatedForm},
+    FormResponse,
 };
 
 #[derive(Serialize, Debug)]
 struct Row {
     participant: Participant,
     user: User,
+    /// Answers to the registration fields, as `Label: answer` separated by semicolons
+    registration: String,
+}
+
+async fn rows(
+    db: &mut DbPoolConnection,
+    contest: &Contest,
+    participants: Vec<Participant>,
+) -> Result<Vec<Row>> {
+    let mut rows = Vec::with_capacity(participants.len());
+    for participant in participants {
+        if let Some(user) = User::get(db, participant.user_id).await? {
+            let registration = participant
+                .registration_answers(contest)
+                .into_iter()
+                .map(|(label, answer)| format!("{label}: {answer}"))
+                .collect::<Vec<_>>()
+                .join("; ");
+            rows.push(Row {
+                participant,
+                user,
+                registration,
+            })
+        }
+    }
+    Ok(rows)
 }
 
 #[get("/contests/<contest_id>/admin/participants")]
@@ -26,21 +63,75 @@ pub async fn participants(
     user: &User,
     admin: Option<&Admin>,
     contest_id: i64,
+    _token: &CsrfToken,
 ) -> ResultResponse<Template> {
     let (contest, _) =
         Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
-    let just_participants = Participant::list_not_judge(&mut db, contest_id).await?;
-    let mut participants = vec![];
-    for participant in just_participants {
-        let p_user = User::get(&mut db, participant.user_id).await?;
-        if let Some(user) = p_user {
-            participants.push(Row { participant, user })
-        }
-    }
-    let ctx = context_with_base_authed!(user, contest, participants);
+    let participants = Participant::list_not_judge(&mut db, contest_id).await?;
+    let participants = rows(&mut db, &contest, participants).await?;
+    let pending = Participant::list_pending(&mut db, contest_id).await?;
+    let pending = rows(&mut db, &contest, pending).await?;
+    let waitlist = Participant::list_waitlisted(&mut db, contest_id).await?;
+    let waitlist = rows(&mut db, &contest, waitlist).await?;
+    let ctx = context_with_base_authed!(user, contest, participants, pending, waitlist);
     Ok(Template::render("contests/admin/participants", ctx))
 }
 
+/// Everyone registered with their answers to the registration fields, one column per field
+#[get("/contests/<contest_id>/admin/participants/export?<format>")]
+pub async fn export_participants(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    format: Option<ExportFormat>,
+) -> ResultResponse<Export> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let mut header = ["Name", "Email", "Registered At", "Status"]
+        .map(String::from)
+        .to_vec();
+    header.extend(contest.registration_fields().into_iter().map(|f| f.label));
+    let mut table = vec![header];
+
+    let mut participants = Participant::list_not_judge(&mut db, contest_id).await?;
+    participants.extend(Participant::list_pending(&mut db, contest_id).await?);
+    participants.extend(Participant::list_waitlisted(&mut db, contest_id).await?);
+    for participant in participants {
+        let Some(participant_user) = User::get(&mut db, participant.user_id).await? else {
+            continue;
+        };
+        let status = if participant.waitlisted {
+            "Waitlisted"
+        } else if participant.pending {
+            "Pending"
+        } else {
+            "Registered"
+        };
+        let mut row = vec![
+            participant_user.display_name().to_string(),
+            participant_user.email.clone(),
+            participant
+                .registered_at
+                .map(|t| t.format("%F %T").to_string())
+                .unwrap_or_default(),
+            status.to_string(),
+        ];
+        row.extend(
+            participant
+                .registration_answers(&contest)
+                .into_iter()
+                .map(|(_, answer)| answer),
+        );
+        table.push(row);
+    }
+
+    Ok(Table(table).into_export(
+        format.unwrap_or_default(),
+        &format!("{}-participants", slug::slugify(&contest.name)),
+    ))
+}
+
 #[get("/contests/<contest_id>/admin/participants/<p_id>/kick")]
 pub async fn kick_participant_get(
     contest_id: i64,
@@ -72,7 +163,8 @@ pub async fn kick_participant_post(
     _token: &VerifyCsrfToken,
     admin: Option<&Admin>,
 ) -> ResultResponse<Redirect> {
-    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
     let target_participant = Participant::by_id(&mut db, p_id)
         .await?
         .ok_or(Status::NotFound)?;
@@ -80,10 +172,168 @@ pub async fn kick_participant_post(
         log::error!("Failed to delete participant: {:?}", e);
         Status::InternalServerError
     })?;
+    let promoted = Participant::promote_waitlisted(&mut db, &contest).await?;
     let mut leaderboard_manager = leaderboards.lock().await;
     leaderboard_manager
         .delete_participant_for_contest(p_id, contest_id)
         .await;
+    if !promoted.is_empty() {
+        log::info!(
+            "Gave {} waitlisted participant(s) a spot in contest {}",
+            promoted.len(),
+            contest_id
+        );
+        leaderboard_manager
+            .refresh_leaderboard(&mut db, &contest)
+            .await?;
+    }
     Ok(Message::success("Participant Kicked")
         .to(&format!("/contests/{}/admin/participants", contest_id)))
 }
+
+async fn get_pending(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    p_id: i64,
+) -> ResultResponse<Participant> {
+    Participant::by_id(db, p_id)
+        .await?
+        .filter(|p| p.contest_id == contest_id && p.pending)
+        .ok_or(Status::NotFound.into())
+}
+
+#[post("/contests/<contest_id>/admin/participants/<p_id>/approve")]
+pub async fn approve_participant(
+    contest_id: i64,
+    p_id: i64,
+    mut db: DbConnection,
+    leaderboards: &State<LeaderboardManagerHandle>,
+    user: &User,
+    _token: &VerifyCsrfToken,
+    admin: Option<&Admin>,
+) -> ResultResponse<Redirect> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let mut target_participant = get_pending(&mut db, contest_id, p_id).await?;
+    if let Some(max_participants) = contest.max_participants {
+        let participants = Participant::list_not_judge(&mut db, contest_id).await?;
+        if participants.len() >= max_participants as usize {
+            return Ok(Message::error("This contest is already full")
+                .to(&format!("/contests/{}/admin/participants", contest_id)));
+        }
+    }
+    target_participant.approve(&mut db).await?;
+    let mut leaderboard_manager = leaderboards.lock().await;
+    leaderboard_manager
+        .refresh_leaderboard(&mut db, &contest)
+        .await?;
+    Ok(Message::success("Participant Approved")
+        .to(&format!("/contests/{}/admin/participants", contest_id)))
+}
+
+#[post("/contests/<contest_id>/admin/participants/<p_id>/reject")]
+pub async fn reject_participant(
+    contest_id: i64,
+    p_id: i64,
+    mut db: DbConnection,
+    user: &User,
+    _token: &VerifyCsrfToken,
+    admin: Option<&Admin>,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let target_participant = get_pending(&mut db, contest_id, p_id).await?;
+    target_participant.delete(&mut db).await?;
+    Ok(Message::success("Registration Rejected")
+        .to(&format!("/contests/{}/admin/participants", contest_id)))
+}
+
+struct ExtraTimeFormTemplate<'r> {
+    participant: &'r Participant,
+}
+
+impl TemplatedForm for ExtraTimeFormTemplate<'_> {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        HashMap::from_iter([(
+            "extra_minutes".to_string(),
+            self.participant.extra_minutes.to_string(),
+        )])
+    }
+}
+
+#[derive(FromForm)]
+pub struct ExtraTimeForm {
+    #[field(validate = range(0..=1440))]
+    extra_minutes: i64,
+}
+
+async fn get_target(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    p_id: i64,
+) -> ResultResponse<(Participant, User)> {
+    let participant = Participant::by_id(db, p_id)
+        .await?
+        .filter(|p| p.contest_id == contest_id && p.role() == ParticipantRole::Competitor)
+        .ok_or(Status::NotFound)?;
+    let user = User::get_or_404(db, participant.user_id).await?;
+    Ok((participant, user))
+}
+
+#[get("/contests/<contest_id>/admin/participants/<p_id>/time")]
+pub async fn extra_time_get(
+    contest_id: i64,
+    p_id: i64,
+    mut db: DbConnection,
+    user: &User,
+    _token: &CsrfToken,
+    admin: Option<&Admin>,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let (target_participant, target_user) = get_target(&mut db, contest_id, p_id).await?;
+    let form = FormTemplateObject::get(ExtraTimeFormTemplate {
+        participant: &target_participant,
+    });
+    let ctx = context_with_base_authed!(user, contest, target_participant, target_user, form);
+    Ok(Template::render("contests/admin/participant_time", ctx))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post(
+    "/contests/<contest_id>/admin/participants/<p_id>/time",
+    data = "<form>"
+)]
+pub async fn extra_time_post(
+    contest_id: i64,
+    p_id: i64,
+    mut db: DbConnection,
+    leaderboards: &State<LeaderboardManagerHandle>,
+    form: Form<Contextual<'_, ExtraTimeForm>>,
+    user: &User,
+    _token: &VerifyCsrfToken,
+    admin: Option<&Admin>,
+) -> FormResponse {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let (mut target_participant, target_user) = get_target(&mut db, contest_id, p_id).await?;
+    if let Some(ref value) = form.value {
+        target_participant
+            .set_extra_minutes(&mut db, value.extra_minutes)
+            .await?;
+        // Which completions count depends on when the contest ends for them
+        let mut leaderboard_manager = leaderboards.lock().await;
+        leaderboard_manager
+            .refresh_leaderboard(&mut db, &contest)
+            .await?;
+        return Ok(Message::success("Extra Time Saved")
+            .to(&format!("/contests/{}/admin/participants", contest_id)));
+    }
+    let form = FormTemplateObject::from_rocket_context(
+        ExtraTimeFormTemplate {
+            participant: &target_participant,
+        },
+        &form.context,
+    );
+    let ctx = context_with_base_authed!(user, contest, target_participant, target_user, form);
+    Err(Template::render("contests/admin/participant_time", ctx).into())
+}
@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+
+use rocket::{form::Form, get, post, FromForm};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    problems::Problem,
+};
+
+#[get("/contests/<contest_id>/admin/problems")]
+pub async fn problem_order_get(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problems = Problem::list(&mut db, contest_id).await?;
+    let ctx = context_with_base_authed!(user, contest, problems);
+    Ok(Template::render("contests/admin/problems", ctx))
+}
+
+#[derive(FromForm)]
+pub struct ProblemOrderForm {
+    /// Every problem's id, in the new order
+    order: Vec<i64>,
+}
+
+#[post("/contests/<contest_id>/admin/problems", data = "<form>")]
+pub async fn problem_order_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    form: Form<ProblemOrderForm>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problems = Problem::list(&mut db, contest_id).await?;
+    let current = problems.iter().map(|p| p.id).collect::<HashSet<_>>();
+    let ordered = form.order.iter().copied().collect::<HashSet<_>>();
+    let url = format!("/contests/{}/admin/problems", contest_id);
+    if form.order.len() != problems.len() || current != ordered {
+        return Ok(Message::error(
+            "Problems were added or removed since the page was loaded, please try again",
+        )
+        .to(&url));
+    }
+    Problem::reorder(&mut db, contest_id, &form.order).await?;
+    Ok(Message::success("Problems Reordered").to(&url))
+}
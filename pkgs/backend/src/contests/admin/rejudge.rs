@@ -0,0 +1,263 @@
+use log::error;
+use rocket::{
+    form::Form,
+    futures::{SinkExt, StreamExt},
+    get,
+    http::Status,
+    post,
+    response::Redirect,
+    FromForm, State,
+};
+use rocket_dyn_templates::Template;
+use rocket_ws::{stream::DuplexStream, WebSocket};
+use tokio::{
+    select,
+    sync::watch,
+    time::{self, Duration, Instant},
+};
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    problems::Problem,
+    run::rejudge::{Rejudge, RejudgeControl, RejudgeManagerHandle, RejudgeProgress, RejudgeStatus},
+    FormResponse,
+};
+
+#[get("/contests/<contest_id>/admin/rejudges")]
+pub async fn rejudges(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    rejudge_manager: &State<RejudgeManagerHandle>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let mut rejudges = Rejudge::list(&mut db, contest_id).await?;
+
+    // Rejudges that were running when the server stopped won't have an active task anymore,
+    // they're only saved as interrupted when the next one is started
+    let mut manager = rejudge_manager.lock().await;
+    for rejudge in rejudges.iter_mut() {
+        if !rejudge.status.is_finished() && !manager.is_active(rejudge.id) {
+            rejudge.status = RejudgeStatus::Interrupted;
+        }
+    }
+    let has_active = manager.has_active_for_contest(contest_id);
+    drop(manager);
+
+    let problems = Problem::list(&mut db, contest_id).await?;
+    let ctx = context_with_base_authed!(user, contest, rejudges, problems, has_active);
+    Ok(Template::render("contests/admin/rejudges", ctx))
+}
+
+#[derive(FromForm)]
+pub struct StartRejudgeForm {
+    /// `None` to rejudge every problem in the contest
+    problem_id: Option<i64>,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/contests/<contest_id>/admin/rejudges", data = "<form>")]
+pub async fn start_rejudge(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    rejudge_manager: &State<RejudgeManagerHandle>,
+    form: Form<StartRejudgeForm>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem_id = form.problem_id.filter(|id| *id != 0);
+    if let Some(problem_id) = problem_id {
+        Problem::by_id(&mut db, contest_id, problem_id)
+            .await?
+            .ok_or(Status::NotFound)?;
+    }
+
+    let mut manager = rejudge_manager.lock().await;
+    if manager.has_active_for_contest(contest_id) {
+        return Ok(
+            Message::error("A rejudge is already running for this contest")
+                .to(&format!("/contests/{}/admin/rejudges", contest_id)),
+        );
+    }
+    manager.mark_interrupted(&mut db, contest_id).await?;
+    let rejudge = manager.start(&mut db, &contest, problem_id).await?;
+    drop(manager);
+
+    Ok(Message::success("Rejudge started").to(&format!(
+        "/contests/{}/admin/rejudges/{}",
+        contest_id, rejudge.id
+    )))
+}
+
+#[get("/contests/<contest_id>/admin/rejudges/<rejudge_id>")]
+pub async fn view_rejudge(
+    mut db: DbConnection,
+    contest_id: i64,
+    rejudge_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    rejudge_manager: &State<RejudgeManagerHandle>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let rejudge = Rejudge::get(&mut db, contest_id, rejudge_id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    let problem = if let Some(problem_id) = rejudge.problem_id {
+        Problem::by_id(&mut db, contest_id, problem_id).await?
+    } else {
+        None
+    };
+    let active = rejudge_manager.lock().await.is_active(rejudge_id);
+    let ctx = context_with_base_authed!(user, contest, rejudge, problem, active);
+    Ok(Template::render("contests/admin/rejudge", ctx))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/contests/<contest_id>/admin/rejudges/<rejudge_id>/<action>")]
+pub async fn control_rejudge(
+    mut db: DbConnection,
+    contest_id: i64,
+    rejudge_id: i64,
+    action: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    rejudge_manager: &State<RejudgeManagerHandle>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    Rejudge::get(&mut db, contest_id, rejudge_id)
+        .await?
+        .ok_or(Status::NotFound)?;
+
+    let (control, msg) = match action {
+        "pause" => (RejudgeControl::Pause, "Rejudge paused"),
+        "resume" => (RejudgeControl::Run, "Rejudge resumed"),
+        "cancel" => (RejudgeControl::Cancel, "Rejudge cancelled"),
+        _ => return Err(Status::NotFound.into()),
+    };
+
+    let url = format!("/contests/{}/admin/rejudges/{}", contest_id, rejudge_id);
+    if rejudge_manager.lock().await.control(rejudge_id, control) {
+        Ok(Message::success(msg).to(&url))
+    } else {
+        Ok(Message::error("This rejudge isn't running").to(&url))
+    }
+}
+
+enum LoopRes {
+    NoOp,
+    Break,
+    Ping,
+    Pong(Vec<u8>),
+    Msg(RejudgeProgress),
+}
+
+async fn websocket_loop(mut stream: DuplexStream, mut rx: watch::Receiver<RejudgeProgress>) {
+    let sleep = time::sleep(Duration::from_secs(10));
+    tokio::pin!(sleep);
+
+    // Send the current progress right away so the page doesn't show stale numbers
+    let initial = rx.borrow_and_update().clone();
+    let mut next = Some(LoopRes::Msg(initial));
+
+    loop {
+        let res = if let Some(res) = next.take() {
+            res
+        } else {
+            select! {
+                () = &mut sleep => {
+                    sleep.as_mut().reset(Instant::now() + Duration::from_secs(10));
+                    LoopRes::Ping
+                },
+                client_message = stream.next() => {
+                    if let Some(client_message) = client_message {
+                        match client_message {
+                            Ok(rocket_ws::Message::Close(_)) => LoopRes::Break,
+                            Ok(rocket_ws::Message::Ping(data)) => LoopRes::Pong(data),
+                            _ => LoopRes::NoOp
+                        }
+                    } else {
+                        LoopRes::Break
+                    }
+                }
+                changed = rx.changed() => {
+                    if changed.is_ok() {
+                        LoopRes::Msg(rx.borrow_and_update().clone())
+                    } else {
+                        LoopRes::Break
+                    }
+                }
+            }
+        };
+
+        match res {
+            LoopRes::Break => break,
+            LoopRes::Msg(msg) => {
+                let finished = msg.rejudge.status.is_finished();
+                let json_string = serde_json::to_string(&msg).unwrap();
+                let res = stream.send(rocket_ws::Message::Text(json_string)).await;
+                if let Err(e) = res {
+                    error!("Error sending message: {:?}", e);
+                }
+                if finished {
+                    break;
+                }
+            }
+            LoopRes::Ping => {
+                let res = stream
+                    .send(rocket_ws::Message::Ping(vec![5, 4, 2, 6, 7, 3, 2, 5, 3]))
+                    .await;
+                if let Err(e) = res {
+                    error!("Error sending ping: {:?}", e);
+                }
+            }
+            LoopRes::Pong(data) => {
+                let res = stream.send(rocket_ws::Message::Pong(data)).await;
+                if let Err(e) = res {
+                    error!("Error sending pong: {:?}", e);
+                }
+            }
+            LoopRes::NoOp => {}
+        }
+    }
+}
+
+#[get("/contests/<contest_id>/admin/rejudges/<rejudge_id>/ws")]
+pub async fn rejudge_ws(
+    ws: WebSocket,
+    mut db: DbConnection,
+    contest_id: i64,
+    rejudge_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    rejudge_manager: &State<RejudgeManagerHandle>,
+) -> ResultResponse<rocket_ws::Channel<'static>> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let rx = rejudge_manager
+        .lock()
+        .await
+        .subscribe(rejudge_id)
+        .ok_or(Status::NotFound)?;
+    Ok(ws.channel(move |stream| {
+        Box::pin(async move {
+            websocket_loop(stream, rx).await;
+            Ok(())
+        })
+    }))
+}
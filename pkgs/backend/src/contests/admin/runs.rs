@@ -4,13 +4,16 @@ use rocket::{get, http::Status, post, response::Redirect, State};
 use rocket_dyn_templates::Template;
 
 use crate::{
-    auth::users::{Admin, User},
+    auth::{
+        impersonation::NotImpersonating,
+        users::{Admin, User},
+    },
     contests::{Contest, Participant},
     context_with_base_authed,
     db::DbConnection,
     error::prelude::*,
     messages::Message,
-    problems::{JudgeRun, Problem, ProblemCompletion},
+    problems::{JudgeRun, Problem, ProblemAnalytics, ProblemCompletion},
     run::ManagerHandle,
     times::{format_datetime_human_readable, ClientTimeZone},
 };
@@ -108,6 +111,7 @@ pub async fn cancel(
     ))
 }
 
+#[allow(clippy::too_many_arguments)]
 #[post("/contests/<contest_id>/admin/runs/<user_id>/<problem_id>/cancel")]
 pub async fn cancel_post(
     mut db: DbConnection,
@@ -117,6 +121,7 @@ pub async fn cancel_post(
     problem_id: i64,
     admin: Option<&Admin>,
     manager_handle: &State<ManagerHandle>,
+    _read_write: NotImpersonating,
 ) -> ResultResponse<Redirect> {
     Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
     Problem::by_id(&mut db, contest_id, problem_id)
@@ -187,7 +192,18 @@ pub async fn problem(
         })
         .collect::<Vec<_>>();
 
-    let ctx = context_with_base_authed!(user, rows, formatted_times, contest, problem);
+    // Until the contest is over these would just be a snapshot of a moving target
+    let analytics = if contest.has_ended() {
+        Some(
+            ProblemAnalytics::get(&mut db, &contest, problem.id)
+                .await?
+                .unwrap_or_default(),
+        )
+    } else {
+        None
+    };
+
+    let ctx = context_with_base_authed!(user, rows, formatted_times, analytics, contest, problem);
     Ok(Template::render("contests/admin/runs_problem", ctx))
 }
 
@@ -212,6 +228,14 @@ pub async fn view_user_run(
     let most_recent = JudgeRun::get_latest(&mut db, target_participant.user_id, problem.id).await?;
     let success_recent =
         JudgeRun::get_latest_success(&mut db, target_participant.user_id, problem.id).await?;
+    let most_recent_source = match most_recent.as_ref() {
+        Some(run) => run.cached_from_user(&mut db).await?,
+        None => None,
+    };
+    let success_recent_source = match success_recent.as_ref() {
+        Some(run) => run.cached_from_user(&mut db).await?,
+        None => None,
+    };
     Ok(Template::render(
         "contests/admin/runs_view",
         context_with_base_authed!(
@@ -220,7 +244,9 @@ pub async fn view_user_run(
             contest,
             problem,
             most_recent,
-            success_recent
+            success_recent,
+            most_recent_source,
+            success_recent_source
         ),
     ))
 }
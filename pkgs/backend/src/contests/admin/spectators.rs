@@ -0,0 +1,93 @@
+use rocket::{form::Form, get, http::Status, post, response::Redirect, FromForm};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::{Contest, Participant},
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+};
+
+#[derive(FromForm)]
+pub struct SpectatorForm<'r> {
+    #[field(validate = len(1..=255))]
+    email: &'r str,
+}
+
+async fn find_user_by_email(db: &mut DbPoolConnection, email: &str) -> Result<Option<User>> {
+    sqlx::query_as!(
+        User,
+        "SELECT * FROM user WHERE email = ? COLLATE NOCASE",
+        email
+    )
+    .fetch_optional(&mut **db)
+    .await
+    .context("Failed to find user by email")
+}
+
+#[get("/contests/<contest_id>/admin/spectators")]
+pub async fn spectators(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let spectators = Participant::list(&mut db, contest_id)
+        .await?
+        .into_iter()
+        .filter(|(p, _)| p.is_spectator)
+        .collect::<Vec<_>>();
+    let ctx = context_with_base_authed!(user, contest, spectators);
+    Ok(Template::render("contests/admin/spectators", ctx))
+}
+
+#[post("/contests/<contest_id>/admin/spectators", data = "<form>")]
+pub async fn add_spectator(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    form: Form<SpectatorForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let url = format!("/contests/{contest_id}/admin/spectators");
+    let Some(spectator) = find_user_by_email(&mut db, form.email.trim()).await? else {
+        return Ok(Message::error("No one with that email has an account").to(&url));
+    };
+    if Participant::get(&mut db, contest_id, spectator.id)
+        .await?
+        .is_some()
+    {
+        return Ok(Message::error("They're already in this contest").to(&url));
+    }
+    Participant::create_spectator(&mut db, contest_id, spectator.id).await?;
+    Ok(Message::success("Spectator Added").to(&url))
+}
+
+#[post("/contests/<contest_id>/admin/spectators/<p_id>/remove")]
+pub async fn remove_spectator(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    p_id: i64,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let spectator = Participant::by_id(&mut db, p_id)
+        .await?
+        .filter(|p| p.contest_id == contest_id && p.is_spectator)
+        .ok_or(Status::NotFound)?;
+    spectator.delete(&mut db).await?;
+    Ok(Message::success("Spectator Removed")
+        .to(&format!("/contests/{contest_id}/admin/spectators")))
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use rocket::{get, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    contests::{clock_time_between, Contest},
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    run::CodeInfo,
+};
+
+/// How many bars the submissions over time chart is split into
+const TIMELINE_BUCKETS: i64 = 24;
+
+#[derive(Serialize)]
+struct TimelineBucket {
+    /// Time since the start of the contest, e.g. `1:30`
+    label: String,
+    count: i64,
+    accepted: i64,
+    /// Height of the bar relative to the busiest bucket
+    percent: i64,
+}
+
+#[derive(Serialize)]
+struct VerdictCount {
+    name: String,
+    count: i64,
+    percent: i64,
+}
+
+#[derive(Serialize)]
+struct LanguageCount {
+    name: String,
+    count: i64,
+    percent: i64,
+}
+
+#[derive(Serialize)]
+struct ProblemStats {
+    label: String,
+    name: String,
+    submissions: i64,
+    verdicts: Vec<VerdictCount>,
+    solvers: i64,
+    /// Average submissions it took solvers to get accepted, including the accepted one
+    average_attempts: Option<String>,
+    /// Contest time until the first solve, e.g. `0:42`
+    first_solve: Option<String>,
+    /// How far into the contest the first solve came
+    first_solve_percent: i64,
+}
+
+#[derive(Serialize)]
+struct ContestStats {
+    submissions: i64,
+    /// How long the contest is, e.g. `5:00`
+    length: String,
+    timeline: Vec<TimelineBucket>,
+    languages: Vec<LanguageCount>,
+    problems: Vec<ProblemStats>,
+}
+
+fn percent(part: i64, whole: i64) -> i64 {
+    if whole == 0 {
+        0
+    } else {
+        part * 100 / whole
+    }
+}
+
+fn format_clock(minutes: i64) -> String {
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+impl ContestStats {
+    /// Everything is counted over runs and completions of participants who aren't judges,
+    /// judges testing their problems would skew it
+    async fn get(
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+        code_info: &CodeInfo,
+    ) -> Result<Self> {
+        let contest_secs = (contest.end_time - contest.start_time).num_seconds().max(1);
+        let bucket_secs = (contest_secs + TIMELINE_BUCKETS - 1) / TIMELINE_BUCKETS;
+
+        // Runs from extra time land in the last bucket
+        let mut buckets = HashMap::<i64, (i64, i64)>::new();
+        for row in sqlx::query!(
+            "SELECT CAST((julianday(judge_run.ran_at) - julianday(?)) * 86400 AS INTEGER) / ? AS \"bucket!: i64\", COUNT(*) AS \"count!: i64\", SUM(COALESCE(judge_run.manual_verdict, judge_run.amount_run = judge_run.total_cases AND judge_run.error IS NULL)) AS \"accepted!: i64\"
+            FROM judge_run JOIN problem ON judge_run.problem_id = problem.id JOIN participant ON participant.user_id = judge_run.user_id AND participant.contest_id = problem.contest_id
+            WHERE problem.contest_id = ? AND NOT participant.is_judge AND NOT participant.is_spectator AND NOT participant.pending AND judge_run.ran_at >= ? AND judge_run.ran_at <= datetime(?, '+' || participant.extra_minutes || ' minutes')
+            GROUP BY 1",
+            contest.start_time,
+            bucket_secs,
+            contest.id,
+            contest.start_time,
+            contest.end_time
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to count submissions over time")?
+        {
+            let entry = buckets
+                .entry(row.bucket.clamp(0, TIMELINE_BUCKETS - 1))
+                .or_default();
+            entry.0 += row.count;
+            entry.1 += row.accepted;
+        }
+        let busiest = buckets.values().map(|(c, _)| *c).max().unwrap_or(0);
+        let timeline = (0..TIMELINE_BUCKETS)
+            .map(|i| {
+                let (count, accepted) = buckets.get(&i).copied().unwrap_or((0, 0));
+                TimelineBucket {
+                    label: format_clock(i * bucket_secs / 60),
+                    count,
+                    accepted,
+                    percent: percent(count, busiest),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let languages = sqlx::query!(
+            "SELECT judge_run.language, COUNT(*) AS \"count!: i64\"
+            FROM judge_run JOIN problem ON judge_run.problem_id = problem.id JOIN participant ON participant.user_id = judge_run.user_id AND participant.contest_id = problem.contest_id
+            WHERE problem.contest_id = ? AND NOT participant.is_judge AND NOT participant.is_spectator AND NOT participant.pending
+            GROUP BY judge_run.language ORDER BY 2 DESC, judge_run.language",
+            contest.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to count languages")?;
+        let submissions = languages.iter().map(|l| l.count).sum::<i64>();
+        let languages = languages
+            .into_iter()
+            .map(|l| LanguageCount {
+                name: code_info
+                    .run_config
+                    .languages
+                    .get(&l.language)
+                    .map_or_else(|| l.language.clone(), |c| c.display.name.clone()),
+                count: l.count,
+                percent: percent(l.count, submissions),
+            })
+            .collect::<Vec<_>>();
+
+        // The first line of an error is the verdict, e.g. `Time Limit Exceeded`, the rest is details
+        let mut verdicts = HashMap::<i64, Vec<(String, i64)>>::new();
+        for row in sqlx::query!(
+            "SELECT judge_run.problem_id, CASE WHEN COALESCE(judge_run.manual_verdict, judge_run.amount_run = judge_run.total_cases AND judge_run.error IS NULL) THEN 'Accepted' WHEN judge_run.error IS NULL THEN 'Rejected' ELSE substr(judge_run.error, 1, instr(judge_run.error || char(10), char(10)) - 1) END AS \"verdict!: String\", COUNT(*) AS \"count!: i64\"
+            FROM judge_run JOIN problem ON judge_run.problem_id = problem.id JOIN participant ON participant.user_id = judge_run.user_id AND participant.contest_id = problem.contest_id
+            WHERE problem.contest_id = ? AND NOT participant.is_judge AND NOT participant.is_spectator AND NOT participant.pending
+            GROUP BY 1, 2 ORDER BY 3 DESC",
+            contest.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to count verdicts")?
+        {
+            verdicts
+                .entry(row.problem_id)
+                .or_default()
+                .push((row.verdict, row.count));
+        }
+
+        let completions = sqlx::query!(
+            "SELECT problem_completion.problem_id AS \"problem_id!\", COUNT(*) AS \"solvers!: i64\", AVG(problem_completion.number_wrong + 1) AS \"attempts!: f64\", MIN(problem_completion.completed_at) AS \"first_solve!: NaiveDateTime\"
+            FROM problem_completion JOIN participant ON problem_completion.participant_id = participant.p_id
+            WHERE participant.contest_id = ? AND NOT participant.is_judge AND NOT participant.is_spectator AND NOT participant.pending AND problem_completion.completed_at IS NOT NULL
+            GROUP BY problem_completion.problem_id",
+            contest.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to get completion stats")?
+        .into_iter()
+        .map(|r| (r.problem_id, (r.solvers, r.attempts, r.first_solve)))
+        .collect::<HashMap<_, _>>();
+
+        let pauses = contest.pauses();
+        let clock_secs = clock_time_between(&pauses, contest.start_time, contest.end_time)
+            .num_seconds()
+            .max(1);
+        let problems = sqlx::query!(
+            "SELECT id, name, label FROM problem WHERE contest_id = ? ORDER BY ordering, id",
+            contest.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to fetch problems")?
+        .into_iter()
+        .map(|problem| {
+            let verdicts = verdicts.remove(&problem.id).unwrap_or_default();
+            let submissions = verdicts.iter().map(|(_, c)| c).sum::<i64>();
+            let (solvers, attempts, first_solve) = match completions.get(&problem.id) {
+                Some((solvers, attempts, first)) => {
+                    let since_start = clock_time_between(&pauses, contest.start_time, *first)
+                        .num_seconds()
+                        .max(0);
+                    (*solvers, Some(*attempts), Some(since_start))
+                }
+                None => (0, None, None),
+            };
+            ProblemStats {
+                label: problem.label,
+                name: problem.name,
+                submissions,
+                verdicts: verdicts
+                    .into_iter()
+                    .map(|(name, count)| VerdictCount {
+                        name,
+                        count,
+                        percent: percent(count, submissions),
+                    })
+                    .collect(),
+                solvers,
+                average_attempts: attempts.map(|a| format!("{a:.1}")),
+                first_solve: first_solve.map(|s| format_clock(s / 60)),
+                first_solve_percent: first_solve
+                    .map(|s| percent(s, clock_secs).min(100))
+                    .unwrap_or(0),
+            }
+        })
+        .collect();
+
+        Ok(Self {
+            submissions,
+            length: format_clock(contest_secs / 60),
+            timeline,
+            languages,
+            problems,
+        })
+    }
+}
+
+#[get("/contests/<contest_id>/admin/stats")]
+pub async fn contest_stats(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    code_info: &State<CodeInfo>,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let stats = ContestStats::get(&mut db, &contest, code_info).await?;
+    let ctx = context_with_base_authed!(user, contest, stats);
+    Ok(Template::render("contests/admin/stats", ctx))
+}
@@ -0,0 +1,52 @@
+use rocket::{get, post, response::Redirect};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    problems::{TelemetryReport, LARGE_PASTE_CHARS},
+};
+
+/// Who pasted a lot into the editor or kept leaving the page, for judges to look into
+#[get("/contests/<contest_id>/admin/telemetry")]
+pub async fn telemetry(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let reports = TelemetryReport::list(&mut db, contest_id).await?;
+    let ctx = context_with_base_authed!(
+        user,
+        contest,
+        reports,
+        large_paste_chars: LARGE_PASTE_CHARS
+    );
+    Ok(Template::render("contests/admin/telemetry", ctx))
+}
+
+#[post("/contests/<contest_id>/admin/telemetry/clear")]
+pub async fn clear_telemetry(
+    mut db: DbConnection,
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    TelemetryReport::clear(&mut db, contest_id).await?;
+    Ok(
+        Message::success("Telemetry Cleared")
+            .to(&format!("/contests/{contest_id}/admin/telemetry")),
+    )
+}
@@ -0,0 +1,324 @@
+//! Binding a competitor's submissions to where they made their first one from, for proctored
+//! contests. Submissions from another address or device are flagged for judges or blocked,
+//! judges can allow others when someone's network legitimately changes.
+
+use chrono::NaiveDateTime;
+use rocket::{
+    request::{self, FromRequest},
+    FromFormField, Request,
+};
+use sqlx::{encode::IsNull, Decode, Encode, Type};
+
+use crate::{db::DbPoolConnection, error::prelude::*};
+
+use super::{Contest, Participant, ParticipantRole};
+
+/// What happens to submissions that don't match a participant's binding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, FromFormField, Default)]
+pub enum BindingMode {
+    #[default]
+    Off,
+    /// Judge them as usual but flag the run for judges
+    Flag,
+    /// Refuse them
+    Block,
+}
+
+impl From<String> for BindingMode {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "Flag" => Self::Flag,
+            "Block" => Self::Block,
+            _ => Self::Off,
+        }
+    }
+}
+
+impl From<BindingMode> for String {
+    fn from(m: BindingMode) -> Self {
+        format!("{:?}", m)
+    }
+}
+
+impl Type<sqlx::Sqlite> for BindingMode {
+    fn type_info() -> <sqlx::Sqlite as sqlx::Database>::TypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl Encode<'_, sqlx::Sqlite> for BindingMode {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Sqlite as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> IsNull {
+        let val = format!("{:?}", self);
+        <std::string::String as Encode<'_, sqlx::Sqlite>>::encode_by_ref(&val, buf)
+    }
+}
+
+impl Decode<'_, sqlx::Sqlite> for BindingMode {
+    fn decode(
+        value: <sqlx::Sqlite as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        Ok(s.into())
+    }
+}
+
+/// Where a request came from, the device is a hash of the browser's `User-Agent` and
+/// `Accept-Language` so it changes with the browser but not the network
+#[derive(Debug, Clone)]
+pub struct ClientOrigin {
+    pub ip: String,
+    pub device: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientOrigin {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let ip = req
+            .client_ip()
+            .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+        let header = |name| req.headers().get_one(name).unwrap_or_default();
+        let device = sha256::digest(format!(
+            "{}\n{}",
+            header("User-Agent"),
+            header("Accept-Language")
+        ));
+        request::Outcome::Success(ClientOrigin {
+            ip,
+            device: device[..16].to_string(),
+        })
+    }
+}
+
+/// What to do with a submission, see [`SessionBinding::check`]
+pub enum BindingVerdict {
+    Allowed,
+    /// Judge it, but flag the run with this
+    Flagged(String),
+    Blocked(String),
+}
+
+impl BindingVerdict {
+    /// Add the binding's flag to any other flag a run already has
+    pub fn flag(&self, other: Option<String>) -> Option<String> {
+        match (self, other) {
+            (Self::Flagged(flag), Some(other)) => Some(format!("{other}; {flag}")),
+            (Self::Flagged(flag), None) => Some(flag.clone()),
+            (_, other) => other,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionBinding {
+    pub participant_id: i64,
+    pub ip: String,
+    pub device: String,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub bound_at: NaiveDateTime,
+    pub mismatch_ip: Option<String>,
+    pub mismatch_device: Option<String>,
+    #[serde(serialize_with = "crate::times::serialize_option_to_js")]
+    pub mismatch_at: Option<NaiveDateTime>,
+    pub mismatches: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BindingAllowance {
+    pub id: i64,
+    pub participant_id: i64,
+    pub ip: Option<String>,
+    pub device: Option<String>,
+    pub added_by: Option<i64>,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub added_at: NaiveDateTime,
+}
+
+impl SessionBinding {
+    pub async fn list(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            SessionBinding,
+            "SELECT session_binding.* FROM session_binding JOIN participant ON participant.p_id = session_binding.participant_id WHERE participant.contest_id = ? ORDER BY session_binding.bound_at",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't list session bindings of contest {contest_id}"))
+    }
+
+    pub async fn allowances(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+    ) -> Result<Vec<BindingAllowance>> {
+        sqlx::query_as!(
+            BindingAllowance,
+            "SELECT session_binding_allowance.* FROM session_binding_allowance JOIN participant ON participant.p_id = session_binding_allowance.participant_id WHERE participant.contest_id = ? ORDER BY session_binding_allowance.id",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't list binding allowances of contest {contest_id}"))
+    }
+
+    /// Let a participant submit from another address and/or device
+    pub async fn allow(
+        db: &mut DbPoolConnection,
+        participant_id: i64,
+        ip: Option<&str>,
+        device: Option<&str>,
+        added_by: i64,
+    ) -> Result {
+        let now = chrono::offset::Utc::now().naive_utc();
+        sqlx::query!(
+            "INSERT INTO session_binding_allowance (participant_id, ip, device, added_by, added_at) VALUES (?, ?, ?, ?, ?)",
+            participant_id,
+            ip,
+            device,
+            added_by,
+            now
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't add binding allowance for participant {participant_id}"))?;
+        sqlx::query!(
+            "UPDATE session_binding SET mismatch_ip = NULL, mismatch_device = NULL, mismatch_at = NULL WHERE participant_id = ?",
+            participant_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Couldn't clear binding mismatch of participant {participant_id}"))
+    }
+
+    pub async fn remove_allowance(
+        db: &mut DbPoolConnection,
+        participant_id: i64,
+        id: i64,
+    ) -> Result {
+        sqlx::query!(
+            "DELETE FROM session_binding_allowance WHERE id = ? AND participant_id = ?",
+            id,
+            participant_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Couldn't remove binding allowance {id}"))
+    }
+
+    /// Forget where a participant is bound to and what they were allowed, their next
+    /// submission binds them again
+    pub async fn reset(db: &mut DbPoolConnection, participant_id: i64) -> Result {
+        sqlx::query!(
+            "DELETE FROM session_binding_allowance WHERE participant_id = ?",
+            participant_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| {
+            format!("Couldn't clear binding allowances of participant {participant_id}")
+        })?;
+        sqlx::query!(
+            "DELETE FROM session_binding WHERE participant_id = ?",
+            participant_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Couldn't reset session binding of participant {participant_id}"))
+    }
+
+    /// Check a submission against the submitter's binding, binding them if it's their first.
+    /// Only competitors are bound, and only while the contest is running for them.
+    pub async fn check(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        user_id: i64,
+        origin: &ClientOrigin,
+    ) -> Result<BindingVerdict> {
+        let contest = Contest::get(db, contest_id)
+            .await?
+            .ok_or_else(|| anyhow!("Couldn't find contest with id {}", contest_id))?;
+        if contest.session_binding == BindingMode::Off {
+            return Ok(BindingVerdict::Allowed);
+        }
+        let participant = Participant::get(db, contest_id, user_id)
+            .await?
+            .filter(|p| p.role() == ParticipantRole::Competitor);
+        let Some(participant) = participant.filter(|p| contest.is_running_for(Some(p))) else {
+            return Ok(BindingVerdict::Allowed);
+        };
+
+        let binding = sqlx::query_as!(
+            SessionBinding,
+            "SELECT * FROM session_binding WHERE participant_id = ?",
+            participant.p_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| {
+            format!(
+                "Couldn't get session binding of participant {}",
+                participant.p_id
+            )
+        })?;
+        let now = chrono::offset::Utc::now().naive_utc();
+        let Some(binding) = binding else {
+            sqlx::query!(
+                "INSERT INTO session_binding (participant_id, ip, device, bound_at) VALUES (?, ?, ?, ?)",
+                participant.p_id,
+                origin.ip,
+                origin.device,
+                now
+            )
+            .execute(&mut **db)
+            .await
+            .with_context(|| format!("Couldn't bind participant {}", participant.p_id))?;
+            return Ok(BindingVerdict::Allowed);
+        };
+
+        let allowed = sqlx::query!(
+            "SELECT COUNT(*) AS \"ip_allowed!: i64\", (SELECT COUNT(*) FROM session_binding_allowance WHERE participant_id = ?1 AND device = ?3) AS \"device_allowed!: i64\" FROM session_binding_allowance WHERE participant_id = ?1 AND ip = ?2",
+            participant.p_id,
+            origin.ip,
+            origin.device
+        )
+        .fetch_one(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't get binding allowances of participant {}", participant.p_id))?;
+        let ip_ok = binding.ip == origin.ip || allowed.ip_allowed > 0;
+        let device_ok = binding.device == origin.device || allowed.device_allowed > 0;
+        if ip_ok && device_ok {
+            return Ok(BindingVerdict::Allowed);
+        }
+
+        sqlx::query!(
+            "UPDATE session_binding SET mismatch_ip = ?, mismatch_device = ?, mismatch_at = ?, mismatches = mismatches + 1 WHERE participant_id = ?",
+            origin.ip,
+            origin.device,
+            now,
+            participant.p_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't record binding mismatch of participant {}", participant.p_id))?;
+        let what = match (ip_ok, device_ok) {
+            (false, false) => "address and device",
+            (false, true) => "address",
+            _ => "device",
+        };
+        Ok(match contest.session_binding {
+            BindingMode::Block => BindingVerdict::Blocked(format!(
+                "Submissions in this contest have to come from the {what} you first submitted from, ask a judge if yours changed"
+            )),
+            _ => BindingVerdict::Flagged(format!(
+                "Submitted from a different {what} ({}) than the first submission ({})",
+                origin.ip, binding.ip
+            )),
+        })
+    }
+}
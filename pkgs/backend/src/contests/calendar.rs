@@ -0,0 +1,158 @@
+use chrono::Duration;
+use rocket::{
+    get,
+    http::{ContentType, Status},
+    State,
+};
+
+use crate::{
+    auth::users::{Admin, User},
+    branding::BrandingConfig,
+    db::DbConnection,
+    error::prelude::*,
+    rate_limit::{self, RateLimited},
+    times::naive_to_ics_time,
+};
+
+use super::{Contest, Participant};
+
+/// Contests that ended this long ago still show up in the feed, so they don't vanish
+/// from subscribers' calendars the moment they're over
+const PAST_DAYS: i64 = 30;
+/// When calendar apps remind subscribers the contest is starting
+const REMINDER_MINUTES: i64 = 15;
+
+/// Where the site is hosted, needed for links and event IDs since feeds are read outside the browser
+pub struct SiteUrl(pub String);
+
+/// Escape text values, see RFC 5545 section 3.3.11
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Lines longer than 75 bytes have to be folded onto continuation lines starting with a space
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+struct Calendar {
+    name: String,
+    url: String,
+    lines: Vec<String>,
+}
+
+impl Calendar {
+    fn new(name: &str, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            url: url.trim_end_matches('/').to_string(),
+            lines: vec![],
+        }
+    }
+
+    fn add(&mut self, contest: &Contest) {
+        let host = self.url.split("://").last().unwrap_or_default();
+        let link = format!("{}/contests/{}", self.url, contest.id);
+        let now = chrono::offset::Utc::now().naive_utc();
+        let mut description = format!("{link}\n\n");
+        if let Some(desc) = contest.description.as_ref() {
+            description.push_str(desc);
+        }
+        self.lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:contest-{}@{host}", contest.id),
+            format!("DTSTAMP:{}", naive_to_ics_time(now)),
+            format!("DTSTART:{}", naive_to_ics_time(contest.start_time)),
+            format!("DTEND:{}", naive_to_ics_time(contest.end_time)),
+            format!("SUMMARY:{}", escape(&contest.name)),
+            format!("DESCRIPTION:{}", escape(description.trim_end())),
+            format!("URL:{link}"),
+            "BEGIN:VALARM".to_string(),
+            "ACTION:DISPLAY".to_string(),
+            format!("DESCRIPTION:{} is starting", escape(&contest.name)),
+            format!("TRIGGER:-PT{REMINDER_MINUTES}M"),
+            "END:VALARM".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+
+    fn render(self) -> (ContentType, String) {
+        let mut out = String::new();
+        for line in [
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            format!("PRODID:-//{}//Contests//EN", escape(&self.name)),
+            "CALSCALE:GREGORIAN".to_string(),
+            format!("X-WR-CALNAME:{}", escape(&self.name)),
+        ]
+        .into_iter()
+        .chain(self.lines)
+        .chain(["END:VCALENDAR".to_string()])
+        {
+            out.push_str(&fold(&line));
+        }
+        (ContentType::Calendar, out)
+    }
+}
+
+/// Every contest the user can see that hasn't been over for long, for subscribing to
+#[get("/calendar.ics")]
+pub async fn contests_calendar(
+    mut db: DbConnection,
+    user: Option<&User>,
+    admin: Option<&Admin>,
+    branding: &State<BrandingConfig>,
+    url: &State<SiteUrl>,
+    _limit: RateLimited<rate_limit::Api>,
+) -> ResultResponse<(ContentType, String)> {
+    let contests = if admin.is_some() {
+        Contest::list(&mut db).await?
+    } else {
+        Contest::list_visible(&mut db, user.map(|u| u.id)).await?
+    };
+    let cutoff = chrono::offset::Utc::now().naive_utc() - Duration::days(PAST_DAYS);
+    let mut calendar = Calendar::new(&branding.name, &url.0);
+    for contest in contests.iter().filter(|c| c.end_time > cutoff) {
+        calendar.add(contest);
+    }
+    Ok(calendar.render())
+}
+
+#[get("/<contest_id>/calendar.ics")]
+pub async fn contest_calendar(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: Option<&User>,
+    admin: Option<&Admin>,
+    branding: &State<BrandingConfig>,
+    url: &State<SiteUrl>,
+    _limit: RateLimited<rate_limit::Api>,
+) -> ResultResponse<(ContentType, String)> {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let participant = if let Some(user) = user {
+        Participant::get(&mut db, contest_id, user.id).await?
+    } else {
+        None
+    };
+    if !contest.is_visible_to(participant.as_ref(), admin) && !contest.published {
+        return Err(Status::NotFound.into());
+    }
+    let mut calendar = Calendar::new(&format!("{} - {}", contest.name, branding.name), &url.0);
+    calendar.add(&contest);
+    Ok(calendar.render())
+}
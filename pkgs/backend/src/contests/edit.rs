@@ -3,7 +3,7 @@ use std::collections::HashSet;
 use chrono::TimeZone;
 use log::info;
 use rocket::{
-    form::{Contextual, Form},
+    form::{Contextual, Error, Form},
     get, post, State,
 };
 use rocket_dyn_templates::Template;
@@ -17,6 +17,7 @@ use crate::{
     db::DbConnection,
     error::prelude::*,
     messages::Message,
+    run::CodeInfo,
     template::FormTemplateObject,
     times::ClientTimeZone,
 };
@@ -53,7 +54,8 @@ pub async fn edit_contest_get(
 pub async fn edit_contest_post(
     id: i64,
     user: &User,
-    form: Form<Contextual<'_, ContestForm<'_>>>,
+    mut form: Form<Contextual<'_, ContestForm<'_>>>,
+    info: &State<CodeInfo>,
     leaderboard_handle: &State<LeaderboardManagerHandle>,
     client_time_zone: ClientTimeZone,
     _token: &VerifyCsrfToken,
@@ -61,7 +63,17 @@ pub async fn edit_contest_post(
     mut db: DbConnection,
 ) -> FormResponse {
     let mut contest = Contest::get_or_404(&mut db, id).await?;
-    if let Some(ref value) = form.value {
+    let default_languages = form
+        .value
+        .as_ref()
+        .map(|v| info.run_config.normalize_language_list(v.default_languages))
+        .transpose()
+        .unwrap_or_else(|why| {
+            form.context
+                .push_error(Error::validation(why).with_name("default_languages"));
+            None
+        });
+    if let (Some(value), Some(default_languages)) = (form.value.as_ref(), default_languages) {
         let tz = client_time_zone.timezone();
         contest.name = value.name.to_string();
         contest.description = value.description.map(|s| s.to_string());
@@ -80,6 +92,51 @@ pub async fn edit_contest_post(
         contest.max_participants = value.max_participants;
         contest.penalty = value.penalty;
         contest.freeze_time = value.freeze_time;
+        contest.rules = value
+            .rules
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        contest.verdict_cache = value.verdict_cache;
+        contest.require_approval = value.require_approval;
+        contest.printing_enabled = value.printing_enabled;
+        contest.comments_during_contest = value.comments_during_contest;
+        contest.default_cpu_time = value.default_cpu_time;
+        contest.default_memory_limit = value.default_memory_limit;
+        contest.default_languages = default_languages;
+        contest.max_team_size = value.max_team_size;
+        contest.allow_withdrawal = value.allow_withdrawal;
+        contest.spectators_see_unfrozen = value.spectators_see_unfrozen;
+        contest.editor_telemetry = value.editor_telemetry;
+        contest.session_binding = value.session_binding;
+        contest.max_line_length = value.max_line_length;
+        contest.banned_patterns = value
+            .banned_patterns
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        contest.required_header = value
+            .required_header
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        contest.published = value.published;
+        contest.publish_at = value
+            .publish_at
+            .as_ref()
+            .filter(|_| !value.published)
+            .map(|t| tz.from_local_datetime(&t.0).unwrap().naive_utc());
+        contest.visibility = value.visibility;
+        contest.join_code = value
+            .join_code
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        contest.registration_fields = value
+            .registration_fields
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
 
         contest.update(&mut db).await?;
 
@@ -103,14 +160,22 @@ pub async fn edit_contest_post(
             Participant::create_or_make_judge(&mut db, contest.id, *judge).await?;
         }
 
-        let mut leaderboard_manager = leaderboard_handle.lock().await;
-        let leaderboard = leaderboard_manager
-            .get_leaderboard(&mut db, &contest)
-            .await?;
-        drop(leaderboard_manager);
-        let mut leaderboard = leaderboard.lock().await;
+        // Raising the cap opens up spots for the waitlist
+        let promoted = Participant::promote_waitlisted(&mut db, &contest).await?;
+        if !promoted.is_empty() {
+            info!(
+                "Gave {} waitlisted participant(s) a spot in contest {}",
+                promoted.len(),
+                contest.id
+            );
+        }
+
         info!("Refreshing leaderboard for contest {}", contest.id);
-        leaderboard.full_refresh(&mut db, Some(&contest)).await?;
+        leaderboard_handle
+            .lock()
+            .await
+            .refresh_leaderboard(&mut db, &contest)
+            .await?;
 
         Ok(Message::success("Contest Updated").to(&format!("/contests/{id}")))
     } else {
@@ -0,0 +1,203 @@
+//! A repo with every participant's accepted solutions for judges to archive or look over for
+//! plagiarism, only once the contest is over for everyone so nothing leaks while it's running.
+
+use std::collections::BTreeMap;
+
+use rocket::{get, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    branding::BrandingConfig,
+    contests::{Contest, Participant, Team},
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    problems::{JudgeRun, Problem},
+    run::CodeInfo,
+};
+
+use super::{
+    commit_root, gen_code, language_ext,
+    object::{Object, ObjectType},
+    problem_dir,
+    repo::FakeRepo,
+    tree::Tree,
+    ExportKind, RepoMapGuard, CACHE_TIME_MINUTES,
+};
+
+const BLOB_MODE: &str = "100644";
+const DIR_MODE: &str = "040000";
+
+/// A name that's safe as a folder or file name, with an id so two people or teams with the same
+/// name don't end up in the same folder
+fn safe_name(name: &str, id: i64) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    let name = name
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if name.is_empty() {
+        id.to_string()
+    } else {
+        format!("{name}-{id}")
+    }
+}
+
+fn blob(repo: &mut FakeRepo, contents: &str) -> Result<Vec<u8>> {
+    let obj = Object::new(contents.as_bytes().to_vec(), ObjectType::Blob)
+        .context("Failed to serialize blob")?;
+    let hash = obj.get_hash();
+    repo.add_object(obj);
+    Ok(hash)
+}
+
+fn add_tree(repo: &mut FakeRepo, tree: &Tree) -> Result<Vec<u8>> {
+    let obj = tree.to_object().context("Failed to serialize tree")?;
+    let hash = obj.get_hash();
+    repo.add_object(obj);
+    Ok(hash)
+}
+
+/// Accepted solutions, by team (or participant when they aren't on one) then problem folder then
+/// file name
+type Solutions = BTreeMap<String, BTreeMap<String, Vec<(String, String)>>>;
+
+#[get("/contests/<contest_id>/export/all")]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_all_solutions(
+    user: &User,
+    contest_id: i64,
+    mut db: DbConnection,
+    admin: Option<&Admin>,
+    info: &State<CodeInfo>,
+    branding: &State<BrandingConfig>,
+    repos_handle: RepoMapGuard<'_>,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+
+    let participants = Participant::list(&mut db, contest_id)
+        .await?
+        .into_iter()
+        .filter(|(p, _)| p.competes())
+        .collect::<Vec<_>>();
+
+    // Anyone given extra time might still be working, so wait for the last of them
+    if !contest.has_ended()
+        || participants
+            .iter()
+            .any(|(p, _)| !contest.has_ended_for(Some(p)))
+    {
+        let ctx = context_with_base_authed!(user, contest);
+        return Ok(Template::render("contests/export_all", ctx));
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+
+    let repos = repos_handle.lock().await;
+    if let Some((code, _, _, generated)) = repos.get(&(contest_id, user.id, ExportKind::All)) {
+        if now - *generated < chrono::Duration::minutes(CACHE_TIME_MINUTES as i64) {
+            let ctx = context_with_base_authed!(user, code, generated, contest);
+            return Ok(Template::render("contests/export_all", ctx));
+        }
+    }
+    drop(repos);
+
+    let problems = Problem::list(&mut db, contest_id).await?;
+    let mut solutions = Solutions::new();
+    let mut solution_count = 0;
+    for (participant, p_user) in participants.iter() {
+        let team = if contest.max_team_size.is_some() {
+            Team::for_participant(&mut db, participant.p_id).await?
+        } else {
+            None
+        };
+        let folder = match &team {
+            Some(team) => format!("team-{}", safe_name(&team.name, team.id)),
+            None => safe_name(p_user.display_name(), p_user.id),
+        };
+        for problem in problems.iter() {
+            let Some(run) = JudgeRun::get_latest_success(&mut db, p_user.id, problem.id).await?
+            else {
+                continue;
+            };
+            let file = format!(
+                "{}.{}",
+                safe_name(p_user.display_name(), p_user.id),
+                language_ext(info, &run.language)
+            );
+            solutions
+                .entry(folder.clone())
+                .or_default()
+                .entry(problem_dir(problem))
+                .or_default()
+                .push((file, run.program));
+            solution_count += 1;
+        }
+    }
+
+    let mut repo = FakeRepo::new();
+    let mut root_tree = Tree::new();
+
+    for (folder, by_problem) in solutions.iter() {
+        let mut folder_tree = Tree::new();
+        for (dir, files) in by_problem.iter() {
+            let mut problem_tree = Tree::new();
+            for (file, program) in files.iter() {
+                let hash = blob(&mut repo, program)?;
+                problem_tree.add_entry(BLOB_MODE.to_string(), hash, file.clone());
+            }
+            let hash = add_tree(&mut repo, &problem_tree)?;
+            folder_tree.add_entry(DIR_MODE.to_string(), hash, dir.clone());
+        }
+        let hash = add_tree(&mut repo, &folder_tree)?;
+        root_tree.add_entry(DIR_MODE.to_string(), hash, folder.clone());
+    }
+
+    let problems_txt = problems
+        .iter()
+        .map(|p| format!("- {}. {} (`{}/`)", p.label, p.name, problem_dir(p)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let folders_txt = solutions
+        .iter()
+        .map(|(folder, by_problem)| format!("- [{folder}]({folder}/): {} solved", by_problem.len()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let readme = format!(
+        "# Accepted Solutions for {name}\n\nThis repo contains the most recent accepted solution of every participant in {name}, {solution_count} in total. Each folder is a team, or a participant who wasn't on one, with a folder for each problem they solved holding one file per member.\n\n## Problems\n\n{problems_txt}\n\n## Participants\n\n{folders_txt}\n\nExported by {display_name}, generated by {site_name} {version}\n",
+        name = contest.name,
+        display_name = user.display_name(),
+        version = env!("CARGO_PKG_VERSION"),
+        site_name = branding.name,
+    );
+    let hash = blob(&mut repo, &readme)?;
+    root_tree.add_entry(BLOB_MODE.to_string(), hash, "README.md".to_string());
+
+    commit_root(&mut repo, &root_tree, now)?;
+
+    let code = gen_code();
+    let now = chrono::Utc::now().naive_utc();
+
+    let mut repos = repos_handle.lock().await;
+
+    repos.insert(
+        (contest_id, user.id, ExportKind::All),
+        (code.clone(), repo, None, now),
+    );
+
+    let ctx = context_with_base_authed!(user, code, contest, solution_count);
+    Ok(Template::render("contests/export_all", ctx))
+}
@@ -0,0 +1,55 @@
+//! `metadata.json` for each problem folder, so an exported repo says how each solution did
+//! without having to go back to the site
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    contests::Contest,
+    error::prelude::*,
+    problems::{JudgeRun, Problem, ProblemCompletion, ReferenceVerdict},
+};
+
+#[derive(Serialize)]
+pub struct ProblemMetadata<'a> {
+    label: &'a str,
+    name: &'a str,
+    slug: &'a str,
+    /// Verdict of the most recent run, `None` if the problem was never attempted
+    verdict: Option<ReferenceVerdict>,
+    language: Option<&'a str>,
+    submitted_at: Option<NaiveDateTime>,
+    accepted_at: Option<NaiveDateTime>,
+    attempts: i64,
+    /// Wrong attempts counted against the participant, only ones before the first accepted run
+    wrong_attempts: i64,
+    penalty_minutes: i64,
+}
+
+impl<'a> ProblemMetadata<'a> {
+    pub fn new(
+        contest: &Contest,
+        problem: &'a Problem,
+        latest: Option<&'a JudgeRun>,
+        latest_success: Option<&'a JudgeRun>,
+        completion: Option<&ProblemCompletion>,
+        attempts: i64,
+    ) -> Self {
+        let wrong_attempts = completion.map_or(0, |c| c.number_wrong);
+        Self {
+            label: &problem.label,
+            name: &problem.name,
+            slug: &problem.slug,
+            verdict: latest.map(ReferenceVerdict::of_run),
+            language: latest.map(|r| r.language.as_str()),
+            submitted_at: latest.map(|r| r.ran_at),
+            accepted_at: latest_success.map(|r| r.ran_at),
+            attempts,
+            wrong_attempts,
+            penalty_minutes: wrong_attempts * contest.penalty,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize problem metadata")
+    }
+}
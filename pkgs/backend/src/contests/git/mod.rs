@@ -4,7 +4,11 @@ use chrono::NaiveDateTime;
 use rand::distr::Alphanumeric;
 use rand::Rng;
 use repo::FakeRepo;
-use rocket::{fairing::AdHoc, http::Status, State};
+use rocket::{
+    fairing::AdHoc,
+    http::{ContentType, Header, Status},
+    Responder, State,
+};
 use rocket_dyn_templates::Template;
 use tokio::sync::Mutex;
 
@@ -13,27 +17,53 @@ use crate::{
     branding::BrandingConfig,
     contests::git::{commit::Commit, refs::Ref},
     context_with_base_authed,
-    db::DbConnection,
+    db::{DbConnection, DbPoolConnection},
     error::prelude::*,
-    problems::{JudgeRun, Problem},
+    leaderboard::{standings_table, LeaderboardManagerHandle},
+    problems::{JudgeRun, Problem, ProblemCompletion},
+    rate_limit::{self, RateLimited},
     run::CodeInfo,
+    zip::ZipWriter,
 };
 
 use self::{
+    metadata::ProblemMetadata,
     object::{Object, ObjectType},
     tree::Tree,
 };
 
-use super::Contest;
+use super::{Contest, Participant};
 
+mod all;
 mod commit;
+mod metadata;
 mod object;
 mod refs;
 mod repo;
 mod store;
 mod tree;
 
-type RepoMap = HashMap<(i64, i64), (String, FakeRepo, NaiveDateTime)>;
+/// Which repo a link is for, a user can have one of each at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum ExportKind {
+    /// The user's own solutions
+    Own,
+    /// Every participant's accepted solutions, for judges, see [`all`]
+    All,
+}
+
+impl ExportKind {
+    fn from_repo_name(name: &str) -> Option<Self> {
+        match name {
+            "solutions.git" => Some(Self::Own),
+            "all-solutions.git" => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+/// Each export's code, repo, zip of the same files if there is one, and when it was generated
+type RepoMap = HashMap<(i64, i64, ExportKind), (String, FakeRepo, Option<Vec<u8>>, NaiveDateTime)>;
 type RepoMapHandle = Arc<Mutex<RepoMap>>;
 type RepoMapGuard<'a> = &'a State<RepoMapHandle>;
 
@@ -41,6 +71,66 @@ fn run_to_object(run: &JudgeRun) -> Result<Object> {
     Object::new(run.program.as_bytes().to_vec(), ObjectType::Blob)
 }
 
+/// Folder a problem's files go in, prefixed with its label so folders sort like the problem list
+fn problem_dir(problem: &Problem) -> String {
+    format!("{}-{}", problem.label, problem.slug)
+}
+
+/// File extension for programs in a language
+fn language_ext<'a>(info: &'a CodeInfo, language: &str) -> &'a str {
+    info.run_config
+        .languages
+        .get(language)
+        .and_then(|l| l.runner.file_name.split('.').next_back())
+        .unwrap_or("txt")
+}
+
+/// Commit a root tree already in the repo as `main` and tag it
+fn commit_root(repo: &mut FakeRepo, root_tree: &Tree, now: NaiveDateTime) -> Result {
+    repo.add_object(root_tree.to_object()?);
+
+    let root_hash = root_tree.to_object()?.get_hash_str();
+
+    let now_epoch = now.and_utc().timestamp();
+    let author = format!("Solution Exporter <solution-export@example.com> {now_epoch} +0000");
+    let commit = Commit::new(
+        root_hash.clone(),
+        String::new(),
+        author.clone(),
+        author,
+        String::new(),
+        "Initial Commit".to_string(),
+    );
+
+    let commit_obj = commit.to_object()?;
+    let commit_hash = commit_obj.get_hash_str();
+
+    // Add commit to the repo
+    repo.add_object(commit_obj);
+
+    repo.add_head("main", Ref::Object(commit_hash.clone()));
+    repo.add_tag("import", Ref::Object(commit_hash));
+    Ok(())
+}
+
+/// A repo that's still around, `None` if it expired or the code is wrong
+fn get_repo<'a>(
+    repos: &'a RepoMap,
+    contest_id: i64,
+    user_id: i64,
+    repo_name: &str,
+    code: &str,
+) -> Option<&'a FakeRepo> {
+    let kind = ExportKind::from_repo_name(repo_name)?;
+    let (real_code, repo, _, generated) = repos.get(&(contest_id, user_id, kind))?;
+    let now = chrono::Utc::now().naive_utc();
+    if now - *generated > chrono::Duration::minutes(CACHE_TIME_MINUTES as i64) || code != real_code
+    {
+        return None;
+    }
+    Some(repo)
+}
+
 fn gen_code() -> String {
     rand::rng()
         .sample_iter(&Alphanumeric)
@@ -51,7 +141,29 @@ fn gen_code() -> String {
 
 const CACHE_TIME_MINUTES: usize = 5;
 
+#[derive(Responder)]
+pub struct ZipDownload {
+    inner: (ContentType, Vec<u8>),
+    disposition: Header<'static>,
+}
+
+impl ZipDownload {
+    fn new(contest: &Contest, body: Vec<u8>) -> Self {
+        Self {
+            inner: (ContentType::ZIP, body),
+            disposition: Header::new(
+                "Content-Disposition",
+                format!(
+                    "attachment; filename=\"{}-solutions.zip\"",
+                    slug::slugify(&contest.name)
+                ),
+            ),
+        }
+    }
+}
+
 #[get("/contests/<contest_id>/export")]
+#[allow(clippy::too_many_arguments)]
 pub async fn export_solutions(
     user: &User,
     contest_id: i64,
@@ -59,28 +171,89 @@ pub async fn export_solutions(
     admin: Option<&Admin>,
     info: &State<CodeInfo>,
     branding: &State<BrandingConfig>,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
     repos_handle: RepoMapGuard<'_>,
 ) -> ResultResponse<Template> {
-    let (contest, _participant, can_edit) =
+    let (contest, participant, can_edit) =
+        Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    let (code, generated) = own_export(
+        &mut db,
+        user,
+        &contest,
+        participant.as_ref(),
+        info,
+        branding,
+        leaderboard_manager,
+        repos_handle,
+    )
+    .await?;
+    let ctx = context_with_base_authed!(user, code, generated, contest, can_edit);
+    Ok(Template::render("contests/export", ctx))
+}
+
+/// The same files as the git export as a zip, for anyone who doesn't use git
+#[get("/contests/<contest_id>/export.zip")]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_zip(
+    user: &User,
+    contest_id: i64,
+    mut db: DbConnection,
+    admin: Option<&Admin>,
+    info: &State<CodeInfo>,
+    branding: &State<BrandingConfig>,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+    repos_handle: RepoMapGuard<'_>,
+) -> ResultResponse<ZipDownload> {
+    let (contest, participant, _) =
         Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    own_export(
+        &mut db,
+        user,
+        &contest,
+        participant.as_ref(),
+        info,
+        branding,
+        leaderboard_manager,
+        repos_handle,
+    )
+    .await?;
+    let repos = repos_handle.lock().await;
+    let zip = repos
+        .get(&(contest_id, user.id, ExportKind::Own))
+        .and_then(|(_, _, zip, _)| zip.clone())
+        .ok_or(Status::NotFound)?;
+    Ok(ZipDownload::new(&contest, zip))
+}
 
+/// The code for a user's export of their own solutions, along with when it was generated if it
+/// was cached. The repo and zip are built if there isn't one from the last few minutes.
+#[allow(clippy::too_many_arguments)]
+async fn own_export(
+    db: &mut DbPoolConnection,
+    user: &User,
+    contest: &Contest,
+    participant: Option<&Participant>,
+    info: &CodeInfo,
+    branding: &BrandingConfig,
+    leaderboard_manager: &LeaderboardManagerHandle,
+    repos_handle: &RepoMapHandle,
+) -> Result<(String, Option<NaiveDateTime>)> {
+    let contest_id = contest.id;
     let now = chrono::Utc::now().naive_utc();
 
     let repos = repos_handle.lock().await;
-    if let Some((code, _, generated)) = repos.get(&(contest_id, user.id)) {
+    if let Some((code, _, _, generated)) = repos.get(&(contest_id, user.id, ExportKind::Own)) {
         if now - *generated < chrono::Duration::minutes(CACHE_TIME_MINUTES as i64) {
-            let ctx = context_with_base_authed!(user, code, generated, contest, can_edit);
-            return Ok(Template::render("contests/export", ctx));
+            return Ok((code.clone(), Some(*generated)));
         }
     }
     drop(repos);
 
-    let problems = Problem::list(&mut db, contest_id).await?;
+    let problems = Problem::list(db, contest_id).await?;
     let mut runs = Vec::with_capacity(problems.len());
     for problem in problems.iter() {
-        let latest_successful_run =
-            JudgeRun::get_latest_success(&mut db, user.id, problem.id).await?;
-        let latest_run = JudgeRun::get_latest(&mut db, user.id, problem.id).await?;
+        let latest_successful_run = JudgeRun::get_latest_success(db, user.id, problem.id).await?;
+        let latest_run = JudgeRun::get_latest(db, user.id, problem.id).await?;
         runs.push((
             latest_successful_run.map(|r| {
                 let obj = run_to_object(&r)
@@ -115,7 +288,7 @@ pub async fn export_solutions(
     let problem_description_objs = problems
         .iter()
         .map(|p| {
-            let md = format!("# {}\n\n{}\n", p.name, p.description.trim());
+            let md = format!("# {}. {}\n\n{}\n", p.label, p.name, p.description.trim());
             Object::new(md.as_bytes().to_vec(), ObjectType::Blob)
                 .context("Failed to serialize problem description")
         })
@@ -126,42 +299,61 @@ pub async fn export_solutions(
         repo.add_object(obj.clone());
     }
 
-    // Now make trees to represent folders for each problem
-    let problem_trees = runs
+    let mut problem_metadata_objs = Vec::with_capacity(problems.len());
+    for (problem, (sr, mr)) in problems.iter().zip(runs.iter()) {
+        let completion = match participant {
+            Some(p) => {
+                ProblemCompletion::get_for_problem_and_participant(db, problem.id, p.p_id).await?
+            }
+            None => None,
+        };
+        let attempts = JudgeRun::count(db, user.id, problem.id).await?;
+        let metadata = ProblemMetadata::new(
+            contest,
+            problem,
+            mr.as_ref().map(|(r, _)| r),
+            sr.as_ref().map(|(r, _)| r),
+            completion.as_ref(),
+            attempts,
+        );
+        let obj = Object::new(metadata.to_json()?.into_bytes(), ObjectType::Blob)
+            .context("Failed to serialize problem metadata")?;
+        repo.add_object(obj.clone());
+        problem_metadata_objs.push(obj);
+    }
+
+    // The files in each problem's folder, the same for the repo and the zip
+    let problem_files = runs
         .iter()
-        .zip(problem_description_objs.iter())
-        .map(|(runs, problems)| {
-            let mut tree = tree::Tree::new();
-            tree.add_entry(
-                BLOB_MODE.to_string(),
-                problems.get_hash(),
-                "description.md".to_string(),
-            );
+        .zip(
+            problem_description_objs
+                .iter()
+                .zip(problem_metadata_objs.iter()),
+        )
+        .map(|(runs, (description, metadata))| {
+            let mut files = vec![
+                ("description.md".to_string(), description),
+                ("metadata.json".to_string(), metadata),
+            ];
             if let Some((run, obj)) = &runs.0 {
-                let ext = info
-                    .run_config
-                    .languages
-                    .get(&run.language)
-                    .and_then(|l| l.runner.file_name.split('.').next_back())
-                    .unwrap_or("txt");
-                tree.add_entry(
-                    BLOB_MODE.to_string(),
-                    obj.get_hash(),
-                    format!("most-recent-success.{ext}"),
-                );
+                let ext = language_ext(info, &run.language);
+                files.push((format!("most-recent-success.{ext}"), obj));
             }
             if let Some((run, obj)) = &runs.1 {
-                let ext = info
-                    .run_config
-                    .languages
-                    .get(&run.language)
-                    .and_then(|l| l.runner.file_name.split('.').next_back())
-                    .unwrap_or("txt");
-                tree.add_entry(
-                    BLOB_MODE.to_string(),
-                    obj.get_hash(),
-                    format!("most-recent.{ext}"),
-                );
+                let ext = language_ext(info, &run.language);
+                files.push((format!("most-recent.{ext}"), obj));
+            }
+            files
+        })
+        .collect::<Vec<_>>();
+
+    // Now make trees to represent folders for each problem
+    let problem_trees = problem_files
+        .iter()
+        .map(|files| {
+            let mut tree = tree::Tree::new();
+            for (name, obj) in files.iter() {
+                tree.add_entry(BLOB_MODE.to_string(), obj.get_hash(), name.clone());
             }
             let obj = tree.to_object().unwrap();
             (tree, obj)
@@ -175,13 +367,13 @@ pub async fn export_solutions(
 
     let problems_txt = problems
         .iter()
-        .map(|p| format!("- [{}]({}/)", p.name, p.slug))
+        .map(|p| format!("- {}. [{}]({}/)", p.label, p.name, problem_dir(p)))
         .collect::<Vec<_>>()
         .join("\n");
 
     // TODO: Use branding name
     let readme = format!(
-        "# Solutions for {name}\n\nThis repo contains the solutions for {name} by {display_name}\n\n## Problems\n\n{problems_txt}\n\nGenerated by {site_name} {version}\n",
+        "# Solutions for {name}\n\nThis repo contains the solutions for {name} by {display_name}\n\nEach problem's folder has a `metadata.json` with the verdict, language and time of the most recent run and the wrong attempts counted against it. `standings.csv` is the leaderboard as it was when this was generated.\n\n## Problems\n\n{problems_txt}\n\nGenerated by {site_name} {version}\n",
         name = contest.name,
         display_name = user.display_name(),
         version = env!("CARGO_PKG_VERSION"),
@@ -194,13 +386,21 @@ pub async fn export_solutions(
     // Add README to the repo
     repo.add_object(readme_obj.clone());
 
+    // Standings as the leaderboard shows them, so frozen ones stay frozen
+    let standings = standings_table(db, leaderboard_manager, contest, false)
+        .await?
+        .to_csv();
+    let standings_obj = Object::new(standings.into_bytes(), ObjectType::Blob)
+        .context("Failed to serialize standings")?;
+    repo.add_object(standings_obj.clone());
+
     let mut root_tree = Tree::new();
 
     for (tree, problem) in problem_trees.iter().zip(problems.iter()) {
         root_tree.add_entry(
             DIR_MODE.to_string(),
             tree.1.get_hash(),
-            problem.slug.clone(),
+            problem_dir(problem),
         );
     }
 
@@ -210,97 +410,101 @@ pub async fn export_solutions(
         "README.md".to_string(),
     );
 
-    // Add root tree to the repo
-    repo.add_object(root_tree.to_object()?);
-
-    let root_hash = root_tree.to_object()?.get_hash_str();
-
-    let now_epoch = now.and_utc().timestamp();
-    let author = format!("Solution Exporter <solution-export@example.com> {now_epoch} +0000");
-    let commit = Commit::new(
-        root_hash.clone(),
-        String::new(),
-        author.clone(),
-        author,
-        String::new(),
-        "Initial Commit".to_string(),
+    root_tree.add_entry(
+        BLOB_MODE.to_string(),
+        standings_obj.get_hash(),
+        "standings.csv".to_string(),
     );
 
-    let commit_obj = commit.to_object()?;
-    let commit_hash = commit_obj.get_hash_str();
-
-    // Add commit to the repo
-    repo.add_object(commit_obj);
+    commit_root(&mut repo, &root_tree, now)?;
 
-    repo.add_head("main", Ref::Object(commit_hash.clone()));
-    repo.add_tag("import", Ref::Object(commit_hash));
+    let mut zip = ZipWriter::new(now);
+    for (files, problem) in problem_files.iter().zip(problems.iter()) {
+        for (name, obj) in files.iter() {
+            zip.add(&format!("{}/{name}", problem_dir(problem)), obj.contents())?;
+        }
+    }
+    zip.add("README.md", readme_obj.contents())?;
+    zip.add("standings.csv", standings_obj.contents())?;
+    let zip = zip.finish()?;
 
     let code = gen_code();
     let now = chrono::Utc::now().naive_utc();
 
     let mut repos = repos_handle.lock().await;
 
-    repos.insert((contest_id, user.id), (code.clone(), repo, now));
+    repos.insert(
+        (contest_id, user.id, ExportKind::Own),
+        (code.clone(), repo, Some(zip), now),
+    );
 
-    let ctx = context_with_base_authed!(user, code, contest, can_edit);
-    Ok(Template::render("contests/export", ctx))
+    Ok((code, None))
 }
 
-#[get("/contests/<contest_id>/export/<user_id>/<code>/solutions.git/info/refs")]
+/// The zip of an export, for downloading with the same link as the repo
+#[get("/contests/<contest_id>/export/<user_id>/<code>/solutions.zip")]
+async fn zip_download(
+    contest_id: i64,
+    user_id: i64,
+    code: &str,
+    mut db: DbConnection,
+    repos_handle: RepoMapGuard<'_>,
+) -> ResultResponse<ZipDownload> {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let repos = repos_handle.lock().await;
+    get_repo(&repos, contest_id, user_id, "solutions.git", code).ok_or(Status::NotFound)?;
+    let zip = repos
+        .get(&(contest_id, user_id, ExportKind::Own))
+        .and_then(|(_, _, zip, _)| zip.clone())
+        .ok_or(Status::NotFound)?;
+    Ok(ZipDownload::new(&contest, zip))
+}
+
+// Each clone or fetch starts here, the object requests after it aren't counted
+#[get("/contests/<contest_id>/export/<user_id>/<code>/<repo_name>/info/refs")]
 async fn git_info_refs(
     contest_id: i64,
     user_id: i64,
     code: &str,
+    repo_name: &str,
     repos_handle: RepoMapGuard<'_>,
+    _limit: RateLimited<rate_limit::Api>,
 ) -> ResultResponse<String> {
     let repos = repos_handle.lock().await;
-    let (real_code, repo, generated) = repos.get(&(contest_id, user_id)).ok_or(Status::NotFound)?;
-    let now = chrono::Utc::now().naive_utc();
-    if now - *generated > chrono::Duration::minutes(CACHE_TIME_MINUTES as i64) || code != real_code
-    {
-        return Err(Status::NotFound.into());
-    }
+    let repo = get_repo(&repos, contest_id, user_id, repo_name, code).ok_or(Status::NotFound)?;
 
     Ok(repo.dump_refs())
 }
 
-#[get("/contests/<contest_id>/export/<user_id>/<code>/solutions.git/objects/<folder>/<rest>")]
+#[get("/contests/<contest_id>/export/<user_id>/<code>/<repo_name>/objects/<folder>/<rest>")]
+#[allow(clippy::too_many_arguments)]
 async fn git_objects(
     contest_id: i64,
     user_id: i64,
     code: &str,
+    repo_name: &str,
     folder: &str,
     rest: &str,
     repos_handle: RepoMapGuard<'_>,
 ) -> ResultResponse<Vec<u8>> {
     let repos = repos_handle.lock().await;
-    let (real_code, repo, generated) = repos.get(&(contest_id, user_id)).ok_or(Status::NotFound)?;
-    let now = chrono::Utc::now().naive_utc();
-    if now - *generated > chrono::Duration::minutes(CACHE_TIME_MINUTES as i64) || code != real_code
-    {
-        return Err(Status::NotFound.into());
-    }
+    let repo = get_repo(&repos, contest_id, user_id, repo_name, code).ok_or(Status::NotFound)?;
 
     let obj = repo.get_object(folder, rest).ok_or(Status::NotFound)?;
 
     Ok(obj.compressed_serialize()?)
 }
 
-#[get("/contests/<contest_id>/export/<user_id>/<code>/solutions.git/HEAD")]
+#[get("/contests/<contest_id>/export/<user_id>/<code>/<repo_name>/HEAD")]
 async fn git_head(
     contest_id: i64,
     user_id: i64,
     code: &str,
+    repo_name: &str,
     repos_handle: RepoMapGuard<'_>,
 ) -> ResultResponse<String> {
     let repos = repos_handle.lock().await;
-    let (real_code, _repo, generated) =
-        repos.get(&(contest_id, user_id)).ok_or(Status::NotFound)?;
-    let now = chrono::Utc::now().naive_utc();
-    if now - *generated > chrono::Duration::minutes(CACHE_TIME_MINUTES as i64) || code != real_code
-    {
-        return Err(Status::NotFound.into());
-    }
+    get_repo(&repos, contest_id, user_id, repo_name, code).ok_or(Status::NotFound)?;
 
     let main_ref = Ref::Forward("refs/heads/main".to_string());
     Ok(main_ref.to_string())
@@ -318,14 +522,22 @@ pub fn stage() -> AdHoc {
                 .await;
                 let now = chrono::Utc::now().naive_utc();
                 let mut repos = handle_clone.lock().await;
-                repos.retain(|_, (_, _, generated)| {
+                repos.retain(|_, (_, _, _, generated)| {
                     now - *generated < chrono::Duration::minutes(CACHE_TIME_MINUTES as i64)
                 });
             }
         });
         rocket.manage(repo_map).mount(
             "/",
-            routes![export_solutions, git_info_refs, git_objects, git_head],
+            routes![
+                export_solutions,
+                export_zip,
+                zip_download,
+                all::export_all_solutions,
+                git_info_refs,
+                git_objects,
+                git_head
+            ],
         )
     })
 }
@@ -32,6 +32,10 @@ impl Object {
         Ok(Object { contents, o_type })
     }
 
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
+
     pub fn get_hash(&self) -> Vec<u8> {
         let mut hasher = Sha1::new();
         hasher.update(self.serialize());
@@ -1,8 +1,20 @@
+use std::collections::HashMap;
+
 use log::error;
-use rocket::{http::Status, post, response::Redirect, State};
+use rocket::{
+    form::Form,
+    http::{CookieJar, Status},
+    post,
+    response::Redirect,
+    FromForm, State,
+};
 
 use crate::{
-    auth::users::{Admin, User},
+    auth::{
+        challenge::{self, ProofOfWorkConfig, SpentChallenges},
+        impersonation::NotImpersonating,
+        users::{Admin, User},
+    },
     db::DbConnection,
     leaderboard::LeaderboardManagerHandle,
     messages::Message,
@@ -11,13 +23,52 @@ use crate::{
 
 use super::{Contest, Participant};
 
-#[post("/<contest_id>/join", rank = 10)]
+#[derive(FromForm)]
+pub struct JoinForm<'r> {
+    pow_solution: Option<&'r str>,
+    join_code: Option<&'r str>,
+    /// Answers to the contest's registration fields, keyed by their position
+    fields: HashMap<usize, &'r str>,
+}
+
+/// Check the answers against the contest's registration fields, returning them as a JSON
+/// array in the same order as the fields. Labels aren't unique, so answers aren't keyed by them
+fn registration_data(
+    contest: &Contest,
+    answers: &HashMap<usize, &str>,
+) -> Result<Option<String>, String> {
+    let fields = contest.registration_fields();
+    if fields.is_empty() {
+        return Ok(None);
+    }
+    let mut data = Vec::with_capacity(fields.len());
+    for (i, field) in fields.into_iter().enumerate() {
+        let answer = answers.get(&i).map(|a| a.trim()).unwrap_or_default();
+        if answer.is_empty() {
+            return Err(format!("Please fill out \"{}\"", field.label));
+        } else if answer.len() > 100 {
+            return Err(format!("\"{}\" must be under 100 characters", field.label));
+        } else if !field.options.is_empty() && !field.options.iter().any(|o| o == answer) {
+            return Err(format!("Please pick an option for \"{}\"", field.label));
+        }
+        data.push(answer.to_string());
+    }
+    Ok(Some(serde_json::to_string(&data).unwrap_or_default()))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/<contest_id>/join", data = "<form>", rank = 10)]
 pub async fn join_contest(
     mut db: DbConnection,
     contest_id: i64,
     leaderboard_handle: &State<LeaderboardManagerHandle>,
+    pow_config: &State<ProofOfWorkConfig>,
+    spent_challenges: &State<SpentChallenges>,
+    cookies: &CookieJar<'_>,
+    form: Form<JoinForm<'_>>,
     user: &User,
     admin: Option<&Admin>,
+    _read_write: NotImpersonating,
 ) -> FormResponse {
     let contest = Contest::get_or_404(&mut db, contest_id).await?;
     if admin.is_some()
@@ -26,25 +77,66 @@ pub async fn join_contest(
             .is_some()
     {
         Ok(Redirect::to(format!("/contests/{}/", contest_id)))
+    } else if !contest.published {
+        Err(Status::NotFound.into())
     } else if contest.can_register() {
-        if let Some(max_participants) = &contest.max_participants {
-            let participants = Participant::list_not_judge(&mut db, contest_id).await?;
-            if participants.len() >= *max_participants as usize {
-                return Err(Status::Forbidden.into());
+        if !challenge::verify(pow_config, spent_challenges, cookies, form.pow_solution) {
+            return Ok(
+                Message::error("Couldn't verify your browser, please try again")
+                    .to(&format!("/contests/{}/", contest_id)),
+            );
+        }
+        if let Some(join_code) = &contest.join_code {
+            if form.join_code.map(str::trim) != Some(join_code.as_str()) {
+                return Ok(Message::error("That join code isn't right")
+                    .to(&format!("/contests/{}/", contest_id)));
             }
         }
-        let participant = Participant::temp(user.id, contest_id, false);
-        if let Err(why) = participant.insert(&mut db).await {
-            error!("Error inserting participant: {:?}", why);
-            Err(Status::InternalServerError.into())
+        let registration_data = match registration_data(&contest, &form.fields) {
+            Ok(data) => data,
+            Err(why) => return Ok(Message::error(&why).to(&format!("/contests/{}/", contest_id))),
+        };
+        let mut participant = Participant::temp(user.id, contest_id, false);
+        participant.pending = contest.require_approval;
+        participant.registration_data = registration_data;
+        let participant = match participant.insert_or_waitlist(&mut db, &contest).await {
+            Ok(participant) => participant,
+            Err(why) => {
+                error!("Error inserting participant: {:?}", why);
+                return Err(Status::InternalServerError.into());
+            }
+        };
+        if participant.waitlisted {
+            let position = participant
+                .waitlist_position(&mut db)
+                .await?
+                .unwrap_or_default();
+            Ok(Message::info(
+                "This contest is full, you're number {position} on the waitlist. You'll get a spot if one opens up",
+            )
+            .arg("position", position)
+            .to(&format!("/contests/{}/", contest_id)))
+        } else if participant.pending {
+            Ok(
+                Message::info("Thanks for signing up! The judges will review your registration")
+                    .to(&format!("/contests/{}/", contest_id)),
+            )
         } else {
             let mut leaderboard_manager = leaderboard_handle.lock().await;
             leaderboard_manager
                 .refresh_leaderboard(&mut db, &contest)
                 .await?;
 
-            Ok(Message::success(&format!("Welcome to {}!", contest.name))
-                .to(&format!("/contests/{}/", contest_id)))
+            if contest.rules.is_some() {
+                Ok(
+                    Message::info("Please read and accept the rules for this contest")
+                        .to(&format!("/contests/{}/rules", contest_id)),
+                )
+            } else {
+                Ok(Message::success("Welcome to {name}!")
+                    .arg("name", &contest.name)
+                    .to(&format!("/contests/{}/", contest_id)))
+            }
         }
     } else {
         Err(Status::Forbidden.into())
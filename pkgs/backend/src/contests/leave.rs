@@ -0,0 +1,97 @@
+use log::info;
+use rocket::{get, http::Status, post, response::Redirect, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        impersonation::NotImpersonating,
+        users::User,
+    },
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    leaderboard::LeaderboardManagerHandle,
+    messages::Message,
+};
+
+use super::{
+    teams::{Team, TeamMember},
+    Contest, Participant,
+};
+
+/// The user's participation, or a message explaining why they can't withdraw
+async fn get_withdrawal(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    user: &User,
+) -> ResultResponse<Result<(Contest, Participant), Redirect>> {
+    let contest = Contest::get_or_404(db, contest_id).await?;
+    let participant = Participant::get(db, contest_id, user.id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    match participant.withdrawal_blocked(db, &contest).await? {
+        Some(why) => Ok(Err(
+            Message::error(why).to(&format!("/contests/{contest_id}/"))
+        )),
+        None => Ok(Ok((contest, participant))),
+    }
+}
+
+#[get("/<contest_id>/leave")]
+pub async fn leave_get(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    _token: &CsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    let (contest, participant) = match get_withdrawal(&mut db, contest_id, user).await? {
+        Ok(found) => found,
+        Err(redirect) => return Ok(Err(redirect)),
+    };
+    let ctx = context_with_base_authed!(user, contest, participant);
+    Ok(Ok(Template::render("contests/leave", ctx)))
+}
+
+/// Give up a spot, or a place on the waitlist. The participant is taken off their team and
+/// whoever is next on the waitlist gets the spot
+#[post("/<contest_id>/leave")]
+pub async fn leave_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    leaderboard_handle: &State<LeaderboardManagerHandle>,
+    user: &User,
+    _token: &VerifyCsrfToken,
+    _read_write: NotImpersonating,
+) -> ResultResponse<Redirect> {
+    let (contest, participant) = match get_withdrawal(&mut db, contest_id, user).await? {
+        Ok(found) => found,
+        Err(redirect) => return Ok(redirect),
+    };
+    if let Some(team) = Team::for_participant(&mut db, participant.p_id).await? {
+        if let Some(member) = TeamMember::get(&mut db, team.id, participant.p_id).await? {
+            team.remove_member(&mut db, member).await?;
+        }
+    }
+    TeamMember::clear_invites(&mut db, participant.p_id).await?;
+    let p_id = participant.p_id;
+    participant.delete(&mut db).await?;
+
+    let promoted = Participant::promote_waitlisted(&mut db, &contest).await?;
+    if !promoted.is_empty() {
+        info!(
+            "Gave {} waitlisted participant(s) a spot in contest {}",
+            promoted.len(),
+            contest_id
+        );
+    }
+    let mut leaderboard_manager = leaderboard_handle.lock().await;
+    leaderboard_manager
+        .delete_participant_for_contest(p_id, contest_id)
+        .await;
+    leaderboard_manager
+        .refresh_leaderboard(&mut db, &contest)
+        .await?;
+    Ok(Message::success("You've withdrawn from the contest")
+        .to(&format!("/contests/{contest_id}/")))
+}
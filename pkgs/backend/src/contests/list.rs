@@ -19,7 +19,11 @@ pub async fn contests_list(
     timezone: ClientTimeZone,
     mut db: DbConnection,
 ) -> ResultResponse<Template> {
-    let contests = Contest::list(&mut db).await?;
+    let contests = if admin.is_some() {
+        Contest::list(&mut db).await?
+    } else {
+        Contest::list_visible(&mut db, user.map(|u| u.id)).await?
+    };
     let tz = timezone.timezone();
     let start_times = contests
         .iter()
@@ -15,16 +15,30 @@ use crate::{
 };
 
 mod admin;
+mod binding;
+mod calendar;
 mod delete;
 mod edit;
 mod git;
 mod join;
+mod leave;
 mod list;
 mod new;
 mod participant;
+mod publish;
+mod rules;
+mod source_checks;
+mod statements;
+mod status;
+mod submissions;
+mod teams;
 mod view;
+mod visibility;
 
-pub use participant::Participant;
+pub use binding::{BindingAllowance, BindingMode, BindingVerdict, ClientOrigin, SessionBinding};
+pub use participant::{Participant, ParticipantRole};
+pub use teams::{Team, TeamMember};
+pub use visibility::ContestVisibility;
 
 #[derive(Serialize, Clone)]
 pub struct Contest {
@@ -41,6 +55,62 @@ pub struct Contest {
     pub penalty: i64,
     max_participants: Option<i64>,
     created_at: Option<NaiveDateTime>,
+    /// Markdown document participants must accept before seeing problems
+    pub rules: Option<String>,
+    /// Reuse accepted verdicts for identical submissions instead of running them again
+    pub verdict_cache: bool,
+    /// Registrations wait for a judge to approve them before the participant can compete
+    pub require_approval: bool,
+    /// Extra questions asked when registering, see [`Contest::registration_fields`]
+    pub registration_fields: Option<String>,
+    pub visibility: ContestVisibility,
+    /// Code participants need to enter to join, hidden from everyone but judges
+    #[serde(skip_serializing)]
+    pub join_code: Option<String>,
+    /// Participants can send code to be printed and delivered to them, for onsite contests
+    pub printing_enabled: bool,
+    /// Show judges' comments on runs to participants right away instead of after the contest ends
+    pub comments_during_contest: bool,
+    /// Set while the clock is paused, see [`Contest::pause_clock`]
+    #[serde(serialize_with = "crate::times::serialize_option_to_js")]
+    pub paused_at: Option<NaiveDateTime>,
+    /// Earlier pauses, see [`Contest::pauses`]
+    #[serde(skip_serializing)]
+    pub pauses: Option<String>,
+    /// Drafts are hidden from everyone but judges and admins
+    pub published: bool,
+    /// When a draft gets published automatically, see [`Contest::publish_due`]
+    #[serde(serialize_with = "crate::times::serialize_option_to_js")]
+    pub publish_at: Option<NaiveDateTime>,
+    /// CPU time new problems start with, in seconds
+    pub default_cpu_time: i64,
+    /// Memory limit new problems start with, in MiB
+    pub default_memory_limit: i64,
+    /// Languages new problems start with, see [`crate::problems::Problem::allowed_languages`]
+    pub default_languages: String,
+    /// Largest team participants can form, teams are turned off when unset
+    pub max_team_size: Option<i64>,
+    /// Participants can leave on their own before the contest starts, see [`Participant::can_leave`]
+    pub allow_withdrawal: bool,
+    /// Spectators see the real standings while the leaderboard is frozen
+    pub spectators_see_unfrozen: bool,
+    /// Record pastes and focus loss in the problem editor for judges, see `problems::telemetry`
+    pub editor_telemetry: bool,
+    /// Tie competitors' submissions to where they first submitted from, see [`SessionBinding`]
+    pub session_binding: BindingMode,
+    /// Longest line a program can have, in characters, see [`Contest::check_source`]
+    pub max_line_length: Option<i64>,
+    /// Regexes programs can't match, one per line
+    pub banned_patterns: Option<String>,
+    /// Lines every program needs near the top, like a team name or honor code comment
+    pub required_header: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RegistrationField {
+    pub label: String,
+    /// Allowed answers, empty if any answer is accepted
+    pub options: Vec<String>,
 }
 
 impl Contest {
@@ -54,6 +124,27 @@ impl Contest {
         freeze_time: i64,
         penalty: i64,
         max_participants: Option<i64>,
+        rules: Option<String>,
+        verdict_cache: bool,
+        require_approval: bool,
+        registration_fields: Option<String>,
+        visibility: ContestVisibility,
+        join_code: Option<String>,
+        printing_enabled: bool,
+        comments_during_contest: bool,
+        published: bool,
+        publish_at: Option<NaiveDateTime>,
+        default_cpu_time: i64,
+        default_memory_limit: i64,
+        default_languages: String,
+        max_team_size: Option<i64>,
+        allow_withdrawal: bool,
+        spectators_see_unfrozen: bool,
+        editor_telemetry: bool,
+        session_binding: BindingMode,
+        max_line_length: Option<i64>,
+        banned_patterns: Option<String>,
+        required_header: Option<String>,
     ) -> Self {
         Self {
             id: 0,
@@ -66,6 +157,29 @@ impl Contest {
             penalty,
             max_participants,
             created_at: None,
+            rules,
+            verdict_cache,
+            require_approval,
+            registration_fields,
+            visibility,
+            join_code,
+            printing_enabled,
+            comments_during_contest,
+            paused_at: None,
+            pauses: None,
+            published,
+            publish_at,
+            default_cpu_time,
+            default_memory_limit,
+            default_languages,
+            max_team_size,
+            allow_withdrawal,
+            spectators_see_unfrozen,
+            editor_telemetry,
+            session_binding,
+            max_line_length,
+            banned_patterns,
+            required_header,
         }
     }
 
@@ -76,6 +190,35 @@ impl Contest {
             .context("Error fetching contests")
     }
 
+    /// Contests belonging to a series in the order they were held
+    pub async fn list_in_series(db: &mut DbPoolConnection, series_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Contest,
+            "SELECT contest.* FROM contest JOIN series_contest ON contest.id = series_contest.contest_id WHERE series_contest.series_id = ? ORDER BY contest.start_time ASC",
+            series_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Error fetching contests in series {}", series_id))
+    }
+
+    /// Contests to show in the list, unlisted and private contests are only shown to their members
+    /// and drafts only to their judges
+    pub async fn list_visible(
+        db: &mut DbPoolConnection,
+        user_id: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Contest,
+            "SELECT * FROM contest WHERE (published OR id IN (SELECT contest_id FROM participant WHERE user_id = ? AND is_judge)) AND (visibility = 'Public' OR id IN (SELECT contest_id FROM participant WHERE user_id = ?)) ORDER BY created_at DESC",
+            user_id,
+            user_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Error fetching visible contests")
+    }
+
     pub async fn list_user_in(db: &mut DbPoolConnection, user_id: i64) -> Result<Vec<Self>> {
         sqlx::query_as!(Contest, "SELECT contest.* FROM contest JOIN participant ON contest.id = participant.contest_id WHERE participant.user_id = ?", user_id)
             .fetch_all(&mut **db)
@@ -100,11 +243,17 @@ impl Contest {
         admin: Option<&Admin>,
     ) -> ResultResponse<(Self, Option<Participant>)> {
         let contest = Self::get_or_404(db, id).await?;
-        let participant = Participant::get(db, id, user.id).await?;
+        let mut participant = Participant::get(db, id, user.id).await?;
         let is_judge = participant.as_ref().is_some_and(|p| p.is_judge);
         if !is_judge && admin.is_none() {
             Err(Status::Forbidden.into())
         } else {
+            if let Some(participant) = participant.as_mut() {
+                // Doubles as the heartbeat for judge presence, not worth failing the request over
+                if let Err(why) = participant.record_activity(db).await {
+                    log::error!("Couldn't record judge activity: {:?}", why);
+                }
+            }
             Ok((contest, participant))
         }
     }
@@ -121,9 +270,16 @@ impl Contest {
         } else {
             None
         };
+        if !contest.is_visible_to(participant.as_ref(), admin) {
+            return Err(Status::NotFound.into());
+        }
         let can_edit = admin.is_some() || participant.as_ref().is_some_and(|p| p.is_judge);
         let started = contest.has_started();
-        if !started && !can_edit {
+        let locked_out = !can_edit
+            && participant
+                .as_ref()
+                .is_some_and(|p| p.is_locked_out(&contest));
+        if (!started && !can_edit) || locked_out {
             Err(Status::Forbidden.into())
         } else {
             Ok((contest, participant, admin.is_some()))
@@ -133,7 +289,7 @@ impl Contest {
     pub async fn insert(&self, db: &mut DbPoolConnection) -> Result<Self> {
         sqlx::query_as!(
             Contest,
-            "INSERT INTO contest (name, description, start_time, registration_deadline, end_time, freeze_time, penalty, max_participants) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
+            "INSERT INTO contest (name, description, start_time, registration_deadline, end_time, freeze_time, penalty, max_participants, rules, verdict_cache, require_approval, registration_fields, visibility, join_code, printing_enabled, comments_during_contest, published, publish_at, default_cpu_time, default_memory_limit, default_languages, max_team_size, allow_withdrawal, spectators_see_unfrozen, editor_telemetry, session_binding, max_line_length, banned_patterns, required_header) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
             self.name,
             self.description,
             self.start_time,
@@ -141,14 +297,35 @@ impl Contest {
             self.end_time,
             self.freeze_time,
             self.penalty,
-            self.max_participants
+            self.max_participants,
+            self.rules,
+            self.verdict_cache,
+            self.require_approval,
+            self.registration_fields,
+            self.visibility,
+            self.join_code,
+            self.printing_enabled,
+            self.comments_during_contest,
+            self.published,
+            self.publish_at,
+            self.default_cpu_time,
+            self.default_memory_limit,
+            self.default_languages,
+            self.max_team_size,
+            self.allow_withdrawal,
+            self.spectators_see_unfrozen,
+            self.editor_telemetry,
+            self.session_binding,
+            self.max_line_length,
+            self.banned_patterns,
+            self.required_header
         ).fetch_one(&mut **db).await.context("Error inserting contest")
     }
 
     pub async fn update(&self, db: &mut DbPoolConnection) -> Result {
         sqlx::query_as!(
             Contest,
-            "UPDATE contest SET name = ?, description = ?, start_time = ?, registration_deadline = ?, end_time = ?, freeze_time = ?, penalty = ?, max_participants = ? WHERE id = ?",
+            "UPDATE contest SET name = ?, description = ?, start_time = ?, registration_deadline = ?, end_time = ?, freeze_time = ?, penalty = ?, max_participants = ?, rules = ?, verdict_cache = ?, require_approval = ?, registration_fields = ?, visibility = ?, join_code = ?, printing_enabled = ?, comments_during_contest = ?, published = ?, publish_at = ?, default_cpu_time = ?, default_memory_limit = ?, default_languages = ?, max_team_size = ?, allow_withdrawal = ?, spectators_see_unfrozen = ?, editor_telemetry = ?, session_binding = ?, max_line_length = ?, banned_patterns = ?, required_header = ? WHERE id = ?",
             self.name,
             self.description,
             self.start_time,
@@ -157,6 +334,27 @@ impl Contest {
             self.freeze_time,
             self.penalty,
             self.max_participants,
+            self.rules,
+            self.verdict_cache,
+            self.require_approval,
+            self.registration_fields,
+            self.visibility,
+            self.join_code,
+            self.printing_enabled,
+            self.comments_during_contest,
+            self.published,
+            self.publish_at,
+            self.default_cpu_time,
+            self.default_memory_limit,
+            self.default_languages,
+            self.max_team_size,
+            self.allow_withdrawal,
+            self.spectators_see_unfrozen,
+            self.editor_telemetry,
+            self.session_binding,
+            self.max_line_length,
+            self.banned_patterns,
+            self.required_header,
             self.id
         ).execute(&mut **db).await.map(|_| ()).with_context(|| format!("Error updating contest with id: {}", self.id))
     }
@@ -169,28 +367,127 @@ impl Contest {
             .with_context(|| format!("Error deleting contest {}", self.id))
     }
 
+    /// Private contests are only visible to their participants, judges and admins, drafts only to judges and admins
+    pub fn is_visible_to(&self, participant: Option<&Participant>, admin: Option<&Admin>) -> bool {
+        if !self.published {
+            return admin.is_some() || participant.is_some_and(|p| p.is_judge);
+        }
+        self.visibility != ContestVisibility::Private || participant.is_some() || admin.is_some()
+    }
+
+    /// Publish drafts whose scheduled time has passed, returning the contests that were published
+    pub async fn publish_due(db: &mut DbPoolConnection) -> Result<Vec<Self>> {
+        let now = chrono::offset::Utc::now().naive_utc();
+        sqlx::query_as!(
+            Contest,
+            "UPDATE contest SET published = TRUE, publish_at = NULL WHERE NOT published AND publish_at <= ? RETURNING *",
+            now
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Error publishing scheduled contests")
+    }
+
     pub fn has_started(&self) -> bool {
         let now = chrono::offset::Utc::now().naive_utc();
         self.start_time < now
     }
 
+    /// A paused contest hasn't ended even if its end time passed, resuming pushes the end back
     pub fn has_ended(&self) -> bool {
+        self.has_ended_for(None)
+    }
+
+    /// Whether the contest is over for a participant, who might have been given extra time
+    pub fn has_ended_for(&self, participant: Option<&Participant>) -> bool {
         let now = chrono::offset::Utc::now().naive_utc();
-        self.end_time < now
+        self.end_time_for(participant) < now && !self.is_paused()
+    }
+
+    /// When the contest ends for a participant, anyone given extra time gets it added on
+    pub fn end_time_for(&self, participant: Option<&Participant>) -> NaiveDateTime {
+        let extra_minutes = participant.map_or(0, |p| p.extra_minutes);
+        self.end_time + chrono::Duration::minutes(extra_minutes)
     }
 
     pub fn is_frozen(&self) -> bool {
         if self.freeze_time == 0 {
             return false;
         }
-        let now = chrono::offset::Utc::now().naive_utc();
+        let now = self.clock_now();
         let freeze_time_utc = self.end_time - chrono::Duration::minutes(self.freeze_time);
         freeze_time_utc < now && self.end_time > now
     }
 
     pub fn is_running(&self) -> bool {
+        self.is_running_for(None)
+    }
+
+    /// Whether a participant can still compete, counting any extra time they were given
+    pub fn is_running_for(&self, participant: Option<&Participant>) -> bool {
         let now = chrono::offset::Utc::now().naive_utc();
-        self.start_time < now && self.end_time > now
+        self.start_time < now && self.end_time_for(participant) > now && !self.is_paused()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// The current time as far as the contest is concerned, which stands still while paused
+    fn clock_now(&self) -> NaiveDateTime {
+        self.paused_at
+            .unwrap_or_else(|| chrono::offset::Utc::now().naive_utc())
+    }
+
+    /// Finished pauses as `(paused at, resumed at)`, oldest first
+    pub fn pauses(&self) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        self.pauses
+            .as_deref()
+            .and_then(|p| serde_json::from_str(p).ok())
+            .unwrap_or_default()
+    }
+
+    /// Total time the clock spent paused in earlier pauses
+    pub fn paused_seconds(&self) -> i64 {
+        self.pauses()
+            .iter()
+            .map(|(start, end)| (*end - *start).num_seconds())
+            .sum()
+    }
+
+    /// Stop the clock, participants can't submit until it's resumed
+    pub fn pause_clock(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(chrono::offset::Utc::now().naive_utc());
+        }
+    }
+
+    /// Start the clock again, the end time moves back by however long it was paused for
+    pub fn resume_clock(&mut self) -> Result {
+        if let Some(paused_at) = self.paused_at.take() {
+            let now = chrono::offset::Utc::now().naive_utc();
+            self.end_time += now - paused_at;
+            let mut pauses = self.pauses();
+            pauses.push((paused_at, now));
+            self.pauses =
+                Some(serde_json::to_string(&pauses).context("Failed to serialize pauses")?);
+        }
+        Ok(())
+    }
+
+    /// Save changes to the end time and pauses, kept apart from [`Contest::update`] so an edit can't undo a pause
+    pub async fn save_clock(&self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!(
+            "UPDATE contest SET end_time = ?, paused_at = ?, pauses = ? WHERE id = ?",
+            self.end_time,
+            self.paused_at,
+            self.pauses,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to save clock of contest {}", self.id))
     }
 
     pub fn can_register(&self) -> bool {
@@ -198,20 +495,73 @@ impl Contest {
         self.registration_deadline > now
     }
 
+    /// Parse the registration questions, one per line as either `Label` or `Label: option, option`
+    pub fn registration_fields(&self) -> Vec<RegistrationField> {
+        self.registration_fields
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|line| match line.split_once(':') {
+                Some((label, options)) => RegistrationField {
+                    label: label.trim().to_string(),
+                    options: options
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|o| !o.is_empty())
+                        .map(|o| o.to_string())
+                        .collect(),
+                },
+                None => RegistrationField {
+                    label: line.to_string(),
+                    options: vec![],
+                },
+            })
+            .collect()
+    }
+
     pub fn progress(&self) -> usize {
-        let now = chrono::offset::Utc::now().naive_utc();
-        let total_seconds = (self.end_time - self.start_time).num_seconds() as f64;
-        let elapsed_seconds = (now - self.start_time).num_seconds() as f64;
+        let pauses = self.pauses();
+        let total_seconds =
+            clock_time_between(&pauses, self.start_time, self.end_time).num_seconds() as f64;
+        let elapsed_seconds =
+            clock_time_between(&pauses, self.start_time, self.clock_now()).num_seconds() as f64;
         (elapsed_seconds / total_seconds * 100.0) as usize
     }
 
+    /// How far into the contest the clock is, from `0` before it starts to its whole length once
+    /// it's over
+    pub fn elapsed_seconds(&self) -> i64 {
+        let pauses = self.pauses();
+        let now = self.clock_now().max(self.start_time).min(self.end_time);
+        clock_time_between(&pauses, self.start_time, now).num_seconds()
+    }
+
     pub fn freeze_percent(&self) -> usize {
-        let total_minutes = (self.end_time - self.start_time).num_minutes() as f64;
+        let total_minutes =
+            clock_time_between(&self.pauses(), self.start_time, self.end_time).num_minutes() as f64;
         let freeze_minutes = total_minutes - (self.freeze_time as f64);
         (freeze_minutes / total_minutes * 100.0) as usize
     }
 }
 
+/// How long the contest clock ran between two times, leaving out any of the given pauses
+pub fn clock_time_between(
+    pauses: &[(NaiveDateTime, NaiveDateTime)],
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> chrono::Duration {
+    pauses.iter().fold(to - from, |time, (start, end)| {
+        let overlap = (*end).min(to) - (*start).max(from);
+        if overlap > chrono::Duration::zero() {
+            time - overlap
+        } else {
+            time
+        }
+    })
+}
+
 struct ContestFormTemplate<'r> {
     contest: Option<&'r Contest>,
     judges: &'r Vec<User>,
@@ -267,6 +617,95 @@ impl<'r> TemplatedForm for ContestFormTemplate<'r> {
                         .map(|i| i.to_string())
                         .unwrap_or("null".to_string()),
                 ),
+                (
+                    "rules".to_string(),
+                    contest.rules.clone().unwrap_or_default(),
+                ),
+                (
+                    "verdict_cache".to_string(),
+                    contest.verdict_cache.to_string(),
+                ),
+                (
+                    "require_approval".to_string(),
+                    contest.require_approval.to_string(),
+                ),
+                (
+                    "registration_fields".to_string(),
+                    contest.registration_fields.clone().unwrap_or_default(),
+                ),
+                ("visibility".to_string(), String::from(contest.visibility)),
+                (
+                    "join_code".to_string(),
+                    contest.join_code.clone().unwrap_or_default(),
+                ),
+                (
+                    "printing_enabled".to_string(),
+                    contest.printing_enabled.to_string(),
+                ),
+                (
+                    "comments_during_contest".to_string(),
+                    contest.comments_during_contest.to_string(),
+                ),
+                ("published".to_string(), contest.published.to_string()),
+                (
+                    "publish_at".to_string(),
+                    contest
+                        .publish_at
+                        .map(|t| {
+                            datetime_to_html_time(&self.timezone.timezone().from_utc_datetime(&t))
+                        })
+                        .unwrap_or_default(),
+                ),
+                (
+                    "default_cpu_time".to_string(),
+                    contest.default_cpu_time.to_string(),
+                ),
+                (
+                    "default_memory_limit".to_string(),
+                    contest.default_memory_limit.to_string(),
+                ),
+                (
+                    "default_languages".to_string(),
+                    contest.default_languages.replace(',', ", "),
+                ),
+                (
+                    "max_team_size".to_string(),
+                    contest
+                        .max_team_size
+                        .map(|i| i.to_string())
+                        .unwrap_or_default(),
+                ),
+                (
+                    "allow_withdrawal".to_string(),
+                    contest.allow_withdrawal.to_string(),
+                ),
+                (
+                    "spectators_see_unfrozen".to_string(),
+                    contest.spectators_see_unfrozen.to_string(),
+                ),
+                (
+                    "editor_telemetry".to_string(),
+                    contest.editor_telemetry.to_string(),
+                ),
+                (
+                    "session_binding".to_string(),
+                    String::from(contest.session_binding),
+                ),
+                (
+                    "max_line_length".to_string(),
+                    contest
+                        .max_line_length
+                        .map(|i| i.to_string())
+                        .unwrap_or_default(),
+                ),
+                (
+                    "banned_patterns".to_string(),
+                    contest.banned_patterns.clone().unwrap_or_default(),
+                ),
+                (
+                    "required_header".to_string(),
+                    contest.required_header.clone().unwrap_or_default(),
+                ),
             ]);
             for judge in self.judges.iter() {
                 map.insert(format!("judges[{}]", judge.id), "true".to_string());
@@ -282,6 +721,27 @@ impl<'r> TemplatedForm for ContestFormTemplate<'r> {
                 ("freeze_time".to_string(), "0".to_string()),
                 ("penalty".to_string(), "30".to_string()),
                 ("max_participants".to_string(), "".to_string()),
+                ("rules".to_string(), "".to_string()),
+                ("verdict_cache".to_string(), "false".to_string()),
+                ("require_approval".to_string(), "false".to_string()),
+                ("registration_fields".to_string(), "".to_string()),
+                ("visibility".to_string(), "Public".to_string()),
+                ("join_code".to_string(), "".to_string()),
+                ("printing_enabled".to_string(), "false".to_string()),
+                ("comments_during_contest".to_string(), "false".to_string()),
+                ("published".to_string(), "true".to_string()),
+                ("publish_at".to_string(), String::new()),
+                ("default_cpu_time".to_string(), "1".to_string()),
+                ("default_memory_limit".to_string(), "125".to_string()),
+                ("default_languages".to_string(), String::new()),
+                ("max_team_size".to_string(), String::new()),
+                ("allow_withdrawal".to_string(), "true".to_string()),
+                ("spectators_see_unfrozen".to_string(), "false".to_string()),
+                ("editor_telemetry".to_string(), "false".to_string()),
+                ("session_binding".to_string(), "Off".to_string()),
+                ("max_line_length".to_string(), String::new()),
+                ("banned_patterns".to_string(), String::new()),
+                ("required_header".to_string(), String::new()),
             ])
         }
     }
@@ -327,6 +787,21 @@ fn within_bound<'r, 'e>(
     }
 }
 
+#[inline]
+fn valid_join_code<'r, 'e>(
+    join_code: &'r Option<&'r str>,
+    visibility: &'r ContestVisibility,
+) -> Result<(), rocket::form::Errors<'e>> {
+    let code = join_code.map(str::trim).unwrap_or_default();
+    if code.len() > 50 {
+        Err(form::Error::validation("Must be 50 characters or less").into())
+    } else if *visibility == ContestVisibility::Private && code.is_empty() {
+        Err(form::Error::validation("Private contests need a join code").into())
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(FromForm)]
 struct ContestForm<'r> {
     #[field(validate = len(1..=100))]
@@ -343,24 +818,78 @@ struct ContestForm<'r> {
     penalty: i64,
     #[field(validate = over_1())]
     max_participants: Option<i64>,
+    rules: Option<&'r str>,
+    verdict_cache: bool,
+    require_approval: bool,
+    #[field(validate = len_under_1000())]
+    registration_fields: Option<&'r str>,
+    visibility: ContestVisibility,
+    #[field(validate = valid_join_code(&self.visibility))]
+    join_code: Option<&'r str>,
+    printing_enabled: bool,
+    comments_during_contest: bool,
+    published: bool,
+    publish_at: Option<FormDateTime>,
+    #[field(validate = range(1..=100))]
+    default_cpu_time: i64,
+    #[field(validate = range(1..))]
+    default_memory_limit: i64,
+    default_languages: &'r str,
+    #[field(validate = over_1())]
+    max_team_size: Option<i64>,
+    allow_withdrawal: bool,
+    spectators_see_unfrozen: bool,
+    editor_telemetry: bool,
+    session_binding: BindingMode,
+    #[field(validate = over_1())]
+    max_line_length: Option<i64>,
+    #[field(validate = len_under_1000())]
+    #[field(validate = source_checks::valid_banned_patterns())]
+    banned_patterns: Option<&'r str>,
+    #[field(validate = len_under_1000())]
+    required_header: Option<&'r str>,
     judges: HashMap<i64, bool>,
 }
 
 pub fn stage() -> AdHoc {
     AdHoc::on_ignite("Contests App", |rocket| async {
-        rocket.attach(admin::stage()).attach(git::stage()).mount(
-            "/contests",
-            routes![
-                list::contests_list,
-                new::new_contest_get,
-                new::new_contest_post,
-                edit::edit_contest_get,
-                edit::edit_contest_post,
-                delete::delete_contest_get,
-                delete::delete_contest_post,
-                join::join_contest,
-                view::view_contest,
-            ],
-        )
+        let url = rocket
+            .figment()
+            .extract_inner::<String>("url")
+            .unwrap_or_default();
+        let statements_config = rocket
+            .figment()
+            .extract_inner::<statements::StatementsConfig>("statements")
+            .unwrap_or_default();
+        rocket
+            .manage(calendar::SiteUrl(url))
+            .manage(statements_config)
+            .attach(admin::stage())
+            .attach(git::stage())
+            .attach(publish::stage())
+            .attach(teams::stage())
+            .mount(
+                "/contests",
+                routes![
+                    list::contests_list,
+                    calendar::contests_calendar,
+                    calendar::contest_calendar,
+                    new::new_contest_get,
+                    new::new_contest_post,
+                    edit::edit_contest_get,
+                    edit::edit_contest_post,
+                    delete::delete_contest_get,
+                    delete::delete_contest_post,
+                    join::join_contest,
+                    leave::leave_get,
+                    leave::leave_post,
+                    rules::view_rules,
+                    rules::accept_rules,
+                    submissions::submissions,
+                    statements::statements_zip,
+                    view::view_contest,
+                ],
+            )
+            .mount("/api/v1", routes![status::contest_status])
     })
 }
@@ -1,7 +1,7 @@
 use chrono::TimeZone;
 use rocket::{
-    form::{Contextual, Form},
-    get, post,
+    form::{Contextual, Error, Form},
+    get, post, State,
 };
 use rocket_dyn_templates::Template;
 
@@ -14,6 +14,7 @@ use crate::{
     context_with_base_authed,
     db::DbConnection,
     messages::Message,
+    run::CodeInfo,
     template::FormTemplateObject,
     times::ClientTimeZone,
     FormResponse,
@@ -47,9 +48,20 @@ pub async fn new_contest_post(
     timezone: ClientTimeZone,
     _admin: &Admin,
     _token: &VerifyCsrfToken,
-    form: Form<Contextual<'_, ContestForm<'_>>>,
+    mut form: Form<Contextual<'_, ContestForm<'_>>>,
+    info: &State<CodeInfo>,
 ) -> FormResponse {
-    if let Some(ref value) = form.value {
+    let default_languages = form
+        .value
+        .as_ref()
+        .map(|v| info.run_config.normalize_language_list(v.default_languages))
+        .transpose()
+        .unwrap_or_else(|why| {
+            form.context
+                .push_error(Error::validation(why).with_name("default_languages"));
+            None
+        });
+    if let (Some(value), Some(default_languages)) = (form.value.as_ref(), default_languages) {
         let tz = timezone.timezone();
 
         let name = value.name.to_string();
@@ -66,9 +78,39 @@ pub async fn new_contest_post(
             .from_local_datetime(&value.end_time.0)
             .unwrap()
             .naive_utc();
+        let publish_at = value
+            .publish_at
+            .as_ref()
+            .filter(|_| !value.published)
+            .map(|t| tz.from_local_datetime(&t.0).unwrap().naive_utc());
         let freeze_time = value.freeze_time;
         let penalty = value.penalty;
         let max_participants = value.max_participants;
+        let rules = value
+            .rules
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        let registration_fields = value
+            .registration_fields
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        let join_code = value
+            .join_code
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        let banned_patterns = value
+            .banned_patterns
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        let required_header = value
+            .required_header
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
         let contest = Contest::temp(
             name,
             description,
@@ -78,6 +120,27 @@ pub async fn new_contest_post(
             freeze_time,
             penalty,
             max_participants,
+            rules,
+            value.verdict_cache,
+            value.require_approval,
+            registration_fields,
+            value.visibility,
+            join_code,
+            value.printing_enabled,
+            value.comments_during_contest,
+            value.published,
+            publish_at,
+            value.default_cpu_time,
+            value.default_memory_limit,
+            default_languages,
+            value.max_team_size,
+            value.allow_withdrawal,
+            value.spectators_see_unfrozen,
+            value.editor_telemetry,
+            value.session_binding,
+            value.max_line_length,
+            banned_patterns,
+            required_header,
         );
         let contest = contest.insert(&mut db).await?;
         for judge in value.judges.keys() {
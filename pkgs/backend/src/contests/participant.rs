@@ -1,14 +1,43 @@
 use chrono::NaiveDateTime;
 
-use crate::{auth::users::User, db::DbPoolConnection, error::prelude::*};
+use crate::{auth::users::User, db::DbPoolConnection, error::prelude::*, problems::JudgeRun};
+
+use super::Contest;
+
+/// What someone is in a contest for
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticipantRole {
+    Competitor,
+    Judge,
+    /// Follows the contest without competing, e.g. a coach or the media. They can see every
+    /// run's verdict but not its code
+    Spectator,
+}
 
 #[derive(Serialize, Debug, Clone)]
 pub struct Participant {
     pub p_id: i64,
     pub user_id: i64,
-    contest_id: i64,
+    pub contest_id: i64,
     pub is_judge: bool,
-    registered_at: Option<NaiveDateTime>,
+    pub registered_at: Option<NaiveDateTime>,
+    pub rules_accepted_at: Option<NaiveDateTime>,
+    /// Waiting for a judge to approve their registration
+    pub pending: bool,
+    /// JSON object of answers to the contest's registration fields
+    pub registration_data: Option<String>,
+    /// Last time this judge used a judge-only page
+    last_active_at: Option<NaiveDateTime>,
+    /// Judge doesn't want to count towards the online judges shown to participants
+    pub presence_hidden: bool,
+    /// Extra time granted as an accommodation, see [`Contest::end_time_for`]
+    pub extra_minutes: i64,
+    /// Registered after the contest filled up, waiting for a spot. Always pending too
+    pub waitlisted: bool,
+    /// When they got a spot off the waitlist
+    pub promoted_at: Option<NaiveDateTime>,
+    /// See [`ParticipantRole::Spectator`]
+    pub is_spectator: bool,
 }
 
 impl Participant {
@@ -59,6 +88,15 @@ impl Participant {
                     contest_id: row.contest_id,
                     is_judge: row.is_judge,
                     registered_at: row.registered_at,
+                    rules_accepted_at: row.rules_accepted_at,
+                    pending: row.pending,
+                    registration_data: row.registration_data,
+                    last_active_at: row.last_active_at,
+                    presence_hidden: row.presence_hidden,
+                    extra_minutes: row.extra_minutes,
+                    waitlisted: row.waitlisted,
+                    promoted_at: row.promoted_at,
+                    is_spectator: row.is_spectator,
                 };
                 let user = User {
                     id: row.id,
@@ -73,6 +111,7 @@ impl Participant {
                     created_at: row.created_at,
                     github_id: row.github_id,
                     google_id: row.google_id,
+                    locale: row.locale,
                 };
                 (participant, user)
             })
@@ -110,7 +149,7 @@ impl Participant {
     pub async fn list_not_judge(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
         sqlx::query_as!(
             Participant,
-            "SELECT * FROM participant WHERE contest_id = ? AND is_judge = false",
+            "SELECT * FROM participant WHERE contest_id = ? AND is_judge = false AND is_spectator = false AND pending = false",
             contest_id
         )
         .fetch_all(&mut **db)
@@ -118,14 +157,165 @@ impl Participant {
         .context("Failed to list all non-judges")
     }
 
+    /// How many spots are in use, pending participants hold theirs while they wait for approval
+    /// so that the waitlist can't be jumped
+    pub async fn count_taken(db: &mut DbPoolConnection, contest_id: i64) -> Result<i64> {
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM participant WHERE contest_id = ? AND is_judge = false AND is_spectator = false AND waitlisted = false",
+            contest_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count)
+        .with_context(|| format!("Failed to count taken spots for {contest_id}"))
+    }
+
+    pub async fn list_pending(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Participant,
+            "SELECT * FROM participant WHERE contest_id = ? AND pending = true AND waitlisted = false ORDER BY registered_at",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to list pending participants")
+    }
+
+    /// Accepted members of the team a participant is on, empty if they aren't on one
+    pub async fn list_teammates(db: &mut DbPoolConnection, p_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Participant,
+            "SELECT participant.* FROM participant JOIN team_member ON participant.p_id = team_member.p_id WHERE NOT team_member.pending AND team_member.team_id = (SELECT team_id FROM team_member WHERE p_id = ? AND NOT pending)",
+            p_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get teammates of participant {}", p_id))
+    }
+
+    /// A contest's participant by their account's email, ignoring case
+    pub async fn get_by_email(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        email: &str,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Participant,
+            "SELECT participant.* FROM participant JOIN user ON participant.user_id = user.id WHERE participant.contest_id = ? AND user.email = ? COLLATE NOCASE",
+            contest_id,
+            email
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .context("Failed to find participant by email")
+    }
+
+    /// Everyone waiting for a spot, first come first served
+    pub async fn list_waitlisted(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Participant,
+            "SELECT * FROM participant WHERE contest_id = ? AND waitlisted = true ORDER BY registered_at, p_id",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to list waitlisted participants")
+    }
+
+    /// Where this participant is on the waitlist, starting at 1
+    pub async fn waitlist_position(&self, db: &mut DbPoolConnection) -> Result<Option<usize>> {
+        if !self.waitlisted {
+            return Ok(None);
+        }
+        let waitlist = Self::list_waitlisted(db, self.contest_id).await?;
+        Ok(waitlist
+            .iter()
+            .position(|p| p.p_id == self.p_id)
+            .map(|i| i + 1))
+    }
+
+    /// Give any open spots to the people at the front of the waitlist, they go on to be approved
+    /// if the contest needs that. Returns everyone who got a spot
+    pub async fn promote_waitlisted(
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+    ) -> Result<Vec<Self>> {
+        if contest.has_ended() {
+            return Ok(vec![]);
+        }
+        let mut waitlist = Self::list_waitlisted(db, contest.id).await?.into_iter();
+        let taken = Self::count_taken(db, contest.id).await?;
+        let open = contest
+            .max_participants
+            .map_or(usize::MAX, |max| max.saturating_sub(taken).max(0) as usize);
+        let now = chrono::offset::Utc::now().naive_utc();
+        let mut promoted = vec![];
+        for mut participant in waitlist.by_ref().take(open) {
+            sqlx::query!(
+                "UPDATE participant SET waitlisted = false, pending = ?, promoted_at = ? WHERE p_id = ?",
+                contest.require_approval,
+                now,
+                participant.p_id
+            )
+            .execute(&mut **db)
+            .await
+            .with_context(|| {
+                format!("Failed to promote participant {} off the waitlist", participant.p_id)
+            })?;
+            participant.waitlisted = false;
+            participant.pending = contest.require_approval;
+            participant.promoted_at = Some(now);
+            promoted.push(participant);
+        }
+        Ok(promoted)
+    }
+
+    /// Register someone, putting them on the waitlist if the contest is already full. The spots
+    /// are counted and the participant inserted in one transaction, so two people signing up at
+    /// the same time can't both get the last spot
+    pub async fn insert_or_waitlist(
+        mut self,
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+    ) -> Result<Participant> {
+        // IMMEDIATE takes the write lock up front, so the count can't go stale before the insert
+        sqlx::query("BEGIN IMMEDIATE")
+            .execute(&mut **db)
+            .await
+            .context("Failed to start registration")?;
+        let inserted = async {
+            if let Some(max_participants) = contest.max_participants {
+                if Self::count_taken(db, contest.id).await? >= max_participants {
+                    self.waitlisted = true;
+                    self.pending = true;
+                }
+            }
+            self.insert(db).await
+        }
+        .await;
+        let finish = if inserted.is_ok() {
+            "COMMIT"
+        } else {
+            "ROLLBACK"
+        };
+        sqlx::query(finish)
+            .execute(&mut **db)
+            .await
+            .context("Failed to finish registration")?;
+        inserted
+    }
+
     pub async fn insert(&self, db: &mut DbPoolConnection) -> Result<Participant> {
         sqlx::query_as!(
             Participant,
-            "INSERT INTO participant (user_id, contest_id, is_judge, registered_at) VALUES (?, ?, ?, ?) RETURNING *",
+            "INSERT INTO participant (user_id, contest_id, is_judge, registered_at, pending, registration_data, waitlisted) VALUES (?, ?, ?, ?, ?, ?, ?) RETURNING *",
             self.user_id,
             self.contest_id,
             self.is_judge,
-            self.registered_at
+            self.registered_at,
+            self.pending,
+            self.registration_data,
+            self.waitlisted
         )
         .fetch_one(&mut **db)
         .await.context("Failed to insert new participant")
@@ -155,12 +345,197 @@ impl Participant {
     ) -> Result<Participant> {
         sqlx::query_as!(
             Participant,
-            "INSERT INTO participant (user_id, contest_id, is_judge) VALUES (?, ?, true) ON CONFLICT (user_id, contest_id) DO UPDATE SET is_judge = true RETURNING *",
+            "INSERT INTO participant (user_id, contest_id, is_judge) VALUES (?, ?, true) ON CONFLICT (user_id, contest_id) DO UPDATE SET is_judge = true, is_spectator = false, pending = false, waitlisted = false RETURNING *",
             user_id,
             contest_id
         ).fetch_one(&mut **db).await.context("Failed to create or make judge")
     }
 
+    /// Add someone who isn't in the contest yet as a spectator
+    pub async fn create_spectator(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        user_id: i64,
+    ) -> Result<Participant> {
+        sqlx::query_as!(
+            Participant,
+            "INSERT INTO participant (user_id, contest_id, is_judge, is_spectator) VALUES (?, ?, false, true) RETURNING *",
+            user_id,
+            contest_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Failed to create spectator")
+    }
+
+    pub fn role(&self) -> ParticipantRole {
+        if self.is_judge {
+            ParticipantRole::Judge
+        } else if self.is_spectator {
+            ParticipantRole::Spectator
+        } else {
+            ParticipantRole::Competitor
+        }
+    }
+
+    /// Why this participant can't submit runs or answers, if they can't
+    pub fn runs_blocked(&self) -> Option<&'static str> {
+        (self.role() == ParticipantRole::Spectator).then_some("Spectators can't submit runs")
+    }
+
+    /// Whether this participant is on the leaderboard, pending registrations aren't yet
+    pub fn competes(&self) -> bool {
+        self.role() == ParticipantRole::Competitor && !self.pending
+    }
+
+    pub async fn approve(&mut self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!(
+            "UPDATE participant SET pending = false, waitlisted = false WHERE p_id = ?",
+            self.p_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to approve participant {}", self.p_id))?;
+        self.pending = false;
+        self.waitlisted = false;
+        Ok(())
+    }
+
+    /// Judges count as online for this long after their last activity
+    const ONLINE_MINUTES: i64 = 5;
+    /// Only write activity to the database this often
+    const ACTIVITY_INTERVAL_SECONDS: i64 = 60;
+
+    /// Record that this judge is active, skipped if it was already recorded recently
+    pub async fn record_activity(&mut self, db: &mut DbPoolConnection) -> Result {
+        let now = chrono::offset::Utc::now().naive_utc();
+        let recent = self
+            .last_active_at
+            .is_some_and(|t| now - t < chrono::Duration::seconds(Self::ACTIVITY_INTERVAL_SECONDS));
+        if !self.is_judge || recent {
+            return Ok(());
+        }
+        sqlx::query!(
+            "UPDATE participant SET last_active_at = ? WHERE p_id = ?",
+            now,
+            self.p_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to record activity for {}", self.p_id))?;
+        self.last_active_at = Some(now);
+        Ok(())
+    }
+
+    pub async fn set_presence_hidden(&mut self, db: &mut DbPoolConnection, hidden: bool) -> Result {
+        sqlx::query!(
+            "UPDATE participant SET presence_hidden = ? WHERE p_id = ?",
+            hidden,
+            self.p_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to update presence for {}", self.p_id))?;
+        self.presence_hidden = hidden;
+        Ok(())
+    }
+
+    pub async fn set_extra_minutes(&mut self, db: &mut DbPoolConnection, minutes: i64) -> Result {
+        sqlx::query!(
+            "UPDATE participant SET extra_minutes = ? WHERE p_id = ?",
+            minutes,
+            self.p_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to update extra time for {}", self.p_id))?;
+        self.extra_minutes = minutes;
+        Ok(())
+    }
+
+    /// How many judges have been active recently and haven't hidden their presence
+    pub async fn count_judges_online(db: &mut DbPoolConnection, contest_id: i64) -> Result<i64> {
+        let since = chrono::offset::Utc::now().naive_utc()
+            - chrono::Duration::minutes(Self::ONLINE_MINUTES);
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM participant WHERE contest_id = ? AND is_judge = true AND presence_hidden = false AND last_active_at > ?",
+            contest_id,
+            since
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count)
+        .with_context(|| format!("Failed to count online judges for {contest_id}"))
+    }
+
+    /// Answers to the contest's registration fields, in the order the contest asks them
+    pub fn registration_answers(&self, contest: &Contest) -> Vec<(String, String)> {
+        let data = self
+            .registration_data
+            .as_deref()
+            .and_then(|d| serde_json::from_str::<Vec<String>>(d).ok())
+            .unwrap_or_default();
+        contest
+            .registration_fields()
+            .into_iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let answer = data.get(i).cloned().unwrap_or_default();
+                (f.label, answer)
+            })
+            .collect()
+    }
+
+    /// Why this participant can't leave the contest on their own, if they can't. Anyone still
+    /// waiting for a spot or approval can always leave, otherwise the contest has to allow it
+    /// and it can't have started or have any of their runs
+    pub async fn withdrawal_blocked(
+        &self,
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+    ) -> Result<Option<&'static str>> {
+        let reason = if self.is_judge || self.is_spectator {
+            Some("Judges and spectators can't withdraw, ask an admin to take you off the contest")
+        } else if self.pending {
+            None
+        } else if !contest.allow_withdrawal {
+            Some("This contest doesn't allow withdrawing, ask a judge to remove you")
+        } else if contest.has_started() {
+            Some("You can't withdraw once the contest has started")
+        } else if JudgeRun::any_in_contest(db, self.user_id, contest.id).await? {
+            Some("You can't withdraw after submitting")
+        } else {
+            None
+        };
+        Ok(reason)
+    }
+
+    /// Whether this participant is kept from the problems, either because they're
+    /// waiting for approval or haven't accepted the rules yet
+    pub fn is_locked_out(&self, contest: &Contest) -> bool {
+        !self.is_judge && (self.pending || self.needs_to_accept_rules(contest))
+    }
+
+    pub fn needs_to_accept_rules(&self, contest: &Contest) -> bool {
+        self.role() == ParticipantRole::Competitor
+            && contest.rules.is_some()
+            && self.rules_accepted_at.is_none()
+    }
+
+    pub async fn accept_rules(&mut self, db: &mut DbPoolConnection) -> Result {
+        let now = chrono::offset::Utc::now().naive_utc();
+        sqlx::query!(
+            "UPDATE participant SET rules_accepted_at = ? WHERE p_id = ?",
+            now,
+            self.p_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to record rules acceptance for {}", self.p_id))?;
+        self.rules_accepted_at = Some(now);
+        Ok(())
+    }
+
     // pub async fn update(&self, db: &mut DbPoolConnection) -> Result<(), sqlx::Error> {
     //     sqlx::query_as!(
     //         Participant,
@@ -180,6 +555,15 @@ impl Participant {
             contest_id,
             is_judge,
             registered_at: None,
+            rules_accepted_at: None,
+            pending: false,
+            registration_data: None,
+            last_active_at: None,
+            presence_hidden: false,
+            extra_minutes: 0,
+            waitlisted: false,
+            promoted_at: None,
+            is_spectator: false,
         }
     }
 }
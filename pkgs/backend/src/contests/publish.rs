@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use log::{error, info};
+use rocket::fairing::AdHoc;
+use rocket_db_pools::Database as R_Database;
+
+use crate::{
+    db::{Database, DbPool},
+    error::prelude::*,
+};
+
+use super::Contest;
+
+/// How often to check for drafts that are due to be published
+const PUBLISH_INTERVAL_SECONDS: u64 = 60;
+
+/// Publish any drafts whose scheduled time has passed
+async fn publish_scheduled(pool: &DbPool) -> Result {
+    let mut conn = pool
+        .acquire()
+        .await
+        .context("Couldn't get db connection for publishing contests")?;
+    for contest in Contest::publish_due(&mut conn).await? {
+        info!("Published contest {} ({})", contest.id, contest.name);
+    }
+    Ok(())
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_liftoff("Contest Publishing Task", |rocket| {
+        Box::pin(async move {
+            let Some(db) = Database::fetch(rocket) else {
+                error!("Couldn't start contest publishing task");
+                return;
+            };
+            let pool = db.0.clone();
+            let shutdown = rocket.shutdown();
+            tokio::spawn(async move {
+                let mut ticker =
+                    tokio::time::interval(Duration::from_secs(PUBLISH_INTERVAL_SECONDS));
+                tokio::pin!(shutdown);
+                loop {
+                    tokio::select! {
+                        _ = ticker.tick() => {
+                            if let Err(why) = publish_scheduled(&pool).await {
+                                error!("Publishing scheduled contests failed: {why:?}");
+                            }
+                        }
+                        _ = &mut shutdown => break,
+                    }
+                }
+            });
+        })
+    })
+}
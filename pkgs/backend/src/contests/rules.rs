@@ -0,0 +1,60 @@
+use chrono::TimeZone;
+use rocket::{get, http::Status, post};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::User,
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    times::{format_datetime_human_readable, ClientTimeZone},
+    FormResponse,
+};
+
+use super::{Contest, Participant};
+
+#[get("/<contest_id>/rules")]
+pub async fn view_rules(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    tz: ClientTimeZone,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    // Contests recording editor telemetry always have a rules page to disclose it on
+    if contest.rules.is_none() && !contest.editor_telemetry {
+        return Err(Status::NotFound.into());
+    }
+    let participant = Participant::get(&mut db, contest_id, user.id).await?;
+    let needs_to_accept = participant
+        .as_ref()
+        .is_some_and(|p| p.needs_to_accept_rules(&contest));
+    let accepted_at = participant
+        .as_ref()
+        .and_then(|p| p.rules_accepted_at)
+        .map(|t| format_datetime_human_readable(tz.timezone().from_utc_datetime(&t)));
+    let ctx = context_with_base_authed!(user, contest, participant, needs_to_accept, accepted_at);
+    Ok(Template::render("contests/rules", ctx))
+}
+
+#[post("/<contest_id>/rules")]
+pub async fn accept_rules(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let mut participant = Participant::get(&mut db, contest_id, user.id)
+        .await?
+        .ok_or(Status::Forbidden)?;
+    if participant.needs_to_accept_rules(&contest) {
+        participant.accept_rules(&mut db).await?;
+    }
+    Ok(Message::success("Rules accepted").to(&format!("/contests/{}/", contest_id)))
+}
@@ -0,0 +1,94 @@
+//! Checks on a program's source before it's queued, for contests that want to keep out things
+//! like including `/dev/random` or code squashed onto one line, or need a header in every
+//! submission. Checked in `RunManager::request_job` so every way of submitting gets them.
+
+use regex::Regex;
+use rocket::form;
+
+use super::Contest;
+
+/// How far into a program the required header has to start
+const HEADER_LINES: usize = 20;
+
+/// Most banned patterns a contest can have, each one is run over every submission
+const MAX_BANNED_PATTERNS: usize = 50;
+
+fn pattern_lines(patterns: &str) -> impl Iterator<Item = &str> {
+    patterns.lines().map(str::trim).filter(|l| !l.is_empty())
+}
+
+impl Contest {
+    /// Why a program can't be submitted to this contest, if it can't
+    pub fn check_source(&self, program: &str) -> Result<(), String> {
+        if let Some(max) = self.max_line_length {
+            let long_line = program
+                .lines()
+                .enumerate()
+                .find(|(_, l)| l.chars().count() as i64 > max);
+            if let Some((i, line)) = long_line {
+                return Err(format!(
+                    "Line {} is {} characters long, lines can be at most {max} characters in this contest",
+                    i + 1,
+                    line.chars().count()
+                ));
+            }
+        }
+
+        for pattern in pattern_lines(self.banned_patterns.as_deref().unwrap_or_default()) {
+            // Checked when the contest was saved, so one that doesn't compile is skipped
+            let Ok(re) = Regex::new(pattern) else {
+                continue;
+            };
+            if let Some(found) = re.find(program) {
+                let line = program[..found.start()].lines().count().max(1);
+                return Err(format!(
+                    "Line {line} contains `{}`, which isn't allowed in this contest",
+                    found.as_str().trim()
+                ));
+            }
+        }
+
+        if let Some(header) = self.required_header.as_deref() {
+            let top = program
+                .lines()
+                .take(HEADER_LINES + header.lines().count())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let missing = header
+                .lines()
+                .map(str::trim)
+                .find(|l| !l.is_empty() && !top.contains(l));
+            if let Some(missing) = missing {
+                return Err(format!(
+                    "Programs in this contest need a comment near the top with `{missing}`"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+pub(super) fn valid_banned_patterns<'r, 'e>(
+    patterns: &'r Option<&'r str>,
+) -> Result<(), form::Errors<'e>> {
+    let Some(patterns) = patterns else {
+        return Ok(());
+    };
+    if pattern_lines(patterns).count() > MAX_BANNED_PATTERNS {
+        return Err(form::Error::validation(format!(
+            "At most {MAX_BANNED_PATTERNS} patterns are allowed"
+        ))
+        .into());
+    }
+    for (i, pattern) in pattern_lines(patterns).enumerate() {
+        if let Err(why) = Regex::new(pattern) {
+            return Err(form::Error::validation(format!(
+                "Pattern {} isn't a valid regex: {why}",
+                i + 1
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+//! Every problem statement in a contest as one zip, for participants who want copies to read
+//! offline when the connection at the venue isn't reliable
+
+use rocket::{
+    get,
+    http::{ContentType, Header},
+    Responder, State,
+};
+
+use crate::{
+    auth::users::{Admin, User},
+    db::DbConnection,
+    error::prelude::*,
+    md::MarkdownConfig,
+    pdf,
+    problems::{Problem, TestCase},
+    zip::ZipWriter,
+};
+
+use super::Contest;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StatementsConfig {
+    /// Command that reads an HTML page on stdin and writes a PDF to stdout, e.g.
+    /// `["weasyprint", "-", "-"]`. When unset the bundle only has markdown.
+    pub pdf_command: Option<Vec<String>>,
+}
+
+#[derive(Responder)]
+pub struct StatementsDownload {
+    inner: (ContentType, Vec<u8>),
+    disposition: Header<'static>,
+}
+
+fn plural(n: i64) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// A problem's statement as one markdown file, with its limits and sample cases
+fn statement(problem: &Problem, samples: &[TestCase]) -> String {
+    let mut md = format!(
+        "# {}. {}\n\nCPU Time: {} second{}, Memory Limit: {} MiB\n\n{}\n",
+        problem.label,
+        problem.name,
+        problem.cpu_time,
+        plural(problem.cpu_time),
+        problem.memory_limit,
+        problem.description.trim()
+    );
+    for (i, sample) in samples.iter().enumerate() {
+        md.push_str(&format!(
+            "\n## Sample {}\n\nInput:\n\n```\n{}\n```\n",
+            i + 1,
+            sample.stdin.trim_end()
+        ));
+        if !sample.use_regex {
+            md.push_str(&format!(
+                "\nOutput:\n\n```\n{}\n```\n",
+                sample.expected_pattern.trim_end()
+            ));
+        }
+    }
+    md
+}
+
+/// Once the contest has started, a zip with a folder for each problem holding its statement and
+/// sample cases, and all the statements in one PDF if there's a converter configured
+#[get("/<contest_id>/statements.zip")]
+pub async fn statements_zip(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: Option<&User>,
+    admin: Option<&Admin>,
+    config: &State<StatementsConfig>,
+    markdown: &State<MarkdownConfig>,
+) -> ResultResponse<StatementsDownload> {
+    let (contest, _, _) =
+        Contest::get_or_404_assert_started(&mut db, contest_id, user, admin).await?;
+    let problems = Problem::list(&mut db, contest_id).await?;
+
+    let now = chrono::offset::Utc::now().naive_utc();
+    let mut zip = ZipWriter::new(now);
+    let mut html = String::new();
+    let mut index = format!("# {}\n\n", contest.name);
+    for problem in problems.iter() {
+        let dir = format!("{}-{}", problem.label, problem.slug);
+        let samples = TestCase::get_samples_for_problem(&mut db, problem.id).await?;
+        let md = statement(problem, &samples);
+        zip.add(&format!("{dir}/statement.md"), md.as_bytes())?;
+        for (i, sample) in samples.iter().enumerate() {
+            zip.add(
+                &format!("{dir}/samples/{}.in", i + 1),
+                sample.stdin.as_bytes(),
+            )?;
+            if !sample.use_regex {
+                zip.add(
+                    &format!("{dir}/samples/{}.out", i + 1),
+                    sample.expected_pattern.as_bytes(),
+                )?;
+            }
+        }
+        index.push_str(&format!(
+            "- {}. [{}]({dir}/statement.md)\n",
+            problem.label, problem.name
+        ));
+        if config.pdf_command.is_some() {
+            html.push_str(&format!(
+                "<section style=\"break-after: page\">{}</section>\n",
+                markdown.render(&md)?
+            ));
+        }
+    }
+    zip.add("README.md", index.as_bytes())?;
+
+    if let Some(command) = config.pdf_command.as_deref().filter(|c| !c.is_empty()) {
+        let page = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{html}</body>\n</html>\n",
+            ammonia::clean_text(&contest.name)
+        );
+        let pdf = pdf::convert(command, page)
+            .await
+            .with_context(|| format!("Couldn't make statements PDF for contest {contest_id}"))?;
+        zip.add("statements.pdf", &pdf)?;
+    }
+
+    Ok(StatementsDownload {
+        inner: (ContentType::ZIP, zip.finish()?),
+        disposition: Header::new(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"{}-statements.zip\"",
+                slug::slugify(&contest.name)
+            ),
+        ),
+    })
+}
@@ -0,0 +1,80 @@
+use chrono::NaiveDateTime;
+use rocket::{get, http::Status, serde::json::Json};
+
+use crate::{
+    db::DbConnection,
+    error::prelude::*,
+    rate_limit::{self, RateLimited},
+};
+
+use super::Contest;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ContestState {
+    NotStarted,
+    Running,
+    Frozen,
+    Ended,
+}
+
+#[derive(Serialize)]
+pub struct ContestStatus {
+    id: i64,
+    name: String,
+    state: ContestState,
+    /// The clock is stopped, `seconds_remaining` won't go down until it's resumed
+    paused: bool,
+    /// Until the start if it hasn't started, until the end if it's running, `0` once it's over
+    seconds_remaining: i64,
+    start_time: NaiveDateTime,
+    end_time: NaiveDateTime,
+    registration_open: bool,
+}
+
+impl ContestStatus {
+    fn new(contest: Contest) -> Self {
+        let now = chrono::offset::Utc::now().naive_utc();
+        let (state, seconds_remaining) = if !contest.has_started() {
+            (
+                ContestState::NotStarted,
+                (contest.start_time - now).num_seconds(),
+            )
+        } else if contest.has_ended() {
+            (ContestState::Ended, 0)
+        } else {
+            let state = if contest.is_frozen() {
+                ContestState::Frozen
+            } else {
+                ContestState::Running
+            };
+            let clock_now = contest.paused_at.unwrap_or(now);
+            (state, (contest.end_time - clock_now).num_seconds().max(0))
+        };
+        Self {
+            id: contest.id,
+            state,
+            paused: contest.is_paused(),
+            seconds_remaining,
+            start_time: contest.start_time,
+            end_time: contest.end_time,
+            registration_open: contest.can_register(),
+            name: contest.name,
+        }
+    }
+}
+
+/// Where a contest is at for displays and bots, no login needed so only contests
+/// anyone can see are available
+#[get("/contests/<contest_id>/status")]
+pub async fn contest_status(
+    mut db: DbConnection,
+    contest_id: i64,
+    _limit: RateLimited<rate_limit::Api>,
+) -> ResultResponse<Json<ContestStatus>> {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    if !contest.is_visible_to(None, None) {
+        return Err(Status::NotFound.into());
+    }
+    Ok(Json(ContestStatus::new(contest)))
+}
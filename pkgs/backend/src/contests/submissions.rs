@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use chrono::TimeZone;
+use rocket::{get, http::Status};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    problems::{JudgeRun, Problem},
+    times::{format_datetime_human_readable, ClientTimeZone},
+};
+
+use super::{clock_time_between, Contest, Participant, ParticipantRole};
+
+/// How many of the latest runs are shown
+const MAX_SUBMISSIONS: i64 = 200;
+
+/// A run without its code, spectators only get to see how it went
+#[derive(Serialize)]
+struct Submission {
+    id: i64,
+    time: String,
+    name: String,
+    problem_label: String,
+    problem_name: String,
+    language: String,
+    /// `None` while the freeze hides it
+    accepted: Option<bool>,
+    cases_passed: i64,
+    total_cases: i64,
+}
+
+/// Every competitor's latest runs and their verdicts, for judges and spectators
+#[get("/<contest_id>/submissions")]
+pub async fn submissions(
+    mut db: DbConnection,
+    contest_id: i64,
+    tz: ClientTimeZone,
+    user: &User,
+    admin: Option<&Admin>,
+) -> ResultResponse<Template> {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let participant = Participant::get(&mut db, contest_id, user.id).await?;
+    let role = participant.as_ref().map(Participant::role);
+    let can_edit = admin.is_some() || role == Some(ParticipantRole::Judge);
+    if !can_edit && role != Some(ParticipantRole::Spectator) {
+        return Err(Status::NotFound.into());
+    }
+    let sees_through_freeze = can_edit || contest.spectators_see_unfrozen;
+
+    let problems = Problem::list(&mut db, contest_id)
+        .await?
+        .into_iter()
+        .map(|p| (p.id, p))
+        .collect::<HashMap<_, _>>();
+    let competitors = Participant::list(&mut db, contest_id)
+        .await?
+        .into_iter()
+        .map(|(p, u)| (u.id, (p, u)))
+        .collect::<HashMap<_, _>>();
+    let pauses = contest.pauses();
+    let tz = tz.timezone();
+
+    let runs = JudgeRun::list_recent_for_contest(&mut db, contest_id, MAX_SUBMISSIONS).await?;
+    let submissions = runs
+        .into_iter()
+        .filter_map(|run| {
+            let problem = problems.get(&run.problem_id)?;
+            let (competitor, competitor_user) = competitors.get(&run.user_id)?;
+            // Same as the leaderboard, runs in the freeze stay hidden until the participant is done
+            let frozen = contest.freeze_time != 0
+                && !contest.has_ended_for(Some(competitor))
+                && clock_time_between(&pauses, run.ran_at, contest.end_time).num_minutes()
+                    <= contest.freeze_time;
+            Some(Submission {
+                id: run.id,
+                time: format_datetime_human_readable(tz.from_utc_datetime(&run.ran_at)),
+                name: competitor_user.display_name().to_string(),
+                problem_label: problem.label.clone(),
+                problem_name: problem.name.clone(),
+                accepted: (sees_through_freeze || !frozen).then(|| run.success()),
+                cases_passed: run.amount_run,
+                total_cases: run.total_cases,
+                language: run.language,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let ctx = context_with_base_authed!(
+        user,
+        submissions,
+        can_edit,
+        max_submissions: MAX_SUBMISSIONS,
+        contest
+    );
+    Ok(Template::render("contests/submissions", ctx))
+}
@@ -0,0 +1,98 @@
+use rocket::{get, http::Status, post};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::User,
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+};
+
+use super::{
+    get_contest, get_contest_and_competitor, roster_locked, Participant, Team, TeamMember,
+};
+
+/// Landing page for a team's join link, people who haven't signed up for the contest yet
+/// are pointed there first
+#[get("/<contest_id>/teams/join/<code>")]
+pub async fn join_get(
+    mut db: DbConnection,
+    contest_id: i64,
+    code: &str,
+    user: &User,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let contest = get_contest(&mut db, contest_id).await?;
+    let team = Team::by_invite_code(&mut db, contest_id, code)
+        .await?
+        .ok_or(Status::NotFound)?;
+    let participant = Participant::get(&mut db, contest_id, user.id).await?;
+    let members = team.members(&mut db).await?;
+    let current_team = match participant.as_ref() {
+        Some(p) => Team::for_participant(&mut db, p.p_id).await?,
+        None => None,
+    };
+    // Someone already invited has a spot saved for them
+    let invited = participant
+        .as_ref()
+        .is_some_and(|p| members.iter().any(|(m, _)| m.p_id == p.p_id));
+    let has_room = invited || team.has_room(&contest, &members);
+    let locked = roster_locked(&contest);
+    let ctx = context_with_base_authed!(
+        user,
+        contest,
+        team,
+        members,
+        participant,
+        current_team,
+        has_room,
+        locked,
+        code
+    );
+    Ok(Template::render("contests/teams/join", ctx))
+}
+
+#[post("/<contest_id>/teams/join/<code>")]
+pub async fn join_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    code: &str,
+    user: &User,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (contest, participant) = get_contest_and_competitor(&mut db, contest_id, user).await?;
+    let team = Team::by_invite_code(&mut db, contest_id, code)
+        .await?
+        .ok_or(Status::NotFound)?;
+    let url = format!("/contests/{contest_id}/teams/join/{code}");
+    if roster_locked(&contest) {
+        return Ok(Message::error("Teams can't change once the contest has started").to(&url));
+    }
+    if Team::for_participant(&mut db, participant.p_id)
+        .await?
+        .is_some()
+    {
+        return Ok(Message::error("You're already on a team, leave it first").to(&url));
+    }
+    let members = team.members(&mut db).await?;
+    let has_room = team.has_room(&contest, &members);
+    let invite = members
+        .into_iter()
+        .find(|(m, _)| m.p_id == participant.p_id)
+        .map(|(m, _)| m);
+    if let Some(mut invite) = invite {
+        invite.accept(&mut db).await?;
+    } else if !has_room {
+        return Ok(Message::error("This team is full").to(&url));
+    } else {
+        TeamMember::insert(&mut db, team.id, participant.p_id, false, false).await?;
+        TeamMember::clear_invites(&mut db, participant.p_id).await?;
+    }
+    Ok(Message::success("Welcome to {name}!")
+        .arg("name", &team.name)
+        .to(&format!("/contests/{contest_id}/teams/{}", team.id)))
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use rocket::{
+    form::{Contextual, Error, Form},
+    get,
+    http::Status,
+    post, FromForm,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+    template::{FormTemplateObject, TemplatedForm},
+};
+
+use super::{
+    get_contest, get_contest_and_competitor, roster_locked, Contest, Participant, Team, TeamMember,
+};
+
+#[derive(FromForm)]
+pub struct NewTeamForm<'r> {
+    #[field(validate = len(1..=50))]
+    name: &'r str,
+}
+
+struct NewTeamFormTemplate;
+
+impl TemplatedForm for NewTeamFormTemplate {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        HashMap::from_iter([("name".to_string(), "".to_string())])
+    }
+}
+
+async fn render_list(
+    db: &mut DbPoolConnection,
+    contest: &Contest,
+    user: &User,
+    participant: Option<&Participant>,
+    form: FormTemplateObject,
+) -> ResultResponse<Template> {
+    let teams = Team::list(db, contest.id).await?;
+    let (my_team, invites) = match participant {
+        Some(p) => (
+            Team::for_participant(db, p.p_id).await?,
+            Team::list_invites(db, p.p_id).await?,
+        ),
+        None => (None, vec![]),
+    };
+    let can_create =
+        participant.is_some_and(|p| p.competes()) && my_team.is_none() && !roster_locked(contest);
+    let ctx = context_with_base_authed!(user, contest, teams, my_team, invites, can_create, form);
+    Ok(Template::render("contests/teams/list", ctx))
+}
+
+#[get("/<contest_id>/teams")]
+pub async fn teams_list(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let contest = get_contest(&mut db, contest_id).await?;
+    let participant = Participant::get(&mut db, contest_id, user.id).await?;
+    if participant.is_none() && admin.is_none() {
+        return Err(Status::Forbidden.into());
+    }
+    let form = FormTemplateObject::get(NewTeamFormTemplate);
+    render_list(&mut db, &contest, user, participant.as_ref(), form).await
+}
+
+#[post("/<contest_id>/teams", data = "<form>")]
+pub async fn create_team(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    mut form: Form<Contextual<'_, NewTeamForm<'_>>>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (contest, participant) = get_contest_and_competitor(&mut db, contest_id, user).await?;
+    let url = format!("/contests/{contest_id}/teams");
+    if roster_locked(&contest) {
+        return Ok(Message::error("Teams can't change once the contest has started").to(&url));
+    }
+    if Team::for_participant(&mut db, participant.p_id)
+        .await?
+        .is_some()
+    {
+        return Ok(Message::error("You're already on a team, leave it first").to(&url));
+    }
+    if let Some(ref value) = form.value {
+        let name = value.name.trim();
+        let problem = if name.is_empty() {
+            Some("Please enter a name")
+        } else if Team::name_taken(&mut db, contest_id, name).await? {
+            Some("That name is already taken")
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            form.context
+                .push_error(Error::validation(problem).with_name("name"));
+        } else {
+            let team = Team::insert(&mut db, contest_id, name).await?;
+            TeamMember::insert(&mut db, team.id, participant.p_id, true, false).await?;
+            TeamMember::clear_invites(&mut db, participant.p_id).await?;
+            return Ok(
+                Message::success("Team created, send the join link to your teammates")
+                    .to(&format!("/contests/{contest_id}/teams/{}", team.id)),
+            );
+        }
+    }
+    let form = FormTemplateObject::from_rocket_context(NewTeamFormTemplate, &form.context);
+    Err(
+        render_list(&mut db, &contest, user, Some(&participant), form)
+            .await?
+            .into(),
+    )
+}
@@ -0,0 +1,407 @@
+use chrono::NaiveDateTime;
+use rand::{distr::Alphanumeric, Rng};
+use rocket::{fairing::AdHoc, http::Status, routes};
+
+use crate::{auth::users::User, db::DbPoolConnection, error::prelude::*};
+
+use super::{Contest, Participant};
+
+mod join;
+mod list;
+mod roster;
+mod view;
+
+/// A group of participants competing together, formed by the participants themselves
+#[derive(Serialize, Debug, Clone)]
+pub struct Team {
+    pub id: i64,
+    pub contest_id: i64,
+    pub name: String,
+    /// Secret part of the join link, only shown to members
+    #[serde(skip_serializing)]
+    pub invite_code: String,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TeamMember {
+    pub team_id: i64,
+    pub p_id: i64,
+    /// Leaders manage the roster, see [`roster`]
+    pub is_leader: bool,
+    /// Invited by a leader but hasn't accepted yet
+    pub pending: bool,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub joined_at: NaiveDateTime,
+}
+
+impl Team {
+    const INVITE_CODE_LENGTH: usize = 16;
+
+    fn gen_invite_code() -> String {
+        rand::rng()
+            .sample_iter(&Alphanumeric)
+            .take(Self::INVITE_CODE_LENGTH)
+            .map(char::from)
+            .collect()
+    }
+
+    pub async fn insert(db: &mut DbPoolConnection, contest_id: i64, name: &str) -> Result<Self> {
+        let invite_code = Self::gen_invite_code();
+        sqlx::query_as!(
+            Team,
+            "INSERT INTO team (contest_id, name, invite_code) VALUES (?, ?, ?) RETURNING *",
+            contest_id,
+            name,
+            invite_code
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Failed to insert team")
+    }
+
+    pub async fn get(db: &mut DbPoolConnection, contest_id: i64, id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Team,
+            "SELECT * FROM team WHERE id = ? AND contest_id = ?",
+            id,
+            contest_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get team {id}"))
+    }
+
+    pub async fn get_or_404(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        id: i64,
+    ) -> ResultResponse<Self> {
+        Self::get(db, contest_id, id)
+            .await?
+            .ok_or(Status::NotFound.into())
+    }
+
+    pub async fn by_invite_code(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        invite_code: &str,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Team,
+            "SELECT * FROM team WHERE contest_id = ? AND invite_code = ?",
+            contest_id,
+            invite_code
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .context("Failed to get team by invite code")
+    }
+
+    pub async fn by_name(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        name: &str,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Team,
+            "SELECT * FROM team WHERE contest_id = ? AND name = ?",
+            contest_id,
+            name
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get team {name}"))
+    }
+
+    pub async fn name_taken(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        name: &str,
+    ) -> Result<bool> {
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM team WHERE contest_id = ? AND name = ?",
+            contest_id,
+            name
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count > 0)
+        .context("Failed to check team name")
+    }
+
+    /// Every team in a contest along with how many members it has, pending invites not counted
+    pub async fn list(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<(Self, i64)>> {
+        let rows = sqlx::query!(
+            "SELECT team.*, (SELECT COUNT(*) FROM team_member WHERE team_id = team.id AND NOT pending) AS \"members!: i64\" FROM team WHERE contest_id = ? ORDER BY name",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to list teams for contest {contest_id}"))?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let team = Team {
+                    id: row.id,
+                    contest_id: row.contest_id,
+                    name: row.name,
+                    invite_code: row.invite_code,
+                    created_at: row.created_at,
+                };
+                (team, row.members)
+            })
+            .collect())
+    }
+
+    /// The team a participant is on, invites they haven't accepted don't count
+    pub async fn for_participant(db: &mut DbPoolConnection, p_id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Team,
+            "SELECT team.* FROM team JOIN team_member ON team.id = team_member.team_id WHERE team_member.p_id = ? AND NOT team_member.pending",
+            p_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get team for participant {p_id}"))
+    }
+
+    /// Everyone sharing completions with a participant, their whole team in a team contest
+    /// and just themselves otherwise
+    pub async fn completion_sharers(
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+        participant: &Participant,
+    ) -> Result<Vec<Participant>> {
+        if contest.max_team_size.is_none() {
+            return Ok(vec![participant.clone()]);
+        }
+        let members = Participant::list_teammates(db, participant.p_id).await?;
+        if members.is_empty() {
+            Ok(vec![participant.clone()])
+        } else {
+            Ok(members)
+        }
+    }
+
+    /// Teams that invited a participant and are waiting on an answer
+    pub async fn list_invites(db: &mut DbPoolConnection, p_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Team,
+            "SELECT team.* FROM team JOIN team_member ON team.id = team_member.team_id WHERE team_member.p_id = ? AND team_member.pending ORDER BY team_member.joined_at",
+            p_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to list team invites for participant {p_id}"))
+    }
+
+    /// Everyone on the team or invited to it, leaders first then in the order they joined
+    pub async fn members(&self, db: &mut DbPoolConnection) -> Result<Vec<(TeamMember, User)>> {
+        let members = sqlx::query_as!(
+            TeamMember,
+            "SELECT * FROM team_member WHERE team_id = ? ORDER BY is_leader DESC, joined_at",
+            self.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to list members of team {}", self.id))?;
+        let mut res = Vec::with_capacity(members.len());
+        for member in members {
+            if let Some(participant) = Participant::by_id(db, member.p_id).await? {
+                if let Some(user) = User::get(db, participant.user_id).await? {
+                    res.push((member, user));
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Whether the team can take someone else, counting both members and pending invites
+    /// so leaders can't invite more people than could ever join
+    pub fn has_room(&self, contest: &Contest, members: &[(TeamMember, User)]) -> bool {
+        contest
+            .max_team_size
+            .is_some_and(|max| (members.len() as i64) < max)
+    }
+
+    pub async fn regenerate_invite_code(&mut self, db: &mut DbPoolConnection) -> Result {
+        let invite_code = Self::gen_invite_code();
+        sqlx::query!(
+            "UPDATE team SET invite_code = ? WHERE id = ?",
+            invite_code,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to regenerate invite code for team {}", self.id))?;
+        self.invite_code = invite_code;
+        Ok(())
+    }
+
+    /// Take someone off the team or cancel their invite. If the last leader leaves the longest
+    /// standing member takes over, and a team nobody is left on is deleted, returning `true`
+    pub async fn remove_member(
+        self,
+        db: &mut DbPoolConnection,
+        member: TeamMember,
+    ) -> Result<bool> {
+        member.delete(db).await?;
+        let remaining = self.members(db).await?;
+        let mut joined = remaining.into_iter().filter(|(m, _)| !m.pending);
+        if let Some((mut first, _)) = joined.next() {
+            if !first.is_leader && !joined.any(|(m, _)| m.is_leader) {
+                first.set_leader(db, true).await?;
+            }
+            Ok(false)
+        } else {
+            self.delete(db).await?;
+            Ok(true)
+        }
+    }
+
+    pub async fn delete(self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!("DELETE FROM team WHERE id = ?", self.id)
+            .execute(&mut **db)
+            .await
+            .map(|_| ())
+            .with_context(|| format!("Failed to delete team {}", self.id))
+    }
+}
+
+impl TeamMember {
+    pub async fn get(db: &mut DbPoolConnection, team_id: i64, p_id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            TeamMember,
+            "SELECT * FROM team_member WHERE team_id = ? AND p_id = ?",
+            team_id,
+            p_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get member {p_id} of team {team_id}"))
+    }
+
+    pub async fn insert(
+        db: &mut DbPoolConnection,
+        team_id: i64,
+        p_id: i64,
+        is_leader: bool,
+        pending: bool,
+    ) -> Result<Self> {
+        sqlx::query_as!(
+            TeamMember,
+            "INSERT INTO team_member (team_id, p_id, is_leader, pending) VALUES (?, ?, ?, ?) RETURNING *",
+            team_id,
+            p_id,
+            is_leader,
+            pending
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Failed to insert team member")
+    }
+
+    /// Turn an invite into a membership, any other invites the participant had are dropped
+    pub async fn accept(&mut self, db: &mut DbPoolConnection) -> Result {
+        let now = chrono::offset::Utc::now().naive_utc();
+        sqlx::query!(
+            "UPDATE team_member SET pending = false, joined_at = ? WHERE team_id = ? AND p_id = ?",
+            now,
+            self.team_id,
+            self.p_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to accept invite for {}", self.p_id))?;
+        self.pending = false;
+        self.joined_at = now;
+        Self::clear_invites(db, self.p_id).await
+    }
+
+    pub async fn clear_invites(db: &mut DbPoolConnection, p_id: i64) -> Result {
+        sqlx::query!("DELETE FROM team_member WHERE p_id = ? AND pending", p_id)
+            .execute(&mut **db)
+            .await
+            .map(|_| ())
+            .with_context(|| format!("Failed to clear team invites for {p_id}"))
+    }
+
+    pub async fn set_leader(&mut self, db: &mut DbPoolConnection, is_leader: bool) -> Result {
+        sqlx::query!(
+            "UPDATE team_member SET is_leader = ? WHERE team_id = ? AND p_id = ?",
+            is_leader,
+            self.team_id,
+            self.p_id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to update leader status for {}", self.p_id))?;
+        self.is_leader = is_leader;
+        Ok(())
+    }
+
+    pub async fn delete(self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!(
+            "DELETE FROM team_member WHERE team_id = ? AND p_id = ?",
+            self.team_id,
+            self.p_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to remove {} from team {}", self.p_id, self.team_id))
+    }
+}
+
+/// Teams only exist in contests that turned them on, anyone else gets a 404
+async fn get_contest(db: &mut DbPoolConnection, contest_id: i64) -> ResultResponse<Contest> {
+    let contest = Contest::get_or_404(db, contest_id).await?;
+    if contest.max_team_size.is_none() || !contest.published {
+        Err(Status::NotFound.into())
+    } else {
+        Ok(contest)
+    }
+}
+
+/// The user's participation in a contest that has teams, judges and pending registrations
+/// can't be on a team
+async fn get_contest_and_competitor(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    user: &User,
+) -> ResultResponse<(Contest, Participant)> {
+    let contest = get_contest(db, contest_id).await?;
+    let participant = Participant::get(db, contest_id, user.id)
+        .await?
+        .filter(Participant::competes)
+        .ok_or(Status::Forbidden)?;
+    Ok((contest, participant))
+}
+
+/// Rosters are set once the contest starts
+fn roster_locked(contest: &Contest) -> bool {
+    contest.has_started()
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Teams", |rocket| async {
+        rocket.mount(
+            "/contests",
+            routes![
+                list::teams_list,
+                list::create_team,
+                view::view_team,
+                roster::invite_member,
+                roster::accept_invite,
+                roster::decline_invite,
+                roster::remove_member,
+                roster::promote_member,
+                roster::regenerate_link,
+                join::join_get,
+                join::join_post,
+            ],
+        )
+    })
+}
@@ -0,0 +1,199 @@
+use rocket::{form::Form, http::Status, post, FromForm};
+
+use crate::{
+    auth::{csrf::VerifyCsrfToken, users::User},
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+};
+
+use super::{get_contest_and_competitor, roster_locked, Contest, Participant, Team, TeamMember};
+
+#[derive(FromForm)]
+pub struct InviteForm<'r> {
+    email: &'r str,
+}
+
+/// The team along with the user's own membership, which has to be a full one
+async fn get_team_and_member(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    team_id: i64,
+    user: &User,
+) -> ResultResponse<(Contest, Team, TeamMember)> {
+    let (contest, participant) = get_contest_and_competitor(db, contest_id, user).await?;
+    if roster_locked(&contest) {
+        return Err(Status::Forbidden.into());
+    }
+    let team = Team::get_or_404(db, contest_id, team_id).await?;
+    let member = TeamMember::get(db, team_id, participant.p_id)
+        .await?
+        .filter(|m| !m.pending)
+        .ok_or(Status::Forbidden)?;
+    Ok((contest, team, member))
+}
+
+#[post("/<contest_id>/teams/<team_id>/invite", data = "<form>")]
+pub async fn invite_member(
+    mut db: DbConnection,
+    contest_id: i64,
+    team_id: i64,
+    user: &User,
+    form: Form<InviteForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (contest, team, member) = get_team_and_member(&mut db, contest_id, team_id, user).await?;
+    if !member.is_leader {
+        return Err(Status::Forbidden.into());
+    }
+    let url = format!("/contests/{contest_id}/teams/{team_id}");
+    let members = team.members(&mut db).await?;
+    if !team.has_room(&contest, &members) {
+        return Ok(Message::error("Your team is full").to(&url));
+    }
+    let invitee = Participant::get_by_email(&mut db, contest_id, form.email.trim())
+        .await?
+        .filter(Participant::competes);
+    let Some(invitee) = invitee else {
+        return Ok(
+            Message::error("No one with that email has signed up for this contest").to(&url),
+        );
+    };
+    if members.iter().any(|(m, _)| m.p_id == invitee.p_id) {
+        return Ok(Message::error("They're already on your team or invited to it").to(&url));
+    }
+    if Team::for_participant(&mut db, invitee.p_id)
+        .await?
+        .is_some()
+    {
+        return Ok(Message::error("They're already on another team").to(&url));
+    }
+    TeamMember::insert(&mut db, team.id, invitee.p_id, false, true).await?;
+    Ok(Message::success("Invite sent, they can accept it from the teams page").to(&url))
+}
+
+#[post("/<contest_id>/teams/<team_id>/accept")]
+pub async fn accept_invite(
+    mut db: DbConnection,
+    contest_id: i64,
+    team_id: i64,
+    user: &User,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (contest, participant) = get_contest_and_competitor(&mut db, contest_id, user).await?;
+    let url = format!("/contests/{contest_id}/teams/{team_id}");
+    if roster_locked(&contest) {
+        return Ok(Message::error("Teams can't change once the contest has started").to(&url));
+    }
+    let mut invite = TeamMember::get(&mut db, team_id, participant.p_id)
+        .await?
+        .filter(|m| m.pending)
+        .ok_or(Status::NotFound)?;
+    if Team::for_participant(&mut db, participant.p_id)
+        .await?
+        .is_some()
+    {
+        return Ok(Message::error("You're already on a team, leave it first").to(&url));
+    }
+    invite.accept(&mut db).await?;
+    Ok(Message::success("Welcome to the team!").to(&url))
+}
+
+#[post("/<contest_id>/teams/<team_id>/decline")]
+pub async fn decline_invite(
+    mut db: DbConnection,
+    contest_id: i64,
+    team_id: i64,
+    user: &User,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (_, participant) = get_contest_and_competitor(&mut db, contest_id, user).await?;
+    let invite = TeamMember::get(&mut db, team_id, participant.p_id)
+        .await?
+        .filter(|m| m.pending)
+        .ok_or(Status::NotFound)?;
+    invite.delete(&mut db).await?;
+    Ok(Message::info("Invite declined").to(&format!("/contests/{contest_id}/teams")))
+}
+
+/// Leaders can remove anyone or cancel an invite, everyone else can only remove themselves,
+/// see [`Team::remove_member`]
+#[post("/<contest_id>/teams/<team_id>/members/<p_id>/remove")]
+pub async fn remove_member(
+    mut db: DbConnection,
+    contest_id: i64,
+    team_id: i64,
+    p_id: i64,
+    user: &User,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (_, team, member) = get_team_and_member(&mut db, contest_id, team_id, user).await?;
+    let leaving = member.p_id == p_id;
+    if !member.is_leader && !leaving {
+        return Err(Status::Forbidden.into());
+    }
+    let target = TeamMember::get(&mut db, team_id, p_id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    let was_invite = target.pending;
+    if team.remove_member(&mut db, target).await? {
+        return Ok(
+            Message::info("You left the team, it was disbanded since no one was left")
+                .to(&format!("/contests/{contest_id}/teams")),
+        );
+    }
+
+    if leaving {
+        Ok(Message::info("You left the team").to(&format!("/contests/{contest_id}/teams")))
+    } else if was_invite {
+        Ok(
+            Message::info("Invite cancelled")
+                .to(&format!("/contests/{contest_id}/teams/{team_id}")),
+        )
+    } else {
+        Ok(Message::success("Member removed")
+            .to(&format!("/contests/{contest_id}/teams/{team_id}")))
+    }
+}
+
+#[post("/<contest_id>/teams/<team_id>/members/<p_id>/promote")]
+pub async fn promote_member(
+    mut db: DbConnection,
+    contest_id: i64,
+    team_id: i64,
+    p_id: i64,
+    user: &User,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (_, _, member) = get_team_and_member(&mut db, contest_id, team_id, user).await?;
+    if !member.is_leader {
+        return Err(Status::Forbidden.into());
+    }
+    let mut target = TeamMember::get(&mut db, team_id, p_id)
+        .await?
+        .filter(|m| !m.pending)
+        .ok_or(Status::NotFound)?;
+    target.set_leader(&mut db, true).await?;
+    Ok(Message::success("Member promoted to leader")
+        .to(&format!("/contests/{contest_id}/teams/{team_id}")))
+}
+
+/// Swap out the join link, for when the old one ended up somewhere it shouldn't have
+#[post("/<contest_id>/teams/<team_id>/link")]
+pub async fn regenerate_link(
+    mut db: DbConnection,
+    contest_id: i64,
+    team_id: i64,
+    user: &User,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (_, mut team, member) = get_team_and_member(&mut db, contest_id, team_id, user).await?;
+    if !member.is_leader {
+        return Err(Status::Forbidden.into());
+    }
+    team.regenerate_invite_code(&mut db).await?;
+    Ok(
+        Message::success("New join link created, the old one won't work anymore")
+            .to(&format!("/contests/{contest_id}/teams/{team_id}")),
+    )
+}
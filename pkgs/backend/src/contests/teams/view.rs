@@ -0,0 +1,57 @@
+use rocket::{get, http::Status};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::CsrfToken,
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+};
+
+use super::{get_contest, roster_locked, Participant, Team};
+
+#[get("/<contest_id>/teams/<team_id>")]
+pub async fn view_team(
+    mut db: DbConnection,
+    contest_id: i64,
+    team_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let contest = get_contest(&mut db, contest_id).await?;
+    let participant = Participant::get(&mut db, contest_id, user.id).await?;
+    if participant.is_none() && admin.is_none() {
+        return Err(Status::Forbidden.into());
+    }
+    let team = Team::get_or_404(&mut db, contest_id, team_id).await?;
+    let members = team.members(&mut db).await?;
+    let me = participant
+        .as_ref()
+        .and_then(|p| members.iter().find(|(m, _)| m.p_id == p.p_id))
+        .map(|(m, _)| m.clone());
+    let is_member = me.as_ref().is_some_and(|m| !m.pending);
+    let is_invited = me.as_ref().is_some_and(|m| m.pending);
+    let locked = roster_locked(&contest);
+    let can_manage = !locked && me.as_ref().is_some_and(|m| m.is_leader);
+    let has_room = team.has_room(&contest, &members);
+    let invite_code = is_member.then(|| team.invite_code.clone());
+    let my_p_id = participant.as_ref().map(|p| p.p_id);
+    let ctx = context_with_base_authed!(
+        user,
+        contest,
+        team,
+        members,
+        is_member,
+        is_invited,
+        locked,
+        can_manage,
+        has_room,
+        invite_code,
+        my_p_id
+    );
+    Ok(Template::render("contests/teams/view", ctx))
+}
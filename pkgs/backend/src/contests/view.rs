@@ -1,13 +1,18 @@
 use chrono::TimeZone;
-use rocket::get;
+use rocket::{get, http::Status};
 use rocket_dyn_templates::Template;
 
 use crate::{
-    auth::users::{Admin, User},
+    auth::{
+        challenge::Challenge,
+        users::{Admin, User},
+    },
+    certificates::{self, CertificateTemplate},
     context_with_base,
     db::DbConnection,
     error::prelude::*,
     problems::Problem,
+    series::Series,
     times::{datetime_to_html_time, format_datetime_human_readable, ClientTimeZone},
 };
 
@@ -20,6 +25,7 @@ pub async fn view_contest(
     tz: ClientTimeZone,
     user: Option<&User>,
     admin: Option<&Admin>,
+    pow: Challenge,
 ) -> ResultResponse<Template> {
     let contest = Contest::get_or_404(&mut db, contest_id).await?;
     let participant = if let Some(user) = user {
@@ -28,12 +34,29 @@ pub async fn view_contest(
         None
     };
 
-    let problems = Problem::list(&mut db, contest_id).await?;
+    // Non-members of private contests only get enough to join
+    let is_visible = contest.is_visible_to(participant.as_ref(), admin);
+    if !is_visible && !contest.published {
+        return Err(Status::NotFound.into());
+    }
+    let problems = if is_visible {
+        Problem::list(&mut db, contest_id).await?
+    } else {
+        vec![]
+    };
 
     let (participants, judges) = Participant::list(&mut db, contest_id)
         .await?
         .into_iter()
+        .filter(|p| is_visible && !p.0.pending && !p.0.is_spectator)
         .partition::<Vec<_>, _>(|p| !p.0.is_judge);
+    let registration_fields = contest.registration_fields();
+    let needs_join_code = contest.join_code.is_some();
+    let judges_online = if contest.is_running() {
+        Some(Participant::count_judges_online(&mut db, contest_id).await?)
+    } else {
+        None
+    };
 
     let start_local = tz.timezone().from_utc_datetime(&contest.start_time);
     let start_local_html = datetime_to_html_time(&start_local);
@@ -41,25 +64,73 @@ pub async fn view_contest(
 
     let start_formatted = format_datetime_human_readable(start_local);
     let end_formatted = format_datetime_human_readable(end_local);
+    let publish_formatted = contest
+        .publish_at
+        .map(|t| format_datetime_human_readable(tz.timezone().from_utc_datetime(&t)));
     let tz_name = tz.timezone().name();
 
     let can_edit = admin.is_some() || participant.as_ref().is_some_and(|p| p.is_judge);
+    let is_spectator = participant.as_ref().is_some_and(|p| p.is_spectator);
+    let join_code = if can_edit {
+        contest.join_code.clone()
+    } else {
+        None
+    };
+    let needs_rules = !can_edit
+        && participant
+            .as_ref()
+            .is_some_and(|p| p.needs_to_accept_rules(&contest));
+    let series = Series::list_for_contest(&mut db, contest.id).await?;
+    let certificate_available = match participant.as_ref() {
+        Some(p) if p.competes() && contest.has_ended_for(Some(p)) => {
+            CertificateTemplate::get(&mut db, contest.id).await?.enabled
+                && certificates::results_final(&mut db, &contest).await?
+        }
+        _ => false,
+    };
+
+    let waitlist_position = match participant.as_ref() {
+        Some(p) => p.waitlist_position(&mut db).await?,
+        None => None,
+    };
+    let can_leave = match participant.as_ref() {
+        Some(p) => p.withdrawal_blocked(&mut db, &contest).await?.is_none(),
+        None => false,
+    };
+    // Only worth pointing out until the contest starts
+    let promoted = participant
+        .as_ref()
+        .is_some_and(|p| p.promoted_at.is_some() && !contest.has_started());
 
     let ctx = context_with_base!(
         user,
         problems,
+        waitlist_position,
+        can_leave,
+        promoted,
         participants,
         tz_name,
         can_edit,
+        is_spectator,
+        needs_rules,
+        registration_fields,
+        needs_join_code,
+        judges_online,
+        join_code,
+        series,
+        certificate_available,
+        is_visible,
         start_formatted,
         start_local_html,
         end_formatted,
+        publish_formatted,
         is_admin: admin.is_some(),
         judges,
         started: contest.has_started(),
-        ended: contest.has_ended(),
+        ended: contest.has_ended_for(participant.as_ref()),
         contest,
-        participant
+        participant,
+        pow
     );
     Ok(Template::render("contests/view", ctx))
 }
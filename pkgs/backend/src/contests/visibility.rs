@@ -0,0 +1,56 @@
+use rocket::FromFormField;
+use serde::Serialize;
+use sqlx::{encode::IsNull, Decode, Encode, Type};
+
+/// Who can find a contest, see [`super::Contest::is_visible_to`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, FromFormField, Default)]
+pub enum ContestVisibility {
+    /// Listed for everyone
+    #[default]
+    Public,
+    /// Not listed, but anyone with the link can view and join it
+    Unlisted,
+    /// Not listed, only members can view it and joining needs the join code
+    Private,
+}
+
+impl From<String> for ContestVisibility {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "Unlisted" => Self::Unlisted,
+            "Private" => Self::Private,
+            _ => Self::Public,
+        }
+    }
+}
+
+impl From<ContestVisibility> for String {
+    fn from(v: ContestVisibility) -> Self {
+        format!("{:?}", v)
+    }
+}
+
+impl Type<sqlx::Sqlite> for ContestVisibility {
+    fn type_info() -> <sqlx::Sqlite as sqlx::Database>::TypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl Encode<'_, sqlx::Sqlite> for ContestVisibility {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Sqlite as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> IsNull {
+        let val = format!("{:?}", self);
+        <std::string::String as Encode<'_, sqlx::Sqlite>>::encode_by_ref(&val, buf)
+    }
+}
+
+impl Decode<'_, sqlx::Sqlite> for ContestVisibility {
+    fn decode(
+        value: <sqlx::Sqlite as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        Ok(s.into())
+    }
+}
@@ -1,6 +1,11 @@
 use std::path::{Path, PathBuf};
 
-use rocket::{fairing::AdHoc, http::Header};
+use rocket::{
+    fairing::AdHoc,
+    http::Header,
+    request::{FromRequest, Outcome},
+    Request,
+};
 use serde::Deserialize;
 
 const SRI_HASHES_FILE: &str = "sriHashes.json";
@@ -16,6 +21,22 @@ struct SRIHashes {
     // ext_style_hashes: Vec<String>,
 }
 
+/// Guard for pages meant to be put in an iframe on other sites, their responses get
+/// `frame-ancestors` from the `embed_origins` config instead of `'none'`
+pub struct Embeddable;
+
+struct AllowFraming(bool);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Embeddable {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        req.local_cache(|| AllowFraming(true));
+        Outcome::Success(Embeddable)
+    }
+}
+
 fn join_hashes(hashes: &[String]) -> String {
     hashes
         .iter()
@@ -24,7 +45,7 @@ fn join_hashes(hashes: &[String]) -> String {
         .join(" ")
 }
 
-fn stage_inner(path: &Path) -> AdHoc {
+fn stage_inner(path: &Path, embed_origins: &[String]) -> AdHoc {
     let raw_hashes = std::fs::read_to_string(path).unwrap();
     let hashes: SRIHashes = serde_json::from_str(&raw_hashes).unwrap();
     let directives: Vec<String> = vec![
@@ -47,8 +68,16 @@ fn stage_inner(path: &Path) -> AdHoc {
         // format!("style-src-elem 'self' {} {}", join_hashes(&hashes.ext_style_hashes), join_hashes(&hashes.inline_style_hashes)),
     ];
     let value = directives.join("; ");
-    AdHoc::on_response("Content-Security-Policy", move |_req, resp| {
-        let value = value.clone();
+    let embed_value = value.replace(
+        "frame-ancestors 'none'",
+        &format!("frame-ancestors {}", embed_origins.join(" ")),
+    );
+    AdHoc::on_response("Content-Security-Policy", move |req, resp| {
+        let value = if req.local_cache(|| AllowFraming(false)).0 {
+            embed_value.clone()
+        } else {
+            value.clone()
+        };
         Box::pin(async move {
             let header = Header::new("Content-Security-Policy", value);
             resp.adjoin_header(header)
@@ -67,7 +96,10 @@ pub fn stage() -> AdHoc {
                 .unwrap_or_else(|| "templates".to_string()),
         );
         let path = template_dir.join(SRI_HASHES_FILE);
+        let embed_origins = figment
+            .extract_inner::<Vec<String>>("embed_origins")
+            .unwrap_or_else(|_| vec!["*".to_string()]);
 
-        rocket.attach(stage_inner(&path))
+        rocket.attach(stage_inner(&path, &embed_origins))
     })
 }
@@ -0,0 +1,343 @@
+use std::process::Stdio;
+
+use log::error;
+use openssl::base64;
+use rocket::fairing::AdHoc;
+use tera::{Context as TeraContext, Tera};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    branding::{BrandingConfig, ParsedColorConfig},
+    error::prelude::*,
+};
+
+const TEMPLATES: [(&str, &str); 10] = [
+    ("base.html", include_str!("templates/base.html")),
+    ("button.html", include_str!("templates/button.html")),
+    (
+        "password_reset.html",
+        include_str!("templates/password_reset.html"),
+    ),
+    (
+        "password_reset.txt",
+        include_str!("templates/password_reset.txt"),
+    ),
+    (
+        "account_created.html",
+        include_str!("templates/account_created.html"),
+    ),
+    (
+        "account_created.txt",
+        include_str!("templates/account_created.txt"),
+    ),
+    (
+        "inbox_message.html",
+        include_str!("templates/inbox_message.html"),
+    ),
+    (
+        "inbox_message.txt",
+        include_str!("templates/inbox_message.txt"),
+    ),
+    (
+        "verify_email.html",
+        include_str!("templates/verify_email.html"),
+    ),
+    (
+        "verify_email.txt",
+        include_str!("templates/verify_email.txt"),
+    ),
+];
+
+/// Every kind of email we know how to send, each has an `{key}.html` and `{key}.txt` template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailKind {
+    PasswordReset,
+    AccountCreated,
+    InboxMessage,
+    VerifyEmail,
+}
+
+impl EmailKind {
+    pub const ALL: [EmailKind; 4] = [
+        EmailKind::PasswordReset,
+        EmailKind::AccountCreated,
+        EmailKind::InboxMessage,
+        EmailKind::VerifyEmail,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            EmailKind::PasswordReset => "password_reset",
+            EmailKind::AccountCreated => "account_created",
+            EmailKind::InboxMessage => "inbox_message",
+            EmailKind::VerifyEmail => "verify_email",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.key() == key)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            EmailKind::PasswordReset => "Single-use password reset link for a local account",
+            EmailKind::AccountCreated => {
+                "Temporary password or claim link for a local account created by an admin"
+            }
+            EmailKind::InboxMessage => "Copy of a message a judge sent to a participant's inbox",
+            EmailKind::VerifyEmail => "Link to verify the email of a self-registered local account",
+        }
+    }
+
+    fn subject(&self, brand_name: &str) -> String {
+        match self {
+            EmailKind::PasswordReset => format!("Reset your {brand_name} password"),
+            EmailKind::AccountCreated => format!("Your {brand_name} account"),
+            EmailKind::InboxMessage => format!("New message on {brand_name}"),
+            EmailKind::VerifyEmail => format!("Verify your {brand_name} email"),
+        }
+    }
+
+    /// Placeholder data used when previewing this email
+    fn sample_context(&self, url: &str) -> TeraContext {
+        let mut ctx = TeraContext::new();
+        ctx.insert("name", "Jane Doe");
+        match self {
+            EmailKind::PasswordReset => {
+                ctx.insert(
+                    "reset_url",
+                    &format!("{url}/auth/password/reset/EXAMPLE_TOKEN"),
+                );
+                ctx.insert("expires_hours", &24);
+            }
+            EmailKind::AccountCreated => {
+                ctx.insert("email", "jane.doe@example.com");
+                ctx.insert("temporary_password", "correct-horse-battery");
+                ctx.insert("login_url", &format!("{url}/auth/login"));
+            }
+            EmailKind::InboxMessage => {
+                ctx.insert("contest_name", "Example Contest");
+                ctx.insert("message_subject", "About your submission to problem A");
+                ctx.insert(
+                    "message_body",
+                    "Your program reads the input from a file, it should read from stdin instead.",
+                );
+                ctx.insert("inbox_url", &format!("{url}/inbox"));
+            }
+            EmailKind::VerifyEmail => {
+                ctx.insert(
+                    "verify_url",
+                    &format!("{url}/auth/password/verify/EXAMPLE_TOKEN"),
+                );
+                ctx.insert("expires_hours", &24);
+            }
+        }
+        ctx
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EmailConfig {
+    /// Kinds of email that will actually be sent, everything else can only be previewed
+    #[serde(default)]
+    pub enabled: Vec<EmailKind>,
+    /// Command that reads a whole message, headers included, on stdin and sends it, e.g.
+    /// `["sendmail", "-t", "-i"]`. Without one nothing is sent even if it's enabled.
+    pub sendmail_command: Option<Vec<String>>,
+    /// Address emails are sent from, left to the command when unset
+    pub from: Option<String>,
+}
+
+/// Lines in a base64 body can't be longer than this
+const BASE64_LINE: usize = 76;
+
+fn base64_lines(s: &str) -> String {
+    let encoded = base64::encode_block(s.as_bytes());
+    encoded
+        .as_bytes()
+        .chunks(BASE64_LINE)
+        .map(|c| String::from_utf8_lossy(c).to_string())
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+/// Headers can't have line breaks in them, or someone could add their own
+fn header_value(s: &str) -> String {
+    s.replace(['\r', '\n'], " ")
+}
+
+impl EmailConfig {
+    pub fn is_enabled(&self, kind: EmailKind) -> bool {
+        self.enabled.contains(&kind)
+    }
+
+    /// Whether emails of a kind will actually go out
+    pub fn can_send(&self, kind: EmailKind) -> bool {
+        self.is_enabled(kind)
+            && self
+                .sendmail_command
+                .as_ref()
+                .is_some_and(|c| !c.is_empty())
+    }
+
+    /// Send an email with both its HTML and text versions, does nothing if it can't be sent
+    pub async fn send(&self, kind: EmailKind, to: &str, email: &RenderedEmail) -> Result {
+        if !self.can_send(kind) {
+            return Ok(());
+        }
+        let Some((program, args)) = self.sendmail_command.as_ref().and_then(|c| c.split_first())
+        else {
+            return Ok(());
+        };
+        let boundary = format!("wcpc-{}", rand::random::<u64>());
+        let mut message = String::new();
+        if let Some(from) = &self.from {
+            message.push_str(&format!("From: {}\r\n", header_value(from)));
+        }
+        message.push_str(&format!(
+            "To: {}\r\nSubject: =?UTF-8?B?{}?=\r\nMIME-Version: 1.0\r\nContent-Type: multipart/alternative; boundary=\"{boundary}\"\r\n\r\n",
+            header_value(to),
+            base64::encode_block(email.subject.as_bytes())
+        ));
+        for (content_type, body) in [("text/plain", &email.text), ("text/html", &email.html)] {
+            message.push_str(&format!(
+                "--{boundary}\r\nContent-Type: {content_type}; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+                base64_lines(body)
+            ));
+        }
+        message.push_str(&format!("--{boundary}--\r\n"));
+
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Couldn't start sendmail command {program}"))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .context("Sendmail command has no stdin")?;
+        stdin
+            .write_all(message.as_bytes())
+            .await
+            .context("Couldn't write to sendmail command")?;
+        drop(stdin);
+        let output = child
+            .wait_with_output()
+            .await
+            .context("Sendmail command failed")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Sendmail command exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Branding info passed to every email template as `branding`.
+/// Colors are converted to hex since email clients don't understand most CSS color syntax.
+#[derive(Debug, Clone, Serialize)]
+struct EmailBranding {
+    name: String,
+    url: String,
+    logo_url: String,
+    primary: String,
+    secondary: String,
+    accent: String,
+    background: String,
+    text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+pub struct EmailTemplates {
+    tera: Tera,
+    branding: EmailBranding,
+}
+
+impl EmailTemplates {
+    fn new(branding: &BrandingConfig, colors: &ParsedColorConfig, url: &str) -> Result<Self> {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(TEMPLATES)
+            .context("Couldn't parse email templates")?;
+        let branding = EmailBranding {
+            name: branding.name.clone(),
+            url: url.to_string(),
+            logo_url: format!("{url}/android-chrome-96x96.png"),
+            primary: colors.primary.hex(),
+            secondary: colors.secondary.hex(),
+            accent: colors.accent.hex(),
+            background: colors.background.hex(),
+            text: colors.text.hex(),
+        };
+        Ok(Self { tera, branding })
+    }
+
+    pub fn render(&self, kind: EmailKind, mut ctx: TeraContext) -> Result<RenderedEmail> {
+        let subject = kind.subject(&self.branding.name);
+        ctx.insert("branding", &self.branding);
+        ctx.insert("subject", &subject);
+        let html = self
+            .tera
+            .render(&format!("{}.html", kind.key()), &ctx)
+            .with_context(|| format!("Couldn't render HTML for {} email", kind.key()))?;
+        let text = self
+            .tera
+            .render(&format!("{}.txt", kind.key()), &ctx)
+            .with_context(|| format!("Couldn't render text for {} email", kind.key()))?;
+        Ok(RenderedEmail {
+            subject,
+            html,
+            text,
+        })
+    }
+
+    /// Where the site is, for links in emails
+    pub fn url(&self) -> &str {
+        &self.branding.url
+    }
+
+    pub fn render_preview(&self, kind: EmailKind) -> Result<RenderedEmail> {
+        self.render(kind, kind.sample_context(&self.branding.url))
+    }
+}
+
+/// Needs to be attached after the templating stage, which manages the branding config
+pub fn stage() -> AdHoc {
+    AdHoc::try_on_ignite("Email Templates", |rocket| async {
+        let figment = rocket.figment();
+        let url = figment.extract_inner::<String>("url").unwrap_or_default();
+        let config = figment
+            .extract_inner::<EmailConfig>("email")
+            .unwrap_or_default();
+
+        let templates = match (
+            rocket.state::<BrandingConfig>(),
+            rocket.state::<ParsedColorConfig>(),
+        ) {
+            (Some(branding), Some(colors)) => {
+                EmailTemplates::new(branding, colors, url.trim_end_matches('/'))
+            }
+            _ => Err(anyhow!("Branding wasn't loaded before email templates")),
+        };
+
+        match templates {
+            Ok(templates) => Ok(rocket.manage(templates).manage(config)),
+            Err(e) => {
+                error!("Failed to set up email templates: {:?}", e);
+                Err(rocket)
+            }
+        }
+    })
+}
@@ -0,0 +1,267 @@
+//! Translations from gettext catalogs, messages are looked up by their English text so anything
+//! without a translation is just shown in English
+
+use std::{borrow::Cow, collections::HashMap, path::PathBuf, sync::Arc};
+
+use log::{info, warn};
+use rocket::{
+    fairing::AdHoc,
+    http::Header,
+    request::{self, FromRequest},
+    Request, State,
+};
+
+use crate::{auth::users::User, error::prelude::*};
+
+mod po;
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct I18nConfig {
+    /// Where `<locale>.po` files are read from
+    locales_dir: PathBuf,
+    /// The locale the site is written in, it doesn't need a catalog
+    default_locale: String,
+    default_locale_name: String,
+}
+
+impl Default for I18nConfig {
+    fn default() -> Self {
+        Self {
+            locales_dir: PathBuf::from("locales/"),
+            default_locale: "en".to_string(),
+            default_locale_name: "English".to_string(),
+        }
+    }
+}
+
+pub struct Catalog {
+    /// What the language calls itself, from the `X-Language-Name` header of the catalog
+    pub name: String,
+    messages: HashMap<String, String>,
+}
+
+#[derive(Clone)]
+pub struct Locales {
+    default_locale: String,
+    default_locale_name: String,
+    catalogs: Arc<HashMap<String, Catalog>>,
+}
+
+/// Languages written right to left, by their primary language subtag
+const RTL_LANGUAGES: &[&str] = &["ar", "dv", "fa", "he", "ku", "ps", "sd", "ug", "ur", "yi"];
+
+pub fn is_rtl(locale: &str) -> bool {
+    let language = locale.split(['-', '_']).next().unwrap_or(locale);
+    RTL_LANGUAGES
+        .iter()
+        .any(|l| l.eq_ignore_ascii_case(language))
+}
+
+/// A number the browser formats for the page's language (e.g. `1.234,5` in German),
+/// until then, or without JavaScript, it's formatted like in English without grouping
+pub fn localized_number_tag(value: f64, decimals: usize) -> String {
+    format!("<data value=\"{value}\" data-localize-number=\"{decimals}\">{value:.decimals$}</data>")
+}
+
+/// Replace `{name}` placeholders in a message with their values
+pub fn fill(template: &str, args: &[(impl AsRef<str>, impl AsRef<str>)]) -> String {
+    let mut out = template.to_string();
+    for (name, value) in args {
+        out = out.replace(&format!("{{{}}}", name.as_ref()), value.as_ref());
+    }
+    out
+}
+
+impl Locales {
+    pub fn load(config: &I18nConfig) -> Result<Self> {
+        let mut catalogs = HashMap::new();
+        if config.locales_dir.is_dir() {
+            let entries = std::fs::read_dir(&config.locales_dir)
+                .context("Couldn't read locales directory")?;
+            for entry in entries {
+                let path = entry.context("Couldn't read locales directory")?.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("po") {
+                    continue;
+                }
+                let Some(code) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                let source = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Couldn't read {}", path.display()))?;
+                let mut messages = po::parse(&source)
+                    .with_context(|| format!("Couldn't parse {}", path.display()))?;
+                let header = po::parse_header(&messages.remove("").unwrap_or_default());
+                let name = header
+                    .get("X-Language-Name")
+                    .cloned()
+                    .unwrap_or_else(|| code.to_string());
+                info!("Loaded {} translations for {code}", messages.len());
+                catalogs.insert(code.replace('_', "-"), Catalog { name, messages });
+            }
+        } else {
+            warn!(
+                "Locales directory {} doesn't exist, only {} will be available",
+                config.locales_dir.display(),
+                config.default_locale_name
+            );
+        }
+        Ok(Self {
+            default_locale: config.default_locale.clone(),
+            default_locale_name: config.default_locale_name.clone(),
+            catalogs: Arc::new(catalogs),
+        })
+    }
+
+    pub fn default_locale(&self) -> &str {
+        &self.default_locale
+    }
+
+    /// The locale to actually use, the default if there's no usable one
+    pub fn resolve<'a>(&'a self, locale: Option<&'a str>) -> &'a str {
+        locale
+            .filter(|l| self.is_available(l))
+            .unwrap_or(&self.default_locale)
+    }
+
+    pub fn is_available(&self, locale: &str) -> bool {
+        locale == self.default_locale || self.catalogs.contains_key(locale)
+    }
+
+    /// Locales and their names for a dropdown, the default first
+    pub fn available(&self) -> Vec<(String, String)> {
+        let mut others = self
+            .catalogs
+            .iter()
+            .map(|(code, c)| (code.clone(), c.name.clone()))
+            .collect::<Vec<_>>();
+        others.sort();
+        std::iter::once((
+            self.default_locale.clone(),
+            self.default_locale_name.clone(),
+        ))
+        .chain(others)
+        .collect()
+    }
+
+    pub fn translate<'a>(&'a self, locale: &str, msgid: &'a str) -> &'a str {
+        self.catalogs
+            .get(locale)
+            .and_then(|c| c.messages.get(msgid))
+            .map_or(msgid, |s| s.as_str())
+    }
+
+    /// Pick the locale the browser wants most out of an `Accept-Language` header, a locale we
+    /// only have the language of (e.g. `es` for `es-MX`) is used if there's nothing closer
+    pub fn negotiate(&self, accept_language: &str) -> Option<String> {
+        let mut wanted = accept_language
+            .split(',')
+            .filter_map(|part| {
+                let mut pieces = part.split(';');
+                let tag = pieces.next()?.trim();
+                let quality = pieces
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect::<Vec<_>>();
+        wanted.sort_by(|a, b| b.1.total_cmp(&a.1));
+        wanted.iter().find_map(|(tag, _)| {
+            let language = tag.split('-').next().unwrap_or(tag);
+            [*tag, language].into_iter().find_map(|t| {
+                self.available()
+                    .into_iter()
+                    .map(|(code, _)| code)
+                    .find(|code| code.eq_ignore_ascii_case(t))
+            })
+        })
+    }
+}
+
+/// The locale to show a request in, the user's setting if they picked one and otherwise
+/// whatever their browser asks for
+#[derive(Debug)]
+pub struct ClientLocale(String);
+
+impl ClientLocale {
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientLocale {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let locale = req
+            .local_cache_async(async {
+                let Some(locales) = req.guard::<&State<Locales>>().await.succeeded() else {
+                    return "en".to_string();
+                };
+                let user = req.guard::<&User>().await.succeeded();
+                user.and_then(|u| u.locale.clone())
+                    .filter(|l| locales.is_available(l))
+                    .or_else(|| {
+                        req.headers()
+                            .get_one("Accept-Language")
+                            .and_then(|h| locales.negotiate(h))
+                    })
+                    .unwrap_or_else(|| locales.default_locale().to_string())
+            })
+            .await;
+        rocket::outcome::Outcome::Success(ClientLocale(locale.clone()))
+    }
+}
+
+/// Translate the message passed along with a redirect (see [`crate::messages::Message`]),
+/// filling in its `msg_arg_*` parameters
+fn translate_location(locales: &Locales, locale: &str, location: &str) -> Option<String> {
+    let (path, query) = location.split_once('?')?;
+    let mut msg = None;
+    let mut args = vec![];
+    let mut rest = vec![];
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let decoded = urlencoding::decode(value).map(Cow::into_owned).ok();
+        match (key, decoded) {
+            ("msg", Some(value)) => msg = Some(value),
+            (key, Some(value)) if key.starts_with("msg_arg_") => {
+                args.push((key["msg_arg_".len()..].to_string(), value))
+            }
+            _ => rest.push(pair),
+        }
+    }
+    let msg = fill(locales.translate(locale, &msg?), &args);
+    let mut out = format!("{path}?msg={}", urlencoding::encode(&msg));
+    for pair in rest {
+        out.push('&');
+        out.push_str(pair);
+    }
+    Some(out)
+}
+
+pub fn stage(locales: Locales) -> AdHoc {
+    AdHoc::on_ignite("Internationalization", |rocket| async {
+        rocket
+            .manage(locales)
+            .attach(AdHoc::on_response("Translate Messages", |req, resp| {
+                Box::pin(async move {
+                    if !resp.status().class().is_redirection() {
+                        return;
+                    }
+                    let Some(location) = resp.headers().get_one("Location") else {
+                        return;
+                    };
+                    let Some(locales) = req.guard::<&State<Locales>>().await.succeeded() else {
+                        return;
+                    };
+                    let locale = req.guard::<ClientLocale>().await.succeeded();
+                    let locale = locale.as_ref().map_or("", |l| l.code());
+                    if let Some(translated) = translate_location(locales, locale, location) {
+                        resp.set_header(Header::new("Location", translated));
+                    }
+                })
+            }))
+    })
+}
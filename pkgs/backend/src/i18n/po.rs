@@ -0,0 +1,127 @@
+//! Just enough of the gettext PO format to read translated messages, plural forms only keep
+//! their first translation and fuzzy entries are left out like `msgfmt` does
+
+use std::collections::HashMap;
+
+use anyhow::bail;
+
+use crate::error::prelude::*;
+
+#[derive(Default)]
+struct Entry {
+    fuzzy: bool,
+    msgid: Option<String>,
+    msgstr: Option<String>,
+}
+
+/// Which string continuation lines (lines that are only a quoted string) add on to
+enum Field {
+    None,
+    Context,
+    Id,
+    Str,
+}
+
+/// Unquote a PO string, e.g. `"Hello \"you\"\n"`
+fn unquote(raw: &str) -> Result<String> {
+    let inner = raw
+        .trim()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| anyhow!("Expected a quoted string, got {raw}"))?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(c @ ('"' | '\\')) => out.push(c),
+            other => bail!("Unknown escape \\{}", other.unwrap_or(' ')),
+        }
+    }
+    Ok(out)
+}
+
+/// The header is the translation of the empty message, lines of `Name: value`
+pub fn parse_header(header: &str) -> HashMap<String, String> {
+    header
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect()
+}
+
+/// Messages and their translations, the header is kept under the empty message
+pub fn parse(source: &str) -> Result<HashMap<String, String>> {
+    let mut messages = HashMap::new();
+    let mut entry = Entry::default();
+    let mut field = Field::None;
+
+    let mut finish = |entry: Entry| {
+        if let (Some(id), Some(translated), false) = (entry.msgid, entry.msgstr, entry.fuzzy) {
+            if !translated.is_empty() {
+                messages.insert(id, translated);
+            }
+        }
+    };
+
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        let res: Result = (|| {
+            if line.is_empty() {
+                return Ok(());
+            }
+            if let Some(flags) = line.strip_prefix("#,") {
+                if entry.msgid.is_some() {
+                    finish(std::mem::take(&mut entry));
+                }
+                entry.fuzzy = flags.split(',').any(|f| f.trim() == "fuzzy");
+                field = Field::None;
+            } else if line.starts_with('#') {
+                // Comments and references
+            } else if let Some(rest) = line.strip_prefix("msgctxt ") {
+                if entry.msgid.is_some() {
+                    finish(std::mem::take(&mut entry));
+                }
+                unquote(rest)?;
+                field = Field::Context;
+            } else if let Some(rest) = line.strip_prefix("msgid_plural ") {
+                unquote(rest)?;
+                field = Field::None;
+            } else if let Some(rest) = line.strip_prefix("msgid ") {
+                if entry.msgid.is_some() {
+                    finish(std::mem::take(&mut entry));
+                }
+                entry.msgid = Some(unquote(rest)?);
+                field = Field::Id;
+            } else if let Some(rest) = line.strip_prefix("msgstr[0] ") {
+                entry.msgstr = Some(unquote(rest)?);
+                field = Field::Str;
+            } else if line.starts_with("msgstr[") {
+                field = Field::None;
+            } else if let Some(rest) = line.strip_prefix("msgstr ") {
+                entry.msgstr = Some(unquote(rest)?);
+                field = Field::Str;
+            } else if line.starts_with('"') {
+                let more = unquote(line)?;
+                match field {
+                    Field::Id => entry.msgid.get_or_insert_with(String::new).push_str(&more),
+                    Field::Str => entry.msgstr.get_or_insert_with(String::new).push_str(&more),
+                    Field::Context | Field::None => {}
+                }
+            } else {
+                bail!("Unexpected line");
+            }
+            Ok(())
+        })();
+        res.with_context(|| format!("Invalid PO file on line {}", i + 1))?;
+    }
+    finish(entry);
+
+    Ok(messages)
+}
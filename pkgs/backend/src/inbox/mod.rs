@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use rocket::{fairing::AdHoc, routes};
+
+use crate::{db::DbPoolConnection, error::prelude::*};
+
+mod send;
+mod view;
+
+/// Who a message goes to, written as `user:<id>` or `team:<id>` in the send form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recipient {
+    User(i64),
+    Team(i64),
+}
+
+impl Recipient {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (kind, id) = s.split_once(':')?;
+        let id = id.parse().ok()?;
+        match kind {
+            "user" => Some(Self::User(id)),
+            "team" => Some(Self::Team(id)),
+            _ => None,
+        }
+    }
+}
+
+/// A message from a judge that stays in the participant's inbox, unlike a flash
+/// [`crate::messages::Message`]
+#[derive(Serialize, Debug, Clone)]
+pub struct InboxMessage {
+    pub id: i64,
+    pub contest_id: i64,
+    pub sender_id: Option<i64>,
+    pub recipient_id: Option<i64>,
+    /// Set instead of `recipient_id` for messages to a whole team, every member gets it
+    pub team_id: Option<i64>,
+    pub subject: String,
+    pub body: String,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+}
+
+impl InboxMessage {
+    pub async fn insert(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        sender_id: i64,
+        recipient: Recipient,
+        subject: &str,
+        body: &str,
+    ) -> Result<Self> {
+        let (recipient_id, team_id) = match recipient {
+            Recipient::User(id) => (Some(id), None),
+            Recipient::Team(id) => (None, Some(id)),
+        };
+        sqlx::query_as!(
+            InboxMessage,
+            "INSERT INTO inbox_message (contest_id, sender_id, recipient_id, team_id, subject, body) VALUES (?, ?, ?, ?, ?, ?) RETURNING *",
+            contest_id,
+            sender_id,
+            recipient_id,
+            team_id,
+            subject,
+            body
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Failed to insert inbox message")
+    }
+
+    pub async fn get(db: &mut DbPoolConnection, id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(InboxMessage, "SELECT * FROM inbox_message WHERE id = ?", id)
+            .fetch_optional(&mut **db)
+            .await
+            .with_context(|| format!("Failed to get inbox message {id}"))
+    }
+
+    /// Every message sent in a contest, newest first
+    pub async fn list_for_contest(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            InboxMessage,
+            "SELECT * FROM inbox_message WHERE contest_id = ? ORDER BY created_at DESC, id DESC",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to list inbox messages for contest {contest_id}"))
+    }
+
+    /// Every message a user got, directly or through a team they're on, newest first
+    pub async fn list_for_user(db: &mut DbPoolConnection, user_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            InboxMessage,
+            "SELECT * FROM inbox_message WHERE recipient_id = ? OR team_id IN (SELECT team_member.team_id FROM team_member JOIN participant ON participant.p_id = team_member.p_id WHERE participant.user_id = ? AND NOT team_member.pending) ORDER BY created_at DESC, id DESC",
+            user_id,
+            user_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to list inbox messages for user {user_id}"))
+    }
+
+    /// How many messages a user hasn't opened yet, for the badge in the navbar
+    pub async fn unread_count(db: &mut DbPoolConnection, user_id: i64) -> Result<i64> {
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count!: i64\" FROM inbox_message WHERE (recipient_id = ? OR team_id IN (SELECT team_member.team_id FROM team_member JOIN participant ON participant.p_id = team_member.p_id WHERE participant.user_id = ? AND NOT team_member.pending)) AND id NOT IN (SELECT message_id FROM inbox_read WHERE user_id = ?)",
+            user_id,
+            user_id,
+            user_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count)
+        .with_context(|| format!("Failed to count unread messages for user {user_id}"))
+    }
+
+    /// Messages a user has opened
+    pub async fn read_ids(db: &mut DbPoolConnection, user_id: i64) -> Result<HashSet<i64>> {
+        sqlx::query!(
+            "SELECT message_id FROM inbox_read WHERE user_id = ?",
+            user_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .map(|rows| rows.into_iter().map(|r| r.message_id).collect())
+        .with_context(|| format!("Failed to get read messages for user {user_id}"))
+    }
+
+    pub async fn mark_read(&self, db: &mut DbPoolConnection, user_id: i64) -> Result {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO inbox_read (message_id, user_id) VALUES (?, ?)",
+            self.id,
+            user_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to mark message {} as read", self.id))
+    }
+
+    /// How many of the recipients have opened the message, for the judges' list
+    pub async fn read_count(&self, db: &mut DbPoolConnection) -> Result<i64> {
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count!: i64\" FROM inbox_read WHERE message_id = ?",
+            self.id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count)
+        .with_context(|| format!("Failed to count reads of message {}", self.id))
+    }
+
+    /// Whether a user got this message, team messages only go to current members
+    pub async fn is_for(&self, db: &mut DbPoolConnection, user_id: i64) -> Result<bool> {
+        if self.recipient_id == Some(user_id) {
+            return Ok(true);
+        }
+        let Some(team_id) = self.team_id else {
+            return Ok(false);
+        };
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count!: i64\" FROM team_member JOIN participant ON participant.p_id = team_member.p_id WHERE team_member.team_id = ? AND participant.user_id = ? AND NOT team_member.pending",
+            team_id,
+            user_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count > 0)
+        .with_context(|| format!("Failed to check who message {} is for", self.id))
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Inbox", |rocket| async {
+        rocket
+            .mount(
+                "/inbox",
+                routes![view::inbox, view::inbox_message, view::unread],
+            )
+            .mount(
+                "/contests",
+                routes![send::sent_messages, send::send_message],
+            )
+    })
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use chrono::TimeZone;
+use log::warn;
+use rocket::{
+    form::{Contextual, Error, Form},
+    get, post, FromForm, State,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::{Contest, Participant, Team},
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    email::{EmailConfig, EmailKind, EmailTemplates},
+    error::prelude::*,
+    messages::Message,
+    template::{FormTemplateObject, TemplatedForm},
+    times::{format_datetime_human_readable, ClientTimeZone},
+};
+
+use super::{InboxMessage, Recipient};
+
+#[derive(FromForm)]
+pub struct SendForm<'r> {
+    #[field(validate = len(1..))]
+    to: &'r str,
+    #[field(validate = len(1..=100))]
+    subject: &'r str,
+    #[field(validate = len(1..))]
+    body: &'r str,
+    email: bool,
+}
+
+struct SendFormTemplate;
+
+impl TemplatedForm for SendFormTemplate {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        HashMap::from_iter([
+            ("to".to_string(), "".to_string()),
+            ("subject".to_string(), "".to_string()),
+            ("body".to_string(), "".to_string()),
+            ("email".to_string(), "false".to_string()),
+        ])
+    }
+}
+
+#[derive(Serialize)]
+struct RecipientOption {
+    value: String,
+    name: String,
+}
+
+#[derive(Serialize)]
+struct SentRow {
+    message: InboxMessage,
+    to: String,
+    sent: String,
+    read_count: i64,
+}
+
+/// Everyone a judge can message, teams first then competitors by name
+async fn recipient_options(
+    db: &mut DbPoolConnection,
+    contest: &Contest,
+) -> Result<Vec<RecipientOption>> {
+    let mut options = Vec::new();
+    if contest.max_team_size.is_some() {
+        for (team, _) in Team::list(db, contest.id).await? {
+            options.push(RecipientOption {
+                value: format!("team:{}", team.id),
+                name: format!("Team: {}", team.name),
+            });
+        }
+    }
+    let mut participants = Participant::list(db, contest.id)
+        .await?
+        .into_iter()
+        .filter(|(p, _)| p.competes())
+        .collect::<Vec<_>>();
+    participants.sort_by_cached_key(|(_, u)| u.display_name().to_lowercase());
+    for (_, u) in participants {
+        options.push(RecipientOption {
+            value: format!("user:{}", u.id),
+            name: u.display_name().to_string(),
+        });
+    }
+    Ok(options)
+}
+
+/// Every user that gets a message, `None` if the recipient isn't in the contest
+async fn resolve(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    recipient: Recipient,
+) -> Result<Option<Vec<User>>> {
+    match recipient {
+        Recipient::User(id) => {
+            let competes = Participant::get(db, contest_id, id)
+                .await?
+                .is_some_and(|p| p.competes());
+            if !competes {
+                return Ok(None);
+            }
+            Ok(User::get(db, id).await?.map(|u| vec![u]))
+        }
+        Recipient::Team(id) => {
+            let Some(team) = Team::get(db, contest_id, id).await? else {
+                return Ok(None);
+            };
+            let members = team
+                .members(db)
+                .await?
+                .into_iter()
+                .filter(|(m, _)| !m.pending)
+                .map(|(_, u)| u)
+                .collect();
+            Ok(Some(members))
+        }
+    }
+}
+
+async fn sent_rows(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    tz: &ClientTimeZone,
+) -> Result<Vec<SentRow>> {
+    let tz = tz.timezone();
+    let mut rows = Vec::new();
+    for message in InboxMessage::list_for_contest(db, contest_id).await? {
+        let recipient = match (message.recipient_id, message.team_id) {
+            (_, Some(id)) => Recipient::Team(id),
+            (Some(id), _) => Recipient::User(id),
+            _ => continue,
+        };
+        let to = match recipient {
+            Recipient::User(id) => User::get(db, id)
+                .await?
+                .map(|u| u.display_name().to_string()),
+            Recipient::Team(id) => Team::get(db, contest_id, id)
+                .await?
+                .map(|t| format!("Team: {}", t.name)),
+        }
+        .unwrap_or_else(|| "Deleted".to_string());
+        let read_count = message.read_count(db).await?;
+        let sent = format_datetime_human_readable(tz.from_utc_datetime(&message.created_at));
+        rows.push(SentRow {
+            message,
+            to,
+            sent,
+            read_count,
+        });
+    }
+    Ok(rows)
+}
+
+#[get("/<contest_id>/admin/inbox")]
+pub async fn sent_messages(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    tz: ClientTimeZone,
+    email_config: &State<EmailConfig>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let recipients = recipient_options(&mut db, &contest).await?;
+    let rows = sent_rows(&mut db, contest_id, &tz).await?;
+    let can_email = email_config.can_send(EmailKind::InboxMessage);
+    let form = FormTemplateObject::get(SendFormTemplate);
+    let ctx = context_with_base_authed!(user, contest, recipients, rows, can_email, form);
+    Ok(Template::render("contests/admin/inbox", ctx))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[post("/<contest_id>/admin/inbox", data = "<form>")]
+pub async fn send_message(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    tz: ClientTimeZone,
+    email_config: &State<EmailConfig>,
+    templates: &State<EmailTemplates>,
+    mut form: Form<Contextual<'_, SendForm<'_>>>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    if let Some(ref value) = form.value {
+        let resolved = match Recipient::parse(value.to) {
+            Some(recipient) => resolve(&mut db, contest_id, recipient)
+                .await?
+                .map(|r| (recipient, r)),
+            None => None,
+        };
+        if let Some((recipient, users)) = resolved {
+            let message = InboxMessage::insert(
+                &mut db,
+                contest_id,
+                user.id,
+                recipient,
+                value.subject,
+                value.body,
+            )
+            .await?;
+            if value.email && email_config.can_send(EmailKind::InboxMessage) {
+                // On-site accounts have a username where the email would be
+                for to in users.into_iter().filter(|u| u.email.contains('@')) {
+                    let mut ctx = tera::Context::new();
+                    ctx.insert("name", to.display_name());
+                    ctx.insert("contest_name", &contest.name);
+                    ctx.insert("message_subject", &message.subject);
+                    ctx.insert("message_body", &message.body);
+                    ctx.insert(
+                        "inbox_url",
+                        &format!("{}/inbox/{}", templates.url(), message.id),
+                    );
+                    let sent = match templates.render(EmailKind::InboxMessage, ctx) {
+                        Ok(email) => {
+                            email_config
+                                .send(EmailKind::InboxMessage, &to.email, &email)
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    // The message is in their inbox either way, don't fail the whole send
+                    if let Err(e) = sent {
+                        warn!(
+                            "Couldn't email message {} to user {}: {e:?}",
+                            message.id, to.id
+                        );
+                    }
+                }
+            }
+            return Ok(
+                Message::success("Message Sent").to(&format!("/contests/{contest_id}/admin/inbox"))
+            );
+        } else {
+            let err = Error::validation("Not in this contest").with_name("to");
+            form.context.push_error(err);
+        }
+    }
+    let recipients = recipient_options(&mut db, &contest).await?;
+    let rows = sent_rows(&mut db, contest_id, &tz).await?;
+    let can_email = email_config.can_send(EmailKind::InboxMessage);
+    let form = FormTemplateObject::from_rocket_context(SendFormTemplate, &form.context);
+    let ctx = context_with_base_authed!(user, contest, recipients, rows, can_email, form);
+    Err(Template::render("contests/admin/inbox", ctx).into())
+}
@@ -0,0 +1,76 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use rocket::{get, http::Status, serde::json::Json};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::User,
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    rate_limit::{self, RateLimited},
+};
+
+use super::InboxMessage;
+
+#[derive(Serialize)]
+struct InboxEntry {
+    message: InboxMessage,
+    contest_name: String,
+    read: bool,
+}
+
+#[derive(Serialize)]
+pub struct UnreadCount {
+    count: i64,
+}
+
+#[get("/")]
+pub async fn inbox(mut db: DbConnection, user: &User) -> ResultResponse<Template> {
+    let messages = InboxMessage::list_for_user(&mut db, user.id).await?;
+    let read = InboxMessage::read_ids(&mut db, user.id).await?;
+    let mut contest_names = HashMap::<i64, String>::new();
+    let mut entries = Vec::with_capacity(messages.len());
+    for message in messages {
+        if let Entry::Vacant(slot) = contest_names.entry(message.contest_id) {
+            let name = Contest::get(&mut db, message.contest_id)
+                .await?
+                .map(|c| c.name)
+                .unwrap_or_default();
+            slot.insert(name);
+        }
+        entries.push(InboxEntry {
+            contest_name: contest_names[&message.contest_id].clone(),
+            read: read.contains(&message.id),
+            message,
+        });
+    }
+    let ctx = context_with_base_authed!(user, entries);
+    Ok(Template::render("inbox", ctx))
+}
+
+#[get("/<id>")]
+pub async fn inbox_message(mut db: DbConnection, id: i64, user: &User) -> ResultResponse<Template> {
+    let message = InboxMessage::get(&mut db, id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    if !message.is_for(&mut db, user.id).await? {
+        return Err(Status::NotFound.into());
+    }
+    message.mark_read(&mut db, user.id).await?;
+    let contest = Contest::get_or_404(&mut db, message.contest_id).await?;
+    let ctx = context_with_base_authed!(user, message, contest);
+    Ok(Template::render("inbox/message", ctx))
+}
+
+/// Polled by the navbar to show a badge when there's something new
+#[get("/unread")]
+pub async fn unread(
+    mut db: DbConnection,
+    user: &User,
+    _limit: RateLimited<rate_limit::Api>,
+) -> ResultResponse<Json<UnreadCount>> {
+    let count = InboxMessage::unread_count(&mut db, user.id).await?;
+    Ok(Json(UnreadCount { count }))
+}
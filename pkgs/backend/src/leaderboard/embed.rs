@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use rocket::{get, http::Header, serde::json::Json, Responder, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::User,
+    context_with_base,
+    csp::Embeddable,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    rate_limit::{self, RateLimited},
+};
+
+use super::{ranks, scoring::ScoreEntry, LeaderboardData, LeaderboardManagerHandle};
+
+#[derive(Serialize)]
+struct EmbedRow {
+    rank: usize,
+    p_id: i64,
+    name: String,
+    solved: usize,
+    /// Total score in minutes, penalties included
+    score: i64,
+    scores: HashMap<String, ScoreEntry>,
+    /// Replayed from an earlier contest rather than someone competing
+    ghost: bool,
+}
+
+#[derive(Serialize)]
+struct SnapshotContest {
+    id: i64,
+    name: String,
+    start_time: NaiveDateTime,
+    end_time: NaiveDateTime,
+    is_running: bool,
+    is_frozen: bool,
+}
+
+#[derive(Serialize)]
+struct SnapshotProblem {
+    id: i64,
+    label: String,
+    name: String,
+}
+
+#[derive(Serialize)]
+pub struct Snapshot {
+    contest: SnapshotContest,
+    problems: Vec<SnapshotProblem>,
+    /// Problem IDs to whoever solved them first
+    first_map: HashMap<String, Option<i64>>,
+    standings: Vec<EmbedRow>,
+}
+
+#[derive(Responder)]
+pub struct SnapshotResponse {
+    inner: Json<Snapshot>,
+    cors: Header<'static>,
+}
+
+/// Standings as anyone without an account sees them, never with emails or anything
+/// else only organizers should see
+async fn get_rows(
+    db: &mut DbPoolConnection,
+    leaderboard_manager: &LeaderboardManagerHandle,
+    contest_id: i64,
+) -> ResultResponse<(LeaderboardData, Vec<EmbedRow>)> {
+    let mut data = LeaderboardData::get(db, leaderboard_manager, contest_id, None, None).await?;
+    let entries = std::mem::take(&mut data.entries);
+    let rows = ranks(&entries)
+        .into_iter()
+        .zip(entries)
+        .map(|(rank, entry)| EmbedRow {
+            rank,
+            p_id: entry.p_id,
+            name: entry.user.display_name().to_string(),
+            solved: entry.scores.len(),
+            score: entry.total_score() / 60,
+            scores: entry.scores,
+            ghost: entry.ghost,
+        })
+        .collect();
+    Ok((data, rows))
+}
+
+/// The leaderboard without any of the site around it, for putting in an iframe on event pages
+#[get("/contests/<contest_id>/leaderboard/embed")]
+pub async fn leaderboard_embed(
+    mut db: DbConnection,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+    contest_id: i64,
+    _embed: Embeddable,
+) -> ResultResponse<Template> {
+    let (
+        LeaderboardData {
+            contest,
+            problems,
+            first_map,
+            is_frozen,
+            ..
+        },
+        rows,
+    ) = get_rows(&mut db, leaderboard_manager, contest_id).await?;
+
+    let user: Option<&User> = None;
+    Ok(Template::render(
+        "contests/leaderboard_embed",
+        context_with_base!(
+            user,
+            rows,
+            problems,
+            first_map,
+            is_frozen,
+            is_running: contest.is_running(),
+            contest
+        ),
+    ))
+}
+
+/// The same standings as the embed as JSON, for sites that want to render them themselves
+#[get("/contests/<contest_id>/leaderboard/json")]
+pub async fn leaderboard_snapshot(
+    mut db: DbConnection,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+    contest_id: i64,
+    _limit: RateLimited<rate_limit::Api>,
+) -> ResultResponse<SnapshotResponse> {
+    let (data, standings) = get_rows(&mut db, leaderboard_manager, contest_id).await?;
+    let snapshot = Snapshot {
+        contest: SnapshotContest {
+            id: data.contest.id,
+            name: data.contest.name.clone(),
+            start_time: data.contest.start_time,
+            end_time: data.contest.end_time,
+            is_running: data.contest.is_running(),
+            is_frozen: data.is_frozen,
+        },
+        problems: data
+            .problems
+            .into_iter()
+            .map(|p| SnapshotProblem {
+                id: p.id,
+                label: p.label,
+                name: p.name,
+            })
+            .collect(),
+        first_map: data.first_map,
+        standings,
+    };
+    Ok(SnapshotResponse {
+        inner: Json(snapshot),
+        cors: Header::new("Access-Control-Allow-Origin", "*"),
+    })
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+
+use rocket::{
+    get,
+    http::{ContentType, Header},
+    FromFormField, Responder, State,
+};
+
+use crate::{
+    auth::users::{Admin, User},
+    contests::Contest,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    problems::ProblemAnalytics,
+};
+
+use super::{ranks, LeaderboardEntry, LeaderboardManagerHandle, ProblemIdTemp};
+
+#[derive(FromFormField, Default, Clone, Copy)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// Still CSV, but with a byte order mark and CRLF line endings so Excel
+    /// opens it with the right encoding instead of mangling non-ASCII names
+    Excel,
+}
+
+#[derive(Responder)]
+pub struct Export {
+    inner: (ContentType, Vec<u8>),
+    disposition: Header<'static>,
+}
+
+/// Rows of fields built up in memory then encoded all at once
+pub struct Table(pub Vec<Vec<String>>);
+
+impl Table {
+    fn field(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    fn encode(&self, newline: &str) -> String {
+        let mut body = String::new();
+        for row in self.0.iter() {
+            let line = row
+                .iter()
+                .map(|f| Self::field(f))
+                .collect::<Vec<_>>()
+                .join(",");
+            body.push_str(&line);
+            body.push_str(newline);
+        }
+        body
+    }
+
+    /// Plain CSV, for putting in other files like the git export
+    pub fn to_csv(&self) -> String {
+        self.encode("\n")
+    }
+
+    pub fn into_export(self, format: ExportFormat, file_stem: &str) -> Export {
+        let body = match format {
+            ExportFormat::Csv => self.encode("\n"),
+            ExportFormat::Excel => format!("\u{FEFF}{}", self.encode("\r\n")),
+        };
+        Export {
+            inner: (ContentType::CSV, body.into_bytes()),
+            disposition: Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"{file_stem}.csv\""),
+            ),
+        }
+    }
+}
+
+/// Split a CSV line into fields, handling quoted fields with `""` escapes
+pub fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+/// Standings and results are for organizers, participants see them on the leaderboard
+async fn get_contest_data(
+    db: &mut DbPoolConnection,
+    leaderboard_manager: &LeaderboardManagerHandle,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+) -> ResultResponse<(Contest, Vec<ProblemIdTemp>, Vec<LeaderboardEntry>)> {
+    let (contest, _) = Contest::get_or_404_assert_can_edit(db, contest_id, user, admin).await?;
+    let (problems, entries) = standings_data(db, leaderboard_manager, &contest).await?;
+    Ok((contest, problems, entries))
+}
+
+async fn standings_data(
+    db: &mut DbPoolConnection,
+    leaderboard_manager: &LeaderboardManagerHandle,
+    contest: &Contest,
+) -> Result<(Vec<ProblemIdTemp>, Vec<LeaderboardEntry>)> {
+    let problems = sqlx::query_as!(
+        ProblemIdTemp,
+        "SELECT id, slug, name, label from problem WHERE contest_id = ? ORDER BY ordering, id",
+        contest.id
+    )
+    .fetch_all(&mut **db)
+    .await
+    .context("Failed to fetch problems")?;
+    let mut manager = leaderboard_manager.lock().await;
+    let leaderboard = manager.get_leaderboard(db, contest).await?.clone();
+    drop(manager);
+    let entries = leaderboard.lock().await.full(db).await?;
+    Ok((problems, entries))
+}
+
+fn minutes(secs: i64) -> String {
+    format!("{:.2}", secs as f64 / 60.0)
+}
+
+/// Standings as they are on the leaderboard, frozen if it is, with each participant's penalty
+/// breakdown and when they solved each problem. Emails are only for organizers.
+pub async fn standings_table(
+    db: &mut DbPoolConnection,
+    leaderboard_manager: &LeaderboardManagerHandle,
+    contest: &Contest,
+    with_emails: bool,
+) -> Result<Table> {
+    let (problems, entries) = standings_data(db, leaderboard_manager, contest).await?;
+
+    let mut header = [
+        "Rank",
+        "Name",
+        "Email",
+        "Solved",
+        "Score (min)",
+        "Solve Time (min)",
+        "Penalty (min)",
+        "Wrong Attempts",
+    ]
+    .map(String::from)
+    .to_vec();
+    if !with_emails {
+        header.remove(2);
+    }
+    for problem in problems.iter() {
+        header.push(format!("{} Solve Time (min)", problem.label));
+        header.push(format!("{} Wrong Attempts", problem.label));
+    }
+    let mut rows = vec![header];
+
+    for (entry, rank) in entries.iter().zip(ranks(&entries)) {
+        let solved = entry.scores.len();
+        let score = entry.total_score();
+        let solve_secs: i64 = entry.scores.values().map(|s| s.secs_taken).sum();
+        let wrong: i64 = entry.scores.values().map(|s| s.num_wrong).sum();
+        let mut row = vec![
+            rank.to_string(),
+            entry.user.display_name().to_string(),
+            entry.user.email.clone(),
+            solved.to_string(),
+            minutes(score),
+            minutes(solve_secs),
+            (wrong * contest.penalty).to_string(),
+            wrong.to_string(),
+        ];
+        if !with_emails {
+            row.remove(2);
+        }
+        for problem in problems.iter() {
+            match entry.scores.get(&problem.id.to_string()) {
+                Some(s) => {
+                    row.push(minutes(s.secs_taken));
+                    row.push(s.num_wrong.to_string());
+                }
+                None => {
+                    row.push(String::new());
+                    row.push(String::new());
+                }
+            }
+        }
+        rows.push(row);
+    }
+
+    Ok(Table(rows))
+}
+
+/// Final standings for organizers, see [`standings_table`]
+#[get("/contests/<contest_id>/leaderboard/export?<format>")]
+pub async fn export_standings(
+    mut db: DbConnection,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+    contest_id: i64,
+    format: Option<ExportFormat>,
+    user: &User,
+    admin: Option<&Admin>,
+) -> ResultResponse<Export> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let table = standings_table(&mut db, leaderboard_manager, &contest, true).await?;
+
+    let format = format.unwrap_or_default();
+    Ok(table.into_export(
+        format,
+        &format!("{}-standings", slug::slugify(&contest.name)),
+    ))
+}
+
+/// How each problem went over the whole contest
+#[get("/contests/<contest_id>/leaderboard/export/problems?<format>")]
+pub async fn export_problem_stats(
+    mut db: DbConnection,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+    contest_id: i64,
+    format: Option<ExportFormat>,
+    user: &User,
+    admin: Option<&Admin>,
+) -> ResultResponse<Export> {
+    let (contest, problems, entries) =
+        get_contest_data(&mut db, leaderboard_manager, contest_id, user, admin).await?;
+
+    // Anyone with a completion row ran the judge at least once, solved or not
+    let attempts = sqlx::query!(
+        "SELECT problem_id, COUNT(*) AS \"attempted!: i64\", SUM(number_wrong) AS \"wrong!: i64\" FROM problem_completion JOIN participant ON problem_completion.participant_id = participant.p_id WHERE participant.contest_id = ? AND NOT participant.is_judge AND NOT participant.is_spectator AND NOT participant.pending GROUP BY problem_id",
+        contest.id
+    )
+    .fetch_all(&mut **db)
+    .await
+    .context("Failed to count attempts")?
+    .into_iter()
+    .map(|r| (r.problem_id, (r.attempted, r.wrong)))
+    .collect::<HashMap<_, _>>();
+    let analytics = ProblemAnalytics::list(&mut db, &contest).await?;
+
+    let header = [
+        "Label",
+        "Problem",
+        "Attempted",
+        "Solved",
+        "Solve Rate (%)",
+        "Wrong Attempts",
+        "First Solve (min)",
+        "First Solver",
+        "Average Solve Time (min)",
+        "Median Solve Time (min)",
+        "Wasted Attempts",
+        "Observed Difficulty",
+    ]
+    .map(String::from)
+    .to_vec();
+    let mut rows = vec![header];
+    let participants = entries.len();
+
+    for problem in problems.iter() {
+        let key = problem.id.to_string();
+        let solves = entries
+            .iter()
+            .filter_map(|e| e.scores.get(&key).map(|s| (e, s)))
+            .collect::<Vec<_>>();
+        let first = solves.iter().min_by_key(|(_, s)| s.secs_taken);
+        let (attempted, wrong) = attempts.get(&problem.id).copied().unwrap_or((0, 0));
+        let rate = if participants == 0 {
+            0.0
+        } else {
+            solves.len() as f64 / participants as f64 * 100.0
+        };
+        let problem_analytics = analytics.get(&problem.id).cloned().unwrap_or_default();
+        let average = (!solves.is_empty())
+            .then(|| solves.iter().map(|(_, s)| s.secs_taken).sum::<i64>() / solves.len() as i64);
+        rows.push(vec![
+            problem.label.clone(),
+            problem.name.clone(),
+            attempted.to_string(),
+            solves.len().to_string(),
+            format!("{rate:.1}"),
+            wrong.to_string(),
+            first
+                .map(|(_, s)| minutes(s.secs_taken))
+                .unwrap_or_default(),
+            first
+                .map(|(e, _)| e.user.display_name().to_string())
+                .unwrap_or_default(),
+            average.map(minutes).unwrap_or_default(),
+            problem_analytics
+                .median_secs
+                .map(minutes)
+                .unwrap_or_default(),
+            problem_analytics.wasted_attempts.to_string(),
+            String::from(problem_analytics.observed_difficulty),
+        ]);
+    }
+
+    let format = format.unwrap_or_default();
+    Ok(Table(rows).into_export(
+        format,
+        &format!("{}-problems", slug::slugify(&contest.name)),
+    ))
+}
@@ -0,0 +1,241 @@
+//! Ghosts are the standings of an earlier contest laid over a contest's leaderboard for training,
+//! each of their solves shows once the contest clock gets as far as it did the first time
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use crate::{
+    auth::users::User,
+    contests::{clock_time_between, Contest},
+    db::DbPoolConnection,
+    error::prelude::*,
+};
+
+use super::{export::split_csv_line, scoring::ScoreEntry, LeaderboardEntry};
+
+const MAX_GHOSTS: usize = 1000;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Ghost {
+    pub id: i64,
+    pub contest_id: i64,
+    pub name: String,
+}
+
+struct GhostSolve {
+    ghost_id: i64,
+    problem_id: i64,
+    solve_seconds: i64,
+    num_wrong: i64,
+}
+
+/// A row of imported standings
+pub struct ImportedGhost {
+    name: String,
+    /// Problem ID, seconds into the contest, and wrong attempts before it
+    solves: Vec<(i64, i64, i64)>,
+}
+
+fn parse_minutes(field: &str) -> Option<i64> {
+    field
+        .parse::<f64>()
+        .ok()
+        .filter(|m| m.is_finite() && *m >= 0.0)
+        .map(|m| (m * 60.0).round() as i64)
+}
+
+impl Ghost {
+    pub async fn list(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Ghost,
+            "SELECT * FROM ghost WHERE contest_id = ? ORDER BY id",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't list ghosts of contest {contest_id}"))
+    }
+
+    pub async fn clear(db: &mut DbPoolConnection, contest_id: i64) -> Result {
+        sqlx::query!("DELETE FROM ghost WHERE contest_id = ?", contest_id)
+            .execute(&mut **db)
+            .await
+            .map(|_| ())
+            .with_context(|| format!("Couldn't clear ghosts of contest {contest_id}"))
+    }
+
+    /// Read standings exported from another contest (see `export_standings`), problems are
+    /// matched up by label and any other columns are ignored
+    pub fn parse_standings(
+        csv: &str,
+        labels: &HashMap<String, i64>,
+    ) -> Result<Vec<ImportedGhost>, String> {
+        let mut lines = csv
+            .trim_start_matches('\u{FEFF}')
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty());
+        let Some((_, header)) = lines.next() else {
+            return Err("There's no one to import".to_string());
+        };
+        let header = split_csv_line(header);
+        let column = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+        let name_col = column("Name").ok_or("The first line needs a Name column")?;
+        let problem_cols = labels
+            .iter()
+            .filter_map(|(label, id)| {
+                let time = column(&format!("{label} Solve Time (min)"))?;
+                Some((*id, time, column(&format!("{label} Wrong Attempts"))))
+            })
+            .collect::<Vec<_>>();
+        if problem_cols.is_empty() {
+            return Err(
+                "None of the solve time columns match a problem label in this contest".to_string(),
+            );
+        }
+
+        let mut ghosts = vec![];
+        for (i, line) in lines {
+            let line_no = i + 1;
+            let fields = split_csv_line(line);
+            let field = |col: usize| fields.get(col).map_or("", |f| f.as_str());
+            let name = field(name_col);
+            if name.is_empty() || name.chars().count() > 32 {
+                return Err(format!(
+                    "Line {line_no} needs a name of at most 32 characters"
+                ));
+            }
+            let mut solves = vec![];
+            for (problem_id, time_col, wrong_col) in problem_cols.iter() {
+                let time = field(*time_col);
+                if time.is_empty() {
+                    continue;
+                }
+                let seconds = parse_minutes(time)
+                    .ok_or_else(|| format!("Line {line_no} has an invalid solve time {time}"))?;
+                let wrong = wrong_col.map(field).unwrap_or_default();
+                let wrong = if wrong.is_empty() {
+                    0
+                } else {
+                    wrong
+                        .parse::<i64>()
+                        .ok()
+                        .filter(|w| *w >= 0)
+                        .ok_or_else(|| {
+                            format!("Line {line_no} has invalid wrong attempts {wrong}")
+                        })?
+                };
+                solves.push((*problem_id, seconds, wrong));
+            }
+            ghosts.push(ImportedGhost {
+                name: name.to_string(),
+                solves,
+            });
+            if ghosts.len() > MAX_GHOSTS {
+                return Err(format!("Only {MAX_GHOSTS} ghosts can be imported at once"));
+            }
+        }
+        if ghosts.is_empty() {
+            Err("There's no one to import".to_string())
+        } else {
+            Ok(ghosts)
+        }
+    }
+
+    pub async fn import(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        ghosts: Vec<ImportedGhost>,
+    ) -> Result {
+        for imported in ghosts {
+            let ghost = sqlx::query_as!(
+                Ghost,
+                "INSERT INTO ghost (contest_id, name) VALUES (?, ?) RETURNING *",
+                contest_id,
+                imported.name
+            )
+            .fetch_one(&mut **db)
+            .await
+            .with_context(|| format!("Couldn't add ghost {}", imported.name))?;
+            for (problem_id, seconds, wrong) in imported.solves {
+                sqlx::query!(
+                    "INSERT INTO ghost_solve (ghost_id, problem_id, solve_seconds, num_wrong) VALUES (?, ?, ?, ?)",
+                    ghost.id,
+                    problem_id,
+                    seconds,
+                    wrong
+                )
+                .execute(&mut **db)
+                .await
+                .with_context(|| format!("Couldn't add solves of ghost {}", ghost.id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the contest's ghosts to the standings with whatever they'd solved by now. Their solves
+    /// from during the freeze stay hidden like everyone else's unless `through_freeze`.
+    /// Returns how many seconds until another of their solves shows, if one will.
+    pub async fn overlay(
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+        entries: &mut Vec<LeaderboardEntry>,
+        through_freeze: bool,
+    ) -> Result<Option<i64>> {
+        let ghosts = Self::list(db, contest.id).await?;
+        if ghosts.is_empty() {
+            return Ok(None);
+        }
+        let solves = sqlx::query_as!(
+            GhostSolve,
+            "SELECT ghost_solve.* FROM ghost_solve JOIN ghost ON ghost.id = ghost_solve.ghost_id WHERE ghost.contest_id = ?",
+            contest.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't get ghost solves of contest {}", contest.id))?;
+
+        let elapsed = contest.elapsed_seconds();
+        let hiding_freeze = contest.is_frozen() && !through_freeze;
+        let shown_until = if hiding_freeze {
+            let length =
+                clock_time_between(&contest.pauses(), contest.start_time, contest.end_time)
+                    .num_seconds();
+            elapsed.min(length - contest.freeze_time * 60)
+        } else {
+            elapsed
+        };
+
+        let mut scores = HashMap::<i64, HashMap<String, ScoreEntry>>::new();
+        for solve in solves.iter().filter(|s| s.solve_seconds <= shown_until) {
+            scores.entry(solve.ghost_id).or_default().insert(
+                solve.problem_id.to_string(),
+                ScoreEntry {
+                    id: solve.problem_id,
+                    score: solve.solve_seconds + solve.num_wrong * contest.penalty * 60,
+                    time_taken: solve.solve_seconds / 60,
+                    secs_taken: solve.solve_seconds,
+                    num_wrong: solve.num_wrong,
+                },
+            );
+        }
+        entries.extend(ghosts.into_iter().map(|ghost| LeaderboardEntry {
+            user: User::temporary(String::new(), String::new(), ghost.name, ""),
+            p_id: -ghost.id,
+            scores: scores.remove(&ghost.id).unwrap_or_default(),
+            ghost: true,
+        }));
+        // Stable, so ghosts go below anyone they're tied with
+        entries.sort_by_key(|e| (Reverse(e.scores.len()), e.total_score()));
+
+        let next = (contest.is_running() && !hiding_freeze)
+            .then(|| {
+                solves
+                    .iter()
+                    .map(|s| s.solve_seconds)
+                    .filter(|s| *s > elapsed)
+                    .min()
+            })
+            .flatten();
+        Ok(next.map(|s| s - elapsed))
+    }
+}
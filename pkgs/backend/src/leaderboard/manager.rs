@@ -7,7 +7,7 @@ use tokio::sync::Mutex;
 
 use crate::{
     auth::users::User,
-    contests::{Contest, Participant},
+    contests::{clock_time_between, Contest, Participant},
     db::DbPoolConnection,
     error::prelude::*,
     problems::ProblemCompletion,
@@ -28,6 +28,31 @@ pub struct LeaderboardEntry {
     pub user: User,
     pub p_id: i64,
     pub scores: HashMap<String, ScoreEntry>,
+    /// Replayed from an earlier contest, see [`super::ghosts`]
+    pub ghost: bool,
+}
+
+impl LeaderboardEntry {
+    pub fn total_score(&self) -> i64 {
+        self.scores.values().map(|s| s.score).sum()
+    }
+}
+
+/// Places for entries in leaderboard order, people with the same solves and score share one
+pub fn ranks(entries: &[LeaderboardEntry]) -> Vec<usize> {
+    let mut ranks = Vec::with_capacity(entries.len());
+    let mut previous = None;
+    for (i, entry) in entries.iter().enumerate() {
+        let key = (entry.scores.len(), entry.total_score());
+        let rank = if previous == Some(key) {
+            ranks[i - 1]
+        } else {
+            i + 1
+        };
+        ranks.push(rank);
+        previous = Some(key);
+    }
+    ranks
 }
 
 impl Leaderboard {
@@ -54,6 +79,19 @@ impl Leaderboard {
         self.contest.is_frozen()
     }
 
+    /// Standings with nothing hidden by the freeze, worked out from scratch since the shared
+    /// leaderboard stops taking in completions while it's frozen
+    pub async fn unfrozen(
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+    ) -> Result<(Vec<LeaderboardEntry>, HashMap<i64, Option<i64>>)> {
+        let mut contest = contest.clone();
+        contest.freeze_time = 0;
+        let (mut leaderboard, _rx) = Self::new(db, contest).await?;
+        let entries = leaderboard.full(db).await?;
+        Ok((entries, leaderboard.first_map))
+    }
+
     fn get_first_person_for_problem(scores: &[ParticipantScores], problem_id: i64) -> Option<i64> {
         scores
             .iter()
@@ -102,11 +140,13 @@ impl Leaderboard {
 
     pub async fn full(&mut self, db: &mut DbPoolConnection) -> Result<Vec<LeaderboardEntry>> {
         let now = chrono::Utc::now().naive_utc();
-        if self
-            .last_update
-            .map(|lu| now > self.contest.end_time && lu < self.contest.end_time)
-            .unwrap_or(true)
-        {
+        // Refresh once the contest ends, or someone's extra time does, to show what the freeze hid
+        let passed_end = |lu: NaiveDateTime| {
+            std::iter::once(self.contest.end_time)
+                .chain(self.scores.iter().map(|s| s.participant_end))
+                .any(|end| now > end && lu < end)
+        };
+        if self.last_update.map(passed_end).unwrap_or(true) {
             self.full_refresh(db, None).await?;
         } else {
             self.last_update = Some(now);
@@ -127,7 +167,7 @@ impl Leaderboard {
             "
             SELECT user.*, participant.p_id FROM participant 
             JOIN user ON participant.user_id = user.id 
-            WHERE contest_id = ? AND is_judge = false
+            WHERE contest_id = ? AND is_judge = false AND is_spectator = false AND pending = false
             ORDER BY CASE participant.p_id {} ELSE 0 END;
         ",
             if cases.is_empty() {
@@ -156,6 +196,7 @@ impl Leaderboard {
                             .map(|(k, v)| (k.to_string(), v))
                             .collect::<HashMap<_, _>>()
                     }),
+                    ghost: false,
                 }
             })
             .collect::<Vec<_>>();
@@ -166,6 +207,14 @@ impl Leaderboard {
         if self.is_frozen() {
             return;
         }
+        // Extra time after the contest ends stays frozen until the participant's own end
+        if self.contest.freeze_time != 0
+            && completion
+                .completed_at
+                .is_some_and(|c| c > self.contest.end_time)
+        {
+            return;
+        }
 
         let original_order = self
             .scores
@@ -187,6 +236,7 @@ impl Leaderboard {
                     score: ScoreEntry::from_completion(
                         completion,
                         self.contest.start_time,
+                        &self.contest.pauses(),
                         self.contest.penalty,
                     ),
                 });
@@ -237,6 +287,46 @@ impl Leaderboard {
         self.send_msg(LeaderboardUpdateMessage::ReOrder { participant_map });
     }
 
+    /// Post a submission to the ticker, `participant_ids` are everyone it counts for
+    /// so a teammate holding the first solve still makes it a first solve
+    pub fn announce(
+        &self,
+        participant_ids: &[i64],
+        name: &str,
+        problem_id: i64,
+        problem_label: &str,
+        solved: bool,
+        at: NaiveDateTime,
+    ) {
+        // The ticker would give away what the frozen board is hiding
+        if self.is_frozen() {
+            return;
+        }
+        // Same as completions, extra time after the end stays hidden while there's a freeze
+        if self.contest.freeze_time != 0 && at > self.contest.end_time {
+            return;
+        }
+        let first = self.first_map.get(&problem_id).copied().flatten();
+        let kind = if !solved {
+            TickerKind::Rejected
+        } else if first.is_some_and(|f| participant_ids.contains(&f)) {
+            TickerKind::FirstSolve
+        } else {
+            TickerKind::Solved
+        };
+        let elapsed = clock_time_between(&self.contest.pauses(), self.contest.start_time, at);
+        self.send_msg(LeaderboardUpdateMessage::Event {
+            kind,
+            name: name.to_string(),
+            problem_label: problem_label.to_string(),
+            clock: format!(
+                "{:02}:{:02}",
+                elapsed.num_hours(),
+                elapsed.num_minutes() % 60
+            ),
+        });
+    }
+
     pub fn remove_user(&mut self, user_id: i64) {
         self.scores.retain(|s| s.user_id != user_id);
         self.send_msg(LeaderboardUpdateMessage::FullRefresh);
@@ -262,14 +352,36 @@ impl Leaderboard {
         db: &mut DbPoolConnection,
         contest: Option<&Contest>,
     ) -> Result {
+        let mut times_changed = false;
         if let Some(c) = contest {
+            times_changed = c.start_time != self.contest.start_time
+                || c.end_time != self.contest.end_time
+                || c.freeze_time != self.contest.freeze_time
+                || c.paused_at != self.contest.paused_at;
             self.contest = c.clone();
+            if times_changed {
+                // Sent even while frozen, clients need the new times to know when the freeze ends
+                self.tx
+                    .send(LeaderboardUpdateMessage::contest_updated(&self.contest))?;
+            }
         }
+        let old_standings = Self::standings(&self.scores);
         self.scores = Self::get_scores(db, &self.contest).await?;
         self.first_map = Self::get_first(db, &self.scores, &self.contest).await?;
-        self.tx.send(LeaderboardUpdateMessage::FullRefresh)?;
+        // Clients can apply new times live, so only reload them if the standings moved too
+        if !times_changed || Self::standings(&self.scores) != old_standings {
+            self.tx.send(LeaderboardUpdateMessage::FullRefresh)?;
+        }
         Ok(())
     }
+
+    /// Everything clients display per participant
+    fn standings(scores: &[ParticipantScores]) -> Vec<(i64, HashMap<i64, ScoreEntry>)> {
+        scores
+            .iter()
+            .map(|s| (s.participant_id, s.scores.clone()))
+            .collect()
+    }
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -296,6 +408,45 @@ pub enum LeaderboardUpdateMessage {
     ReOrder {
         participant_map: HashMap<i64, (usize, usize)>,
     },
+    /// A line for the ticker, e.g. "Team X solved B at 01:23", `clock` is contest time
+    #[serde(rename_all = "camelCase")]
+    Event {
+        kind: TickerKind,
+        name: String,
+        problem_label: String,
+        clock: String,
+    },
+    /// Contest times were edited or the clock was paused, timestamps are milliseconds since the epoch
+    #[serde(rename_all = "camelCase")]
+    ContestUpdated {
+        start_time: i64,
+        end_time: i64,
+        freeze_time: i64,
+        is_frozen: bool,
+        is_paused: bool,
+        paused_seconds: i64,
+    },
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TickerKind {
+    FirstSolve,
+    Solved,
+    Rejected,
+}
+
+impl LeaderboardUpdateMessage {
+    fn contest_updated(contest: &Contest) -> Self {
+        Self::ContestUpdated {
+            start_time: contest.start_time.and_utc().timestamp_millis(),
+            end_time: contest.end_time.and_utc().timestamp_millis(),
+            freeze_time: contest.freeze_time,
+            is_frozen: contest.is_frozen(),
+            is_paused: contest.is_paused(),
+            paused_seconds: contest.paused_seconds(),
+        }
+    }
 }
 
 pub type LeaderboardUpdateSender = tokio::sync::broadcast::Sender<LeaderboardUpdateMessage>;
@@ -374,6 +525,23 @@ impl LeaderboardManager {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub async fn announce(
+        &mut self,
+        contest: &Contest,
+        participant_ids: &[i64],
+        name: &str,
+        problem_id: i64,
+        problem_label: &str,
+        solved: bool,
+        at: NaiveDateTime,
+    ) {
+        if let Some((leaderboard, _)) = self.leaderboards.get(&contest.id) {
+            let leaderboard = leaderboard.lock().await;
+            leaderboard.announce(participant_ids, name, problem_id, problem_label, solved, at);
+        }
+    }
+
     pub async fn process_completion(&mut self, completion: &ProblemCompletion, contest: &Contest) {
         if let Some((leaderboard, _)) = self.leaderboards.get_mut(&contest.id) {
             let mut leaderboard = leaderboard.lock().await;
@@ -1,32 +1,109 @@
 use std::{collections::HashMap, sync::Arc};
 
 use chrono::TimeZone;
-use rocket::{fairing::AdHoc, get, routes, State};
+use rocket::{fairing::AdHoc, get, http::Status, routes, State};
 
+mod embed;
+mod export;
+mod ghosts;
 mod manager;
+mod presentation;
 mod scoring;
+mod series;
 mod ws;
 
-pub use manager::{LeaderboardManager, LeaderboardManagerHandle};
+pub use export::{split_csv_line, standings_table, Export, ExportFormat, Table};
+pub use ghosts::Ghost;
+pub use manager::{ranks, LeaderboardEntry, LeaderboardManager, LeaderboardManagerHandle};
 use rocket_dyn_templates::Template;
+pub use series::series_standings;
 use tokio::sync::Mutex;
 
 use crate::{
     auth::users::{Admin, User},
-    contests::{Contest, Participant},
+    contests::{Contest, Participant, ParticipantRole},
     context_with_base,
-    db::DbConnection,
+    db::{DbConnection, DbPoolConnection},
     error::prelude::*,
     times::{datetime_to_html_time, ClientTimeZone},
 };
 
-use self::ws::leaderboard_ws;
+use self::{manager::Leaderboard, ws::leaderboard_ws};
 
 #[derive(Serialize)]
 struct ProblemIdTemp {
     pub id: i64,
     pub slug: String,
     pub name: String,
+    pub label: String,
+}
+
+/// Everything both leaderboard pages show
+struct LeaderboardData {
+    contest: Contest,
+    participant: Option<Participant>,
+    problems: Vec<ProblemIdTemp>,
+    entries: Vec<LeaderboardEntry>,
+    first_map: HashMap<String, Option<i64>>,
+    is_frozen: bool,
+    /// Seconds until one of the contest's ghosts solves something, see [`Ghost::overlay`]
+    next_ghost_solve: Option<i64>,
+}
+
+impl LeaderboardData {
+    async fn get(
+        db: &mut DbPoolConnection,
+        leaderboard_manager: &LeaderboardManagerHandle,
+        contest_id: i64,
+        user: Option<&User>,
+        admin: Option<&Admin>,
+    ) -> ResultResponse<Self> {
+        let contest = Contest::get_or_404(db, contest_id).await?;
+        let participant = if let Some(user) = user {
+            Participant::get(db, contest_id, user.id).await?
+        } else {
+            None
+        };
+        if !contest.is_visible_to(participant.as_ref(), admin) {
+            return Err(Status::NotFound.into());
+        }
+        let mut leaderboard_manager = leaderboard_manager.lock().await;
+        let leaderboard = leaderboard_manager
+            .get_leaderboard(db, &contest)
+            .await?
+            .clone();
+        drop(leaderboard_manager);
+        let mut leaderboard = leaderboard.lock().await;
+
+        let problems = sqlx::query_as!(
+            ProblemIdTemp,
+            "SELECT id, slug, name, label from problem WHERE contest_id = ? ORDER BY ordering, id",
+            contest.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to fetch problems")?;
+
+        let mut entries = leaderboard.full(db).await?;
+        let is_frozen = leaderboard.is_frozen();
+        let first_map = leaderboard
+            .first_map
+            .iter()
+            .map(|(k, v)| (k.to_string(), *v))
+            .collect::<HashMap<_, _>>();
+        drop(leaderboard);
+        let next_ghost_solve = Ghost::overlay(db, &contest, &mut entries, false).await?;
+
+        Ok(Self {
+            contest,
+            participant,
+            problems,
+            entries,
+            first_map,
+            is_frozen,
+            next_ghost_solve,
+        })
+    }
 }
 
 #[get("/contests/<contest_id>/leaderboard")]
@@ -38,49 +115,38 @@ async fn leaderboard_get(
     user: Option<&User>,
     admin: Option<&Admin>,
 ) -> ResultResponse<Template> {
-    let contest = Contest::get_or_404(&mut db, contest_id).await?;
-    let mut leaderboard_manager = leaderboard_manager.lock().await;
-    let leaderboard = leaderboard_manager
-        .get_leaderboard(&mut db, &contest)
-        .await?
-        .clone();
-    drop(leaderboard_manager);
-    let mut leaderboard = leaderboard.lock().await;
-
-    let problems = sqlx::query_as!(
-        ProblemIdTemp,
-        "SELECT id, slug, name from problem WHERE contest_id = ?",
-        contest.id
-    )
-    .fetch_all(&mut **db)
-    .await
-    .context("Failed to fetch problems")?;
-
-    let is_judge = if let Some(user) = user {
-        Participant::get(&mut db, contest_id, user.id)
-            .await?
-            .is_some_and(|p| p.is_judge)
-    } else {
-        false
-    };
-
-    let entries = leaderboard.full(&mut db).await?;
-    let is_frozen = leaderboard.is_frozen();
+    let LeaderboardData {
+        contest,
+        participant,
+        problems,
+        mut entries,
+        mut first_map,
+        is_frozen,
+        mut next_ghost_solve,
+    } = LeaderboardData::get(&mut db, leaderboard_manager, contest_id, user, admin).await?;
+
+    let is_judge = participant.as_ref().is_some_and(|p| p.is_judge);
+    let sees_through_freeze = is_frozen
+        && contest.spectators_see_unfrozen
+        && participant.is_some_and(|p| p.role() == ParticipantRole::Spectator);
+    if sees_through_freeze {
+        let (unfrozen_entries, unfrozen_first) = Leaderboard::unfrozen(&mut db, &contest).await?;
+        entries = unfrozen_entries;
+        next_ghost_solve = Ghost::overlay(&mut db, &contest, &mut entries, true).await?;
+        first_map = unfrozen_first
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+    }
 
     let start_local = tz.timezone().from_utc_datetime(&contest.start_time);
     let start_local_html = datetime_to_html_time(&start_local);
     let end_local = tz.timezone().from_utc_datetime(&contest.end_time);
     let end_local_html = datetime_to_html_time(&end_local);
 
-    let first_map = leaderboard
-        .first_map
-        .iter()
-        .map(|(k, v)| (k.to_string(), v))
-        .collect::<HashMap<_, _>>();
-
     Ok(Template::render(
         "contests/leaderboard",
-        context_with_base!(user, is_frozen, first_map, freeze_percent: contest.freeze_percent(), progress: contest.progress(), has_started: contest.has_started(), start_local_html, end_local_html, is_running: contest.is_running(), contest, entries, problems, is_admin: admin.is_some(), is_judge),
+        context_with_base!(user, is_frozen, first_map, freeze_percent: contest.freeze_percent(), progress: contest.progress(), paused_seconds: contest.paused_seconds(), has_started: contest.has_started(), start_local_html, end_local_html, is_running: contest.is_running(), contest, entries, problems, is_admin: admin.is_some(), is_judge, sees_through_freeze, next_ghost_solve),
     ))
 }
 
@@ -98,6 +164,17 @@ pub fn stage() -> AdHoc {
         rocket
             .attach(shutdown_fairing)
             .manage::<LeaderboardManagerHandle>(Arc::new(Mutex::new(manager)))
-            .mount("/", routes![leaderboard_get, leaderboard_ws])
+            .mount(
+                "/",
+                routes![
+                    leaderboard_get,
+                    leaderboard_ws,
+                    presentation::leaderboard_presentation,
+                    embed::leaderboard_embed,
+                    embed::leaderboard_snapshot,
+                    export::export_standings,
+                    export::export_problem_stats
+                ],
+            )
     })
 }
@@ -0,0 +1,69 @@
+use rocket::{get, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base,
+    db::DbConnection,
+    error::prelude::*,
+};
+
+use super::{ranks, LeaderboardData, LeaderboardEntry, LeaderboardManagerHandle};
+
+const DEFAULT_INTERVAL: u64 = 10;
+
+#[derive(Serialize)]
+struct PresentationRow {
+    rank: usize,
+    solved: usize,
+    /// Total score in minutes, penalties included
+    score: i64,
+    entry: LeaderboardEntry,
+}
+
+/// The leaderboard for putting up on a projector, it pages through the standings every
+/// `interval` seconds and follows live updates on its own
+#[get("/contests/<contest_id>/leaderboard/presentation?<interval>")]
+pub async fn leaderboard_presentation(
+    mut db: DbConnection,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+    contest_id: i64,
+    interval: Option<u64>,
+    user: Option<&User>,
+    admin: Option<&Admin>,
+) -> ResultResponse<Template> {
+    let LeaderboardData {
+        contest,
+        problems,
+        entries,
+        first_map,
+        is_frozen,
+        ..
+    } = LeaderboardData::get(&mut db, leaderboard_manager, contest_id, user, admin).await?;
+
+    let rows = ranks(&entries)
+        .into_iter()
+        .zip(entries)
+        .map(|(rank, entry)| PresentationRow {
+            rank,
+            solved: entry.scores.len(),
+            score: entry.total_score() / 60,
+            entry,
+        })
+        .collect::<Vec<_>>();
+    let interval = interval.unwrap_or(DEFAULT_INTERVAL).clamp(3, 120);
+
+    Ok(Template::render(
+        "contests/leaderboard_presentation",
+        context_with_base!(
+            user,
+            rows,
+            problems,
+            first_map,
+            is_frozen,
+            interval,
+            is_running: contest.is_running(),
+            contest
+        ),
+    ))
+}
@@ -3,13 +3,13 @@ use std::collections::HashMap;
 use chrono::NaiveDateTime;
 
 use crate::{
-    contests::{Contest, Participant},
+    contests::{clock_time_between, Contest, Participant},
     db::DbPoolConnection,
     error::prelude::*,
     problems::ProblemCompletion,
 };
 
-#[derive(Serialize, Clone, Copy, Debug)]
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ScoreEntry {
     pub id: i64,         // Problem ID
     pub score: i64,      // In Seconds
@@ -19,12 +19,18 @@ pub struct ScoreEntry {
 }
 
 impl ScoreEntry {
+    /// Time taken counts from the start of the contest, leaving out when the clock was paused
     pub fn from_completion(
         completion: &ProblemCompletion,
         contest_start: NaiveDateTime,
+        contest_pauses: &[(NaiveDateTime, NaiveDateTime)],
         contest_penalty_minutes: i64,
     ) -> Self {
-        let delta = completion.completed_at.unwrap() - contest_start;
+        let delta = clock_time_between(
+            contest_pauses,
+            contest_start,
+            completion.completed_at.unwrap(),
+        );
         Self {
             id: completion.problem_id,
             score: delta.num_seconds() + (completion.number_wrong * contest_penalty_minutes * 60),
@@ -38,21 +44,29 @@ impl ScoreEntry {
 #[derive(Serialize, Clone, Debug)]
 pub struct ParticipantScores {
     contest_start: NaiveDateTime,
+    contest_pauses: Vec<(NaiveDateTime, NaiveDateTime)>,
     contest_penalty_minutes: i64,
     contest_end: NaiveDateTime,
     contest_freeze: i64,
+    /// Contest end plus any extra time the participant was given
+    pub participant_end: NaiveDateTime,
     pub participant_id: i64,
     pub user_id: i64,
     pub scores: HashMap<i64, ScoreEntry>,
 }
 
 impl ParticipantScores {
+    /// Completions made during the freeze stay hidden until the participant's own end time,
+    /// which is later than the contest's if they were given extra time
+    #[allow(clippy::too_many_arguments)]
     async fn get_scores(
         db: &mut DbPoolConnection,
         id: i64,
         contest_start: NaiveDateTime,
+        contest_pauses: &[(NaiveDateTime, NaiveDateTime)],
         contest_penalty_minutes: i64,
         contest_end: NaiveDateTime,
+        participant_end: NaiveDateTime,
         contest_freeze: i64,
     ) -> Result<HashMap<i64, ScoreEntry>> {
         let completions = ProblemCompletion::get_for_participant(db, id)
@@ -65,15 +79,22 @@ impl ParticipantScores {
                 c.completed_at
                     .filter(|c| {
                         c >= &contest_start
-                            && c <= &contest_end
+                            && c <= &participant_end
                             && (contest_freeze == 0
-                                || now >= contest_end
-                                || (contest_end - *c).num_minutes() > contest_freeze)
+                                || now >= participant_end
+                                || clock_time_between(contest_pauses, *c, contest_end)
+                                    .num_minutes()
+                                    > contest_freeze)
                     })
                     .map(|_| {
                         (
                             c.problem_id,
-                            ScoreEntry::from_completion(&c, contest_start, contest_penalty_minutes),
+                            ScoreEntry::from_completion(
+                                &c,
+                                contest_start,
+                                contest_pauses,
+                                contest_penalty_minutes,
+                            ),
                         )
                     })
             })
@@ -86,6 +107,8 @@ impl ParticipantScores {
         participant: &Participant,
         contest: &Contest,
     ) -> Result<Self> {
+        let contest_pauses = contest.pauses();
+        let participant_end = contest.end_time_for(Some(participant));
         Ok(Self {
             contest_start: contest.start_time,
             contest_penalty_minutes: contest.penalty,
@@ -97,11 +120,15 @@ impl ParticipantScores {
                 db,
                 participant.p_id,
                 contest.start_time,
+                &contest_pauses,
                 contest.penalty,
                 contest.end_time,
+                participant_end,
                 contest.freeze_time,
             )
             .await?,
+            participant_end,
+            contest_pauses,
         })
     }
 
@@ -112,6 +139,7 @@ impl ParticipantScores {
                     *entry = ScoreEntry::from_completion(
                         completion,
                         self.contest_start,
+                        &self.contest_pauses,
                         self.contest_penalty_minutes,
                     );
                 } else {
@@ -123,6 +151,7 @@ impl ParticipantScores {
                     ScoreEntry::from_completion(
                         completion,
                         self.contest_start,
+                        &self.contest_pauses,
                         self.contest_penalty_minutes,
                     ),
                 );
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use crate::{auth::users::User, contests::Contest, db::DbPoolConnection, error::prelude::*};
+
+use super::LeaderboardManagerHandle;
+
+/// How a participant did in one contest of a series
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct ContestResult {
+    pub solved: i64,
+    /// In minutes, including penalties for wrong submissions
+    pub penalty: i64,
+    /// Whether this result is one of the ones added to the series total
+    pub counted: bool,
+}
+
+#[derive(Serialize)]
+pub struct SeriesEntry {
+    pub user: User,
+    pub solved: i64,
+    pub penalty: i64,
+    /// Contest ID to the participant's result in it, missing if they didn't take part
+    pub results: HashMap<String, ContestResult>,
+}
+
+/// Combine the leaderboards of every contest in a series into one set of standings.
+/// Each participant's contests are ranked the same way a single leaderboard is,
+/// most problems solved then least penalty, and only the best `best_n` of them are added up.
+pub async fn series_standings(
+    db: &mut DbPoolConnection,
+    leaderboard_manager: &LeaderboardManagerHandle,
+    contests: &[Contest],
+    best_n: Option<i64>,
+) -> Result<Vec<SeriesEntry>> {
+    let mut users: HashMap<i64, (User, Vec<(i64, ContestResult)>)> = HashMap::new();
+    for contest in contests {
+        let mut manager = leaderboard_manager.lock().await;
+        let leaderboard = manager.get_leaderboard(db, contest).await?;
+        drop(manager);
+        let entries = leaderboard.lock().await.full(db).await?;
+        for entry in entries {
+            let result = ContestResult {
+                solved: entry.scores.len() as i64,
+                penalty: entry.scores.values().map(|s| s.score).sum::<i64>() / 60,
+                counted: false,
+            };
+            users
+                .entry(entry.user.id)
+                .or_insert_with(|| (entry.user, Vec::new()))
+                .1
+                .push((contest.id, result));
+        }
+    }
+
+    let mut standings = users
+        .into_values()
+        .map(|(user, mut results)| {
+            results
+                .sort_by(|(_, a), (_, b)| b.solved.cmp(&a.solved).then(a.penalty.cmp(&b.penalty)));
+            let counted = best_n.map_or(results.len(), |n| n.max(0) as usize);
+            let mut solved = 0;
+            let mut penalty = 0;
+            for (_, result) in results.iter_mut().take(counted) {
+                result.counted = true;
+                solved += result.solved;
+                penalty += result.penalty;
+            }
+            SeriesEntry {
+                user,
+                solved,
+                penalty,
+                results: results
+                    .into_iter()
+                    .map(|(id, r)| (id.to_string(), r))
+                    .collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+    standings.sort_by(|a, b| {
+        b.solved
+            .cmp(&a.solved)
+            .then(a.penalty.cmp(&b.penalty))
+            .then(a.user.id.cmp(&b.user.id))
+    });
+    Ok(standings)
+}
@@ -1,7 +1,9 @@
 use log::error;
 use rocket::{
     futures::{SinkExt, StreamExt},
-    get, State,
+    get,
+    http::Status,
+    State,
 };
 use rocket_ws::{stream::DuplexStream, WebSocket};
 use tokio::{
@@ -9,7 +11,12 @@ use tokio::{
     time::{self, Duration, Instant},
 };
 
-use crate::{contests::Contest, db::DbConnection, error::prelude::*};
+use crate::{
+    auth::users::{Admin, User},
+    contests::{Contest, Participant},
+    db::DbConnection,
+    error::prelude::*,
+};
 
 use super::{
     manager::{LeaderboardUpdateMessage, LeaderboardUpdateReceiver, ShutdownReceiver},
@@ -97,8 +104,18 @@ pub async fn leaderboard_ws(
     mut db: DbConnection,
     contest_id: i64,
     manager: &State<LeaderboardManagerHandle>,
+    user: Option<&User>,
+    admin: Option<&Admin>,
 ) -> ResultResponse<rocket_ws::Channel<'static>> {
     let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let participant = if let Some(user) = user {
+        Participant::get(&mut db, contest_id, user.id).await?
+    } else {
+        None
+    };
+    if !contest.is_visible_to(participant.as_ref(), admin) {
+        return Err(Status::NotFound.into());
+    }
     let mut manager = manager.lock().await;
     let rx = manager.subscribe_leaderboard(&mut db, &contest).await?;
     let shutdown_rx = manager.subscribe_shutdown();
@@ -13,22 +13,38 @@ extern crate serde;
 extern crate rocket;
 
 mod admin;
+mod archive;
 mod auth;
+mod bank;
+mod banner;
 mod branding;
+mod certificates;
 mod contests;
 mod csp;
 mod db;
+mod email;
 mod error;
+mod i18n;
+mod inbox;
 mod leaderboard;
+mod maintenance;
+mod md;
 mod messages;
+mod pages;
+mod pdf;
+mod printing;
 mod problems;
 mod profile;
+mod rate_limit;
 mod run;
 mod serve;
+mod series;
 mod settings;
+mod tar;
 #[macro_use]
 mod template;
 mod times;
+mod zip;
 
 use crate::auth::users::User;
 use crate::error::prelude::*;
@@ -72,7 +88,11 @@ fn rocket(figment: Figment) -> rocket::Rocket<Build> {
         .attach(error::stage())
         .attach(db::stage())
         .attach(times::stage())
+        .attach(rate_limit::stage())
         .attach(template::stage())
+        .attach(banner::stage())
+        .attach(maintenance::stage())
+        .attach(email::stage())
         .attach(serve::stage())
         .attach(branding::stage())
         .attach(auth::stage())
@@ -80,7 +100,14 @@ fn rocket(figment: Figment) -> rocket::Rocket<Build> {
         .attach(admin::stage())
         .attach(contests::stage())
         .attach(problems::stage())
+        .attach(bank::stage())
+        .attach(printing::stage())
+        .attach(inbox::stage())
+        .attach(certificates::stage())
+        .attach(archive::stage())
         .attach(leaderboard::stage())
+        .attach(series::stage())
+        .attach(pages::stage())
         .attach(profile::stage())
 }
 
@@ -101,6 +128,10 @@ fn main() -> Result {
         Ok(())
     } else if args.contains(&"--worker-test-shell".to_string()) {
         run::worker::run_test_shell().context("Worker test shell failed")
+    } else if args.contains(&"--doctor".to_string()) {
+        run::worker::run_doctor().context("Doctor failed")
+    } else if args.contains(&"--judge-node".to_string()) {
+        run::nodes::run_judge_node().context("Judge node failed")
     } else {
         _main().context("Rocket failed")
     }
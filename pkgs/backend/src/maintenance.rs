@@ -0,0 +1,170 @@
+//! Maintenance mode, everyone but admins gets a 503 page instead of the site. It's turned on by
+//! an admin or, for backups and migrations run from outside, while a lock file exists
+
+use std::{path::PathBuf, sync::RwLock};
+
+use log::{error, warn};
+use rocket::{
+    fairing::AdHoc,
+    get,
+    http::{Method, Status},
+    routes, Request, State,
+};
+use rocket_db_pools::Database as R_Database;
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base,
+    db::{Database, DbPool, DbPoolConnection},
+    error::prelude::*,
+};
+
+const MAINTENANCE_PATH: &str = "/maintenance";
+
+/// Paths that still work in maintenance mode, logging in so admins can get through and
+/// whatever the maintenance page itself needs
+const ALLOWED_PREFIXES: [&str; 4] = [MAINTENANCE_PATH, "/auth/", "/_astro/", "/navbar_logo"];
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// The site is in maintenance mode while this file exists, so backup and migration scripts
+    /// can turn it on around themselves without an admin
+    lock_file: Option<PathBuf>,
+}
+
+pub struct Maintenance {
+    /// Turned on by an admin, with the message to show
+    manual: RwLock<Option<String>>,
+    lock_file: Option<PathBuf>,
+}
+
+impl Maintenance {
+    fn new(config: MaintenanceConfig) -> Self {
+        Self {
+            manual: RwLock::new(None),
+            lock_file: config.lock_file,
+        }
+    }
+
+    /// Whether an admin turned maintenance mode on, and the message they left
+    pub fn manual(&self) -> Option<String> {
+        self.manual.read().ok().and_then(|m| m.clone())
+    }
+
+    pub fn locked(&self) -> bool {
+        self.lock_file.as_ref().is_some_and(|f| f.exists())
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.locked() || self.manual().is_some()
+    }
+
+    fn set_manual(&self, message: Option<String>) {
+        match self.manual.write() {
+            Ok(mut manual) => *manual = message,
+            Err(e) => error!("Couldn't update maintenance mode: {e}"),
+        }
+    }
+
+    /// Turn maintenance mode on with a message, or off with `None`
+    pub async fn set(&self, db: &mut DbPoolConnection, message: Option<String>) -> Result {
+        if let Some(ref message) = message {
+            sqlx::query!(
+                "INSERT INTO maintenance (id, message) VALUES (1, ?) ON CONFLICT(id) DO UPDATE SET message = excluded.message",
+                message
+            )
+            .execute(&mut **db)
+            .await
+            .context("Error turning on maintenance mode")?;
+        } else {
+            sqlx::query!("DELETE FROM maintenance")
+                .execute(&mut **db)
+                .await
+                .context("Error turning off maintenance mode")?;
+        }
+        self.set_manual(message);
+        Ok(())
+    }
+
+    async fn load(&self, pool: &DbPool) -> Result {
+        let mut conn = pool
+            .acquire()
+            .await
+            .context("Couldn't get db connection for loading maintenance mode")?;
+        let message = sqlx::query_scalar!("SELECT message FROM maintenance WHERE id = 1")
+            .fetch_optional(&mut *conn)
+            .await
+            .context("Error fetching maintenance mode")?;
+        self.set_manual(message);
+        Ok(())
+    }
+}
+
+fn is_allowed(path: &str) -> bool {
+    // Anything with an extension is a static file, like the favicon
+    let is_file = path.rsplit('/').next().is_some_and(|s| s.contains('.'));
+    is_file || ALLOWED_PREFIXES.iter().any(|p| path.starts_with(p))
+}
+
+/// Send everyone who isn't an admin to the maintenance page, before their request gets routed
+async fn check_maintenance(req: &mut Request<'_>) {
+    let Some(maintenance) = req.rocket().state::<Maintenance>() else {
+        return;
+    };
+    if !maintenance.is_active() || is_allowed(req.uri().path().as_str()) {
+        return;
+    }
+    if req.guard::<&Admin>().await.succeeded().is_some() {
+        return;
+    }
+    req.set_method(Method::Get);
+    req.set_uri(rocket::http::uri::Origin::parse(MAINTENANCE_PATH).unwrap());
+}
+
+#[get("/maintenance")]
+async fn maintenance_page(
+    user: Option<&User>,
+    maintenance: &State<Maintenance>,
+) -> (Status, Template) {
+    let active = maintenance.is_active();
+    let message = maintenance.manual().unwrap_or_default();
+    let status = if active {
+        Status::ServiceUnavailable
+    } else {
+        Status::Ok
+    };
+    (
+        status,
+        Template::render("maintenance", context_with_base!(user, active, message)),
+    )
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Maintenance Mode", |rocket| async {
+        let config = rocket
+            .figment()
+            .extract_inner::<MaintenanceConfig>("maintenance")
+            .unwrap_or_default();
+        rocket
+            .manage(Maintenance::new(config))
+            .mount("/", routes![maintenance_page])
+            .attach(AdHoc::on_request("Maintenance Check", |req, _| {
+                Box::pin(check_maintenance(req))
+            }))
+            .attach(AdHoc::on_liftoff("Load Maintenance Mode", |rocket| {
+                Box::pin(async move {
+                    let (Some(db), Some(maintenance)) =
+                        (Database::fetch(rocket), rocket.state::<Maintenance>())
+                    else {
+                        error!("Couldn't load maintenance mode");
+                        return;
+                    };
+                    if let Err(why) = maintenance.load(&db.0).await {
+                        warn!("Couldn't load maintenance mode: {why:?}");
+                    }
+                })
+            }))
+    })
+}
@@ -0,0 +1,169 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use latex2mathml::{latex_to_mathml, DisplayStyle};
+use markdown::{CompileOptions, Constructs, Options, ParseOptions};
+use regex::{Captures, Regex};
+
+use crate::error::prelude::*;
+
+/// Extra tags on top of ammonia's defaults that markdown itself can output
+const MARKDOWN_TAGS: [&str; 2] = ["input", "section"];
+
+/// Attributes markdown output relies on, code blocks need their class for highlighting and math,
+/// task lists need their checkboxes and footnotes need their ids to link back and forth
+const MARKDOWN_ATTRIBUTES: [(&str, &[&str]); 7] = [
+    ("code", &["class"]),
+    ("input", &["type", "checked", "disabled"]),
+    ("a", &["id", "data-footnote-ref", "data-footnote-backref"]),
+    ("sup", &["id"]),
+    ("li", &["id"]),
+    ("h2", &["id"]),
+    ("section", &["class", "data-footnotes"]),
+];
+
+/// Tags latex2mathml outputs, only allowed when math is rendered on the server
+const MATHML_TAGS: [&str; 22] = [
+    "math",
+    "semantics",
+    "annotation",
+    "mrow",
+    "mi",
+    "mn",
+    "mo",
+    "mtext",
+    "mspace",
+    "ms",
+    "msup",
+    "msub",
+    "msubsup",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "mover",
+    "munder",
+    "munderover",
+    "mtable",
+    "mtr",
+    "mtd",
+];
+
+const MATHML_ATTRIBUTES: [&str; 10] = [
+    "xmlns",
+    "display",
+    "mathvariant",
+    "stretchy",
+    "accent",
+    "accentunder",
+    "linethickness",
+    "width",
+    "columnalign",
+    "encoding",
+];
+
+static INLINE_MATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<code class="language-math math-inline">([^<]*)</code>"#).unwrap()
+});
+static DISPLAY_MATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<pre><code class="language-math math-display">([^<]*)</code></pre>"#).unwrap()
+});
+
+/// Undo the escaping markdown does to code, the MathML still goes through the sanitizer after
+fn unescape(html: &str) -> String {
+    html.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Swap math blocks for MathML so they show without JS and print properly.
+/// Anything latex2mathml can't handle is left as is for KaTeX to pick up in the browser.
+fn render_math(html: &str) -> String {
+    let replace = |display: DisplayStyle| {
+        move |caps: &Captures| {
+            latex_to_mathml(&unescape(&caps[1]), display).unwrap_or_else(|_| caps[0].to_string())
+        }
+    };
+    let html = DISPLAY_MATH.replace_all(html, replace(DisplayStyle::Block));
+    INLINE_MATH
+        .replace_all(&html, replace(DisplayStyle::Inline))
+        .into_owned()
+}
+
+/// Which HTML makes it through when rendering markdown written by problem and contest authors.
+/// Authors can write raw HTML in their markdown, anything not in the allowlist is stripped
+/// (keeping its text) and scripts, styles and event handlers are always removed.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MarkdownConfig {
+    /// Tags to allow on top of the defaults
+    #[serde(default)]
+    pub extra_tags: Vec<String>,
+    /// Tags to remove from the defaults
+    #[serde(default)]
+    pub removed_tags: Vec<String>,
+    /// Tag name to attributes to allow on it, use `*` as the tag name to allow an attribute on every tag
+    #[serde(default)]
+    pub extra_attributes: HashMap<String, Vec<String>>,
+    /// Set from `branding.server_side_math`
+    #[serde(skip)]
+    pub server_side_math: bool,
+}
+
+impl MarkdownConfig {
+    fn sanitizer(&self) -> ammonia::Builder<'_> {
+        let mut builder = ammonia::Builder::default();
+        builder.add_tags(MARKDOWN_TAGS);
+        for (tag, attrs) in MARKDOWN_ATTRIBUTES {
+            builder.add_tag_attributes(tag, attrs.iter().copied());
+        }
+        if self.server_side_math {
+            builder.add_tags(MATHML_TAGS);
+            for tag in MATHML_TAGS {
+                builder.add_tag_attributes(tag, MATHML_ATTRIBUTES);
+            }
+        }
+        // ammonia panics if a tag is both allowed and has its content removed
+        builder.add_tags(
+            self.extra_tags
+                .iter()
+                .map(String::as_str)
+                .filter(|t| !matches!(*t, "script" | "style")),
+        );
+        builder.rm_tags(self.removed_tags.iter().map(String::as_str));
+        for (tag, attrs) in self.extra_attributes.iter() {
+            let attrs = attrs.iter().map(String::as_str);
+            if tag == "*" {
+                builder.add_generic_attributes(attrs);
+            } else {
+                builder.add_tag_attributes(tag.as_str(), attrs);
+            }
+        }
+        builder
+    }
+
+    /// Render markdown the same way everywhere it's shown, then sanitize the resulting HTML
+    pub fn render(&self, md: &str) -> Result<String> {
+        let options = Options {
+            parse: ParseOptions {
+                constructs: Constructs {
+                    math_text: true,
+                    math_flow: true,
+                    ..Constructs::gfm()
+                },
+                ..ParseOptions::gfm()
+            },
+            compile: CompileOptions {
+                // Sanitizing after compiling is what keeps this safe
+                allow_dangerous_html: true,
+                ..CompileOptions::gfm()
+            },
+        };
+        let rendered = markdown::to_html_with_options(md, &options)
+            .map_err(|e| anyhow!("Failed to render markdown: {:?}", e))?;
+        let rendered = if self.server_side_math {
+            render_math(&rendered)
+        } else {
+            rendered
+        };
+        Ok(self.sanitizer().clean(&rendered).to_string())
+    }
+}
@@ -19,15 +19,27 @@ impl Display for MsgType {
     }
 }
 
+/// A message shown after a redirect. `msg` is English and translated when the redirect is
+/// sent, so anything that changes goes in a `{placeholder}` filled in from `args`
 #[derive(Debug, Clone)]
 pub struct Message {
     pub msg: String,
     pub msg_type: MsgType,
+    pub args: Vec<(String, String)>,
 }
 
 impl Message {
     pub fn new(msg: String, msg_type: MsgType) -> Self {
-        Self { msg, msg_type }
+        Self {
+            msg,
+            msg_type,
+            args: vec![],
+        }
+    }
+
+    pub fn arg(mut self, name: &str, value: impl ToString) -> Self {
+        self.args.push((name.to_string(), value.to_string()));
+        self
     }
 
     pub fn info(msg: &str) -> Self {
@@ -43,15 +55,19 @@ impl Message {
     }
 
     pub fn to(&self, url: &str) -> Redirect {
-        let encoded = urlencoding::encode(&self.msg).to_string();
-        let formatted = format!("{url}?msg={encoded}&msg_type={}", self.msg_type);
-        Redirect::to(formatted)
+        self.to_with_params(url, vec![])
     }
 
     pub fn to_with_params(&self, url: &str, params: Vec<(&str, &str)>) -> Redirect {
         let encoded = urlencoding::encode(&self.msg).to_string();
         let mut formatted = format!("{url}?msg={encoded}&msg_type={}", self.msg_type);
-        for (key, value) in params {
+        let args = self
+            .args
+            .iter()
+            .map(|(name, value)| (format!("msg_arg_{name}"), value.as_str()))
+            .collect::<Vec<_>>();
+        let args = args.iter().map(|(k, v)| (k.as_str(), *v));
+        for (key, value) in args.chain(params) {
             let encoded_key = urlencoding::encode(key);
             let encoded_value = urlencoding::encode(value);
             formatted.push_str(&format!("&{encoded_key}={encoded_value}"));
@@ -0,0 +1,40 @@
+use rocket::{get, post};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    messages::Message,
+    FormResponse, ResultResponse,
+};
+
+use super::Page;
+
+#[get("/<slug>/delete")]
+pub async fn delete_page_get(
+    slug: &str,
+    mut db: DbConnection,
+    _token: &CsrfToken,
+    user: &User,
+    _admin: &Admin,
+) -> ResultResponse<Template> {
+    let page = Page::get_or_404(&mut db, slug).await?;
+    let ctx = context_with_base_authed!(user, page);
+    Ok(Template::render("pages/delete", ctx))
+}
+
+#[post("/<slug>/delete")]
+pub async fn delete_page_post(
+    slug: &str,
+    mut db: DbConnection,
+    _token: &VerifyCsrfToken,
+    _admin: &Admin,
+) -> FormResponse {
+    let page = Page::get_or_404(&mut db, slug).await?;
+    page.delete(&mut db).await?;
+    Ok(Message::success("Page deleted").to("/pages"))
+}
@@ -0,0 +1,63 @@
+use rocket::{
+    form::{Contextual, Form},
+    get, post,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    template::FormTemplateObject,
+};
+
+use super::{slug_taken_error, Page, PageForm, PageFormTemplate};
+
+#[get("/<slug>/edit")]
+pub async fn edit_page_get(
+    user: &User,
+    mut db: DbConnection,
+    slug: &str,
+    _token: &CsrfToken,
+    _admin: &Admin,
+) -> ResultResponse<Template> {
+    let page = Page::get_or_404(&mut db, slug).await?;
+    let form_template = PageFormTemplate { page: Some(&page) };
+    let form = FormTemplateObject::get(form_template);
+    Ok(Template::render(
+        "pages/edit",
+        context_with_base_authed!(user, form, page),
+    ))
+}
+
+#[post("/<slug>/edit", data = "<form>")]
+pub async fn edit_page_post(
+    slug: &str,
+    user: &User,
+    mut form: Form<Contextual<'_, PageForm<'_>>>,
+    _token: &VerifyCsrfToken,
+    _admin: &Admin,
+    mut db: DbConnection,
+) -> FormResponse {
+    let mut page = Page::get_or_404(&mut db, slug).await?;
+    if let Some(ref value) = form.value {
+        if Page::slug_taken(&mut db, value.slug, page.id).await? {
+            form.context.push_error(slug_taken_error());
+        } else {
+            page.slug = value.slug.to_string();
+            page.title = value.title.to_string();
+            page.content = value.content.to_string();
+            page.update(&mut db).await?;
+            return Ok(Message::success("Page Updated").to(&format!("/pages/{}", page.slug)));
+        }
+    }
+    let form_template = PageFormTemplate { page: None };
+    let form = FormTemplateObject::from_rocket_context(form_template, &form.context);
+    let ctx = context_with_base_authed!(user, form, page);
+    Err(Template::render("pages/edit", ctx).into())
+}
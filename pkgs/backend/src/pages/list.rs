@@ -0,0 +1,22 @@
+use rocket::get;
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+};
+
+use super::Page;
+
+#[get("/")]
+pub async fn pages_list(
+    user: &User,
+    _admin: &Admin,
+    mut db: DbConnection,
+) -> ResultResponse<Template> {
+    let pages = Page::list(&mut db).await?;
+    let ctx = context_with_base_authed!(user, pages);
+    Ok(Template::render("pages/list", ctx))
+}
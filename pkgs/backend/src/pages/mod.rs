@@ -0,0 +1,174 @@
+#![allow(clippy::blocks_in_conditions)] // Needed for the derive of FromForm, rocket is weird
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use rocket::{fairing::AdHoc, form, http::Status, routes, FromForm};
+use serde::Serialize;
+
+use crate::{db::DbPoolConnection, error::prelude::*, template::TemplatedForm};
+
+mod delete;
+mod edit;
+mod list;
+mod new;
+mod view;
+
+/// A markdown page admins write on the site, e.g. rules, an FAQ or sponsors,
+/// linked from the navbar or footer with `branding.navbar_pages` and `branding.footer_items`
+#[derive(Serialize, Clone)]
+pub struct Page {
+    pub id: i64,
+    pub slug: String,
+    pub title: String,
+    pub content: String,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl Page {
+    pub async fn list(db: &mut DbPoolConnection) -> Result<Vec<Self>> {
+        sqlx::query_as!(Page, "SELECT * FROM page ORDER BY title ASC")
+            .fetch_all(&mut **db)
+            .await
+            .context("Error fetching pages")
+    }
+
+    pub async fn get(db: &mut DbPoolConnection, slug: &str) -> Result<Option<Self>> {
+        sqlx::query_as!(Page, "SELECT * FROM page WHERE slug = ?", slug)
+            .fetch_optional(&mut **db)
+            .await
+            .with_context(|| format!("Error fetching page {slug}"))
+    }
+
+    pub async fn get_or_404(db: &mut DbPoolConnection, slug: &str) -> ResultResponse<Self> {
+        Self::get(db, slug).await?.ok_or(Status::NotFound.into())
+    }
+
+    /// Whether another page than `except_id` already uses `slug`
+    async fn slug_taken(db: &mut DbPoolConnection, slug: &str, except_id: i64) -> Result<bool> {
+        sqlx::query!(
+            "SELECT id FROM page WHERE slug = ? AND id != ?",
+            slug,
+            except_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .map(|r| r.is_some())
+        .context("Error checking page slug")
+    }
+
+    pub async fn insert(
+        db: &mut DbPoolConnection,
+        slug: &str,
+        title: &str,
+        content: &str,
+    ) -> Result<Self> {
+        sqlx::query_as!(
+            Page,
+            "INSERT INTO page (slug, title, content) VALUES (?, ?, ?) RETURNING *",
+            slug,
+            title,
+            content
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Error inserting page")
+    }
+
+    pub async fn update(&self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!(
+            "UPDATE page SET slug = ?, title = ?, content = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            self.slug,
+            self.title,
+            self.content,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Error updating page with id: {}", self.id))
+    }
+
+    pub async fn delete(self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!("DELETE FROM page WHERE id = ?", self.id)
+            .execute(&mut **db)
+            .await
+            .map(|_| ())
+            .with_context(|| format!("Error deleting page {}", self.slug))
+    }
+}
+
+struct PageFormTemplate<'r> {
+    page: Option<&'r Page>,
+}
+
+impl<'r> TemplatedForm for PageFormTemplate<'r> {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        if let Some(page) = self.page {
+            HashMap::from_iter([
+                ("slug".to_string(), page.slug.clone()),
+                ("title".to_string(), page.title.clone()),
+                ("content".to_string(), page.content.clone()),
+            ])
+        } else {
+            HashMap::from_iter([
+                ("slug".to_string(), "".to_string()),
+                ("title".to_string(), "".to_string()),
+                ("content".to_string(), "".to_string()),
+            ])
+        }
+    }
+}
+
+/// Slugs that would clash with the routes under `/pages`
+const RESERVED_SLUGS: [&str; 1] = ["new"];
+
+#[inline]
+fn valid_slug<'e>(slug: &str) -> Result<(), rocket::form::Errors<'e>> {
+    if !slug
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        Err(
+            form::Error::validation("Only lowercase letters, numbers and dashes are allowed")
+                .into(),
+        )
+    } else if RESERVED_SLUGS.contains(&slug) {
+        Err(form::Error::validation("This slug is reserved").into())
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(FromForm)]
+struct PageForm<'r> {
+    #[field(validate = len(1..=50))]
+    #[field(validate = valid_slug())]
+    slug: &'r str,
+    #[field(validate = len(1..=100))]
+    title: &'r str,
+    content: &'r str,
+}
+
+fn slug_taken_error<'e>() -> form::Error<'e> {
+    form::Error::validation("Another page already uses this slug").with_name("slug")
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Pages App", |rocket| async {
+        rocket.mount(
+            "/pages",
+            routes![
+                list::pages_list,
+                new::new_page_get,
+                new::new_page_post,
+                edit::edit_page_get,
+                edit::edit_page_post,
+                delete::delete_page_get,
+                delete::delete_page_post,
+                view::view_page,
+            ],
+        )
+    })
+}
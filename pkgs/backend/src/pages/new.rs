@@ -0,0 +1,49 @@
+use rocket::{
+    form::{Contextual, Form},
+    get, post,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    template::FormTemplateObject,
+};
+
+use super::{slug_taken_error, Page, PageForm, PageFormTemplate};
+
+#[get("/new")]
+pub async fn new_page_get(user: &User, _admin: &Admin, _token: &CsrfToken) -> Template {
+    let form_template = PageFormTemplate { page: None };
+    let form = FormTemplateObject::get(form_template);
+    let ctx = context_with_base_authed!(user, form);
+    Template::render("pages/new", ctx)
+}
+
+#[post("/new", data = "<form>")]
+pub async fn new_page_post(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    mut form: Form<Contextual<'_, PageForm<'_>>>,
+) -> FormResponse {
+    if let Some(ref value) = form.value {
+        if Page::slug_taken(&mut db, value.slug, 0).await? {
+            form.context.push_error(slug_taken_error());
+        } else {
+            let page = Page::insert(&mut db, value.slug, value.title, value.content).await?;
+            return Ok(Message::success("Page Created").to(&format!("/pages/{}", page.slug)));
+        }
+    }
+    let form_template = PageFormTemplate { page: None };
+    let form = FormTemplateObject::from_rocket_context(form_template, &form.context);
+    let ctx = context_with_base_authed!(user, form);
+    Err(Template::render("pages/new", ctx).into())
+}
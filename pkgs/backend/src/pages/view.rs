@@ -0,0 +1,23 @@
+use rocket::get;
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base,
+    db::DbConnection,
+    error::prelude::*,
+};
+
+use super::Page;
+
+#[get("/<slug>")]
+pub async fn view_page(
+    mut db: DbConnection,
+    slug: &str,
+    user: Option<&User>,
+    admin: Option<&Admin>,
+) -> ResultResponse<Template> {
+    let page = Page::get_or_404(&mut db, slug).await?;
+    let ctx = context_with_base!(user, page, is_admin: admin.is_some());
+    Ok(Template::render("pages/view", ctx))
+}
@@ -0,0 +1,45 @@
+//! Turning documents into PDFs with a command from the config, we don't render PDFs ourselves
+
+use std::{process::Stdio, time::Duration};
+
+use tokio::io::AsyncWriteExt;
+
+use crate::error::prelude::*;
+
+const CONVERT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run `command` with `input` on stdin and return the PDF it wrote to stdout
+pub async fn convert(command: &[String], input: String) -> Result<Vec<u8>> {
+    let (program, args) = command
+        .split_first()
+        .context("PDF converter command is empty")?;
+    let mut child = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Couldn't start PDF converter {program}"))?;
+    let mut stdin = child.stdin.take().context("PDF converter has no stdin")?;
+    let writer = tokio::spawn(async move {
+        stdin.write_all(input.as_bytes()).await?;
+        stdin.shutdown().await
+    });
+    let output = tokio::time::timeout(CONVERT_TIMEOUT, child.wait_with_output())
+        .await
+        .context("PDF converter timed out")?
+        .context("PDF converter failed")?;
+    writer
+        .await
+        .context("Couldn't write to PDF converter")?
+        .context("Couldn't write to PDF converter")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "PDF converter exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,91 @@
+//! Just enough of IPP (RFC 8011) to hand a plain text document to a printer or CUPS server
+
+use crate::error::prelude::*;
+
+const VERSION: [u8; 2] = [1, 1];
+const PRINT_JOB: u16 = 0x0002;
+
+const OPERATION_ATTRIBUTES: u8 = 0x01;
+const END_OF_ATTRIBUTES: u8 = 0x03;
+
+const NAME: u8 = 0x42;
+const URI: u8 = 0x45;
+const CHARSET: u8 = 0x47;
+const NATURAL_LANGUAGE: u8 = 0x48;
+const MIME_MEDIA_TYPE: u8 = 0x49;
+
+const DEFAULT_PORT: u16 = 631;
+
+fn attribute(buf: &mut Vec<u8>, tag: u8, name: &str, value: &str) {
+    buf.push(tag);
+    buf.extend((name.len() as u16).to_be_bytes());
+    buf.extend(name.as_bytes());
+    buf.extend((value.len() as u16).to_be_bytes());
+    buf.extend(value.as_bytes());
+}
+
+/// IPP goes over HTTP, `ipp://host/path` is posted to `http://host:631/path`
+fn http_url(printer_uri: &str) -> Result<String> {
+    let (scheme, rest) = if let Some(rest) = printer_uri.strip_prefix("ipp://") {
+        ("http", rest)
+    } else if let Some(rest) = printer_uri.strip_prefix("ipps://") {
+        ("https", rest)
+    } else {
+        return Err(anyhow!("Printer URI must start with ipp:// or ipps://"));
+    };
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    if host.contains(':') {
+        Ok(format!("{scheme}://{host}/{path}"))
+    } else {
+        Ok(format!("{scheme}://{host}:{DEFAULT_PORT}/{path}"))
+    }
+}
+
+pub async fn print_job(
+    printer_uri: &str,
+    job_name: &str,
+    user_name: &str,
+    document: &str,
+) -> Result {
+    let mut body = Vec::with_capacity(document.len() + 256);
+    body.extend(VERSION);
+    body.extend(PRINT_JOB.to_be_bytes());
+    body.extend(1_u32.to_be_bytes()); // Request ID, we only ever send one per connection
+    body.push(OPERATION_ATTRIBUTES);
+    attribute(&mut body, CHARSET, "attributes-charset", "utf-8");
+    attribute(
+        &mut body,
+        NATURAL_LANGUAGE,
+        "attributes-natural-language",
+        "en",
+    );
+    attribute(&mut body, URI, "printer-uri", printer_uri);
+    attribute(&mut body, NAME, "requesting-user-name", user_name);
+    attribute(&mut body, NAME, "job-name", job_name);
+    attribute(&mut body, MIME_MEDIA_TYPE, "document-format", "text/plain");
+    body.push(END_OF_ATTRIBUTES);
+    body.extend(document.as_bytes());
+
+    let res = reqwest::Client::new()
+        .post(http_url(printer_uri)?)
+        .header("Content-Type", "application/ipp")
+        .body(body)
+        .send()
+        .await
+        .context("Couldn't reach the printer")?
+        .error_for_status()
+        .context("Printer returned an HTTP error")?
+        .bytes()
+        .await
+        .context("Couldn't read the printer's response")?;
+
+    // Status code follows the version, anything from 0x0100 up is an error
+    match res.get(2..4) {
+        Some(&[high, low]) if u16::from_be_bytes([high, low]) < 0x0100 => Ok(()),
+        Some(&[high, low]) => Err(anyhow!(
+            "Printer refused the job with status {:#06x}",
+            u16::from_be_bytes([high, low])
+        )),
+        _ => Err(anyhow!("Printer sent back an invalid response")),
+    }
+}
@@ -0,0 +1,258 @@
+use chrono::NaiveDateTime;
+use rocket::{fairing::AdHoc, routes};
+use sqlx::{encode::IsNull, Decode, Encode, Type};
+
+use crate::{
+    auth::users::User,
+    contests::{Contest, Participant},
+    db::DbPoolConnection,
+    error::prelude::*,
+};
+
+mod ipp;
+mod queue;
+mod submit;
+
+fn default_max_length() -> usize {
+    20_000
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PrintingConfig {
+    /// IPP URI of the printer to send jobs to, e.g. `ipp://localhost:631/printers/lab`.
+    /// When unset jobs only show up in the judges' queue for them to print by hand.
+    pub printer_uri: Option<String>,
+    /// Max length of a print job in characters
+    #[serde(default = "default_max_length")]
+    pub max_length: usize,
+}
+
+impl Default for PrintingConfig {
+    fn default() -> Self {
+        Self {
+            printer_uri: None,
+            max_length: default_max_length(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Default)]
+pub enum PrintStatus {
+    /// Waiting for a judge, no printer is configured
+    #[default]
+    Queued,
+    /// Taken by the printer, waiting to be delivered
+    Sent,
+    /// The printer didn't take it, see [`PrintJob::error`]
+    Failed,
+    /// Handed to the participant
+    Delivered,
+}
+
+impl From<String> for PrintStatus {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "Sent" => Self::Sent,
+            "Failed" => Self::Failed,
+            "Delivered" => Self::Delivered,
+            _ => Self::Queued,
+        }
+    }
+}
+
+impl From<PrintStatus> for String {
+    fn from(s: PrintStatus) -> Self {
+        format!("{:?}", s)
+    }
+}
+
+impl Type<sqlx::Sqlite> for PrintStatus {
+    fn type_info() -> <sqlx::Sqlite as sqlx::Database>::TypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl Encode<'_, sqlx::Sqlite> for PrintStatus {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Sqlite as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> IsNull {
+        let val = format!("{:?}", self);
+        <std::string::String as Encode<'_, sqlx::Sqlite>>::encode_by_ref(&val, buf)
+    }
+}
+
+impl Decode<'_, sqlx::Sqlite> for PrintStatus {
+    fn decode(
+        value: <sqlx::Sqlite as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        Ok(s.into())
+    }
+}
+
+/// Something a participant asked to have printed during an onsite contest
+#[derive(Serialize, Debug, Clone)]
+pub struct PrintJob {
+    pub id: i64,
+    pub contest_id: i64,
+    pub user_id: i64,
+    pub title: String,
+    pub content: String,
+    pub status: PrintStatus,
+    pub error: Option<String>,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+}
+
+impl PrintJob {
+    pub async fn insert(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        user_id: i64,
+        title: &str,
+        content: &str,
+    ) -> Result<Self> {
+        sqlx::query_as!(
+            PrintJob,
+            "INSERT INTO print_job (contest_id, user_id, title, content) VALUES (?, ?, ?, ?) RETURNING *",
+            contest_id,
+            user_id,
+            title,
+            content
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Failed to insert print job")
+    }
+
+    pub async fn get(db: &mut DbPoolConnection, contest_id: i64, id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            PrintJob,
+            "SELECT * FROM print_job WHERE id = ? AND contest_id = ?",
+            id,
+            contest_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get print job {id}"))
+    }
+
+    /// Every job in a contest, oldest first so the queue is handled in order
+    pub async fn list(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            PrintJob,
+            "SELECT * FROM print_job WHERE contest_id = ? ORDER BY created_at ASC",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to list print jobs for contest {contest_id}"))
+    }
+
+    pub async fn list_for_user(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        user_id: i64,
+    ) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            PrintJob,
+            "SELECT * FROM print_job WHERE contest_id = ? AND user_id = ? ORDER BY created_at DESC",
+            contest_id,
+            user_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to list print jobs for user {user_id}"))
+    }
+
+    pub async fn set_status(
+        &mut self,
+        db: &mut DbPoolConnection,
+        status: PrintStatus,
+        error: Option<String>,
+    ) -> Result {
+        sqlx::query!(
+            "UPDATE print_job SET status = ?, error = ? WHERE id = ?",
+            status,
+            error,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to update print job {}", self.id))?;
+        self.status = status;
+        self.error = error;
+        Ok(())
+    }
+
+    /// The text that actually gets printed, with a banner so judges know who to hand it to
+    pub fn document(&self, contest: &Contest, user: &User, participant: &Participant) -> String {
+        let mut banner = vec![
+            format!("Contest: {}", contest.name),
+            format!(
+                "Participant: {} (#{})",
+                user.display_name(),
+                participant.p_id
+            ),
+        ];
+        for (label, answer) in participant.registration_answers(contest) {
+            banner.push(format!("{label}: {answer}"));
+        }
+        banner.push(format!("Title: {}", self.title));
+        banner.push(format!(
+            "Submitted: {} UTC",
+            self.created_at.format("%F %T")
+        ));
+        let rule = "=".repeat(banner.iter().map(|l| l.len()).max().unwrap_or(0));
+        format!("{rule}\n{}\n{rule}\n\n{}", banner.join("\n"), self.content)
+    }
+
+    /// Send the job to the configured printer, recording whether it went through.
+    /// Without a printer the job just stays queued for the judges.
+    pub async fn send(
+        &mut self,
+        db: &mut DbPoolConnection,
+        config: &PrintingConfig,
+        contest: &Contest,
+        user: &User,
+        participant: &Participant,
+    ) -> Result {
+        let Some(printer_uri) = config.printer_uri.as_deref() else {
+            return Ok(());
+        };
+        let job_name = format!("{} - {}", user.display_name(), self.title);
+        let document = self.document(contest, user, participant);
+        match ipp::print_job(printer_uri, &job_name, user.display_name(), &document).await {
+            Ok(()) => self.set_status(db, PrintStatus::Sent, None).await,
+            Err(e) => {
+                warn!(
+                    "Couldn't send print job {} to the printer: {:?}",
+                    self.id, e
+                );
+                self.set_status(db, PrintStatus::Failed, Some(format!("{e:#}")))
+                    .await
+            }
+        }
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Printing", |rocket| async {
+        let config = rocket
+            .figment()
+            .extract_inner::<PrintingConfig>("printing")
+            .unwrap_or_default();
+        rocket.manage(config).mount(
+            "/contests",
+            routes![
+                submit::print_get,
+                submit::print_post,
+                queue::print_queue,
+                queue::print_job_text,
+                queue::mark_delivered,
+                queue::resend,
+            ],
+        )
+    })
+}
@@ -0,0 +1,143 @@
+use chrono::TimeZone;
+use rocket::{get, http::Status, post, response::Redirect, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::{Contest, Participant},
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+    times::{format_datetime_human_readable, ClientTimeZone},
+};
+
+use super::{PrintJob, PrintStatus, PrintingConfig};
+
+#[derive(Serialize)]
+struct Row {
+    job: PrintJob,
+    user: User,
+    /// Answers to the registration fields, as `Label: answer` separated by semicolons, usually where to find them
+    registration: String,
+    submitted: String,
+}
+
+/// The job along with who sent it, 404ing if either is gone
+async fn get_job(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    job_id: i64,
+) -> ResultResponse<(PrintJob, User, Participant)> {
+    let job = PrintJob::get(db, contest_id, job_id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    let user = User::get(db, job.user_id).await?.ok_or(Status::NotFound)?;
+    let participant = Participant::get(db, contest_id, job.user_id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    Ok((job, user, participant))
+}
+
+#[get("/<contest_id>/admin/print")]
+pub async fn print_queue(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    tz: ClientTimeZone,
+    config: &State<PrintingConfig>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let tz = tz.timezone();
+    let mut rows = Vec::new();
+    for job in PrintJob::list(&mut db, contest_id).await? {
+        let Some(job_user) = User::get(&mut db, job.user_id).await? else {
+            continue;
+        };
+        let registration = Participant::get(&mut db, contest_id, job.user_id)
+            .await?
+            .map(|p| {
+                p.registration_answers(&contest)
+                    .into_iter()
+                    .map(|(label, answer)| format!("{label}: {answer}"))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            })
+            .unwrap_or_default();
+        let submitted = format_datetime_human_readable(tz.from_utc_datetime(&job.created_at));
+        rows.push(Row {
+            job,
+            user: job_user,
+            registration,
+            submitted,
+        });
+    }
+    // Anything still waiting on a judge goes first
+    rows.sort_by_key(|r| r.job.status == PrintStatus::Delivered);
+    let has_printer = config.printer_uri.is_some();
+    let ctx = context_with_base_authed!(user, contest, rows, has_printer);
+    Ok(Template::render("contests/admin/print", ctx))
+}
+
+/// The job as it would be printed, for judges printing from their browser
+#[get("/<contest_id>/admin/print/<job_id>/text")]
+pub async fn print_job_text(
+    mut db: DbConnection,
+    contest_id: i64,
+    job_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+) -> ResultResponse<String> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let (job, job_user, participant) = get_job(&mut db, contest_id, job_id).await?;
+    Ok(job.document(&contest, &job_user, &participant))
+}
+
+#[post("/<contest_id>/admin/print/<job_id>/delivered")]
+pub async fn mark_delivered(
+    mut db: DbConnection,
+    contest_id: i64,
+    job_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let (mut job, _, _) = get_job(&mut db, contest_id, job_id).await?;
+    job.set_status(&mut db, PrintStatus::Delivered, None)
+        .await?;
+    Ok(Message::success("Marked as delivered").to(&format!("/contests/{contest_id}/admin/print")))
+}
+
+#[post("/<contest_id>/admin/print/<job_id>/resend")]
+pub async fn resend(
+    mut db: DbConnection,
+    contest_id: i64,
+    job_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    config: &State<PrintingConfig>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    if config.printer_uri.is_none() {
+        return Err(Status::NotFound.into());
+    }
+    let (mut job, job_user, participant) = get_job(&mut db, contest_id, job_id).await?;
+    job.send(&mut db, config, &contest, &job_user, &participant)
+        .await?;
+    let url = format!("/contests/{contest_id}/admin/print");
+    if job.status == PrintStatus::Failed {
+        Ok(Message::error("The printer still couldn't take it").to(&url))
+    } else {
+        Ok(Message::success("Sent to the printer").to(&url))
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use chrono::TimeZone;
+use rocket::{
+    form::{Contextual, Error, Form},
+    get,
+    http::Status,
+    post, FromForm, State,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::User,
+    },
+    contests::{Contest, Participant},
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+    template::{FormTemplateObject, TemplatedForm},
+    times::{format_datetime_human_readable, ClientTimeZone},
+};
+
+use super::{PrintJob, PrintStatus, PrintingConfig};
+
+#[derive(FromForm)]
+pub struct PrintForm<'r> {
+    #[field(validate = len(1..=100))]
+    title: &'r str,
+    #[field(validate = len(1..))]
+    content: &'r str,
+}
+
+struct PrintFormTemplate;
+
+impl TemplatedForm for PrintFormTemplate {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        HashMap::from_iter([
+            ("title".to_string(), "".to_string()),
+            ("content".to_string(), "".to_string()),
+        ])
+    }
+}
+
+/// Printing is only there for participants of contests that have it turned on,
+/// anyone else gets a 404 so the page doesn't show up for online contests
+async fn get_contest_and_participant(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    user: &User,
+) -> ResultResponse<(Contest, Participant)> {
+    let contest = Contest::get_or_404(db, contest_id).await?;
+    if !contest.printing_enabled {
+        return Err(Status::NotFound.into());
+    }
+    let participant = Participant::get(db, contest_id, user.id)
+        .await?
+        .filter(|p| !p.pending)
+        .ok_or(Status::NotFound)?;
+    Ok((contest, participant))
+}
+
+fn submitted_times(jobs: &[PrintJob], tz: &ClientTimeZone) -> Vec<String> {
+    let tz = tz.timezone();
+    jobs.iter()
+        .map(|j| format_datetime_human_readable(tz.from_utc_datetime(&j.created_at)))
+        .collect()
+}
+
+#[get("/<contest_id>/print")]
+pub async fn print_get(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    tz: ClientTimeZone,
+    config: &State<PrintingConfig>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, participant) = get_contest_and_participant(&mut db, contest_id, user).await?;
+    let jobs = PrintJob::list_for_user(&mut db, contest_id, user.id).await?;
+    let times = submitted_times(&jobs, &tz);
+    let can_print = participant.is_judge || contest.is_running_for(Some(&participant));
+    let form = FormTemplateObject::get(PrintFormTemplate);
+    let ctx = context_with_base_authed!(user, contest, jobs, times, can_print, form, max_length: config.max_length);
+    Ok(Template::render("contests/print", ctx))
+}
+
+#[post("/<contest_id>/print", data = "<form>")]
+pub async fn print_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    user: &User,
+    tz: ClientTimeZone,
+    config: &State<PrintingConfig>,
+    mut form: Form<Contextual<'_, PrintForm<'_>>>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (contest, participant) = get_contest_and_participant(&mut db, contest_id, user).await?;
+    let url = format!("/contests/{contest_id}/print");
+    if !participant.is_judge && !contest.is_running_for(Some(&participant)) {
+        return Ok(Message::error("You can only print while the contest is running").to(&url));
+    }
+    if let Some(ref value) = form.value {
+        if value.content.chars().count() > config.max_length {
+            let err =
+                Error::validation(format!("Must be at most {} characters", config.max_length))
+                    .with_name("content");
+            form.context.push_error(err);
+        } else {
+            let mut job =
+                PrintJob::insert(&mut db, contest_id, user.id, value.title, value.content).await?;
+            job.send(&mut db, config, &contest, user, &participant)
+                .await?;
+            let msg = match job.status {
+                PrintStatus::Sent => {
+                    Message::success("Sent to the printer, a judge will bring it to you")
+                }
+                PrintStatus::Failed => Message::info(
+                    "The printer couldn't take it, a judge will print it and bring it to you",
+                ),
+                _ => Message::success("Added to the print queue, a judge will bring it to you"),
+            };
+            return Ok(msg.to(&url));
+        }
+    }
+    let jobs = PrintJob::list_for_user(&mut db, contest_id, user.id).await?;
+    let times = submitted_times(&jobs, &tz);
+    let form = FormTemplateObject::from_rocket_context(PrintFormTemplate, &form.context);
+    let ctx = context_with_base_authed!(user, contest, jobs, times, can_print: true, form, max_length: config.max_length);
+    Err(Template::render("contests/print", ctx).into())
+}
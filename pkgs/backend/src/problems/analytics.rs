@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    contests::{clock_time_between, Contest},
+    db::DbPoolConnection,
+    error::prelude::*,
+};
+
+use super::ProblemDifficulty;
+
+/// How hard a problem turned out to be, for calibrating future problem sets.
+/// Only participants who aren't judges count.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ProblemAnalytics {
+    pub participants: i64,
+    pub solvers: i64,
+    /// Percent of participants who solved it
+    pub solve_rate: f64,
+    /// Contest time until the median solve, in seconds
+    pub median_secs: Option<i64>,
+    /// Wrong submissions from participants who never solved it
+    pub wasted_attempts: i64,
+    /// The difficulty the solve rate suggests, to compare against the one the author picked
+    pub observed_difficulty: ProblemDifficulty,
+}
+
+struct CompletionRow {
+    problem_id: i64,
+    completed_at: Option<NaiveDateTime>,
+    number_wrong: i64,
+}
+
+impl ProblemAnalytics {
+    /// Analytics for every problem in the contest that anyone attempted, keyed by problem id
+    pub async fn list(db: &mut DbPoolConnection, contest: &Contest) -> Result<HashMap<i64, Self>> {
+        let participants = sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM participant WHERE contest_id = ? AND NOT is_judge AND NOT is_spectator AND NOT pending",
+            contest.id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Failed to count participants")?
+        .count;
+        let rows = sqlx::query_as!(
+            CompletionRow,
+            "SELECT problem_completion.problem_id, problem_completion.completed_at, problem_completion.number_wrong
+            FROM problem_completion JOIN participant ON problem_completion.participant_id = participant.p_id
+            WHERE participant.contest_id = ? AND NOT participant.is_judge AND NOT participant.is_spectator AND NOT participant.pending",
+            contest.id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get completions for contest {}", contest.id))?;
+
+        let pauses = contest.pauses();
+        let mut solve_times = HashMap::<i64, Vec<i64>>::new();
+        let mut analytics = HashMap::<i64, Self>::new();
+        for row in rows {
+            let entry = analytics.entry(row.problem_id).or_insert_with(|| Self {
+                participants,
+                ..Default::default()
+            });
+            match row.completed_at {
+                Some(completed_at) => {
+                    entry.solvers += 1;
+                    let secs = clock_time_between(&pauses, contest.start_time, completed_at)
+                        .num_seconds()
+                        .max(0);
+                    solve_times.entry(row.problem_id).or_default().push(secs);
+                }
+                None => entry.wasted_attempts += row.number_wrong,
+            }
+        }
+        for (problem_id, entry) in analytics.iter_mut() {
+            if participants > 0 {
+                entry.solve_rate = entry.solvers as f64 / participants as f64 * 100.0;
+            }
+            if let Some(times) = solve_times.get_mut(problem_id) {
+                times.sort_unstable();
+                entry.median_secs = Some(times[times.len() / 2]);
+            }
+            entry.observed_difficulty = match entry.solve_rate {
+                r if r >= 60.0 => ProblemDifficulty::Easy,
+                r if r >= 25.0 => ProblemDifficulty::Medium,
+                _ => ProblemDifficulty::Hard,
+            };
+        }
+        Ok(analytics)
+    }
+
+    pub async fn get(
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+        problem_id: i64,
+    ) -> Result<Option<Self>> {
+        Ok(Self::list(db, contest).await?.remove(&problem_id))
+    }
+}
@@ -0,0 +1,139 @@
+use std::{str::FromStr, sync::LazyLock};
+
+use color_art::Color;
+use markdown::{mdast::Node, Constructs, ParseOptions};
+use regex::Regex;
+
+use crate::branding::ParsedColorConfig;
+
+/// Code lines longer than this need horizontal scrolling on most screens
+const MAX_CODE_LINE_LENGTH: usize = 80;
+/// WCAG AA minimum for normal sized text
+const MIN_CONTRAST_RATIO: f64 = 4.5;
+/// Don't flood the message shown after saving
+const MAX_WARNINGS: usize = 5;
+
+static IMG_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<img\b[^>]*>").unwrap());
+static ALT_ATTR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\balt\s*=\s*("[^"]*\S[^"]*"|'[^']*\S[^']*'|[^\s>"']+)"#).unwrap()
+});
+static STYLE_ATTR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\bstyle\s*=\s*("[^"]*"|'[^']*')"#).unwrap());
+static STYLE_PROP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:^|;)\s*(color|background-color|background)\s*:\s*([^;]+)").unwrap()
+});
+static FONT_COLOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)<font\b[^>]*\bcolor\s*=\s*["']?([^"'\s>]+)"#).unwrap());
+
+/// Check a problem statement for common accessibility problems, returning a warning for each one found.
+/// These are only suggestions for the author and never stop the problem from being saved.
+pub fn audit_statement(description: &str, colors: &ParsedColorConfig) -> Vec<String> {
+    let options = ParseOptions {
+        constructs: Constructs {
+            math_text: true,
+            math_flow: true,
+            ..Constructs::gfm()
+        },
+        ..ParseOptions::gfm()
+    };
+    let mut warnings = Vec::new();
+    if let Ok(root) = markdown::to_mdast(description, &options) {
+        audit_node(&root, colors, &mut warnings);
+    }
+    warnings.dedup();
+    if warnings.len() > MAX_WARNINGS {
+        let extra = warnings.len() - MAX_WARNINGS;
+        warnings.truncate(MAX_WARNINGS);
+        warnings.push(format!("and {extra} more"));
+    }
+    warnings
+}
+
+fn audit_node(node: &Node, colors: &ParsedColorConfig, warnings: &mut Vec<String>) {
+    match node {
+        Node::Image(image) if image.alt.trim().is_empty() => {
+            warnings.push(format!("image \"{}\" has no alt text", image.url));
+        }
+        Node::ImageReference(image) if image.alt.trim().is_empty() => {
+            warnings.push(format!("image \"{}\" has no alt text", image.identifier));
+        }
+        Node::Code(code) => {
+            let longest = code
+                .value
+                .lines()
+                .map(|l| l.chars().count())
+                .max()
+                .unwrap_or(0);
+            if longest > MAX_CODE_LINE_LENGTH {
+                warnings.push(format!(
+                    "a code block has a line {longest} characters long, try keeping lines under {MAX_CODE_LINE_LENGTH}"
+                ));
+            }
+        }
+        Node::Html(html) => audit_html(&html.value, colors, warnings),
+        _ => {}
+    }
+    if let Some(children) = node.children() {
+        for child in children {
+            audit_node(child, colors, warnings);
+        }
+    }
+}
+
+fn audit_html(html: &str, colors: &ParsedColorConfig, warnings: &mut Vec<String>) {
+    for img in IMG_TAG.find_iter(html) {
+        if !ALT_ATTR.is_match(img.as_str()) {
+            warnings.push("an <img> tag has no alt text".to_string());
+        }
+    }
+
+    for style in STYLE_ATTR.captures_iter(html) {
+        let style = style[1].trim_matches(['"', '\'']);
+        let mut foreground = None;
+        let mut background = None;
+        for prop in STYLE_PROP.captures_iter(style) {
+            let color = Color::from_str(prop[2].trim()).ok();
+            if prop[1].eq_ignore_ascii_case("color") {
+                foreground = color;
+            } else {
+                background = background.or(color);
+            }
+        }
+        match (foreground, background) {
+            (Some(fg), Some(bg)) => check_contrast(&fg, &bg, "its background", warnings),
+            (Some(fg), None) => check_theme_contrast(&fg, colors, warnings),
+            _ => {}
+        }
+    }
+
+    for font in FONT_COLOR.captures_iter(html) {
+        if let Ok(fg) = Color::from_str(&font[1]) {
+            check_theme_contrast(&fg, colors, warnings);
+        }
+    }
+}
+
+fn check_theme_contrast(fg: &Color, colors: &ParsedColorConfig, warnings: &mut Vec<String>) {
+    let (light, dark) = &colors.theme_color;
+    if let Ok(light) = Color::from_str(light) {
+        check_contrast(fg, &light, "the light theme", warnings);
+    }
+    if let Ok(dark) = Color::from_str(dark) {
+        check_contrast(fg, &dark, "the dark theme", warnings);
+    }
+}
+
+fn check_contrast(fg: &Color, bg: &Color, against: &str, warnings: &mut Vec<String>) {
+    let (lighter, darker) = if fg.luminance() > bg.luminance() {
+        (fg.luminance(), bg.luminance())
+    } else {
+        (bg.luminance(), fg.luminance())
+    };
+    let ratio = (lighter + 0.05) / (darker + 0.05);
+    if ratio < MIN_CONTRAST_RATIO {
+        warnings.push(format!(
+            "text colored {} has a contrast ratio of {ratio:.1}:1 against {against}, at least {MIN_CONTRAST_RATIO}:1 is recommended",
+            fg.hex()
+        ));
+    }
+}
@@ -3,6 +3,8 @@ use sqlx::prelude::FromRow;
 
 use crate::{db::DbPoolConnection, error::prelude::*};
 
+use super::{CompareMode, Comparison};
+
 #[derive(Serialize, Deserialize, FromRow, Clone, Debug)]
 pub struct TestCase {
     pub id: i64,
@@ -12,6 +14,11 @@ pub struct TestCase {
     pub expected_pattern: String,
     pub use_regex: bool,
     pub case_insensitive: bool,
+    /// Made by the problem's generator rather than written by hand
+    pub generated: bool,
+    pub compare_mode: CompareMode,
+    /// Shown to participants, who can run their program against it before submitting
+    pub sample: bool,
 }
 
 impl TestCase {
@@ -24,6 +31,9 @@ impl TestCase {
             expected_pattern: form.expected_pattern.to_string(),
             use_regex: form.use_regex,
             case_insensitive: form.case_insensitive,
+            generated: false,
+            compare_mode: form.compare_mode,
+            sample: form.sample,
         }
     }
 
@@ -35,6 +45,21 @@ impl TestCase {
             .collect()
     }
 
+    /// Written cases followed by generated ones, numbered in that order
+    pub fn combine(written: Vec<Self>, generated: Vec<Self>) -> Vec<Self> {
+        let written = written.into_iter().map(|c| (c, false));
+        let generated = generated.into_iter().map(|c| (c, true));
+        written
+            .chain(generated)
+            .enumerate()
+            .map(|(i, (c, generated))| Self {
+                ord: i as i64,
+                generated,
+                ..c
+            })
+            .collect()
+    }
+
     pub async fn save_for_problem(
         db: &mut DbPoolConnection,
         problem_id: i64,
@@ -48,10 +73,10 @@ impl TestCase {
             .context("Failed to delete old test cases")?;
         let values_str = cases
             .iter()
-            .map(|_| "(?, ?, ?, ?, ?, ?)")
+            .map(|_| "(?, ?, ?, ?, ?, ?, ?, ?, ?)")
             .collect::<Vec<_>>()
             .join(",");
-        let query_str = format!("INSERT OR REPLACE INTO test_case (problem_id, ord, stdin, expected_pattern, use_regex, case_insensitive) VALUES {} RETURNING *", values_str);
+        let query_str = format!("INSERT OR REPLACE INTO test_case (problem_id, ord, stdin, expected_pattern, use_regex, case_insensitive, generated, compare_mode, sample) VALUES {} RETURNING *", values_str);
         let mut query = sqlx::query(&query_str);
         for c in cases.iter() {
             query = query
@@ -60,7 +85,10 @@ impl TestCase {
                 .bind(&c.stdin)
                 .bind(&c.expected_pattern)
                 .bind(c.use_regex)
-                .bind(c.case_insensitive);
+                .bind(c.case_insensitive)
+                .bind(c.generated)
+                .bind(c.compare_mode)
+                .bind(c.sample);
         }
         let res = query.fetch_all(&mut **db).await;
         res.context("Failed to upsert new test cases for problem")
@@ -86,6 +114,21 @@ impl TestCase {
         .with_context(|| format!("Failed to get test cases for problem {}", problem_id))
     }
 
+    /// The cases participants can see, see [`TestCase::sample`]
+    pub async fn get_samples_for_problem(
+        db: &mut DbPoolConnection,
+        problem_id: i64,
+    ) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            TestCase,
+            "SELECT * FROM test_case WHERE problem_id = ? AND sample = 1 ORDER BY ord",
+            problem_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get sample cases for problem {}", problem_id))
+    }
+
     pub async fn count_for_problem(db: &mut DbPoolConnection, problem_id: i64) -> Result<i64> {
         sqlx::query!("SELECT id FROM test_case WHERE problem_id = ?", problem_id)
             .fetch_all(&mut **db)
@@ -100,10 +143,12 @@ impl TestCase {
             expected_pattern: &self.expected_pattern,
             use_regex: self.use_regex,
             case_insensitive: self.case_insensitive,
+            compare_mode: self.compare_mode,
+            sample: self.sample,
         }
     }
 
-    pub fn check_output(&self, output: &str) -> Result<bool, String> {
+    pub fn check_output(&self, output: &str, comparison: &Comparison) -> Result<bool, String> {
         if self.use_regex {
             let mut builder = regex::RegexBuilder::new(&self.expected_pattern);
             builder.case_insensitive(self.case_insensitive);
@@ -111,13 +156,14 @@ impl TestCase {
                 .build()
                 .map_err(|e| format!("Couldn't build regex: {e:?}"))?;
             Ok(re.is_match(output.trim()))
+        } else if self.case_insensitive {
+            Ok(comparison.matches(
+                self.compare_mode,
+                &output.to_lowercase(),
+                &self.expected_pattern.to_lowercase(),
+            ))
         } else {
-            let (output, expected) = (output.trim(), self.expected_pattern.trim());
-            if self.case_insensitive {
-                Ok(output.to_lowercase() == expected.to_lowercase())
-            } else {
-                Ok(output == expected)
-            }
+            Ok(comparison.matches(self.compare_mode, output, &self.expected_pattern))
         }
     }
 }
@@ -141,4 +187,6 @@ pub struct TestCaseForm<'r> {
     pub expected_pattern: &'r str,
     pub use_regex: bool,
     pub case_insensitive: bool,
+    pub compare_mode: CompareMode,
+    pub sample: bool,
 }
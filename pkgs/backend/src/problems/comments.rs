@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use rocket::{
+    form::{Contextual, Form},
+    http::Status,
+    post, FromForm,
+};
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        users::{Admin, User},
+    },
+    contests::Contest,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+    template::{FormTemplateObject, TemplatedForm},
+    times::ClientTimeZone,
+};
+
+use super::{runs::render_run, JudgeRun, Problem};
+
+/// A note a judge left on a run, shown to the participant who submitted it
+#[derive(Serialize)]
+pub struct RunComment {
+    pub id: i64,
+    pub run_id: i64,
+    pub author_id: Option<i64>,
+    pub body: String,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+}
+
+impl RunComment {
+    pub async fn list_for_run(db: &mut DbPoolConnection, run_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            RunComment,
+            "SELECT * FROM run_comment WHERE run_id = ? ORDER BY created_at ASC",
+            run_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get comments for run {run_id}"))
+    }
+
+    pub async fn insert(
+        db: &mut DbPoolConnection,
+        run_id: i64,
+        author_id: i64,
+        body: &str,
+    ) -> Result {
+        sqlx::query!(
+            "INSERT INTO run_comment (run_id, author_id, body) VALUES (?, ?, ?)",
+            run_id,
+            author_id,
+            body
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to add comment to run {run_id}"))
+    }
+
+    pub async fn delete(db: &mut DbPoolConnection, run_id: i64, id: i64) -> Result<bool> {
+        sqlx::query!(
+            "DELETE FROM run_comment WHERE id = ? AND run_id = ?",
+            id,
+            run_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .with_context(|| format!("Failed to delete comment {id}"))
+    }
+}
+
+#[derive(FromForm)]
+pub struct RunCommentForm<'r> {
+    #[field(validate = len(1..=2000))]
+    body: &'r str,
+}
+
+pub struct RunCommentFormTemplate;
+
+impl TemplatedForm for RunCommentFormTemplate {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        HashMap::from_iter([("body".to_string(), "".to_string())])
+    }
+}
+
+/// Comments can only be written by judges, returns the run being commented on
+async fn get_run_as_judge(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    slug: &str,
+    run_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+) -> ResultResponse<JudgeRun> {
+    Contest::get_or_404_assert_can_edit(db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(db, contest_id, slug).await?;
+    JudgeRun::get(db, run_id)
+        .await?
+        .filter(|r| r.problem_id == problem.id)
+        .ok_or(Status::NotFound.into())
+}
+
+#[post(
+    "/<contest_id>/problems/<slug>/runs/<run_id>/comments",
+    data = "<form>"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_comment_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    run_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    tz: ClientTimeZone,
+    form: Form<Contextual<'_, RunCommentForm<'_>>>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let run = get_run_as_judge(&mut db, contest_id, slug, run_id, user, admin).await?;
+    if let Some(ref value) = form.value {
+        let body = value.body.trim();
+        if !body.is_empty() {
+            RunComment::insert(&mut db, run.id, user.id, body).await?;
+            return Ok(Message::success("Comment Added").to(&format!(
+                "/contests/{contest_id}/problems/{slug}/runs/{run_id}#comments"
+            )));
+        }
+    }
+    let form = FormTemplateObject::from_rocket_context(RunCommentFormTemplate, &form.context);
+    let template = render_run(&mut db, contest_id, slug, run_id, &tz, admin, user, form).await?;
+    Err(template.into())
+}
+
+#[post("/<contest_id>/problems/<slug>/runs/<run_id>/comments/<comment_id>/delete")]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_comment_delete_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    run_id: i64,
+    comment_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let run = get_run_as_judge(&mut db, contest_id, slug, run_id, user, admin).await?;
+    if !RunComment::delete(&mut db, run.id, comment_id).await? {
+        return Err(Status::NotFound.into());
+    }
+    Ok(Message::success("Comment Deleted").to(&format!(
+        "/contests/{contest_id}/problems/{slug}/runs/{run_id}#comments"
+    )))
+}
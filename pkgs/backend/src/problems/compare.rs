@@ -0,0 +1,131 @@
+use rocket::FromFormField;
+use sqlx::{encode::IsNull, Decode, Encode, Type};
+
+use super::Problem;
+
+/// How a case's output is checked against the expected output, regex cases ignore this
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromFormField, Default)]
+pub enum CompareMode {
+    /// Use the problem's mode, only for cases
+    #[default]
+    Inherit,
+    /// Whole output matches once leading and trailing whitespace is trimmed
+    Exact,
+    /// Same whitespace separated tokens, however they're spaced
+    Tokens,
+    /// Same tokens, with numbers allowed to be within the problem's tolerance
+    Float,
+    /// Same lines, ignoring whitespace at the end of each line
+    Lines,
+}
+
+impl From<String> for CompareMode {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "Exact" => Self::Exact,
+            "Tokens" => Self::Tokens,
+            "Float" => Self::Float,
+            "Lines" => Self::Lines,
+            _ => Self::Inherit,
+        }
+    }
+}
+
+impl From<CompareMode> for String {
+    fn from(m: CompareMode) -> Self {
+        format!("{:?}", m)
+    }
+}
+
+impl Type<sqlx::Sqlite> for CompareMode {
+    fn type_info() -> <sqlx::Sqlite as sqlx::Database>::TypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl Encode<'_, sqlx::Sqlite> for CompareMode {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Sqlite as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> IsNull {
+        let val = format!("{:?}", self);
+        <std::string::String as Encode<'_, sqlx::Sqlite>>::encode_by_ref(&val, buf)
+    }
+}
+
+impl Decode<'_, sqlx::Sqlite> for CompareMode {
+    fn decode(
+        value: <sqlx::Sqlite as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        Ok(s.into())
+    }
+}
+
+/// A problem's comparison settings, sent with judging jobs for cases that inherit them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Comparison {
+    pub mode: CompareMode,
+    pub abs_epsilon: f64,
+    pub rel_epsilon: f64,
+}
+
+impl Default for Comparison {
+    fn default() -> Self {
+        Self {
+            mode: CompareMode::Exact,
+            abs_epsilon: 1e-6,
+            rel_epsilon: 1e-6,
+        }
+    }
+}
+
+impl Comparison {
+    pub fn for_problem(problem: &Problem) -> Self {
+        Self {
+            mode: problem.compare_mode,
+            abs_epsilon: problem.abs_epsilon,
+            rel_epsilon: problem.rel_epsilon,
+        }
+    }
+
+    /// Check output with a case's mode, falling back to this one
+    pub fn matches(&self, mode: CompareMode, output: &str, expected: &str) -> bool {
+        let mode = match mode {
+            CompareMode::Inherit => self.mode,
+            mode => mode,
+        };
+        match mode {
+            CompareMode::Inherit | CompareMode::Exact => output.trim() == expected.trim(),
+            CompareMode::Tokens => output.split_whitespace().eq(expected.split_whitespace()),
+            CompareMode::Float => {
+                let mut output = output.split_whitespace();
+                let mut expected = expected.split_whitespace();
+                loop {
+                    match (output.next(), expected.next()) {
+                        (Some(o), Some(e)) if self.tokens_match(o, e) => {}
+                        (None, None) => return true,
+                        _ => return false,
+                    }
+                }
+            }
+            CompareMode::Lines => output
+                .trim_end()
+                .lines()
+                .map(str::trim_end)
+                .eq(expected.trim_end().lines().map(str::trim_end)),
+        }
+    }
+
+    /// Tokens are compared as numbers when the expected one is a number, otherwise exactly
+    fn tokens_match(&self, output: &str, expected: &str) -> bool {
+        match (output.parse::<f64>(), expected.parse::<f64>()) {
+            (Ok(o), Ok(e)) if o.is_finite() && e.is_finite() => {
+                let diff = (o - e).abs();
+                diff <= self.abs_epsilon || diff <= self.rel_epsilon * e.abs()
+            }
+            (_, Ok(e)) if e.is_finite() => false,
+            _ => output == expected,
+        }
+    }
+}
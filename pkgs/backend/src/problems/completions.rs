@@ -1,6 +1,12 @@
 use chrono::NaiveDateTime;
 
-use crate::{db::DbPoolConnection, error::prelude::*};
+use crate::{
+    contests::{Contest, Participant, ParticipantRole, Team},
+    db::DbPoolConnection,
+    error::prelude::*,
+};
+
+use super::JudgeRun;
 
 #[derive(Serialize, Debug)]
 pub struct ProblemCompletion {
@@ -64,6 +70,24 @@ impl ProblemCompletion {
         })
     }
 
+    /// Save this completion for everyone sharing it, the whole team in a team contest
+    pub async fn upsert_shared(
+        &self,
+        db: &mut DbPoolConnection,
+        sharers: &[Participant],
+    ) -> Result {
+        for sharer in sharers {
+            let completion = Self {
+                participant_id: sharer.p_id,
+                problem_id: self.problem_id,
+                completed_at: self.completed_at,
+                number_wrong: self.number_wrong,
+            };
+            completion.upsert(db).await?;
+        }
+        Ok(())
+    }
+
     pub fn temp(participant_id: i64, problem_id: i64, completed_at: Option<NaiveDateTime>) -> Self {
         Self {
             participant_id,
@@ -72,4 +96,72 @@ impl ProblemCompletion {
             number_wrong: 0,
         }
     }
+
+    /// Update the completion of whoever made a run after its verdict changed,
+    /// only runs made by participants while the contest was running for them count.
+    pub async fn update_for_run(
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+        run: &JudgeRun,
+        now_success: bool,
+    ) -> Result {
+        let participant = match Participant::get(db, contest.id, run.user_id).await? {
+            Some(p) if p.role() == ParticipantRole::Competitor => p,
+            _ => return Ok(()),
+        };
+
+        let end_time = contest.end_time_for(Some(&participant));
+        if run.ran_at < contest.start_time || run.ran_at > end_time {
+            return Ok(());
+        }
+
+        let sharers = Team::completion_sharers(db, contest, &participant).await?;
+
+        let mut completion =
+            Self::get_for_problem_and_participant(db, run.problem_id, participant.p_id)
+                .await?
+                .unwrap_or_else(|| Self::temp(participant.p_id, run.problem_id, None));
+
+        if now_success {
+            if completion.completed_at.is_none_or(|c| c > run.ran_at) {
+                completion.completed_at = Some(run.ran_at);
+            }
+        } else if completion.completed_at == Some(run.ran_at) {
+            // A teammate's run can still count for the whole team
+            let mut earliest: Option<NaiveDateTime> = None;
+            for sharer in sharers.iter() {
+                if let Some(success) = JudgeRun::get_earliest_success_between(
+                    db,
+                    sharer.user_id,
+                    run.problem_id,
+                    contest.start_time,
+                    end_time,
+                )
+                .await?
+                {
+                    earliest = Some(earliest.map_or(success.ran_at, |e| e.min(success.ran_at)));
+                }
+            }
+            completion.completed_at = earliest;
+        }
+
+        // The flip can move the first solve, so count the wrong runs before it again
+        let counted_until = completion
+            .completed_at
+            .unwrap_or(end_time + chrono::Duration::seconds(1));
+        let mut number_wrong = 0;
+        for sharer in sharers.iter() {
+            number_wrong += JudgeRun::count_wrong_between(
+                db,
+                sharer.user_id,
+                run.problem_id,
+                contest.start_time,
+                counted_until,
+            )
+            .await?;
+        }
+        completion.number_wrong = number_wrong;
+
+        completion.upsert_shared(db, &sharers).await
+    }
 }
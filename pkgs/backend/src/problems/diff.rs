@@ -0,0 +1,95 @@
+use chrono::TimeZone;
+use rocket::{get, http::Status};
+use rocket_dyn_templates::Template;
+use similar::{ChangeTag, TextDiff};
+
+use crate::{
+    auth::users::{Admin, User},
+    contests::{Contest, Participant},
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    times::{format_datetime_human_readable, ClientTimeZone},
+};
+
+use super::{JudgeRun, Problem};
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Serialize)]
+struct DiffLine {
+    /// `equal`, `delete`, or `insert`
+    tag: &'static str,
+    old_line: Option<usize>,
+    new_line: Option<usize>,
+    /// Pieces of the line, the flag is set on the parts of a changed line that actually changed
+    segments: Vec<(bool, String)>,
+}
+
+/// Changed lines with some context, hunks are separated by unchanged lines that aren't shown
+fn diff_programs(old: &str, new: &str) -> Vec<Vec<DiffLine>> {
+    let diff = TextDiff::from_lines(old, new);
+    diff.grouped_ops(CONTEXT_LINES)
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .flat_map(|op| diff.iter_inline_changes(op))
+                .map(|change| DiffLine {
+                    tag: match change.tag() {
+                        ChangeTag::Equal => "equal",
+                        ChangeTag::Delete => "delete",
+                        ChangeTag::Insert => "insert",
+                    },
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                    segments: change
+                        .iter_strings_lossy()
+                        .map(|(emphasized, s)| (emphasized, s.trim_end_matches('\n').to_string()))
+                        .collect(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[get("/<contest_id>/problems/<slug>/runs/diff?<from>&<to>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_diff(
+    contest_id: i64,
+    slug: &str,
+    from: i64,
+    to: i64,
+    tz: ClientTimeZone,
+    admin: Option<&Admin>,
+    user: &User,
+    mut db: DbConnection,
+) -> ResultResponse<Template> {
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    let old = JudgeRun::get(&mut db, from).await?;
+    let new = JudgeRun::get(&mut db, to).await?;
+    let (old, new) = old
+        .zip(new)
+        .filter(|(o, n)| {
+            o.problem_id == problem.id && n.problem_id == problem.id && o.user_id == n.user_id
+        })
+        .ok_or(Status::NotFound)?;
+    let participant = Participant::get(&mut db, contest_id, user.id).await?;
+    let can_edit = admin.is_some() || participant.is_some_and(|p| p.is_judge);
+    if old.user_id != user.id && !can_edit {
+        return Err(Status::NotFound.into());
+    }
+
+    let hunks = diff_programs(&old.program, &new.program);
+    let tz = tz.timezone();
+    let old_time = format_datetime_human_readable(tz.from_utc_datetime(&old.ran_at));
+    let new_time = format_datetime_human_readable(tz.from_utc_datetime(&new.ran_at));
+    Ok(Template::render(
+        "problems/run_diff",
+        context_with_base_authed!(
+            user, old, new, old_time, new_time, hunks, contest, problem, can_edit
+        ),
+    ))
+}
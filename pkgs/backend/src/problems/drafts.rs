@@ -0,0 +1,102 @@
+use chrono::NaiveDateTime;
+use rocket::{form::Form, http::Status, post, FromForm, State};
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        users::{Admin, User},
+    },
+    contests::Contest,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    run::CodeInfo,
+};
+
+use super::Problem;
+
+/// The code a user last had in the editor for a problem, so it follows them between page loads
+/// and devices. Each user has at most one per problem.
+pub struct CodeDraft {
+    #[allow(dead_code)]
+    pub user_id: i64,
+    #[allow(dead_code)]
+    pub problem_id: i64,
+    pub language: String,
+    pub program: String,
+    pub updated_at: NaiveDateTime,
+}
+
+impl CodeDraft {
+    pub async fn get(
+        db: &mut DbPoolConnection,
+        user_id: i64,
+        problem_id: i64,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            CodeDraft,
+            "SELECT * FROM code_draft WHERE user_id = ? AND problem_id = ?",
+            user_id,
+            problem_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| {
+            format!("Couldn't fetch draft for user {user_id} and problem {problem_id}")
+        })
+    }
+
+    pub async fn save(
+        db: &mut DbPoolConnection,
+        user_id: i64,
+        problem_id: i64,
+        language: &str,
+        program: &str,
+    ) -> Result {
+        let now = chrono::offset::Utc::now().naive_utc();
+        sqlx::query!(
+            "INSERT INTO code_draft (user_id, problem_id, language, program, updated_at) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(user_id, problem_id) DO UPDATE SET language = excluded.language, program = excluded.program, updated_at = excluded.updated_at",
+            user_id,
+            problem_id,
+            language,
+            program,
+            now
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| {
+            format!("Couldn't save draft for user {user_id} and problem {problem_id}")
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(FromForm)]
+pub struct DraftForm<'r> {
+    language: &'r str,
+    program: &'r str,
+}
+
+/// Autosave what's in the editor, called by the problem page a second after the code stops changing
+#[post("/<contest_id>/problems/<slug>/draft", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn save_draft_post(
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    info: &State<CodeInfo>,
+    form: Form<DraftForm<'_>>,
+    mut db: DbConnection,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Status> {
+    Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    if !info.run_config.languages.contains_key(form.language)
+        || form.program.len() > info.run_config.max_program_length
+    {
+        return Err(Status::BadRequest.into());
+    }
+    CodeDraft::save(&mut db, user.id, problem.id, form.language, form.program).await?;
+    Ok(Status::NoContent)
+}
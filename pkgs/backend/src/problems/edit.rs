@@ -9,16 +9,19 @@ use crate::{
         csrf::{CsrfToken, VerifyCsrfToken},
         users::{Admin, User},
     },
+    branding::ParsedColorConfig,
     contests::Contest,
     context_with_base_authed,
     db::DbConnection,
     error::prelude::*,
     messages::Message,
-    run::ManagerHandle,
+    run::{CodeInfo, ManagerHandle},
     template::FormTemplateObject,
 };
 
-use super::{cases::TestCase, Problem, ProblemForm, ProblemFormTemplate};
+use super::{
+    audit::audit_statement, cases::TestCase, validator, Problem, ProblemForm, ProblemFormTemplate,
+};
 
 #[get("/<contest_id>/problems/<slug>/edit")]
 pub async fn edit_problem_get(
@@ -35,12 +38,18 @@ pub async fn edit_problem_get(
     let test_cases = TestCase::get_for_problem(&mut db, problem.id).await?;
     let form_template = ProblemFormTemplate {
         problem: Some(&problem),
-        test_cases: test_cases.iter().map(TestCase::to_form).collect(),
+        contest: &contest,
+        test_cases: test_cases
+            .iter()
+            .filter(|c| !c.generated)
+            .map(TestCase::to_form)
+            .collect(),
     };
+    let generated_cases = test_cases.iter().filter(|c| c.generated).count();
     let form = FormTemplateObject::get(form_template);
     Ok(Template::render(
         "problems/edit",
-        context_with_base_authed!(user, form, contest, problem),
+        context_with_base_authed!(user, form, contest, problem, generated_cases),
     ))
 }
 
@@ -53,22 +62,37 @@ pub async fn edit_problem_post(
     contest_id: i64,
     slug: &str,
     mut form: Form<Contextual<'_, ProblemForm<'_>>>,
+    info: &State<CodeInfo>,
     _token: &VerifyCsrfToken,
     manager: &State<ManagerHandle>,
+    colors: &State<ParsedColorConfig>,
     mut db: DbConnection,
 ) -> FormResponse {
-    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
     let mut problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
     let test_cases = TestCase::get_for_problem(&mut db, problem.id)
         .await
         .unwrap_or_default();
+    let (generated, written): (Vec<_>, Vec<_>) = test_cases.into_iter().partition(|c| c.generated);
     let form_template = ProblemFormTemplate {
         problem: Some(&problem),
-        test_cases: test_cases.iter().map(TestCase::to_form).collect(),
+        contest: &contest,
+        test_cases: written.iter().map(TestCase::to_form).collect(),
     };
 
     let original_name = problem.name.clone();
-    if let Some(ref value) = form.value {
+    let allowed_languages = form
+        .value
+        .as_ref()
+        .map(|v| info.run_config.normalize_language_list(v.allowed_languages))
+        .transpose()
+        .unwrap_or_else(|why| {
+            form.context
+                .push_error(Error::validation(why).with_name("allowed_languages"));
+            None
+        });
+    if let (Some(value), Some(allowed_languages)) = (form.value.as_ref(), allowed_languages) {
         let new_slug = slug::slugify(value.name);
 
         if Problem::slug_exists(&mut db, &new_slug, contest_id, Some(problem.id)).await? {
@@ -78,18 +102,53 @@ pub async fn edit_problem_post(
             let err =
                 Error::validation("At least one test case is required").with_name("test_cases");
             form.context.push_error(err);
+        } else if let Some(why) = validator::check_cases(
+            manager,
+            user.id,
+            &Problem::temp(contest_id, value),
+            &value.test_cases,
+        )
+        .await
+        {
+            form.context
+                .push_error(Error::validation(why).with_name("test_cases"));
         } else {
             problem.name = value.name.to_string();
             problem.slug = new_slug;
             problem.description = value.description.to_string();
             problem.cpu_time = value.cpu_time;
             problem.memory_limit = value.memory_limit;
+            problem.banned_imports = value.banned_imports.trim().to_string();
+            problem.flag_banned_imports = value.flag_banned_imports;
+            problem.parallelism = value.parallelism;
+            problem.wall_time = value.wall_time;
+            problem.allow_network = value.allow_network;
+            problem.allowed_languages = allowed_languages;
+            problem.validator = value.validator.trim().to_string();
+            problem.validator_language = value.validator_language.trim().to_string();
+            problem.generator = value.generator.trim().to_string();
+            problem.generator_language = value.generator_language.trim().to_string();
+            problem.generator_seeds = value.generator_seeds.trim().to_string();
+            problem.submission_cooldown = value.submission_cooldown;
+            problem.max_attempts = value.max_attempts;
+            problem.output_only = value.output_only;
+            problem.quiz = value.quiz;
             problem.update(&mut db).await?;
-            let test_cases = TestCase::from_vec(problem.id, &value.test_cases);
+            let test_cases =
+                TestCase::combine(TestCase::from_vec(problem.id, &value.test_cases), generated);
             TestCase::save_for_problem(&mut db, problem.id, test_cases).await?;
             let mut manager = manager.lock().await;
             manager.update_problem(problem.id).await;
-            return Ok(Message::success("Problem Updated").to(&format!(
+            let warnings = audit_statement(&problem.description, colors);
+            let message = if warnings.is_empty() {
+                Message::success("Problem Updated")
+            } else {
+                Message::info(
+                    "Problem Updated, but the statement may be hard for some people to read: {warnings}",
+                )
+                .arg("warnings", warnings.join("; "))
+            };
+            return Ok(message.to(&format!(
                 "/contests/{}/problems/{}",
                 contest_id, problem.slug
             )));
@@ -97,7 +156,6 @@ pub async fn edit_problem_post(
     }
 
     let form_ctx = FormTemplateObject::from_rocket_context(form_template, &form.context);
-    let contest = Contest::get(&mut db, contest_id).await.unwrap();
     Err(Template::render(
         "problems/edit",
         context_with_base_authed!(user, form: form_ctx, contest, problem, problem_name: original_name),
@@ -0,0 +1,162 @@
+use log::info;
+use rocket::{post, State};
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        users::{Admin, User},
+    },
+    contests::Contest,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    run::{
+        direct::{run_for_problem, JobOperation},
+        ManagerHandle,
+    },
+};
+
+use super::{
+    cases::{TestCase, TestCaseForm},
+    references::{ReferenceSolution, ReferenceVerdict},
+    validator, CompareMode, Problem,
+};
+
+/// Run a program that should succeed, returning everything it printed
+async fn run_step(
+    manager: &ManagerHandle,
+    user_id: i64,
+    problem: &Problem,
+    language_key: &str,
+    program: &str,
+    stdin: String,
+) -> Result<String, String> {
+    let state = run_for_problem(
+        manager,
+        user_id,
+        problem,
+        language_key,
+        program,
+        JobOperation::Generating(stdin),
+    )
+    .await?;
+    match (state.last_error(), state.output()) {
+        ((_, _, Some(error)), _) => Err(error),
+        (_, Some(output)) => Ok(output.to_string()),
+        (_, None) => Err("No output".to_string()),
+    }
+}
+
+/// Make one case from a seed, the error finishes a sentence about the seed
+async fn generate_case(
+    manager: &ManagerHandle,
+    user_id: i64,
+    problem: &Problem,
+    reference: &ReferenceSolution,
+    seed: &str,
+) -> Result<TestCase, String> {
+    let stdin = run_step(
+        manager,
+        user_id,
+        problem,
+        &problem.generator_language,
+        &problem.generator,
+        format!("{seed}\n"),
+    )
+    .await
+    .map_err(|why| format!("made the generator fail: {why}"))?;
+    validator::check_input(manager, user_id, problem, &stdin)
+        .await
+        .map_err(|why| format!("generated input that {why}"))?;
+    let expected = run_step(
+        manager,
+        user_id,
+        problem,
+        &reference.language,
+        &reference.program,
+        stdin.clone(),
+    )
+    .await
+    .map_err(|why| format!("made {} fail: {why}", reference.name))?;
+    let form = TestCaseForm {
+        stdin: &stdin,
+        expected_pattern: &expected,
+        use_regex: false,
+        case_insensitive: false,
+        compare_mode: CompareMode::Inherit,
+        sample: false,
+    };
+    Ok(TestCase::temp(&form, problem.id, 0))
+}
+
+/// Replace the problem's generated cases, the generator prints each seed's input and the first
+/// reference solution expected to be accepted prints the answer
+#[post("/<contest_id>/problems/<slug>/generate")]
+pub async fn generate_cases_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    manager: &State<ManagerHandle>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let url = format!("/contests/{contest_id}/problems/{slug}/edit");
+
+    if problem.generator.is_empty() {
+        return Ok(Message::error("This problem doesn't have a generator").to(&url));
+    }
+    let seeds = problem
+        .generator_seeds
+        .lines()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    if seeds.is_empty() {
+        return Ok(Message::error("Add some seeds to generate cases from").to(&url));
+    }
+    let reference = ReferenceSolution::list_for_problem(&mut db, problem.id)
+        .await?
+        .into_iter()
+        .find(|r| r.expected == ReferenceVerdict::Accepted);
+    let Some(reference) = reference else {
+        return Ok(Message::error(
+            "Add a reference solution that's expected to be accepted to answer the generated cases",
+        )
+        .to(&format!(
+            "/contests/{contest_id}/problems/{slug}/references"
+        )));
+    };
+
+    let mut generated = Vec::with_capacity(seeds.len());
+    for seed in seeds.iter() {
+        match generate_case(manager, user.id, &problem, &reference, seed).await {
+            Ok(case) => generated.push(case),
+            Err(why) => {
+                return Ok(Message::error("Seed {seed} {why}")
+                    .arg("seed", seed)
+                    .arg("why", why)
+                    .to(&url))
+            }
+        }
+    }
+
+    let (_, written): (Vec<_>, Vec<_>) = TestCase::get_for_problem(&mut db, problem.id)
+        .await?
+        .into_iter()
+        .partition(|c| c.generated);
+    let count = generated.len();
+    TestCase::save_for_problem(&mut db, problem.id, TestCase::combine(written, generated)).await?;
+    let mut manager = manager.lock().await;
+    manager.update_problem(problem.id).await;
+    info!(
+        "{} generated {count} cases for problem {}",
+        user.id, problem.id
+    );
+
+    Ok(Message::success("Generated {count} Cases")
+        .arg("count", count)
+        .to(&url))
+}
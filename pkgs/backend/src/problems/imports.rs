@@ -0,0 +1,58 @@
+use regex::{Regex, RegexBuilder};
+
+/// A single banned import rule, written by judges as `language: pattern` on its own line.
+/// `language` is a key from the `languages` config or `*` to apply to every language,
+/// `pattern` is a regex matched against each line of the program.
+pub struct BannedImport {
+    language: String,
+    pattern: Regex,
+}
+
+impl BannedImport {
+    fn applies_to(&self, language: &str) -> bool {
+        self.language == "*" || self.language == language
+    }
+}
+
+pub fn parse_rules(raw: &str) -> Result<Vec<BannedImport>, String> {
+    raw.lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'))
+        .map(|(line_num, line)| {
+            let (language, pattern) = line.split_once(':').ok_or_else(|| {
+                format!("Line {line_num}: expected `language: pattern`, got `{line}`")
+            })?;
+            let pattern = RegexBuilder::new(pattern.trim())
+                .multi_line(true)
+                .build()
+                .map_err(|e| format!("Line {line_num}: invalid pattern: {e}"))?;
+            Ok(BannedImport {
+                language: language.trim().to_string(),
+                pattern,
+            })
+        })
+        .collect()
+}
+
+/// Scan a program for banned imports, returning a message describing the earliest violation
+pub fn find_violation(rules: &[BannedImport], language: &str, program: &str) -> Option<String> {
+    rules
+        .iter()
+        .filter(|r| r.applies_to(language))
+        .filter_map(|r| r.pattern.find(program))
+        .min_by_key(|m| m.start())
+        .map(|m| {
+            let line_num = program[..m.start()].matches('\n').count() + 1;
+            format!(
+                "Line {line_num}: `{}` is not allowed for this problem",
+                m.as_str().trim()
+            )
+        })
+}
+
+pub fn check_rules(raw: &'_ str) -> Result<(), rocket::form::Errors<'_>> {
+    parse_rules(raw)
+        .map(|_| ())
+        .map_err(|e| rocket::form::Error::validation(e).into())
+}
@@ -0,0 +1,62 @@
+//! Running a program against custom input, a few inputs at a time, outside of submitting.
+//! The runs themselves go over the problem's websocket, see [`crate::run::MAX_INVOCATION_INPUTS`].
+
+use rocket::{get, http::Status, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    rate_limit::{Bucket, RateLimiterHandle},
+    run::{CodeInfo, MAX_INVOCATION_INPUTS},
+};
+
+use super::{
+    drafts::CodeDraft,
+    view::{editor_languages, most_recent_code},
+    JudgeRun, Problem, StarterCode,
+};
+
+#[get("/<contest_id>/problems/<slug>/invoke")]
+pub async fn invoke_get(
+    user: &User,
+    admin: Option<&Admin>,
+    info: &State<CodeInfo>,
+    limiter: &State<RateLimiterHandle>,
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+) -> ResultResponse<Template> {
+    let (contest, _, _) =
+        Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    if !problem.judges_programs() {
+        return Err(Status::NotFound.into());
+    }
+
+    let draft = CodeDraft::get(&mut db, user.id, problem.id).await?;
+    let last_run = JudgeRun::get_latest(&mut db, user.id, problem.id).await?;
+    let most_recent_code = most_recent_code(&draft, &last_run)?;
+
+    let (languages, default_language) = editor_languages(info, &problem, Some(user));
+    let starters = StarterCode::list_for_problem(&mut db, problem.id).await?;
+    let code_info = StarterCode::code_info(&info.languages_json, &starters)?;
+    let max_inputs = MAX_INVOCATION_INPUTS;
+    let quota = limiter.lock().await.quota(Bucket::Invocation);
+
+    let ctx = context_with_base_authed!(
+        user,
+        contest,
+        problem,
+        code_info,
+        languages,
+        default_language,
+        most_recent_code,
+        max_inputs,
+        quota
+    );
+    Ok(Template::render("problems/invoke", ctx))
+}
@@ -6,6 +6,7 @@ use crate::{
     db::DbConnection,
     error::prelude::*,
     problems::Problem,
+    rate_limit::{self, RateLimited},
 };
 
 use super::ProblemData;
@@ -17,6 +18,7 @@ pub async fn problem_export(
     admin: Option<&Admin>,
     user: &User,
     problem_slug: &str,
+    _limit: RateLimited<rate_limit::Api>,
 ) -> ResultResponse<Json<ProblemData>> {
     Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
     let problem = Problem::get_or_404(&mut db, contest_id, problem_slug).await?;
@@ -9,13 +9,14 @@ use rocket_dyn_templates::Template;
 use crate::{
     auth::{
         csrf::CsrfToken,
+        impersonation::NotImpersonating,
         users::{Admin, User},
     },
     contests::Contest,
     context_with_base_authed,
     db::DbConnection,
     error::prelude::*,
-    problems::{cases::TestCaseForm, Problem, ProblemForm, ProblemFormTemplate},
+    problems::ProblemFormTemplate,
     template::{FormTemplateObject, TemplatedForm},
 };
 
@@ -56,6 +57,7 @@ pub async fn problem_import_post(
     admin: Option<&Admin>,
     user: &User,
     _token: &CsrfToken,
+    _read_write: NotImpersonating,
     mut form: Form<Contextual<'_, ProblemImportForm>>,
 ) -> ResultResponse<Template> {
     let (contest, _) =
@@ -63,35 +65,11 @@ pub async fn problem_import_post(
     if let Some(value) = form.value.clone() {
         match serde_json::from_str::<ProblemData>(value.data.as_str()) {
             Ok(problem_data) => {
-                let problem_form = ProblemForm {
-                    name: &problem_data.name,
-                    description: &problem_data.description,
-                    cpu_time: problem_data.cpu_time,
-                    memory_limit: problem_data.memory_limit,
-                    test_cases: problem_data
-                        .cases
-                        .iter()
-                        .map(|c| TestCaseForm {
-                            stdin: &c.stdin,
-                            expected_pattern: &c.expected_pattern,
-                            use_regex: c.use_regex,
-                            case_insensitive: c.case_insensitive,
-                        })
-                        .collect(),
-                };
-                let problem = Problem::temp(contest_id, &problem_form);
-                let cases = problem_data
-                    .cases
-                    .iter()
-                    .map(|c| TestCaseForm {
-                        stdin: &c.stdin,
-                        expected_pattern: &c.expected_pattern,
-                        use_regex: c.use_regex,
-                        case_insensitive: c.case_insensitive,
-                    })
-                    .collect();
+                let problem = problem_data.to_problem(contest_id);
+                let cases = problem_data.case_forms();
                 let form_template = ProblemFormTemplate {
                     problem: Some(&problem),
+                    contest: &contest,
                     test_cases: cases,
                 };
                 let form_template = FormTemplateObject::get(form_template);
@@ -1,9 +1,12 @@
 use rocket::{fairing::AdHoc, routes};
 use serde::Deserialize;
 
-use crate::{db::DbPoolConnection, error::prelude::*};
+use crate::{db::DbPoolConnection, error::prelude::*, run::CodeInfo};
 
-use super::{Problem, TestCase};
+use super::{
+    cases::TestCaseForm, tags::normalize_tags, CompareMode, Comparison, Problem, ProblemDifficulty,
+    ProblemForm, TestCase,
+};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -12,6 +15,10 @@ struct CaseData {
     expected_pattern: String,
     use_regex: bool,
     case_insensitive: bool,
+    #[serde(default)]
+    compare_mode: CompareMode,
+    #[serde(default)]
+    sample: bool,
 }
 
 impl From<TestCase> for CaseData {
@@ -21,17 +28,77 @@ impl From<TestCase> for CaseData {
             expected_pattern: tc.expected_pattern,
             use_regex: tc.use_regex,
             case_insensitive: tc.case_insensitive,
+            compare_mode: tc.compare_mode,
+            sample: tc.sample,
         }
     }
 }
 
+const fn default_parallelism() -> i64 {
+    1
+}
+
+fn default_compare_mode() -> CompareMode {
+    Comparison::default().mode
+}
+
+fn default_abs_epsilon() -> f64 {
+    Comparison::default().abs_epsilon
+}
+
+fn default_rel_epsilon() -> f64 {
+    Comparison::default().rel_epsilon
+}
+
+/// Everything needed to make a problem again somewhere else, this is the export format
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct ProblemData {
+pub struct ProblemData {
     name: String,
     description: String,
     cpu_time: i64,
     memory_limit: i64,
+    #[serde(default)]
+    banned_imports: String,
+    #[serde(default)]
+    flag_banned_imports: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    difficulty: ProblemDifficulty,
+    #[serde(default = "default_parallelism")]
+    parallelism: i64,
+    #[serde(default)]
+    wall_time: i64,
+    #[serde(default)]
+    allow_network: bool,
+    #[serde(default)]
+    allowed_languages: String,
+    #[serde(default)]
+    validator: String,
+    #[serde(default)]
+    validator_language: String,
+    #[serde(default)]
+    generator: String,
+    #[serde(default)]
+    generator_language: String,
+    #[serde(default)]
+    generator_seeds: String,
+    #[serde(default = "default_compare_mode")]
+    compare_mode: CompareMode,
+    #[serde(default = "default_abs_epsilon")]
+    abs_epsilon: f64,
+    #[serde(default = "default_rel_epsilon")]
+    rel_epsilon: f64,
+    #[serde(default)]
+    submission_cooldown: Option<i64>,
+    #[serde(default)]
+    max_attempts: Option<i64>,
+    #[serde(default)]
+    output_only: bool,
+    #[serde(default)]
+    quiz: bool,
+    /// Only cases written by hand, generated ones can be made again from the generator
     cases: Vec<CaseData>,
 }
 
@@ -45,9 +112,125 @@ impl ProblemData {
             description: problem.description.clone(),
             cpu_time: problem.cpu_time,
             memory_limit: problem.memory_limit,
-            cases: cases.into_iter().map(CaseData::from).collect(),
+            banned_imports: problem.banned_imports.clone(),
+            flag_banned_imports: problem.flag_banned_imports,
+            tags: problem
+                .tags
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect(),
+            difficulty: problem.difficulty,
+            parallelism: problem.parallelism,
+            wall_time: problem.wall_time,
+            allow_network: problem.allow_network,
+            allowed_languages: problem.allowed_languages.clone(),
+            validator: problem.validator.clone(),
+            validator_language: problem.validator_language.clone(),
+            generator: problem.generator.clone(),
+            generator_language: problem.generator_language.clone(),
+            generator_seeds: problem.generator_seeds.clone(),
+            compare_mode: problem.compare_mode,
+            abs_epsilon: problem.abs_epsilon,
+            rel_epsilon: problem.rel_epsilon,
+            submission_cooldown: problem.submission_cooldown,
+            max_attempts: problem.max_attempts,
+            output_only: problem.output_only,
+            quiz: problem.quiz,
+            cases: cases
+                .into_iter()
+                .filter(|c| !c.generated)
+                .map(CaseData::from)
+                .collect(),
         })
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn difficulty(&self) -> ProblemDifficulty {
+        self.difficulty
+    }
+
+    /// Tags in the form problems store them, see [`normalize_tags`]
+    pub fn tags(&self) -> String {
+        normalize_tags(&self.tags.join(","))
+    }
+
+    /// Check data that didn't come from a form makes a problem this server can run, fixing up
+    /// what the problem form would have
+    pub fn check(&mut self, info: &CodeInfo) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.name.chars().count() > 32 {
+            return Err("The name must be 1 to 32 characters".to_string());
+        }
+        if self.cases.is_empty() {
+            return Err("At least one test case is required".to_string());
+        }
+        self.allowed_languages = info
+            .run_config
+            .normalize_language_list(&self.allowed_languages)?;
+        self.submission_cooldown = self.submission_cooldown.filter(|s| *s > 0);
+        self.max_attempts = self.max_attempts.filter(|a| *a > 0);
+        self.quiz = self.quiz && !self.output_only;
+        Ok(())
+    }
+
+    fn case_forms(&self) -> Vec<TestCaseForm<'_>> {
+        self.cases
+            .iter()
+            .map(|c| TestCaseForm {
+                stdin: &c.stdin,
+                expected_pattern: &c.expected_pattern,
+                use_regex: c.use_regex,
+                case_insensitive: c.case_insensitive,
+                compare_mode: c.compare_mode,
+                sample: c.sample,
+            })
+            .collect()
+    }
+
+    /// The data filled into a problem form, `tags` should be the joined tags
+    fn form<'a>(&'a self, tags: &'a str) -> ProblemForm<'a> {
+        ProblemForm {
+            name: &self.name,
+            description: &self.description,
+            cpu_time: self.cpu_time,
+            memory_limit: self.memory_limit,
+            banned_imports: &self.banned_imports,
+            flag_banned_imports: self.flag_banned_imports,
+            tags,
+            difficulty: self.difficulty,
+            parallelism: self.parallelism,
+            wall_time: self.wall_time,
+            allow_network: self.allow_network,
+            allowed_languages: &self.allowed_languages,
+            validator: &self.validator,
+            validator_language: &self.validator_language,
+            generator: &self.generator,
+            generator_language: &self.generator_language,
+            generator_seeds: &self.generator_seeds,
+            compare_mode: self.compare_mode,
+            abs_epsilon: self.abs_epsilon,
+            rel_epsilon: self.rel_epsilon,
+            submission_cooldown: self.submission_cooldown.filter(|s| *s > 0),
+            max_attempts: self.max_attempts.filter(|a| *a > 0),
+            output_only: self.output_only,
+            quiz: self.quiz && !self.output_only,
+            test_cases: self.case_forms(),
+        }
+    }
+
+    /// A problem in the contest made from this data, it isn't saved yet
+    pub fn to_problem(&self, contest_id: i64) -> Problem {
+        let tags = self.tags.join(",");
+        Problem::temp(contest_id, &self.form(&tags))
+    }
+
+    /// The written cases for a problem made from this data
+    pub fn to_cases(&self, problem_id: i64) -> Vec<TestCase> {
+        TestCase::from_vec(problem_id, &self.case_forms())
+    }
 }
 
 mod export;
@@ -4,20 +4,46 @@ use std::collections::HashMap;
 
 use rocket::{fairing::AdHoc, http::Status, routes, FromForm};
 
+mod analytics;
+mod audit;
 mod cases;
+mod comments;
+mod compare;
 mod completions;
 mod delete;
+mod diff;
+mod drafts;
 mod edit;
+mod generator;
+mod imports;
+mod invoke;
 mod io;
 mod new;
+mod preview;
+mod references;
 mod runs;
+mod starter;
+mod tags;
+mod telemetry;
+mod validator;
+mod verdict;
 mod view;
 
+pub use analytics::ProblemAnalytics;
 pub use cases::TestCase;
+pub use compare::{CompareMode, Comparison};
 pub use completions::ProblemCompletion;
+pub use io::ProblemData;
+pub use references::ReferenceVerdict;
 pub use runs::JudgeRun;
+pub use starter::StarterCode;
+pub use tags::{check_tags, normalize_tags, ProblemDifficulty, ProblemFilter, Tagged};
+pub use telemetry::{TelemetryReport, LARGE_PASTE_CHARS};
 
-use crate::{db::DbPoolConnection, error::prelude::*, template::TemplatedForm, ResultResponse};
+use crate::{
+    contests::Contest, db::DbPoolConnection, error::prelude::*, template::TemplatedForm,
+    ResultResponse,
+};
 
 use self::cases::TestCaseForm;
 
@@ -30,6 +56,56 @@ pub struct Problem {
     pub description: String,
     pub cpu_time: i64,
     pub memory_limit: i64,
+    pub banned_imports: String,
+    pub flag_banned_imports: bool,
+    pub tags: String,
+    pub difficulty: ProblemDifficulty,
+    /// Workers to split test cases across when judging, 1 runs them one after another
+    pub parallelism: i64,
+    pub wall_time: i64,
+    pub allow_network: bool,
+    /// Comma separated language keys participants can submit in, see [`Problem::allows_language`]
+    pub allowed_languages: String,
+    /// Position in the contest's problem list, see [`Problem::reorder`]
+    pub ordering: i64,
+    /// Short name like `A` shown alongside the problem's name
+    pub label: String,
+    /// Program that checks each test case's input when the problem is saved, empty for none
+    #[serde(skip_serializing)]
+    pub validator: String,
+    #[serde(skip_serializing)]
+    pub validator_language: String,
+    /// Program that prints a test case's input given a seed on stdin, empty for none
+    #[serde(skip_serializing)]
+    pub generator: String,
+    #[serde(skip_serializing)]
+    pub generator_language: String,
+    /// Seeds to run the generator with, one per line
+    #[serde(skip_serializing)]
+    pub generator_seeds: String,
+    /// How cases check output unless they pick their own mode
+    pub compare_mode: CompareMode,
+    /// Tolerances for comparing numbers in the `Float` mode
+    pub abs_epsilon: f64,
+    pub rel_epsilon: f64,
+    /// Seconds a participant has to wait between submissions, see [`Problem::submission_blocked`]
+    pub submission_cooldown: Option<i64>,
+    /// Submissions each participant gets
+    pub max_attempts: Option<i64>,
+    /// Participants upload an answer for each case instead of a program, see `run::answers`
+    pub output_only: bool,
+    /// Participants give a single short answer, each case's expected output is an accepted one
+    pub quiz: bool,
+}
+
+/// Label for the problem at a position, `A` to `Z` then `AA`, `AB`...
+pub fn problem_label(index: usize) -> String {
+    let letter = |i: usize| char::from(b'A' + (i % 26) as u8); // `as` is safe due to the modulo
+    if index < 26 {
+        letter(index).to_string()
+    } else {
+        format!("{}{}", letter(index / 26 - 1), letter(index))
+    }
 }
 
 impl Problem {
@@ -108,7 +184,7 @@ impl Problem {
     pub async fn list(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
         sqlx::query_as!(
             Problem,
-            "SELECT * FROM problem WHERE contest_id = ?",
+            "SELECT * FROM problem WHERE contest_id = ? ORDER BY ordering, id",
             contest_id
         )
         .fetch_all(&mut **db)
@@ -116,16 +192,48 @@ impl Problem {
         .context("Failed to get all problems")
     }
 
+    /// Insert the problem at the end of its contest's list
     pub async fn insert(&self, db: &mut DbPoolConnection) -> Result<Problem> {
+        let ordering = sqlx::query!(
+            "SELECT COALESCE(MAX(ordering) + 1, 0) AS \"ordering!: i64\" FROM problem WHERE contest_id = ?",
+            self.contest_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.ordering)
+        .context("Failed to get position for new problem")?;
+        let label = problem_label(ordering as usize); // `as` is safe, orderings are never negative
         sqlx::query_as!(
             Problem,
-            "INSERT INTO problem (name, contest_id, slug, description, cpu_time, memory_limit) VALUES (?, ?, ?, ?, ?, ?) RETURNING *",
+            "INSERT INTO problem (name, contest_id, slug, description, cpu_time, memory_limit, banned_imports, flag_banned_imports, tags, difficulty, parallelism, wall_time, allow_network, allowed_languages, ordering, label, validator, validator_language, generator, generator_language, generator_seeds, compare_mode, abs_epsilon, rel_epsilon, submission_cooldown, max_attempts, output_only, quiz) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
             self.name,
             self.contest_id,
             self.slug,
             self.description,
             self.cpu_time,
-            self.memory_limit
+            self.memory_limit,
+            self.banned_imports,
+            self.flag_banned_imports,
+            self.tags,
+            self.difficulty,
+            self.parallelism,
+            self.wall_time,
+            self.allow_network,
+            self.allowed_languages,
+            ordering,
+            label,
+            self.validator,
+            self.validator_language,
+            self.generator,
+            self.generator_language,
+            self.generator_seeds,
+            self.compare_mode,
+            self.abs_epsilon,
+            self.rel_epsilon,
+            self.submission_cooldown,
+            self.max_attempts,
+            self.output_only,
+            self.quiz
         )
         .fetch_one(&mut **db)
         .await.context("Failed to insert new problem")
@@ -134,12 +242,32 @@ impl Problem {
     pub async fn update(&self, db: &mut DbPoolConnection) -> Result {
         sqlx::query_as!(
             Problem,
-            "UPDATE problem SET name = ?, slug = ?, description = ?, cpu_time = ?, memory_limit = ? WHERE id = ?",
+            "UPDATE problem SET name = ?, slug = ?, description = ?, cpu_time = ?, memory_limit = ?, banned_imports = ?, flag_banned_imports = ?, tags = ?, difficulty = ?, parallelism = ?, wall_time = ?, allow_network = ?, allowed_languages = ?, validator = ?, validator_language = ?, generator = ?, generator_language = ?, generator_seeds = ?, compare_mode = ?, abs_epsilon = ?, rel_epsilon = ?, submission_cooldown = ?, max_attempts = ?, output_only = ?, quiz = ? WHERE id = ?",
             self.name,
             self.slug,
             self.description,
             self.cpu_time,
             self.memory_limit,
+            self.banned_imports,
+            self.flag_banned_imports,
+            self.tags,
+            self.difficulty,
+            self.parallelism,
+            self.wall_time,
+            self.allow_network,
+            self.allowed_languages,
+            self.validator,
+            self.validator_language,
+            self.generator,
+            self.generator_language,
+            self.generator_seeds,
+            self.compare_mode,
+            self.abs_epsilon,
+            self.rel_epsilon,
+            self.submission_cooldown,
+            self.max_attempts,
+            self.output_only,
+            self.quiz,
             self.id,
         )
         .execute(&mut **db)
@@ -170,8 +298,137 @@ impl Problem {
             description: form.description.to_string(),
             cpu_time: form.cpu_time,
             memory_limit: form.memory_limit,
+            banned_imports: form.banned_imports.trim().to_string(),
+            flag_banned_imports: form.flag_banned_imports,
+            tags: tags::normalize_tags(form.tags),
+            difficulty: form.difficulty,
+            parallelism: form.parallelism,
+            wall_time: form.wall_time,
+            allow_network: form.allow_network,
+            allowed_languages: form.allowed_languages.to_string(),
+            ordering: 0,
+            label: String::new(),
+            validator: form.validator.trim().to_string(),
+            validator_language: form.validator_language.trim().to_string(),
+            generator: form.generator.trim().to_string(),
+            generator_language: form.generator_language.trim().to_string(),
+            generator_seeds: form.generator_seeds.trim().to_string(),
+            compare_mode: form.compare_mode,
+            abs_epsilon: form.abs_epsilon,
+            rel_epsilon: form.rel_epsilon,
+            submission_cooldown: form.submission_cooldown,
+            max_attempts: form.max_attempts,
+            output_only: form.output_only,
+            quiz: form.quiz,
         }
     }
+
+    /// Put a contest's problems in the given order and label them to match,
+    /// `ids` should contain every problem in the contest
+    pub async fn reorder(db: &mut DbPoolConnection, contest_id: i64, ids: &[i64]) -> Result {
+        for (i, id) in ids.iter().enumerate() {
+            let ordering = i as i64;
+            let label = problem_label(i);
+            sqlx::query!(
+                "UPDATE problem SET ordering = ?, label = ? WHERE id = ? AND contest_id = ?",
+                ordering,
+                label,
+                id,
+                contest_id
+            )
+            .execute(&mut **db)
+            .await
+            .with_context(|| format!("Failed to move problem {id}"))?;
+        }
+        Ok(())
+    }
+
+    /// Wall time limit to judge with, `None` when the server's hard timeout applies
+    pub fn wall_time_limit(&self) -> Option<u64> {
+        (self.wall_time > 0).then_some(self.wall_time as u64) // `as` is safe due to DB constraint
+    }
+
+    /// Output-only problems and quizzes are answered directly instead, see `run::answers`
+    pub fn judges_programs(&self) -> bool {
+        !self.output_only && !self.quiz
+    }
+
+    /// Whether participants can submit in a language, every language is allowed if none are listed
+    pub fn allows_language(&self, language: &str) -> bool {
+        self.allowed_languages.is_empty()
+            || self.allowed_languages.split(',').any(|l| l == language)
+    }
+
+    /// Check a program against this problem's banned imports,
+    /// returns a message describing the first violation found
+    pub fn banned_import_violation(&self, language: &str, program: &str) -> Option<String> {
+        // Rules are validated when the problem is saved, so this should never fail
+        let rules = imports::parse_rules(&self.banned_imports).unwrap_or_default();
+        imports::find_violation(&rules, language, program)
+    }
+
+    /// Why a participant can't submit to this problem right now, from its cooldown or attempt
+    /// limit. Only judged submissions count, testing against custom input is always allowed.
+    pub async fn submission_blocked(
+        &self,
+        db: &mut DbPoolConnection,
+        user_id: i64,
+    ) -> Result<Option<String>> {
+        if let Some(max_attempts) = self.max_attempts {
+            if JudgeRun::count(db, user_id, self.id).await? >= max_attempts {
+                return Ok(Some(format!(
+                    "You've used all {max_attempts} attempts for this problem"
+                )));
+            }
+        }
+        if let Some(cooldown) = self.submission_cooldown {
+            if let Some(last) = JudgeRun::get_latest(db, user_id, self.id).await? {
+                let now = chrono::Utc::now().naive_utc();
+                let wait = cooldown - (now - last.ran_at).num_seconds();
+                if wait > 0 {
+                    return Ok(Some(format!(
+                        "Wait {wait} more second{} before submitting to this problem again",
+                        if wait == 1 { "" } else { "s" }
+                    )));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Tagged for Problem {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn tags(&self) -> &str {
+        &self.tags
+    }
+
+    fn difficulty(&self) -> ProblemDifficulty {
+        self.difficulty
+    }
+}
+
+#[inline]
+fn at_least_1<'e>(value: &Option<i64>) -> Result<(), rocket::form::Errors<'e>> {
+    match value {
+        Some(v) if *v < 1 => Err(rocket::form::Error::validation("Must be at least 1").into()),
+        _ => Ok(()),
+    }
+}
+
+#[inline]
+fn not_output_only<'e>(quiz: &bool, output_only: bool) -> Result<(), rocket::form::Errors<'e>> {
+    if *quiz && output_only {
+        Err(rocket::form::Error::validation(
+            "A problem can't be both a quiz and output only",
+        )
+        .into())
+    } else {
+        Ok(())
+    }
 }
 
 #[derive(FromForm)]
@@ -183,11 +440,42 @@ pub struct ProblemForm<'r> {
     cpu_time: i64,
     #[field(validate = range(1..))]
     memory_limit: i64,
+    #[field(validate = imports::check_rules())]
+    banned_imports: &'r str,
+    flag_banned_imports: bool,
+    #[field(validate = tags::check_tags())]
+    tags: &'r str,
+    difficulty: ProblemDifficulty,
+    #[field(validate = range(1..=16))]
+    parallelism: i64,
+    #[field(validate = range(0..=600))]
+    wall_time: i64,
+    allow_network: bool,
+    allowed_languages: &'r str,
+    validator: &'r str,
+    validator_language: &'r str,
+    generator: &'r str,
+    generator_language: &'r str,
+    generator_seeds: &'r str,
+    compare_mode: CompareMode,
+    #[field(validate = with(|e| e.is_finite() && *e >= 0.0, "Must be zero or more"))]
+    abs_epsilon: f64,
+    #[field(validate = with(|e| e.is_finite() && *e >= 0.0, "Must be zero or more"))]
+    rel_epsilon: f64,
+    #[field(validate = at_least_1())]
+    submission_cooldown: Option<i64>,
+    #[field(validate = at_least_1())]
+    max_attempts: Option<i64>,
+    output_only: bool,
+    #[field(validate = not_output_only(self.output_only))]
+    quiz: bool,
     test_cases: Vec<TestCaseForm<'r>>,
 }
 
 pub struct ProblemFormTemplate<'r> {
     problem: Option<&'r Problem>,
+    /// New problems start with the contest's defaults
+    contest: &'r Contest,
     test_cases: Vec<TestCaseForm<'r>>,
 }
 
@@ -199,6 +487,53 @@ impl<'r> TemplatedForm for ProblemFormTemplate<'r> {
                 ("description".to_string(), problem.description.clone()),
                 ("cpu_time".to_string(), problem.cpu_time.to_string()),
                 ("memory_limit".to_string(), problem.memory_limit.to_string()),
+                ("banned_imports".to_string(), problem.banned_imports.clone()),
+                (
+                    "flag_banned_imports".to_string(),
+                    problem.flag_banned_imports.to_string(),
+                ),
+                ("tags".to_string(), problem.tags.replace(',', ", ")),
+                ("difficulty".to_string(), problem.difficulty.into()),
+                ("parallelism".to_string(), problem.parallelism.to_string()),
+                ("wall_time".to_string(), problem.wall_time.to_string()),
+                (
+                    "allow_network".to_string(),
+                    problem.allow_network.to_string(),
+                ),
+                (
+                    "allowed_languages".to_string(),
+                    problem.allowed_languages.replace(',', ", "),
+                ),
+                ("validator".to_string(), problem.validator.clone()),
+                (
+                    "validator_language".to_string(),
+                    problem.validator_language.clone(),
+                ),
+                ("generator".to_string(), problem.generator.clone()),
+                (
+                    "generator_language".to_string(),
+                    problem.generator_language.clone(),
+                ),
+                ("generator_seeds".to_string(), problem.generator_seeds.clone()),
+                ("compare_mode".to_string(), problem.compare_mode.into()),
+                ("abs_epsilon".to_string(), problem.abs_epsilon.to_string()),
+                ("rel_epsilon".to_string(), problem.rel_epsilon.to_string()),
+                (
+                    "submission_cooldown".to_string(),
+                    problem
+                        .submission_cooldown
+                        .map(|s| s.to_string())
+                        .unwrap_or_default(),
+                ),
+                (
+                    "max_attempts".to_string(),
+                    problem
+                        .max_attempts
+                        .map(|a| a.to_string())
+                        .unwrap_or_default(),
+                ),
+                ("output_only".to_string(), problem.output_only.to_string()),
+                ("quiz".to_string(), problem.quiz.to_string()),
             ]);
             for (i, case) in self.test_cases.iter().enumerate() {
                 map.insert(format!("test_cases[{}].stdin", i), case.stdin.to_string());
@@ -214,14 +549,60 @@ impl<'r> TemplatedForm for ProblemFormTemplate<'r> {
                     format!("test_cases[{}].case_insensitive", i),
                     case.case_insensitive.to_string(),
                 );
+                map.insert(
+                    format!("test_cases[{}].compare_mode", i),
+                    case.compare_mode.into(),
+                );
+                map.insert(format!("test_cases[{}].sample", i), case.sample.to_string());
             }
             map
         } else {
             HashMap::from_iter([
                 ("name".to_string(), "".to_string()),
                 ("description".to_string(), "".to_string()),
-                ("cpu_time".to_string(), "1".to_string()),
-                ("memory_limit".to_string(), "125".to_string()),
+                (
+                    "cpu_time".to_string(),
+                    self.contest.default_cpu_time.to_string(),
+                ),
+                (
+                    "memory_limit".to_string(),
+                    self.contest.default_memory_limit.to_string(),
+                ),
+                ("banned_imports".to_string(), "".to_string()),
+                ("flag_banned_imports".to_string(), "false".to_string()),
+                ("tags".to_string(), "".to_string()),
+                (
+                    "difficulty".to_string(),
+                    ProblemDifficulty::default().into(),
+                ),
+                ("parallelism".to_string(), "1".to_string()),
+                ("wall_time".to_string(), "0".to_string()),
+                ("allow_network".to_string(), "false".to_string()),
+                (
+                    "allowed_languages".to_string(),
+                    self.contest.default_languages.replace(',', ", "),
+                ),
+                ("validator".to_string(), String::new()),
+                ("validator_language".to_string(), String::new()),
+                ("generator".to_string(), String::new()),
+                ("generator_language".to_string(), String::new()),
+                ("generator_seeds".to_string(), String::new()),
+                (
+                    "compare_mode".to_string(),
+                    Comparison::default().mode.into(),
+                ),
+                (
+                    "abs_epsilon".to_string(),
+                    Comparison::default().abs_epsilon.to_string(),
+                ),
+                (
+                    "rel_epsilon".to_string(),
+                    Comparison::default().rel_epsilon.to_string(),
+                ),
+                ("submission_cooldown".to_string(), String::new()),
+                ("max_attempts".to_string(), String::new()),
+                ("output_only".to_string(), "false".to_string()),
+                ("quiz".to_string(), "false".to_string()),
             ])
         }
     }
@@ -234,13 +615,31 @@ pub fn stage() -> AdHoc {
             routes![
                 view::list_problems_get,
                 view::view_problem_get,
+                invoke::invoke_get,
                 new::new_problem_get,
                 new::new_problem_post,
                 edit::edit_problem_get,
                 edit::edit_problem_post,
                 delete::delete_problem_get,
                 delete::delete_problem_post,
-                runs::runs
+                preview::preview_problem_post,
+                runs::runs,
+                runs::run_detail,
+                diff::run_diff,
+                comments::run_comment_post,
+                comments::run_comment_delete_post,
+                verdict::run_verdict_post,
+                verdict::run_verdict_clear_post,
+                references::references_get,
+                references::references_post,
+                references::references_delete_post,
+                references::verify_problem_post,
+                starter::starter_get,
+                starter::starter_post,
+                starter::starter_delete_post,
+                generator::generate_cases_post,
+                drafts::save_draft_post,
+                telemetry::telemetry_post
             ],
         )
     })
@@ -9,16 +9,20 @@ use crate::{
         csrf::{CsrfToken, VerifyCsrfToken},
         users::{Admin, User},
     },
+    branding::ParsedColorConfig,
     contests::Contest,
     context_with_base_authed,
     db::DbConnection,
     error::prelude::*,
     leaderboard::LeaderboardManagerHandle,
     messages::Message,
+    run::{CodeInfo, ManagerHandle},
     template::FormTemplateObject,
 };
 
-use super::{cases::TestCase, Problem, ProblemForm, ProblemFormTemplate};
+use super::{
+    audit::audit_statement, cases::TestCase, validator, Problem, ProblemForm, ProblemFormTemplate,
+};
 
 #[get("/<contest_id>/problems/new", rank = 1)]
 pub async fn new_problem_get(
@@ -32,6 +36,7 @@ pub async fn new_problem_get(
         Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
     let form_template = ProblemFormTemplate {
         problem: None,
+        contest: &contest,
         test_cases: vec![],
     };
     let form = FormTemplateObject::get(form_template);
@@ -41,21 +46,37 @@ pub async fn new_problem_get(
     ))
 }
 
+// Has to be a large number of parameters because this is Rocket
+#[allow(clippy::too_many_arguments)]
 #[post("/<contest_id>/problems/new", data = "<form>", rank = 5)]
 pub async fn new_problem_post(
     user: &User,
     admin: Option<&Admin>,
     contest_id: i64,
     mut form: Form<Contextual<'_, ProblemForm<'_>>>,
+    info: &State<CodeInfo>,
     _token: &VerifyCsrfToken,
     leaderboard_handle: &State<LeaderboardManagerHandle>,
+    manager: &State<ManagerHandle>,
+    colors: &State<ParsedColorConfig>,
     mut db: DbConnection,
 ) -> FormResponse {
     let (contest, _) =
         Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
 
-    if let Some(ref value) = form.value {
-        let problem = Problem::temp(contest_id, value);
+    let allowed_languages = form
+        .value
+        .as_ref()
+        .map(|v| info.run_config.normalize_language_list(v.allowed_languages))
+        .transpose()
+        .unwrap_or_else(|why| {
+            form.context
+                .push_error(Error::validation(why).with_name("allowed_languages"));
+            None
+        });
+    if let (Some(value), Some(allowed_languages)) = (form.value.as_ref(), allowed_languages) {
+        let mut problem = Problem::temp(contest_id, value);
+        problem.allowed_languages = allowed_languages;
         if Problem::slug_exists(&mut db, &problem.slug, contest_id, None).await? {
             let err = Error::validation("Problem with this name already exists").with_name("name");
             form.context.push_error(err);
@@ -63,6 +84,11 @@ pub async fn new_problem_post(
             let err =
                 Error::validation("At least one test case is required").with_name("test_cases");
             form.context.push_error(err);
+        } else if let Some(why) =
+            validator::check_cases(manager, user.id, &problem, &value.test_cases).await
+        {
+            form.context
+                .push_error(Error::validation(why).with_name("test_cases"));
         } else {
             let problem = problem.insert(&mut db).await?;
             let test_cases = TestCase::from_vec(problem.id, &value.test_cases);
@@ -71,13 +97,22 @@ pub async fn new_problem_post(
             leaderboard_handle
                 .refresh_leaderboard(&mut db, &contest)
                 .await?;
-            return Ok(Message::success("Problem Created")
-                .to(&format!("/contests/{contest_id}/problems/{}", problem.slug)));
+            let warnings = audit_statement(&problem.description, colors);
+            let message = if warnings.is_empty() {
+                Message::success("Problem Created")
+            } else {
+                Message::info(
+                    "Problem Created, but the statement may be hard for some people to read: {warnings}",
+                )
+                .arg("warnings", warnings.join("; "))
+            };
+            return Ok(message.to(&format!("/contests/{contest_id}/problems/{}", problem.slug)));
         }
     }
 
     let form_template = ProblemFormTemplate {
         problem: None,
+        contest: &contest,
         test_cases: vec![],
     };
     let form = FormTemplateObject::from_rocket_context(form_template, &form.context);
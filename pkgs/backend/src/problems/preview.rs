@@ -0,0 +1,33 @@
+use rocket::{form::Form, post, response::content::RawHtml, FromForm, State};
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        users::{Admin, User},
+    },
+    contests::Contest,
+    db::DbConnection,
+    error::prelude::*,
+    md::MarkdownConfig,
+};
+
+#[derive(FromForm)]
+pub struct PreviewForm<'r> {
+    md: &'r str,
+}
+
+/// Render a problem description while it's being written, exactly as it'll be shown to participants
+#[post("/<contest_id>/problems/preview", data = "<form>")]
+pub async fn preview_problem_post(
+    user: &User,
+    admin: Option<&Admin>,
+    contest_id: i64,
+    form: Form<PreviewForm<'_>>,
+    _token: &VerifyCsrfToken,
+    markdown_config: &State<MarkdownConfig>,
+    mut db: DbConnection,
+) -> ResultResponse<RawHtml<String>> {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let rendered = markdown_config.render(form.md)?;
+    Ok(RawHtml(rendered))
+}
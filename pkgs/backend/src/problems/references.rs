@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use log::info;
+use rocket::{
+    form::{Contextual, Error, Form},
+    get,
+    http::Status,
+    post, FromForm, FromFormField, State,
+};
+use rocket_dyn_templates::Template;
+use sqlx::{encode::IsNull, Decode, Encode, Type};
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+    run::{
+        direct::{run_for_problem, JobOperation},
+        CodeInfo, JobState, ManagerHandle,
+    },
+    template::{FormTemplateObject, TemplatedForm},
+};
+
+use super::{JudgeRun, Problem, TestCase};
+
+/// The verdict a whole run got, judging stops at the first failing case so this is that case's error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, FromFormField)]
+pub enum ReferenceVerdict {
+    Accepted,
+    #[serde(rename = "Wrong Answer")]
+    WrongAnswer,
+    #[serde(rename = "Time Limit Exceeded")]
+    TimeLimitExceeded,
+    #[serde(rename = "Memory Limit Exceeded")]
+    MemoryLimitExceeded,
+    #[serde(rename = "Runtime Error")]
+    RuntimeError,
+    #[serde(rename = "Compilation Error")]
+    CompilationError,
+    /// The run couldn't be judged, never expected so a reference getting this always fails verification
+    #[serde(rename = "Judge Error")]
+    JudgeError,
+}
+
+impl ReferenceVerdict {
+    /// Judging errors are never detailed, so they always start with the name of the error
+    fn from_error(error: &str) -> Self {
+        if error.starts_with("Logic Error") {
+            Self::WrongAnswer
+        } else if error.contains("Time Limit Exceeded") {
+            Self::TimeLimitExceeded
+        } else if error.starts_with("Memory Limit Exceeded") {
+            Self::MemoryLimitExceeded
+        } else if error.starts_with("Runtime Error") || error.starts_with("Output Limit Exceeded") {
+            Self::RuntimeError
+        } else if error.starts_with("Compilation Error") {
+            Self::CompilationError
+        } else {
+            Self::JudgeError
+        }
+    }
+
+    /// The verdict a saved run ended up with, a judge's manual verdict taking the place of the
+    /// automated one
+    pub fn of_run(run: &JudgeRun) -> Self {
+        match (run.manual_verdict, &run.error) {
+            (Some(true), _) => Self::Accepted,
+            (Some(false), _) => Self::WrongAnswer,
+            (None, Some(error)) => Self::from_error(error),
+            (None, None) if run.passed_checks() => Self::Accepted,
+            (None, None) => Self::JudgeError,
+        }
+    }
+
+    /// The verdict of a finished run, and which case caused it if it wasn't accepted
+    fn from_state(state: &JobState) -> (Self, Option<String>) {
+        match state.last_error() {
+            (i, _, Some(error)) => (
+                Self::from_error(&error),
+                Some(format!("Case {}: {error}", i + 1)),
+            ),
+            (_, _, None) => (Self::Accepted, None),
+        }
+    }
+}
+
+impl From<String> for ReferenceVerdict {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "Accepted" => Self::Accepted,
+            "WrongAnswer" => Self::WrongAnswer,
+            "TimeLimitExceeded" => Self::TimeLimitExceeded,
+            "MemoryLimitExceeded" => Self::MemoryLimitExceeded,
+            "RuntimeError" => Self::RuntimeError,
+            "CompilationError" => Self::CompilationError,
+            _ => Self::JudgeError,
+        }
+    }
+}
+
+impl Type<sqlx::Sqlite> for ReferenceVerdict {
+    fn type_info() -> <sqlx::Sqlite as sqlx::Database>::TypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl Encode<'_, sqlx::Sqlite> for ReferenceVerdict {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Sqlite as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> IsNull {
+        let val = format!("{:?}", self);
+        <std::string::String as Encode<'_, sqlx::Sqlite>>::encode_by_ref(&val, buf)
+    }
+}
+
+impl Decode<'_, sqlx::Sqlite> for ReferenceVerdict {
+    fn decode(
+        value: <sqlx::Sqlite as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        Ok(s.into())
+    }
+}
+
+/// A known solution to a problem, verifying the problem checks it still gets the verdict it should
+#[derive(Serialize)]
+pub struct ReferenceSolution {
+    pub id: i64,
+    pub problem_id: i64,
+    pub name: String,
+    pub language: String,
+    #[serde(skip_serializing)]
+    pub program: String,
+    pub expected: ReferenceVerdict,
+    pub last_verdict: Option<ReferenceVerdict>,
+    pub last_detail: Option<String>,
+    #[serde(serialize_with = "crate::times::serialize_option_to_js")]
+    pub verified_at: Option<NaiveDateTime>,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+}
+
+impl ReferenceSolution {
+    pub async fn list_for_problem(db: &mut DbPoolConnection, problem_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            ReferenceSolution,
+            r#"SELECT id, problem_id, name, language, program,
+                expected as "expected: ReferenceVerdict",
+                last_verdict as "last_verdict: ReferenceVerdict",
+                last_detail, verified_at, created_at
+            FROM reference_solution WHERE problem_id = ? ORDER BY created_at ASC"#,
+            problem_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get reference solutions for problem {problem_id}"))
+    }
+
+    pub async fn insert(
+        db: &mut DbPoolConnection,
+        problem_id: i64,
+        form: &ReferenceForm<'_>,
+    ) -> Result {
+        sqlx::query!(
+            "INSERT INTO reference_solution (problem_id, name, language, program, expected) VALUES (?, ?, ?, ?, ?)",
+            problem_id,
+            form.name,
+            form.language,
+            form.program,
+            form.expected
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to add reference solution to problem {problem_id}"))
+    }
+
+    pub async fn delete(db: &mut DbPoolConnection, problem_id: i64, id: i64) -> Result<bool> {
+        sqlx::query!(
+            "DELETE FROM reference_solution WHERE id = ? AND problem_id = ?",
+            id,
+            problem_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .with_context(|| format!("Failed to delete reference solution {id}"))
+    }
+
+    pub fn passed(&self) -> bool {
+        self.last_verdict == Some(self.expected)
+    }
+
+    async fn save_result(
+        &mut self,
+        db: &mut DbPoolConnection,
+        verdict: ReferenceVerdict,
+        detail: Option<String>,
+    ) -> Result {
+        let now = chrono::Utc::now().naive_utc();
+        sqlx::query!(
+            "UPDATE reference_solution SET last_verdict = ?, last_detail = ?, verified_at = ? WHERE id = ?",
+            verdict,
+            detail,
+            now,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .with_context(|| format!("Failed to save result of reference solution {}", self.id))?;
+        self.last_verdict = Some(verdict);
+        self.last_detail = detail;
+        self.verified_at = Some(now);
+        Ok(())
+    }
+}
+
+#[derive(FromForm)]
+pub struct ReferenceForm<'r> {
+    #[field(validate = len(1..=100))]
+    name: &'r str,
+    #[field(validate = len(1..))]
+    language: &'r str,
+    #[field(validate = len(1..))]
+    program: &'r str,
+    #[field(validate = neq(ReferenceVerdict::JudgeError))]
+    expected: ReferenceVerdict,
+}
+
+pub struct ReferenceFormTemplate;
+
+impl TemplatedForm for ReferenceFormTemplate {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        HashMap::from_iter([
+            ("name".to_string(), "".to_string()),
+            ("language".to_string(), "".to_string()),
+            ("program".to_string(), "".to_string()),
+            ("expected".to_string(), "Accepted".to_string()),
+        ])
+    }
+}
+
+fn references_url(contest_id: i64, slug: &str) -> String {
+    format!("/contests/{contest_id}/problems/{slug}/references")
+}
+
+async fn render_references(
+    db: &mut DbPoolConnection,
+    contest: Contest,
+    problem: Problem,
+    user: &User,
+    info: &CodeInfo,
+    form: FormTemplateObject,
+) -> ResultResponse<Template> {
+    let languages = info.run_config.get_languages_for_dropdown();
+    let references = ReferenceSolution::list_for_problem(db, problem.id).await?;
+    let verified = !references.is_empty() && references.iter().all(|r| r.verified_at.is_some());
+    let all_passed = verified && references.iter().all(ReferenceSolution::passed);
+    Ok(Template::render(
+        "problems/references",
+        context_with_base_authed!(
+            user, contest, problem, references, verified, all_passed, languages, form
+        ),
+    ))
+}
+
+#[get("/<contest_id>/problems/<slug>/references")]
+pub async fn references_get(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    info: &State<CodeInfo>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let form = FormTemplateObject::get(ReferenceFormTemplate);
+    render_references(&mut db, contest, problem, user, info, form).await
+}
+
+#[post("/<contest_id>/problems/<slug>/references", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn references_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    info: &State<CodeInfo>,
+    mut form: Form<Contextual<'_, ReferenceForm<'_>>>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    if let Some(ref value) = form.value {
+        if !info.run_config.languages.contains_key(value.language) {
+            form.context
+                .push_error(Error::validation("Unknown language").with_name("language"));
+        } else {
+            ReferenceSolution::insert(&mut db, problem.id, value).await?;
+            return Ok(
+                Message::success("Reference Solution Added").to(&references_url(contest_id, slug))
+            );
+        }
+    }
+    let form = FormTemplateObject::from_rocket_context(ReferenceFormTemplate, &form.context);
+    let template = render_references(&mut db, contest, problem, user, info, form).await?;
+    Err(template.into())
+}
+
+#[post("/<contest_id>/problems/<slug>/references/<reference_id>/delete")]
+pub async fn references_delete_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    reference_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    if !ReferenceSolution::delete(&mut db, problem.id, reference_id).await? {
+        return Err(Status::NotFound.into());
+    }
+    Ok(Message::success("Reference Solution Deleted").to(&references_url(contest_id, slug)))
+}
+
+/// Judge every reference solution against the current limits and test cases, one at a time
+#[post("/<contest_id>/problems/<slug>/references/verify")]
+pub async fn verify_problem_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    manager: &State<ManagerHandle>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let url = references_url(contest_id, slug);
+    let mut references = ReferenceSolution::list_for_problem(&mut db, problem.id).await?;
+    if references.is_empty() {
+        return Ok(Message::error("Add a reference solution to verify this problem").to(&url));
+    }
+    let cases = TestCase::get_for_problem(&mut db, problem.id).await?;
+
+    for reference in references.iter_mut() {
+        let (verdict, detail) = match run_for_problem(
+            manager,
+            user.id,
+            &problem,
+            &reference.language,
+            &reference.program,
+            JobOperation::Judging(cases.clone()),
+        )
+        .await
+        {
+            Ok(state) => ReferenceVerdict::from_state(&state),
+            Err(why) => (ReferenceVerdict::JudgeError, Some(why)),
+        };
+        reference.save_result(&mut db, verdict, detail).await?;
+    }
+
+    let failed = references.iter().filter(|r| !r.passed()).count();
+    info!(
+        "{} verified problem {}, {failed} of {} reference solutions got the wrong verdict",
+        user.id,
+        problem.id,
+        references.len()
+    );
+    if failed == 0 {
+        Ok(Message::success("Every reference solution got its expected verdict").to(&url))
+    } else {
+        Ok(Message::error(
+            "{failed} of {total} reference solutions didn't get their expected verdict",
+        )
+        .arg("failed", failed)
+        .arg("total", references.len())
+        .to(&url))
+    }
+}
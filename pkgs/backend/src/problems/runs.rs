@@ -1,19 +1,23 @@
 use chrono::NaiveDateTime;
 use chrono::TimeZone;
-use rocket::get;
+use rocket::{get, http::Status};
 use rocket_dyn_templates::Template;
 
+use crate::auth::csrf::CsrfToken;
 use crate::auth::users::Admin;
 use crate::auth::users::User;
 use crate::contests::Contest;
 use crate::contests::Participant;
 use crate::context_with_base;
+use crate::context_with_base_authed;
 use crate::db::{DbConnection, DbPoolConnection};
 use crate::error::prelude::*;
-use crate::run::JobState;
+use crate::run::{CaseVerdict, JobState};
+use crate::template::FormTemplateObject;
 use crate::times::format_datetime_human_readable;
 use crate::times::ClientTimeZone;
 
+use super::comments::{RunComment, RunCommentFormTemplate};
 use super::Problem;
 
 #[derive(Debug, Serialize)]
@@ -28,6 +32,23 @@ pub struct JudgeRun {
     pub error: Option<String>,
     #[serde(serialize_with = "crate::times::serialize_to_js")]
     pub ran_at: NaiveDateTime,
+    /// Set when the program broke a rule (e.g. a banned import) that the problem only flags
+    pub flag: Option<String>,
+    /// Identifies the exact program, language and test cases this run was judged with
+    pub cache_key: Option<String>,
+    /// The run whose verdict was reused instead of running this one
+    pub cached_from: Option<i64>,
+    /// Syscall the sandbox stopped the program for, only shown to admins
+    pub blocked_syscall: Option<String>,
+    /// JSON list of [`CaseVerdict`]s, `None` for runs saved before these were kept
+    pub case_verdicts: Option<String>,
+    pub compile_output: Option<String>,
+    /// Verdict a judge set by hand, takes the place of the automated one when set
+    pub manual_verdict: Option<bool>,
+    pub override_reason: Option<String>,
+    pub overridden_by: Option<i64>,
+    #[serde(serialize_with = "crate::times::serialize_option_to_js")]
+    pub overridden_at: Option<NaiveDateTime>,
 }
 
 impl JudgeRun {
@@ -41,6 +62,7 @@ impl JudgeRun {
         total_cases: i64,
         error: Option<String>,
         ran_at: NaiveDateTime,
+        flag: Option<String>,
     ) -> Self {
         Self {
             id: 0,
@@ -52,6 +74,16 @@ impl JudgeRun {
             total_cases,
             error,
             ran_at,
+            flag,
+            cache_key: None,
+            cached_from: None,
+            blocked_syscall: None,
+            case_verdicts: None,
+            compile_output: None,
+            manual_verdict: None,
+            override_reason: None,
+            overridden_by: None,
+            overridden_at: None,
         }
     }
 
@@ -62,9 +94,10 @@ impl JudgeRun {
         language: String,
         state: &JobState,
         ran_at: NaiveDateTime,
+        flag: Option<String>,
     ) -> Self {
         let (amount_run, _, error) = state.last_error();
-        Self::temp(
+        let mut run = Self::temp(
             problem_id,
             user_id,
             amount_run as i64,
@@ -73,7 +106,31 @@ impl JudgeRun {
             state.len() as i64,
             error,
             ran_at,
-        )
+            flag,
+        );
+        run.set_details(state);
+        run
+    }
+
+    /// Copy the parts of a finished job's state that are only shown on the run's own page
+    pub fn set_details(&mut self, state: &JobState) {
+        self.blocked_syscall = state.blocked_syscall().map(str::to_string);
+        self.case_verdicts = serde_json::to_string(&state.case_verdicts()).ok();
+        self.compile_output = state.compile_output().map(str::to_string);
+    }
+
+    pub fn verdicts(&self) -> Vec<CaseVerdict> {
+        self.case_verdicts
+            .as_deref()
+            .and_then(|v| serde_json::from_str(v).ok())
+            .unwrap_or_default()
+    }
+
+    pub async fn get(db: &mut DbPoolConnection, id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(JudgeRun, "SELECT * FROM judge_run WHERE id = ?", id)
+            .fetch_optional(&mut **db)
+            .await
+            .with_context(|| format!("Failed to get run {}", id))
     }
 
     pub async fn list(
@@ -94,6 +151,18 @@ impl JudgeRun {
         .with_context(|| format!("Failed to get runs for user {} and problem {}", user_id, problem_id))
     }
 
+    pub async fn count(db: &mut DbPoolConnection, user_id: i64, problem_id: i64) -> Result<i64> {
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count!: i64\" FROM judge_run WHERE user_id = ? AND problem_id = ?",
+            user_id,
+            problem_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count)
+        .with_context(|| format!("Failed to count runs for user {} and problem {}", user_id, problem_id))
+    }
+
     pub async fn get_latest(
         db: &mut DbPoolConnection,
         user_id: i64,
@@ -117,7 +186,7 @@ impl JudgeRun {
     ) -> Result<Option<Self>> {
         sqlx::query_as!(
             JudgeRun,
-            "SELECT * FROM judge_run WHERE user_id = ? AND problem_id = ? AND amount_run = total_cases AND error IS NULL ORDER BY ran_at DESC LIMIT 1",
+            "SELECT * FROM judge_run WHERE user_id = ? AND problem_id = ? AND COALESCE(manual_verdict, amount_run = total_cases AND error IS NULL) ORDER BY ran_at DESC LIMIT 1",
             user_id,
             problem_id
         )
@@ -126,12 +195,163 @@ impl JudgeRun {
             .with_context(|| format!("Failed to get latest successful run for user {} and problem {}", user_id, problem_id))
     }
 
+    pub async fn get_earliest_success_between(
+        db: &mut DbPoolConnection,
+        user_id: i64,
+        problem_id: i64,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            JudgeRun,
+            "SELECT * FROM judge_run WHERE user_id = ? AND problem_id = ? AND COALESCE(manual_verdict, amount_run = total_cases AND error IS NULL) AND ran_at BETWEEN ? AND ? ORDER BY ran_at ASC LIMIT 1",
+            user_id,
+            problem_id,
+            start,
+            end
+        )
+            .fetch_optional(&mut **db)
+            .await
+            .with_context(|| format!("Failed to get earliest successful run for user {} and problem {}", user_id, problem_id))
+    }
+
+    /// How many rejected runs a user made on a problem from `start` up to but not including `end`
+    pub async fn count_wrong_between(
+        db: &mut DbPoolConnection,
+        user_id: i64,
+        problem_id: i64,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<i64> {
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM judge_run WHERE user_id = ? AND problem_id = ? AND NOT COALESCE(manual_verdict, amount_run = total_cases AND error IS NULL) AND ran_at >= ? AND ran_at < ?",
+            user_id,
+            problem_id,
+            start,
+            end
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count)
+        .with_context(|| format!("Failed to count wrong runs for user {} and problem {}", user_id, problem_id))
+    }
+
+    /// All runs made by (non-judge) participants in a contest, optionally for a single problem, oldest first
+    pub async fn list_for_rejudge(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        problem_id: Option<i64>,
+    ) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            JudgeRun,
+            "SELECT judge_run.* FROM judge_run
+            JOIN problem ON problem.id = judge_run.problem_id
+            JOIN participant ON participant.user_id = judge_run.user_id AND participant.contest_id = problem.contest_id
+            WHERE problem.contest_id = ? AND (? IS NULL OR problem.id = ?) AND participant.is_judge = FALSE AND participant.is_spectator = FALSE
+            ORDER BY judge_run.ran_at ASC",
+            contest_id,
+            problem_id,
+            problem_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get runs to rejudge for contest {}", contest_id))
+    }
+
+    /// The latest runs made by those competing in a contest, newest first
+    pub async fn list_recent_for_contest(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            JudgeRun,
+            "SELECT judge_run.* FROM judge_run
+            JOIN problem ON problem.id = judge_run.problem_id
+            JOIN participant ON participant.user_id = judge_run.user_id AND participant.contest_id = problem.contest_id
+            WHERE problem.contest_id = ? AND participant.is_judge = FALSE AND participant.is_spectator = FALSE
+            ORDER BY judge_run.ran_at DESC LIMIT ?",
+            contest_id,
+            limit
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get recent runs for contest {}", contest_id))
+    }
+
+    /// Whether the user has run anything on the contest's problems
+    pub async fn any_in_contest(
+        db: &mut DbPoolConnection,
+        user_id: i64,
+        contest_id: i64,
+    ) -> Result<bool> {
+        sqlx::query!(
+            "SELECT EXISTS (SELECT 1 FROM judge_run JOIN problem ON problem.id = judge_run.problem_id WHERE judge_run.user_id = ? AND problem.contest_id = ?) AS \"any!: bool\"",
+            user_id,
+            contest_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.any)
+        .with_context(|| format!("Failed to check for runs by user {} in contest {}", user_id, contest_id))
+    }
+
+    /// Latest accepted run of a problem with the given cache key
+    pub async fn find_cached(
+        db: &mut DbPoolConnection,
+        problem_id: i64,
+        cache_key: &str,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            JudgeRun,
+            "SELECT * FROM judge_run WHERE problem_id = ? AND cache_key = ? AND amount_run = total_cases AND error IS NULL AND manual_verdict IS NULL ORDER BY ran_at DESC LIMIT 1",
+            problem_id,
+            cache_key
+        )
+            .fetch_optional(&mut **db)
+            .await
+            .with_context(|| format!("Failed to get cached run for problem {}", problem_id))
+    }
+
+    /// The user who made the run this one reused its verdict from, if it still exists
+    pub async fn cached_from_user(&self, db: &mut DbPoolConnection) -> Result<Option<User>> {
+        if let Some(source_id) = self.cached_from {
+            let source = sqlx::query!("SELECT user_id FROM judge_run WHERE id = ?", source_id)
+                .fetch_optional(&mut **db)
+                .await
+                .with_context(|| format!("Failed to get source of cached run {}", self.id))?;
+            if let Some(source) = source {
+                return User::get(db, source.user_id).await;
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn update_verdict(&self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!(
+            "UPDATE judge_run SET amount_run = ?, total_cases = ?, error = ?, cache_key = ?, cached_from = ?, blocked_syscall = ?, case_verdicts = ?, compile_output = ? WHERE id = ?",
+            self.amount_run,
+            self.total_cases,
+            self.error,
+            self.cache_key,
+            self.cached_from,
+            self.blocked_syscall,
+            self.case_verdicts,
+            self.compile_output,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to update verdict for run {}", self.id))
+    }
+
     pub const MAX_RUNS_PER_USER: i64 = 25;
 
     pub async fn write_to_db(self, db: &mut DbPoolConnection) -> Result<Self> {
         let new = sqlx::query_as!(
             JudgeRun,
-            "INSERT INTO judge_run (problem_id, user_id, amount_run, program, language, total_cases, error, ran_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
+            "INSERT INTO judge_run (problem_id, user_id, amount_run, program, language, total_cases, error, ran_at, flag, cache_key, cached_from, blocked_syscall, case_verdicts, compile_output) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
             self.problem_id,
             self.user_id,
             self.amount_run,
@@ -139,7 +359,13 @@ impl JudgeRun {
             self.language,
             self.total_cases,
             self.error,
-            self.ran_at
+            self.ran_at,
+            self.flag,
+            self.cache_key,
+            self.cached_from,
+            self.blocked_syscall,
+            self.case_verdicts,
+            self.compile_output
         )
             .fetch_one(&mut **db)
             .await.context("Failed to insert new run")?;
@@ -168,9 +394,41 @@ impl JudgeRun {
         Ok(new)
     }
 
+    /// Whether the run passed, going by a judge's manual verdict if there is one
     pub fn success(&self) -> bool {
+        self.manual_verdict.unwrap_or_else(|| self.passed_checks())
+    }
+
+    /// Whether the program passed every test case, ignoring any manual verdict
+    pub fn passed_checks(&self) -> bool {
         self.amount_run == self.total_cases && self.error.is_none()
     }
+
+    /// Set or clear (with `None`) the verdict a judge gave this run by hand
+    pub async fn set_manual_verdict(
+        &mut self,
+        db: &mut DbPoolConnection,
+        verdict: Option<bool>,
+        reason: Option<String>,
+        judge_id: Option<i64>,
+    ) -> Result {
+        self.manual_verdict = verdict;
+        self.override_reason = reason;
+        self.overridden_by = judge_id;
+        self.overridden_at = verdict.map(|_| chrono::offset::Utc::now().naive_utc());
+        sqlx::query!(
+            "UPDATE judge_run SET manual_verdict = ?, override_reason = ?, overridden_by = ?, overridden_at = ? WHERE id = ?",
+            self.manual_verdict,
+            self.override_reason,
+            self.overridden_by,
+            self.overridden_at,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to set manual verdict for run {}", self.id))
+    }
 }
 
 #[get("/<contest_id>/problems/<slug>/runs")]
@@ -206,3 +464,95 @@ pub async fn runs(
         context_with_base!(user, runs, contest, problem, can_edit, formatted_times, max_runs: JudgeRun::MAX_RUNS_PER_USER),
     ))
 }
+
+#[get("/<contest_id>/problems/<slug>/runs/<run_id>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_detail(
+    contest_id: i64,
+    slug: &str,
+    run_id: i64,
+    tz: ClientTimeZone,
+    admin: Option<&Admin>,
+    user: &User,
+    mut db: DbConnection,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let form = FormTemplateObject::get(RunCommentFormTemplate);
+    render_run(&mut db, contest_id, slug, run_id, &tz, admin, user, form).await
+}
+
+/// The run's page, shared with posting comments so it can be shown again with the form's errors
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn render_run(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    slug: &str,
+    run_id: i64,
+    tz: &ClientTimeZone,
+    admin: Option<&Admin>,
+    user: &User,
+    form: FormTemplateObject,
+) -> ResultResponse<Template> {
+    let problem = Problem::get_or_404(db, contest_id, slug).await?;
+    let contest = Contest::get_or_404(db, contest_id).await?;
+    let run = JudgeRun::get(db, run_id)
+        .await?
+        .filter(|r| r.problem_id == problem.id)
+        .ok_or(Status::NotFound)?;
+    let participant = Participant::get(db, contest_id, user.id).await?;
+    let can_edit = admin.is_some() || participant.as_ref().is_some_and(|p| p.is_judge);
+    let is_owner = run.user_id == user.id;
+    if !is_owner && !can_edit {
+        return Err(Status::NotFound.into());
+    }
+    let run_user = User::get(db, run.user_id).await?.ok_or(Status::NotFound)?;
+    let verdicts = run.verdicts();
+    let overridden_by = match run.overridden_by {
+        Some(id) => User::get(db, id).await?,
+        None => None,
+    };
+    let tz = tz.timezone();
+    let formatted_time = format_datetime_human_readable(tz.from_utc_datetime(&run.ran_at));
+    let resubmit_code = serde_json::to_string(&(run.program.as_str(), run.language.as_str()))
+        .context("Failed to serialize run code")?;
+
+    let comments = RunComment::list_for_run(db, run.id).await?;
+    let comments_visible =
+        can_edit || contest.comments_during_contest || contest.has_ended_for(participant.as_ref());
+    let hidden_comments = if comments_visible { 0 } else { comments.len() };
+    let comments = if comments_visible { comments } else { vec![] };
+    let mut comment_authors = Vec::with_capacity(comments.len());
+    for comment in comments.iter() {
+        let author = match comment.author_id {
+            Some(id) => User::get(db, id).await?,
+            None => None,
+        };
+        comment_authors.push(author);
+    }
+    let comment_times = comments
+        .iter()
+        .map(|c| format_datetime_human_readable(tz.from_utc_datetime(&c.created_at)))
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "problems/run",
+        context_with_base_authed!(
+            user,
+            run,
+            run_user,
+            is_owner,
+            verdicts,
+            contest,
+            problem,
+            can_edit,
+            formatted_time,
+            resubmit_code,
+            overridden_by,
+            comments,
+            comment_authors,
+            comment_times,
+            hidden_comments,
+            form
+        ),
+    ))
+}
@@ -0,0 +1,294 @@
+//! Starter code for "fill in the function" problems, the editor starts with it instead of the
+//! language's default code. Anything in the locked prefix or suffix has to be kept as is, which
+//! is checked in `RunManager::request_job` so every way of submitting gets it.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use rocket::{
+    form::{Contextual, Error, Form},
+    get,
+    http::Status,
+    post, FromForm, State,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    messages::Message,
+    run::CodeInfo,
+    template::{FormTemplateObject, TemplatedForm},
+};
+
+use super::Problem;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StarterCode {
+    pub problem_id: i64,
+    pub language: String,
+    pub locked_prefix: String,
+    pub code: String,
+    pub locked_suffix: String,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub updated_at: NaiveDateTime,
+}
+
+/// Browsers send form textareas with `\r\n`, the editor uses `\n`
+fn normalize(code: &str) -> String {
+    code.replace("\r\n", "\n")
+}
+
+impl StarterCode {
+    pub async fn list_for_problem(db: &mut DbPoolConnection, problem_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            StarterCode,
+            "SELECT * FROM starter_code WHERE problem_id = ? ORDER BY language",
+            problem_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get starter code for problem {problem_id}"))
+    }
+
+    pub async fn get(
+        db: &mut DbPoolConnection,
+        problem_id: i64,
+        language: &str,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            StarterCode,
+            "SELECT * FROM starter_code WHERE problem_id = ? AND language = ?",
+            problem_id,
+            language
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get {language} starter code for problem {problem_id}"))
+    }
+
+    /// Add starter code for a language, replacing any it already had
+    pub async fn save(
+        db: &mut DbPoolConnection,
+        problem_id: i64,
+        form: &StarterForm<'_>,
+    ) -> Result {
+        let now = chrono::offset::Utc::now().naive_utc();
+        let locked_prefix = normalize(form.locked_prefix);
+        let code = normalize(form.code);
+        let locked_suffix = normalize(form.locked_suffix);
+        sqlx::query!(
+            "INSERT INTO starter_code (problem_id, language, locked_prefix, code, locked_suffix, updated_at) VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (problem_id, language) DO UPDATE SET locked_prefix = excluded.locked_prefix, code = excluded.code, locked_suffix = excluded.locked_suffix, updated_at = excluded.updated_at",
+            problem_id,
+            form.language,
+            locked_prefix,
+            code,
+            locked_suffix,
+            now
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to save starter code for problem {problem_id}"))
+    }
+
+    pub async fn delete(
+        db: &mut DbPoolConnection,
+        problem_id: i64,
+        language: &str,
+    ) -> Result<bool> {
+        sqlx::query!(
+            "DELETE FROM starter_code WHERE problem_id = ? AND language = ?",
+            problem_id,
+            language
+        )
+        .execute(&mut **db)
+        .await
+        .map(|r| r.rows_affected() > 0)
+        .with_context(|| {
+            format!("Failed to delete {language} starter code of problem {problem_id}")
+        })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        !self.locked_prefix.trim().is_empty() || !self.locked_suffix.trim().is_empty()
+    }
+
+    /// What the editor starts with
+    pub fn full(&self) -> String {
+        format!("{}{}{}", self.locked_prefix, self.code, self.locked_suffix)
+    }
+
+    /// Why a program doesn't keep the locked parts, if it doesn't. Whitespace at the end of
+    /// either is ignored since editors like to add or remove a trailing newline.
+    pub fn check(&self, program: &str) -> Result<(), String> {
+        let program = normalize(program);
+        let program = program.trim_end();
+        let prefix = self.locked_prefix.trim_end();
+        let suffix = self.locked_suffix.trim_end();
+        if !program.starts_with(prefix) {
+            return Err(
+                "Your program has to start with the problem's starter code as is, use the reset button to get it back"
+                    .to_string(),
+            );
+        }
+        if !program[prefix.len()..].ends_with(suffix) {
+            return Err(
+                "Your program has to end with the problem's starter code as is, use the reset button to get it back"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// The languages JSON the editor gets, with this problem's starter code in place of each
+    /// language's default code
+    pub fn code_info(languages_json: &str, starters: &[Self]) -> Result<String> {
+        if starters.is_empty() {
+            return Ok(languages_json.to_string());
+        }
+        let mut info = serde_json::from_str::<serde_json::Value>(languages_json)
+            .context("Failed to parse languages JSON")?;
+        for starter in starters {
+            let Some(lang) = info
+                .get_mut(&starter.language)
+                .and_then(|l| l.as_object_mut())
+            else {
+                continue;
+            };
+            lang.insert("defaultCode".to_string(), starter.full().into());
+            if starter.is_locked() {
+                lang.insert(
+                    "lockedPrefix".to_string(),
+                    starter.locked_prefix.clone().into(),
+                );
+                lang.insert(
+                    "lockedSuffix".to_string(),
+                    starter.locked_suffix.clone().into(),
+                );
+            }
+        }
+        serde_json::to_string(&info).context("Failed to serialize languages JSON")
+    }
+}
+
+#[derive(FromForm)]
+pub struct StarterForm<'r> {
+    #[field(validate = len(1..))]
+    language: &'r str,
+    #[field(validate = len(..=10000))]
+    locked_prefix: &'r str,
+    #[field(validate = len(..=50000))]
+    code: &'r str,
+    #[field(validate = len(..=10000))]
+    locked_suffix: &'r str,
+}
+
+pub struct StarterFormTemplate;
+
+impl TemplatedForm for StarterFormTemplate {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        HashMap::from_iter([
+            ("language".to_string(), "".to_string()),
+            ("locked_prefix".to_string(), "".to_string()),
+            ("code".to_string(), "".to_string()),
+            ("locked_suffix".to_string(), "".to_string()),
+        ])
+    }
+}
+
+fn starter_url(contest_id: i64, slug: &str) -> String {
+    format!("/contests/{contest_id}/problems/{slug}/starter")
+}
+
+async fn render_starter(
+    db: &mut DbPoolConnection,
+    contest: Contest,
+    problem: Problem,
+    user: &User,
+    info: &CodeInfo,
+    form: FormTemplateObject,
+) -> ResultResponse<Template> {
+    let mut languages = info.run_config.get_languages_for_dropdown();
+    languages.retain(|(key, _)| problem.allows_language(key));
+    let starters = StarterCode::list_for_problem(db, problem.id).await?;
+    Ok(Template::render(
+        "problems/starter",
+        context_with_base_authed!(user, contest, problem, starters, languages, form),
+    ))
+}
+
+#[get("/<contest_id>/problems/<slug>/starter")]
+pub async fn starter_get(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    info: &State<CodeInfo>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let form = FormTemplateObject::get(StarterFormTemplate);
+    render_starter(&mut db, contest, problem, user, info, form).await
+}
+
+#[post("/<contest_id>/problems/<slug>/starter", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn starter_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    info: &State<CodeInfo>,
+    mut form: Form<Contextual<'_, StarterForm<'_>>>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (contest, _) =
+        Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    if let Some(ref value) = form.value {
+        if !info.run_config.languages.contains_key(value.language) {
+            form.context
+                .push_error(Error::validation("Unknown language").with_name("language"));
+        } else if !problem.allows_language(value.language) {
+            form.context.push_error(
+                Error::validation("This problem doesn't allow this language").with_name("language"),
+            );
+        } else {
+            StarterCode::save(&mut db, problem.id, value).await?;
+            return Ok(Message::success("Starter Code Saved").to(&starter_url(contest_id, slug)));
+        }
+    }
+    let form = FormTemplateObject::from_rocket_context(StarterFormTemplate, &form.context);
+    let template = render_starter(&mut db, contest, problem, user, info, form).await?;
+    Err(template.into())
+}
+
+#[post("/<contest_id>/problems/<slug>/starter/<language>/delete")]
+pub async fn starter_delete_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    language: &str,
+    user: &User,
+    admin: Option<&Admin>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    Contest::get_or_404_assert_can_edit(&mut db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    if !StarterCode::delete(&mut db, problem.id, language).await? {
+        return Err(Status::NotFound.into());
+    }
+    Ok(Message::success("Starter Code Removed").to(&starter_url(contest_id, slug)))
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeSet;
+
+use rocket::{form, FromForm, FromFormField};
+use sqlx::{encode::IsNull, Decode, Encode, Type};
+
+/// How hard a problem is, set by the problem's author
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    FromFormField,
+    Default,
+)]
+pub enum ProblemDifficulty {
+    /// The author didn't pick a difficulty
+    #[default]
+    Unrated,
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl From<String> for ProblemDifficulty {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "Easy" => Self::Easy,
+            "Medium" => Self::Medium,
+            "Hard" => Self::Hard,
+            _ => Self::Unrated,
+        }
+    }
+}
+
+impl From<ProblemDifficulty> for String {
+    fn from(d: ProblemDifficulty) -> Self {
+        format!("{:?}", d)
+    }
+}
+
+impl Type<sqlx::Sqlite> for ProblemDifficulty {
+    fn type_info() -> <sqlx::Sqlite as sqlx::Database>::TypeInfo {
+        <String as Type<sqlx::Sqlite>>::type_info()
+    }
+}
+
+impl Encode<'_, sqlx::Sqlite> for ProblemDifficulty {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Sqlite as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> IsNull {
+        let val = format!("{:?}", self);
+        <std::string::String as Encode<'_, sqlx::Sqlite>>::encode_by_ref(&val, buf)
+    }
+}
+
+impl Decode<'_, sqlx::Sqlite> for ProblemDifficulty {
+    fn decode(
+        value: <sqlx::Sqlite as sqlx::database::HasValueRef<'_>>::ValueRef,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let s = <String as Decode<sqlx::Sqlite>>::decode(value)?;
+        Ok(s.into())
+    }
+}
+
+const MAX_TAGS: usize = 10;
+const MAX_TAG_LEN: usize = 32;
+
+/// Turn user input like "Graphs, dp ,graphs" into the stored form, "dp,graphs"
+pub fn normalize_tags(tags: &str) -> String {
+    tags.split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn check_tags<'v>(tags: &str) -> form::Result<'v, ()> {
+    let tags = normalize_tags(tags);
+    let tags = tags
+        .split(',')
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>();
+    if tags.len() > MAX_TAGS {
+        Err(form::Error::validation(format!("At most {MAX_TAGS} tags are allowed")).into())
+    } else if let Some(tag) = tags.iter().find(|t| t.len() > MAX_TAG_LEN) {
+        Err(form::Error::validation(format!(
+            "Tag \"{tag}\" is longer than {MAX_TAG_LEN} characters"
+        ))
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Anything listed alongside other problems that can be filtered by tag and difficulty
+pub trait Tagged {
+    fn name(&self) -> &str;
+    /// Normalized tags, see [`normalize_tags`]
+    fn tags(&self) -> &str;
+    fn difficulty(&self) -> ProblemDifficulty;
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags().split(',').any(|t| t == tag)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, FromFormField, Default)]
+pub enum ProblemSort {
+    /// Whatever order the list came in
+    #[default]
+    Default,
+    Name,
+    Easiest,
+    Hardest,
+}
+
+/// Query string for narrowing down a list of problems, e.g. `?tag=graphs&difficulty=Easy&sort=Name`.
+/// Invalid or missing values are ignored so a bad link still shows every problem.
+#[derive(FromForm, Serialize, Default)]
+pub struct ProblemFilter {
+    pub tag: Option<String>,
+    pub difficulty: Option<ProblemDifficulty>,
+    pub sort: Option<ProblemSort>,
+}
+
+impl ProblemFilter {
+    pub fn apply<T: Tagged>(&self, problems: &mut Vec<T>) {
+        let tag = self
+            .tag
+            .as_ref()
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty());
+        problems.retain(|p| {
+            tag.as_ref().is_none_or(|t| p.has_tag(t))
+                && self.difficulty.is_none_or(|d| p.difficulty() == d)
+        });
+        match self.sort.unwrap_or_default() {
+            ProblemSort::Default => {}
+            ProblemSort::Name => problems.sort_by_key(|p| p.name().to_lowercase()),
+            // Unrated problems go last either way
+            ProblemSort::Easiest => problems
+                .sort_by_key(|p| (p.difficulty() == ProblemDifficulty::Unrated, p.difficulty())),
+            ProblemSort::Hardest => {
+                problems.sort_by_key(|p| std::cmp::Reverse(p.difficulty()));
+            }
+        }
+    }
+
+    /// Every tag used by any of the problems, for showing as filter options
+    pub fn all_tags<T: Tagged>(problems: &[T]) -> Vec<String> {
+        problems
+            .iter()
+            .flat_map(|p| p.tags().split(','))
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
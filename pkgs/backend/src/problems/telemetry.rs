@@ -0,0 +1,164 @@
+//! Pastes and focus loss in the problem editor, recorded for contests that turn on
+//! `editor_telemetry` so judges can see who might be worth a closer look. They're signals,
+//! not proof, so nothing acts on them automatically.
+
+use rocket::{form::Form, http::Status, post, FromForm};
+
+use crate::{
+    auth::{csrf::VerifyCsrfToken, users::User},
+    contests::{Contest, Participant},
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+};
+
+use super::Problem;
+
+/// Pastes at least this long count as large, the editor only reports those
+pub const LARGE_PASTE_CHARS: i64 = 200;
+
+/// Most of each counter a single report can add, the editor reports every 30 seconds
+const MAX_EVENTS_PER_REPORT: isize = 1000;
+const MAX_CHARS_PER_REPORT: isize = 10_000_000;
+
+/// A participant's totals across a contest's problems
+#[derive(Serialize)]
+pub struct TelemetryReport {
+    pub p_id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub large_pastes: i64,
+    pub pasted_chars: i64,
+    pub focus_losses: i64,
+    /// Labels of problems with large pastes, e.g. `A, C`
+    pub pasted_into: String,
+    /// A rough ranking for judges to start from, see [`TelemetryReport::score`]
+    pub score: i64,
+    /// `High`, `Medium` or `Low`
+    pub risk: &'static str,
+}
+
+impl TelemetryReport {
+    /// Pasting a lot of code counts for more than switching windows, participants switch to read
+    /// the statement or docs all the time
+    fn score(large_pastes: i64, pasted_chars: i64, focus_losses: i64) -> i64 {
+        large_pastes * 5 + pasted_chars / 1000 + focus_losses
+    }
+
+    fn risk(score: i64) -> &'static str {
+        if score >= 30 {
+            "High"
+        } else if score >= 10 {
+            "Medium"
+        } else {
+            "Low"
+        }
+    }
+
+    /// Everyone with anything recorded in a contest, riskiest first
+    pub async fn list(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
+        let rows = sqlx::query!(
+            "SELECT participant.p_id, user.id AS user_id, COALESCE(user.display_name, user.default_display_name) AS name,
+                SUM(editor_telemetry.large_pastes) AS large_pastes, SUM(editor_telemetry.pasted_chars) AS pasted_chars,
+                SUM(editor_telemetry.focus_losses) AS focus_losses,
+                GROUP_CONCAT(CASE WHEN editor_telemetry.large_pastes > 0 THEN problem.label END, ', ') AS pasted_into
+            FROM editor_telemetry
+            JOIN participant ON participant.p_id = editor_telemetry.participant_id
+            JOIN user ON user.id = participant.user_id
+            JOIN problem ON problem.id = editor_telemetry.problem_id
+            WHERE participant.contest_id = ?
+            GROUP BY participant.p_id",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't get editor telemetry of contest {contest_id}"))?;
+        let mut reports = rows
+            .into_iter()
+            .map(|r| {
+                let score = Self::score(r.large_pastes, r.pasted_chars, r.focus_losses);
+                Self {
+                    p_id: r.p_id,
+                    user_id: r.user_id,
+                    name: r.name,
+                    large_pastes: r.large_pastes,
+                    pasted_chars: r.pasted_chars,
+                    focus_losses: r.focus_losses,
+                    pasted_into: r.pasted_into.unwrap_or_default(),
+                    score,
+                    risk: Self::risk(score),
+                }
+            })
+            .collect::<Vec<_>>();
+        reports.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        Ok(reports)
+    }
+
+    pub async fn clear(db: &mut DbPoolConnection, contest_id: i64) -> Result {
+        sqlx::query!(
+            "DELETE FROM editor_telemetry WHERE problem_id IN (SELECT id FROM problem WHERE contest_id = ?)",
+            contest_id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Couldn't clear editor telemetry of contest {contest_id}"))
+    }
+}
+
+#[derive(FromForm)]
+pub struct TelemetryForm {
+    #[field(validate = range(0..=MAX_EVENTS_PER_REPORT))]
+    large_pastes: i64,
+    #[field(validate = range(0..=MAX_CHARS_PER_REPORT))]
+    pasted_chars: i64,
+    #[field(validate = range(0..=MAX_EVENTS_PER_REPORT))]
+    focus_losses: i64,
+}
+
+/// What the problem page's editor saw since its last report, only kept for competitors while
+/// the contest is running
+#[post("/<contest_id>/problems/<slug>/telemetry", data = "<form>")]
+pub async fn telemetry_post(
+    contest_id: i64,
+    slug: &str,
+    user: &User,
+    form: Form<TelemetryForm>,
+    mut db: DbConnection,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Status> {
+    let contest = Contest::get_or_404(&mut db, contest_id).await?;
+    if !contest.editor_telemetry {
+        return Err(Status::NotFound.into());
+    }
+    let problem = Problem::get_or_404(&mut db, contest_id, slug).await?;
+    let participant = Participant::get(&mut db, contest_id, user.id)
+        .await?
+        .filter(|p| p.competes() && contest.is_running_for(Some(p)));
+    let Some(participant) = participant else {
+        return Ok(Status::NoContent);
+    };
+    if form.large_pastes == 0 && form.focus_losses == 0 {
+        return Ok(Status::NoContent);
+    }
+    let now = chrono::offset::Utc::now().naive_utc();
+    sqlx::query!(
+        "INSERT INTO editor_telemetry (participant_id, problem_id, large_pastes, pasted_chars, focus_losses, updated_at) VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(participant_id, problem_id) DO UPDATE SET large_pastes = large_pastes + excluded.large_pastes,
+            pasted_chars = pasted_chars + excluded.pasted_chars, focus_losses = focus_losses + excluded.focus_losses, updated_at = excluded.updated_at",
+        participant.p_id,
+        problem.id,
+        form.large_pastes,
+        form.pasted_chars,
+        form.focus_losses,
+        now
+    )
+    .execute(&mut **db)
+    .await
+    .with_context(|| {
+        format!(
+            "Couldn't record editor telemetry for participant {} on problem {}",
+            participant.p_id, problem.id
+        )
+    })?;
+    Ok(Status::NoContent)
+}
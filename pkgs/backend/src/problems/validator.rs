@@ -0,0 +1,48 @@
+use crate::run::{
+    direct::{run_for_problem, JobOperation},
+    ManagerHandle,
+};
+
+use super::{cases::TestCaseForm, Problem};
+
+/// Run the problem's validator against every case's input, returning why the first case it
+/// rejected was rejected. The validator should exit with a non-zero code for malformed input.
+pub async fn check_cases(
+    manager: &ManagerHandle,
+    user_id: i64,
+    problem: &Problem,
+    cases: &[TestCaseForm<'_>],
+) -> Option<String> {
+    for (i, case) in cases.iter().enumerate() {
+        if let Err(why) = check_input(manager, user_id, problem, case.stdin).await {
+            return Some(format!("Case {} {why}", i + 1));
+        }
+    }
+    None
+}
+
+/// Run the problem's validator against one input, the error finishes a sentence about the case
+pub async fn check_input(
+    manager: &ManagerHandle,
+    user_id: i64,
+    problem: &Problem,
+    stdin: &str,
+) -> Result<(), String> {
+    if problem.validator.is_empty() {
+        return Ok(());
+    }
+    let state = run_for_problem(
+        manager,
+        user_id,
+        problem,
+        &problem.validator_language,
+        &problem.validator,
+        JobOperation::Testing(stdin.to_string()),
+    )
+    .await
+    .map_err(|why| format!("couldn't be validated: {why}"))?;
+    match state.last_error() {
+        (_, _, Some(error)) => Err(format!("was rejected by the validator: {error}")),
+        (_, _, None) => Ok(()),
+    }
+}
@@ -0,0 +1,133 @@
+use log::info;
+use rocket::{form::Form, http::Status, post, FromForm, State};
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        users::{Admin, User},
+    },
+    contests::Contest,
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    leaderboard::LeaderboardManagerHandle,
+    messages::Message,
+};
+
+use super::{JudgeRun, Problem, ProblemCompletion};
+
+#[derive(FromForm)]
+pub struct ManualVerdictForm<'r> {
+    accepted: bool,
+    #[field(validate = len(1..=500))]
+    reason: &'r str,
+}
+
+/// Set (or clear) a run's manual verdict, then fix up the completion and leaderboard if it changed whether the run passed
+#[allow(clippy::too_many_arguments)]
+async fn apply_verdict(
+    db: &mut DbPoolConnection,
+    contest_id: i64,
+    slug: &str,
+    run_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    leaderboard_handle: &LeaderboardManagerHandle,
+    verdict: Option<(bool, &str)>,
+) -> ResultResponse<()> {
+    let (contest, _) = Contest::get_or_404_assert_can_edit(db, contest_id, user, admin).await?;
+    let problem = Problem::get_or_404(db, contest_id, slug).await?;
+    let mut run = JudgeRun::get(db, run_id)
+        .await?
+        .filter(|r| r.problem_id == problem.id)
+        .ok_or(Status::NotFound)?;
+
+    let was_success = run.success();
+    match verdict {
+        Some((accepted, reason)) => {
+            run.set_manual_verdict(db, Some(accepted), Some(reason.to_string()), Some(user.id))
+                .await?;
+            info!(
+                "{} overrode the verdict of run {} to {}: {}",
+                user.id,
+                run.id,
+                if accepted { "accepted" } else { "rejected" },
+                reason
+            );
+        }
+        None => {
+            run.set_manual_verdict(db, None, None, None).await?;
+            info!("{} removed the manual verdict of run {}", user.id, run.id);
+        }
+    }
+    let now_success = run.success();
+
+    if was_success != now_success {
+        ProblemCompletion::update_for_run(db, &contest, &run, now_success).await?;
+        let mut leaderboard_manager = leaderboard_handle.lock().await;
+        leaderboard_manager
+            .refresh_leaderboard(db, &contest)
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[post("/<contest_id>/problems/<slug>/runs/<run_id>/verdict", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_verdict_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    run_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    leaderboard_handle: &State<LeaderboardManagerHandle>,
+    form: Form<ManualVerdictForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let reason = form.reason.trim();
+    let url = format!("/contests/{contest_id}/problems/{slug}/runs/{run_id}");
+    if reason.is_empty() {
+        return Ok(Message::error("Please give a reason for overriding the verdict").to(&url));
+    }
+    apply_verdict(
+        &mut db,
+        contest_id,
+        slug,
+        run_id,
+        user,
+        admin,
+        leaderboard_handle,
+        Some((form.accepted, reason)),
+    )
+    .await?;
+    Ok(Message::success("Verdict Overridden").to(&url))
+}
+
+#[post("/<contest_id>/problems/<slug>/runs/<run_id>/verdict/clear")]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_verdict_clear_post(
+    mut db: DbConnection,
+    contest_id: i64,
+    slug: &str,
+    run_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    leaderboard_handle: &State<LeaderboardManagerHandle>,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    apply_verdict(
+        &mut db,
+        contest_id,
+        slug,
+        run_id,
+        user,
+        admin,
+        leaderboard_handle,
+        None,
+    )
+    .await?;
+    Ok(Message::success("Manual Verdict Removed").to(&format!(
+        "/contests/{contest_id}/problems/{slug}/runs/{run_id}"
+    )))
+}
@@ -1,22 +1,27 @@
-use rocket::{get, State};
+use rocket::{get, http::Status, State};
 use rocket_dyn_templates::Template;
 
 use crate::{
+    archive::ArchivedProblem,
     auth::users::{Admin, User},
-    contests::{Contest, Participant},
+    contests::{Contest, Participant, ParticipantRole},
     context_with_base,
     db::DbConnection,
     error::prelude::*,
     run::CodeInfo,
 };
 
-use super::{JudgeRun, Problem, ProblemCompletion, TestCase};
+use super::{
+    drafts::CodeDraft, telemetry::LARGE_PASTE_CHARS, JudgeRun, Problem, ProblemCompletion,
+    ProblemFilter, StarterCode, TestCase,
+};
 
-#[get("/<contest_id>/problems")]
+#[get("/<contest_id>/problems?<filter..>")]
 pub async fn list_problems_get(
     user: Option<&User>,
     admin: Option<&Admin>,
     contest_id: i64,
+    filter: ProblemFilter,
     mut db: DbConnection,
 ) -> ResultResponse<Template> {
     let contest = Contest::get_or_404(&mut db, contest_id).await?;
@@ -25,20 +30,69 @@ pub async fn list_problems_get(
     } else {
         None
     };
+    if !contest.is_visible_to(participant.as_ref(), admin) {
+        return Err(Status::NotFound.into());
+    }
     let is_judge = participant.as_ref().is_some_and(|p| p.is_judge);
     let is_admin = admin.is_some();
-    let can_see = is_admin || is_judge || contest.has_started();
-    let problems = if can_see {
+    let needs_rules = !is_admin
+        && participant
+            .as_ref()
+            .is_some_and(|p| p.needs_to_accept_rules(&contest));
+    let pending = !is_admin && !is_judge && participant.as_ref().is_some_and(|p| p.pending);
+    let can_see = is_admin || is_judge || (contest.has_started() && !needs_rules && !pending);
+    let mut problems = if can_see {
         Problem::list(&mut db, contest_id).await?
     } else {
         vec![]
     };
+    let tags = ProblemFilter::all_tags(&problems);
+    filter.apply(&mut problems);
     Ok(Template::render(
         "problems",
-        context_with_base!(user, problems, is_admin, participant, started: can_see, contest, can_edit: is_judge || is_admin),
+        context_with_base!(user, problems, tags, filter, is_admin, participant, needs_rules, pending, started: can_see, contest, can_edit: is_judge || is_admin),
     ))
 }
 
+/// The code the editor starts with when there's nothing newer saved locally, as JSON.
+/// A draft wins unless a newer run came from somewhere other than the editor, like an upload.
+/// Once there's a draft the server's copy is newer than what the editor saved locally, so the
+/// last element tells it to prefer this one.
+pub(super) fn most_recent_code(
+    draft: &Option<CodeDraft>,
+    last_run: &Option<JudgeRun>,
+) -> Result<String> {
+    let most_recent_code = match (draft, last_run) {
+        (Some(d), Some(lr)) if lr.ran_at <= d.updated_at => {
+            Some((d.program.as_str(), d.language.as_str(), true))
+        }
+        (Some(d), None) => Some((d.program.as_str(), d.language.as_str(), true)),
+        (d, lr) => lr
+            .as_ref()
+            .map(|lr| (lr.program.as_str(), lr.language.as_str(), d.is_some())),
+    };
+    serde_json::to_string(&most_recent_code).context("Failed to serialize most recent code")
+}
+
+/// Languages the problem allows for the editor's dropdown, and which one it starts with
+pub(super) fn editor_languages<'a>(
+    info: &'a CodeInfo,
+    problem: &Problem,
+    user: Option<&'a User>,
+) -> (Vec<(&'a String, &'a String)>, &'a String) {
+    let mut languages = info.run_config.get_languages_for_dropdown();
+    languages.retain(|(key, _)| problem.allows_language(key));
+    let default_language = user
+        .map(|u| &u.default_language)
+        .filter(|l| info.run_config.languages.contains_key(*l))
+        .into_iter()
+        .chain([&info.run_config.default_language])
+        .find(|l| problem.allows_language(l))
+        .or_else(|| languages.first().map(|(key, _)| *key))
+        .unwrap_or(&info.run_config.default_language);
+    (languages, default_language)
+}
+
 #[get("/<contest_id>/problems/<slug>", rank = 10)]
 pub async fn view_problem_get(
     user: Option<&User>,
@@ -63,18 +117,34 @@ pub async fn view_problem_get(
         .await
         .unwrap_or(0);
 
+    let samples = TestCase::get_samples_for_problem(&mut db, problem.id).await?;
+
     let last_run = if let Some(user) = user {
         JudgeRun::get_latest(&mut db, user.id, problem.id).await?
     } else {
         None
     };
 
-    let most_recent_code = serde_json::to_string(
-        &last_run
+    let attempts_used = match (user, &participant) {
+        (Some(user), Some(p)) if problem.max_attempts.is_some() && p.competes() => {
+            Some(JudgeRun::count(&mut db, user.id, problem.id).await?)
+        }
+        _ => None,
+    };
+
+    // Participants are told on the rules page, see `telemetry`
+    let telemetry = contest.editor_telemetry
+        && participant
             .as_ref()
-            .map(|lr| (lr.program.as_str(), lr.language.as_str())),
-    )
-    .context("Failed to serialize most recent code")?;
+            .is_some_and(|p| p.competes() && contest.is_running_for(Some(p)));
+
+    let draft = if let Some(user) = user {
+        CodeDraft::get(&mut db, user.id, problem.id).await?
+    } else {
+        None
+    };
+
+    let most_recent_code = most_recent_code(&draft, &last_run)?;
 
     let last_run = last_run
         .filter(|r| r.total_cases == case_count) // Don't show runs when test cases have changed
@@ -82,12 +152,14 @@ pub async fn view_problem_get(
             r.error.is_some() || completion.map(|c| c.completed_at.is_some()).unwrap_or(true)
         }); // Don't show run if judge overrode completion
 
-    let languages = info.run_config.get_languages_for_dropdown();
-    let code_info = &info.languages_json;
-    let default_language = user
-        .map(|u| &u.default_language)
-        .filter(|l| info.run_config.languages.contains_key(*l))
-        .unwrap_or(&info.run_config.default_language);
+    let archived = ArchivedProblem::is_published(&mut db, problem.id).await?;
+
+    let (languages, default_language) = editor_languages(info, &problem, user);
+    let starters = StarterCode::list_for_problem(&mut db, problem.id).await?;
+    let code_info = StarterCode::code_info(&info.languages_json, &starters)?;
+    let language_limits = info
+        .run_config
+        .limits_per_language((problem.cpu_time as u64, problem.memory_limit as u64)); // `as` is safe due to DB constraint
 
     Ok(Template::render(
         "problems/view",
@@ -96,14 +168,22 @@ pub async fn view_problem_get(
             problem,
             last_run,
             case_count,
+            samples,
             most_recent_code,
-            ended: contest.has_ended(),
+            ended: contest.has_ended_for(participant.as_ref()),
             contest,
             code_info,
             languages,
+            language_limits,
             default_language,
             can_edit,
-            participating: participant.is_some_and(|p| !p.is_judge),
+            is_admin: admin.is_some(),
+            attempts_used,
+            telemetry,
+            large_paste_chars: LARGE_PASTE_CHARS,
+            participating: participant.is_some_and(|p| p.role() == ParticipantRole::Competitor),
+            practice: false,
+            archived,
         ),
     ))
 }
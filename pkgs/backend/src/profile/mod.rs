@@ -3,16 +3,21 @@ use rocket::{fairing::AdHoc, get, routes, State};
 use rocket_dyn_templates::Template;
 
 use crate::{
-    auth::users::User,
-    contests::{Contest, Participant},
+    auth::users::{Admin, User},
+    contests::{Contest, Participant, ParticipantRole},
     context_with_base,
     db::DbConnection,
     leaderboard::LeaderboardManagerHandle,
     problems::Problem,
+    run::CodeInfo,
     times::ClientTimeZone,
     ResultResponse,
 };
 
+mod stats;
+
+use stats::ProfileStats;
+
 #[derive(Serialize)]
 struct ProfileContestEntry {
     id: i64,
@@ -27,9 +32,11 @@ struct ProfileContestEntry {
 async fn profile(
     mut db: DbConnection,
     leaderboard_manager: &State<LeaderboardManagerHandle>,
+    code_info: &State<CodeInfo>,
     user_id: i64,
     tz: ClientTimeZone,
     user: Option<&User>,
+    admin: Option<&Admin>,
 ) -> ResultResponse<Template> {
     let profile = User::get_or_404(&mut db, user_id).await?;
     let joined = tz
@@ -44,6 +51,14 @@ async fn profile(
     let mut contest_entries = Vec::<ProfileContestEntry>::with_capacity(contests.len());
 
     for contest in contests {
+        let viewer_participant = if let Some(user) = user {
+            Participant::get(&mut db, contest.id, user.id).await?
+        } else {
+            None
+        };
+        if !contest.is_visible_to(viewer_participant.as_ref(), admin) {
+            continue;
+        }
         let mut leaderboards = leaderboard_manager.lock().await;
         let leaderboard = leaderboards.get_leaderboard(&mut db, &contest).await?;
         drop(leaderboards);
@@ -53,7 +68,11 @@ async fn profile(
         if let Some((solved, rank)) = stats {
             let role = Participant::get(&mut db, contest.id, user_id)
                 .await?
-                .map(|p| if p.is_judge { "Judge" } else { "Participant" })
+                .map(|p| match p.role() {
+                    ParticipantRole::Judge => "Judge",
+                    ParticipantRole::Spectator => "Spectator",
+                    ParticipantRole::Competitor => "Participant",
+                })
                 .unwrap_or("Participant");
             contest_entries.push(ProfileContestEntry {
                 id: contest.id,
@@ -66,7 +85,29 @@ async fn profile(
         }
     }
 
-    let ctx = context_with_base!(user, contests: contest_entries, is_me, joined, profile);
+    let contests_participated = contest_entries
+        .iter()
+        .filter(|c| c.role == "Participant")
+        .count();
+    let stats = ProfileStats::get(
+        &mut db,
+        user_id,
+        user.map(|u| u.id),
+        is_me || admin.is_some(),
+        tz.timezone(),
+        &code_info.run_config,
+    )
+    .await?;
+
+    let ctx = context_with_base!(
+        user,
+        contests: contest_entries,
+        contests_participated,
+        stats,
+        is_me,
+        joined,
+        profile
+    );
     Ok(Template::render("profile", ctx))
 }
 
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+use crate::{
+    db::DbPoolConnection, error::prelude::*, run::RunConfig, times::format_datetime_human_readable,
+};
+
+/// How many weeks back the submission heatmap goes
+const HEATMAP_WEEKS: i64 = 52;
+const RECENT_ACCEPTED: usize = 10;
+
+#[derive(Serialize)]
+pub struct LanguageCount {
+    pub name: String,
+    pub count: usize,
+    pub percent: usize,
+}
+
+#[derive(Serialize)]
+pub struct HeatmapDay {
+    pub date: String,
+    pub count: usize,
+    /// 0 for no submissions up to 4 for the busiest days
+    pub level: usize,
+}
+
+#[derive(Serialize)]
+pub struct RecentRun {
+    pub contest_id: i64,
+    pub contest_name: String,
+    pub problem_slug: String,
+    pub problem_name: String,
+    pub language: String,
+    pub ran_at: String,
+}
+
+#[derive(Serialize)]
+pub struct ProfileStats {
+    pub problems_solved: i64,
+    pub submissions: usize,
+    pub languages: Vec<LanguageCount>,
+    /// Weeks starting on Sunday, the current week is cut off at today
+    pub heatmap: Vec<Vec<HeatmapDay>>,
+    pub recent_accepted: Vec<RecentRun>,
+}
+
+struct VisibleRun {
+    language: String,
+    ran_at: NaiveDateTime,
+    amount_run: i64,
+    total_cases: i64,
+    error: Option<String>,
+    manual_verdict: Option<bool>,
+    problem_name: String,
+    problem_slug: String,
+    contest_id: i64,
+    contest_name: String,
+}
+
+impl VisibleRun {
+    fn success(&self) -> bool {
+        self.manual_verdict
+            .unwrap_or(self.amount_run == self.total_cases && self.error.is_none())
+    }
+}
+
+impl ProfileStats {
+    /// Gather statistics for a user's profile.
+    /// Unless `show_all` is set (the viewer is the user or an admin) only contests that have ended are counted,
+    /// so the profile can't be used to peek past a frozen leaderboard, and private contests are left out unless the viewer is in them.
+    pub async fn get(
+        db: &mut DbPoolConnection,
+        user_id: i64,
+        viewer_id: Option<i64>,
+        show_all: bool,
+        tz: &Tz,
+        run_config: &RunConfig,
+    ) -> Result<Self> {
+        let now = chrono::offset::Utc::now().naive_utc();
+        let runs = sqlx::query_as!(
+            VisibleRun,
+            "SELECT judge_run.language, judge_run.ran_at, judge_run.amount_run, judge_run.total_cases, judge_run.error, judge_run.manual_verdict, problem.name AS problem_name, problem.slug AS problem_slug, contest.id AS contest_id, contest.name AS contest_name
+            FROM judge_run JOIN problem ON judge_run.problem_id = problem.id JOIN contest ON problem.contest_id = contest.id
+            WHERE judge_run.user_id = ? AND (? OR (contest.end_time < ? AND (contest.visibility != 'Private' OR contest.id IN (SELECT contest_id FROM participant WHERE user_id = ?))))
+            ORDER BY judge_run.ran_at DESC",
+            user_id,
+            show_all,
+            now,
+            viewer_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Couldn't get runs for profile of user {user_id}"))?;
+
+        let problems_solved = sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM problem_completion
+            JOIN participant ON problem_completion.participant_id = participant.p_id JOIN contest ON participant.contest_id = contest.id
+            WHERE participant.user_id = ? AND problem_completion.completed_at IS NOT NULL AND (? OR (contest.end_time < ? AND (contest.visibility != 'Private' OR contest.id IN (SELECT contest_id FROM participant WHERE user_id = ?))))",
+            user_id,
+            show_all,
+            now,
+            viewer_id
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count)
+        .with_context(|| format!("Couldn't count problems solved by user {user_id}"))?;
+
+        let language_name = |key: &str| {
+            run_config
+                .languages
+                .get(key)
+                .map_or_else(|| key.to_string(), |l| l.display.name.clone())
+        };
+
+        let mut language_counts = HashMap::<&str, usize>::new();
+        for run in runs.iter() {
+            *language_counts.entry(run.language.as_str()).or_default() += 1;
+        }
+        let mut languages = language_counts
+            .into_iter()
+            .map(|(key, count)| LanguageCount {
+                name: language_name(key),
+                count,
+                percent: count * 100 / runs.len(),
+            })
+            .collect::<Vec<_>>();
+        languages.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(&b.name)));
+
+        let recent_accepted = runs
+            .iter()
+            .filter(|r| r.success())
+            .take(RECENT_ACCEPTED)
+            .map(|r| RecentRun {
+                contest_id: r.contest_id,
+                contest_name: r.contest_name.clone(),
+                problem_slug: r.problem_slug.clone(),
+                problem_name: r.problem_name.clone(),
+                language: language_name(&r.language),
+                ran_at: format_datetime_human_readable(tz.from_utc_datetime(&r.ran_at)),
+            })
+            .collect();
+
+        Ok(Self {
+            problems_solved,
+            submissions: runs.len(),
+            languages,
+            heatmap: Self::heatmap(&runs, tz),
+            recent_accepted,
+        })
+    }
+
+    fn heatmap(runs: &[VisibleRun], tz: &Tz) -> Vec<Vec<HeatmapDay>> {
+        let today = tz
+            .from_utc_datetime(&chrono::offset::Utc::now().naive_utc())
+            .date_naive();
+        let start = today - Duration::weeks(HEATMAP_WEEKS);
+        let start = start - Duration::days(start.weekday().num_days_from_sunday() as i64);
+
+        let mut per_day = HashMap::<NaiveDate, usize>::new();
+        for run in runs.iter() {
+            let day = tz.from_utc_datetime(&run.ran_at).date_naive();
+            if day >= start {
+                *per_day.entry(day).or_default() += 1;
+            }
+        }
+        let busiest = per_day.values().copied().max().unwrap_or(0);
+
+        let days = start
+            .iter_days()
+            .take_while(|d| *d <= today)
+            .map(|date| {
+                let count = per_day.get(&date).copied().unwrap_or(0);
+                HeatmapDay {
+                    date: date.format("%B %-d, %Y").to_string(),
+                    count,
+                    level: if count == 0 {
+                        0
+                    } else {
+                        (count * 4).div_ceil(busiest)
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut weeks = Vec::with_capacity(HEATMAP_WEEKS as usize + 1);
+        let mut days = days.into_iter().peekable();
+        while days.peek().is_some() {
+            weeks.push(days.by_ref().take(7).collect());
+        }
+        weeks
+    }
+}
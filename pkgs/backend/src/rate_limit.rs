@@ -0,0 +1,321 @@
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use rocket::{
+    catch, catchers,
+    fairing::AdHoc,
+    http::{Header, Status},
+    request::{FromRequest, Outcome},
+    Request,
+};
+use rocket_dyn_templates::Template;
+use tokio::sync::Mutex;
+
+use crate::auth::users::User;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LimitConfig {
+    /// Maximum number of requests allowed within `window_secs`
+    pub max_requests: u32,
+    pub window_secs: u64,
+}
+
+impl LimitConfig {
+    const fn new(max_requests: u32, window_secs: u64) -> Self {
+        Self {
+            max_requests,
+            window_secs,
+        }
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_login() -> LimitConfig {
+    LimitConfig::new(20, 60)
+}
+
+fn default_run_connect() -> LimitConfig {
+    LimitConfig::new(30, 60)
+}
+
+fn default_run_job() -> LimitConfig {
+    LimitConfig::new(10, 60)
+}
+
+fn default_api() -> LimitConfig {
+    LimitConfig::new(120, 60)
+}
+
+fn default_invocation() -> LimitConfig {
+    LimitConfig::new(30, 60 * 60)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Also count requests from logged out clients against their IP, off by default since
+    /// a whole lab behind NAT or a proxy shares one unless `ip_header` tells them apart
+    #[serde(default)]
+    pub per_ip: bool,
+    /// Login pages and OAuth / SAML login redirects, only limited with `per_ip`
+    #[serde(default = "default_login")]
+    pub login: LimitConfig,
+    /// Opening a run WebSocket
+    #[serde(default = "default_run_connect")]
+    pub run_connect: LimitConfig,
+    /// Judge / test requests sent over the run WebSocket
+    #[serde(default = "default_run_job")]
+    pub run_job: LimitConfig,
+    /// Endpoints meant for scripts and tools, like problem exports and git clones of solutions
+    #[serde(default = "default_api")]
+    pub api: LimitConfig,
+    /// Custom invocations, which can run several inputs at once, they also count against `run_job`
+    #[serde(default = "default_invocation")]
+    pub invocation: LimitConfig,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            per_ip: false,
+            login: default_login(),
+            run_connect: default_run_connect(),
+            run_job: default_run_job(),
+            api: default_api(),
+            invocation: default_invocation(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Bucket {
+    Login,
+    RunConnect,
+    RunJob,
+    Api,
+    Invocation,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum Key {
+    User(i64),
+    Ip(IpAddr),
+}
+
+/// Fixed window counter, `(window start, hits in window)`
+type Window = (Instant, u32);
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    windows: HashMap<(Bucket, Key), Window>,
+    last_prune: Instant,
+}
+
+pub type RateLimiterHandle = Arc<Mutex<RateLimiter>>;
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            windows: HashMap::new(),
+            last_prune: Instant::now(),
+        }
+    }
+
+    fn limit_for(&self, bucket: Bucket) -> LimitConfig {
+        match bucket {
+            Bucket::Login => self.config.login,
+            Bucket::RunConnect => self.config.run_connect,
+            Bucket::RunJob => self.config.run_job,
+            Bucket::Api => self.config.api,
+            Bucket::Invocation => self.config.invocation,
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        if now.duration_since(self.last_prune) < PRUNE_INTERVAL {
+            return;
+        }
+        let config = self.config.clone();
+        self.windows.retain(|(bucket, _), (start, _)| {
+            let window = match bucket {
+                Bucket::Login => config.login.window(),
+                Bucket::RunConnect => config.run_connect.window(),
+                Bucket::RunJob => config.run_job.window(),
+                Bucket::Api => config.api.window(),
+                Bucket::Invocation => config.invocation.window(),
+            };
+            now.duration_since(*start) < window
+        });
+        self.last_prune = now;
+    }
+
+    /// Peek at a key without recording a hit, returns how long until it frees up if it's limited
+    fn limited_for(&self, bucket: Bucket, key: Key, now: Instant) -> Option<Duration> {
+        let limit = self.limit_for(bucket);
+        self.windows
+            .get(&(bucket, key))
+            .filter(|(start, hits)| {
+                now.duration_since(*start) < limit.window() && *hits >= limit.max_requests
+            })
+            .map(|(start, _)| limit.window().saturating_sub(now.duration_since(*start)))
+    }
+
+    fn hit(&mut self, bucket: Bucket, key: Key, now: Instant) {
+        let window = self.limit_for(bucket).window();
+        let entry = self.windows.entry((bucket, key)).or_insert((now, 0));
+        if now.duration_since(entry.0) >= window {
+            *entry = (now, 0);
+        }
+        entry.1 += 1;
+    }
+
+    /// A bucket's limit for showing to users, `None` when limits aren't enforced
+    pub fn quota(&self, bucket: Bucket) -> Option<LimitConfig> {
+        self.config.enabled.then(|| self.limit_for(bucket))
+    }
+
+    /// Record a hit against a bucket for the given user, or for the IP of a logged out client
+    /// when `per_ip` is on. If the key is over its limit nothing is recorded and the time
+    /// until the request can be retried is returned.
+    pub fn check(
+        &mut self,
+        bucket: Bucket,
+        user_id: Option<i64>,
+        ip: Option<IpAddr>,
+    ) -> Result<(), Duration> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        // Logged in users are only counted by who they are, the room may share their IP
+        let key = match (user_id, ip) {
+            (Some(user_id), _) => Key::User(user_id),
+            (None, Some(ip)) if self.config.per_ip => Key::Ip(ip),
+            _ => return Ok(()),
+        };
+
+        let now = Instant::now();
+        self.prune(now);
+
+        if let Some(retry_after) = self.limited_for(bucket, key, now) {
+            return Err(retry_after);
+        }
+        self.hit(bucket, key, now);
+
+        Ok(())
+    }
+}
+
+pub fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs().max(1)
+}
+
+pub trait LimitedRoute: Send + Sync {
+    const BUCKET: Bucket;
+}
+
+pub struct Login;
+
+impl LimitedRoute for Login {
+    const BUCKET: Bucket = Bucket::Login;
+}
+
+pub struct RunConnect;
+
+impl LimitedRoute for RunConnect {
+    const BUCKET: Bucket = Bucket::RunConnect;
+}
+
+pub struct Api;
+
+impl LimitedRoute for Api {
+    const BUCKET: Bucket = Bucket::Api;
+}
+
+/// Request guard that counts the request against `B`'s limit,
+/// fails with 429 (and a `Retry-After` header from the catcher) when over it
+pub struct RateLimited<B: LimitedRoute>(PhantomData<B>);
+
+struct RetryAfter(Option<u64>);
+
+#[rocket::async_trait]
+impl<'r, B: LimitedRoute> FromRequest<'r> for RateLimited<B> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter = match req.rocket().state::<RateLimiterHandle>() {
+            Some(limiter) => limiter,
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        let user_id = req.guard::<&User>().await.succeeded().map(|u| u.id);
+        let ip = req.client_ip();
+
+        let res = limiter.lock().await.check(B::BUCKET, user_id, ip);
+
+        match res {
+            Ok(()) => Outcome::Success(RateLimited(PhantomData)),
+            Err(retry_after) => {
+                let secs = retry_after_secs(retry_after);
+                req.local_cache(|| RetryAfter(Some(secs)));
+                Outcome::Error((Status::TooManyRequests, ()))
+            }
+        }
+    }
+}
+
+#[derive(Responder)]
+#[response(status = 429)]
+struct TooManyRequests {
+    inner: Template,
+    retry_after: Header<'static>,
+}
+
+#[catch(429)]
+fn too_many_requests(req: &Request) -> TooManyRequests {
+    let secs = req.local_cache(|| RetryAfter(None)).0.unwrap_or(1);
+    let status = Status::TooManyRequests;
+    let message = format!("{status}, try again in {secs} seconds");
+    let code = status.code;
+    TooManyRequests {
+        inner: Template::render(
+            "error",
+            context! { message, code, version: env!("CARGO_PKG_VERSION") },
+        ),
+        retry_after: Header::new("Retry-After", secs.to_string()),
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Rate Limiting", |rocket| async {
+        let config = rocket
+            .figment()
+            .extract_inner::<RateLimitConfig>("rate_limit")
+            .unwrap_or_else(|_| {
+                warn!("No rate_limit config found, using defaults");
+                RateLimitConfig::default()
+            });
+        let limiter: RateLimiterHandle = Arc::new(Mutex::new(RateLimiter::new(config)));
+        rocket
+            .manage(limiter)
+            .register("/", catchers![too_many_requests])
+    })
+}
@@ -0,0 +1,359 @@
+//! Problems answered without a program, nothing runs in a sandbox so runs skip the [`RunManager`]
+//! queue, answers are checked against the cases right away and saved like a judged run.
+//!
+//! - Output-only problems, participants download every case's input, work out the answers
+//!   however they like and upload one file per case
+//! - Quizzes, a single short answer where each case's expected output is an accepted answer
+
+use rocket::{
+    form::Form,
+    fs::TempFile,
+    get,
+    http::{ContentType, Header, Status},
+    post,
+    response::Redirect,
+    tokio::io::AsyncReadExt,
+    FromForm, Responder, State,
+};
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        impersonation::NotImpersonating,
+        users::{Admin, User},
+    },
+    contests::{BindingVerdict, ClientOrigin, Contest, Participant, SessionBinding},
+    db::{DbConnection, DbPoolConnection},
+    error::prelude::*,
+    leaderboard::LeaderboardManagerHandle,
+    messages::Message,
+    problems::{Comparison, JudgeRun, Problem, TestCase},
+    rate_limit::{self, Bucket, RateLimiterHandle},
+    zip::ZipWriter,
+};
+
+use super::{
+    job::{CaseStatus, JobState},
+    manager::RunManager,
+    worker::CaseError,
+    CodeInfo,
+};
+
+/// Saved as the run's language, so runs of these problems stand out
+const ANSWERS_LANGUAGE: &str = "output";
+const QUIZ_LANGUAGE: &str = "answer";
+
+#[derive(Responder)]
+pub struct Inputs {
+    inner: (ContentType, Vec<u8>),
+    disposition: Header<'static>,
+}
+
+#[derive(FromForm)]
+pub struct AnswersForm<'r> {
+    /// One per case, in the same order as the cases
+    answers: Vec<TempFile<'r>>,
+}
+
+#[derive(FromForm)]
+pub struct QuizForm<'r> {
+    #[field(validate = len(1..=1024))]
+    answer: &'r str,
+}
+
+async fn read_answer(
+    file: &TempFile<'_>,
+    case: usize,
+    max_length: usize,
+) -> Result<String, String> {
+    if file.len() as usize > max_length {
+        return Err(format!(
+            "The answer for case {case} is too big, answers can be at most {max_length} bytes"
+        ));
+    }
+    let mut answer = String::new();
+    file.open()
+        .await
+        .map_err(|_| format!("Couldn't read the answer for case {case}"))?
+        .read_to_string(&mut answer)
+        .await
+        .map_err(|_| format!("The answer for case {case} isn't text"))?;
+    Ok(answer)
+}
+
+/// Whether the user can answer the problem right now, the same rules as submitting a program
+async fn check_answer(
+    db: &mut DbPoolConnection,
+    limiter: &RateLimiterHandle,
+    problem: &Problem,
+    user_id: i64,
+    participant: Option<&Participant>,
+    can_edit: bool,
+    origin: &ClientOrigin,
+) -> Result<BindingVerdict> {
+    if let Some(reason) = participant.and_then(Participant::runs_blocked) {
+        return Ok(BindingVerdict::Blocked(reason.to_string()));
+    }
+    let limited = limiter
+        .lock()
+        .await
+        .check(Bucket::RunJob, Some(user_id), None);
+    if let Err(retry_after) = limited {
+        return Ok(BindingVerdict::Blocked(format!(
+            "Too many runs, try again in {} seconds",
+            rate_limit::retry_after_secs(retry_after)
+        )));
+    }
+    if can_edit {
+        return Ok(BindingVerdict::Allowed);
+    }
+    if let Some(reason) = problem.submission_blocked(db, user_id).await? {
+        return Ok(BindingVerdict::Blocked(reason));
+    }
+    SessionBinding::check(db, problem.contest_id, user_id, origin).await
+}
+
+/// Save checked answers as a run, which updates completions and the leaderboard
+#[allow(clippy::too_many_arguments)]
+async fn save_answers(
+    db: &mut DbPoolConnection,
+    problem: &Problem,
+    user_id: i64,
+    program: String,
+    language: &str,
+    state: &JobState,
+    flag: Option<String>,
+    leaderboard_handle: LeaderboardManagerHandle,
+    url: &str,
+) -> Result<Redirect> {
+    let ran_at = chrono::offset::Utc::now().naive_utc();
+    let run = JudgeRun::from_job_state(
+        problem.id,
+        user_id,
+        program,
+        language.to_string(),
+        state,
+        ran_at,
+        flag,
+    );
+    let success = run.success();
+    let (case, penalty_applies, error) = state.last_error();
+    RunManager::save_run(
+        db,
+        problem.contest_id,
+        problem.id,
+        user_id,
+        run,
+        ran_at,
+        penalty_applies,
+        false,
+        leaderboard_handle,
+    )
+    .await?;
+
+    Ok(if success {
+        Message::success("Correct!").to(url)
+    } else if problem.quiz {
+        Message::error("Wrong Answer").to(url)
+    } else {
+        Message::error("Case {case} failed: {error}")
+            .arg("case", case + 1)
+            .arg("error", error.unwrap_or_default())
+            .to(url)
+    })
+}
+
+/// Every case's input as `<case>.in` files in a zip
+#[get("/inputs/<contest_id>/<problem_id>")]
+pub async fn inputs(
+    contest_id: i64,
+    problem_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    mut db: DbConnection,
+) -> ResultResponse<Inputs> {
+    Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    let problem = Problem::by_id(&mut db, contest_id, problem_id)
+        .await?
+        .filter(|p| p.output_only)
+        .ok_or(Status::NotFound)?;
+    let cases = TestCase::get_for_problem(&mut db, problem.id).await?;
+    let mut zip = ZipWriter::new(chrono::offset::Utc::now().naive_utc());
+    for (i, case) in cases.iter().enumerate() {
+        zip.add(&format!("{}.in", i + 1), case.stdin.as_bytes())?;
+    }
+    Ok(Inputs {
+        inner: (ContentType::ZIP, zip.finish()?),
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("attachment; filename=\"{}-inputs.zip\"", problem.slug),
+        ),
+    })
+}
+
+/// Check answers to an output-only problem, they count just like a judged program would
+#[post("/answers/<contest_id>/<problem_id>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn answers_post(
+    contest_id: i64,
+    problem_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    limiter: &State<RateLimiterHandle>,
+    info: &State<CodeInfo>,
+    leaderboard_handle: &State<LeaderboardManagerHandle>,
+    form: Form<AnswersForm<'_>>,
+    mut db: DbConnection,
+    origin: ClientOrigin,
+    _read_write: NotImpersonating,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (_, participant, can_edit) =
+        Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    let problem = Problem::by_id(&mut db, contest_id, problem_id)
+        .await?
+        .filter(|p| p.output_only)
+        .ok_or(Status::NotFound)?;
+    let url = format!("/contests/{contest_id}/problems/{}", problem.slug);
+
+    let cases = TestCase::get_for_problem(&mut db, problem.id).await?;
+    if cases.is_empty() {
+        return Err(Status::NotFound.into());
+    }
+    if form.answers.len() != cases.len() {
+        return Ok(
+            Message::error("Upload an answer for each of the {count} cases")
+                .arg("count", cases.len())
+                .to(&url),
+        );
+    }
+    let mut answers = Vec::with_capacity(cases.len());
+    for (i, file) in form.answers.iter().enumerate() {
+        match read_answer(file, i + 1, info.run_config.max_program_length).await {
+            Ok(answer) => answers.push(answer),
+            Err(why) => return Ok(Message::error(&why).to(&url)),
+        }
+    }
+
+    let verdict = check_answer(
+        &mut db,
+        limiter,
+        &problem,
+        user.id,
+        participant.as_ref(),
+        can_edit,
+        &origin,
+    )
+    .await?;
+    if let BindingVerdict::Blocked(reason) = &verdict {
+        return Ok(Message::error(reason).to(&url));
+    }
+
+    // Checked in order and stopping at the first wrong answer, the same as judging a program
+    let comparison = Comparison::for_problem(&problem);
+    let mut state = JobState::new_judging(cases.len());
+    state.start_first();
+    for (case, answer) in cases.iter().zip(answers.iter()) {
+        let status = match case.check_output(answer, &comparison) {
+            Ok(true) => CaseStatus::passed(String::new()),
+            Ok(false) => CaseStatus::from_case_error(CaseError::Logic, false),
+            Err(why) => CaseStatus::from_case_error(CaseError::Judge(why), false),
+        };
+        let failed = matches!(status, CaseStatus::Failed(_, _));
+        state.complete_case(status);
+        if failed {
+            break;
+        }
+    }
+
+    let redirect = save_answers(
+        &mut db,
+        &problem,
+        user.id,
+        answers.join("\n"),
+        ANSWERS_LANGUAGE,
+        &state,
+        verdict.flag(None),
+        leaderboard_handle.inner().clone(),
+        &url,
+    )
+    .await?;
+    Ok(redirect)
+}
+
+/// Check the answer to a quiz, it's correct if it matches any of the cases
+#[post("/quiz/<contest_id>/<problem_id>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn quiz_post(
+    contest_id: i64,
+    problem_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    limiter: &State<RateLimiterHandle>,
+    leaderboard_handle: &State<LeaderboardManagerHandle>,
+    form: Form<QuizForm<'_>>,
+    mut db: DbConnection,
+    origin: ClientOrigin,
+    _read_write: NotImpersonating,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (_, participant, can_edit) =
+        Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    let problem = Problem::by_id(&mut db, contest_id, problem_id)
+        .await?
+        .filter(|p| p.quiz)
+        .ok_or(Status::NotFound)?;
+    let url = format!("/contests/{contest_id}/problems/{}", problem.slug);
+
+    let cases = TestCase::get_for_problem(&mut db, problem.id).await?;
+    if cases.is_empty() {
+        return Err(Status::NotFound.into());
+    }
+    let verdict = check_answer(
+        &mut db,
+        limiter,
+        &problem,
+        user.id,
+        participant.as_ref(),
+        can_edit,
+        &origin,
+    )
+    .await?;
+    if let BindingVerdict::Blocked(reason) = &verdict {
+        return Ok(Message::error(reason).to(&url));
+    }
+
+    let comparison = Comparison::for_problem(&problem);
+    let mut judge_error = None;
+    let correct = cases.iter().any(|case| {
+        case.check_output(form.answer, &comparison)
+            .unwrap_or_else(|why| {
+                judge_error.get_or_insert(why);
+                false
+            })
+    });
+    // Every case is an accepted answer, so they all pass or fail together
+    let state = if correct {
+        JobState::new_all_passed(cases.len())
+    } else {
+        let error = judge_error.map_or(CaseError::Logic, CaseError::Judge);
+        let mut state = JobState::new_judging(cases.len());
+        state.start_first();
+        state.complete_case(CaseStatus::from_case_error(error, false));
+        state
+    };
+
+    let redirect = save_answers(
+        &mut db,
+        &problem,
+        user.id,
+        form.answer.trim().to_string(),
+        QUIZ_LANGUAGE,
+        &state,
+        verdict.flag(None),
+        leaderboard_handle.inner().clone(),
+        &url,
+    )
+    .await?;
+    Ok(redirect)
+}
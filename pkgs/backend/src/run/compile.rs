@@ -0,0 +1,95 @@
+//! Checking that a program compiles without running it, for editor integrations.
+//! The problem page does the same over its websocket, see [`super::ws::WebSocketRequest::Compile`].
+
+use rocket::{http::Status, post, serde::json::Json, State};
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        users::{Admin, User},
+    },
+    contests::Contest,
+    db::DbConnection,
+    error::prelude::*,
+    problems::Problem,
+    rate_limit::{self, Bucket, RateLimited, RateLimiterHandle},
+};
+
+use super::{
+    direct::{run_for_problem, JobOperation},
+    CodeInfo, ManagerHandle,
+};
+
+#[derive(Deserialize)]
+pub struct CompileRequest {
+    program: String,
+    language: String,
+}
+
+#[derive(Serialize)]
+pub struct CompileCheck {
+    compiled: bool,
+    /// What the compiler printed, warnings included, or why compiling failed on our end
+    output: String,
+}
+
+/// Compile a program in the sandbox with the problem's limits and return the compiler's
+/// diagnostics, nothing is run and nothing is saved
+#[post("/compile/<contest_id>/<problem_id>", data = "<body>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn compile_post(
+    contest_id: i64,
+    problem_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    manager: &State<ManagerHandle>,
+    limiter: &State<RateLimiterHandle>,
+    info: &State<CodeInfo>,
+    body: Json<CompileRequest>,
+    mut db: DbConnection,
+    _token: &VerifyCsrfToken,
+    _limit: RateLimited<rate_limit::Api>,
+) -> ResultResponse<Json<CompileCheck>> {
+    Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    let problem = Problem::by_id(&mut db, contest_id, problem_id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    if !problem.judges_programs() {
+        return Err(Status::NotFound.into());
+    }
+    if !info.run_config.languages.contains_key(&body.language)
+        || !problem.allows_language(&body.language)
+        || body.program.len() > info.run_config.max_program_length
+    {
+        return Err(Status::BadRequest.into());
+    }
+    if limiter
+        .lock()
+        .await
+        .check(Bucket::RunJob, Some(user.id), None)
+        .is_err()
+    {
+        return Err(Status::TooManyRequests.into());
+    }
+
+    let state = run_for_problem(
+        manager,
+        user.id,
+        &problem,
+        &body.language,
+        &body.program,
+        JobOperation::Compiling,
+    )
+    .await
+    .map_err(|why| anyhow!("Couldn't compile a program for problem {problem_id}: {why}"))?;
+    let (_, _, error) = state.last_error();
+    let output = match (state.compile_output(), &error) {
+        (Some(output), _) => output.to_string(),
+        (None, Some(error)) => error.clone(),
+        (None, None) => String::new(),
+    };
+    Ok(Json(CompileCheck {
+        compiled: error.is_none(),
+        output,
+    }))
+}
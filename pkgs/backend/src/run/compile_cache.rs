@@ -0,0 +1,192 @@
+use std::{
+    fs::Permissions,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use log::{info, warn};
+use tokio::sync::Mutex;
+
+use crate::error::prelude::*;
+
+use super::{config::LanguageRunnerInfo, worker::Artifact};
+
+const fn default_max_entries() -> usize {
+    500
+}
+
+const fn default_max_entry_bytes() -> u64 {
+    1024 * 1024 * 64 // 64 MiB
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct CompileCacheConfig {
+    /// Directory to keep compiled programs in, caching is off when unset.
+    /// Everything in it is deleted on startup.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Max number of compiled programs to keep, the least recently used are removed first
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    /// Programs whose artifacts are bigger than this aren't cached
+    #[serde(default = "default_max_entry_bytes")]
+    pub max_entry_bytes: u64,
+}
+
+impl Default for CompileCacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_entries: default_max_entries(),
+            max_entry_bytes: default_max_entry_bytes(),
+        }
+    }
+}
+
+/// Compiled programs keyed by language and program, so testing the same code again skips compiling.
+/// Each entry is a directory of artifacts that's bind mounted read-only into the worker on a hit.
+pub struct CompileCache {
+    config: CompileCacheConfig,
+    /// Held while writing or evicting so entries don't disappear halfway through being written
+    lock: Mutex<()>,
+}
+
+impl CompileCache {
+    pub async fn new(config: CompileCacheConfig) -> Result<Self> {
+        if let Some(dir) = config.dir.as_ref() {
+            // Toolchains may have changed since the last startup, start fresh
+            if dir.exists() {
+                tokio::fs::remove_dir_all(dir)
+                    .await
+                    .context("Couldn't clear compile cache")?;
+            }
+            tokio::fs::create_dir_all(dir)
+                .await
+                .context("Couldn't create compile cache directory")?;
+            // Workers have their own UIDs, they need to be able to read entries
+            tokio::fs::set_permissions(dir, Permissions::from_mode(0o755))
+                .await
+                .context("Couldn't set compile cache permissions")?;
+            info!("Caching compiled programs in {}", dir.display());
+        }
+        Ok(Self {
+            config,
+            lock: Mutex::new(()),
+        })
+    }
+
+    pub fn max_entry_bytes(&self) -> u64 {
+        self.config.max_entry_bytes
+    }
+
+    /// `None` when caching is off or the language doesn't say what to cache
+    pub fn key(
+        &self,
+        language_key: &str,
+        language: &LanguageRunnerInfo,
+        program: &str,
+    ) -> Option<String> {
+        self.config.dir.as_ref()?;
+        let compile_cmd = language.compile_cmd.as_ref()?;
+        if language.artifacts.is_empty() {
+            return None;
+        }
+        let key = serde_json::to_string(&(
+            language_key,
+            &compile_cmd.binary,
+            &compile_cmd.args,
+            &language.artifacts,
+            program,
+        ))
+        .ok()?;
+        Some(sha256::digest(key))
+    }
+
+    /// Directory holding the artifacts for this key, if they've been cached
+    pub async fn get(&self, key: &str) -> Option<PathBuf> {
+        let dir = self.config.dir.as_ref()?.join(key);
+        let _lock = self.lock.lock().await;
+        let entry = std::fs::File::open(&dir).ok()?;
+        // Directory mtimes track when an entry was last used for eviction
+        entry.set_modified(SystemTime::now()).ok();
+        Some(dir)
+    }
+
+    pub async fn put(&self, key: &str, artifacts: Vec<Artifact>) -> Result {
+        let Some(root) = self.config.dir.as_ref() else {
+            return Ok(());
+        };
+        let _lock = self.lock.lock().await;
+        let dir = root.join(key);
+        if dir.exists() {
+            return Ok(());
+        }
+        // Write somewhere else first so a half written entry is never used
+        let tmp = root.join(format!(".{key}.tmp"));
+        write_entry(&tmp, artifacts).await?;
+        tokio::fs::rename(&tmp, &dir)
+            .await
+            .context("Couldn't move compile cache entry into place")?;
+        self.evict(root).await
+    }
+
+    async fn evict(&self, root: &Path) -> Result {
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(root)
+            .await
+            .context("Couldn't read compile cache directory")?;
+        while let Some(entry) = dir.next_entry().await.context("Couldn't read entry")? {
+            let used = entry
+                .metadata()
+                .await
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((used, entry.path()));
+        }
+        if entries.len() <= self.config.max_entries {
+            return Ok(());
+        }
+        entries.sort();
+        let excess = entries.len() - self.config.max_entries;
+        for (_, path) in entries.into_iter().take(excess) {
+            if let Err(why) = tokio::fs::remove_dir_all(&path).await {
+                warn!(
+                    "Couldn't evict compile cache entry {}: {:?}",
+                    path.display(),
+                    why
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn write_entry(dir: &Path, artifacts: Vec<Artifact>) -> Result {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .context("Couldn't create compile cache entry")?;
+    tokio::fs::set_permissions(dir, Permissions::from_mode(0o755))
+        .await
+        .context("Couldn't set compile cache entry permissions")?;
+    for artifact in artifacts {
+        // Names come from the language config, but don't let one escape the entry
+        let name = Path::new(&artifact.name)
+            .file_name()
+            .context("Artifact has no file name")?;
+        let path = dir.join(name);
+        let contents = BASE64_STANDARD
+            .decode(&artifact.contents)
+            .context("Couldn't decode artifact")?;
+        tokio::fs::write(&path, contents)
+            .await
+            .with_context(|| format!("Couldn't write artifact {}", path.display()))?;
+        let mode = if artifact.executable { 0o755 } else { 0o644 };
+        tokio::fs::set_permissions(&path, Permissions::from_mode(mode))
+            .await
+            .context("Couldn't set artifact permissions")?;
+    }
+    Ok(())
+}
@@ -7,7 +7,10 @@ use crate::error::prelude::*;
 
 use serde::Deserialize;
 
-use super::worker::IsolationConfig;
+use super::{
+    compile_cache::CompileCacheConfig, nodes::NodesConfig, pool::PoolConfig,
+    worker::IsolationConfig,
+};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(crate = "rocket::serde")]
@@ -70,8 +73,53 @@ pub struct LanguageRunnerInfo {
     pub compile_cmd: Option<CommandInfo>,
     /// Command to run the program. This will be passed the case's input in stdin
     pub run_cmd: CommandInfo,
+    /// Command that prints the toolchain's version, checked on startup to catch unexpected upgrades
+    #[serde(default)]
+    pub version_cmd: Option<CommandInfo>,
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Files in the working directory the compile command produces that are needed to run the program,
+    /// listing them lets compiled programs be cached (see `run.compile_cache`)
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Scales the problem's CPU time limit, for languages that need more time (e.g. interpreted ones)
+    #[serde(default)]
+    pub time_multiplier: Option<f64>,
+    /// Scales the problem's memory limit, for languages with a heavier runtime
+    #[serde(default)]
+    pub memory_multiplier: Option<f64>,
+}
+
+impl LanguageRunnerInfo {
+    /// Apply this language's multipliers to a problem's `(cpu_time, memory_limit)`,
+    /// rounding up so a multiplier never makes a limit stricter than intended
+    pub fn effective_limits(&self, (cpu_time, memory_limit): (u64, u64)) -> (u64, u64) {
+        (
+            scale_limit(cpu_time, self.time_multiplier),
+            scale_limit(memory_limit, self.memory_multiplier),
+        )
+    }
+
+    /// Apply this language's time multiplier to a problem's wall time limit
+    pub fn effective_wall_time(&self, wall_time: Option<u64>) -> Option<u64> {
+        wall_time.map(|secs| scale_limit(secs, self.time_multiplier))
+    }
+}
+
+fn scale_limit(limit: u64, multiplier: Option<f64>) -> u64 {
+    match multiplier {
+        Some(m) if m > 0.0 => ((limit as f64 * m).ceil() as u64).max(1),
+        _ => limit,
+    }
+}
+
+/// A problem's limits for one language, shown on the problem page when they differ from the defaults
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct LanguageLimits<'a> {
+    pub name: &'a str,
+    pub cpu_time: u64,
+    pub memory_limit: u64,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -90,6 +138,10 @@ const fn default_pizzaz() -> u64 {
     250
 }
 
+const fn default_judge_error_retries() -> usize {
+    2
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(crate = "rocket::serde")]
 pub struct RunConfig {
@@ -102,12 +154,42 @@ pub struct RunConfig {
     pub default_language: String,
     #[serde(default)]
     pub isolation: IsolationConfig,
+    /// Workers to keep started ahead of time
+    #[serde(default)]
+    pub pool: PoolConfig,
+    /// Where to cache compiled programs
+    #[serde(default)]
+    pub compile_cache: CompileCacheConfig,
+    /// Judge nodes that run jobs for this server
+    #[serde(default)]
+    pub nodes: NodesConfig,
     /// How many milliseconds to wait between cases
     #[serde(default = "default_pizzaz")]
     pub pizzaz: u64,
+    /// How many more times to judge a run that hit a judge error before it's quarantined
+    #[serde(default = "default_judge_error_retries")]
+    pub judge_error_retries: usize,
 }
 
 impl RunConfig {
+    /// Runner info for every language, with binaries resolved to absolute paths
+    pub fn runner_info(&self) -> Result<HashMap<String, LanguageRunnerInfo>> {
+        self.languages
+            .iter()
+            .map(|(k, l)| {
+                let mut l = l.clone();
+                if let Some(compiled_cmd) = l.runner.compile_cmd.as_mut() {
+                    compiled_cmd.setup()?;
+                }
+                l.runner.run_cmd.setup()?;
+                if let Some(version_cmd) = l.runner.version_cmd.as_mut() {
+                    version_cmd.setup()?;
+                }
+                Ok((k.clone(), l.runner))
+            })
+            .collect()
+    }
+
     pub fn get_languages_for_dropdown(&self) -> Vec<(&String, &String)> {
         let mut res = self
             .languages
@@ -117,4 +199,37 @@ impl RunConfig {
         res.sort_by(|a, b| a.1.cmp(b.1));
         res
     }
+
+    /// Normalize a comma separated list of language keys,
+    /// returns a message naming the first key that isn't configured
+    pub fn normalize_language_list(&self, raw: &str) -> Result<String, String> {
+        let mut keys: Vec<&str> = Vec::new();
+        for key in raw.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+            if !self.languages.contains_key(key) {
+                return Err(format!("Unknown language \"{key}\""));
+            }
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        Ok(keys.join(","))
+    }
+
+    /// Limits for every language that changes them, sorted by language name
+    pub fn limits_per_language(&self, soft_limits: (u64, u64)) -> Vec<LanguageLimits<'_>> {
+        let mut res = self
+            .languages
+            .values()
+            .filter_map(|l| {
+                let (cpu_time, memory_limit) = l.runner.effective_limits(soft_limits);
+                ((cpu_time, memory_limit) != soft_limits).then_some(LanguageLimits {
+                    name: &l.display.name,
+                    cpu_time,
+                    memory_limit,
+                })
+            })
+            .collect::<Vec<_>>();
+        res.sort_by(|a, b| a.name.cmp(b.name));
+        res
+    }
 }
@@ -0,0 +1,42 @@
+use tokio::sync::watch;
+
+use crate::problems::{Comparison, Problem};
+
+use super::{job::run_job, manager::ManagerJobRequest, JobState, ManagerHandle};
+
+pub use super::job::JobOperation;
+
+/// Run a program with a problem's limits outside the judging queue and wait for it to finish,
+/// for checking the problem's own data rather than judging a participant.
+/// Errors are for when the program can't be run at all, like an unknown language.
+pub async fn run_for_problem(
+    manager: &ManagerHandle,
+    user_id: i64,
+    problem: &Problem,
+    language_key: &str,
+    program: &str,
+    op: JobOperation,
+) -> Result<JobState, String> {
+    let mut manager = manager.lock().await;
+    let (request, workers) = manager.prepare_job(ManagerJobRequest {
+        user_id,
+        problem_id: problem.id,
+        contest_id: problem.contest_id,
+        program: program.to_string(),
+        language_key: language_key.to_string(),
+        soft_limits: (problem.cpu_time as u64, problem.memory_limit as u64), // `as` is safe due to DB constraint
+        wall_time: problem.wall_time_limit(),
+        network: problem.allow_network,
+        parallelism: problem.parallelism as usize, // Same here
+        comparison: Comparison::for_problem(problem),
+        op,
+        flag: None,
+        practice: true,
+    })?;
+    let cancel = manager.shutdown_token();
+    drop(manager);
+
+    let (state_tx, _state_rx) = watch::channel(JobState::new_for_op(&request.op));
+    let (state, _) = run_job(&request, state_tx, cancel, &workers).await;
+    Ok(state)
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use chrono::NaiveDateTime;
+use log::{info, warn};
+
+use crate::{
+    db::{DbPool, DbPoolConnection},
+    error::prelude::*,
+};
+
+use super::{
+    config::{CommandInfo, LanguageRunnerInfo},
+    where_is,
+};
+
+/// Key used for the binaries every language shares through `run.isolation.include_bins`
+const SHARED_KEY: &str = "(shared binaries)";
+/// Version commands that take longer than this are treated as failing
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Component (version output, binary path) to its value or hash
+type Fingerprint = BTreeMap<String, String>;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ToolchainDrift {
+    pub id: i64,
+    pub language: String,
+    /// Changed components, one per line prefixed with `-` for the old value and `+` for the new one
+    pub diff: String,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub detected_at: NaiveDateTime,
+    pub acknowledged: bool,
+}
+
+impl ToolchainDrift {
+    pub async fn list(db: &mut DbPoolConnection) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            ToolchainDrift,
+            "SELECT * FROM toolchain_drift ORDER BY acknowledged ASC, detected_at DESC"
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to list toolchain drift")
+    }
+
+    pub async fn count_unacknowledged(db: &mut DbPoolConnection) -> Result<i64> {
+        sqlx::query!(
+            "SELECT COUNT(*) AS \"count: i64\" FROM toolchain_drift WHERE acknowledged = false"
+        )
+        .fetch_one(&mut **db)
+        .await
+        .map(|r| r.count)
+        .context("Failed to count toolchain drift")
+    }
+
+    pub async fn acknowledge(db: &mut DbPoolConnection, id: i64) -> Result {
+        sqlx::query!(
+            "UPDATE toolchain_drift SET acknowledged = true WHERE id = ?",
+            id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to acknowledge toolchain drift {id}"))
+    }
+
+    async fn insert(db: &mut DbPoolConnection, language: &str, diff: &str) -> Result {
+        sqlx::query!(
+            "INSERT INTO toolchain_drift (language, diff) VALUES (?, ?)",
+            language,
+            diff
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to record toolchain drift for {language}"))
+    }
+}
+
+async fn hash_binary(path: &str) -> String {
+    match tokio::fs::read(path).await {
+        Ok(bytes) => sha256::digest(bytes),
+        Err(why) => format!("unreadable ({})", why.kind()),
+    }
+}
+
+async fn version_output(cmd: &CommandInfo) -> String {
+    let mut cmd = tokio::process::Command::from(cmd.make_command());
+    cmd.kill_on_drop(true);
+    match tokio::time::timeout(VERSION_TIMEOUT, cmd.output()).await {
+        Ok(Ok(output)) => {
+            let mut out = String::from_utf8_lossy(&output.stdout).to_string();
+            out.push_str(&String::from_utf8_lossy(&output.stderr));
+            out.trim().to_string()
+        }
+        Ok(Err(why)) => format!("failed to run ({})", why.kind()),
+        Err(_) => "timed out".to_string(),
+    }
+}
+
+async fn fingerprint_language(runner: &LanguageRunnerInfo) -> Fingerprint {
+    let mut fingerprint = Fingerprint::new();
+    if let Some(version_cmd) = runner.version_cmd.as_ref() {
+        fingerprint.insert("version".to_string(), version_output(version_cmd).await);
+    }
+    let binaries = runner
+        .compile_cmd
+        .iter()
+        .chain([&runner.run_cmd])
+        .map(|c| c.binary.clone());
+    for binary in binaries {
+        let hash = hash_binary(&binary).await;
+        fingerprint.insert(binary, hash);
+    }
+    fingerprint
+}
+
+async fn fingerprint_shared(include_bins: &[String]) -> Fingerprint {
+    let mut fingerprint = Fingerprint::new();
+    for path in include_bins.iter().filter_map(|b| where_is(b)) {
+        let path = path.to_string_lossy().to_string();
+        let hash = hash_binary(&path).await;
+        fingerprint.insert(path, hash);
+    }
+    fingerprint
+}
+
+/// Lines describing what changed between two fingerprints, empty if nothing did
+fn diff(old: &Fingerprint, new: &Fingerprint) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (key, old_value) in old.iter() {
+        match new.get(key) {
+            Some(new_value) if new_value == old_value => {}
+            Some(new_value) => {
+                lines.push(format!("- {key}: {old_value}"));
+                lines.push(format!("+ {key}: {new_value}"));
+            }
+            None => lines.push(format!("- {key}: {old_value}")),
+        }
+    }
+    for (key, new_value) in new.iter() {
+        if !old.contains_key(key) {
+            lines.push(format!("+ {key}: {new_value}"));
+        }
+    }
+    lines
+}
+
+async fn stored_fingerprints(db: &mut DbPoolConnection) -> Result<HashMap<String, Fingerprint>> {
+    let rows = sqlx::query!("SELECT language, fingerprint FROM toolchain_fingerprint")
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to get stored toolchain fingerprints")?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|r| {
+            serde_json::from_str(&r.fingerprint)
+                .ok()
+                .map(|f| (r.language, f))
+        })
+        .collect())
+}
+
+async fn store_fingerprint(
+    db: &mut DbPoolConnection,
+    language: &str,
+    fingerprint: &Fingerprint,
+) -> Result {
+    let json = serde_json::to_string(fingerprint).context("Failed to serialize fingerprint")?;
+    let now = chrono::offset::Utc::now().naive_utc();
+    sqlx::query!(
+        "INSERT INTO toolchain_fingerprint (language, fingerprint, recorded_at) VALUES (?, ?, ?) ON CONFLICT (language) DO UPDATE SET fingerprint = excluded.fingerprint, recorded_at = excluded.recorded_at",
+        language,
+        json,
+        now
+    )
+    .execute(&mut **db)
+    .await
+    .map(|_| ())
+    .with_context(|| format!("Failed to store toolchain fingerprint for {language}"))
+}
+
+/// Fingerprint every language's toolchain and compare it against the one stored on the last startup,
+/// recording any differences for admins to review.
+/// Languages seen for the first time are only stored.
+pub async fn check_for_drift(
+    pool: DbPool,
+    languages: HashMap<String, LanguageRunnerInfo>,
+    include_bins: Vec<String>,
+) -> Result {
+    let mut current = BTreeMap::new();
+    for (key, runner) in languages.iter() {
+        current.insert(key.clone(), fingerprint_language(runner).await);
+    }
+    current.insert(
+        SHARED_KEY.to_string(),
+        fingerprint_shared(&include_bins).await,
+    );
+
+    let mut db = pool
+        .acquire()
+        .await
+        .context("Couldn't get a connection to check for toolchain drift")?;
+    let stored = stored_fingerprints(&mut db).await?;
+
+    let mut drifted = 0;
+    for (language, fingerprint) in current.iter() {
+        if let Some(old) = stored.get(language) {
+            let lines = diff(old, fingerprint);
+            if !lines.is_empty() {
+                warn!(
+                    "Toolchain for {} changed since the last startup:\n{}",
+                    language,
+                    lines.join("\n")
+                );
+                ToolchainDrift::insert(&mut db, language, &lines.join("\n")).await?;
+                drifted += 1;
+            }
+        }
+        store_fingerprint(&mut db, language, fingerprint).await?;
+    }
+    if drifted == 0 {
+        info!("No toolchain changes since the last startup");
+    }
+    Ok(())
+}
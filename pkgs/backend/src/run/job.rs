@@ -1,17 +1,27 @@
 use core::fmt;
 use std::{
     fmt::{Display, Formatter},
-    time::Instant,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
 };
 
 use chrono::NaiveDateTime;
+use rocket::futures::future::join_all;
+use similar::{ChangeTag, TextDiff};
+use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 
-use crate::{error::prelude::*, problems::TestCase, run::worker::Worker};
+use crate::{
+    error::prelude::*,
+    problems::{Comparison, TestCase},
+    run::worker::Worker,
+};
 
 use super::{
+    compile_cache::CompileCache,
     config::LanguageRunnerInfo,
-    worker::{CaseError, CaseResult, IsolationConfig},
+    pool::{PooledWorker, WorkerPoolHandle},
+    worker::{CaseError, CaseResult, OutputStream},
     JobStateSender,
 };
 
@@ -28,10 +38,34 @@ pub enum CaseStatus {
     Failed(bool, String),
 }
 
+/// Most output kept for showing a case, so big outputs aren't all sent to the browser
+const MAX_DISPLAYED_OUTPUT: usize = 64 * 1024;
+
+/// Cut output down to [`MAX_DISPLAYED_OUTPUT`] bytes, noting how much was left out
+fn truncate_output(mut output: String) -> String {
+    let total = output.len();
+    if total <= MAX_DISPLAYED_OUTPUT {
+        return output;
+    }
+    let mut end = MAX_DISPLAYED_OUTPUT;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    output.truncate(end);
+    output.push_str(&format!(
+        "\n... (truncated, showing {end} of {total} bytes)"
+    ));
+    output
+}
+
 impl CaseStatus {
+    pub fn passed(output: String) -> Self {
+        Self::Passed(truncate_output(output))
+    }
+
     pub fn from_case_error(e: CaseError, details: bool) -> Self {
         let msg = e.to_string(details);
-        Self::Failed(e.gives_penalty(), msg)
+        Self::Failed(e.gives_penalty(), truncate_output(msg))
     }
 }
 
@@ -47,6 +81,54 @@ impl Display for CaseStatus {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CaseOutcome {
+    Passed,
+    Failed,
+    NotRun,
+}
+
+/// How a single case went, saved with judge runs to show on the run's page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseVerdict {
+    pub outcome: CaseOutcome,
+    /// Why the case failed, judging errors never have details so this can't leak test data
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub time_ms: Option<u64>,
+}
+
+/// How a sample case's output compares to what's expected, for showing them side by side
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleResult {
+    /// Whether judging would accept the output
+    pub accepted: bool,
+    /// Each line tagged `equal`, `delete` for expected lines the output is missing, or `insert`
+    /// for lines it has that weren't expected
+    pub diff: Vec<(String, String)>,
+}
+
+impl SampleResult {
+    fn new(case: &TestCase, output: &str, comparison: &Comparison) -> Self {
+        let accepted = case.check_output(output, comparison).unwrap_or(false);
+        let diff = TextDiff::from_lines(case.expected_pattern.as_str(), output)
+            .iter_all_changes()
+            .map(|change| {
+                let tag = match change.tag() {
+                    ChangeTag::Equal => "equal",
+                    ChangeTag::Delete => "delete",
+                    ChangeTag::Insert => "insert",
+                };
+                let line = change.value().trim_end_matches('\n').to_string();
+                (tag.to_string(), line)
+            })
+            .collect();
+        Self { accepted, diff }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum JobState {
@@ -54,9 +136,54 @@ pub enum JobState {
         cases: Vec<CaseStatus>,
         idx: usize,
         complete: bool,
+        /// Syscall seccomp stopped the program for, only for admins (see [`JobState::for_participant`])
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blocked_syscall: Option<String>,
+        /// Milliseconds each case took to run in real time, `None` for cases that didn't run
+        #[serde(default)]
+        times: Vec<Option<u64>>,
+        /// What the compiler printed when compiling failed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compile_output: Option<String>,
+        /// What went wrong on our end when a case hit a judge error, only for admins
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        judge_error: Option<String>,
     },
     Testing {
         status: CaseStatus,
+        /// What the compiler or program has printed so far while it's still running, only in the
+        /// states streamed to the websocket, see [`JobState::push_live_output`]
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        live_output: String,
+    },
+    /// Running the program on the problem's sample cases, a case only fails if the program does,
+    /// whether its output is right is in its result
+    Samples {
+        cases: Vec<CaseStatus>,
+        idx: usize,
+        complete: bool,
+        results: Vec<Option<SampleResult>>,
+    },
+    /// A custom invocation, the program is run on each input whether or not the ones before
+    /// failed
+    Invoking {
+        cases: Vec<CaseStatus>,
+        idx: usize,
+        complete: bool,
+        /// What the compiler printed, even when it succeeded, `None` when a cached build was used
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compile_output: Option<String>,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        live_output: String,
+    },
+    /// Only compiling the program to check it, it passes if it compiles
+    Compiling {
+        status: CaseStatus,
+        /// What the compiler printed, warnings included
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compile_output: Option<String>,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        live_output: String,
     },
 }
 
@@ -66,29 +193,87 @@ impl JobState {
             cases: vec![CaseStatus::Pending; cases],
             idx: 0,
             complete: false,
+            blocked_syscall: None,
+            times: vec![None; cases],
+            compile_output: None,
+            judge_error: None,
+        }
+    }
+
+    pub fn new_samples(cases: usize) -> Self {
+        Self::Samples {
+            cases: vec![CaseStatus::Pending; cases],
+            idx: 0,
+            complete: false,
+            results: vec![None; cases],
+        }
+    }
+
+    pub fn new_invoking(inputs: usize) -> Self {
+        Self::Invoking {
+            cases: vec![CaseStatus::Pending; inputs],
+            idx: 0,
+            complete: false,
+            compile_output: None,
+            live_output: String::new(),
         }
     }
 
     pub fn new_testing() -> Self {
         Self::Testing {
             status: CaseStatus::Pending,
+            live_output: String::new(),
+        }
+    }
+
+    pub fn new_compiling() -> Self {
+        Self::Compiling {
+            status: CaseStatus::Pending,
+            compile_output: None,
+            live_output: String::new(),
+        }
+    }
+
+    /// A finished judging state where every case passed, used when reusing a cached verdict
+    pub fn new_all_passed(cases: usize) -> Self {
+        Self::Judging {
+            cases: vec![CaseStatus::Passed(String::new()); cases],
+            idx: cases.saturating_sub(1),
+            complete: true,
+            blocked_syscall: None,
+            times: vec![None; cases],
+            compile_output: None,
+            judge_error: None,
         }
     }
 
     pub fn new_for_op(op: &JobOperation) -> Self {
         match op {
             JobOperation::Judging(cases) => Self::new_judging(cases.len()),
-            JobOperation::Testing(_) => Self::new_testing(),
+            JobOperation::Samples(cases) => Self::new_samples(cases.len()),
+            JobOperation::Invoking(inputs) => Self::new_invoking(inputs.len()),
+            JobOperation::Testing(_) | JobOperation::Generating(_) => Self::new_testing(),
+            JobOperation::Compiling => Self::new_compiling(),
         }
     }
 
+    /// Testing, running samples, invoking or compiling, where the input isn't secret so errors
+    /// can have details
     pub fn is_testing(&self) -> bool {
-        matches!(self, Self::Testing { .. })
+        matches!(
+            self,
+            Self::Testing { .. }
+                | Self::Samples { .. }
+                | Self::Invoking { .. }
+                | Self::Compiling { .. }
+        )
     }
 
     pub fn last_error(&self) -> (usize, bool, Option<String>) {
         match self {
-            Self::Judging { cases, .. } => cases
+            Self::Judging { cases, .. }
+            | Self::Samples { cases, .. }
+            | Self::Invoking { cases, .. } => cases
                 .iter()
                 .enumerate()
                 .find_map(|(i, c)| {
@@ -99,7 +284,7 @@ impl JobState {
                     }
                 })
                 .unwrap_or_else(|| (self.len(), false, None)),
-            Self::Testing { status } => {
+            Self::Testing { status, .. } | Self::Compiling { status, .. } => {
                 if let CaseStatus::Failed(penalty, e) = status {
                     (0, *penalty, Some(e.clone()))
                 } else {
@@ -109,17 +294,32 @@ impl JobState {
         }
     }
 
+    /// What the program printed, if it was testing and finished without an error
+    pub fn output(&self) -> Option<&str> {
+        match self {
+            Self::Testing {
+                status: CaseStatus::Passed(output),
+                ..
+            } => Some(output),
+            _ => None,
+        }
+    }
+
     pub fn len(&self) -> usize {
         match self {
-            Self::Judging { cases, .. } => cases.len(),
-            Self::Testing { .. } => 1,
+            Self::Judging { cases, .. }
+            | Self::Samples { cases, .. }
+            | Self::Invoking { cases, .. } => cases.len(),
+            Self::Testing { .. } | Self::Compiling { .. } => 1,
         }
     }
 
     pub fn complete(&self) -> bool {
         match self {
-            Self::Judging { complete, .. } => *complete,
-            Self::Testing { status } => matches!(
+            Self::Judging { complete, .. }
+            | Self::Samples { complete, .. }
+            | Self::Invoking { complete, .. } => *complete,
+            Self::Testing { status, .. } | Self::Compiling { status, .. } => matches!(
                 status,
                 CaseStatus::Passed(_) | CaseStatus::Failed(_, _) | CaseStatus::NotRun
             ),
@@ -128,13 +328,15 @@ impl JobState {
 
     pub fn start_first(&mut self) {
         match self {
-            Self::Judging { cases, .. } => {
+            Self::Judging { cases, .. }
+            | Self::Samples { cases, .. }
+            | Self::Invoking { cases, .. } => {
                 // Shouldn't be empty, but to avoid a panic just in case
                 if let Some(c) = cases.get_mut(0) {
                     *c = CaseStatus::Running;
                 }
             }
-            Self::Testing { status } => {
+            Self::Testing { status, .. } | Self::Compiling { status, .. } => {
                 *status = CaseStatus::Running;
             }
         }
@@ -146,6 +348,19 @@ impl JobState {
                 cases,
                 idx,
                 complete,
+                ..
+            }
+            | Self::Samples {
+                cases,
+                idx,
+                complete,
+                ..
+            }
+            | Self::Invoking {
+                cases,
+                idx,
+                complete,
+                ..
             } => {
                 if *idx == cases.len() - 1 {
                     *complete = true;
@@ -163,17 +378,225 @@ impl JobState {
                     *idx += 1;
                 }
             }
-            Self::Testing { status: my_status } => {
+            Self::Testing {
+                status: my_status, ..
+            }
+            | Self::Compiling {
+                status: my_status, ..
+            } => {
                 *my_status = status;
             }
         }
     }
+
+    /// Complete the input of an invocation that's running and start the next, unlike
+    /// [`JobState::complete_case`] a failure doesn't stop the rest
+    fn complete_input(&mut self, status: CaseStatus) {
+        if let Self::Invoking {
+            cases,
+            idx,
+            complete,
+            ..
+        } = self
+        {
+            cases[*idx] = status;
+            if *idx + 1 >= cases.len() {
+                *complete = true;
+            } else {
+                *idx += 1;
+                cases[*idx] = CaseStatus::Running;
+            }
+        }
+    }
+
+    /// Add to what's been printed so far, up to [`MAX_DISPLAYED_OUTPUT`] bytes. Only the state in
+    /// the channel gets this, the job's own state is published over it once the command's done.
+    pub fn push_live_output(&mut self, chunk: &str) {
+        if let Self::Testing { live_output, .. }
+        | Self::Invoking { live_output, .. }
+        | Self::Compiling { live_output, .. } = self
+        {
+            let room = MAX_DISPLAYED_OUTPUT.saturating_sub(live_output.len());
+            let mut end = chunk.len().min(room);
+            while !chunk.is_char_boundary(end) {
+                end -= 1;
+            }
+            live_output.push_str(&chunk[..end]);
+        }
+    }
+
+    fn set_compile_output(&mut self, output: String) {
+        if let Self::Invoking { compile_output, .. } | Self::Compiling { compile_output, .. } = self
+        {
+            *compile_output = Some(truncate_output(output));
+        }
+    }
+
+    /// Record how the output of the sample case that's running compares to what's expected,
+    /// before it's completed
+    fn set_sample_result(&mut self, result: SampleResult) {
+        if let Self::Samples { idx, results, .. } = self {
+            if let Some(r) = results.get_mut(*idx) {
+                *r = Some(result);
+            }
+        }
+    }
+
+    /// Remember details of a failure that the case's status leaves out so they can be saved with the run,
+    /// the syscall behind a blocked syscall and judge errors for admins and the compiler's output
+    /// for the user
+    pub fn note_error(&mut self, e: &CaseError) {
+        if let (
+            Self::Invoking { compile_output, .. } | Self::Compiling { compile_output, .. },
+            CaseError::Compilation(output),
+        ) = (&mut *self, e)
+        {
+            *compile_output = Some(truncate_output(output.clone()));
+        }
+        if let Self::Judging {
+            blocked_syscall,
+            compile_output,
+            judge_error,
+            ..
+        } = self
+        {
+            match e {
+                CaseError::Judge(why) => {
+                    judge_error.get_or_insert_with(|| why.clone());
+                }
+                CaseError::SyscallBlocked { syscall, .. } => {
+                    blocked_syscall.get_or_insert_with(|| syscall.clone());
+                }
+                CaseError::Compilation(output) => {
+                    *compile_output = Some(truncate_output(output.clone()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Why judging failed on our end, see [`CaseError::Judge`]
+    pub fn judge_error(&self) -> Option<&str> {
+        match self {
+            Self::Judging { judge_error, .. } => judge_error.as_deref(),
+            Self::Testing { .. }
+            | Self::Samples { .. }
+            | Self::Invoking { .. }
+            | Self::Compiling { .. } => None,
+        }
+    }
+
+    pub fn compile_output(&self) -> Option<&str> {
+        match self {
+            Self::Judging { compile_output, .. }
+            | Self::Invoking { compile_output, .. }
+            | Self::Compiling { compile_output, .. } => compile_output.as_deref(),
+            Self::Testing { .. } | Self::Samples { .. } => None,
+        }
+    }
+
+    /// Record how long a case took to run
+    pub fn set_case_time(&mut self, i: usize, time: Duration) {
+        if let Self::Judging { times, .. } = self {
+            if let Some(t) = times.get_mut(i) {
+                *t = Some(time.as_millis() as u64);
+            }
+        }
+    }
+
+    /// The outcome of each case, empty when testing
+    pub fn case_verdicts(&self) -> Vec<CaseVerdict> {
+        match self {
+            Self::Judging { cases, times, .. } => cases
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let (outcome, error) = match c {
+                        CaseStatus::Passed(_) => (CaseOutcome::Passed, None),
+                        CaseStatus::Failed(_, e) => (CaseOutcome::Failed, Some(e.clone())),
+                        _ => (CaseOutcome::NotRun, None),
+                    };
+                    CaseVerdict {
+                        outcome,
+                        error,
+                        time_ms: times.get(i).copied().flatten(),
+                    }
+                })
+                .collect(),
+            Self::Testing { .. }
+            | Self::Samples { .. }
+            | Self::Invoking { .. }
+            | Self::Compiling { .. } => vec![],
+        }
+    }
+
+    pub fn blocked_syscall(&self) -> Option<&str> {
+        match self {
+            Self::Judging {
+                blocked_syscall, ..
+            } => blocked_syscall.as_deref(),
+            Self::Testing { .. }
+            | Self::Samples { .. }
+            | Self::Invoking { .. }
+            | Self::Compiling { .. } => None,
+        }
+    }
+
+    /// This state without anything only admins should see, for sending to participants
+    pub fn for_participant(&self) -> Self {
+        let mut state = self.clone();
+        if let Self::Judging {
+            blocked_syscall,
+            judge_error,
+            ..
+        } = &mut state
+        {
+            *blocked_syscall = None;
+            *judge_error = None;
+        }
+        state
+    }
+
+    /// Set one case's status, for cases run in parallel that finish in any order
+    pub fn set_case(&mut self, i: usize, status: CaseStatus) {
+        if let Self::Judging { cases, .. } = self {
+            if let Some(c) = cases.get_mut(i) {
+                *c = status;
+            }
+        }
+    }
+
+    /// Complete a judging state whose cases ran in parallel so it matches running them in order,
+    /// the first failure decides the verdict and every case after it is marked as not run
+    pub fn finish_parallel(&mut self) {
+        if let Self::Judging {
+            cases,
+            idx,
+            complete,
+            ..
+        } = self
+        {
+            let first_failure = cases
+                .iter()
+                .position(|c| matches!(c, CaseStatus::Failed(_, _)));
+            if let Some(failed) = first_failure {
+                cases
+                    .iter_mut()
+                    .skip(failed + 1)
+                    .for_each(|c| *c = CaseStatus::NotRun);
+            }
+            *idx = first_failure.unwrap_or(cases.len().saturating_sub(1));
+            *complete = true;
+        }
+    }
 }
 
 impl Display for JobState {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            Self::Judging { cases, .. } => {
+            Self::Judging { cases, .. }
+            | Self::Samples { cases, .. }
+            | Self::Invoking { cases, .. } => {
                 for (i, c) in cases.iter().enumerate() {
                     if i != 0 {
                         write!(f, " ")?;
@@ -182,7 +605,9 @@ impl Display for JobState {
                 }
                 Ok(())
             }
-            Self::Testing { status } => write!(f, "{}", status),
+            Self::Testing { status, .. } | Self::Compiling { status, .. } => {
+                write!(f, "{}", status)
+            }
         }
     }
 }
@@ -191,6 +616,22 @@ impl Display for JobState {
 pub enum JobOperation {
     Judging(Vec<TestCase>),
     Testing(String),
+    /// Testing, but keeping all of the output since it's saved as test data rather than shown
+    Generating(String),
+    /// Testing with each of the problem's sample cases, see [`SampleResult`]
+    Samples(Vec<TestCase>),
+    /// Testing with each of several inputs the user gave, see [`JobState::Invoking`]
+    Invoking(Vec<String>),
+    /// Only compiling, for the compiler's diagnostics without running anything, see
+    /// [`JobState::Compiling`]
+    Compiling,
+}
+
+impl JobOperation {
+    /// Whether output is streamed while it's printed, when it's shown to the user anyway
+    pub fn streams_output(&self) -> bool {
+        matches!(self, Self::Testing(_) | Self::Invoking(_) | Self::Compiling)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -203,7 +644,56 @@ pub struct JobRequest {
     pub language_key: String,
     pub language: LanguageRunnerInfo,
     pub soft_limits: (u64, u64),
+    /// Real time limit per case in seconds, `None` uses the isolation config's hard timeout
+    pub wall_time: Option<u64>,
+    /// Run with loopback networking, only set when `isolation.allow_network` is on
+    pub network: bool,
+    /// Workers to split judging cases across, doesn't change the verdict
+    pub parallelism: usize,
+    /// The problem's comparison settings, for cases that don't pick their own mode
+    pub comparison: Comparison,
     pub op: JobOperation,
+    pub flag: Option<String>,
+    /// Practice runs from the archive are saved but never count towards a contest
+    pub practice: bool,
+    /// Save a snapshot of the worker's directory once it's done, an admin asked for the user's
+    /// next run
+    #[serde(default)]
+    pub snapshot: bool,
+}
+
+impl JobRequest {
+    /// Hash of everything that decides a judging verdict, `None` when testing.
+    /// Test case ids aren't included so re-saving a problem with the same cases keeps the key.
+    pub fn cache_key(&self) -> Option<String> {
+        if let JobOperation::Judging(cases) = &self.op {
+            let cases = cases
+                .iter()
+                .map(|c| {
+                    (
+                        &c.stdin,
+                        &c.expected_pattern,
+                        c.use_regex,
+                        c.case_insensitive,
+                        c.compare_mode,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let key = serde_json::to_string(&(
+                &self.language_key,
+                self.soft_limits,
+                self.wall_time,
+                self.network,
+                self.comparison,
+                cases,
+                &self.program,
+            ))
+            .ok()?;
+            Some(sha256::digest(key))
+        } else {
+            None
+        }
+    }
 }
 
 struct JobContext {
@@ -213,12 +703,18 @@ struct JobContext {
     ins: Instant,
 }
 
-fn publish_state(sender: &JobStateSender, state: JobState) {
+pub(super) fn publish_state(sender: &JobStateSender, state: JobState) {
     if let Err(why) = sender.send(state) {
         error!("Couldn't send state update: {:?}", why);
     }
 }
 
+/// Add output to the published state as the worker streams it
+fn live_output_stream(sender: &JobStateSender) -> OutputStream {
+    let sender = sender.clone();
+    Box::new(move |chunk| sender.send_modify(|state| state.push_live_output(chunk)))
+}
+
 impl JobContext {
     fn new(req: &JobRequest, sender: JobStateSender) -> Self {
         Self {
@@ -259,21 +755,12 @@ pub async fn run_job(
     request: &JobRequest,
     state_tx: JobStateSender,
     shutdown: CancellationToken,
-    isolation: &IsolationConfig,
-    pizzaz: u64,
+    workers: &WorkerPoolHandle,
 ) -> (JobState, NaiveDateTime) {
     let started_at = chrono::offset::Utc::now().naive_utc();
     let tx = state_tx.clone();
     let rx = state_tx.subscribe();
-    let res = _run_job(
-        state_tx,
-        shutdown,
-        request,
-        request.language.clone(),
-        isolation.clone(),
-        pizzaz,
-    )
-    .await;
+    let res = _run_job(state_tx, shutdown, request, workers).await;
     match res {
         Ok(state) => (state, started_at),
         Err(e) => {
@@ -282,6 +769,7 @@ pub async fn run_job(
             }
             let mut last_state = rx.borrow().clone();
             let details = last_state.is_testing();
+            last_state.note_error(&e);
             last_state.complete_case(CaseStatus::from_case_error(e, details));
             info!("Job {} State: {}", request.id, last_state);
             publish_state(&tx, last_state.clone());
@@ -294,9 +782,7 @@ async fn _run_job(
     state_tx: JobStateSender,
     shutdown: CancellationToken,
     request: &JobRequest,
-    language: LanguageRunnerInfo,
-    isolation: IsolationConfig,
-    pizzaz: u64,
+    workers: &WorkerPoolHandle,
 ) -> Result<JobState, CaseError> {
     let mut ctx = JobContext::new(request, state_tx);
 
@@ -311,39 +797,186 @@ async fn _run_job(
     }
     .to_string();
 
-    let mut worker = Worker::new(
-        request.id,
-        &request.program,
-        shutdown,
-        language,
-        isolation,
-        pizzaz,
-        &diag,
-        request.soft_limits,
-    )
-    .await
-    .context("Worker Creation Failed")?;
-
-    let res = run_worker(&mut worker, request, &mut ctx).await;
+    if let JobOperation::Judging(cases) = &request.op {
+        if request.parallelism > 1 && cases.len() > 1 {
+            return run_parallel(request, cases, ctx, workers, shutdown, &diag).await;
+        }
+    }
 
-    worker.finish().await?;
+    let stream = request
+        .op
+        .streams_output()
+        .then(|| live_output_stream(&ctx.sender));
+    let (mut pooled, compile_output) =
+        start_worker(request, workers, shutdown, &diag, stream).await?;
+    if let Some(output) = compile_output {
+        ctx.state.set_compile_output(output);
+    }
+    if request.op.streams_output() {
+        // Clears what the compiler streamed, the program's output streams next
+        ctx.publish_state();
+    }
+    let res = run_worker(&mut pooled.worker, request, &mut ctx).await;
+    pooled.worker.stream_output(None);
+    save_snapshot(&mut pooled.worker, request).await;
+    let reusable = res.as_ref().err().is_none_or(can_reuse);
+    workers.release(pooled, reusable).await?;
 
     res.map(|_| ctx.state)
 }
 
+/// Save a snapshot of the worker's directory when the request asks for one, workers killed for
+/// going over a limit are already gone along with their directory
+async fn save_snapshot(worker: &mut Worker, request: &JobRequest) {
+    if !request.snapshot {
+        return;
+    }
+    if !worker.is_alive() {
+        warn!(
+            "Job {} worker was stopped before its directory could be snapshotted",
+            request.id
+        );
+        return;
+    }
+    match worker.snapshot(request.user_id, request.id).await {
+        Ok(Some(path)) => info!("Job {} snapshot saved to {}", request.id, path.display()),
+        Ok(None) => warn!("Job {} directory is too big to snapshot", request.id),
+        Err(why) => error!("Couldn't snapshot job {}: {:?}", request.id, why),
+    }
+}
+
+/// Workers killed or cancelled mid-run can't take another job
+fn can_reuse(e: &CaseError) -> bool {
+    !(e.should_kill_worker() || matches!(e, CaseError::Cancelled))
+}
+
+/// Claim a worker and compile the program in it, unless it was already compiled and cached.
+/// Returns what the compiler printed too, `None` when it was cached. The worker streams output
+/// to `stream` until it's released.
+async fn start_worker(
+    request: &JobRequest,
+    workers: &WorkerPoolHandle,
+    shutdown: CancellationToken,
+    diag: &str,
+    stream: Option<OutputStream>,
+) -> Result<(PooledWorker, Option<String>), CaseError> {
+    let compile_cache = workers.compile_cache();
+    let cache_key = compile_cache.key(&request.language_key, &request.language, &request.program);
+    // Checking a program is for what the compiler says, so it's always compiled
+    let cached = match cache_key.as_ref() {
+        Some(_) if matches!(request.op, JobOperation::Compiling) => None,
+        Some(key) => compile_cache.get(key).await,
+        None => None,
+    };
+
+    let mut pooled = workers
+        .claim(
+            &request.language_key,
+            &request.program,
+            request.soft_limits,
+            shutdown,
+            diag,
+            cached.as_deref(),
+            request.network,
+        )
+        .await
+        .context("Worker Creation Failed")?;
+    pooled.worker.set_wall_time(request.wall_time);
+    pooled.worker.set_comparison(request.comparison);
+    pooled.worker.stream_output(stream);
+
+    if cached.is_some() {
+        return Ok((pooled, None));
+    }
+    match compile(&mut pooled.worker, request, compile_cache, cache_key).await {
+        Ok(output) => Ok((pooled, Some(output))),
+        Err(e) => {
+            pooled.worker.stream_output(None);
+            save_snapshot(&mut pooled.worker, request).await;
+            workers.release(pooled, can_reuse(&e)).await?;
+            Err(e)
+        }
+    }
+}
+
+/// Compile the program, caching what it produced if the language says what to keep
+async fn compile(
+    worker: &mut Worker,
+    request: &JobRequest,
+    compile_cache: &CompileCache,
+    cache_key: Option<String>,
+) -> CaseResult<String> {
+    let output = worker.compile().await?;
+    if let Some(key) = cache_key {
+        let artifacts = worker
+            .read_artifacts(
+                request.language.artifacts.clone(),
+                compile_cache.max_entry_bytes(),
+            )
+            .await?;
+        if let Some(artifacts) = artifacts {
+            if let Err(why) = compile_cache.put(&key, artifacts).await {
+                warn!("Couldn't cache compiled program: {:?}", why);
+            }
+        }
+    }
+    Ok(output)
+}
+
 async fn run_worker(worker: &mut Worker, request: &JobRequest, ctx: &mut JobContext) -> CaseResult {
-    worker.compile().await?;
     match &request.op {
         JobOperation::Testing(stdin) => {
+            let output = worker.run_cmd(Some(stdin)).await?;
+            ctx.state.complete_case(CaseStatus::passed(output));
+            ctx.publish_state();
+        }
+        JobOperation::Generating(stdin) => {
             let output = worker.run_cmd(Some(stdin)).await?;
             ctx.state.complete_case(CaseStatus::Passed(output));
             ctx.publish_state();
         }
-        JobOperation::Judging(cases) => {
-            for case in cases.iter() {
-                let output = worker.run_case(case).await?;
+        JobOperation::Compiling => {
+            ctx.state.complete_case(CaseStatus::Passed(String::new()));
+            ctx.publish_state();
+        }
+        JobOperation::Invoking(inputs) => {
+            for stdin in inputs {
+                match worker.run_cmd(Some(stdin)).await {
+                    Ok(output) => ctx.state.complete_input(CaseStatus::passed(output)),
+                    // A worker that's gone can't run the rest
+                    Err(e) if !can_reuse(&e) => return Err(e),
+                    Err(e) => ctx
+                        .state
+                        .complete_input(CaseStatus::from_case_error(e, true)),
+                }
+                ctx.publish_state();
+            }
+        }
+        JobOperation::Samples(cases) => {
+            for case in cases {
+                let output = worker.run_cmd(Some(&case.stdin)).await?;
+                let output = truncate_output(output);
+                ctx.state
+                    .set_sample_result(SampleResult::new(case, &output, &request.comparison));
                 ctx.state.complete_case(CaseStatus::Passed(output));
                 ctx.publish_state();
+            }
+        }
+        JobOperation::Judging(cases) => {
+            for (i, case) in cases.iter().enumerate() {
+                let started = Instant::now();
+                let res = worker.run_case(case).await;
+                ctx.state.set_case_time(i, started.elapsed());
+                let output = match res {
+                    Ok(output) => output,
+                    Err(e) => {
+                        // Publish the time so it's in the state the error is added to
+                        ctx.publish_state();
+                        return Err(e);
+                    }
+                };
+                ctx.state.complete_case(CaseStatus::passed(output));
+                ctx.publish_state();
                 if ctx.state.complete() {
                     break;
                 }
@@ -352,3 +985,102 @@ async fn run_worker(worker: &mut Worker, request: &JobRequest, ctx: &mut JobCont
     }
     Ok(())
 }
+
+/// Judge with the cases split across several workers, each taking the next case in order.
+/// Every worker compiles before any case runs, so a compile error fails the job the same way it
+/// would with one worker. Once a case fails no later cases are started, cases already running
+/// are left to finish since an earlier one could still fail and take the verdict.
+async fn run_parallel(
+    request: &JobRequest,
+    cases: &[TestCase],
+    ctx: JobContext,
+    workers: &WorkerPoolHandle,
+    shutdown: CancellationToken,
+    diag: &str,
+) -> Result<JobState, CaseError> {
+    let count = request.parallelism.min(cases.len());
+    let started =
+        join_all((0..count).map(|_| start_worker(request, workers, shutdown.clone(), diag, None)))
+            .await;
+
+    let mut pooled = Vec::with_capacity(count);
+    let mut error = None;
+    for res in started {
+        match res {
+            Ok((p, _)) => pooled.push(p),
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    if let Some(e) = error {
+        for p in pooled {
+            workers.release(p, true).await?;
+        }
+        return Err(e);
+    }
+
+    let next = AtomicUsize::new(0);
+    let first_failure = AtomicUsize::new(usize::MAX);
+    let ctx = Mutex::new(ctx);
+    let reusable = join_all(
+        pooled
+            .iter_mut()
+            .map(|p| run_shard(&mut p.worker, cases, &ctx, &next, &first_failure)),
+    )
+    .await;
+    if let Some(p) = pooled.first_mut() {
+        save_snapshot(&mut p.worker, request).await;
+    }
+    for (p, reusable) in pooled.into_iter().zip(reusable) {
+        workers.release(p, reusable).await?;
+    }
+
+    let mut ctx = ctx.into_inner();
+    ctx.state.finish_parallel();
+    ctx.publish_state();
+    Ok(ctx.state)
+}
+
+/// Run cases in one worker until there are none left or one fails,
+/// returns whether the worker can be reused
+async fn run_shard(
+    worker: &mut Worker,
+    cases: &[TestCase],
+    ctx: &Mutex<JobContext>,
+    next: &AtomicUsize,
+    first_failure: &AtomicUsize,
+) -> bool {
+    loop {
+        let i = next.fetch_add(1, Ordering::SeqCst);
+        if i >= cases.len() || i > first_failure.load(Ordering::SeqCst) {
+            return true;
+        }
+        {
+            let mut ctx = ctx.lock().await;
+            ctx.state.set_case(i, CaseStatus::Running);
+            ctx.publish_state();
+        }
+        let started = Instant::now();
+        let res = worker.run_case(&cases[i]).await;
+        let mut ctx = ctx.lock().await;
+        ctx.state.set_case_time(i, started.elapsed());
+        match res {
+            Ok(output) => {
+                ctx.state.set_case(i, CaseStatus::passed(output));
+                ctx.publish_state();
+            }
+            Err(e) => {
+                if let CaseError::Judge(ref why) = e {
+                    error!("Job {} Judge Error: {}", ctx.id, why);
+                }
+                first_failure.fetch_min(i, Ordering::SeqCst);
+                let reusable = can_reuse(&e);
+                ctx.state.note_error(&e);
+                ctx.state.set_case(i, CaseStatus::from_case_error(e, false));
+                ctx.publish_state();
+                return reusable;
+            }
+        }
+    }
+}
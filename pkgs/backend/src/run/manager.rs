@@ -1,26 +1,44 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use chrono::NaiveDateTime;
-use log::error;
+use log::{error, info, warn};
 use rocket::figment::Profile;
 use rocket_db_pools::Pool;
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 
-use crate::contests::{Contest, Participant};
+use crate::auth::users::User;
+use crate::contests::{Contest, Participant, Team};
 use crate::db::{DbPool, DbPoolConnection};
 use crate::error::prelude::*;
 use crate::leaderboard::LeaderboardManagerHandle;
-use crate::problems::{JudgeRun, ProblemCompletion};
-
-use super::job::{run_job, JobOperation, JobRequest};
-use super::worker::IsolationConfig;
+use crate::problems::{Comparison, JudgeRun, Problem, ProblemCompletion, StarterCode, TestCase};
+
+use super::compile_cache::CompileCache;
+use super::fingerprint;
+use super::job::{publish_state, CaseStatus, JobOperation, JobRequest};
+use super::nodes::{Scheduler, SchedulerHandle};
+use super::pending::PendingJob;
+use super::pool::{WorkerPool, WorkerPoolHandle};
+use super::quarantine::QuarantinedRun;
+use super::versions::{self, ToolchainVersionsHandle};
+use super::worker::CGroupCapabilities;
 
 use super::config::{LanguageRunnerInfo, RunConfig};
 use super::{JobState, JobStateReceiver};
 
 type UserId = i64;
+type TeamId = i64;
+
+/// How many times a pending job can be cut off by a restart before it's dropped
+const MAX_RESUMES: i64 = 3;
+
+/// Shown in place of the judge error when a run is quarantined, see [`QuarantinedRun`]
+const QUARANTINED_MESSAGE: &str =
+    "Judge Error\nSomething went wrong on our end, an admin will judge this run again";
 
 type RunHandle = Arc<Mutex<Option<(i64, JobStateReceiver, CancellationToken)>>>;
 
@@ -34,15 +52,26 @@ pub type ProblemUpdatedSender = tokio::sync::watch::Sender<ProblemUpdatedMessage
 
 pub struct RunManager {
     config: RunConfig,
-    isolation_config: IsolationConfig,
+    worker_pool: WorkerPoolHandle,
+    scheduler: SchedulerHandle,
     language_runner_info: HashMap<String, LanguageRunnerInfo>,
     id_counter: u64,
     jobs: HashMap<UserId, RunHandle>,
+    /// In team contests the same handles are also kept per team and problem, so only one
+    /// member can be judged on a problem at a time
+    team_jobs: HashMap<(TeamId, i64), RunHandle>,
     db_pool: DbPool,
     job_started_channel: (JobStartedSender, JobStartedReceiver),
     problem_updated_channels: HashMap<i64, ProblemUpdatedSender>,
     leaderboard_handle: LeaderboardManagerHandle,
     shutdown: CancellationToken,
+    /// When set new jobs are refused so the server can be restarted once in-flight ones finish
+    draining: Arc<AtomicBool>,
+    in_flight: Arc<AtomicUsize>,
+    /// What cgroups could do when workers fell back to running without them
+    reduced_enforcement: Option<CGroupCapabilities>,
+    /// Users whose next run gets a snapshot of the worker's directory saved
+    snapshot_users: HashSet<UserId>,
 }
 
 pub struct ManagerJobRequest {
@@ -52,7 +81,13 @@ pub struct ManagerJobRequest {
     pub program: String,
     pub language_key: String,
     pub soft_limits: (u64, u64),
+    pub wall_time: Option<u64>,
+    pub network: bool,
+    pub parallelism: usize,
+    pub comparison: Comparison,
     pub op: JobOperation,
+    pub flag: Option<String>,
+    pub practice: bool,
 }
 
 impl RunManager {
@@ -61,41 +96,117 @@ impl RunManager {
         config: RunConfig,
         leaderboard_manager: LeaderboardManagerHandle,
         pool: DbPool,
+        versions: ToolchainVersionsHandle,
         shutdown: CancellationToken,
     ) -> Result<Self> {
         let (tx, rx) = tokio::sync::broadcast::channel(10);
 
         let run_data = config
-            .languages
-            .iter()
-            .map(|(k, l)| {
-                let mut l = l.clone();
-                if let Some(compiled_cmd) = l.runner.compile_cmd.as_mut() {
-                    compiled_cmd.setup()?;
-                }
-                l.runner.run_cmd.setup()?;
-                Ok::<(String, LanguageRunnerInfo), anyhow::Error>((k.clone(), l.runner))
-            })
-            .collect::<Result<_, _>>()
+            .runner_info()
             .context("Failed to initialize language runner data")?;
 
         let mut isolation_config = config.isolation.clone();
         isolation_config.setup(profile.as_str() == "debug").await?;
+        let reduced_enforcement = isolation_config.reduced_enforcement.then(|| {
+            isolation_config
+                .cgroup_capabilities
+                .clone()
+                .unwrap_or_default()
+        });
+
+        let drift_check = fingerprint::check_for_drift(
+            pool.clone(),
+            run_data.clone(),
+            isolation_config.include_bins().to_vec(),
+        );
+        tokio::spawn(async move {
+            if let Err(why) = drift_check.await {
+                error!("Couldn't check for toolchain drift: {:?}", why);
+            }
+        });
+        tokio::spawn(versions::probe_versions(
+            config.clone(),
+            run_data.clone(),
+            isolation_config.clone(),
+            versions,
+            shutdown.clone(),
+        ));
+
+        let compile_cache = CompileCache::new(config.compile_cache.clone()).await?;
+        let worker_pool = WorkerPool::new(
+            config.pool.clone(),
+            run_data.clone(),
+            isolation_config,
+            config.pizzaz,
+            compile_cache,
+            shutdown.clone(),
+        );
+        tokio::spawn(worker_pool.clone().fill());
+
+        let scheduler = Scheduler::new(config.nodes.clone(), shutdown.clone());
 
         Ok(Self {
             config,
-            isolation_config,
+            worker_pool,
+            scheduler,
             language_runner_info: run_data,
             id_counter: 1,
             leaderboard_handle: leaderboard_manager,
             jobs: HashMap::with_capacity(10),
+            team_jobs: HashMap::with_capacity(10),
             db_pool: pool,
             job_started_channel: (tx, rx),
             problem_updated_channels: HashMap::with_capacity(5),
             shutdown,
+            draining: Arc::new(AtomicBool::new(false)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            reduced_enforcement,
+            snapshot_users: HashSet::new(),
         })
     }
 
+    /// Snapshot the worker's directory for the user's next run (or stop waiting to)
+    pub fn set_snapshot_next_run(&mut self, user_id: UserId, snapshot: bool) {
+        if snapshot {
+            self.snapshot_users.insert(user_id);
+        } else {
+            self.snapshot_users.remove(&user_id);
+        }
+    }
+
+    /// Users waiting for their next run to be snapshotted
+    pub fn snapshot_users(&self) -> Vec<UserId> {
+        self.snapshot_users.iter().copied().collect()
+    }
+
+    /// Why workers run without cgroups, `None` when they have them
+    pub fn reduced_enforcement(&self) -> Option<&CGroupCapabilities> {
+        self.reduced_enforcement.as_ref()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Stop (or resume) accepting new jobs, jobs already judging are left to finish
+    pub fn set_draining(&mut self, draining: bool) {
+        self.draining.store(draining, Ordering::SeqCst);
+        if draining {
+            info!("Draining, new runs will be refused");
+            if self.in_flight.load(Ordering::SeqCst) == 0 {
+                info!("No runs are in flight, the server can be safely stopped");
+            }
+        } else {
+            info!("Stopped draining, accepting runs again");
+        }
+    }
+
+    fn job_finished(in_flight: &AtomicUsize, draining: &AtomicBool) {
+        if in_flight.fetch_sub(1, Ordering::SeqCst) == 1 && draining.load(Ordering::SeqCst) {
+            info!("All runs have finished, the server can be safely stopped");
+        }
+    }
+
     pub async fn all_active_jobs(&self) -> Vec<(UserId, i64)> {
         let mut active_jobs = Vec::with_capacity(self.jobs.len());
         for (user_id, handle) in self.jobs.iter() {
@@ -107,6 +218,10 @@ impl RunManager {
         active_jobs
     }
 
+    pub fn scheduler(&self) -> SchedulerHandle {
+        self.scheduler.clone()
+    }
+
     pub fn subscribe(&self) -> JobStartedReceiver {
         self.job_started_channel.0.subscribe()
     }
@@ -124,7 +239,12 @@ impl RunManager {
         }
     }
 
-    async fn start_job(&mut self, request: JobRequest) -> Result<(), String> {
+    async fn start_job(
+        &mut self,
+        request: JobRequest,
+        team_id: Option<TeamId>,
+        resumed: bool,
+    ) -> Result<(), String> {
         if request.program.len() > self.config.max_program_length {
             return Err(format!(
                 "Program too long, max length is {} bytes",
@@ -135,7 +255,6 @@ impl RunManager {
         let user_id = request.user_id;
         let problem_id = request.problem_id;
         let contest_id = request.contest_id;
-        let pizzaz = self.config.pizzaz;
         let program = request.program.clone();
 
         let shutdown = CancellationToken::new();
@@ -150,6 +269,9 @@ impl RunManager {
         ))));
 
         self.jobs.insert(user_id, handle.clone());
+        if let Some(team_id) = team_id {
+            self.team_jobs.insert((team_id, problem_id), handle.clone());
+        }
 
         let pool = self.db_pool.clone();
 
@@ -157,27 +279,115 @@ impl RunManager {
 
         let shutdown_job = shutdown.clone();
 
-        let isolation = self.isolation_config.clone();
+        let workers = self.worker_pool.clone();
+
+        let scheduler = self.scheduler.clone();
+
+        let cache_key = request.cache_key();
+
+        let in_flight = self.in_flight.clone();
+        let draining = self.draining.clone();
+        in_flight.fetch_add(1, Ordering::SeqCst);
+
+        let pending = matches!(request.op, JobOperation::Judging(_));
+        let server_shutdown = self.shutdown.clone();
+        let judge_error_retries = self.config.judge_error_retries;
 
         tokio::spawn(async move {
-            let (state, ran_at) =
-                run_job(&request, state_tx, shutdown_job, &isolation, pizzaz).await;
+            if pending && !resumed {
+                if let Err(why) = Self::remember_pending(&pool, &request).await {
+                    error!("Couldn't save pending job: {:?}", why);
+                }
+            }
+
+            let cached = if let Some(key) = cache_key.as_ref() {
+                Self::find_cached_verdict(&pool, contest_id, problem_id, key)
+                    .await
+                    .unwrap_or_else(|why| {
+                        error!("Couldn't look up cached verdict: {:?}", why);
+                        None
+                    })
+            } else {
+                None
+            };
+
+            let (mut state, mut ran_at) = if let Some(cached) = cached.as_ref() {
+                let state = JobState::new_all_passed(cached.total_cases as usize);
+                publish_state(&state_tx, state.clone());
+                (state, chrono::offset::Utc::now().naive_utc())
+            } else {
+                scheduler
+                    .dispatch(&request, state_tx.clone(), shutdown_job.clone(), &workers)
+                    .await
+            };
+
+            // Judge errors are on us rather than the program, so judge it again before anyone
+            // sees one. Workers that hit one are thrown away, so each retry gets a fresh worker.
+            let mut attempts = 1;
+            while state.judge_error().is_some()
+                && attempts <= judge_error_retries
+                && !shutdown_job.is_cancelled()
+            {
+                warn!(
+                    "Job {} hit a judge error, retrying (attempt {} of {})",
+                    request.id,
+                    attempts + 1,
+                    judge_error_retries + 1
+                );
+                publish_state(&state_tx, JobState::new_for_op(&request.op));
+                (state, ran_at) = scheduler
+                    .dispatch(&request, state_tx.clone(), shutdown_job.clone(), &workers)
+                    .await;
+                attempts += 1;
+            }
+
+            // Cut off by the server stopping, it's judged again on startup instead of being saved
+            let interrupted = pending && server_shutdown.is_cancelled();
 
-            if !matches!(state, JobState::Judging { .. }) {
+            if interrupted || !matches!(state, JobState::Judging { .. }) {
                 handle.lock().await.take();
+                Self::job_finished(&in_flight, &draining);
                 return;
             }
 
+            let quarantined = state.judge_error().map(str::to_string);
+            if quarantined.is_some() {
+                let (case, _, _) = state.last_error();
+                state.set_case(
+                    case,
+                    CaseStatus::Failed(false, QUARANTINED_MESSAGE.to_string()),
+                );
+                publish_state(&state_tx, state.clone());
+            }
+
             match pool.get().await {
+                Ok(mut conn) if quarantined.is_some() => {
+                    let error = quarantined.unwrap_or_default();
+                    warn!(
+                        "Quarantining job {} after {} judge errors: {}",
+                        request.id, attempts, error
+                    );
+                    if let Err(why) =
+                        QuarantinedRun::save(&mut conn, &request, &error, attempts as i64).await
+                    {
+                        error!("Couldn't quarantine run: {:?}", why);
+                    }
+                    if let Err(why) = PendingJob::remove(&mut conn, user_id).await {
+                        error!("Couldn't remove pending job: {:?}", why);
+                    }
+                }
                 Ok(mut conn) => {
-                    let run = JudgeRun::from_job_state(
+                    let mut run = JudgeRun::from_job_state(
                         problem_id,
                         user_id,
                         program,
                         request.language_key.clone(),
                         &state,
                         ran_at,
+                        request.flag.clone(),
                     );
+                    run.cache_key = cache_key;
+                    run.cached_from = cached.map(|c| c.id);
                     if let Err(why) = Self::save_run(
                         &mut conn,
                         contest_id,
@@ -186,18 +396,23 @@ impl RunManager {
                         run,
                         ran_at,
                         state.last_error().1,
+                        request.practice,
                         leaderboard_handle,
                     )
                     .await
                     {
                         error!("Couldn't save run: {:?}", why);
                     }
+                    if let Err(why) = PendingJob::remove(&mut conn, user_id).await {
+                        error!("Couldn't remove pending job: {:?}", why);
+                    }
                 }
                 Err(e) => {
                     error!("Couldn't get db connection: {:?}", e);
                 }
             }
             handle.lock().await.take();
+            Self::job_finished(&in_flight, &draining);
         });
 
         self.job_started_channel
@@ -208,8 +423,142 @@ impl RunManager {
         Ok(())
     }
 
+    async fn remember_pending(pool: &DbPool, request: &JobRequest) -> Result {
+        let mut conn = pool.get().await.context("Couldn't get db connection")?;
+        PendingJob::save(&mut conn, request).await
+    }
+
+    /// Start judging jobs cut off by the last shutdown again, jobs that were cut off
+    /// too many times are dropped in case they're what's bringing the server down
+    pub async fn resume_pending(&mut self) -> Result {
+        let mut conn = self
+            .db_pool
+            .get()
+            .await
+            .context("Couldn't get db connection")?;
+        let jobs = PendingJob::take_interrupted(&mut conn).await?;
+        let mut resumed = 0;
+        for job in jobs {
+            if job.attempts > MAX_RESUMES {
+                warn!(
+                    "Dropping pending job for user {} on problem {}, it was interrupted {} times",
+                    job.user_id, job.problem_id, job.attempts
+                );
+                PendingJob::remove(&mut conn, job.user_id).await?;
+                continue;
+            }
+            let problem = Problem::by_id(&mut conn, job.contest_id, job.problem_id).await?;
+            let res = if let Some(problem) = problem {
+                let cases = TestCase::get_for_problem(&mut conn, problem.id).await?;
+                let req = self.create_job_request(ManagerJobRequest {
+                    user_id: job.user_id,
+                    problem_id: problem.id,
+                    contest_id: problem.contest_id,
+                    program: job.program,
+                    language_key: job.language,
+                    soft_limits: (problem.cpu_time as u64, problem.memory_limit as u64), // `as` is safe due to DB constraint
+                    wall_time: problem.wall_time_limit(),
+                    network: problem.allow_network,
+                    parallelism: problem.parallelism as usize, // Same here
+                    comparison: Comparison::for_problem(&problem),
+                    op: JobOperation::Judging(cases),
+                    flag: job.flag,
+                    practice: job.practice,
+                });
+                let team_id = if job.practice {
+                    None
+                } else {
+                    self.team_for(job.contest_id, job.user_id).await
+                };
+                match req {
+                    Ok(req) => self.start_job(req, team_id, true).await,
+                    Err(why) => Err(why),
+                }
+            } else {
+                Err("Problem no longer exists".to_string())
+            };
+            if let Err(why) = res {
+                warn!(
+                    "Couldn't resume pending job for user {} on problem {}: {}",
+                    job.user_id, job.problem_id, why
+                );
+                PendingJob::remove(&mut conn, job.user_id).await?;
+            } else {
+                resumed += 1;
+            }
+        }
+        if resumed > 0 {
+            info!(
+                "Resumed {} judging job(s) interrupted by the last shutdown",
+                resumed
+            );
+        }
+        Ok(())
+    }
+
+    /// Judge a quarantined run again, e.g. once whatever was breaking the judge is fixed. It's
+    /// queued like a new submission, so it's refused if the user already has a job running.
+    pub async fn retry_quarantined(&mut self, run: &QuarantinedRun) -> Result<(), String> {
+        let problem = async {
+            let mut conn = self
+                .db_pool
+                .get()
+                .await
+                .context("Couldn't get db connection")?;
+            let problem = Problem::by_id(&mut conn, run.contest_id, run.problem_id).await?;
+            let cases = match problem.as_ref() {
+                Some(problem) => TestCase::get_for_problem(&mut conn, problem.id).await?,
+                None => vec![],
+            };
+            Ok::<_, anyhow::Error>(problem.map(|p| (p, cases)))
+        };
+        let (problem, cases) = match problem.await {
+            Ok(Some(found)) => found,
+            Ok(None) => return Err("Problem no longer exists".to_string()),
+            Err(why) => {
+                error!("Couldn't get problem {}: {:?}", run.problem_id, why);
+                return Err("Couldn't get the problem, try again in a moment".to_string());
+            }
+        };
+        self.request_job(ManagerJobRequest {
+            user_id: run.user_id,
+            problem_id: problem.id,
+            contest_id: problem.contest_id,
+            program: run.program.clone(),
+            language_key: run.language.clone(),
+            soft_limits: (problem.cpu_time as u64, problem.memory_limit as u64), // `as` is safe due to DB constraint
+            wall_time: problem.wall_time_limit(),
+            network: problem.allow_network,
+            parallelism: problem.parallelism as usize, // Same here
+            comparison: Comparison::for_problem(&problem),
+            op: JobOperation::Judging(cases),
+            flag: run.flag.clone(),
+            practice: run.practice,
+        })
+        .await
+    }
+
+    /// An accepted run with the same cache key, only if the contest opted in to reusing verdicts.
+    /// Failed verdicts are never reused since judge errors and time limits can be flaky.
+    async fn find_cached_verdict(
+        pool: &DbPool,
+        contest_id: i64,
+        problem_id: i64,
+        cache_key: &str,
+    ) -> Result<Option<JudgeRun>> {
+        let mut conn = pool.get().await.context("Couldn't get db connection")?;
+        let enabled = Contest::get(&mut conn, contest_id)
+            .await?
+            .is_some_and(|c| c.verdict_cache);
+        if enabled {
+            JudgeRun::find_cached(&mut conn, problem_id, cache_key).await
+        } else {
+            Ok(None)
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
-    async fn save_run(
+    pub(super) async fn save_run(
         conn: &mut DbPoolConnection,
         contest_id: i64,
         problem_id: i64,
@@ -217,6 +566,7 @@ impl RunManager {
         judge_run: JudgeRun,
         ran_at: NaiveDateTime,
         penalty_applies: bool,
+        practice: bool,
         leaderboard_handle: LeaderboardManagerHandle,
     ) -> Result {
         let contest = Contest::get(conn, contest_id)
@@ -226,9 +576,15 @@ impl RunManager {
         let success = judge_run.success();
         judge_run.write_to_db(conn).await?;
 
+        if practice {
+            return Ok(());
+        }
+
         let participant = Participant::get(conn, contest_id, user_id).await?;
 
-        if participant.as_ref().is_none_or(|p| p.is_judge) || !contest.is_running() {
+        if participant.as_ref().is_none_or(|p| !p.competes())
+            || !contest.is_running_for(participant.as_ref())
+        {
             return Ok(());
         }
 
@@ -246,18 +602,66 @@ impl RunManager {
                     )
                 });
 
+        // Runs after a solve don't change anything, so they aren't worth a ticker line
+        let newsworthy = completion.completed_at.is_none();
         if success && completion.completed_at.is_none() {
             completion.completed_at = Some(ran_at);
         } else if penalty_applies && completion.completed_at.is_none() {
             completion.number_wrong += 1;
         }
 
-        completion.upsert(conn).await?;
+        // Teammates share completions, so the solve and any penalty count for the whole team
+        let sharers = Team::completion_sharers(conn, &contest, &participant).await?;
+        completion.upsert_shared(conn, &sharers).await?;
+
+        let ticker = if newsworthy {
+            let team = if contest.max_team_size.is_some() {
+                Team::for_participant(conn, participant.p_id).await?
+            } else {
+                None
+            };
+            let name = match team {
+                Some(team) => team.name,
+                None => User::get(conn, user_id)
+                    .await?
+                    .map(|u| u.display_name().to_string())
+                    .unwrap_or_default(),
+            };
+            let label = sqlx::query!("SELECT label FROM problem WHERE id = ?", problem_id)
+                .fetch_one(&mut **conn)
+                .await
+                .context("Failed to get problem label")?
+                .label;
+            Some((name, label))
+        } else {
+            None
+        };
 
+        let mut leaderboard_manager = leaderboard_handle.lock().await;
         if completion.completed_at.is_some() {
-            let mut leaderboard_manager = leaderboard_handle.lock().await;
+            for sharer in sharers.iter() {
+                let shared = ProblemCompletion {
+                    participant_id: sharer.p_id,
+                    ..completion
+                };
+                leaderboard_manager
+                    .process_completion(&shared, &contest)
+                    .await;
+            }
+        }
+
+        if let Some((name, label)) = ticker {
+            let sharer_ids = sharers.iter().map(|p| p.p_id).collect::<Vec<_>>();
             leaderboard_manager
-                .process_completion(&completion, &contest)
+                .announce(
+                    &contest,
+                    &sharer_ids,
+                    &name,
+                    problem_id,
+                    &label,
+                    success,
+                    ran_at,
+                )
                 .await;
         }
 
@@ -284,12 +688,14 @@ impl RunManager {
     }
 
     pub async fn shutdown(&mut self) {
+        self.team_jobs.clear();
         for (_, handle) in self.jobs.drain() {
             let handle = handle.lock().await;
             if let Some((_, _, shutdown)) = handle.as_ref() {
                 shutdown.cancel();
             }
         }
+        self.worker_pool.drain().await;
     }
 
     pub async fn update_problem(&mut self, problem_id: i64) {
@@ -317,6 +723,9 @@ impl RunManager {
             .ok_or_else(|| format!("Language {} not found", req.language_key))?
             .clone();
 
+        let soft_limits = language_info.effective_limits(req.soft_limits);
+        let wall_time = language_info.effective_wall_time(req.wall_time);
+
         let id = self.id_counter;
         self.id_counter += 1;
 
@@ -328,24 +737,209 @@ impl RunManager {
             program: req.program,
             language_key: req.language_key,
             language: language_info,
-            soft_limits: req.soft_limits,
+            soft_limits,
+            wall_time,
+            network: req.network && self.config.isolation.allow_network,
+            parallelism: req.parallelism,
+            comparison: req.comparison,
             op: req.op,
+            flag: req.flag,
+            practice: req.practice,
+            snapshot: false,
         })
     }
 
-    pub async fn request_job(&mut self, request: ManagerJobRequest) -> Result<(), String> {
-        if let Some(handle) = self.jobs.get(&request.user_id) {
-            let handle = handle.lock().await;
-            if handle.is_some() {
-                Err("User already has a job running".to_string())
-            } else {
-                drop(handle);
-                let req = self.create_job_request(request)?;
-                self.start_job(req).await
+    /// Build a request to run outside the manager's queue, along with the workers to run it on
+    pub fn prepare_job(
+        &mut self,
+        request: ManagerJobRequest,
+    ) -> Result<(JobRequest, WorkerPoolHandle), String> {
+        let req = self.create_job_request(request)?;
+        Ok((req, self.worker_pool.clone()))
+    }
+
+    /// Token for cancelling jobs run outside the manager when the server shuts down
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.child_token()
+    }
+
+    /// Judge a saved run again against the problem's current cases. It waits its turn with the
+    /// scheduler like a submission, so judge nodes share the load and draining waits for it,
+    /// but nothing is saved, the caller decides what to do with the resulting state
+    pub fn rejudge(
+        &mut self,
+        run: &JudgeRun,
+        problem: &Problem,
+        cases: Vec<TestCase>,
+        cancel: CancellationToken,
+    ) -> Result<impl Future<Output = (JobRequest, JobState)> + Send + 'static, String> {
+        if self.is_draining() {
+            return Err("Judging is paused while the server is updated".to_string());
+        }
+        let (request, workers) = self.prepare_job(ManagerJobRequest {
+            user_id: run.user_id,
+            problem_id: problem.id,
+            contest_id: problem.contest_id,
+            program: run.program.clone(),
+            language_key: run.language.clone(),
+            soft_limits: (problem.cpu_time as u64, problem.memory_limit as u64), // `as` is safe due to DB constraint
+            wall_time: problem.wall_time_limit(),
+            network: problem.allow_network,
+            parallelism: problem.parallelism as usize, // Same here
+            comparison: Comparison::for_problem(problem),
+            op: JobOperation::Judging(cases),
+            flag: run.flag.clone(),
+            practice: false,
+        })?;
+        let scheduler = self.scheduler.clone();
+        let in_flight = self.in_flight.clone();
+        let draining = self.draining.clone();
+        let judge_error_retries = self.config.judge_error_retries;
+        in_flight.fetch_add(1, Ordering::SeqCst);
+
+        Ok(async move {
+            let (state_tx, _state_rx) =
+                tokio::sync::watch::channel(JobState::new_for_op(&request.op));
+            let (mut state, _) = scheduler
+                .dispatch(&request, state_tx.clone(), cancel.clone(), &workers)
+                .await;
+            // Same as submissions, a judge error shouldn't decide the new verdict
+            let mut attempts = 1;
+            while state.judge_error().is_some()
+                && attempts <= judge_error_retries
+                && !cancel.is_cancelled()
+            {
+                (state, _) = scheduler
+                    .dispatch(&request, state_tx.clone(), cancel.clone(), &workers)
+                    .await;
+                attempts += 1;
+            }
+            Self::job_finished(&in_flight, &draining);
+            (request, state)
+        })
+    }
+
+    async fn is_contest_paused(&self, contest_id: i64) -> bool {
+        let paused = async {
+            let mut conn = self
+                .db_pool
+                .get()
+                .await
+                .context("Couldn't get db connection")?;
+            Ok::<_, anyhow::Error>(
+                Contest::get(&mut conn, contest_id)
+                    .await?
+                    .is_some_and(|c| c.is_paused()),
+            )
+        };
+        paused.await.unwrap_or_else(|why| {
+            error!(
+                "Couldn't check if contest {} is paused: {:?}",
+                contest_id, why
+            );
+            false
+        })
+    }
+
+    /// Why the contest's source checks turn a program away, see [`Contest::check_source`], or
+    /// why it doesn't keep the problem's locked starter code, see [`StarterCode::check`]
+    async fn source_rejected(&self, request: &ManagerJobRequest) -> Option<String> {
+        let checks = async {
+            let mut conn = self
+                .db_pool
+                .get()
+                .await
+                .context("Couldn't get db connection")?;
+            let contest = Contest::get(&mut conn, request.contest_id).await?;
+            let starter =
+                StarterCode::get(&mut conn, request.problem_id, &request.language_key).await?;
+            Ok::<_, anyhow::Error>((contest, starter))
+        };
+        match checks.await {
+            Ok((contest, starter)) => contest
+                .and_then(|c| c.check_source(&request.program).err())
+                .or_else(|| starter.and_then(|s| s.check(&request.program).err())),
+            Err(why) => {
+                error!(
+                    "Couldn't get contest {} to check a program's source: {:?}",
+                    request.contest_id, why
+                );
+                None
             }
+        }
+    }
+
+    /// The team a user submits for, only in contests with teams turned on
+    async fn team_for(&self, contest_id: i64, user_id: UserId) -> Option<TeamId> {
+        let team = async {
+            let mut conn = self
+                .db_pool
+                .get()
+                .await
+                .context("Couldn't get db connection")?;
+            let in_team_mode = Contest::get(&mut conn, contest_id)
+                .await?
+                .is_some_and(|c| c.max_team_size.is_some());
+            let participant = if in_team_mode {
+                Participant::get(&mut conn, contest_id, user_id).await?
+            } else {
+                None
+            };
+            let team = match participant {
+                Some(p) => Team::for_participant(&mut conn, p.p_id).await?,
+                None => None,
+            };
+            Ok::<_, anyhow::Error>(team.map(|t| t.id))
+        };
+        team.await.unwrap_or_else(|why| {
+            error!("Couldn't get team for user {}: {:?}", user_id, why);
+            None
+        })
+    }
+
+    async fn has_active_job<K: std::hash::Hash + Eq>(
+        jobs: &HashMap<K, RunHandle>,
+        key: &K,
+    ) -> bool {
+        match jobs.get(key) {
+            Some(handle) => handle.lock().await.is_some(),
+            None => false,
+        }
+    }
+
+    pub async fn request_job(&mut self, request: ManagerJobRequest) -> Result<(), String> {
+        if self.is_draining() {
+            return Err(
+                "Judging is paused for a moment while the server is updated, try again in a few minutes"
+                    .to_string(),
+            );
+        }
+        if !request.practice && self.is_contest_paused(request.contest_id).await {
+            return Err(
+                "The contest clock is paused, you can submit again once it's resumed".to_string(),
+            );
+        }
+        if let Some(why) = self.source_rejected(&request).await {
+            return Err(why);
+        }
+        if Self::has_active_job(&self.jobs, &request.user_id).await {
+            return Err("User already has a job running".to_string());
+        }
+        let team_id = if request.practice {
+            None
         } else {
-            let req = self.create_job_request(request)?;
-            self.start_job(req).await
+            self.team_for(request.contest_id, request.user_id).await
+        };
+        if let Some(team_id) = team_id {
+            if Self::has_active_job(&self.team_jobs, &(team_id, request.problem_id)).await {
+                return Err(
+                    "A teammate is already being judged on this problem, wait for them to finish"
+                        .to_string(),
+                );
+            }
         }
+        let mut req = self.create_job_request(request)?;
+        req.snapshot = self.snapshot_users.remove(&req.user_id);
+        self.start_job(req, team_id, false).await
     }
 }
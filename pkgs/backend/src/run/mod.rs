@@ -8,11 +8,23 @@ use tokio_util::sync::CancellationToken;
 
 use crate::{db::Database, leaderboard::LeaderboardManagerHandle};
 
-use self::manager::RunManager;
+use self::{manager::RunManager, rejudge::RejudgeManager, versions::ToolchainVersionsHandle};
 
+mod answers;
+mod compile;
+mod compile_cache;
 mod config;
+pub mod direct;
+pub mod fingerprint;
 mod job;
 mod manager;
+pub mod nodes;
+mod pending;
+mod pool;
+mod quarantine;
+pub mod rejudge;
+mod upload;
+pub mod versions;
 pub mod worker;
 mod ws;
 
@@ -24,7 +36,9 @@ pub type JobStateReceiver = tokio::sync::watch::Receiver<JobStateMessage>;
 pub type ManagerHandle = Arc<Mutex<RunManager>>;
 
 pub use config::RunConfig;
-pub use job::JobState;
+pub use job::{CaseVerdict, JobState};
+pub use quarantine::QuarantinedRun;
+pub use ws::MAX_INVOCATION_INPUTS;
 
 pub struct CodeInfo {
     pub run_config: RunConfig,
@@ -94,23 +108,56 @@ pub fn stage() -> AdHoc {
                 let code_info = serde_json::to_string(&languages_display).unwrap();
                 let leaderboard_manager =
                     rocket.state::<LeaderboardManagerHandle>().unwrap().clone();
+                let versions = ToolchainVersionsHandle::default();
                 let manager = manager::RunManager::new(
                     profile,
                     config.clone(),
-                    leaderboard_manager,
-                    pool,
-                    shutdown,
+                    leaderboard_manager.clone(),
+                    pool.clone(),
+                    versions.clone(),
+                    shutdown.clone(),
                 )
                 .await;
                 match manager {
-                    Ok(manager) => Ok(rocket
-                        .attach(shutdown_fairing)
-                        .manage::<CodeInfo>(CodeInfo {
-                            run_config: config,
-                            languages_json: code_info,
-                        })
-                        .manage::<ManagerHandle>(Arc::new(Mutex::new(manager)))
-                        .mount("/run", routes![ws::ws_channel])),
+                    Ok(mut manager) => {
+                        if let Err(why) = manager.resume_pending().await {
+                            error!("Couldn't resume pending jobs: {:?}", why);
+                        }
+                        let scheduler = manager.scheduler();
+                        let manager: ManagerHandle = Arc::new(Mutex::new(manager));
+                        let rejudge_manager = RejudgeManager::new(
+                            pool,
+                            manager.clone(),
+                            leaderboard_manager,
+                            shutdown,
+                        );
+                        Ok(rocket
+                            .attach(shutdown_fairing)
+                            .manage::<CodeInfo>(CodeInfo {
+                                run_config: config,
+                                languages_json: code_info,
+                            })
+                            .manage::<ManagerHandle>(manager)
+                            .manage::<ToolchainVersionsHandle>(versions)
+                            .manage::<nodes::SchedulerHandle>(scheduler)
+                            .manage::<rejudge::RejudgeManagerHandle>(Arc::new(Mutex::new(
+                                rejudge_manager,
+                            )))
+                            .mount(
+                                "/run",
+                                routes![
+                                    ws::ws_channel,
+                                    ws::archive_ws_channel,
+                                    upload::upload_post,
+                                    compile::compile_post,
+                                    answers::inputs,
+                                    answers::answers_post,
+                                    answers::quiz_post,
+                                    nodes::judge_node_channel
+                                ],
+                            )
+                            .mount("/", routes![versions::environment]))
+                    }
                     Err(why) => {
                         error!("{why:?}");
                         Err(rocket)
@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use log::{info, warn};
+use openssl::memcmp;
+use rocket::{
+    futures::{SinkExt, StreamExt},
+    get,
+    http::Status,
+    request::{self, FromRequest},
+    Request, State,
+};
+use rocket_ws::{stream::DuplexStream, Channel, Message, WebSocket};
+use tokio::{
+    select,
+    time::{self, Instant},
+};
+
+use crate::run::job::publish_state;
+
+use super::{NodeMessage, SchedulerHandle};
+
+/// Nodes that haven't answered a ping in this long are dropped
+const NODE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A judge node that sent the right secret as a bearer token
+pub struct JudgeNode;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for JudgeNode {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let scheduler = match req.guard::<&State<SchedulerHandle>>().await.succeeded() {
+            Some(scheduler) => scheduler,
+            None => return request::Outcome::Error((Status::InternalServerError, ())),
+        };
+        let token = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "));
+        match (scheduler.secret(), token) {
+            (Some(secret), Some(token))
+                if secret.len() == token.len()
+                    && memcmp::eq(secret.as_bytes(), token.as_bytes()) =>
+            {
+                request::Outcome::Success(JudgeNode)
+            }
+            _ => request::Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+#[get("/judge")]
+pub async fn judge_node_channel(
+    ws: WebSocket,
+    _node: JudgeNode,
+    scheduler: &State<SchedulerHandle>,
+) -> Channel<'static> {
+    let scheduler = (*scheduler).clone();
+    ws.channel(move |stream| {
+        Box::pin(async move {
+            node_loop(stream, scheduler).await;
+            Ok(())
+        })
+    })
+}
+
+async fn node_loop(mut stream: DuplexStream, scheduler: SchedulerHandle) {
+    let hello = match stream.next().await {
+        Some(Ok(Message::Text(raw))) => serde_json::from_str::<NodeMessage>(&raw).ok(),
+        _ => None,
+    };
+    let Some(NodeMessage::Hello {
+        name,
+        slots,
+        languages,
+    }) = hello
+    else {
+        warn!("Judge node connected without saying hello");
+        return;
+    };
+
+    let (id, jobs, mut rx) = scheduler.connect(name.clone(), slots, languages);
+    info!("Judge node {} connected with {} slot(s)", name, slots);
+
+    let mut ping = time::interval(Duration::from_secs(10));
+    let mut last_seen = Instant::now();
+
+    loop {
+        select! {
+            _ = ping.tick() => {
+                if last_seen.elapsed() > NODE_TIMEOUT {
+                    warn!("Judge node {} timed out", name);
+                    break;
+                }
+                if stream.send(Message::Ping(vec![])).await.is_err() {
+                    break;
+                }
+            }
+            msg = rx.recv() => {
+                let Some(msg) = msg else {
+                    break;
+                };
+                let raw = serde_json::to_string(&msg).unwrap();
+                if stream.send(Message::Text(raw)).await.is_err() {
+                    break;
+                }
+            }
+            msg = stream.next() => {
+                last_seen = Instant::now();
+                match msg {
+                    Some(Ok(Message::Text(raw))) => match serde_json::from_str::<NodeMessage>(&raw) {
+                        Ok(NodeMessage::State { job_id, state }) => {
+                            if let Some(job) = jobs.lock().unwrap().get(&job_id) {
+                                publish_state(&job.state_tx, state);
+                            }
+                        }
+                        Ok(NodeMessage::Done { job_id, state, ran_at }) => {
+                            if let Some(job) = jobs.lock().unwrap().remove(&job_id) {
+                                job.done.send((state, ran_at)).ok();
+                            }
+                        }
+                        Ok(NodeMessage::Hello { .. }) => {}
+                        Err(why) => {
+                            warn!("Invalid message from judge node {}: {:?}", name, why);
+                        }
+                    },
+                    Some(Ok(Message::Ping(data))) => {
+                        stream.send(Message::Pong(data)).await.ok();
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                }
+            }
+            _ = scheduler.shutdown.cancelled() => {
+                stream.close(None).await.ok();
+                break;
+            }
+        }
+    }
+
+    // Jobs sent after this fail to send instead of waiting on a node that's gone
+    drop(rx);
+    scheduler.disconnect(id);
+    info!("Judge node {} disconnected", name);
+}
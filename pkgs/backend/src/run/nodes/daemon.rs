@@ -0,0 +1,253 @@
+use std::{collections::HashMap, time::Duration};
+
+use log::{error, info, warn};
+use rocket::futures::{SinkExt, StreamExt};
+use tokio::{
+    select,
+    sync::{mpsc, watch},
+};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    error::prelude::*,
+    run::{
+        compile_cache::CompileCache,
+        config::LanguageRunnerInfo,
+        job::{run_job, JobRequest},
+        pool::{WorkerPool, WorkerPoolHandle},
+        worker::CaseError,
+        JobState, RunConfig,
+    },
+};
+
+use super::{failed_state, NodeMessage, ServerMessage};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+const fn default_slots() -> usize {
+    1
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct JudgeNodeConfig {
+    /// WebSocket URL of the server's judge endpoint, e.g. `ws://10.0.0.2:8000/run/judge`
+    pub server: String,
+    /// Has to match `run.nodes.secret` on the server
+    pub secret: String,
+    /// Shown to admins on the server
+    pub name: String,
+    /// Jobs to run at once
+    #[serde(default = "default_slots")]
+    pub slots: usize,
+}
+
+/// Run as a judge node, taking jobs from the server in `judge_node.server` and running them with
+/// this machine's `run` config. Keeps reconnecting until stopped.
+#[tokio::main]
+pub async fn run_judge_node() -> Result {
+    NodeLogger::setup();
+    let figment = crate::figment()?;
+    let config = figment
+        .extract_inner::<RunConfig>("run")
+        .context("Couldn't get run config")?;
+    let node = figment
+        .extract_inner::<JudgeNodeConfig>("judge_node")
+        .context("Couldn't get judge node config")?;
+
+    let languages = config
+        .runner_info()
+        .context("Failed to initialize language runner data")?;
+    let mut isolation = config.isolation.clone();
+    isolation
+        .setup(figment.profile().as_str() == "debug")
+        .await
+        .context("Couldn't setup isolation")?;
+
+    let shutdown = CancellationToken::new();
+    let compile_cache = CompileCache::new(config.compile_cache.clone()).await?;
+    let workers = WorkerPool::new(
+        config.pool.clone(),
+        languages.clone(),
+        isolation,
+        config.pizzaz,
+        compile_cache,
+        shutdown.clone(),
+    );
+    tokio::spawn(workers.clone().fill());
+
+    let ctrl_c = shutdown.clone();
+    tokio::spawn(async move {
+        tokio::signal::ctrl_c().await.ok();
+        info!("Stopping judge node");
+        ctrl_c.cancel();
+    });
+
+    while !shutdown.is_cancelled() {
+        match serve(&node, &languages, &workers, &shutdown).await {
+            Ok(()) => info!("Disconnected from {}", node.server),
+            Err(why) => error!("Connection to {} failed: {:?}", node.server, why),
+        }
+        select! {
+            () = tokio::time::sleep(RECONNECT_DELAY) => {}
+            () = shutdown.cancelled() => {}
+        }
+    }
+
+    workers.drain().await;
+    Ok(())
+}
+
+async fn serve(
+    node: &JudgeNodeConfig,
+    languages: &HashMap<String, LanguageRunnerInfo>,
+    workers: &WorkerPoolHandle,
+    shutdown: &CancellationToken,
+) -> Result {
+    let mut request = node
+        .server
+        .as_str()
+        .into_client_request()
+        .context("Invalid server URL")?;
+    request.headers_mut().insert(
+        "Authorization",
+        format!("Bearer {}", node.secret)
+            .parse()
+            .context("Invalid secret")?,
+    );
+    let (mut stream, _) = tokio_tungstenite::connect_async(request)
+        .await
+        .context("Couldn't connect")?;
+    info!("Connected to {} as {}", node.server, node.name);
+
+    let hello = NodeMessage::Hello {
+        name: node.name.clone(),
+        slots: node.slots,
+        languages: languages.keys().cloned().collect(),
+    };
+    stream
+        .send(Message::Text(serde_json::to_string(&hello)?))
+        .await
+        .context("Couldn't say hello")?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut running = HashMap::new();
+    let connection = shutdown.child_token();
+
+    let res = loop {
+        select! {
+            Some(msg) = rx.recv() => {
+                if let NodeMessage::Done { job_id, .. } = &msg {
+                    running.remove(job_id);
+                }
+                let raw = serde_json::to_string(&msg).unwrap();
+                if let Err(why) = stream.send(Message::Text(raw)).await {
+                    break Err(why).context("Couldn't send message");
+                }
+            }
+            msg = stream.next() => match msg {
+                Some(Ok(Message::Text(raw))) => match serde_json::from_str::<ServerMessage>(&raw) {
+                    Ok(ServerMessage::Job { request }) => {
+                        let cancel = connection.child_token();
+                        running.insert(request.id, cancel.clone());
+                        let language = languages.get(&request.language_key).cloned();
+                        tokio::spawn(judge(*request, language, workers.clone(), tx.clone(), cancel));
+                    }
+                    Ok(ServerMessage::Cancel { job_id }) => {
+                        if let Some(cancel) = running.get(&job_id) {
+                            cancel.cancel();
+                        }
+                    }
+                    Err(why) => warn!("Invalid message from the server: {:?}", why),
+                },
+                Some(Ok(Message::Close(_))) | None => break Ok(()),
+                Some(Err(why)) => break Err(why).context("Connection error"),
+                _ => {}
+            },
+            () = shutdown.cancelled() => {
+                stream.close(None).await.ok();
+                break Ok(());
+            }
+        }
+    };
+
+    // Jobs can't report back without the connection, the server starts them over elsewhere
+    connection.cancel();
+    res
+}
+
+async fn judge(
+    mut request: JobRequest,
+    language: Option<LanguageRunnerInfo>,
+    workers: WorkerPoolHandle,
+    tx: mpsc::UnboundedSender<NodeMessage>,
+    cancel: CancellationToken,
+) {
+    let job_id = request.id;
+    let (state, ran_at) = if let Some(language) = language {
+        // Binaries are where this machine has them, limits were already worked out by the server
+        request.language = language;
+        let (state_tx, mut state_rx) = watch::channel(JobState::new_for_op(&request.op));
+        let forward_tx = tx.clone();
+        let forward = tokio::spawn(async move {
+            while state_rx.changed().await.is_ok() {
+                let state = state_rx.borrow_and_update().clone();
+                if forward_tx
+                    .send(NodeMessage::State { job_id, state })
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        let res = run_job(&request, state_tx, cancel, &workers).await;
+        forward.await.ok();
+        res
+    } else {
+        let error = CaseError::Judge(format!(
+            "Language {} isn't set up on this node",
+            request.language_key
+        ));
+        (
+            failed_state(&request, error),
+            chrono::offset::Utc::now().naive_utc(),
+        )
+    };
+    tx.send(NodeMessage::Done {
+        job_id,
+        state,
+        ran_at,
+    })
+    .ok();
+}
+
+pub struct NodeLogger;
+
+impl NodeLogger {
+    pub fn setup() {
+        let level = if cfg!(debug_assertions) {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Info
+        };
+        log::set_max_level(level);
+        if let Err(why) = log::set_boxed_logger(Box::new(Self)) {
+            eprintln!("Failed to set logger: {:?}", why);
+        }
+    }
+}
+
+impl log::Log for NodeLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[Judge Node][{}]: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
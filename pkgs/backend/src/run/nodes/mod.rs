@@ -0,0 +1,369 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use chrono::NaiveDateTime;
+use log::warn;
+use tokio::{
+    select,
+    sync::{mpsc, oneshot, Notify},
+};
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    job::{publish_state, run_job, CaseStatus, JobRequest},
+    pool::WorkerPoolHandle,
+    worker::CaseError,
+    JobState, JobStateSender,
+};
+
+mod channel;
+mod daemon;
+
+pub use channel::judge_node_channel;
+pub use daemon::run_judge_node;
+
+/// How many times a job is started before giving up when its judge nodes keep disconnecting
+const MAX_ATTEMPTS: usize = 3;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct NodesConfig {
+    /// Shared secret judge nodes connect with, nodes can't connect when unset
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    /// Jobs this server runs itself at once, jobs wait for a free slot here or on a node past that.
+    /// Unset means no limit, which also means jobs are never sent to nodes
+    #[serde(default)]
+    pub local_slots: Option<usize>,
+}
+
+/// Sent from a judge node to the server
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum NodeMessage {
+    Hello {
+        name: String,
+        slots: usize,
+        languages: Vec<String>,
+    },
+    State {
+        job_id: u64,
+        state: JobState,
+    },
+    Done {
+        job_id: u64,
+        state: JobState,
+        ran_at: NaiveDateTime,
+    },
+}
+
+/// Sent from the server to a judge node
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ServerMessage {
+    Job { request: Box<JobRequest> },
+    Cancel { job_id: u64 },
+}
+
+/// A job running on a node, states from the node are published to `state_tx`
+struct RemoteJob {
+    state_tx: JobStateSender,
+    done: oneshot::Sender<(JobState, NaiveDateTime)>,
+}
+
+type RemoteJobs = Arc<Mutex<HashMap<u64, RemoteJob>>>;
+
+struct Node {
+    name: String,
+    slots: usize,
+    languages: HashSet<String>,
+    running: usize,
+    tx: mpsc::UnboundedSender<ServerMessage>,
+    jobs: RemoteJobs,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct NodeStatus {
+    pub name: String,
+    pub slots: usize,
+    pub running: usize,
+    pub languages: String,
+}
+
+#[derive(Default)]
+struct Slots {
+    local_running: usize,
+    nodes: HashMap<u64, Node>,
+    next_node_id: u64,
+}
+
+enum Target {
+    Local,
+    Remote {
+        node_id: u64,
+        tx: mpsc::UnboundedSender<ServerMessage>,
+        jobs: RemoteJobs,
+    },
+}
+
+/// A claimed slot on this server or a node, freed when dropped
+struct Slot {
+    scheduler: SchedulerHandle,
+    target: Target,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let node_id = match &self.target {
+            Target::Local => None,
+            Target::Remote { node_id, .. } => Some(*node_id),
+        };
+        self.scheduler.free(node_id);
+    }
+}
+
+/// Decides where jobs run, balancing them across this server and any connected judge nodes
+/// by how full each one is
+pub struct Scheduler {
+    config: NodesConfig,
+    slots: Mutex<Slots>,
+    freed: Notify,
+    shutdown: CancellationToken,
+}
+
+pub type SchedulerHandle = Arc<Scheduler>;
+
+impl Scheduler {
+    pub fn new(config: NodesConfig, shutdown: CancellationToken) -> SchedulerHandle {
+        Arc::new(Self {
+            config,
+            slots: Mutex::new(Slots::default()),
+            freed: Notify::new(),
+            shutdown,
+        })
+    }
+
+    fn secret(&self) -> Option<&str> {
+        self.config.secret.as_deref()
+    }
+
+    pub fn local_slots(&self) -> Option<usize> {
+        self.config.local_slots
+    }
+
+    pub fn local_running(&self) -> usize {
+        self.slots.lock().unwrap().local_running
+    }
+
+    /// Connected nodes, sorted by name
+    pub fn nodes(&self) -> Vec<NodeStatus> {
+        let slots = self.slots.lock().unwrap();
+        let mut res = slots
+            .nodes
+            .values()
+            .map(|n| {
+                let mut languages = n.languages.iter().cloned().collect::<Vec<_>>();
+                languages.sort();
+                NodeStatus {
+                    name: n.name.clone(),
+                    slots: n.slots,
+                    running: n.running,
+                    languages: languages.join(", "),
+                }
+            })
+            .collect::<Vec<_>>();
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        res
+    }
+
+    fn connect(
+        &self,
+        name: String,
+        slots: usize,
+        languages: Vec<String>,
+    ) -> (u64, RemoteJobs, mpsc::UnboundedReceiver<ServerMessage>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let jobs = RemoteJobs::default();
+        let mut state = self.slots.lock().unwrap();
+        let id = state.next_node_id;
+        state.next_node_id += 1;
+        state.nodes.insert(
+            id,
+            Node {
+                name,
+                slots,
+                languages: languages.into_iter().collect(),
+                running: 0,
+                tx,
+                jobs: jobs.clone(),
+            },
+        );
+        drop(state);
+        self.freed.notify_waiters();
+        (id, jobs, rx)
+    }
+
+    /// Forget a node, jobs that were running on it are started again elsewhere
+    fn disconnect(&self, id: u64) {
+        let node = self.slots.lock().unwrap().nodes.remove(&id);
+        if let Some(node) = node {
+            // Dropping the senders wakes up the jobs waiting on them
+            node.jobs.lock().unwrap().clear();
+        }
+    }
+
+    fn free(&self, node_id: Option<u64>) {
+        let mut slots = self.slots.lock().unwrap();
+        match node_id {
+            None => slots.local_running -= 1,
+            Some(id) => {
+                if let Some(node) = slots.nodes.get_mut(&id) {
+                    node.running -= 1;
+                }
+            }
+        }
+        drop(slots);
+        self.freed.notify_waiters();
+    }
+
+    /// Take a slot wherever the least of the capacity is used, this server wins ties
+    fn try_claim(self: &Arc<Self>, language_key: &str) -> Option<Slot> {
+        let mut slots = self.slots.lock().unwrap();
+        let local = match self.config.local_slots {
+            None => Some(0.0),
+            Some(max) => {
+                (slots.local_running < max).then(|| slots.local_running as f64 / max as f64)
+            }
+        };
+        let remote = slots
+            .nodes
+            .iter()
+            .filter(|(_, n)| n.running < n.slots && n.languages.contains(language_key))
+            .map(|(id, n)| (*id, n.running as f64 / n.slots as f64))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let node_id = match (local, remote) {
+            (Some(local), Some((id, remote))) if remote < local => id,
+            (Some(_), _) => {
+                slots.local_running += 1;
+                return Some(Slot {
+                    scheduler: Arc::clone(self),
+                    target: Target::Local,
+                });
+            }
+            (None, Some((id, _))) => id,
+            (None, None) => return None,
+        };
+        let node = slots.nodes.get_mut(&node_id)?;
+        node.running += 1;
+        Some(Slot {
+            scheduler: Arc::clone(self),
+            target: Target::Remote {
+                node_id,
+                tx: node.tx.clone(),
+                jobs: node.jobs.clone(),
+            },
+        })
+    }
+
+    /// Wait for a free slot, `None` if the job is cancelled first
+    async fn claim(
+        self: &Arc<Self>,
+        language_key: &str,
+        shutdown: &CancellationToken,
+    ) -> Option<Slot> {
+        loop {
+            let freed = self.freed.notified();
+            tokio::pin!(freed);
+            freed.as_mut().enable();
+            if let Some(slot) = self.try_claim(language_key) {
+                return Some(slot);
+            }
+            select! {
+                () = freed => {}
+                () = shutdown.cancelled() => return None,
+            }
+        }
+    }
+
+    /// Run a job wherever there's a free slot, on this server or a judge node.
+    /// Jobs that lose their node partway through are started over somewhere else.
+    pub async fn dispatch(
+        self: &Arc<Self>,
+        request: &JobRequest,
+        state_tx: JobStateSender,
+        shutdown: CancellationToken,
+        workers: &WorkerPoolHandle,
+    ) -> (JobState, NaiveDateTime) {
+        for attempt in 1..=MAX_ATTEMPTS {
+            let Some(slot) = self.claim(&request.language_key, &shutdown).await else {
+                break;
+            };
+            match &slot.target {
+                Target::Local => return run_job(request, state_tx, shutdown, workers).await,
+                Target::Remote { tx, jobs, .. } => {
+                    if let Some(res) = run_remote(request, &state_tx, &shutdown, tx, jobs).await {
+                        return res;
+                    }
+                    warn!(
+                        "Job {} lost its judge node (attempt {} of {})",
+                        request.id, attempt, MAX_ATTEMPTS
+                    );
+                    publish_state(&state_tx, JobState::new_for_op(&request.op));
+                }
+            }
+        }
+        let error = if shutdown.is_cancelled() {
+            CaseError::Cancelled
+        } else {
+            CaseError::Judge("Judge node disconnected".to_string())
+        };
+        let state = failed_state(request, error);
+        publish_state(&state_tx, state.clone());
+        (state, chrono::offset::Utc::now().naive_utc())
+    }
+}
+
+/// State for a job that couldn't run at all
+fn failed_state(request: &JobRequest, error: CaseError) -> JobState {
+    let mut state = JobState::new_for_op(&request.op);
+    state.start_first();
+    let details = state.is_testing();
+    state.note_error(&error);
+    state.complete_case(CaseStatus::from_case_error(error, details));
+    state
+}
+
+/// Send a job to a node and wait for it to finish, `None` if the node went away
+async fn run_remote(
+    request: &JobRequest,
+    state_tx: &JobStateSender,
+    shutdown: &CancellationToken,
+    tx: &mpsc::UnboundedSender<ServerMessage>,
+    jobs: &RemoteJobs,
+) -> Option<(JobState, NaiveDateTime)> {
+    let (done_tx, mut done_rx) = oneshot::channel();
+    jobs.lock().unwrap().insert(
+        request.id,
+        RemoteJob {
+            state_tx: state_tx.clone(),
+            done: done_tx,
+        },
+    );
+    let sent = tx.send(ServerMessage::Job {
+        request: Box::new(request.clone()),
+    });
+    if sent.is_err() {
+        jobs.lock().unwrap().remove(&request.id);
+        return None;
+    }
+    select! {
+        res = &mut done_rx => return res.ok(),
+        () = shutdown.cancelled() => {
+            tx.send(ServerMessage::Cancel { job_id: request.id }).ok();
+        }
+    }
+    // The node still sends back the cancelled state
+    done_rx.await.ok()
+}
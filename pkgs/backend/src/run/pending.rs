@@ -0,0 +1,64 @@
+use chrono::NaiveDateTime;
+
+use crate::{db::DbPoolConnection, error::prelude::*};
+
+use super::job::JobRequest;
+
+/// A judging job that's been requested but hasn't finished yet, kept in the database so a restart
+/// doesn't lose it. Rows are removed when the job finishes or is cancelled, but are left in place
+/// when the server shuts down.
+pub struct PendingJob {
+    pub user_id: i64,
+    pub problem_id: i64,
+    pub contest_id: i64,
+    pub program: String,
+    pub language: String,
+    pub flag: Option<String>,
+    pub practice: bool,
+    pub attempts: i64,
+    #[allow(dead_code)]
+    pub queued_at: NaiveDateTime,
+}
+
+impl PendingJob {
+    pub async fn save(db: &mut DbPoolConnection, req: &JobRequest) -> Result {
+        sqlx::query!(
+            "INSERT OR REPLACE INTO pending_jobs (user_id, problem_id, contest_id, program, language, flag, practice) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            req.user_id,
+            req.problem_id,
+            req.contest_id,
+            req.program,
+            req.language_key,
+            req.flag,
+            req.practice
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to save pending job for user {}", req.user_id))
+    }
+
+    pub async fn remove(db: &mut DbPoolConnection, user_id: i64) -> Result {
+        sqlx::query!("DELETE FROM pending_jobs WHERE user_id = ?", user_id)
+            .execute(&mut **db)
+            .await
+            .map(|_| ())
+            .with_context(|| format!("Failed to remove pending job for user {user_id}"))
+    }
+
+    /// Every job left over from the last run of the server, marking the attempt that was cut off
+    /// as superseded
+    pub async fn take_interrupted(db: &mut DbPoolConnection) -> Result<Vec<Self>> {
+        sqlx::query!("UPDATE pending_jobs SET attempts = attempts + 1")
+            .execute(&mut **db)
+            .await
+            .context("Failed to supersede pending jobs")?;
+        sqlx::query_as!(
+            PendingJob,
+            "SELECT * FROM pending_jobs ORDER BY queued_at ASC"
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to list pending jobs")
+    }
+}
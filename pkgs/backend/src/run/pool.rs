@@ -0,0 +1,339 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::bail;
+use log::{error, info, warn};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::error::prelude::*;
+
+use super::{
+    compile_cache::CompileCache,
+    config::LanguageRunnerInfo,
+    worker::{BindMountConfig, IsolationConfig, Worker},
+};
+
+const fn default_max_uses() -> usize {
+    1
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct PoolConfig {
+    /// Idle workers to keep started for each language, 0 starts a new worker for every job
+    #[serde(default)]
+    pub size: usize,
+    /// How many jobs a worker runs before it's replaced, 1 means workers are never reused.
+    /// Reused workers have their directory cleared between jobs but nothing else is reset.
+    #[serde(default = "default_max_uses")]
+    pub max_uses: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            max_uses: default_max_uses(),
+        }
+    }
+}
+
+/// A worker claimed from the pool, hand it back with [`WorkerPool::release`] when the job is done
+pub struct PooledWorker {
+    pub worker: Worker,
+    language_key: String,
+    uses: usize,
+}
+
+/// Workers that have already been spawned and isolated, waiting for a program.
+/// Starting a worker (spawning, namespaces, UID/GID maps) is most of the wait on short jobs,
+/// keeping some ready means a job only has to write its program before compiling.
+pub struct WorkerPool {
+    config: PoolConfig,
+    languages: HashMap<String, LanguageRunnerInfo>,
+    isolation: IsolationConfig,
+    pizzaz: u64,
+    idle: Mutex<HashMap<String, Vec<PooledWorker>>>,
+    id_counter: AtomicU64,
+    compile_cache: CompileCache,
+    shutdown: CancellationToken,
+}
+
+pub type WorkerPoolHandle = Arc<WorkerPool>;
+
+impl WorkerPool {
+    pub fn new(
+        config: PoolConfig,
+        languages: HashMap<String, LanguageRunnerInfo>,
+        isolation: IsolationConfig,
+        pizzaz: u64,
+        compile_cache: CompileCache,
+        shutdown: CancellationToken,
+    ) -> WorkerPoolHandle {
+        Arc::new(Self {
+            config,
+            languages,
+            isolation,
+            pizzaz,
+            idle: Mutex::new(HashMap::new()),
+            id_counter: AtomicU64::new(1),
+            compile_cache,
+            shutdown,
+        })
+    }
+
+    pub fn compile_cache(&self) -> &CompileCache {
+        &self.compile_cache
+    }
+
+    async fn start_worker(
+        &self,
+        language_key: &str,
+        program: &str,
+        shutdown: CancellationToken,
+        diag: &str,
+        soft_limits: (u64, u64),
+        isolation: IsolationConfig,
+    ) -> Result<Worker> {
+        let language = self
+            .languages
+            .get(language_key)
+            .with_context(|| format!("Language {language_key} not found"))?
+            .clone();
+        let id = self.id_counter.fetch_add(1, Ordering::Relaxed);
+        Worker::new(
+            id,
+            program,
+            shutdown,
+            language,
+            isolation,
+            self.pizzaz,
+            diag,
+            soft_limits,
+        )
+        .await
+    }
+
+    /// Start workers until every language has `size` idle ones
+    pub async fn fill(self: Arc<Self>) {
+        for language_key in self.languages.keys() {
+            Arc::clone(&self).refill(language_key.clone()).await;
+        }
+        if self.config.size > 0 {
+            info!(
+                "Worker pool ready with {} worker(s) per language",
+                self.config.size
+            );
+        }
+    }
+
+    async fn refill(self: Arc<Self>, language_key: String) {
+        loop {
+            let idle = self
+                .idle
+                .lock()
+                .await
+                .get(&language_key)
+                .map_or(0, Vec::len);
+            if idle >= self.config.size || self.shutdown.is_cancelled() {
+                break;
+            }
+            let diag = format!("Pooled worker for {language_key}");
+            let worker = self
+                .start_worker(
+                    &language_key,
+                    "",
+                    self.shutdown.child_token(),
+                    &diag,
+                    (0, 0),
+                    self.isolation.clone(),
+                )
+                .await;
+            match worker {
+                Ok(worker) => {
+                    self.idle
+                        .lock()
+                        .await
+                        .entry(language_key.clone())
+                        .or_default()
+                        .push(PooledWorker {
+                            worker,
+                            language_key: language_key.clone(),
+                            uses: 0,
+                        });
+                }
+                Err(why) => {
+                    error!(
+                        "Couldn't start pooled worker for {}: {:?}",
+                        language_key, why
+                    );
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Get a worker with the program loaded, from the pool if one is idle or started fresh otherwise.
+    /// Workers for programs that are already compiled or need networking are always started fresh,
+    /// since cached artifacts have to be bind mounted and loopback brought up while isolating.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn claim(
+        self: &Arc<Self>,
+        language_key: &str,
+        program: &str,
+        soft_limits: (u64, u64),
+        shutdown: CancellationToken,
+        diag: &str,
+        cached: Option<&Path>,
+        network: bool,
+    ) -> Result<PooledWorker> {
+        if network && !self.isolation.allow_network {
+            bail!("Networking isn't allowed on this judge, see isolation.allow_network");
+        }
+        if cached.is_some() || network {
+            return self
+                .claim_fresh(
+                    language_key,
+                    program,
+                    soft_limits,
+                    shutdown,
+                    diag,
+                    cached,
+                    network,
+                )
+                .await;
+        }
+
+        let idle = self
+            .idle
+            .lock()
+            .await
+            .get_mut(language_key)
+            .and_then(Vec::pop);
+        if self.config.size > 0 {
+            tokio::spawn(Arc::clone(self).refill(language_key.to_string()));
+        }
+
+        if let Some(mut pooled) = idle {
+            let language = &self.languages[language_key];
+            let res = pooled
+                .worker
+                .load_program(program, &language.file_name, soft_limits, shutdown.clone())
+                .await;
+            match res {
+                Ok(()) => {
+                    info!("Using pooled worker for {}", diag);
+                    pooled.uses += 1;
+                    return Ok(pooled);
+                }
+                Err(why) => {
+                    warn!(
+                        "Pooled worker couldn't load program, starting a new one: {:?}",
+                        why
+                    );
+                    pooled.worker.finish().await.ok();
+                }
+            }
+        }
+
+        let worker = self
+            .start_worker(
+                language_key,
+                program,
+                shutdown,
+                diag,
+                soft_limits,
+                self.isolation.clone(),
+            )
+            .await?;
+        Ok(PooledWorker {
+            worker,
+            language_key: language_key.to_string(),
+            uses: 1,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn claim_fresh(
+        &self,
+        language_key: &str,
+        program: &str,
+        soft_limits: (u64, u64),
+        shutdown: CancellationToken,
+        diag: &str,
+        cached: Option<&Path>,
+        network: bool,
+    ) -> Result<PooledWorker> {
+        let mut isolation = self.isolation.clone();
+        isolation.loopback = network;
+        if let Some(cached) = cached {
+            isolation.bind_mounts.push(BindMountConfig {
+                src: cached.to_path_buf(),
+                no_exec: false,
+            });
+        }
+        let mut worker = self
+            .start_worker(
+                language_key,
+                program,
+                shutdown,
+                diag,
+                soft_limits,
+                isolation,
+            )
+            .await?;
+        if let Some(cached) = cached {
+            let paths = self.languages[language_key]
+                .artifacts
+                .iter()
+                .map(|name| cached.join(name))
+                .collect();
+            if let Err(why) = worker.link_artifacts(paths).await {
+                worker.finish().await.ok();
+                return Err(why);
+            }
+            info!("Using cached compile for {}", diag);
+        }
+        Ok(PooledWorker {
+            worker,
+            language_key: language_key.to_string(),
+            // Its bind mounts and network are specific to this job, never reuse it
+            uses: usize::MAX,
+        })
+    }
+
+    /// Return a worker after its job, it goes back in the pool if it's healthy,
+    /// hasn't hit `max_uses` and the pool isn't full, otherwise it's stopped
+    pub async fn release(&self, pooled: PooledWorker, reusable: bool) -> Result {
+        let keep = reusable
+            && pooled.worker.is_alive()
+            && pooled.uses < self.config.max_uses
+            && !self.shutdown.is_cancelled();
+        if keep {
+            let mut idle = self.idle.lock().await;
+            let workers = idle.entry(pooled.language_key.clone()).or_default();
+            if workers.len() < self.config.size {
+                workers.push(pooled);
+                return Ok(());
+            }
+        }
+        pooled.worker.finish().await
+    }
+
+    /// Stop every idle worker, called on shutdown
+    pub async fn drain(&self) {
+        let idle = std::mem::take(&mut *self.idle.lock().await);
+        for pooled in idle.into_values().flatten() {
+            if let Err(why) = pooled.worker.finish().await {
+                error!("Couldn't stop pooled worker: {:?}", why);
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::NaiveDateTime;
+
+use crate::{db::DbPoolConnection, error::prelude::*};
+
+use super::job::JobRequest;
+
+/// A judging job that hit a judge error every time it was retried. It's held here for an admin
+/// instead of saving a judge error the participant can't do anything about, admins can judge it
+/// again once whatever broke is fixed or dismiss it.
+#[derive(Serialize, Debug, Clone)]
+pub struct QuarantinedRun {
+    pub id: i64,
+    pub user_id: i64,
+    pub problem_id: i64,
+    pub contest_id: i64,
+    #[serde(skip_serializing)]
+    pub program: String,
+    pub language: String,
+    pub flag: Option<String>,
+    pub practice: bool,
+    pub error: String,
+    pub attempts: i64,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub quarantined_at: NaiveDateTime,
+}
+
+/// A quarantined run with who and what it's for, for the runs admin page
+#[derive(Serialize)]
+pub struct QuarantinedRunRow {
+    #[serde(flatten)]
+    pub run: QuarantinedRun,
+    pub user_name: String,
+    pub problem_name: String,
+    pub problem_slug: String,
+}
+
+impl QuarantinedRun {
+    pub async fn save(
+        db: &mut DbPoolConnection,
+        req: &JobRequest,
+        error: &str,
+        attempts: i64,
+    ) -> Result {
+        let now = chrono::offset::Utc::now().naive_utc();
+        sqlx::query!(
+            "INSERT INTO quarantined_run (user_id, problem_id, contest_id, program, language, flag, practice, error, attempts, quarantined_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            req.user_id,
+            req.problem_id,
+            req.contest_id,
+            req.program,
+            req.language_key,
+            req.flag,
+            req.practice,
+            error,
+            attempts,
+            now
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to quarantine job {}", req.id))
+    }
+
+    pub async fn get(db: &mut DbPoolConnection, id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            QuarantinedRun,
+            "SELECT * FROM quarantined_run WHERE id = ?",
+            id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get quarantined run {id}"))
+    }
+
+    /// Every quarantined run, newest first
+    pub async fn list(db: &mut DbPoolConnection) -> Result<Vec<QuarantinedRunRow>> {
+        let rows = sqlx::query!(
+            "SELECT quarantined_run.*, COALESCE(user.display_name, user.default_display_name) AS user_name, problem.name AS problem_name, problem.slug AS problem_slug
+            FROM quarantined_run
+            JOIN user ON user.id = quarantined_run.user_id
+            JOIN problem ON problem.id = quarantined_run.problem_id
+            ORDER BY quarantined_run.quarantined_at DESC"
+        )
+        .fetch_all(&mut **db)
+        .await
+        .context("Failed to list quarantined runs")?;
+        Ok(rows
+            .into_iter()
+            .map(|r| QuarantinedRunRow {
+                run: QuarantinedRun {
+                    id: r.id,
+                    user_id: r.user_id,
+                    problem_id: r.problem_id,
+                    contest_id: r.contest_id,
+                    program: r.program,
+                    language: r.language,
+                    flag: r.flag,
+                    practice: r.practice,
+                    error: r.error,
+                    attempts: r.attempts,
+                    quarantined_at: r.quarantined_at,
+                },
+                user_name: r.user_name,
+                problem_name: r.problem_name,
+                problem_slug: r.problem_slug,
+            })
+            .collect())
+    }
+
+    pub async fn remove(db: &mut DbPoolConnection, id: i64) -> Result {
+        sqlx::query!("DELETE FROM quarantined_run WHERE id = ?", id)
+            .execute(&mut **db)
+            .await
+            .map(|_| ())
+            .with_context(|| format!("Failed to remove quarantined run {id}"))
+    }
+}
@@ -0,0 +1,499 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::NaiveDateTime;
+use log::{error, info};
+use tokio::{
+    select,
+    sync::{watch, Mutex},
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    contests::Contest,
+    db::{DbPool, DbPoolConnection},
+    error::prelude::*,
+    leaderboard::LeaderboardManagerHandle,
+    problems::{JudgeRun, Problem, ProblemCompletion, TestCase},
+};
+
+use super::ManagerHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RejudgeStatus {
+    Running,
+    Paused,
+    Cancelled,
+    Interrupted,
+    Complete,
+}
+
+impl RejudgeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Paused => "paused",
+            Self::Cancelled => "cancelled",
+            Self::Interrupted => "interrupted",
+            Self::Complete => "complete",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Cancelled | Self::Interrupted | Self::Complete)
+    }
+}
+
+impl From<String> for RejudgeStatus {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "running" => Self::Running,
+            "paused" => Self::Paused,
+            "cancelled" => Self::Cancelled,
+            "complete" => Self::Complete,
+            _ => Self::Interrupted,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Rejudge {
+    pub id: i64,
+    pub contest_id: i64,
+    pub problem_id: Option<i64>,
+    pub status: RejudgeStatus,
+    pub total: i64,
+    pub processed: i64,
+    /// Runs that were failing and now pass
+    pub now_passing: i64,
+    /// Runs that were passing and now fail
+    pub now_failing: i64,
+    /// Runs that couldn't be rejudged
+    pub errors: i64,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub created_at: NaiveDateTime,
+    #[serde(serialize_with = "crate::times::serialize_option_to_js")]
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl Rejudge {
+    pub async fn create(
+        db: &mut DbPoolConnection,
+        contest_id: i64,
+        problem_id: Option<i64>,
+        total: i64,
+    ) -> Result<Self> {
+        let status = RejudgeStatus::Running.as_str();
+        sqlx::query_as!(
+            Rejudge,
+            "INSERT INTO rejudge (contest_id, problem_id, status, total) VALUES (?, ?, ?, ?) RETURNING *",
+            contest_id,
+            problem_id,
+            status,
+            total
+        )
+        .fetch_one(&mut **db)
+        .await
+        .with_context(|| format!("Failed to create rejudge for contest {}", contest_id))
+    }
+
+    pub async fn get(db: &mut DbPoolConnection, contest_id: i64, id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Rejudge,
+            "SELECT * FROM rejudge WHERE id = ? AND contest_id = ?",
+            id,
+            contest_id
+        )
+        .fetch_optional(&mut **db)
+        .await
+        .with_context(|| format!("Failed to get rejudge {}", id))
+    }
+
+    pub async fn list(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Rejudge,
+            "SELECT * FROM rejudge WHERE contest_id = ? ORDER BY created_at DESC",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Failed to list rejudges for contest {}", contest_id))
+    }
+
+    pub async fn save_progress(&self, db: &mut DbPoolConnection) -> Result {
+        let status = self.status.as_str();
+        sqlx::query!(
+            "UPDATE rejudge SET status = ?, processed = ?, now_passing = ?, now_failing = ?, errors = ?, finished_at = ? WHERE id = ?",
+            status,
+            self.processed,
+            self.now_passing,
+            self.now_failing,
+            self.errors,
+            self.finished_at,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Failed to save progress for rejudge {}", self.id))
+    }
+}
+
+// Keep in sync with TypeScript type
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RejudgeProgress {
+    pub rejudge: Rejudge,
+    pub eta_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejudgeControl {
+    Run,
+    Pause,
+    Cancel,
+}
+
+struct ActiveRejudge {
+    contest_id: i64,
+    control: watch::Sender<RejudgeControl>,
+    progress: watch::Receiver<RejudgeProgress>,
+    cancel: CancellationToken,
+}
+
+impl ActiveRejudge {
+    fn is_finished(&self) -> bool {
+        // The sender is dropped if the task stopped without finishing normally
+        self.progress.has_changed().is_err() || self.progress.borrow().rejudge.status.is_finished()
+    }
+}
+
+pub struct RejudgeManager {
+    active: HashMap<i64, ActiveRejudge>,
+    db_pool: DbPool,
+    run_manager: ManagerHandle,
+    leaderboard_handle: LeaderboardManagerHandle,
+    shutdown: CancellationToken,
+}
+
+pub type RejudgeManagerHandle = Arc<Mutex<RejudgeManager>>;
+
+impl RejudgeManager {
+    pub fn new(
+        db_pool: DbPool,
+        run_manager: ManagerHandle,
+        leaderboard_handle: LeaderboardManagerHandle,
+        shutdown: CancellationToken,
+    ) -> Self {
+        Self {
+            active: HashMap::new(),
+            db_pool,
+            run_manager,
+            leaderboard_handle,
+            shutdown,
+        }
+    }
+
+    fn prune(&mut self) {
+        self.active.retain(|_, r| !r.is_finished());
+    }
+
+    pub fn is_active(&mut self, id: i64) -> bool {
+        self.prune();
+        self.active.contains_key(&id)
+    }
+
+    pub fn has_active_for_contest(&mut self, contest_id: i64) -> bool {
+        self.prune();
+        self.active.values().any(|r| r.contest_id == contest_id)
+    }
+
+    /// Save rejudges that were running when the server stopped as interrupted,
+    /// they don't have an active task anymore
+    pub async fn mark_interrupted(&mut self, db: &mut DbPoolConnection, contest_id: i64) -> Result {
+        for mut rejudge in Rejudge::list(db, contest_id).await? {
+            if !rejudge.status.is_finished() && !self.is_active(rejudge.id) {
+                rejudge.status = RejudgeStatus::Interrupted;
+                rejudge.save_progress(db).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn subscribe(&self, id: i64) -> Option<watch::Receiver<RejudgeProgress>> {
+        self.active.get(&id).map(|r| r.progress.clone())
+    }
+
+    /// Send a control message to a running rejudge, returns `false` if it isn't running
+    pub fn control(&mut self, id: i64, control: RejudgeControl) -> bool {
+        self.prune();
+        if let Some(active) = self.active.get(&id) {
+            active.control.send(control).ok();
+            if control == RejudgeControl::Cancel {
+                active.cancel.cancel();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    pub async fn start(
+        &mut self,
+        db: &mut DbPoolConnection,
+        contest: &Contest,
+        problem_id: Option<i64>,
+    ) -> Result<Rejudge> {
+        let runs = JudgeRun::list_for_rejudge(db, contest.id, problem_id).await?;
+        let rejudge = Rejudge::create(db, contest.id, problem_id, runs.len() as i64).await?;
+
+        let (control_tx, control_rx) = watch::channel(RejudgeControl::Run);
+        let (progress_tx, progress_rx) = watch::channel(RejudgeProgress {
+            rejudge: rejudge.clone(),
+            eta_secs: None,
+        });
+        let cancel = self.shutdown.child_token();
+
+        self.active.insert(
+            rejudge.id,
+            ActiveRejudge {
+                contest_id: contest.id,
+                control: control_tx,
+                progress: progress_rx,
+                cancel: cancel.clone(),
+            },
+        );
+
+        let task = RejudgeTask {
+            rejudge: rejudge.clone(),
+            runs,
+            contest: contest.clone(),
+            pool: self.db_pool.clone(),
+            run_manager: self.run_manager.clone(),
+            leaderboard_handle: self.leaderboard_handle.clone(),
+            control_rx,
+            progress_tx,
+            cancel,
+        };
+
+        tokio::spawn(async move {
+            let id = task.rejudge.id;
+            if let Err(why) = task.run().await {
+                error!("Rejudge {} failed: {:?}", id, why);
+            }
+        });
+
+        Ok(rejudge)
+    }
+}
+
+enum RunOutcome {
+    Unchanged,
+    NowPassing,
+    NowFailing,
+    /// The rejudge was cancelled while this run was being judged, or the server is draining
+    Aborted,
+}
+
+struct RejudgeTask {
+    rejudge: Rejudge,
+    runs: Vec<JudgeRun>,
+    contest: Contest,
+    pool: DbPool,
+    run_manager: ManagerHandle,
+    leaderboard_handle: LeaderboardManagerHandle,
+    control_rx: watch::Receiver<RejudgeControl>,
+    progress_tx: watch::Sender<RejudgeProgress>,
+    cancel: CancellationToken,
+}
+
+impl RejudgeTask {
+    async fn publish(&mut self, conn: &mut DbPoolConnection, eta_secs: Option<u64>) -> Result {
+        self.rejudge.save_progress(conn).await?;
+        self.progress_tx
+            .send(RejudgeProgress {
+                rejudge: self.rejudge.clone(),
+                eta_secs,
+            })
+            .ok();
+        Ok(())
+    }
+
+    async fn finish(&mut self, conn: &mut DbPoolConnection, status: RejudgeStatus) -> Result {
+        self.rejudge.status = status;
+        self.rejudge.finished_at = Some(chrono::offset::Utc::now().naive_utc());
+        self.publish(conn, None).await?;
+
+        if self.rejudge.now_passing > 0 || self.rejudge.now_failing > 0 {
+            let mut leaderboard_manager = self.leaderboard_handle.lock().await;
+            leaderboard_manager
+                .refresh_leaderboard(conn, &self.contest)
+                .await?;
+        }
+
+        info!(
+            "Rejudge {} finished ({}), {}/{} runs processed",
+            self.rejudge.id,
+            status.as_str(),
+            self.rejudge.processed,
+            self.rejudge.total
+        );
+        Ok(())
+    }
+
+    /// Wait until the rejudge is allowed to continue, returning the status to finish with if it shouldn't
+    async fn wait_while_paused(
+        &mut self,
+        conn: &mut DbPoolConnection,
+        eta_secs: Option<u64>,
+    ) -> Result<Option<RejudgeStatus>> {
+        loop {
+            if self.cancel.is_cancelled() {
+                return Ok(Some(self.stopped_status()));
+            }
+            let control = *self.control_rx.borrow_and_update();
+            match control {
+                RejudgeControl::Run => {
+                    if self.rejudge.status != RejudgeStatus::Running {
+                        self.rejudge.status = RejudgeStatus::Running;
+                        self.publish(conn, eta_secs).await?;
+                    }
+                    return Ok(None);
+                }
+                RejudgeControl::Cancel => return Ok(Some(RejudgeStatus::Cancelled)),
+                RejudgeControl::Pause => {
+                    if self.rejudge.status != RejudgeStatus::Paused {
+                        self.rejudge.status = RejudgeStatus::Paused;
+                        self.publish(conn, None).await?;
+                    }
+                    select! {
+                        res = self.control_rx.changed() => {
+                            if res.is_err() {
+                                return Ok(Some(RejudgeStatus::Interrupted));
+                            }
+                        }
+                        _ = self.cancel.cancelled() => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn stopped_status(&self) -> RejudgeStatus {
+        if *self.control_rx.borrow() == RejudgeControl::Cancel {
+            RejudgeStatus::Cancelled
+        } else {
+            RejudgeStatus::Interrupted
+        }
+    }
+
+    async fn run(mut self) -> Result {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .context("Couldn't get db connection")?;
+
+        let mut problems: HashMap<i64, (Problem, Vec<TestCase>)> = HashMap::new();
+        let mut time_spent = Duration::ZERO;
+        let mut eta_secs = None;
+
+        let runs = std::mem::take(&mut self.runs);
+
+        for mut run in runs {
+            if let Some(status) = self.wait_while_paused(&mut conn, eta_secs).await? {
+                return self.finish(&mut conn, status).await;
+            }
+
+            let started = Instant::now();
+
+            if !problems.contains_key(&run.problem_id) {
+                if let Some(problem) =
+                    Problem::by_id(&mut conn, self.contest.id, run.problem_id).await?
+                {
+                    let cases = TestCase::get_for_problem(&mut conn, problem.id).await?;
+                    problems.insert(problem.id, (problem, cases));
+                }
+            }
+
+            let outcome = if let Some((problem, cases)) = problems.get(&run.problem_id) {
+                self.rejudge_run(&mut conn, &mut run, problem, cases.clone())
+                    .await
+            } else {
+                Err(anyhow!("Problem {} no longer exists", run.problem_id))
+            };
+
+            match outcome {
+                Ok(RunOutcome::Aborted) => {
+                    let status = self.stopped_status();
+                    return self.finish(&mut conn, status).await;
+                }
+                Ok(RunOutcome::NowPassing) => self.rejudge.now_passing += 1,
+                Ok(RunOutcome::NowFailing) => self.rejudge.now_failing += 1,
+                Ok(RunOutcome::Unchanged) => {}
+                Err(why) => {
+                    error!(
+                        "Rejudge {} couldn't rejudge run {}: {:?}",
+                        self.rejudge.id, run.id, why
+                    );
+                    self.rejudge.errors += 1;
+                }
+            }
+
+            self.rejudge.processed += 1;
+            time_spent += started.elapsed();
+            let remaining = (self.rejudge.total - self.rejudge.processed).max(0) as u32;
+            eta_secs = Some((time_spent / self.rejudge.processed as u32 * remaining).as_secs());
+            self.publish(&mut conn, eta_secs).await?;
+        }
+
+        self.finish(&mut conn, RejudgeStatus::Complete).await
+    }
+
+    async fn rejudge_run(
+        &self,
+        conn: &mut DbPoolConnection,
+        run: &mut JudgeRun,
+        problem: &Problem,
+        cases: Vec<TestCase>,
+    ) -> Result<RunOutcome> {
+        let mut run_manager = self.run_manager.lock().await;
+        if run_manager.is_draining() {
+            return Ok(RunOutcome::Aborted);
+        }
+        let job = run_manager
+            .rejudge(run, problem, cases, self.cancel.child_token())
+            .map_err(|e| anyhow!(e))?;
+        drop(run_manager);
+
+        let (request, state) = job.await;
+
+        if self.cancel.is_cancelled() {
+            return Ok(RunOutcome::Aborted);
+        }
+
+        let was_success = run.success();
+        let (amount_run, _, error) = state.last_error();
+        run.amount_run = amount_run as i64;
+        run.total_cases = state.len() as i64;
+        run.error = error;
+        run.set_details(&state);
+        run.cache_key = request.cache_key();
+        run.cached_from = None;
+        run.update_verdict(conn).await?;
+        let now_success = run.success();
+
+        if was_success == now_success {
+            return Ok(RunOutcome::Unchanged);
+        }
+
+        ProblemCompletion::update_for_run(conn, &self.contest, run, now_success).await?;
+
+        Ok(if now_success {
+            RunOutcome::NowPassing
+        } else {
+            RunOutcome::NowFailing
+        })
+    }
+}
@@ -0,0 +1,160 @@
+use std::path::Path;
+
+use rocket::{
+    form::Form, fs::TempFile, http::Status, post, tokio::io::AsyncReadExt, FromForm, State,
+};
+
+use crate::{
+    auth::{
+        csrf::VerifyCsrfToken,
+        users::{Admin, User},
+    },
+    contests::{BindingVerdict, ClientOrigin, Contest, Participant, SessionBinding},
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    problems::{Comparison, Problem, TestCase},
+    rate_limit::{self, Bucket, RateLimiterHandle},
+};
+
+use super::{job::JobOperation, manager::ManagerJobRequest, CodeInfo, ManagerHandle};
+
+#[derive(FromForm)]
+pub struct UploadForm<'r> {
+    language: &'r str,
+    file: TempFile<'r>,
+}
+
+/// Read an uploaded program, checking it's small enough and named like the language expects
+async fn read_program(
+    file: &TempFile<'_>,
+    file_name: &str,
+    max_length: usize,
+) -> Result<String, String> {
+    if let Some(expected) = Path::new(file_name).extension() {
+        let actual = file
+            .raw_name()
+            .map(|n| n.dangerous_unsafe_unsanitized_raw().as_str())
+            .and_then(|n| Path::new(n).extension());
+        if actual != Some(expected) {
+            return Err(format!(
+                "That file isn't a .{} file",
+                expected.to_string_lossy()
+            ));
+        }
+    }
+    if file.len() as usize > max_length {
+        return Err(format!(
+            "That file is too big, programs can be at most {max_length} bytes"
+        ));
+    }
+    let mut program = String::new();
+    file.open()
+        .await
+        .map_err(|_| "Couldn't read that file".to_string())?
+        .read_to_string(&mut program)
+        .await
+        .map_err(|_| "That file isn't text".to_string())?;
+    Ok(program)
+}
+
+/// Judge a program uploaded as a file, an alternative to the editor that goes through the same queue.
+/// The problem page's websocket picks up the job's state once it starts.
+#[post("/upload/<contest_id>/<problem_id>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn upload_post(
+    contest_id: i64,
+    problem_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    manager: &State<ManagerHandle>,
+    limiter: &State<RateLimiterHandle>,
+    info: &State<CodeInfo>,
+    form: Form<UploadForm<'_>>,
+    mut db: DbConnection,
+    origin: ClientOrigin,
+    _token: &VerifyCsrfToken,
+) -> FormResponse {
+    let (_, participant, can_edit) =
+        Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    let problem = Problem::by_id(&mut db, contest_id, problem_id)
+        .await?
+        .ok_or(Status::NotFound)?;
+    let url = format!("/contests/{contest_id}/problems/{}", problem.slug);
+
+    if let Some(reason) = participant.as_ref().and_then(Participant::runs_blocked) {
+        return Ok(Message::error(reason).to(&url));
+    }
+
+    if !problem.judges_programs() {
+        return Ok(Message::error("This problem takes answers instead of a program").to(&url));
+    }
+    let Some(language) = info.run_config.languages.get(form.language) else {
+        return Ok(Message::error("Unknown language").to(&url));
+    };
+    if !problem.allows_language(form.language) {
+        return Ok(Message::error("That language isn't allowed for this problem").to(&url));
+    }
+    let program = match read_program(
+        &form.file,
+        &language.runner.file_name,
+        info.run_config.max_program_length,
+    )
+    .await
+    {
+        Ok(program) => program,
+        Err(why) => return Ok(Message::error(&why).to(&url)),
+    };
+    let violation = problem.banned_import_violation(form.language, &program);
+    if let Some(reason) = violation.as_ref().filter(|_| !problem.flag_banned_imports) {
+        return Ok(Message::error(reason).to(&url));
+    }
+
+    let limited = limiter
+        .lock()
+        .await
+        .check(Bucket::RunJob, Some(user.id), None);
+    if let Err(retry_after) = limited {
+        return Ok(
+            Message::error("Too many runs, try again in {seconds} seconds")
+                .arg("seconds", rate_limit::retry_after_secs(retry_after))
+                .to(&url),
+        );
+    }
+
+    let mut binding = BindingVerdict::Allowed;
+    if !can_edit {
+        if let Some(reason) = problem.submission_blocked(&mut db, user.id).await? {
+            return Ok(Message::error(&reason).to(&url));
+        }
+        binding = SessionBinding::check(&mut db, contest_id, user.id, &origin).await?;
+        if let BindingVerdict::Blocked(reason) = &binding {
+            return Ok(Message::error(reason).to(&url));
+        }
+    }
+
+    let cases = TestCase::get_for_problem(&mut db, problem.id).await?;
+    if cases.is_empty() {
+        return Err(Status::NotFound.into());
+    }
+    let request = ManagerJobRequest {
+        user_id: user.id,
+        problem_id: problem.id,
+        contest_id: problem.contest_id,
+        program,
+        language_key: form.language.to_string(),
+        soft_limits: (problem.cpu_time as u64, problem.memory_limit as u64), // `as` is safe due to DB constraint
+        wall_time: problem.wall_time_limit(),
+        network: problem.allow_network,
+        parallelism: problem.parallelism as usize, // Same here
+        comparison: Comparison::for_problem(&problem),
+        op: JobOperation::Judging(cases),
+        flag: binding.flag(violation),
+        practice: false,
+    };
+    let mut manager = manager.lock().await;
+    match manager.request_job(request).await {
+        Ok(_) => Ok(Message::success("Upload Submitted").to(&url)),
+        Err(why) => Ok(Message::error(&why).to(&url)),
+    }
+}
@@ -0,0 +1,124 @@
+use std::{collections::HashMap, sync::Arc};
+
+use log::{info, warn};
+use rocket::{get, State};
+use rocket_dyn_templates::Template;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+
+use crate::{auth::users::User, context_with_base, error::prelude::*};
+
+use super::{
+    config::{CommandInfo, LanguageRunnerInfo},
+    worker::{CaseError, IsolationConfig, Worker},
+    RunConfig,
+};
+
+/// CPU seconds and MiB of memory a version command gets
+const VERSION_LIMITS: (u64, u64) = (10, 1024);
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ToolchainVersion {
+    pub key: String,
+    pub name: String,
+    /// Output of the language's version command
+    pub version: String,
+    pub compile_cmd: Option<String>,
+    pub run_cmd: String,
+    pub time_multiplier: Option<f64>,
+    pub memory_multiplier: Option<f64>,
+}
+
+fn command_line(cmd: &CommandInfo) -> String {
+    [cmd.binary.as_str()]
+        .into_iter()
+        .chain(cmd.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Toolchain versions as seen from inside the sandbox, probed once on startup.
+/// Empty until the probe finishes.
+pub type ToolchainVersionsHandle = Arc<RwLock<Vec<ToolchainVersion>>>;
+
+async fn probe_language(
+    runner: &LanguageRunnerInfo,
+    iso: &IsolationConfig,
+    shutdown: &CancellationToken,
+) -> Result<Option<String>> {
+    let Some(version_cmd) = runner.version_cmd.clone() else {
+        return Ok(None);
+    };
+    let probe_info = LanguageRunnerInfo {
+        compile_cmd: None,
+        run_cmd: version_cmd,
+        ..runner.clone()
+    };
+    let mut worker = Worker::new(
+        0,
+        "",
+        shutdown.child_token(),
+        probe_info,
+        iso.clone(),
+        0,
+        "Version Probe",
+        VERSION_LIMITS,
+    )
+    .await
+    .context("Couldn't create worker")?;
+    let res = worker.run_cmd(None).await;
+    worker.finish().await?;
+    Ok(Some(match res {
+        Ok(output) => output.trim().to_string(),
+        Err(CaseError::Runtime(output)) => format!("failed: {}", output.trim()),
+        Err(e) => format!("failed: {e:?}"),
+    }))
+}
+
+/// Run every language's version command in the sandbox, the same way submissions are run,
+/// so what's reported is what programs are actually compiled and run with
+pub async fn probe_versions(
+    config: RunConfig,
+    languages: HashMap<String, LanguageRunnerInfo>,
+    iso: IsolationConfig,
+    versions: ToolchainVersionsHandle,
+    shutdown: CancellationToken,
+) {
+    let mut res = Vec::with_capacity(languages.len());
+    for (key, runner) in languages.iter() {
+        let version = probe_language(runner, &iso, &shutdown)
+            .await
+            .unwrap_or_else(|why| {
+                warn!("Couldn't get the toolchain version for {}: {:?}", key, why);
+                Some("unknown, see the logs".to_string())
+            })
+            .unwrap_or_else(|| "not configured".to_string());
+        let name = config
+            .languages
+            .get(key)
+            .map(|l| l.display.name.clone())
+            .unwrap_or_else(|| key.clone());
+        res.push(ToolchainVersion {
+            key: key.clone(),
+            name,
+            version,
+            compile_cmd: runner.compile_cmd.as_ref().map(command_line),
+            run_cmd: command_line(&runner.run_cmd),
+            time_multiplier: runner.time_multiplier,
+            memory_multiplier: runner.memory_multiplier,
+        });
+    }
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    info!("Probed toolchain versions for {} language(s)", res.len());
+    *versions.write().await = res;
+}
+
+#[get("/environment")]
+pub async fn environment(
+    user: Option<&User>,
+    versions: &State<ToolchainVersionsHandle>,
+) -> Template {
+    let versions = versions.read().await.clone();
+    let ctx = context_with_base!(user, versions);
+    Template::render("environment", ctx)
+}
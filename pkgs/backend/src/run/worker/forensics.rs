@@ -0,0 +1,267 @@
+//! Forensic bundles of workers that died unexpectedly, zips of everything that might say why for
+//! debugging the sandbox, and snapshots of a worker's directory that admins asked for.
+//! They're saved where the worker ran, so ones from judge nodes stay on the node.
+
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use chrono::NaiveDateTime;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use crate::{error::prelude::*, zip::ZipWriter};
+
+use super::isolation::IsolationConfig;
+
+/// Lines of the worker's stderr kept for a bundle
+const STDERR_LINES: usize = 200;
+
+/// Most of each message kept, the first ones carry the whole program
+const MAX_MESSAGE_CHARS: usize = 4096;
+
+/// Reports of each kind kept before the oldest are removed
+const MAX_REPORTS: usize = 100;
+
+/// Kernel log lines kept, only ones about the OOM killer
+const MAX_OOM_LINES: usize = 20;
+
+/// The end of a worker's stderr, shared with the task reading it
+pub type StderrTail = Arc<Mutex<VecDeque<String>>>;
+
+/// Where reports are saved, `isolation.crash_reports_dir` or a directory in the temp dir
+pub fn crash_reports_dir(iso: &IsolationConfig) -> PathBuf {
+    iso.crash_reports_dir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("wcpc_crash_reports"))
+}
+
+/// Pass the worker's stderr through to ours like before it was piped, keeping the last lines
+pub fn tail_stderr(stderr: impl AsyncRead + Unpin + Send + 'static) -> StderrTail {
+    let tail = StderrTail::default();
+    let lines_tail = tail.clone();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            eprintln!("{line}");
+            let mut tail = lines_tail.lock().unwrap();
+            if tail.len() == STDERR_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    });
+    tail
+}
+
+/// Cut a message down to size for a bundle
+pub fn truncate_message(msg: &str) -> String {
+    if msg.chars().count() > MAX_MESSAGE_CHARS {
+        let cut = msg.chars().take(MAX_MESSAGE_CHARS).collect::<String>();
+        format!("{cut}... (truncated)")
+    } else {
+        msg.trim_end().to_string()
+    }
+}
+
+/// Lines about the OOM killer from the kernel log, which often isn't readable without privileges
+async fn oom_lines() -> String {
+    let output = tokio::process::Command::new("dmesg").output().await;
+    match output {
+        Ok(output) if output.status.success() => {
+            let log = String::from_utf8_lossy(&output.stdout);
+            let lines = log
+                .lines()
+                .filter(|l| {
+                    let l = l.to_lowercase();
+                    l.contains("oom") || l.contains("out of memory") || l.contains("killed process")
+                })
+                .collect::<Vec<_>>();
+            let start = lines.len().saturating_sub(MAX_OOM_LINES);
+            if lines.is_empty() {
+                "No OOM killer lines in the kernel log".to_string()
+            } else {
+                lines[start..].join("\n")
+            }
+        }
+        Ok(output) => format!(
+            "The kernel log isn't readable: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(why) => format!("The kernel log isn't readable: {why}"),
+    }
+}
+
+pub struct CrashBundle {
+    pub worker_id: u64,
+    /// What the worker was started for, see [`super::InitialWorkerInfo`]
+    pub diagnostic_info: String,
+    pub reason: String,
+    pub exit_status: String,
+    pub stderr: Vec<String>,
+    pub last_service_message: Option<String>,
+    pub last_worker_message: Option<String>,
+    pub cgroup_stats: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReportKind {
+    /// A [`CrashBundle`]
+    Crash,
+    /// A gzipped tarball of a worker's directory, see [`super::Worker::snapshot`]
+    Snapshot,
+}
+
+impl ReportKind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Self::Crash => "crash_",
+            Self::Snapshot => "snapshot_",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Crash => ".zip",
+            Self::Snapshot => ".tar.gz",
+        }
+    }
+
+    fn is_name(&self, name: &str) -> bool {
+        name.starts_with(self.prefix())
+            && name.ends_with(self.extension())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.'))
+    }
+}
+
+/// A saved report, for the runs admin page
+#[derive(Serialize)]
+pub struct SavedReport {
+    pub name: String,
+    pub bytes: u64,
+    #[serde(serialize_with = "crate::times::serialize_to_js")]
+    pub saved_at: NaiveDateTime,
+}
+
+/// Save a report in `dir`, removing the oldest of its kind past [`MAX_REPORTS`]
+async fn save(dir: &Path, kind: ReportKind, name: &str, bytes: Vec<u8>) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("Couldn't create {}", dir.display()))?;
+    let path = dir.join(format!("{}{}{}", kind.prefix(), name, kind.extension()));
+    tokio::fs::write(&path, bytes)
+        .await
+        .with_context(|| format!("Couldn't write {}", path.display()))?;
+
+    let reports = list(dir, kind).await?;
+    for old in reports.iter().skip(MAX_REPORTS) {
+        tokio::fs::remove_file(dir.join(&old.name)).await.ok();
+    }
+    Ok(path)
+}
+
+/// Save a snapshot of a user's run
+pub async fn save_snapshot(
+    dir: &Path,
+    user_id: i64,
+    job_id: u64,
+    tarball: Vec<u8>,
+) -> Result<PathBuf> {
+    let now = chrono::offset::Utc::now().naive_utc();
+    let name = format!(
+        "{}_user{}_job{}",
+        now.format("%Y%m%d_%H%M%S%.3f"),
+        user_id,
+        job_id
+    );
+    save(dir, ReportKind::Snapshot, &name, tarball).await
+}
+
+impl CrashBundle {
+    pub async fn save(self, dir: &Path) -> Result<PathBuf> {
+        let now = chrono::offset::Utc::now().naive_utc();
+        let mut zip = ZipWriter::new(now);
+        let summary = format!(
+            "Worker: {} ({})\nTime: {} UTC\nReason: {}\nExit status: {}\n",
+            self.worker_id, self.diagnostic_info, now, self.reason, self.exit_status
+        );
+        zip.add("summary.txt", summary.as_bytes())?;
+        zip.add("stderr.txt", self.stderr.join("\n").as_bytes())?;
+        let messages = format!(
+            "Last message to the worker:\n{}\n\nLast message from the worker:\n{}\n",
+            self.last_service_message.as_deref().unwrap_or("(none)"),
+            self.last_worker_message.as_deref().unwrap_or("(none)")
+        );
+        zip.add("messages.txt", messages.as_bytes())?;
+        zip.add("cgroup.txt", self.cgroup_stats.as_bytes())?;
+        zip.add("dmesg.txt", oom_lines().await.as_bytes())?;
+
+        let name = format!(
+            "{}_worker{}",
+            now.format("%Y%m%d_%H%M%S%.3f"),
+            self.worker_id
+        );
+        save(dir, ReportKind::Crash, &name, zip.finish()?).await
+    }
+}
+
+/// Every report of a kind in `dir`, newest first
+pub async fn list(dir: &Path, kind: ReportKind) -> Result<Vec<SavedReport>> {
+    let mut reports = vec![];
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(why) if why.kind() == std::io::ErrorKind::NotFound => return Ok(reports),
+        Err(why) => return Err(why).with_context(|| format!("Couldn't read {}", dir.display())),
+    };
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .with_context(|| format!("Couldn't read {}", dir.display()))?
+    {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !kind.is_name(&name) {
+            continue;
+        }
+        let Ok(meta) = entry.metadata().await else {
+            continue;
+        };
+        let saved_at = meta
+            .modified()
+            .ok()
+            .map(chrono::DateTime::<chrono::Utc>::from)
+            .map(|t| t.naive_utc())
+            .unwrap_or_default();
+        reports.push(SavedReport {
+            name,
+            bytes: meta.len(),
+            saved_at,
+        });
+    }
+    reports.sort_by_key(|r| std::cmp::Reverse(r.saved_at));
+    Ok(reports)
+}
+
+/// A report's file, `None` if there's no report of that kind with that name
+pub async fn read(dir: &Path, kind: ReportKind, name: &str) -> Result<Option<Vec<u8>>> {
+    if !kind.is_name(name) {
+        return Ok(None);
+    }
+    match tokio::fs::read(dir.join(name)).await {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(why) if why.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(why) => Err(why).with_context(|| format!("Couldn't read report {name}")),
+    }
+}
+
+/// Remove every report of a kind in `dir`
+pub async fn clear(dir: &Path, kind: ReportKind) -> Result {
+    for report in list(dir, kind).await? {
+        tokio::fs::remove_file(dir.join(&report.name))
+            .await
+            .with_context(|| format!("Couldn't remove report {}", report.name))?;
+    }
+    Ok(())
+}
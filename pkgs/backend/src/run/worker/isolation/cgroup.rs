@@ -1,6 +1,11 @@
 //! Modules controls cgroup creation for the container
 
-use std::{fmt::Display, ops::Sub, os::unix::fs::MetadataExt, path::PathBuf};
+use std::{
+    fmt::Display,
+    ops::Sub,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
 
 use anyhow::bail;
 
@@ -103,6 +108,15 @@ impl CGroup {
         Ok(())
     }
 
+    /// Check children of this cgroup get a controller, it has to be enabled in `subtree_control`
+    pub async fn verify_subtree_controller(&self, controller: &str) -> Result {
+        let enabled = self.read_prop("cgroup.subtree_control").await?;
+        if !enabled.split_whitespace().any(|c| c == controller) {
+            bail!("cgroup controller {} isn't enabled for workers", controller);
+        }
+        Ok(())
+    }
+
     pub async fn create_child(&self, name: &str, ephemeral: bool) -> Result<Self> {
         let path = self.path.join(name);
         tokio::fs::create_dir(&path)
@@ -161,6 +175,11 @@ impl CGroup {
             self.write_prop(prop.0, prop.1).await?;
         }
 
+        // Disk I/O limits, one line per disk
+        for line in lim.io_max.iter() {
+            self.write_prop("io.max", line).await?;
+        }
+
         Ok(())
     }
 
@@ -316,9 +335,63 @@ impl Display for CGroupStats {
     }
 }
 
+/// What this host's cgroups can do for workers, logged as JSON on startup so deploy scripts can
+/// check it, and shown to admins when workers run with reduced enforcement
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CGroupCapabilities {
+    /// `/sys/fs/cgroup` is a cgroup v2 hierarchy
+    pub v2: bool,
+    /// The server is in a cgroup v1 hierarchy, which workers can't use
+    pub v1: bool,
+    /// The cgroup the server started in
+    pub path: Option<PathBuf>,
+    /// The server owns its cgroup, so it can make one for each worker
+    pub delegated: bool,
+    /// Controllers workers need that aren't available in the server's cgroup
+    pub missing_controllers: Vec<String>,
+}
+
+impl CGroupCapabilities {
+    pub async fn detect(limit: &LimitConfig) -> Self {
+        let mut caps = Self {
+            v2: Path::new("/sys/fs/cgroup/cgroup.controllers").exists(),
+            ..Default::default()
+        };
+        // v1 lines look like `4:memory:/path`, the v2 one is `0::/path`
+        caps.v1 = tokio::fs::read_to_string("/proc/self/cgroup")
+            .await
+            .is_ok_and(|info| info.lines().any(|l| !l.starts_with("0::")));
+        let Ok(current) = CGroup::get_current().await else {
+            return caps;
+        };
+        caps.path = Some(current.path().clone());
+        if !caps.v2 {
+            return caps;
+        }
+        caps.delegated = current.verify_access().await.is_ok();
+        let available = current
+            .read_prop("cgroup.controllers")
+            .await
+            .unwrap_or_default();
+        let available = available.split_whitespace().collect::<Vec<_>>();
+        caps.missing_controllers = BASE_REQUIRED_CONTROLLERS
+            .iter()
+            .map(|c| c.to_string())
+            .chain(limit.additional_controllers.iter().flatten().cloned())
+            .filter(|c| !available.contains(&c.as_str()))
+            .collect();
+        caps
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+// May expand in the future / add config
+const BASE_REQUIRED_CONTROLLERS: [&str; 2] = ["memory", "cpu"];
+
 pub async fn setup_cgroups(limit: &LimitConfig) -> Result<(CGroup, CGroup)> {
-    // May expand in the future / add config
-    const BASE_REQUIRED_CONTROLLERS: [&str; 2] = ["memory", "cpu"];
     const SERVICE_CGROUP_NAME: &str = "wcpc_service";
 
     let root_group = CGroup::get_current().await?;
@@ -360,5 +433,46 @@ pub async fn setup_cgroups(limit: &LimitConfig) -> Result<(CGroup, CGroup)> {
             .context("Couldn't enable additional controllers in subtree control")?;
     }
 
+    if limit.io.is_some() {
+        let res = async {
+            root_group.verify_controllers(&["io"]).await?;
+            root_group.enable_subtree_control(&["io"]).await
+        }
+        .await;
+        if let Err(why) = res {
+            warn!(
+                "Couldn't enable the io controller, disk I/O won't be limited: {:?}",
+                why
+            );
+        }
+    }
+
     Ok((root_group, new_group))
 }
+
+/// The `MAJ:MIN` of the disk a path is on, for `io.max`
+pub async fn block_device(path: &Path) -> Result<String> {
+    let meta = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("Couldn't get metadata of {}", path.display()))?;
+    let dev = meta.dev();
+    let (major, minor) = (nix::sys::stat::major(dev), nix::sys::stat::minor(dev));
+    let sys = PathBuf::from(format!("/sys/dev/block/{major}:{minor}"));
+    if !sys.exists() {
+        bail!("{} isn't on a block device", path.display());
+    }
+    // io.max only takes whole disks, a partition is limited through its disk
+    if sys.join("partition").exists() {
+        let disk = tokio::fs::canonicalize(&sys)
+            .await
+            .context("Couldn't resolve block device")?
+            .parent()
+            .context("Partition has no disk")?
+            .join("dev");
+        let disk = tokio::fs::read_to_string(&disk)
+            .await
+            .with_context(|| format!("Couldn't read {}", disk.display()))?;
+        return Ok(disk.trim().to_string());
+    }
+    Ok(format!("{major}:{minor}"))
+}
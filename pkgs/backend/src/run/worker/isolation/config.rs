@@ -5,7 +5,7 @@ use anyhow::bail;
 use crate::{error::prelude::*, run::where_is};
 
 use super::{
-    cgroup,
+    cgroup::{self, CGroupCapabilities},
     seccomp::{BpfConfig, SockFilter},
     CGroup,
 };
@@ -34,6 +34,22 @@ const fn default_hard_memory_limit() -> u64 {
     1024 * 1024 * 350 // 350 MB
 }
 
+const fn default_max_output() -> u64 {
+    1024 * 1024 * 16 // 16 MiB
+}
+
+const fn default_max_stack() -> u64 {
+    1024 * 1024 * 64 // 64 MiB
+}
+
+const fn default_max_open_files() -> u64 {
+    256
+}
+
+const fn default_max_file_size() -> u64 {
+    1024 * 1024 * 64 // 64 MiB
+}
+
 const fn default_nice() -> i32 {
     10
 }
@@ -87,6 +103,30 @@ pub struct LimitConfig {
     /// as a hard limit. This should be set above anything you plan to set as a soft limit
     /// Default: 350 MB
     pub hard_memory_limit_bytes: u64,
+    #[serde(default = "default_max_output")]
+    /// Cap on how many bytes a command can write to stdout, and separately to stderr
+    /// Output is read as it's written and the command is killed as soon as it goes over,
+    /// so a program printing in a loop can't use up the worker's memory
+    /// Default: 16 MiB
+    pub max_output_bytes: u64,
+    #[serde(default = "default_max_stack")]
+    /// Stack size limit (`RLIMIT_STACK`) for the worker and everything it runs in bytes
+    /// Recursive solutions can need a lot of stack, so this is higher than most systems' 8 MiB
+    /// Set to 0 to keep the limit the server was started with
+    /// Default: 64 MiB
+    pub max_stack_bytes: u64,
+    #[serde(default = "default_max_open_files")]
+    /// Limit on open file descriptors (`RLIMIT_NOFILE`) for the worker and everything it runs
+    /// Set to 0 to keep the limit the server was started with
+    /// Default: 256
+    pub max_open_files: u64,
+    #[serde(default = "default_max_file_size")]
+    /// Largest file the worker or anything it runs can write in bytes (`RLIMIT_FSIZE`),
+    /// going over kills the process with `SIGXFSZ`. This also applies to compiled programs,
+    /// so keep it above the size of anything compilers produce
+    /// Set to 0 to keep the limit the server was started with
+    /// Default: 64 MiB
+    pub max_file_size_bytes: u64,
     #[serde(default = "default_nice")]
     /// The niceness delegated to the worker process
     /// This is a value between -20 and 19, with 19 being the lowest priority
@@ -136,6 +176,48 @@ pub struct LimitConfig {
     ///
     /// Default: None
     pub additional_properties: Option<HashMap<String, String>>,
+    /// Optional disk bandwidth and IOPS limits for each worker, written to `io.max`
+    /// so a submission writing huge files can't thrash the disks the host is using.
+    /// Needs the `io` controller, when it isn't available the limits are skipped with a warning
+    ///
+    /// Default: None
+    #[serde(default)]
+    pub io: Option<IoLimitConfig>,
+    /// `io.max` lines for the disks in `io`, worked out in `IsolationConfig::setup`
+    #[serde(skip)]
+    pub io_max: Vec<String>,
+}
+
+/// Limits on each worker's disk I/O, any left out aren't limited
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IoLimitConfig {
+    /// Bytes read per second
+    pub read_bytes_per_sec: Option<u64>,
+    /// Bytes written per second
+    pub write_bytes_per_sec: Option<u64>,
+    /// Read operations per second
+    pub read_iops: Option<u64>,
+    /// Write operations per second
+    pub write_iops: Option<u64>,
+    /// Paths on the disks to limit, each path's whole disk is limited
+    /// Default: the workers' parent directory and every bind mount
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+}
+
+impl IoLimitConfig {
+    /// The part of an `io.max` line after the device
+    fn limits(&self) -> String {
+        let limit = |v: Option<u64>| v.map_or_else(|| "max".to_string(), |v| v.to_string());
+        format!(
+            "rbps={} wbps={} riops={} wiops={}",
+            limit(self.read_bytes_per_sec),
+            limit(self.write_bytes_per_sec),
+            limit(self.read_iops),
+            limit(self.write_iops)
+        )
+    }
 }
 
 impl Default for LimitConfig {
@@ -145,11 +227,17 @@ impl Default for LimitConfig {
             hard_timeout_internal_secs: default_hard_timeout_internal(),
             hard_timeout_user_secs: default_hard_timeout_user(),
             hard_memory_limit_bytes: default_hard_memory_limit(),
+            max_output_bytes: default_max_output(),
+            max_stack_bytes: default_max_stack(),
+            max_open_files: default_max_open_files(),
+            max_file_size_bytes: default_max_file_size(),
             additional_controllers: None,
             additional_properties: None,
             nice: default_nice(),
             shutdown_retry_interval: default_shutdown_retry_interval(),
             shutdown_retries: default_shutdown_retries(),
+            io: None,
+            io_max: Vec::new(),
         }
     }
 }
@@ -159,6 +247,10 @@ impl Default for LimitConfig {
 pub struct IsolationConfig {
     #[serde(default)]
     pub workers_parent: Option<PathBuf>,
+    /// Where to save forensic bundles of workers that die unexpectedly, see `worker::forensics`
+    /// Default: a `wcpc_crash_reports` directory in the temp dir
+    #[serde(default)]
+    pub crash_reports_dir: Option<PathBuf>,
     #[serde(default)]
     pub bind_mounts: Vec<BindMountConfig>,
     #[serde(default)]
@@ -172,12 +264,43 @@ pub struct IsolationConfig {
     #[serde(default)]
     pub compiled_seccomp_program: Option<Vec<SockFilter>>,
     #[serde(default)]
+    pub compiled_network_seccomp_program: Option<Vec<SockFilter>>,
+    /// Let problems enable networking for their runs, see `loopback`
+    /// Default: false
+    #[serde(default)]
+    pub allow_network: bool,
+    /// Bring up the loopback interface in the worker's network namespace and allow socket syscalls,
+    /// so programs can talk to themselves over `127.0.0.1`. The namespace is still cut off from
+    /// the host, this is set per worker for problems that enable networking, not in the config
+    #[serde(default)]
+    pub loopback: bool,
+    #[serde(default)]
     pub limits: LimitConfig,
+    /// Run workers without cgroups when they can't be set up, instead of refusing to start.
+    /// Only rlimits limit programs then, see `reduced_enforcement`
+    /// Default: false
+    #[serde(default)]
+    pub allow_reduced_enforcement: bool,
+    /// Set by `setup` when workers run without cgroups, so the worker limits each run's CPU time
+    /// and address space with rlimits instead. Memory use isn't measured and runs killed for CPU
+    /// time only report the limit, not the time used
+    #[serde(default)]
+    pub reduced_enforcement: bool,
     #[serde(skip)]
     pub cgroups: Option<(CGroup, CGroup)>,
+    #[serde(skip)]
+    pub cgroup_capabilities: Option<CGroupCapabilities>,
 }
 
 impl IsolationConfig {
+    pub fn include_bins(&self) -> &[String] {
+        &self.include_bins
+    }
+
+    pub fn seccomp(&self) -> &BpfConfig {
+        &self.seccomp
+    }
+
     fn add_bins_to_path(&mut self) -> Result {
         let bin_paths = self
             .include_bins
@@ -198,14 +321,20 @@ impl IsolationConfig {
         Ok(())
     }
 
-    fn compile_seccomp(&mut self) -> Result {
-        let seccomp_program = super::seccomp::compile_filter(&self.seccomp)
+    pub(super) fn compile_seccomp(&mut self) -> Result {
+        let seccomp_program = super::seccomp::compile_filter(&self.seccomp, &[])
             .context("Failed to setup seccomp program")?;
         self.compiled_seccomp_program = Some(seccomp_program);
+        if self.allow_network {
+            let network_program =
+                super::seccomp::compile_filter(&self.seccomp, &super::syscalls::NETWORK_SYSCALLS)
+                    .context("Failed to setup network seccomp program")?;
+            self.compiled_network_seccomp_program = Some(network_program);
+        }
         Ok(())
     }
 
-    fn verify_tmpfs_limit(&self) -> Result {
+    pub(super) fn verify_tmpfs_limit(&self) -> Result {
         const PATTERN: &str = r"^\d+(?:\.\d+)?(?:k|m|g|%)?$";
         let re = regex::Regex::new(PATTERN).context("Couldn't compile regex")?;
         if !re.is_match(&self.limits.tmpfs_size) {
@@ -214,6 +343,43 @@ impl IsolationConfig {
         Ok(())
     }
 
+    /// Work out the `io.max` lines for `limits.io`, skipping paths that aren't on a block device
+    /// like tmpfs or overlay mounts
+    async fn resolve_io_max(&mut self, root: &CGroup) {
+        let Some(io) = &self.limits.io else {
+            return;
+        };
+        if let Err(why) = root.verify_subtree_controller("io").await {
+            warn!("Not limiting disk I/O: {:?}", why);
+            return;
+        }
+        let paths = if io.paths.is_empty() {
+            let parent = self
+                .workers_parent
+                .clone()
+                .unwrap_or_else(std::env::temp_dir);
+            std::iter::once(parent)
+                .chain(self.bind_mounts.iter().map(|b| b.src.clone()))
+                .collect()
+        } else {
+            io.paths.clone()
+        };
+        let mut devices = Vec::with_capacity(paths.len());
+        for path in paths {
+            match cgroup::block_device(&path).await {
+                Ok(device) if !devices.contains(&device) => devices.push(device),
+                Ok(_) => {}
+                Err(why) => warn!("Not limiting disk I/O for {}: {:?}", path.display(), why),
+            }
+        }
+        let limits = io.limits();
+        self.limits.io_max = devices
+            .into_iter()
+            .map(|device| format!("{device} {limits}"))
+            .collect();
+        info!("Limiting worker disk I/O with {:?}", self.limits.io_max);
+    }
+
     pub async fn setup(&mut self, allow_cgroup_failure: bool) -> Result {
         if self.limits.nice < -20 || self.limits.nice > 19 {
             bail!(
@@ -221,21 +387,34 @@ impl IsolationConfig {
                 self.limits.nice
             );
         }
+        let capabilities = CGroupCapabilities::detect(&self.limits).await;
+        info!("cgroup capabilities: {}", capabilities.to_json());
         match cgroup::setup_cgroups(&self.limits).await {
             Ok(cgroups) => {
+                self.resolve_io_max(&cgroups.0).await;
                 self.cgroups = Some(cgroups);
             }
             Err(why) => {
-                if allow_cgroup_failure {
+                if self.allow_reduced_enforcement || allow_cgroup_failure {
                     warn!("Couldn't setup cgroups: {:?}", why);
-                    warn!("Because of debug mode, we will continue without cgroups");
-                    warn!("This WILL MAKE RUNNERS NON-FUNCTIONAL");
-                    warn!("In the production profile this will be an error");
+                    warn!("Running workers with REDUCED ENFORCEMENT, only rlimits limit programs");
+                    warn!("Memory limits are on address space and memory use isn't measured");
+                    if !self.allow_reduced_enforcement {
+                        warn!("Because of debug mode, in the production profile this will be an error");
+                    }
                 } else {
-                    bail!("Couldn't setup cgroups: {}", why);
+                    bail!(
+                        "Couldn't setup cgroups: {}\ncgroup capabilities: {}\n\
+                         Run with --doctor for how to fix this, or set \
+                         run.isolation.allow_reduced_enforcement to run without cgroups",
+                        why,
+                        capabilities.to_json()
+                    );
                 }
             }
         }
+        self.reduced_enforcement = self.cgroups.is_none();
+        self.cgroup_capabilities = Some(capabilities);
         self.verify_tmpfs_limit()?;
         self.add_bins_to_path()
             .context("Couldn't resolve binaries")?;
@@ -0,0 +1,246 @@
+//! `--doctor`, checks this machine can run workers so misconfigurations show up
+//! before the first submission turns them into judge errors
+
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    os::unix::fs::PermissionsExt,
+    path::Path,
+};
+
+use anyhow::bail;
+use nix::{
+    libc,
+    sched::CloneFlags,
+    sys::wait::{waitpid, WaitStatus},
+    unistd::ForkResult,
+};
+
+use crate::{
+    error::prelude::*,
+    run::{where_is, RunConfig},
+};
+
+use super::{cgroup::CGroup, id_map, mounts::mount_root, seccomp, IsolationConfig};
+
+#[derive(Default)]
+struct Report {
+    failed: usize,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, res: Result, fix: impl Display) {
+        match res {
+            Ok(()) => println!("[ok] {name}"),
+            Err(why) => {
+                self.failed += 1;
+                println!("[!!] {name}: {why:#}");
+                println!("     {fix}");
+            }
+        }
+    }
+
+    fn warn(&self, name: &str, why: impl Display) {
+        println!("[??] {name}: {why}");
+    }
+}
+
+/// Check everything workers need from the system with this machine's `run` config,
+/// exits with 1 if anything is missing
+pub fn run_doctor() -> Result {
+    let figment = crate::figment()?;
+    let mut config = figment
+        .extract_inner::<RunConfig>("run")
+        .context("Couldn't get run config")?
+        .isolation;
+    let mut report = Report::default();
+
+    println!("Checking the sandbox environment\n");
+
+    report.check(
+        "Tmpfs size",
+        config.verify_tmpfs_limit(),
+        "Set run.isolation.limits.tmpfs_size to a size like \"5%\", \"512m\" or \"1g\"",
+    );
+    report.check(
+        "Seccomp filter",
+        config.compile_seccomp(),
+        "Check every syscall in run.isolation.seccomp.allowed_calls exists on this architecture",
+    );
+
+    // These fork, which is only safe before the async runtime starts any threads
+    report.check(
+        "Unprivileged user namespaces",
+        probe(|| {
+            nix::sched::unshare(CloneFlags::CLONE_NEWUSER).context("Couldn't unshare")?;
+            Ok(())
+        }),
+        "Enable them with `sysctl kernel.unprivileged_userns_clone=1` (Debian) or \
+         `sysctl user.max_user_namespaces=15000`, on Ubuntu 24.04+ also set \
+         `kernel.apparmor_restrict_unprivileged_userns=0` or add an AppArmor profile for this binary",
+    );
+    let mount_dir = std::env::temp_dir().join(format!("wcpc_doctor_{}", std::process::id()));
+    std::fs::create_dir_all(&mount_dir).context("Couldn't create mount directory")?;
+    report.check(
+        "Tmpfs mount in a mount namespace",
+        probe(|| {
+            nix::sched::unshare(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNS)
+                .context("Couldn't unshare")?;
+            mount_root(&mount_dir, &config.limits.tmpfs_size)
+        }),
+        "Make sure user namespaces work (see above) and nothing like a container runtime blocks mounts",
+    );
+    std::fs::remove_dir(&mount_dir).ok();
+    if let Some(program) = config.compiled_seccomp_program.clone() {
+        let listener = config.seccomp().diagnostics();
+        let fix = if listener {
+            "run.isolation.seccomp.diagnostics needs Linux 5.0 or newer, turn it off on older kernels"
+        } else {
+            "The kernel needs CONFIG_SECCOMP_FILTER, check `grep Seccomp /proc/self/status`"
+        };
+        report.check(
+            "Seccomp support",
+            probe(|| {
+                nix::sys::prctl::set_no_new_privs().context("Couldn't set no new privs")?;
+                seccomp::install_filters(&program, listener).map(drop)
+            }),
+            fix,
+        );
+    }
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Couldn't start runtime")?
+        .block_on(check_host(&config, &mut report));
+
+    println!();
+    if report.failed == 0 {
+        println!("Everything looks ready to run workers");
+        Ok(())
+    } else {
+        println!("{} check(s) failed", report.failed);
+        std::process::exit(1);
+    }
+}
+
+/// Checks that don't need a child process
+async fn check_host(config: &IsolationConfig, report: &mut Report) {
+    report.check(
+        "Subordinate UIDs and GIDs",
+        id_map::get_uid_gid_maps(config).await.map(drop),
+        "Give the user running the server a range in /etc/subuid and /etc/subgid \
+         (`usermod --add-subuids 100000-165535 --add-subgids 100000-165535 $USER`), \
+         or set run.isolation.override_subuid and override_subgid",
+    );
+
+    for prog in ["newuidmap", "newgidmap"] {
+        match where_is(prog) {
+            Some(path) => {
+                report.check(prog, Ok(()), "");
+                let setuid =
+                    std::fs::metadata(&path).is_ok_and(|m| m.permissions().mode() & 0o4000 != 0);
+                if !setuid {
+                    report.warn(
+                        prog,
+                        format!(
+                            "{} isn't setuid, make sure it has the setuid capability instead",
+                            path.display()
+                        ),
+                    );
+                }
+            }
+            None => report.check(
+                prog,
+                Err(anyhow!("Not found in PATH")),
+                "Install the uidmap (Debian/Ubuntu) or shadow-utils (Fedora) package",
+            ),
+        }
+    }
+
+    let cgroup_v2 = Path::new("/sys/fs/cgroup/cgroup.controllers").exists();
+    report.check(
+        "cgroup v2",
+        if cgroup_v2 {
+            Ok(())
+        } else {
+            Err(anyhow!("/sys/fs/cgroup isn't a cgroup v2 hierarchy"))
+        },
+        "Boot with systemd.unified_cgroup_hierarchy=1, cgroup v1 isn't supported. \
+         Otherwise set run.isolation.allow_reduced_enforcement to run workers with rlimits only",
+    );
+    if !cgroup_v2 {
+        return;
+    }
+
+    let mut controllers = vec!["memory".to_string(), "cpu".to_string()];
+    controllers.extend(
+        config
+            .limits
+            .additional_controllers
+            .iter()
+            .flatten()
+            .cloned(),
+    );
+    let res = async {
+        let group = CGroup::get_current().await?;
+        group.verify_access().await?;
+        Ok(group)
+    }
+    .await;
+    match res {
+        Ok(group) => {
+            report.check("cgroup delegation", Ok(()), "");
+            report.check(
+                "cgroup controllers",
+                group.verify_controllers(&controllers).await,
+                "Delegate the controllers to the server's cgroup, with systemd set \
+                 `Delegate=yes` (or `Delegate=memory cpu`) on the service",
+            );
+            if config.limits.io.is_some() {
+                if let Err(why) = group.verify_controllers(&["io"]).await {
+                    report.warn(
+                        "io controller",
+                        format!("{why:#}, disk I/O limits in run.isolation.limits.io won't apply"),
+                    );
+                }
+            }
+        }
+        Err(why) => report.check(
+            "cgroup delegation",
+            Err(why),
+            "Run the server in a cgroup it owns, with systemd use a service with `Delegate=yes` \
+             or `systemd-run --user --scope -p Delegate=yes`",
+        ),
+    }
+}
+
+/// Run a check in a forked child so the namespaces and filters it sets up don't stick to us
+fn probe(check: impl FnOnce() -> Result) -> Result {
+    let (read, write) = nix::unistd::pipe().context("Couldn't create pipe")?;
+    // Safety: only called before the runtime starts, so we're still single threaded
+    match unsafe { nix::unistd::fork() }.context("Couldn't fork")? {
+        ForkResult::Child => {
+            drop(read);
+            let code = match check() {
+                Ok(()) => 0,
+                Err(why) => {
+                    write!(std::fs::File::from(write), "{why:#}").ok();
+                    1
+                }
+            };
+            // Skip exit handlers, a seccomp filter might not allow what they do
+            unsafe { libc::_exit(code) }
+        }
+        ForkResult::Parent { child } => {
+            drop(write);
+            let mut msg = String::new();
+            std::fs::File::from(read).read_to_string(&mut msg).ok();
+            match waitpid(child, None).context("Couldn't wait for check")? {
+                WaitStatus::Exited(_, 0) => Ok(()),
+                WaitStatus::Exited(_, _) => bail!("{msg}"),
+                status => bail!("Check stopped unexpectedly ({status:?})"),
+            }
+        }
+    }
+}
@@ -1,13 +1,36 @@
 //! Misc hardening of the process
 //!
 
+use nix::sys::resource::{getrlimit, setrlimit, Resource};
+
 use crate::error::prelude::*;
 
-pub fn harden_process() -> Result {
+use super::LimitConfig;
+
+pub fn harden_process(limits: &LimitConfig) -> Result {
     // TODO: Set more secure bits?
 
     debug!("Applying misc. hardening to process");
 
     nix::sys::prctl::set_dumpable(false).context("Couldn't set dumpable to false")?;
-    nix::sys::prctl::set_no_new_privs().context("Couldn't set no new privs")
+    nix::sys::prctl::set_no_new_privs().context("Couldn't set no new privs")?;
+
+    // Limits are inherited by everything the worker runs, soft and hard are the same
+    // so user code can't raise them back up
+    for (resource, limit) in [
+        (Resource::RLIMIT_STACK, limits.max_stack_bytes),
+        (Resource::RLIMIT_NOFILE, limits.max_open_files),
+        (Resource::RLIMIT_FSIZE, limits.max_file_size_bytes),
+    ] {
+        if limit != 0 {
+            // Raising the hard limit needs privileges we don't have anymore
+            let (_, hard) =
+                getrlimit(resource).with_context(|| format!("Couldn't get {resource:?}"))?;
+            let limit = limit.min(hard);
+            setrlimit(resource, limit, limit)
+                .with_context(|| format!("Couldn't set {resource:?} to {limit}"))?;
+        }
+    }
+
+    Ok(())
 }
@@ -1,10 +1,11 @@
-use std::{path::Path, time::Instant};
+use std::{os::fd::OwnedFd, path::Path, time::Instant};
 
 use chroot::chroot;
 use environment::{setup_environment, setup_environment_post_chroot};
 use harden::harden_process;
 use id_map::wait_for_id_mapping;
 use mounts::mount_root;
+use network::bring_up_loopback;
 use nix::unistd::{Gid, Uid};
 use unshare::unshare;
 use user::{su_root, su_runner};
@@ -14,23 +15,27 @@ use crate::error::prelude::*;
 mod cgroup;
 mod chroot;
 mod config;
+mod doctor;
 mod environment;
 mod harden;
 pub mod id_map;
 mod mounts;
+mod network;
 pub mod seccomp;
 mod syscalls;
 mod unshare;
 mod user;
 
-pub use cgroup::{CGroup, CGroupStats};
+pub use cgroup::{CGroup, CGroupCapabilities, CGroupStats};
 pub use config::*;
+pub use doctor::run_doctor;
 
 const RUNNER_UID: Uid = Uid::from_raw(1000);
 const RUNNER_GID: Gid = Gid::from_raw(100);
 
 /// Isolate a process in a new namespace.
-pub fn isolate(config: &IsolationConfig, root: &Path) -> Result {
+/// Returns the seccomp listener when `seccomp.diagnostics` is on, see [`seccomp::handle_violations`]
+pub fn isolate(config: &IsolationConfig, root: &Path) -> Result<Option<OwnedFd>> {
     debug!("Isolating Process");
     let instant = Instant::now();
     unshare().context("Couldn't unshare")?;
@@ -40,14 +45,20 @@ pub fn isolate(config: &IsolationConfig, root: &Path) -> Result {
     setup_environment(root, &config.bind_mounts).context("Couldn't setup environment")?;
     chroot(root).context("Couldn't chroot to jail")?;
     setup_environment_post_chroot().context("Couldn't setup environment post chroot")?;
+    if config.loopback {
+        bring_up_loopback().context("Couldn't bring up loopback")?;
+    }
     su_runner()?;
-    harden_process().context("Couldn't harden process")?;
-    let program = config
-        .compiled_seccomp_program
-        .as_ref()
-        .context("Seccomp program not compiled")?;
-    seccomp::install_filters(program).context("Couldn't install seccomp filters")?;
+    harden_process(&config.limits).context("Couldn't harden process")?;
+    let program = if config.loopback {
+        config.compiled_network_seccomp_program.as_ref()
+    } else {
+        config.compiled_seccomp_program.as_ref()
+    }
+    .context("Seccomp program not compiled")?;
+    let listener = seccomp::install_filters(program, config.seccomp().diagnostics())
+        .context("Couldn't install seccomp filters")?;
     let elapsed = instant.elapsed();
     debug!("Isolation Complete ({elapsed:?})");
-    Ok(())
+    Ok(listener)
 }
@@ -0,0 +1,43 @@
+//! Networking for workers of problems that enable it, only loopback is available
+//!
+
+use std::os::fd::AsRawFd;
+
+use nix::{
+    libc,
+    sys::socket::{socket, AddressFamily, SockFlag, SockType},
+};
+
+use crate::error::prelude::*;
+
+nix::ioctl_read_bad!(get_interface_flags, libc::SIOCGIFFLAGS, libc::ifreq);
+nix::ioctl_write_ptr_bad!(set_interface_flags, libc::SIOCSIFFLAGS, libc::ifreq);
+
+/// Set `lo` up in our network namespace, new namespaces start with it down.
+/// Needs `CAP_NET_ADMIN` so this has to happen before we switch to the runner user
+pub fn bring_up_loopback() -> Result {
+    debug!("Bringing up loopback interface");
+
+    let sock = socket(
+        AddressFamily::Inet,
+        SockType::Datagram,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )
+    .context("Couldn't create socket")?;
+
+    // Safety: ifreq is plain data, all zeroes is a valid value
+    let mut req: libc::ifreq = unsafe { std::mem::zeroed() };
+    for (dst, src) in req.ifr_name.iter_mut().zip(b"lo\0") {
+        *dst = *src as libc::c_char;
+    }
+
+    // Safety: req is a valid ifreq naming an interface, the kernel only writes within it
+    unsafe {
+        get_interface_flags(sock.as_raw_fd(), &mut req).context("Couldn't get lo flags")?;
+        req.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+        set_interface_flags(sock.as_raw_fd(), &req).context("Couldn't set lo flags")?;
+    }
+
+    Ok(())
+}
@@ -2,9 +2,18 @@
 //! Filters should be compiled by service process and
 //! applied in a worker process.
 
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
 
 use anyhow::Ok;
+use nix::{
+    errno::Errno,
+    libc,
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
 use seccompiler::{sock_filter, BpfProgram, SeccompAction, SeccompFilter, TargetArch};
 
 use crate::error::prelude::*;
@@ -81,6 +90,16 @@ pub enum _SeccompAction {
     Trap,
 }
 
+impl _SeccompAction {
+    /// Whether a syscall hitting this action doesn't go through
+    fn blocks(&self) -> bool {
+        matches!(
+            self,
+            Self::Errno { .. } | Self::KillThread | Self::KillProcess | Self::Trap
+        )
+    }
+}
+
 impl From<_SeccompAction> for SeccompAction {
     fn from(action: _SeccompAction) -> Self {
         match action {
@@ -102,11 +121,51 @@ pub struct BpfConfig {
     mismatch_action: _SeccompAction,
     #[serde(default)]
     allowed_calls: Vec<String>,
+    /// Record which syscall a program was blocked for so admins can see it on the run,
+    /// only works when `mismatch_action` blocks syscalls. Needs Linux 5.0 or newer
+    #[serde(default)]
+    diagnostics: bool,
+    /// Tell participants what kind of thing their program was stopped for (e.g. starting processes),
+    /// needs `diagnostics`
+    #[serde(default)]
+    hints: bool,
+}
+
+impl BpfConfig {
+    /// Whether blocked syscalls are sent to the worker to record instead of being handled by the kernel
+    pub fn diagnostics(&self) -> bool {
+        self.diagnostics && self.mismatch_action.blocks()
+    }
+
+    pub fn hints(&self) -> bool {
+        self.hints && self.diagnostics()
+    }
 }
 
 type SyscallNo = i32;
 
-pub fn compile_filter(config: &BpfConfig) -> Result<Vec<SockFilter>> {
+/// Trace data the mismatch action is compiled with when diagnostics are on, swapped for
+/// `SECCOMP_RET_USER_NOTIF` afterwards since seccompiler can't produce that action itself
+const NOTIFY_SENTINEL: u32 = 0x5ecc;
+const SECCOMP_RET_TRACE: u32 = 0x7ff00000;
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc00000;
+const BPF_RET_K: u16 = 0x06;
+
+nix::ioctl_readwrite!(notif_recv, b'!', 0, libc::seccomp_notif);
+nix::ioctl_readwrite!(notif_send, b'!', 1, libc::seccomp_notif_resp);
+
+/// Name of a syscall by its number on this architecture
+pub fn syscall_name(nr: i32) -> Option<&'static str> {
+    let table: &[(&'static str, SyscallNo)] = match get_arch() {
+        TargetArch::x86_64 => &X86_64_CALLS,
+        TargetArch::aarch64 => &AARCH64_CALLS,
+        _ => unreachable!("Not reachable as get_arch fails compilation"),
+    };
+    table.iter().find(|(_, n)| *n == nr).map(|(name, _)| *name)
+}
+
+/// Compile the filter for the config, allowing `extra_calls` on top of the base list
+pub fn compile_filter(config: &BpfConfig, extra_calls: &[&str]) -> Result<Vec<SockFilter>> {
     let arch = get_arch();
 
     let call_table: HashMap<&str, SyscallNo> = match arch {
@@ -117,6 +176,7 @@ pub fn compile_filter(config: &BpfConfig) -> Result<Vec<SockFilter>> {
 
     let rules = BASE_ALLOWED_SYSCALLS
         .into_iter()
+        .chain(extra_calls.iter().copied())
         .chain(config.allowed_calls.iter().map(|s| s.as_str()))
         .map(|call| {
             call_table
@@ -133,23 +193,103 @@ pub fn compile_filter(config: &BpfConfig) -> Result<Vec<SockFilter>> {
         .collect::<Result<_, _>>()
         .map_err(|call| anyhow!("Unknown syscall for seccomp: {}", call))?;
 
-    let filter = SeccompFilter::new(
-        rules,
-        config.mismatch_action.into(),
-        SeccompAction::Allow,
-        arch,
-    )
-    .context("Failed to create seccomp filter")?;
+    let mismatch_action = if config.diagnostics() {
+        SeccompAction::Trace(NOTIFY_SENTINEL)
+    } else {
+        config.mismatch_action.into()
+    };
+
+    let filter = SeccompFilter::new(rules, mismatch_action, SeccompAction::Allow, arch)
+        .context("Failed to create seccomp filter")?;
 
     let compiled: BpfProgram = filter
         .try_into()
         .context("Failed to compile seccomp filter")?;
 
-    Ok(compiled.into_iter().map(Into::into).collect())
+    let mut program = compiled
+        .into_iter()
+        .map(SockFilter::from)
+        .collect::<Vec<_>>();
+    if config.diagnostics() {
+        for inst in program.iter_mut() {
+            if inst.code == BPF_RET_K && inst.k == SECCOMP_RET_TRACE | NOTIFY_SENTINEL {
+                inst.k = SECCOMP_RET_USER_NOTIF;
+            }
+        }
+    }
+
+    Ok(program)
 }
 
-pub fn install_filters(filters: &[SockFilter]) -> Result {
+/// Install the filter, with `listener` the filter sends blocked syscalls to the returned
+/// file descriptor instead (see [`handle_violations`])
+pub fn install_filters(filters: &[SockFilter], listener: bool) -> Result<Option<OwnedFd>> {
     debug!("Applying seccomp filters");
-    let bpf_filter = filters.iter().map(|s| s.clone().into()).collect::<Vec<_>>();
-    seccompiler::apply_filter(&bpf_filter).context("Couldn't apply seccomp filter")
+    if !listener {
+        let bpf_filter = filters.iter().map(|s| s.clone().into()).collect::<Vec<_>>();
+        seccompiler::apply_filter(&bpf_filter).context("Couldn't apply seccomp filter")?;
+        return Ok(None);
+    }
+
+    let mut bpf_filter = filters
+        .iter()
+        .map(|s| libc::sock_filter {
+            code: s.code,
+            jt: s.jt as u8,
+            jf: s.jf as u8,
+            k: s.k,
+        })
+        .collect::<Vec<_>>();
+    let prog = libc::sock_fprog {
+        len: bpf_filter.len() as u16,
+        filter: bpf_filter.as_mut_ptr(),
+    };
+    // Safety: prog points to a filter that outlives the call, the kernel copies it
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_NEW_LISTENER,
+            &prog as *const libc::sock_fprog,
+        )
+    };
+    if fd < 0 {
+        return Err(Errno::last()).context("Couldn't apply seccomp filter with a listener");
+    }
+    // Safety: the kernel just gave us this descriptor and nothing else owns it
+    Ok(Some(unsafe { OwnedFd::from_raw_fd(fd as i32) }))
+}
+
+/// Answer syscalls the filter blocked until the listener closes, calling `on_blocked` with each
+/// syscall's number before doing what `mismatch_action` says to the process that made it
+pub fn handle_violations(listener: OwnedFd, config: &BpfConfig, on_blocked: impl Fn(i32)) {
+    loop {
+        // Safety: both are plain data, the kernel wants the request zeroed
+        let mut req: libc::seccomp_notif = unsafe { std::mem::zeroed() };
+        match unsafe { notif_recv(listener.as_raw_fd(), &mut req) } {
+            Err(Errno::EINTR) | Err(Errno::ENOENT) => continue,
+            Err(why) => {
+                warn!("Stopped handling seccomp violations: {why}");
+                break;
+            }
+            _ => {}
+        }
+        on_blocked(req.data.nr);
+
+        let mut resp: libc::seccomp_notif_resp = unsafe { std::mem::zeroed() };
+        resp.id = req.id;
+        resp.error = -libc::ENOSYS;
+        let pid = Pid::from_raw(req.pid as i32);
+        match config.mismatch_action {
+            _SeccompAction::Errno { errno } => resp.error = -(errno as i32),
+            _SeccompAction::Trap => {
+                kill(pid, Signal::SIGSYS).ok();
+            }
+            _ => {
+                kill(pid, Signal::SIGKILL).ok();
+            }
+        }
+        // Fails when the process is already gone, which is fine
+        unsafe { notif_send(listener.as_raw_fd(), &mut resp) }.ok();
+    }
 }
@@ -656,6 +656,18 @@ pub const SPECIAL_CASE_SYSCALLS: [i64; 1] = [
     1008, // rr framework syscall, julia always uses this syscall and obv this isn't smth we can find through Linux headers
 ];
 
+/// Allowed on top of the base list for workers with networking enabled
+pub const NETWORK_SYSCALLS: [&str; 8] = [
+    "accept",
+    "accept4",
+    "sendto",
+    "sendmsg",
+    "recvmsg",
+    "getsockname",
+    "getsockopt",
+    "shutdown",
+];
+
 pub const BASE_ALLOWED_SYSCALLS: [&str; 134] = [
     "sched_yield",
     "statx",
@@ -1,9 +1,13 @@
-use std::{collections::HashMap, fmt::Display, os::unix::process::ExitStatusExt, process::Output};
+use std::{
+    collections::HashMap, fmt::Display, os::unix::process::ExitStatusExt, path::PathBuf,
+    process::Output,
+};
 
-use crate::error::prelude::*;
+use crate::{error::prelude::*, i18n::fill};
 
 use super::config::CommandInfo;
 
+pub mod forensics;
 mod isolation;
 /// Service process side of the worker
 mod service_side;
@@ -11,9 +15,9 @@ mod test_shell;
 /// Worker process side of the worker
 mod worker_side;
 
-pub use isolation::IsolationConfig;
+pub use isolation::{run_doctor, BindMountConfig, CGroupCapabilities, IsolationConfig};
 use nix::sys::signal::Signal;
-pub use service_side::Worker;
+pub use service_side::{OutputStream, Worker};
 pub use test_shell::run_test_shell;
 pub use worker_side::run_from_child;
 
@@ -38,6 +42,23 @@ pub enum ServiceMessage {
     /// Confirm to the worker that it's UID and GID maps have been set
     /// status (true if successful)
     UidGidMapResult(bool),
+    /// Clear the worker's directory and write a new program to it, for pooled workers.
+    /// file_name, program
+    LoadProgram(String, String),
+    /// Read files the compile command produced so they can be cached.
+    /// file names, max total size in bytes
+    ReadArtifacts(Vec<String>, u64),
+    /// Symlink cached files (from a read-only bind mount) into the working directory
+    /// paths
+    LinkArtifacts(Vec<PathBuf>),
+    /// Limit the next command with rlimits, for workers running without cgroups.
+    /// CPU time in seconds, address space in bytes
+    SetRunLimits(u64, u64),
+    /// Send the next command's output as it's written, as well as all of it once it's done
+    StreamNextOutput,
+    /// Tar up everything in the working directory for an admin to download.
+    /// max total size of the files in bytes
+    Snapshot(u64),
     /// Stop the worker process.
     Stop,
 }
@@ -56,6 +77,15 @@ impl ServiceMessage {
     }
 }
 
+/// A file produced by compiling a program
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+    pub name: String,
+    /// Base64 encoded contents
+    pub contents: String,
+    pub executable: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CmdOutput {
     stdout: String,
@@ -72,6 +102,10 @@ pub struct CmdExit {
 pub struct CmdFailure(CmdOutput, CmdExit);
 
 impl CmdFailure {
+    pub fn signal(&self) -> Option<i32> {
+        self.1.signal
+    }
+
     fn interpret_exit_status(&self) -> String {
         let ex = self
             .1
@@ -113,6 +147,10 @@ impl Display for CmdFailure {
 pub enum CmdResult {
     Success(CmdOutput),
     Failure(CmdFailure),
+    /// Killed for writing more than `max_output_bytes`
+    OutputLimitExceeded,
+    /// Failed after seccomp blocked a syscall, with the syscall's name
+    SyscallBlocked(CmdFailure, String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -120,6 +158,13 @@ pub enum CmdResult {
 pub enum WorkerMessage {
     /// A completed command with its output.
     CmdComplete(CmdResult),
+    /// Output a command that's still running has written so far since the last chunk, stdout and
+    /// stderr mixed together, only after `StreamNextOutput`
+    OutputChunk(String),
+    /// Files asked for with `ReadArtifacts`, `None` if any were missing or they're too big
+    Artifacts(Option<Vec<Artifact>>),
+    /// The gzipped tarball asked for with `Snapshot`, base64 encoded, `None` if it's too big
+    Snapshot(Option<String>),
     /// Request service to create a UID and GID mapping.
     /// Contains the PID of the worker process post-fork.
     RequestUidGidMap(i32),
@@ -183,9 +228,17 @@ pub enum CaseError {
     Logic,
     Cancelled,
     HardTimeLimitExceeded,
+    WallTimeExceeded(u64),
     CpuTimeExceeded(u64),
     MemoryLimitExceeded(u64),
+    OutputLimitExceeded(u64),
     Runtime(String),
+    /// A runtime error after seccomp blocked a syscall, the syscall is only shown to admins
+    SyscallBlocked {
+        syscall: String,
+        output: String,
+        hint: bool,
+    },
     Compilation(String),
     Judge(String),
 }
@@ -202,9 +255,12 @@ impl CaseError {
             self,
             CaseError::CpuTimeExceeded(_)
                 | CaseError::MemoryLimitExceeded(_)
+                | CaseError::OutputLimitExceeded(_)
                 | CaseError::Logic
                 | CaseError::Runtime(_)
+                | CaseError::SyscallBlocked { .. }
                 | CaseError::HardTimeLimitExceeded
+                | CaseError::WallTimeExceeded(_)
         )
     }
 
@@ -212,12 +268,16 @@ impl CaseError {
         matches!(
             self,
             CaseError::HardTimeLimitExceeded
+                | CaseError::WallTimeExceeded(_)
                 | CaseError::CpuTimeExceeded(_)
                 | CaseError::MemoryLimitExceeded(_)
+                | CaseError::OutputLimitExceeded(_)
                 | CaseError::Judge(_)
         )
     }
 
+    /// The verdict and each line of details are separate messages for translation catalogs,
+    /// see [`crate::i18n`]
     pub fn to_string(&self, details: bool) -> String {
         match self {
             CaseError::Logic => "Logic Error".to_string(),
@@ -228,6 +288,20 @@ impl CaseError {
                     "Runtime Error".to_string()
                 }
             }
+            CaseError::SyscallBlocked {
+                syscall,
+                output,
+                hint,
+            } => {
+                let hint = hint
+                    .then(|| format!("\n\nHint: {}", syscall_hint(syscall)))
+                    .unwrap_or_default();
+                if details {
+                    format!("Runtime Error:\n{output}{hint}")
+                } else {
+                    format!("Runtime Error{hint}")
+                }
+            }
             CaseError::Compilation(ref s) => {
                 if details {
                     format!("Compilation Error:\n{s}")
@@ -239,7 +313,14 @@ impl CaseError {
                 if details {
                     let seconds = time / 1_000_000;
                     let milliseconds = (time % 1_000_000) / 1000;
-                    format!("Time Limit Exceeded\nYour time: {seconds}s {milliseconds}ms")
+                    let line = fill(
+                        "Your time: {seconds}s {milliseconds}ms",
+                        &[
+                            ("seconds", seconds.to_string()),
+                            ("milliseconds", milliseconds.to_string()),
+                        ],
+                    );
+                    format!("Time Limit Exceeded\n{line}")
                 } else {
                     "Time Limit Exceeded".to_string()
                 }
@@ -247,14 +328,61 @@ impl CaseError {
             CaseError::MemoryLimitExceeded(used) => {
                 if details {
                     let mega = used / (1024 * 1024);
-                    format!("Memory Limit Exceeded\nYour memory usage: {mega} MiB")
+                    let line = fill(
+                        "Your memory usage: {mega} MiB",
+                        &[("mega", mega.to_string())],
+                    );
+                    format!("Memory Limit Exceeded\n{line}")
                 } else {
                     "Memory Limit Exceeded".to_string()
                 }
             }
+            CaseError::OutputLimitExceeded(max) => {
+                if details {
+                    let line = fill(
+                        "Your program printed more than {kib} KiB",
+                        &[("kib", (max / 1024).to_string())],
+                    );
+                    format!("Output Limit Exceeded\n{line}")
+                } else {
+                    "Output Limit Exceeded".to_string()
+                }
+            }
+            CaseError::WallTimeExceeded(secs) => {
+                if details {
+                    let line = fill(
+                        "Your program ran for more than {secs}s of real time",
+                        &[("secs", secs.to_string())],
+                    );
+                    format!("Wall Time Limit Exceeded\n{line}")
+                } else {
+                    "Wall Time Limit Exceeded".to_string()
+                }
+            }
             CaseError::HardTimeLimitExceeded => "Hard Time Limit Exceeded".to_string(),
             CaseError::Judge(_) => "Judge Error".to_string(),
             CaseError::Cancelled => "Run Cancelled".to_string(),
         }
     }
 }
+
+/// What a participant was probably trying to do when a syscall was blocked, without naming it
+fn syscall_hint(syscall: &str) -> &'static str {
+    match syscall {
+        "fork" | "vfork" | "clone" | "clone3" | "execveat" => {
+            "Your program tried to start a new process, which isn't allowed"
+        }
+        "accept" | "accept4" | "sendto" | "sendmsg" | "recvmsg" | "socket" | "connect" | "bind"
+        | "listen" | "getsockname" | "getsockopt" | "shutdown" => {
+            "Your program tried to use the network, which isn't allowed"
+        }
+        "ptrace" | "process_vm_writev" | "kcmp" | "pidfd_open" | "pidfd_getfd" => {
+            "Your program tried to inspect another process, which isn't allowed"
+        }
+        "mount" | "umount2" | "chroot" | "pivot_root" | "unshare" | "setns" | "setuid"
+        | "setgid" | "setresuid" | "setresgid" | "reboot" => {
+            "Your program tried to change the judge's environment, which isn't allowed"
+        }
+        _ => "Your program used an operating system feature the judge doesn't allow",
+    }
+}
@@ -3,10 +3,11 @@ use std::{
     future::Future,
     path::{Path, PathBuf},
     process::Stdio,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::bail;
+use base64::{prelude::BASE64_STANDARD, Engine};
 use nix::{errno::Errno, sys::signal, unistd::Pid};
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
@@ -17,28 +18,39 @@ use tokio_util::sync::CancellationToken;
 
 use crate::{
     error::prelude::*,
-    problems::TestCase,
+    problems::{Comparison, TestCase},
     run::config::{CommandInfo, LanguageRunnerInfo},
 };
 
 use super::{
+    forensics::{self, CrashBundle, StderrTail},
     isolation::{
         self,
         id_map::{map_uid_gid, MapInfo},
         CGroup, CGroupStats, IsolationConfig, LimitConfig,
     },
-    CaseError, CaseResult, CmdResult, InitialWorkerInfo, ServiceMessage, WorkerMessage,
+    Artifact, CaseError, CaseResult, CmdOutput, CmdResult, InitialWorkerInfo, ServiceMessage,
+    WorkerMessage,
 };
 
 pub struct Worker {
+    id: u64,
+    diag: String,
     tmp_dir: PathBuf,
     child: Child,
     sub_child_pid: Option<Pid>,
-    cgroup: CGroup,
+    // None when running with reduced enforcement, see `IsolationConfig::reduced_enforcement`
+    cgroup: Option<CGroup>,
     last_stat: CGroupStats,
     // CPU time, memory usage
     limits: LimitConfig,
     soft_limits: (u64, u64),
+    // Whether participants get hints about blocked syscalls
+    syscall_hints: bool,
+    // Real time limit for running the program, when the problem sets one
+    wall_time: Option<u64>,
+    // How output is compared for cases that don't pick a mode
+    comparison: Comparison,
     shutdown: CancellationToken,
     compile_cmd: Option<CommandInfo>,
     run_cmd: CommandInfo,
@@ -46,8 +58,20 @@ pub struct Worker {
     env: HashMap<String, String>,
     stdout: BufReader<ChildStdout>,
     pizzaz: u64,
+    // The end of the worker's stderr and the last messages each way, for crash bundles
+    stderr: StderrTail,
+    last_sent: Option<String>,
+    last_received: Option<String>,
+    crash_reports_dir: PathBuf,
+    output_stream: Option<OutputStream>,
 }
 
+/// Gets the output of commands as they write it, see [`Worker::stream_output`]
+pub type OutputStream = Box<dyn Fn(&str) + Send + Sync>;
+
+/// Most a worker's files can add up to for a snapshot
+const MAX_SNAPSHOT_BYTES: u64 = 1024 * 1024 * 64; // 64 MiB
+
 enum WaitForResult<T> {
     Ok(T),
     Cancelled,
@@ -62,7 +86,7 @@ impl Worker {
             .env_clear()
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
+            .stderr(Stdio::piped())
             .current_dir(tmp_dir);
         Ok(cmd)
     }
@@ -105,17 +129,22 @@ impl Worker {
 
         let name = Self::make_temp_name(format!("wcpc_worker_{}", id).as_str())?;
 
-        let root_cgroup = iso
-            .cgroups
-            .as_ref()
-            .map(|(r, _)| r)
-            .context("No cgroup root")?;
-        let mut cgroup = root_cgroup.create_child(&name, true).await?;
-        cgroup.apply_hard_limits(&iso.limits).await?;
-        cgroup.shutdown_config = Some((
-            iso.limits.shutdown_retry_interval,
-            iso.limits.shutdown_retries,
-        ));
+        let cgroup = if iso.reduced_enforcement {
+            None
+        } else {
+            let root_cgroup = iso
+                .cgroups
+                .as_ref()
+                .map(|(r, _)| r)
+                .context("No cgroup root")?;
+            let mut cgroup = root_cgroup.create_child(&name, true).await?;
+            cgroup.apply_hard_limits(&iso.limits).await?;
+            cgroup.shutdown_config = Some((
+                iso.limits.shutdown_retry_interval,
+                iso.limits.shutdown_retries,
+            ));
+            Some(cgroup)
+        };
 
         let tmp_parent = iso.workers_parent.as_deref();
         let tmp_dir = Self::make_temp(tmp_parent, &name)
@@ -129,8 +158,11 @@ impl Worker {
         let stdin = child.stdin.take().context("Couldn't take child stdin")?;
         let stdout = child.stdout.take().context("Couldn't take child stdout")?;
         let stdout_reader = BufReader::new(stdout);
+        let stderr = child.stderr.take().context("Couldn't take child stderr")?;
 
         let mut worker = Self {
+            id,
+            diag: diag.to_string(),
             tmp_dir,
             compile_cmd: run.compile_cmd.clone(),
             run_cmd: run.run_cmd.clone(),
@@ -141,10 +173,18 @@ impl Worker {
             cgroup,
             limits: iso.limits.clone(),
             soft_limits,
+            syscall_hints: iso.seccomp().hints(),
+            wall_time: None,
+            comparison: Comparison::default(),
             pizzaz,
             last_stat: CGroupStats::default(),
             stdin,
             stdout: stdout_reader,
+            stderr: forensics::tail_stderr(stderr),
+            last_sent: None,
+            last_received: None,
+            crash_reports_dir: forensics::crash_reports_dir(&iso),
+            output_stream: None,
         };
 
         let res = worker.init(program, diag, iso, run, map_info).await;
@@ -166,10 +206,12 @@ impl Worker {
         map_info: MapInfo,
     ) -> Result {
         let pid = self.child.id().context("Worker process has no PID")?;
-        self.cgroup
-            .move_pid(pid as i32)
-            .await
-            .context("Couldn't move PID to cgroup")?;
+        if let Some(cgroup) = &self.cgroup {
+            cgroup
+                .move_pid(pid as i32)
+                .await
+                .context("Couldn't move PID to cgroup")?;
+        }
 
         let msg = ServiceMessage::InitialInfo(InitialWorkerInfo {
             diagnostic_info: diag.to_string(),
@@ -203,32 +245,142 @@ impl Worker {
         }
     }
 
-    pub async fn compile(&mut self) -> CaseResult {
+    /// Set the problem's wall time limit for runs, only used when it's stricter than the
+    /// hard timeout in the isolation config
+    pub fn set_wall_time(&mut self, wall_time: Option<u64>) {
+        self.wall_time = wall_time;
+    }
+
+    /// Set the problem's comparison settings for judging cases
+    pub fn set_comparison(&mut self, comparison: Comparison) {
+        self.comparison = comparison;
+    }
+
+    /// Pass the output of the commands run after this to `stream` as it's written, on top of
+    /// returning all of it once they finish, for showing progress on long compiles and runs
+    pub fn stream_output(&mut self, stream: Option<OutputStream>) {
+        self.output_stream = stream;
+    }
+
+    /// Swap in a new program for a worker that was started ahead of time or already ran one,
+    /// anything the last program left behind is removed first
+    pub async fn load_program(
+        &mut self,
+        program: &str,
+        file_name: &str,
+        soft_limits: (u64, u64),
+        shutdown: CancellationToken,
+    ) -> Result {
+        self.shutdown = shutdown;
+        self.soft_limits = soft_limits;
+        let msg = ServiceMessage::LoadProgram(file_name.to_string(), program.to_string());
+        self.send_message(msg).await?;
+        let msg = self.wait_for_new_message(None).await?;
+        if let WorkerMessage::Ready = msg {
+            Ok(())
+        } else {
+            bail!("Unexpected worker response: {:?}", msg);
+        }
+    }
+
+    /// Get files the compile command produced, `None` if any are missing or they're over `max_bytes`
+    pub async fn read_artifacts(
+        &mut self,
+        names: Vec<String>,
+        max_bytes: u64,
+    ) -> Result<Option<Vec<Artifact>>> {
+        self.send_message(ServiceMessage::ReadArtifacts(names, max_bytes))
+            .await?;
+        let msg = self.wait_for_new_message(None).await?;
+        if let WorkerMessage::Artifacts(artifacts) = msg {
+            Ok(artifacts)
+        } else {
+            bail!("Unexpected worker response: {:?}", msg);
+        }
+    }
+
+    /// Link cached artifacts into the worker's directory, they need to be bind mounted already
+    pub async fn link_artifacts(&mut self, paths: Vec<PathBuf>) -> Result {
+        self.send_message(ServiceMessage::LinkArtifacts(paths))
+            .await?;
+        let msg = self.wait_for_new_message(None).await?;
+        if let WorkerMessage::Ready = msg {
+            Ok(())
+        } else {
+            bail!("Unexpected worker response: {:?}", msg);
+        }
+    }
+
+    /// Save a snapshot of the worker's directory for admins, `None` if it's too big to save.
+    /// The directory is a tmpfs only the worker can see, so the worker has to tar it up itself
+    pub async fn snapshot(&mut self, user_id: i64, job_id: u64) -> Result<Option<PathBuf>> {
+        self.send_message(ServiceMessage::Snapshot(MAX_SNAPSHOT_BYTES))
+            .await?;
+        // Compressing a big directory can take longer than the internal timeout
+        let timeout = Duration::from_secs(self.limits.hard_timeout_user_secs);
+        let msg = self.wait_for_new_message(Some(timeout)).await?;
+        let WorkerMessage::Snapshot(tarball) = msg else {
+            bail!("Unexpected worker response: {:?}", msg);
+        };
+        let Some(tarball) = tarball else {
+            return Ok(None);
+        };
+        let tarball = BASE64_STANDARD
+            .decode(tarball)
+            .context("Couldn't decode snapshot")?;
+        forensics::save_snapshot(&self.crash_reports_dir, user_id, job_id, tarball)
+            .await
+            .map(Some)
+    }
+
+    /// Whether the worker process is still around, it won't be after being killed for a limit
+    pub fn is_alive(&self) -> bool {
+        self.child.id().is_some()
+    }
+
+    /// Compile the program if the language needs it, returns what the compiler printed
+    pub async fn compile(&mut self) -> CaseResult<String> {
         if let Some(cmd) = self.compile_cmd.clone() {
-            self.exec_cmd(cmd, None, false)
-                .await
-                .map_err(|e| match e {
-                    CaseError::Runtime(failure) => CaseError::Compilation(failure),
-                    e => e,
-                })
-                .map(|_| ())?;
+            let output = self.exec_cmd(cmd, None, false).await.map_err(|e| match e {
+                CaseError::Runtime(failure) => CaseError::Compilation(failure),
+                CaseError::SyscallBlocked {
+                    syscall, output, ..
+                } => {
+                    // Compilers hitting the filter usually means `seccomp.allowed_calls` needs it
+                    warn!("Compile command was blocked from using syscall {}", syscall);
+                    CaseError::Compilation(output)
+                }
+                e => e,
+            })?;
+            return Ok(format!("{}{}", output.stdout, output.stderr));
         }
-        Ok(())
+        Ok(String::new())
     }
 
     pub async fn run_cmd(&mut self, stdin: Option<&str>) -> CaseResult<String> {
-        self.cgroup
-            .apply_soft_limits(self.soft_limits.0, self.soft_limits.1 * 1024 * 1024)
-            .await?;
+        let (cpu_secs, memory_bytes) = (self.soft_limits.0, self.soft_limits.1 * 1024 * 1024);
+        if let Some(cgroup) = &self.cgroup {
+            cgroup.apply_soft_limits(cpu_secs, memory_bytes).await?;
+        } else {
+            self.send_message(ServiceMessage::SetRunLimits(cpu_secs, memory_bytes))
+                .await?;
+            let msg = self.wait_for_new_message(None).await?;
+            if !matches!(msg, WorkerMessage::Ready) {
+                return Err(anyhow!("Unexpected worker response: {:?}", msg).into());
+            }
+        }
         // Sleep for a bit of pizzaz
         tokio::time::sleep(Duration::from_millis(self.pizzaz)).await;
         self.exec_cmd(self.run_cmd.clone(), stdin.map(|s| s.to_string()), true)
             .await
+            .map(|output| output.stdout)
     }
 
     pub async fn run_case(&mut self, case: &TestCase) -> CaseResult<String> {
         self.run_cmd(Some(&case.stdin)).await.and_then(|output| {
-            let correct = case.check_output(&output).map_err(CaseError::Judge)?;
+            let correct = case
+                .check_output(&output, &self.comparison)
+                .map_err(CaseError::Judge)?;
             if correct {
                 Ok(output)
             } else {
@@ -253,7 +405,7 @@ impl Worker {
         cmd: CommandInfo,
         stdin: Option<String>,
         track_stats: bool,
-    ) -> CaseResult<String> {
+    ) -> CaseResult<CmdOutput> {
         let res = self._exec_cmd(cmd, stdin, track_stats).await;
         match res {
             Err(e) if e.should_kill_worker() => {
@@ -270,26 +422,47 @@ impl Worker {
         cmd: CommandInfo,
         stdin: Option<String>,
         track_stats: bool,
-    ) -> CaseResult<String> {
+    ) -> CaseResult<CmdOutput> {
+        if self.output_stream.is_some() {
+            self.send_message(ServiceMessage::StreamNextOutput).await?;
+            let msg = self.wait_for_new_message(None).await?;
+            if !matches!(msg, WorkerMessage::Ready) {
+                return Err(anyhow!("Unexpected worker response: {:?}", msg).into());
+            }
+        }
         let msg = ServiceMessage::RunCmd(cmd.clone(), stdin, self.env.clone());
 
-        if track_stats {
+        // Without a cgroup there's nothing to poll, the rlimits stop the program instead
+        let poll_stats = track_stats && self.cgroup.is_some();
+        if poll_stats {
             self.update_stats().await?;
         }
+        let rlimited = self.cgroup.is_none();
 
         // Clone needed here due to us holding a mutable reference to self while also
         // needing an immutable reference to self.cgroup to check stats during program
         // runtime
-        let mut cgroup = self.cgroup.clone();
-        cgroup.ephemeral = false; // Don't delete cgroup on drop
+        let cgroup = self.cgroup.clone().map(|mut cgroup| {
+            cgroup.ephemeral = false; // Don't delete cgroup on drop
+            cgroup
+        });
         let base_stats = self.last_stat;
         let cpu_limit = self.soft_limits.0;
+        let max_output = self.limits.max_output_bytes;
+        let syscall_hints = self.syscall_hints;
         let shutdown = self.shutdown.clone();
 
         self.send_message(msg).await?;
 
-        let timeout = Duration::from_secs(self.limits.hard_timeout_user_secs);
-        let future = self.wait_for_new_message(Some(timeout));
+        let hard_timeout = self.limits.hard_timeout_user_secs;
+        let (timeout, timed_out) = match self.wall_time {
+            Some(secs) if track_stats && (hard_timeout == 0 || secs < hard_timeout) => {
+                (secs, CaseError::WallTimeExceeded(secs))
+            }
+            _ => (hard_timeout, CaseError::HardTimeLimitExceeded),
+        };
+        let timeout = Duration::from_secs(timeout);
+        let future = self.wait_for_cmd_message(timeout);
 
         tokio::pin!(future);
 
@@ -301,29 +474,45 @@ impl Worker {
                     break match msg {
                         WorkerMessage::CmdComplete(res) => match res {
                             CmdResult::Success(output) => {
-                                if track_stats {
-                                    let diff = cgroup.get_stats().await? - base_stats;
-                                    Self::check_stat_diff(diff, &cgroup, cpu_limit).await
-                                } else {
-                                    Ok(())
-                                }.map(|_| output.stdout)
+                                match &cgroup {
+                                    Some(cgroup) if poll_stats => {
+                                        let diff = cgroup.get_stats().await? - base_stats;
+                                        Self::check_stat_diff(diff, cgroup, cpu_limit).await
+                                    }
+                                    _ => Ok(()),
+                                }.map(|_| output)
+                            },
+                            // The rlimit doesn't say how much time was used, only that it was too much
+                            CmdResult::Failure(failure)
+                                if rlimited && failure.signal() == Some(signal::Signal::SIGXCPU as i32) =>
+                            {
+                                Err(CaseError::CpuTimeExceeded(cpu_limit * 1_000_000))
                             },
                             CmdResult::Failure(failure) => Err(CaseError::Runtime(failure.to_string())),
+                            CmdResult::SyscallBlocked(failure, syscall) => Err(CaseError::SyscallBlocked {
+                                syscall,
+                                output: failure.to_string(),
+                                hint: syscall_hints,
+                            }),
+                            CmdResult::OutputLimitExceeded => Err(CaseError::OutputLimitExceeded(max_output)),
                         },
                         WorkerMessage::Cancelled => Err(CaseError::Cancelled),
-                        WorkerMessage::TimedOut => Err(CaseError::HardTimeLimitExceeded),
+                        WorkerMessage::TimedOut => Err(timed_out),
                         _ => Err(anyhow!("Unexpected worker response: {:?}", msg).into()),
                     }
                 }
                 // This branch cannot return from the function with an error, as it would
                 // result in the worker future never having a shutdown signal sent
                 // meaning it could hang indefinitely
-                _ = tokio::time::sleep(Duration::from_millis(100)), if track_stats => {
+                _ = tokio::time::sleep(Duration::from_millis(100)), if poll_stats => {
+                    let Some(cgroup) = &cgroup else {
+                        continue;
+                    };
                     let res = cgroup.get_stats().await;
                     match res {
                         Ok(stats) => {
                             let diff = stats - base_stats;
-                            if let Err(e) = Self::check_stat_diff(diff, &cgroup, cpu_limit).await {
+                            if let Err(e) = Self::check_stat_diff(diff, cgroup, cpu_limit).await {
                                 break Err(e);
                             }
                         },
@@ -346,12 +535,37 @@ impl Worker {
 
     async fn send_message(&mut self, msg: ServiceMessage) -> Result {
         let msg = format!("{}\n", msg.serialize()?);
+        self.last_sent = Some(forensics::truncate_message(&msg));
         self.stdin
             .write_all(msg.as_bytes())
             .await
             .context("Couldn't write message to worker")
     }
 
+    /// Wait for a command to finish, passing on any output it streams along the way.
+    /// The timeout is for the whole command no matter how much it streams.
+    async fn wait_for_cmd_message(&mut self, timeout: Duration) -> Result<WorkerMessage> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // A timeout of 0 means there isn't one, so the last moments can't be rounded down to it
+            let remaining = if timeout.is_zero() {
+                timeout
+            } else {
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .max(Duration::from_millis(1))
+            };
+            match self.wait_for_new_message(Some(remaining)).await? {
+                WorkerMessage::OutputChunk(chunk) => {
+                    if let Some(stream) = &self.output_stream {
+                        stream(&chunk);
+                    }
+                }
+                msg => return Ok(msg),
+            }
+        }
+    }
+
     async fn wait_for_new_message(&mut self, timeout: Option<Duration>) -> Result<WorkerMessage> {
         let mut buf = String::new();
         let shutdown_rx = self.shutdown.clone();
@@ -363,11 +577,24 @@ impl Worker {
 
         match res {
             WaitForResult::Ok(res) => {
-                res.context("Couldn't read worker message")?;
-                let msg =
-                    serde_json::from_str(&buf).context("Couldn't deserialize worker message")?;
+                let msg = match res {
+                    Ok(0) => Err(anyhow!("Worker closed its output")),
+                    Ok(_) => serde_json::from_str::<WorkerMessage>(&buf)
+                        .context("Couldn't deserialize worker message"),
+                    Err(why) => Err(why).context("Couldn't read worker message"),
+                };
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(why) => {
+                        self.save_crash_bundle(&format!("{why:?}")).await;
+                        return Err(why);
+                    }
+                };
+                self.last_received = Some(forensics::truncate_message(&buf));
                 if let WorkerMessage::InternalError(why) = msg {
                     self.wait_child().await?;
+                    self.save_crash_bundle(&format!("Worker internal error: {why}"))
+                        .await;
                     bail!("Worker internal error: {}", why);
                 } else if msg.is_internal() {
                     bail!("Unexpected internal message: {:?}", msg);
@@ -380,6 +607,52 @@ impl Worker {
         }
     }
 
+    /// Save what there is to know about the worker dying for admins, failing to only logs since
+    /// the run is already failing with a judge error
+    async fn save_crash_bundle(&mut self, reason: &str) {
+        // Let the last of stderr through, it's usually where the worker said what went wrong
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let exit_status = match self.child.try_wait() {
+            Ok(Some(status)) => status.to_string(),
+            Ok(None) => "Still running".to_string(),
+            Err(why) => format!("Unknown: {why}"),
+        };
+        let cgroup_stats = match &self.cgroup {
+            Some(cgroup) => match cgroup.get_stats().await {
+                Ok(stats) => {
+                    let peak = cgroup
+                        .get_memory_peak()
+                        .await
+                        .map_or_else(|why| format!("unknown ({why})"), |b| format!("{b} bytes"));
+                    format!("{stats}\nPeak memory: {peak}\n")
+                }
+                Err(why) => format!("Couldn't read cgroup stats: {why:?}\n"),
+            },
+            None => "No cgroup, the worker ran with reduced enforcement\n".to_string(),
+        };
+        let bundle = CrashBundle {
+            worker_id: self.id,
+            diagnostic_info: self.diag.clone(),
+            reason: reason.to_string(),
+            exit_status,
+            stderr: self.stderr.lock().unwrap().iter().cloned().collect(),
+            last_service_message: self.last_sent.clone(),
+            last_worker_message: self.last_received.clone(),
+            cgroup_stats,
+        };
+        match bundle.save(&self.crash_reports_dir).await {
+            Ok(path) => warn!(
+                "Worker {} died, saved a crash report to {}",
+                self.id,
+                path.display()
+            ),
+            Err(why) => error!(
+                "Couldn't save crash report for worker {}: {:?}",
+                self.id, why
+            ),
+        }
+    }
+
     async fn wait_child(&mut self) -> Result {
         let shutdown_rx = self.shutdown.clone();
         let timeout = Duration::from_secs(self.limits.hard_timeout_internal_secs);
@@ -405,12 +678,17 @@ impl Worker {
         select! {
             res = future => WaitForResult::Ok(res),
             _ = shutdown.cancelled() => WaitForResult::Cancelled,
-            _ = tokio::time::sleep(timeout), if timeout.as_secs() != 0 => WaitForResult::HardTimeout,
+            _ = tokio::time::sleep(timeout), if !timeout.is_zero() => WaitForResult::HardTimeout,
         }
     }
 
     async fn update_stats(&mut self) -> Result<CGroupStats> {
-        let new_stat = self.cgroup.get_stats().await?;
+        let new_stat = self
+            .cgroup
+            .as_ref()
+            .context("Worker has no cgroup")?
+            .get_stats()
+            .await?;
         let diff = new_stat - self.last_stat;
         self.last_stat = new_stat;
         Ok(diff)
@@ -466,7 +744,8 @@ impl Worker {
             nix::sys::wait::waitpid(pid, None).context("Couldn't wait for worker process")?;
         }
         self.cgroup
-            .shutdown_sync()
+            .as_ref()
+            .map_or(Ok(()), CGroup::shutdown_sync)
             .context("Couldn't shutdown cgroup")
     }
 }
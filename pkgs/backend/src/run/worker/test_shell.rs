@@ -47,7 +47,11 @@ async fn start(conf: &RunConfig) -> Result {
         file_name: ".dummy".to_string(),
         compile_cmd: None,
         run_cmd: run_cmd_info,
+        version_cmd: None,
         env: [("PATH".to_string(), path)].into_iter().collect(),
+        artifacts: vec![],
+        time_multiplier: None,
+        memory_multiplier: None,
     };
 
     let mut iso = conf.isolation.clone();
@@ -125,6 +129,12 @@ fn print_output(res: CaseResult<String>) {
         Err(CaseError::HardTimeLimitExceeded) => {
             println!("!! Time Limit Exceeded !!");
         }
+        Err(CaseError::WallTimeExceeded(secs)) => {
+            println!("!! Wall Time Limit Exceeded ({secs}s) !!");
+        }
+        Err(CaseError::OutputLimitExceeded(max)) => {
+            println!("!! Output Limit Exceeded ({max} bytes) !!");
+        }
         Err(other) => {
             println!("!! You shouldn't be getting this !!");
             println!("!! {other:?} !!");
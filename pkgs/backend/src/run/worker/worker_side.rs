@@ -1,13 +1,31 @@
 use std::{
-    io::Write,
-    process::{Command, Stdio},
+    io::{ErrorKind, Read, Write},
+    os::unix::{fs::PermissionsExt, process::CommandExt},
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+    sync::Mutex,
+    thread::JoinHandle,
 };
 
+use base64::{prelude::BASE64_STANDARD, Engine};
 use log::{Metadata, Record};
+use nix::{
+    sys::{
+        resource::{setrlimit, Resource},
+        signal::{kill, Signal},
+    },
+    unistd::Pid,
+};
+
+use crate::{error::prelude::*, tar::TarWriter, wait_for_msg};
 
-use crate::{error::prelude::*, wait_for_msg};
+use super::{isolation::seccomp, Artifact, CmdResult, ServiceMessage, WorkerMessage};
 
-use super::{ServiceMessage, WorkerMessage};
+/// Most of a command's output read at once, and so the most in each streamed chunk
+const OUTPUT_CHUNK_BYTES: usize = 4096;
+
+/// First syscall seccomp blocked while running the current command
+static BLOCKED_SYSCALL: Mutex<Option<i32>> = Mutex::new(None);
 
 pub fn run_from_child() {
     WorkerLogger::setup();
@@ -26,10 +44,24 @@ fn _run_from_child() -> Result {
 
     info!("{}", init.diagnostic_info);
 
-    super::isolation::isolate(&init.isolation_config, &dir).context("Couldn't isolate process")?;
+    let listener = super::isolation::isolate(&init.isolation_config, &dir)
+        .context("Couldn't isolate process")?;
+
+    if let Some(listener) = listener {
+        let config = init.isolation_config.seccomp().clone();
+        std::thread::spawn(move || {
+            seccomp::handle_violations(listener, &config, |nr| {
+                BLOCKED_SYSCALL.lock().unwrap().get_or_insert(nr);
+            })
+        });
+    }
 
     std::fs::write(&init.file_name, &init.program).context("Couldn't write program to file")?;
 
+    let max_output = init.isolation_config.limits.max_output_bytes;
+    let mut run_limits = None;
+    let mut stream_output = false;
+
     info!("Worker Started");
 
     WorkerMessage::Ready.send()?;
@@ -43,7 +75,34 @@ fn _run_from_child() -> Result {
                 } else {
                     Stdio::null()
                 });
-                run_cmd(cmd, stdin)?;
+                if let Some((cpu_secs, memory_bytes)) = run_limits.take() {
+                    limit_cmd(&mut cmd, cpu_secs, memory_bytes);
+                }
+                run_cmd(cmd, stdin, max_output, std::mem::take(&mut stream_output))?;
+            }
+            ServiceMessage::SetRunLimits(cpu_secs, memory_bytes) => {
+                run_limits = Some((cpu_secs, memory_bytes));
+                WorkerMessage::Ready.send()?;
+            }
+            ServiceMessage::StreamNextOutput => {
+                stream_output = true;
+                WorkerMessage::Ready.send()?;
+            }
+            ServiceMessage::LoadProgram(file_name, program) => {
+                clear_dir().context("Couldn't clear worker directory")?;
+                std::fs::write(&file_name, &program).context("Couldn't write program to file")?;
+                WorkerMessage::Ready.send()?;
+            }
+            ServiceMessage::ReadArtifacts(names, max_bytes) => {
+                WorkerMessage::Artifacts(read_artifacts(&names, max_bytes)).send()?;
+            }
+            ServiceMessage::Snapshot(max_bytes) => {
+                let tarball = snapshot(max_bytes).context("Couldn't snapshot worker directory")?;
+                WorkerMessage::Snapshot(tarball).send()?;
+            }
+            ServiceMessage::LinkArtifacts(paths) => {
+                link_artifacts(&paths).context("Couldn't link cached artifacts")?;
+                WorkerMessage::Ready.send()?;
             }
             ServiceMessage::Stop => {
                 info!("Stopping Worker");
@@ -58,23 +117,194 @@ fn _run_from_child() -> Result {
     Ok(())
 }
 
-fn run_cmd(mut cmd: Command, stdin: Option<String>) -> Result {
+/// Remove everything a previous program left in the working directory
+fn clear_dir() -> Result {
+    for entry in std::fs::read_dir(".").context("Couldn't read directory")? {
+        let entry = entry.context("Couldn't read directory entry")?;
+        let path = entry.path();
+        let res = if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        res.with_context(|| format!("Couldn't remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn read_artifacts(names: &[String], max_bytes: u64) -> Option<Vec<Artifact>> {
+    let mut total = 0;
+    let mut artifacts = Vec::with_capacity(names.len());
+    for name in names {
+        let metadata = std::fs::metadata(name).ok().filter(|m| m.is_file())?;
+        total += metadata.len();
+        if total > max_bytes {
+            debug!("Artifacts are over {max_bytes} bytes, not caching");
+            return None;
+        }
+        let contents = std::fs::read(name).ok()?;
+        artifacts.push(Artifact {
+            name: name.clone(),
+            contents: BASE64_STANDARD.encode(contents),
+            executable: metadata.permissions().mode() & 0o111 != 0,
+        });
+    }
+    Some(artifacts)
+}
+
+/// Everything in the working directory as a gzipped tarball, `None` if the files are over
+/// `max_bytes` in total
+fn snapshot(max_bytes: u64) -> Result<Option<String>> {
+    let mut tar = TarWriter::new(chrono::offset::Utc::now().naive_utc());
+    let mut total = 0;
+    let mut dirs = vec![PathBuf::from(".")];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir).context("Couldn't read directory")? {
+            let entry = entry.context("Couldn't read directory entry")?;
+            let path = entry.path();
+            let name = path.strip_prefix(".").unwrap_or(&path).to_string_lossy();
+            // Doesn't follow symlinks, cached artifacts are links into a bind mount
+            let metadata = entry.metadata().context("Couldn't read metadata")?;
+            if metadata.is_symlink() {
+                let target = std::fs::read_link(&path).context("Couldn't read link")?;
+                tar.add_symlink(&name, &target.to_string_lossy())?;
+            } else if metadata.is_dir() {
+                tar.add_dir(&name)?;
+                dirs.push(path.clone());
+            } else if metadata.is_file() {
+                total += metadata.len();
+                if total > max_bytes {
+                    debug!("Directory is over {max_bytes} bytes, not snapshotting");
+                    return Ok(None);
+                }
+                let contents = std::fs::read(&path)
+                    .with_context(|| format!("Couldn't read {}", path.display()))?;
+                tar.add_file(&name, &contents, metadata.permissions().mode())?;
+            }
+        }
+    }
+    Ok(Some(BASE64_STANDARD.encode(tar.finish()?)))
+}
+
+fn link_artifacts(paths: &[PathBuf]) -> Result {
+    for path in paths {
+        let name = path.file_name().context("Artifact has no file name")?;
+        std::os::unix::fs::symlink(path, name)
+            .with_context(|| format!("Couldn't link {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Limit a command's CPU time and address space with rlimits, going over the CPU time sends it
+/// `SIGXCPU` and then `SIGKILL` a second later. Only used when the worker has no cgroup
+fn limit_cmd(cmd: &mut Command, cpu_secs: u64, memory_bytes: u64) {
+    // Safety: setrlimit is async-signal-safe, and nothing is allocated after the fork
+    unsafe {
+        cmd.pre_exec(move || {
+            setrlimit(Resource::RLIMIT_CPU, cpu_secs, cpu_secs + 1)?;
+            setrlimit(Resource::RLIMIT_AS, memory_bytes, memory_bytes)?;
+            Ok(())
+        });
+    }
+}
+
+/// Read everything from a pipe up to `max` bytes, killing the process and returning `None`
+/// as soon as it writes more than that. When streaming, each chunk is sent to the service as
+/// it's read too.
+fn read_capped(
+    mut pipe: impl Read,
+    max: u64,
+    pid: Pid,
+    stream: bool,
+) -> std::io::Result<Option<Vec<u8>>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; OUTPUT_CHUNK_BYTES];
+    let mut sent = 0;
+    loop {
+        let read = match pipe.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(why) if why.kind() == ErrorKind::Interrupted => continue,
+            Err(why) => return Err(why),
+        };
+        buf.extend_from_slice(&chunk[..read]);
+        if buf.len() as u64 > max {
+            kill(pid, Signal::SIGKILL).ok();
+            return Ok(None);
+        }
+        if stream {
+            // Hold back a character split between reads until the rest of it comes
+            let end = match std::str::from_utf8(&buf[sent..]) {
+                Err(e) if e.error_len().is_none() => sent + e.valid_up_to(),
+                _ => buf.len(),
+            };
+            if end > sent {
+                let text = String::from_utf8_lossy(&buf[sent..end]).to_string();
+                if let Err(why) = WorkerMessage::OutputChunk(text).send() {
+                    warn!("Couldn't stream output: {why:?}");
+                }
+                sent = end;
+            }
+        }
+    }
+    Ok(Some(buf))
+}
+
+fn join_reader(handle: JoinHandle<std::io::Result<Option<Vec<u8>>>>) -> Result<Option<Vec<u8>>> {
+    handle
+        .join()
+        .map_err(|_| anyhow!("Output reader panicked"))?
+        .context("Couldn't read output")
+}
+
+fn run_cmd(mut cmd: Command, stdin: Option<String>, max_output: u64, stream: bool) -> Result {
     debug!("Running command: `{:?}`", cmd);
 
+    BLOCKED_SYSCALL.lock().unwrap().take();
     let mut child = cmd.spawn().context("Couldn't spawn process")?;
+    let pid = Pid::from_raw(child.id() as i32);
+
+    let stdout = child.stdout.take().context("Couldn't open stdout")?;
+    let stderr = child.stderr.take().context("Couldn't open stderr")?;
+    let stdout = std::thread::spawn(move || read_capped(stdout, max_output, pid, stream));
+    let stderr = std::thread::spawn(move || read_capped(stderr, max_output, pid, stream));
 
     if let Some(stdin_s) = stdin {
-        let stdin = child.stdin.as_mut().context("Couldn't open stdin")?;
-        stdin
-            .write_all(stdin_s.as_bytes())
-            .context("Couldn't write to stdin")?;
+        let mut stdin = child.stdin.take().context("Couldn't open stdin")?;
+        match stdin.write_all(stdin_s.as_bytes()) {
+            // It exited or was killed for its output before reading everything
+            Err(why) if why.kind() == ErrorKind::BrokenPipe => {
+                debug!("Process stopped reading stdin early");
+            }
+            res => res.context("Couldn't write to stdin")?,
+        }
     }
 
-    let output = child
-        .wait_with_output()
-        .context("Couldn't wait for process")?;
+    let stdout = join_reader(stdout)?;
+    let stderr = join_reader(stderr)?;
+    let status = child.wait().context("Couldn't wait for process")?;
 
-    WorkerMessage::CmdComplete(output.into()).send()
+    let res = match (stdout, stderr) {
+        (Some(stdout), Some(stderr)) => Output {
+            status,
+            stdout,
+            stderr,
+        }
+        .into(),
+        _ => CmdResult::OutputLimitExceeded,
+    };
+    // The listener records the syscall before stopping the process, so it's set by now
+    let blocked = BLOCKED_SYSCALL.lock().unwrap().take();
+    let res = match (res, blocked) {
+        (CmdResult::Failure(failure), Some(nr)) => {
+            let name = seccomp::syscall_name(nr)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("#{nr}"));
+            CmdResult::SyscallBlocked(failure, name)
+        }
+        (res, _) => res,
+    };
+    WorkerMessage::CmdComplete(res).send()
 }
 
 pub struct WorkerLogger(String);
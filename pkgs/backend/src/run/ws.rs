@@ -5,6 +5,7 @@ use rocket::{
     http::Status,
     State,
 };
+use rocket_db_pools::Pool;
 use rocket_ws::{stream::DuplexStream, WebSocket};
 use serde::Deserialize;
 use tokio::{
@@ -13,11 +14,16 @@ use tokio::{
 };
 
 use crate::{
-    auth::users::{Admin, User},
-    contests::Contest,
-    db::DbConnection,
+    archive::ArchivedProblem,
+    auth::{
+        impersonation::Impersonation,
+        users::{Admin, User},
+    },
+    contests::{BindingVerdict, ClientOrigin, Contest, Participant, SessionBinding},
+    db::{Database, DbConnection, DbPool},
     error::prelude::*,
-    problems::{Problem, TestCase},
+    problems::{Comparison, Problem, TestCase},
+    rate_limit::{self, Bucket, RateLimited, RateLimiterHandle},
     run::{job::JobOperation, manager::ManagerJobRequest},
 };
 
@@ -36,6 +42,22 @@ pub enum WebSocketRequest {
         language: String,
         input: String,
     },
+    /// Run against the problem's sample cases
+    Samples {
+        program: String,
+        language: String,
+    },
+    /// Run against several inputs of the user's own, from the custom invocation page
+    Invoke {
+        program: String,
+        language: String,
+        inputs: Vec<String>,
+    },
+    /// Only compile, to check for errors without running anything
+    Compile {
+        program: String,
+        language: String,
+    },
 }
 
 impl WebSocketRequest {
@@ -43,6 +65,9 @@ impl WebSocketRequest {
         match self {
             Self::Judge { program, .. } => program,
             Self::Test { program, .. } => program,
+            Self::Samples { program, .. } => program,
+            Self::Invoke { program, .. } => program,
+            Self::Compile { program, .. } => program,
         }
     }
 
@@ -50,6 +75,9 @@ impl WebSocketRequest {
         match self {
             Self::Judge { language, .. } => language,
             Self::Test { language, .. } => language,
+            Self::Samples { language, .. } => language,
+            Self::Invoke { language, .. } => language,
+            Self::Compile { language, .. } => language,
         }
     }
 }
@@ -75,13 +103,79 @@ enum LoopRes {
     NoOp,
 }
 
-async fn websocket_loop(
-    mut stream: DuplexStream,
-    manager_handle: ManagerHandle,
+const IMPERSONATING_REASON: &str = "You're viewing as someone else, runs are disabled";
+const ANSWERED_REASON: &str = "This problem takes answers instead of a program";
+
+/// Most inputs a custom invocation can run at once
+pub const MAX_INVOCATION_INPUTS: usize = 10;
+
+/// Why the problem's cooldown or attempt limit stops a submission, see [`Problem::submission_blocked`]
+async fn submission_blocked(pool: &DbPool, problem: &Problem, user_id: i64) -> Option<String> {
+    let blocked = async {
+        let mut conn = pool.get().await.context("Couldn't get db connection")?;
+        problem.submission_blocked(&mut conn, user_id).await
+    };
+    blocked.await.unwrap_or_else(|why| {
+        error!(
+            "Couldn't check submission limits of problem {} for user {}: {:?}",
+            problem.id, user_id, why
+        );
+        Some("Couldn't check how many times you've submitted, try again in a moment".to_string())
+    })
+}
+
+/// Check a submission against the contest's session binding, see [`SessionBinding::check`]
+async fn check_binding(
+    pool: &DbPool,
+    problem: &Problem,
+    user_id: i64,
+    origin: &ClientOrigin,
+) -> BindingVerdict {
+    let verdict = async {
+        let mut conn = pool.get().await.context("Couldn't get db connection")?;
+        SessionBinding::check(&mut conn, problem.contest_id, user_id, origin).await
+    };
+    verdict.await.unwrap_or_else(|why| {
+        error!(
+            "Couldn't check session binding of user {} in contest {}: {:?}",
+            user_id, problem.contest_id, why
+        );
+        BindingVerdict::Blocked(
+            "Couldn't check where you're submitting from, try again in a moment".to_string(),
+        )
+    })
+}
+
+/// What a run websocket is for and what its runs are allowed to do
+struct LoopOptions {
     problem: Problem,
     test_cases: Vec<TestCase>,
     user_id: i64,
+    practice: bool,
+    /// Whether the problem's cooldown and attempt limit apply, judges and practice runs skip them
+    enforce_limits: bool,
+    /// Why runs are turned off for this connection, if they are
+    read_only: Option<&'static str>,
+    /// Where the connection came from, for contests that bind submissions to it
+    origin: Option<ClientOrigin>,
+}
+
+async fn websocket_loop(
+    mut stream: DuplexStream,
+    manager_handle: ManagerHandle,
+    limiter: RateLimiterHandle,
+    db_pool: DbPool,
+    options: LoopOptions,
 ) {
+    let LoopOptions {
+        problem,
+        test_cases,
+        user_id,
+        practice,
+        enforce_limits,
+        read_only,
+        origin,
+    } = options;
     let mut manager = manager_handle.lock().await;
     let mut started_rx = manager.subscribe();
     let shutdown = manager.subscribe_shutdown(&user_id).await;
@@ -96,9 +190,11 @@ async fn websocket_loop(
 
     let mut state_rx: JobStateReceiver = if let Some(rx) = state_rx {
         let r = rx.borrow();
-        let msg = serde_json::to_string(&WebSocketMessage::StateUpdate { state: r.clone() })
-            .map_err(|e| e.to_string())
-            .unwrap();
+        let msg = serde_json::to_string(&WebSocketMessage::StateUpdate {
+            state: r.for_participant(),
+        })
+        .map_err(|e| e.to_string())
+        .unwrap();
         state_msg = Some(msg);
         drop(r);
         rx
@@ -137,19 +233,42 @@ async fn websocket_loop(
                                 if let Ok(request) = serde_json::from_str::<WebSocketRequest>(&raw) {
                                     let op = match &request {
                                         WebSocketRequest::Judge { .. } => JobOperation::Judging(test_cases.clone()),
-                                        WebSocketRequest::Test { input, .. } => JobOperation::Testing(input.to_string())
+                                        WebSocketRequest::Test { input, .. } => JobOperation::Testing(input.to_string()),
+                                        WebSocketRequest::Samples { .. } => JobOperation::Samples(test_cases.iter().filter(|c| c.sample).cloned().collect()),
+                                        WebSocketRequest::Invoke { inputs, .. } => JobOperation::Invoking(inputs.clone()),
+                                        WebSocketRequest::Compile { .. } => JobOperation::Compiling,
                                     };
 
-                                    let job_to_start = ManagerJobRequest {
-                                        user_id,
-                                        problem_id: problem.id,
-                                        contest_id: problem.contest_id,
-                                        program: request.program().to_string(),
-                                        language_key: request.language().to_string(),
-                                        soft_limits: (problem.cpu_time as u64, problem.memory_limit as u64), // `as` is safe due to DB constraint
-                                        op
-                                    };
-                                    LoopRes::JobStart(job_to_start)
+                                    let violation = problem.banned_import_violation(request.language(), request.program());
+
+                                    if !problem.judges_programs() {
+                                        LoopRes::Msg(WebSocketMessage::RunDenied { reason: ANSWERED_REASON.to_string() })
+                                    } else if matches!(&op, JobOperation::Samples(samples) if samples.is_empty()) {
+                                        LoopRes::Msg(WebSocketMessage::RunDenied { reason: "This problem doesn't have any sample cases".to_string() })
+                                    } else if matches!(&op, JobOperation::Invoking(inputs) if inputs.is_empty() || inputs.len() > MAX_INVOCATION_INPUTS) {
+                                        LoopRes::Msg(WebSocketMessage::RunDenied { reason: format!("Invocations can have between 1 and {MAX_INVOCATION_INPUTS} inputs") })
+                                    } else if !problem.allows_language(request.language()) {
+                                        LoopRes::Msg(WebSocketMessage::RunDenied { reason: "That language isn't allowed for this problem".to_string() })
+                                    } else if let Some(reason) = violation.as_ref().filter(|_| !problem.flag_banned_imports) {
+                                        LoopRes::Msg(WebSocketMessage::RunDenied { reason: reason.clone() })
+                                    } else {
+                                        let job_to_start = ManagerJobRequest {
+                                            user_id,
+                                            problem_id: problem.id,
+                                            contest_id: problem.contest_id,
+                                            program: request.program().to_string(),
+                                            language_key: request.language().to_string(),
+                                            soft_limits: (problem.cpu_time as u64, problem.memory_limit as u64), // `as` is safe due to DB constraint
+                                            wall_time: problem.wall_time_limit(),
+                                            network: problem.allow_network,
+                                            parallelism: problem.parallelism as usize, // Same here
+                                            comparison: Comparison::for_problem(&problem),
+                                            op,
+                                            flag: violation,
+                                            practice
+                                        };
+                                        LoopRes::JobStart(job_to_start)
+                                    }
                                 } else {
                                     LoopRes::Msg(WebSocketMessage::Invalid { error: "Invalid request".to_string() })
                                 }
@@ -173,7 +292,7 @@ async fn websocket_loop(
             }
             Ok(()) = state_rx.changed() => {
                 let state = state_rx.borrow();
-                LoopRes::Msg(WebSocketMessage::StateUpdate { state: state.clone() })
+                LoopRes::Msg(WebSocketMessage::StateUpdate { state: state.for_participant() })
             }
             _ = shutdown.cancelled() => {
                 LoopRes::Break
@@ -195,13 +314,68 @@ async fn websocket_loop(
                     error!("Error sending message: {:?}", e);
                 }
             }
-            LoopRes::JobStart(req) => {
-                let mut manager = manager_handle.lock().await;
-                let msg = match manager.request_job(req).await {
-                    Ok(_) => WebSocketMessage::RunStarted,
-                    Err(why) => WebSocketMessage::RunDenied { reason: why },
+            LoopRes::JobStart(mut req) => {
+                let limited = limiter
+                    .lock()
+                    .await
+                    .check(Bucket::RunJob, Some(user_id), None);
+                // Invocations run several inputs at once so they have their own quota too
+                let quota = if limited.is_ok() && matches!(req.op, JobOperation::Invoking(_)) {
+                    limiter
+                        .lock()
+                        .await
+                        .check(Bucket::Invocation, Some(user_id), None)
+                } else {
+                    Ok(())
+                };
+                // Testing against custom input, the samples, invoking or compiling doesn't count as a
+                // submission
+                let limits_apply = enforce_limits
+                    && read_only.is_none()
+                    && limited.is_ok()
+                    && matches!(req.op, JobOperation::Judging(_));
+                let blocked = if limits_apply {
+                    submission_blocked(&db_pool, &problem, user_id).await
+                } else {
+                    None
+                };
+                let binding = match origin
+                    .as_ref()
+                    .filter(|_| limits_apply && blocked.is_none())
+                {
+                    Some(origin) => check_binding(&db_pool, &problem, user_id, origin).await,
+                    None => BindingVerdict::Allowed,
+                };
+                req.flag = binding.flag(req.flag.take());
+                let msg = if let Some(reason) = read_only {
+                    WebSocketMessage::RunDenied {
+                        reason: reason.to_string(),
+                    }
+                } else if let Err(retry_after) = limited {
+                    WebSocketMessage::RunDenied {
+                        reason: format!(
+                            "Too many runs, try again in {} seconds",
+                            rate_limit::retry_after_secs(retry_after)
+                        ),
+                    }
+                } else if let Err(retry_after) = quota {
+                    WebSocketMessage::RunDenied {
+                        reason: format!(
+                            "You've used up your custom invocations for now, try again in {} minutes",
+                            rate_limit::retry_after_secs(retry_after).div_ceil(60)
+                        ),
+                    }
+                } else if let Some(reason) = blocked {
+                    WebSocketMessage::RunDenied { reason }
+                } else if let BindingVerdict::Blocked(reason) = binding {
+                    WebSocketMessage::RunDenied { reason }
+                } else {
+                    let mut manager = manager_handle.lock().await;
+                    match manager.request_job(req).await {
+                        Ok(_) => WebSocketMessage::RunStarted,
+                        Err(why) => WebSocketMessage::RunDenied { reason: why },
+                    }
                 };
-                drop(manager);
                 let msg = serde_json::to_string(&msg)
                     .map_err(|e| e.to_string())
                     .unwrap();
@@ -231,7 +405,7 @@ async fn websocket_loop(
                 state_rx = rx;
                 let state = state_rx.borrow();
                 let msg = serde_json::to_string(&WebSocketMessage::StateUpdate {
-                    state: state.clone(),
+                    state: state.for_participant(),
                 })
                 .map_err(|e| e.to_string())
                 .unwrap();
@@ -249,6 +423,7 @@ async fn websocket_loop(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 #[get("/ws/<contest_id>/<problem_id>")]
 pub async fn ws_channel(
     ws: WebSocket,
@@ -256,21 +431,86 @@ pub async fn ws_channel(
     problem_id: i64,
     user: &User,
     admin: Option<&Admin>,
+    impersonation: Option<&Impersonation>,
     manager: &State<ManagerHandle>,
+    limiter: &State<RateLimiterHandle>,
+    db_pool: &Database,
     mut db: DbConnection,
+    origin: ClientOrigin,
+    _limit: RateLimited<rate_limit::RunConnect>,
 ) -> ResultResponse<rocket_ws::Channel<'static>> {
-    Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
+    let (_, participant, can_edit) =
+        Contest::get_or_404_assert_started(&mut db, contest_id, Some(user), admin).await?;
     let problem = Problem::by_id(&mut db, contest_id, problem_id)
         .await?
         .ok_or(Status::NotFound)?;
 
     let handle = (*manager).clone();
+    let limiter = (*limiter).clone();
+    let db_pool = db_pool.0.clone();
+    let cases = TestCase::get_for_problem(&mut db, problem_id).await?;
+    if !cases.is_empty() {
+        let user_id = user.id;
+        let read_only = if impersonation.is_some() {
+            Some(IMPERSONATING_REASON)
+        } else {
+            participant.as_ref().and_then(Participant::runs_blocked)
+        };
+        Ok(ws.channel(move |stream| {
+            Box::pin(async move {
+                let options = LoopOptions {
+                    problem,
+                    test_cases: cases,
+                    user_id,
+                    practice: false,
+                    enforce_limits: !can_edit,
+                    read_only,
+                    origin: Some(origin),
+                };
+                websocket_loop(stream, handle, limiter, db_pool, options).await;
+                Ok(())
+            })
+        }))
+    } else {
+        Err(Status::NotFound.into())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/ws/archive/<problem_id>")]
+pub async fn archive_ws_channel(
+    ws: WebSocket,
+    problem_id: i64,
+    user: &User,
+    admin: Option<&Admin>,
+    impersonation: Option<&Impersonation>,
+    manager: &State<ManagerHandle>,
+    limiter: &State<RateLimiterHandle>,
+    db_pool: &Database,
+    mut db: DbConnection,
+    _limit: RateLimited<rate_limit::RunConnect>,
+) -> ResultResponse<rocket_ws::Channel<'static>> {
+    let (problem, _) = ArchivedProblem::get_or_404(&mut db, problem_id, Some(user), admin).await?;
+
+    let handle = (*manager).clone();
+    let limiter = (*limiter).clone();
+    let db_pool = db_pool.0.clone();
     let cases = TestCase::get_for_problem(&mut db, problem_id).await?;
     if !cases.is_empty() {
         let user_id = user.id;
+        let read_only = impersonation.is_some().then_some(IMPERSONATING_REASON);
         Ok(ws.channel(move |stream| {
             Box::pin(async move {
-                websocket_loop(stream, handle, problem, cases, user_id).await;
+                let options = LoopOptions {
+                    problem,
+                    test_cases: cases,
+                    user_id,
+                    practice: true,
+                    enforce_limits: false,
+                    read_only,
+                    origin: None,
+                };
+                websocket_loop(stream, handle, limiter, db_pool, options).await;
                 Ok(())
             })
         }))
@@ -0,0 +1,40 @@
+use rocket::{get, post};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    messages::Message,
+    FormResponse, ResultResponse,
+};
+
+use super::Series;
+
+#[get("/<series_id>/delete")]
+pub async fn delete_series_get(
+    series_id: i64,
+    mut db: DbConnection,
+    _token: &CsrfToken,
+    user: &User,
+    _admin: &Admin,
+) -> ResultResponse<Template> {
+    let series = Series::get_or_404(&mut db, series_id).await?;
+    let ctx = context_with_base_authed!(user, series);
+    Ok(Template::render("series/delete", ctx))
+}
+
+#[post("/<series_id>/delete")]
+pub async fn delete_series_post(
+    series_id: i64,
+    mut db: DbConnection,
+    _token: &VerifyCsrfToken,
+    _admin: &Admin,
+) -> FormResponse {
+    let series = Series::get_or_404(&mut db, series_id).await?;
+    series.delete(&mut db).await?;
+    Ok(Message::success("Series deleted").to("/series"))
+}
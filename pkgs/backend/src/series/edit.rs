@@ -0,0 +1,80 @@
+use rocket::{
+    form::{Contextual, Form},
+    get, post,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    template::FormTemplateObject,
+};
+
+use super::{Series, SeriesForm, SeriesFormTemplate};
+
+#[get("/<id>/edit")]
+pub async fn edit_series_get(
+    user: &User,
+    mut db: DbConnection,
+    id: i64,
+    _token: &CsrfToken,
+    _admin: &Admin,
+) -> ResultResponse<Template> {
+    let series = Series::get_or_404(&mut db, id).await?;
+    let contest_ids = series
+        .contests(&mut db)
+        .await?
+        .into_iter()
+        .map(|c| c.id)
+        .collect::<Vec<_>>();
+    let form_template = SeriesFormTemplate {
+        series: Some(&series),
+        contest_ids: &contest_ids,
+    };
+    let all_contests = Contest::list(&mut db).await?;
+    let form = FormTemplateObject::get(form_template);
+    Ok(Template::render(
+        "series/edit",
+        context_with_base_authed!(user, form, all_contests, series),
+    ))
+}
+
+#[post("/<id>/edit", data = "<form>")]
+pub async fn edit_series_post(
+    id: i64,
+    user: &User,
+    form: Form<Contextual<'_, SeriesForm<'_>>>,
+    _token: &VerifyCsrfToken,
+    _admin: &Admin,
+    mut db: DbConnection,
+) -> FormResponse {
+    let mut series = Series::get_or_404(&mut db, id).await?;
+    if let Some(ref value) = form.value {
+        series.name = value.name.to_string();
+        series.description = value
+            .description
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        series.best_n = value.best_n;
+        series.update(&mut db).await?;
+        series.set_contests(&mut db, &value.contest_ids()).await?;
+        Ok(Message::success("Series Updated").to(&format!("/series/{id}")))
+    } else {
+        let form_template = SeriesFormTemplate {
+            series: None,
+            contest_ids: &[],
+        };
+        let all_contests = Contest::list(&mut db).await?;
+        let form = FormTemplateObject::from_rocket_context(form_template, &form.context);
+        let ctx = context_with_base_authed!(user, form, all_contests, series);
+        Err(Template::render("series/edit", ctx).into())
+    }
+}
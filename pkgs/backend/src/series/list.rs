@@ -0,0 +1,22 @@
+use rocket::get;
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base,
+    db::DbConnection,
+    error::prelude::*,
+};
+
+use super::Series;
+
+#[get("/")]
+pub async fn series_list(
+    user: Option<&User>,
+    admin: Option<&Admin>,
+    mut db: DbConnection,
+) -> ResultResponse<Template> {
+    let series = Series::list(&mut db).await?;
+    let ctx = context_with_base!(user, series, is_admin: admin.is_some());
+    Ok(Template::render("series/list", ctx))
+}
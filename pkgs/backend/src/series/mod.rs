@@ -0,0 +1,241 @@
+#![allow(clippy::blocks_in_conditions)] // Needed for the derive of FromForm, rocket is weird
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use rocket::{fairing::AdHoc, form, http::Status, routes, FromForm};
+use serde::Serialize;
+
+use crate::{
+    auth::users::{Admin, User},
+    contests::{Contest, Participant},
+    db::DbPoolConnection,
+    error::prelude::*,
+    template::TemplatedForm,
+};
+
+mod delete;
+mod edit;
+mod list;
+mod new;
+mod view;
+
+/// A group of contests ranked together, e.g. a semester long league
+#[derive(Serialize, Clone)]
+pub struct Series {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    /// Only each participant's best this many contests count towards the standings, `None` adds up every contest
+    pub best_n: Option<i64>,
+    created_at: Option<NaiveDateTime>,
+}
+
+impl Series {
+    pub fn temp(name: String, description: Option<String>, best_n: Option<i64>) -> Self {
+        Self {
+            id: 0,
+            name,
+            description,
+            best_n,
+            created_at: None,
+        }
+    }
+
+    pub async fn list(db: &mut DbPoolConnection) -> Result<Vec<Self>> {
+        sqlx::query_as!(Series, "SELECT * FROM series ORDER BY created_at DESC")
+            .fetch_all(&mut **db)
+            .await
+            .context("Error fetching series")
+    }
+
+    pub async fn list_for_contest(db: &mut DbPoolConnection, contest_id: i64) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Series,
+            "SELECT series.* FROM series JOIN series_contest ON series.id = series_contest.series_id WHERE series_contest.contest_id = ? ORDER BY series.created_at DESC",
+            contest_id
+        )
+        .fetch_all(&mut **db)
+        .await
+        .with_context(|| format!("Error fetching series for contest {contest_id}"))
+    }
+
+    pub async fn get(db: &mut DbPoolConnection, id: i64) -> Result<Option<Self>> {
+        sqlx::query_as!(Series, "SELECT * FROM series WHERE id = ?", id)
+            .fetch_optional(&mut **db)
+            .await
+            .context("Error fetching series")
+    }
+
+    pub async fn get_or_404(db: &mut DbPoolConnection, id: i64) -> ResultResponse<Self> {
+        Self::get(db, id).await?.ok_or(Status::NotFound.into())
+    }
+
+    pub async fn insert(&self, db: &mut DbPoolConnection) -> Result<Self> {
+        sqlx::query_as!(
+            Series,
+            "INSERT INTO series (name, description, best_n) VALUES (?, ?, ?) RETURNING *",
+            self.name,
+            self.description,
+            self.best_n
+        )
+        .fetch_one(&mut **db)
+        .await
+        .context("Error inserting series")
+    }
+
+    pub async fn update(&self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!(
+            "UPDATE series SET name = ?, description = ?, best_n = ? WHERE id = ?",
+            self.name,
+            self.description,
+            self.best_n,
+            self.id
+        )
+        .execute(&mut **db)
+        .await
+        .map(|_| ())
+        .with_context(|| format!("Error updating series with id: {}", self.id))
+    }
+
+    pub async fn delete(self, db: &mut DbPoolConnection) -> Result {
+        sqlx::query!("DELETE FROM series WHERE id = ?", self.id)
+            .execute(&mut **db)
+            .await
+            .map(|_| ())
+            .with_context(|| format!("Error deleting series {}", self.id))
+    }
+
+    /// Member contests in the order they were held
+    pub async fn contests(&self, db: &mut DbPoolConnection) -> Result<Vec<Contest>> {
+        Contest::list_in_series(db, self.id).await
+    }
+
+    /// Member contests the user is allowed to see, private contests are left out for everyone but their members
+    pub async fn contests_visible_to(
+        &self,
+        db: &mut DbPoolConnection,
+        user: Option<&User>,
+        admin: Option<&Admin>,
+    ) -> Result<Vec<Contest>> {
+        let mut visible = Vec::new();
+        for contest in self.contests(db).await? {
+            let participant = if let Some(user) = user {
+                Participant::get(db, contest.id, user.id).await?
+            } else {
+                None
+            };
+            if contest.is_visible_to(participant.as_ref(), admin) {
+                visible.push(contest);
+            }
+        }
+        Ok(visible)
+    }
+
+    pub async fn set_contests(&self, db: &mut DbPoolConnection, contest_ids: &[i64]) -> Result {
+        sqlx::query!("DELETE FROM series_contest WHERE series_id = ?", self.id)
+            .execute(&mut **db)
+            .await
+            .with_context(|| format!("Error clearing contests in series {}", self.id))?;
+        for contest_id in contest_ids {
+            sqlx::query!(
+                "INSERT INTO series_contest (series_id, contest_id) VALUES (?, ?)",
+                self.id,
+                contest_id
+            )
+            .execute(&mut **db)
+            .await
+            .with_context(|| format!("Error adding contest {contest_id} to series {}", self.id))?;
+        }
+        Ok(())
+    }
+}
+
+struct SeriesFormTemplate<'r> {
+    series: Option<&'r Series>,
+    contest_ids: &'r [i64],
+}
+
+impl<'r> TemplatedForm for SeriesFormTemplate<'r> {
+    fn get_defaults(&mut self) -> HashMap<String, String> {
+        let mut map = if let Some(series) = self.series {
+            HashMap::from_iter([
+                ("name".to_string(), series.name.to_string()),
+                (
+                    "description".to_string(),
+                    series.description.clone().unwrap_or_default(),
+                ),
+                (
+                    "best_n".to_string(),
+                    series.best_n.map(|n| n.to_string()).unwrap_or_default(),
+                ),
+            ])
+        } else {
+            HashMap::from_iter([
+                ("name".to_string(), "".to_string()),
+                ("description".to_string(), "".to_string()),
+                ("best_n".to_string(), "".to_string()),
+            ])
+        };
+        for id in self.contest_ids.iter() {
+            map.insert(format!("contests[{id}]"), "true".to_string());
+        }
+        map
+    }
+}
+
+#[inline]
+fn over_0<'e>(best_n: &Option<i64>) -> Result<(), rocket::form::Errors<'e>> {
+    match best_n {
+        Some(n) if *n < 1 => Err(form::Error::validation("Must be at least 1").into()),
+        _ => Ok(()),
+    }
+}
+
+#[inline]
+fn len_under_1000<'r, 'e>(s: &'r Option<&'r str>) -> Result<(), rocket::form::Errors<'e>> {
+    if s.is_some_and(|s| s.len() >= 1000) {
+        Err(form::Error::validation("Must be under 1000 characters").into())
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(FromForm)]
+struct SeriesForm<'r> {
+    #[field(validate = len(1..=100))]
+    name: &'r str,
+    #[field(validate = len_under_1000())]
+    description: Option<&'r str>,
+    #[field(validate = over_0())]
+    best_n: Option<i64>,
+    contests: HashMap<i64, bool>,
+}
+
+impl SeriesForm<'_> {
+    fn contest_ids(&self) -> Vec<i64> {
+        self.contests
+            .iter()
+            .filter(|(_, checked)| **checked)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+}
+
+pub fn stage() -> AdHoc {
+    AdHoc::on_ignite("Series App", |rocket| async {
+        rocket.mount(
+            "/series",
+            routes![
+                list::series_list,
+                new::new_series_get,
+                new::new_series_post,
+                edit::edit_series_get,
+                edit::edit_series_post,
+                delete::delete_series_get,
+                delete::delete_series_post,
+                view::view_series,
+            ],
+        )
+    })
+}
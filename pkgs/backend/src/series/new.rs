@@ -0,0 +1,67 @@
+use rocket::{
+    form::{Contextual, Form},
+    get, post,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        users::{Admin, User},
+    },
+    contests::Contest,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+    template::FormTemplateObject,
+};
+
+use super::{Series, SeriesForm, SeriesFormTemplate};
+
+#[get("/new")]
+pub async fn new_series_get(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let form_template = SeriesFormTemplate {
+        series: None,
+        contest_ids: &[],
+    };
+    let all_contests = Contest::list(&mut db).await?;
+    let form = FormTemplateObject::get(form_template);
+    let ctx = context_with_base_authed!(user, all_contests, form);
+    Ok(Template::render("series/new", ctx))
+}
+
+#[post("/new", data = "<form>")]
+pub async fn new_series_post(
+    mut db: DbConnection,
+    user: &User,
+    _admin: &Admin,
+    _token: &VerifyCsrfToken,
+    form: Form<Contextual<'_, SeriesForm<'_>>>,
+) -> FormResponse {
+    if let Some(ref value) = form.value {
+        let description = value
+            .description
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        let series = Series::temp(value.name.to_string(), description, value.best_n);
+        let series = series.insert(&mut db).await?;
+        series.set_contests(&mut db, &value.contest_ids()).await?;
+        Ok(Message::success("Series Created").to(&format!("/series/{}", series.id)))
+    } else {
+        let form_template = SeriesFormTemplate {
+            series: None,
+            contest_ids: &[],
+        };
+        let all_contests = Contest::list(&mut db).await?;
+        let form = FormTemplateObject::from_rocket_context(form_template, &form.context);
+        let ctx = context_with_base_authed!(user, all_contests, form);
+        Err(Template::render("series/new", ctx).into())
+    }
+}
@@ -0,0 +1,27 @@
+use rocket::{get, State};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::users::{Admin, User},
+    context_with_base,
+    db::DbConnection,
+    error::prelude::*,
+    leaderboard::{series_standings, LeaderboardManagerHandle},
+};
+
+use super::Series;
+
+#[get("/<id>")]
+pub async fn view_series(
+    mut db: DbConnection,
+    leaderboard_manager: &State<LeaderboardManagerHandle>,
+    id: i64,
+    user: Option<&User>,
+    admin: Option<&Admin>,
+) -> ResultResponse<Template> {
+    let series = Series::get_or_404(&mut db, id).await?;
+    let contests = series.contests_visible_to(&mut db, user, admin).await?;
+    let entries = series_standings(&mut db, leaderboard_manager, &contests, series.best_n).await?;
+    let ctx = context_with_base!(user, series, contests, entries, is_admin: admin.is_some());
+    Ok(Template::render("series/view", ctx))
+}
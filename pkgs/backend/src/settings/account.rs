@@ -1,10 +1,26 @@
-use rocket::get;
+use rocket::{get, State};
 use rocket_dyn_templates::Template;
 
-use crate::{auth::users::User, context_with_base_authed};
+use crate::{
+    auth::{
+        password::{PasswordAuthConfig, PasswordCredential},
+        two_factor::TwoFactor,
+        users::User,
+    },
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+};
 
 #[get("/account")]
-pub fn account_get(user: &User) -> Template {
-    let ctx = context_with_base_authed!(user,);
-    Template::render("settings/account", ctx)
+pub async fn account_get(
+    mut db: DbConnection,
+    user: &User,
+    password_auth: &State<PasswordAuthConfig>,
+) -> ResultResponse<Template> {
+    let has_password =
+        password_auth.enabled && PasswordCredential::get(&mut db, user.id).await?.is_some();
+    let has_two_factor = TwoFactor::is_enabled(&mut db, user.id).await?;
+    let ctx = context_with_base_authed!(user, has_password, has_two_factor);
+    Ok(Template::render("settings/account", ctx))
 }
@@ -11,11 +11,13 @@ use rocket_dyn_templates::Template;
 use crate::{
     auth::{
         csrf::CsrfToken,
+        impersonation::NotImpersonating,
         users::{ColorScheme, User},
     },
     context_with_base_authed,
     db::DbConnection,
     error::prelude::*,
+    i18n::Locales,
     messages::Message,
     run::CodeInfo,
     template::{FormTemplateObject, TemplatedForm},
@@ -36,6 +38,10 @@ impl TemplatedForm for ContestFormTemplate<'_> {
                 "default_language".to_string(),
                 self.user.default_language.clone(),
             ),
+            (
+                "locale".to_string(),
+                self.user.locale.clone().unwrap_or_default(),
+            ),
         ])
     }
 }
@@ -44,12 +50,13 @@ impl TemplatedForm for ContestFormTemplate<'_> {
 pub fn contest_settings_get(
     user: &User,
     code_info: &State<CodeInfo>,
+    locales: &State<Locales>,
     _token: &CsrfToken,
 ) -> Template {
     let form_template = ContestFormTemplate { user };
     let form = FormTemplateObject::get(form_template);
     let languages = code_info.run_config.get_languages_for_dropdown();
-    let ctx = context_with_base_authed!(user, form, languages);
+    let ctx = context_with_base_authed!(user, form, languages, locales: locales.available());
     Template::render("settings/contest", ctx)
 }
 
@@ -57,6 +64,8 @@ pub fn contest_settings_get(
 pub struct ContestForm<'r> {
     color_scheme: ColorScheme,
     default_language: &'r str,
+    /// Empty to go by the browser
+    locale: &'r str,
 }
 
 #[post("/contest", data = "<form>")]
@@ -65,7 +74,9 @@ pub async fn contest_settings_post(
     mut form: Form<Contextual<'_, ContestForm<'_>>>,
     mut db: DbConnection,
     _token: &CsrfToken,
+    _read_write: NotImpersonating,
     code_info: &State<CodeInfo>,
+    locales: &State<Locales>,
 ) -> FormResponse {
     let mut user = user.clone();
     let languages = code_info.run_config.get_languages_for_dropdown();
@@ -74,7 +85,12 @@ pub async fn contest_settings_post(
         let color_scheme = &value.color_scheme;
         user.default_language = default_language.to_string();
         user.color_scheme = color_scheme.clone();
-        if !code_info
+        let locale = value.locale.trim();
+        user.locale = (!locale.is_empty()).then(|| locale.to_string());
+        if !locale.is_empty() && !locales.is_available(locale) {
+            let error = rocket::form::Error::validation("Invalid language").with_name("locale");
+            form.context.push_error(error);
+        } else if !code_info
             .run_config
             .languages
             .contains_key(default_language)
@@ -85,9 +101,10 @@ pub async fn contest_settings_post(
             rocket_ctx.push_error(error);
         } else {
             sqlx::query!(
-                "UPDATE user SET default_language = ?, color_scheme = ? WHERE id = ?",
+                "UPDATE user SET default_language = ?, color_scheme = ?, locale = ? WHERE id = ?",
                 user.default_language,
                 user.color_scheme,
+                user.locale,
                 user.id
             )
             .execute(&mut **db)
@@ -100,7 +117,7 @@ pub async fn contest_settings_post(
     let form_template = ContestFormTemplate { user: &user };
     let form = FormTemplateObject::from_rocket_context(form_template, &form.context);
 
-    let ctx = context_with_base_authed!(&user, form, languages);
+    let ctx = context_with_base_authed!(&user, form, languages, locales: locales.available());
 
     Err(Template::render("settings/contest", ctx).into())
 }
@@ -4,6 +4,7 @@ mod account;
 mod contest;
 mod delete;
 mod profile;
+mod two_factor;
 
 pub fn stage() -> AdHoc {
     AdHoc::on_ignite("Settings App", |rocket| async {
@@ -13,6 +14,11 @@ pub fn stage() -> AdHoc {
                 profile::profile_get,
                 profile::profile_post,
                 account::account_get,
+                two_factor::two_factor_get,
+                two_factor::two_factor_qr,
+                two_factor::two_factor_enable,
+                two_factor::two_factor_recovery,
+                two_factor::two_factor_disable,
                 contest::contest_settings_get,
                 contest::contest_settings_post,
                 delete::delete_user_get,
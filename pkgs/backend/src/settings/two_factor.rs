@@ -0,0 +1,127 @@
+use rocket::{
+    form::Form,
+    get,
+    http::{ContentType, Status},
+    post,
+    response::Redirect,
+    State,
+};
+use rocket_dyn_templates::Template;
+
+use crate::{
+    auth::{
+        csrf::{CsrfToken, VerifyCsrfToken},
+        two_factor::{CodeForm, TwoFactor, TwoFactorConfig, SETTINGS_URI},
+        users::{AdminUsers, User},
+    },
+    branding::BrandingConfig,
+    context_with_base_authed,
+    db::DbConnection,
+    error::prelude::*,
+    messages::Message,
+};
+
+#[get("/account/2fa")]
+pub async fn two_factor_get(
+    mut db: DbConnection,
+    user: &User,
+    config: &State<TwoFactorConfig>,
+    admins: &State<AdminUsers>,
+    _token: &CsrfToken,
+) -> ResultResponse<Template> {
+    let two_factor = match TwoFactor::get(&mut db, user.id).await? {
+        Some(two_factor) => two_factor,
+        None => TwoFactor::begin_enrollment(&mut db, user.id).await?,
+    };
+    let enabled = two_factor.enabled;
+    let secret = (!enabled).then_some(two_factor.secret.as_str());
+    let recovery_codes_left = if enabled {
+        two_factor.recovery_codes_left(&mut db).await?
+    } else {
+        0
+    };
+    let required = config.is_required_for(admins, user);
+    let ctx = context_with_base_authed!(user, enabled, secret, recovery_codes_left, required);
+    Ok(Template::render("settings/two_factor", ctx))
+}
+
+#[get("/account/2fa/qr.png")]
+pub async fn two_factor_qr(
+    mut db: DbConnection,
+    user: &User,
+    branding: &State<BrandingConfig>,
+) -> ResultResponse<(ContentType, Vec<u8>)> {
+    let two_factor = TwoFactor::get(&mut db, user.id)
+        .await?
+        .filter(|t| !t.enabled)
+        .ok_or(Status::NotFound)?;
+    let png = two_factor.qr_png(&branding.name, user)?;
+    Ok((ContentType::PNG, png))
+}
+
+#[post("/account/2fa/enable", data = "<form>")]
+pub async fn two_factor_enable(
+    mut db: DbConnection,
+    user: &User,
+    form: Form<CodeForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    let mut two_factor = TwoFactor::get(&mut db, user.id)
+        .await?
+        .filter(|t| !t.enabled)
+        .ok_or(Status::NotFound)?;
+    if !two_factor.verify(&mut db, form.code).await? {
+        return Ok(Err(Message::error(
+            "That code doesn't match, check your authenticator app and try again",
+        )
+        .to(SETTINGS_URI)));
+    }
+    two_factor.enable(&mut db).await?;
+    let recovery_codes = two_factor.generate_recovery_codes(&mut db).await?;
+    let ctx = context_with_base_authed!(user, recovery_codes);
+    Ok(Ok(Template::render("settings/two_factor_recovery", ctx)))
+}
+
+#[post("/account/2fa/recovery", data = "<form>")]
+pub async fn two_factor_recovery(
+    mut db: DbConnection,
+    user: &User,
+    form: Form<CodeForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Result<Template, Redirect>> {
+    let mut two_factor = TwoFactor::get(&mut db, user.id)
+        .await?
+        .filter(|t| t.enabled)
+        .ok_or(Status::NotFound)?;
+    if !two_factor.verify(&mut db, form.code).await? {
+        return Ok(Err(Message::error("That code isn't valid").to(SETTINGS_URI)));
+    }
+    let recovery_codes = two_factor.generate_recovery_codes(&mut db).await?;
+    let ctx = context_with_base_authed!(user, recovery_codes);
+    Ok(Ok(Template::render("settings/two_factor_recovery", ctx)))
+}
+
+#[post("/account/2fa/disable", data = "<form>")]
+pub async fn two_factor_disable(
+    mut db: DbConnection,
+    user: &User,
+    config: &State<TwoFactorConfig>,
+    admins: &State<AdminUsers>,
+    form: Form<CodeForm<'_>>,
+    _token: &VerifyCsrfToken,
+) -> ResultResponse<Redirect> {
+    if config.is_required_for(admins, user) {
+        return Ok(
+            Message::error("Two-factor authentication is required for admins").to(SETTINGS_URI),
+        );
+    }
+    let mut two_factor = TwoFactor::get(&mut db, user.id)
+        .await?
+        .filter(|t| t.enabled)
+        .ok_or(Status::NotFound)?;
+    if !two_factor.verify(&mut db, form.code).await? {
+        return Ok(Message::error("That code isn't valid").to(SETTINGS_URI));
+    }
+    TwoFactor::remove(&mut db, user.id).await?;
+    Ok(Message::success("Two-factor authentication turned off").to("/settings/account"))
+}
@@ -0,0 +1,101 @@
+//! Just enough of the ustar format (POSIX.1-1988) to bundle a directory for download, gzipped
+
+use std::io::Write;
+
+use anyhow::bail;
+use chrono::NaiveDateTime;
+use flate2::{write::GzEncoder, Compression};
+
+use crate::error::prelude::*;
+
+const BLOCK: usize = 512;
+
+const FILE: u8 = b'0';
+const SYMLINK: u8 = b'2';
+const DIRECTORY: u8 = b'5';
+
+/// Builds a whole archive in memory, like [`crate::zip::ZipWriter`]
+pub struct TarWriter {
+    buf: Vec<u8>,
+    mtime: u64,
+}
+
+/// Write `val` as a NUL terminated octal number filling `field`
+fn octal(field: &mut [u8], val: u64) {
+    let digits = format!("{:0width$o}", val, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+}
+
+impl TarWriter {
+    /// Every entry gets the same modified time, which is all that's needed for a download
+    pub fn new(modified: NaiveDateTime) -> Self {
+        Self {
+            buf: vec![],
+            mtime: modified.and_utc().timestamp().max(0) as u64,
+        }
+    }
+
+    fn header(&mut self, name: &str, mode: u32, size: u64, kind: u8, link: &str) -> Result {
+        let mut header = [0_u8; BLOCK];
+        // Names over 100 bytes are split at a `/` into the prefix field
+        let (prefix, name) = if name.len() > 100 {
+            let split = name
+                .char_indices()
+                .filter(|(i, c)| *c == '/' && *i <= 155 && name.len() - i - 1 <= 100)
+                .map(|(i, _)| i)
+                .next()
+                .with_context(|| format!("Path is too long for a tarball: {name}"))?;
+            (&name[..split], &name[split + 1..])
+        } else {
+            ("", name)
+        };
+        if link.len() > 100 {
+            bail!("Link target is too long for a tarball: {link}");
+        }
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        octal(&mut header[100..108], (mode & 0o7777) as u64);
+        octal(&mut header[108..116], 0); // uid
+        octal(&mut header[116..124], 0); // gid
+        octal(&mut header[124..136], size);
+        octal(&mut header[136..148], self.mtime);
+        header[156] = kind;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        // The checksum is worked out with its own field as spaces
+        header[148..156].fill(b' ');
+        let checksum = header.iter().map(|b| *b as u64).sum::<u64>();
+        octal(&mut header[148..155], checksum);
+        self.buf.extend(header);
+        Ok(())
+    }
+
+    pub fn add_file(&mut self, name: &str, contents: &[u8], mode: u32) -> Result {
+        self.header(name, mode, contents.len() as u64, FILE, "")?;
+        self.buf.extend(contents);
+        let padding = (BLOCK - contents.len() % BLOCK) % BLOCK;
+        self.buf.extend(std::iter::repeat_n(0, padding));
+        Ok(())
+    }
+
+    pub fn add_dir(&mut self, name: &str) -> Result {
+        self.header(&format!("{name}/"), 0o755, 0, DIRECTORY, "")
+    }
+
+    pub fn add_symlink(&mut self, name: &str, target: &str) -> Result {
+        self.header(name, 0o777, 0, SYMLINK, target)
+    }
+
+    /// The archive, gzipped
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        // Two empty blocks mark the end
+        self.buf.extend([0; BLOCK * 2]);
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder
+            .write_all(&self.buf)
+            .context("Couldn't compress tarball")?;
+        encoder.finish().context("Couldn't compress tarball")
+    }
+}
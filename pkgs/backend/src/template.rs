@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
 use anyhow::Context;
-use markdown::{CompileOptions, Constructs, Options, ParseOptions};
 use openssl::{base64, sha::sha256};
 use rocket::{fairing::AdHoc, form::Context as FormContext, http::Status};
 use rocket_dyn_templates::Template;
@@ -10,6 +9,9 @@ use tera::Value;
 use crate::{
     branding::{self, BrandingConfig, SiteMetaInfo},
     error::prelude::*,
+    i18n::{self, I18nConfig, Locales},
+    md::MarkdownConfig,
+    times::{localized_time_tag, parse_template_time},
 };
 
 type FunctionArgs<'a> = &'a HashMap<String, Value>;
@@ -136,26 +138,38 @@ fn format_time_taken(args: FunctionArgs) -> Result<Value, tera::Error> {
     Ok(tera::Value::String(format!("{hours_f}{minutes_f}")))
 }
 
-fn render_markdown(args: FunctionArgs) -> Result<Value, tera::Error> {
-    let text = args
-        .get("md")
+/// Styles `Intl.DateTimeFormat` knows as a `dateStyle`
+const TIME_STYLES: [&str; 4] = ["full", "long", "medium", "short"];
+
+fn format_time(args: FunctionArgs) -> Result<Value, tera::Error> {
+    let time = args
+        .get("time")
         .and_then(|o| o.as_str())
-        .ok_or(tera::Error::msg("md not passed!"))?;
-    let options = Options {
-        parse: ParseOptions {
-            constructs: Constructs {
-                math_text: true,
-                math_flow: true,
-                ..Constructs::gfm()
-            },
-            ..ParseOptions::gfm()
-        },
-        compile: CompileOptions::gfm(),
-    };
+        .and_then(parse_template_time)
+        .ok_or(tera::Error::msg("time not passed!"))?;
+    let style = args
+        .get("style")
+        .and_then(|o| o.as_str())
+        .unwrap_or("medium");
+    if !TIME_STYLES.contains(&style) {
+        return Err(tera::Error::msg(format!("unknown time style {style}!")));
+    }
+    Ok(tera::Value::String(localized_time_tag(time, style)))
+}
 
-    let rendered = markdown::to_html_with_options(text, &options)
-        .map_err(|e| tera::Error::msg(format!("Failed to render markdown: {:?}", e)))?;
-    Ok(tera::Value::String(rendered))
+fn format_number(args: FunctionArgs) -> Result<Value, tera::Error> {
+    let value = args
+        .get("value")
+        .and_then(|o| o.as_f64())
+        .ok_or(tera::Error::msg("value not passed!"))?;
+    let decimals = args
+        .get("decimals")
+        .and_then(|o| o.as_u64())
+        .unwrap_or(0)
+        .min(20) as usize;
+    Ok(tera::Value::String(i18n::localized_number_tag(
+        value, decimals,
+    )))
 }
 
 fn len_of_form_data_list(args: FunctionArgs) -> Result<Value, tera::Error> {
@@ -210,6 +224,8 @@ macro_rules! context_with_base {
             logged_in: $usr.is_some(),
             user: $usr,
             name: $usr.map(|u| u.display_name()).unwrap_or_default(),
+            locale: $usr.and_then(|u| u.locale.clone()),
+            banner: $crate::banner::active(),
             version: env!("CARGO_PKG_VERSION"),
             $($key $(: $value)?),*
         }
@@ -223,6 +239,8 @@ macro_rules! context_with_base_authed {
             logged_in: true,
             user: $usr,
             name: $usr.display_name(),
+            locale: $usr.locale.clone(),
+            banner: $crate::banner::active(),
             version: env!("CARGO_PKG_VERSION"),
             $($key $(: $value)?),*
         }
@@ -248,6 +266,31 @@ pub fn stage() -> AdHoc {
             }
         };
 
+        let mut markdown_config = match figment
+            .extract_inner::<Option<MarkdownConfig>>("markdown")
+            .context("Invalid markdown config found")
+        {
+            Ok(m) => m.unwrap_or_default(),
+            Err(e) => {
+                error!("Failed to load markdown config: {:?}", e);
+                return Err(rocket);
+            }
+        };
+
+        markdown_config.server_side_math = branding.server_side_math;
+
+        let locales = match figment
+            .extract_inner::<Option<I18nConfig>>("i18n")
+            .context("Invalid i18n config found")
+            .and_then(|c| Locales::load(&c.unwrap_or_default()))
+        {
+            Ok(l) => l,
+            Err(e) => {
+                error!("Failed to load translations: {:?}", e);
+                return Err(rocket);
+            }
+        };
+
         let parsed_colors = branding.colors.parse_colors();
 
         let parsed_colors = match parsed_colors {
@@ -273,9 +316,11 @@ pub fn stage() -> AdHoc {
         let rocket = rocket
             .attach(crate::csp::stage())
             .attach(branding::image::stage(&branding))
+            .attach(crate::i18n::stage(locales.clone()))
             .manage(branding.clone())
             .manage(parsed_colors.clone())
-            .manage(meta_info);
+            .manage(meta_info)
+            .manage(markdown_config.clone());
 
         Ok(rocket.attach(Template::custom(move |e| {
             let url_prefix = url_prefix.clone();
@@ -283,6 +328,8 @@ pub fn stage() -> AdHoc {
             let branding = branding.clone();
             let parsed_colors = parsed_colors.clone();
             let theme_style_tag = theme_style_tag.clone();
+            let markdown_config = markdown_config.clone();
+            let locales = locales.clone();
             e.tera
                 .register_function("get_branding", move |_: FunctionArgs| {
                     Ok(serde_json::to_value(&branding).unwrap())
@@ -300,13 +347,53 @@ pub fn stage() -> AdHoc {
             e.tera.register_function("fake_attr", fake_attr);
             e.tera
                 .register_function("format_time_taken", format_time_taken);
-            e.tera.register_function("render_markdown", render_markdown);
+            e.tera
+                .register_function("render_markdown", move |args: FunctionArgs| {
+                    let md = args
+                        .get("md")
+                        .and_then(|o| o.as_str())
+                        .ok_or(tera::Error::msg("md not passed!"))?;
+                    markdown_config
+                        .render(md)
+                        .map(tera::Value::String)
+                        .map_err(|e| tera::Error::msg(e.to_string()))
+                });
             e.tera
                 .register_function("url_prefix", move |_: FunctionArgs| {
                     Ok(tera::Value::String(url_prefix.clone()))
                 });
             e.tera
                 .register_function("len_of_form_data_list", len_of_form_data_list);
+            e.tera.register_function("format_time", format_time);
+            e.tera.register_function("format_number", format_number);
+            let lang_locales = locales.clone();
+            e.tera.register_function("lang", move |args: FunctionArgs| {
+                let locale = args.get("locale").and_then(|l| l.as_str());
+                Ok(tera::Value::String(
+                    lang_locales.resolve(locale).to_string(),
+                ))
+            });
+            let dir_locales = locales.clone();
+            e.tera
+                .register_function("text_dir", move |args: FunctionArgs| {
+                    let locale = dir_locales.resolve(args.get("locale").and_then(|l| l.as_str()));
+                    let dir = if i18n::is_rtl(locale) { "rtl" } else { "ltr" };
+                    Ok(tera::Value::String(dir.to_string()))
+                });
+            e.tera
+                .register_filter("t", move |value: &Value, args: &HashMap<String, Value>| {
+                    let text = value
+                        .as_str()
+                        .ok_or(tera::Error::msg("t only translates strings!"))?;
+                    let locale = args
+                        .get("locale")
+                        .and_then(|l| l.as_str())
+                        .filter(|l| !l.is_empty())
+                        .unwrap_or(locales.default_locale());
+                    Ok(tera::Value::String(
+                        locales.translate(locale, text).to_string(),
+                    ))
+                });
             e.tera
                 .register_function("is_admin", move |args: FunctionArgs| {
                     if let Some(user) = args.get("user").and_then(|o| o.as_object()) {
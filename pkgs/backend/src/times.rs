@@ -9,11 +9,17 @@ use rocket::{
 use serde::Serializer;
 
 const HTML_FORMAT: &str = "%FT%R";
+const ICS_FORMAT: &str = "%Y%m%dT%H%M%SZ";
 
 pub fn naive_to_html_time(dt: NaiveDateTime) -> String {
     dt.format(HTML_FORMAT).to_string()
 }
 
+/// UTC time in the form iCalendar wants, e.g. `20240322T123200Z`
+pub fn naive_to_ics_time(dt: NaiveDateTime) -> String {
+    dt.format(ICS_FORMAT).to_string()
+}
+
 pub fn datetime_to_html_time(dt: &DateTime<Tz>) -> String {
     dt.format(HTML_FORMAT).to_string()
 }
@@ -28,6 +34,25 @@ pub fn format_datetime_human_readable(dt: DateTime<Tz>) -> String {
     dt.format(fstring).to_string()
 }
 
+/// Parse a time the way it ends up in template contexts, either from [`serialize_to_js`] or
+/// chrono's own serialization
+pub fn parse_template_time(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, HTML_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()
+}
+
+/// A `<time>` the browser formats for the page's language and the viewer's timezone, `style`
+/// is an `Intl.DateTimeFormat` `dateStyle` (`full`, `long`, `medium` or `short`).
+/// Until then, or without JavaScript, it shows the time in UTC
+pub fn localized_time_tag(dt: NaiveDateTime, style: &str) -> String {
+    format!(
+        "<time datetime=\"{}Z\" data-localize-time=\"{style}\">{} UTC</time>",
+        dt.format("%FT%T"),
+        dt.format("%F %R")
+    )
+}
+
 pub fn serialize_to_js<S: Serializer>(
     dt: &NaiveDateTime,
     serializer: S,
@@ -35,6 +60,16 @@ pub fn serialize_to_js<S: Serializer>(
     serializer.serialize_str(&naive_to_html_time(*dt))
 }
 
+pub fn serialize_option_to_js<S: Serializer>(
+    dt: &Option<NaiveDateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match dt {
+        Some(dt) => serialize_to_js(dt, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FormDateTime(pub NaiveDateTime);
 
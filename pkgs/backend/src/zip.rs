@@ -0,0 +1,119 @@
+//! Just enough of the ZIP format (APPNOTE 6.3) to bundle generated files for download
+
+use std::io::Write;
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use flate2::{write::DeflateEncoder, Compression, Crc};
+
+use crate::error::prelude::*;
+
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+
+const VERSION: u16 = 20;
+/// Names are UTF-8
+const FLAGS: u16 = 1 << 11;
+const DEFLATE: u16 = 8;
+
+struct Entry {
+    name: String,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Builds a whole archive in memory, fine for the few megabytes we generate at once
+pub struct ZipWriter {
+    buf: Vec<u8>,
+    entries: Vec<Entry>,
+    time: u16,
+    date: u16,
+}
+
+impl ZipWriter {
+    /// Every file gets the same modified time, which is all that's needed for a download
+    pub fn new(modified: NaiveDateTime) -> Self {
+        let time =
+            ((modified.hour() << 11) | (modified.minute() << 5) | (modified.second() / 2)) as u16;
+        let year = modified.year().clamp(1980, 2107) as u32 - 1980;
+        let date = ((year << 9) | (modified.month() << 5) | modified.day()) as u16;
+        Self {
+            buf: vec![],
+            entries: vec![],
+            time,
+            date,
+        }
+    }
+
+    pub fn add(&mut self, name: &str, contents: &[u8]) -> Result {
+        let mut crc = Crc::new();
+        crc.update(contents);
+        let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+        encoder
+            .write_all(contents)
+            .context("Couldn't compress zip entry")?;
+        let compressed = encoder.finish().context("Couldn't compress zip entry")?;
+
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc.sum(),
+            compressed_size: u32::try_from(compressed.len()).context("Zip entry too large")?,
+            size: u32::try_from(contents.len()).context("Zip entry too large")?,
+            offset: u32::try_from(self.buf.len()).context("Zip too large")?,
+        };
+
+        self.buf.extend(LOCAL_FILE_HEADER.to_le_bytes());
+        self.buf.extend(VERSION.to_le_bytes());
+        self.common_fields(&entry);
+        self.buf.extend(0_u16.to_le_bytes()); // Extra field length
+        self.buf.extend(entry.name.as_bytes());
+        self.buf.extend(compressed);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// The fields shared by the local header and the central directory
+    fn common_fields(&mut self, entry: &Entry) {
+        self.buf.extend(FLAGS.to_le_bytes());
+        self.buf.extend(DEFLATE.to_le_bytes());
+        self.buf.extend(self.time.to_le_bytes());
+        self.buf.extend(self.date.to_le_bytes());
+        self.buf.extend(entry.crc.to_le_bytes());
+        self.buf.extend(entry.compressed_size.to_le_bytes());
+        self.buf.extend(entry.size.to_le_bytes());
+        self.buf.extend((entry.name.len() as u16).to_le_bytes());
+    }
+
+    pub fn finish(mut self) -> Result<Vec<u8>> {
+        let directory_start = u32::try_from(self.buf.len()).context("Zip too large")?;
+        let entries = std::mem::take(&mut self.entries);
+        for entry in entries.iter() {
+            self.buf.extend(CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+            self.buf.extend(VERSION.to_le_bytes()); // Made by
+            self.buf.extend(VERSION.to_le_bytes()); // Needed to extract
+            self.common_fields(entry);
+            self.buf.extend(0_u16.to_le_bytes()); // Extra field length
+            self.buf.extend(0_u16.to_le_bytes()); // Comment length
+            self.buf.extend(0_u16.to_le_bytes()); // Disk number
+            self.buf.extend(0_u16.to_le_bytes()); // Internal attributes
+            self.buf.extend(0_u32.to_le_bytes()); // External attributes
+            self.buf.extend(entry.offset.to_le_bytes());
+            self.buf.extend(entry.name.as_bytes());
+        }
+        let directory_size =
+            u32::try_from(self.buf.len()).context("Zip too large")? - directory_start;
+        let count = u16::try_from(entries.len()).context("Too many files for a zip")?;
+
+        self.buf.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        self.buf.extend(0_u16.to_le_bytes()); // This disk
+        self.buf.extend(0_u16.to_le_bytes()); // Disk with the directory
+        self.buf.extend(count.to_le_bytes());
+        self.buf.extend(count.to_le_bytes());
+        self.buf.extend(directory_size.to_le_bytes());
+        self.buf.extend(directory_start.to_le_bytes());
+        self.buf.extend(0_u16.to_le_bytes()); // Comment length
+        Ok(self.buf)
+    }
+}
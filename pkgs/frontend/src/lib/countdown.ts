@@ -30,7 +30,9 @@ export const makeCountdown = (
         };
         updateTimer();
         const interval = setInterval(updateTimer, 1000);
+        return () => clearInterval(interval);
     } else {
         onPassed?.();
+        return () => {};
     }
 };
@@ -9,6 +9,9 @@ type LanguageDisplayInfo = {
     deviconIcon?: string;
     monacoContribution: string;
     defaultCode: string;
+    // Parts of the problem's starter code that submissions have to keep as is
+    lockedPrefix?: string;
+    lockedSuffix?: string;
 };
 
 export type CodeInfo = {
@@ -18,6 +21,18 @@ export type CodeInfo = {
 const getIconName = (key: string, lang: LanguageDisplayInfo) => lang.deviconIcon ?? key;
 const makeIconClass = (icon: string) => `devicon-${icon}-plain`;
 
+// Keep edits out of the locked starter code, unless it's already been changed some other way
+const lockedRanges = (lang: LanguageDisplayInfo) => {
+    const prefix = lang.lockedPrefix ?? "";
+    const suffix = lang.lockedSuffix ?? "";
+    if (!prefix && !suffix) return [];
+    return EditorState.changeFilter.of((tr) => {
+        const doc = tr.startState.doc.toString();
+        if (!doc.startsWith(prefix) || !doc.endsWith(suffix)) return true;
+        return [0, prefix.length, doc.length - suffix.length, doc.length];
+    });
+};
+
 export default (
     codeInfo: CodeInfo,
     defaultLanguage: string,
@@ -29,17 +44,22 @@ export default (
     languageIcon: HTMLSpanElement,
     saveIndicator: HTMLElement,
     resetButton: HTMLButtonElement,
-    mostRecentCode: [string, string] | null
+    mostRecentCode: [string, string, boolean?] | null,
+    draftUrl: string | null
 ) => {
     let editor: EditorView | null = null;
     let currentLanguage = defaultLanguage;
 
     const languageCompartment = new Compartment();
+    const lockCompartment = new Compartment();
 
     const setLanguage = (lang: LanguageDisplayInfo) => {
         if (editor) {
             editor.dispatch({
-                effects: languageCompartment.reconfigure(editorLanguages[lang.monacoContribution]())
+                effects: [
+                    languageCompartment.reconfigure(editorLanguages[lang.monacoContribution]()),
+                    lockCompartment.reconfigure(lockedRanges(lang))
+                ]
             });
         }
     };
@@ -54,7 +74,8 @@ export default (
     const setEditorContent = (content: string) => {
         if (editor) {
             editor.dispatch({
-                changes: { from: 0, to: editor.state.doc.length, insert: content }
+                changes: { from: 0, to: editor.state.doc.length, insert: content },
+                filter: false
             });
         }
     };
@@ -81,10 +102,13 @@ export default (
         }
     };
 
-    const [storedCode, storedLang] = JSON.parse(
-        window.localStorage.getItem(`contest-${contestId}-problem-${problemId}-code`) ??
-            "[null, null]"
-    );
+    // The server's copy is newer than the local one once it's been autosaving drafts
+    const [storedCode, storedLang] = mostRecentCode?.[2]
+        ? [null, null]
+        : JSON.parse(
+              window.localStorage.getItem(`contest-${contestId}-problem-${problemId}-code`) ??
+                  "[null, null]"
+          );
 
     currentLanguage = Object.keys(codeInfo).includes(storedLang ?? "")
         ? storedLang
@@ -111,6 +135,17 @@ export default (
         );
         saveIndicator.dataset.saveState = "saved";
         saveIndicator.ariaLabel = "Changes Saved!";
+        if (draftUrl) {
+            const body = new URLSearchParams();
+            body.append("language", currentLanguage);
+            body.append("program", text);
+            // Browsers refuse keepalive requests over 64KiB, they're only needed when leaving the page
+            fetch(draftUrl, { method: "POST", body, keepalive: text.length < 60000 })
+                .then((res) => {
+                    if (!res.ok) console.warn(`Couldn't save draft: ${res.status}`);
+                })
+                .catch((e) => console.warn("Couldn't save draft", e));
+        }
     };
 
     const onDocChanged = () => {
@@ -140,7 +175,8 @@ export default (
                 if (!update.docChanged) return;
                 onDocChanged();
             }),
-            languageCompartment.of(editorLanguages[langInfo.monacoContribution]())
+            languageCompartment.of(editorLanguages[langInfo.monacoContribution]()),
+            lockCompartment.of(lockedRanges(langInfo))
         ],
         doc: storedCode ?? mostRecentCode?.[0] ?? langInfo.defaultCode
     });
@@ -5,6 +5,8 @@ export type ScoreEntry = {
     num_wrong: number;
 };
 
+export type TickerKind = "firstSolve" | "solved" | "rejected";
+
 export type Message =
     | {
           type: "fullRefresh";
@@ -28,6 +30,22 @@ export type Message =
     | {
           type: "reOrder";
           participantMap: Record<number, [number, number]>;
+      }
+    | {
+          type: "event";
+          kind: TickerKind;
+          name: string;
+          problemLabel: string;
+          clock: string;
+      }
+    | {
+          type: "contestUpdated";
+          startTime: number;
+          endTime: number;
+          freezeTime: number;
+          isFrozen: boolean;
+          isPaused: boolean;
+          pausedSeconds: number;
       };
 
 export default (
@@ -12,6 +12,22 @@ export type WebSocketRequest =
           program: string;
           language: string;
           input: string;
+      }
+    | {
+          type: "samples";
+          program: string;
+          language: string;
+      }
+    | {
+          type: "invoke";
+          program: string;
+          language: string;
+          inputs: string[];
+      }
+    | {
+          type: "compile";
+          program: string;
+          language: string;
       };
 
 export type CaseStatus =
@@ -41,8 +57,31 @@ export type JobState =
     | {
           type: "testing";
           status: CaseStatus;
+          live_output?: string;
+      }
+    | {
+          type: "samples";
+          cases: CaseStatus[];
+          results: (SampleResult | null)[];
+      }
+    | {
+          type: "invoking";
+          cases: CaseStatus[];
+          compile_output?: string;
+          live_output?: string;
+      }
+    | {
+          type: "compiling";
+          status: CaseStatus;
+          compile_output?: string;
+          live_output?: string;
       };
 
+export type SampleResult = {
+    accepted: boolean;
+    diff: [string, string][];
+};
+
 export type WebSocketMessage =
     | {
           type: "stateUpdate";
@@ -60,6 +99,27 @@ export type WebSocketMessage =
           error: string;
       };
 
+const diffLineClass: Record<string, string> = {
+    equal: "",
+    delete: "bg-red-100 text-red-800",
+    insert: "bg-green-100 text-green-800"
+};
+
+const diffLinePrefix: Record<string, string> = { equal: "  ", delete: "- ", insert: "+ " };
+
+// Expected lines the output is missing in red, lines it has that weren't expected in green
+const renderDiff = (elem: HTMLElement, result: SampleResult) => {
+    elem.replaceChildren(
+        ...result.diff.map(([tag, line]) => {
+            const span = document.createElement("span");
+            span.className = `block ${diffLineClass[tag] ?? ""}`;
+            span.innerText = `${diffLinePrefix[tag] ?? "  "}${line}`;
+            return span;
+        })
+    );
+    elem.classList.toggle("hidden", result.accepted);
+};
+
 function randomInRange(min: number, max: number) {
     return Math.random() * (max - min) + min;
 }
@@ -81,8 +141,11 @@ export default (
     const stateIsComplete = (state: JobState) => {
         switch (state.type) {
             case "judging":
+            case "samples":
+            case "invoking":
                 return !state.cases.some((c) => c.status === "pending" || c.status === "running");
             case "testing":
+            case "compiling":
                 return state.status.status !== "pending" && state.status.status !== "running";
         }
     };
@@ -176,7 +239,97 @@ export default (
                             case "failed":
                                 testOutput.value = state.status.content[1] ?? "";
                                 break;
+                            case "running":
+                                // What the compiler, then the program, has printed so far
+                                testOutput.value = state.live_output ?? "";
+                                break;
                         }
+                        break;
+                    case "samples":
+                        for (const [i, c] of state.cases.entries()) {
+                            const elem = document.querySelector(`[data-sample-number='${i}']`);
+                            if (!elem) continue;
+                            const indicator = elem.querySelector(".sample-indicator")!;
+                            const output = elem.querySelector(".sample-output") as HTMLElement;
+                            const diff = elem.querySelector(".sample-diff") as HTMLElement;
+                            const result = state.results[i];
+                            diff.classList.add("hidden");
+                            if (c.status === "passed") {
+                                output.innerText = c.content ?? "";
+                                if (result) renderDiff(diff, result);
+                            } else if (c.status === "failed") {
+                                output.innerText = c.content[1];
+                            } else {
+                                output.innerText = "";
+                            }
+                            indicator.setAttribute(
+                                "data-status",
+                                c.status === "passed" && result && !result.accepted
+                                    ? "error"
+                                    : typeToStatus[c.status]
+                            );
+                        }
+                        break;
+                    case "invoking":
+                        const compileOutput = document.querySelector("#compile-output");
+                        if (compileOutput && state.compile_output !== undefined) {
+                            compileOutput.textContent =
+                                state.compile_output || "The compiler didn't print anything";
+                        }
+                        for (const [i, c] of state.cases.entries()) {
+                            const elem = document.querySelector(`[data-input-number='${i}']`);
+                            if (!elem) continue;
+                            const output = elem.querySelector(".input-output") as HTMLElement;
+                            if (c.status === "passed") {
+                                output.innerText = c.content ?? "";
+                            } else if (c.status === "failed") {
+                                output.innerText = c.content[1];
+                            } else if (c.status === "running") {
+                                output.innerText = state.live_output ?? "";
+                            } else {
+                                output.innerText = "";
+                            }
+                            elem
+                                .querySelector(".input-indicator")!
+                                .setAttribute("data-status", typeToStatus[c.status]);
+                        }
+                        runMessageWrapper.setAttribute(
+                            "data-status",
+                            complete ? "success" : "loading"
+                        );
+                        runMessage.innerText = complete ? "Done" : "Running...";
+                        break;
+                    case "compiling":
+                        debugCaseIndicator.setAttribute(
+                            "data-status",
+                            typeToStatus[state.status.status]
+                        );
+                        const compileOutputElem = document.querySelector("#compile-output");
+                        if (!complete) {
+                            runMessageWrapper.setAttribute("data-status", "loading");
+                            runMessage.innerText = "Compiling...";
+                            if (compileOutputElem) {
+                                compileOutputElem.textContent = state.live_output ?? "";
+                            } else {
+                                testOutput.value = state.live_output ?? "";
+                            }
+                            break;
+                        }
+                        const failed = state.status.status === "failed";
+                        const diagnostics =
+                            state.compile_output ||
+                            (state.status.status === "failed" && state.status.content[1]) ||
+                            "The compiler didn't print anything";
+                        if (compileOutputElem) {
+                            compileOutputElem.textContent = diagnostics;
+                        } else {
+                            testOutput.value = diagnostics;
+                        }
+                        runMessageWrapper.setAttribute(
+                            "data-status",
+                            failed ? "error" : "success"
+                        );
+                        runMessage.innerText = failed ? "Doesn't Compile" : "Compiles!";
                 }
                 break;
             case "invalid":
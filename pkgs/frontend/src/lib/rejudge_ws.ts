@@ -0,0 +1,49 @@
+export type RejudgeStatus = "running" | "paused" | "cancelled" | "interrupted" | "complete";
+
+export type Rejudge = {
+    id: number;
+    contest_id: number;
+    problem_id: number | null;
+    status: RejudgeStatus;
+    total: number;
+    processed: number;
+    now_passing: number;
+    now_failing: number;
+    errors: number;
+    created_at: string;
+    finished_at: string | null;
+};
+
+export type Message = {
+    rejudge: Rejudge;
+    etaSecs: number | null;
+};
+
+export default (
+    contestId: number,
+    rejudgeId: number,
+    onMsg: (msg: Message) => void,
+    onClose?: () => void,
+    onOpen?: () => void
+) => {
+    const scheme = window.location.protocol === "https:" ? "wss" : "ws";
+    const ws = new WebSocket(
+        `${scheme}://${window.location.host}/contests/${contestId}/admin/rejudges/${rejudgeId}/ws`
+    );
+    ws.onopen = () => {
+        console.debug("Connected to rejudge websocket");
+        onOpen?.();
+    };
+    ws.onmessage = (event) => {
+        const message = JSON.parse(event.data) as Message;
+        onMsg(message);
+    };
+    ws.onerror = (error) => {
+        console.error("Error in rejudge websocket", error);
+    };
+    ws.onclose = () => {
+        console.debug("Disconnected from rejudge websocket");
+        onClose?.();
+    };
+    return ws;
+};
@@ -0,0 +1,60 @@
+// Counts large pastes into the editor and times the page loses focus for contests that record
+// editor telemetry, reported every so often and when leaving the page
+const REPORT_INTERVAL_MS = 30000;
+
+export default (telemetryUrl: string, editorElem: HTMLElement, largePasteChars: number) => {
+    let largePastes = 0;
+    let pastedChars = 0;
+    let focusLosses = 0;
+
+    editorElem.addEventListener(
+        "paste",
+        (e) => {
+            const length = e.clipboardData?.getData("text")?.length ?? 0;
+            if (length >= largePasteChars) {
+                largePastes += 1;
+                pastedChars += length;
+            }
+        },
+        { capture: true }
+    );
+
+    // Switching tabs fires both, so only count it once
+    let hidden = false;
+    const lostFocus = () => {
+        if (!hidden) {
+            hidden = true;
+            focusLosses += 1;
+        }
+    };
+    window.addEventListener("blur", lostFocus);
+    document.addEventListener("visibilitychange", () => {
+        if (document.visibilityState === "hidden") {
+            lostFocus();
+        } else {
+            hidden = false;
+        }
+    });
+    window.addEventListener("focus", () => {
+        hidden = false;
+    });
+
+    const report = () => {
+        if (largePastes === 0 && focusLosses === 0) return;
+        const body = new URLSearchParams();
+        body.append("large_pastes", largePastes.toString());
+        body.append("pasted_chars", pastedChars.toString());
+        body.append("focus_losses", focusLosses.toString());
+        largePastes = 0;
+        pastedChars = 0;
+        focusLosses = 0;
+        fetch(telemetryUrl, { method: "POST", body, keepalive: true })
+            .then((res) => {
+                if (!res.ok) console.warn(`Couldn't send telemetry: ${res.status}`);
+            })
+            .catch((e) => console.warn("Couldn't send telemetry", e));
+    };
+
+    setInterval(report, REPORT_INTERVAL_MS);
+    window.addEventListener("pagehide", report);
+};
@@ -2,6 +2,9 @@ export const variable = (expression: string, debugEval?: string) =>
     import.meta.env.DEV ? (debugEval ?? expression) : `{{ ${expression} }}`;
 export const tag = (expression: string, whitespace?: boolean) =>
     `{%${whitespace ? "" : "-"} ${expression} ${whitespace ? "" : "-"}%}`;
+/** Translate fixed text with the catalog for the viewer's locale, see `i18n` in the backend */
+export const t = (text: string) =>
+    variable(`"${text.replaceAll('"', '\\"')}" | t(locale=locale | default(value=''))`, text);
 export const teraIf = (
     condition: string,
     t: string,